@@ -6,6 +6,11 @@ use futures_util::StreamExt;
 use std::fs;
 use std::io::{self, Cursor};
 use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+
+/// Number of decompressed chunks buffered ahead of the archive writer thread.
+const DECODED_CHUNKS_BUFFER_SIZE: usize = 8;
 
 pub fn default_tsv_file_path(network: &StacksNetwork) -> String {
     format!("{:?}-stacks-events.tsv", network).to_lowercase()
@@ -40,38 +45,16 @@ pub async fn download_tsv_file(config: &Config) -> Result<(), String> {
         .or(Err(format!("Failed to GET from '{}'", &file_url)))?;
 
     // Download chunks
-    let (tx, rx) = flume::bounded(0);
+    let (tx, rx) = flume::bounded(config.storage.archive_download_buffer_size);
 
     if res.status() == reqwest::StatusCode::OK {
         destination_path.push(default_tsv_file_path(&config.network.stacks_network));
 
-        let decoder_thread = std::thread::spawn(move || {
-            let mut file = fs::File::create(&destination_path).unwrap();
-            let input = ChannelRead::new(rx);
-            let mut decoder = GzDecoder::new(input);
-            let mut buffer = [0; 512_000];
-            loop {
-                match decoder.read(&mut buffer) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        if let Err(e) = file.write_all(&buffer[..n]) {
-                            return Err(format!(
-                                "unable to update compressed archive: {}",
-                                e.to_string()
-                            ));
-                        }
-                    }
-                    Err(e) => {
-                        return Err(format!(
-                            "unable to write compressed archive: {}",
-                            e.to_string()
-                        ));
-                    }
-                }
-            }
-            let _ = file.flush();
-            Ok(())
-        });
+        let decoder_thread = spawn_archive_decoder(
+            rx,
+            destination_path,
+            config.storage.parallel_archive_decoding,
+        );
         let mut stream = res.bytes_stream();
         while let Some(item) = stream.next().await {
             let chunk = match item {
@@ -96,6 +79,73 @@ pub async fn download_tsv_file(config: &Config) -> Result<(), String> {
     Ok(())
 }
 
+/// Spawns the thread decompressing the chunks of the archive received from `rx` into
+/// `destination_path`. With `parallel`, the decompressed data is written to disk by a second
+/// thread, so that decompressing a chunk doesn't wait for the previous one to be written.
+fn spawn_archive_decoder(
+    rx: flume::Receiver<Vec<u8>>,
+    destination_path: PathBuf,
+    parallel: bool,
+) -> JoinHandle<Result<(), String>> {
+    std::thread::spawn(move || {
+        let mut file = fs::File::create(&destination_path).map_err(|e| {
+            format!(
+                "unable to create file {}: {}",
+                destination_path.display(),
+                e.to_string()
+            )
+        })?;
+        let mut decoder = GzDecoder::new(ChannelRead::new(rx));
+        if !parallel {
+            decode_archive(&mut decoder, |chunk| write_archive_chunk(&mut file, chunk))?;
+            let _ = file.flush();
+            return Ok(());
+        }
+        let (decoded_tx, decoded_rx) = flume::bounded::<Vec<u8>>(DECODED_CHUNKS_BUFFER_SIZE);
+        let writer_thread = std::thread::spawn(move || {
+            for chunk in decoded_rx.iter() {
+                write_archive_chunk(&mut file, &chunk)?;
+            }
+            let _ = file.flush();
+            Ok::<(), String>(())
+        });
+        let decoded = decode_archive(&mut decoder, |chunk| {
+            decoded_tx
+                .send(chunk.to_vec())
+                .map_err(|e| format!("unable to update compressed archive: {}", e.to_string()))
+        });
+        drop(decoded_tx);
+        writer_thread
+            .join()
+            .map_err(|e| format!("archive writer thread failed: {:?}", e))??;
+        decoded
+    })
+}
+
+fn decode_archive<F>(decoder: &mut impl Read, mut write: F) -> Result<(), String>
+where
+    F: FnMut(&[u8]) -> Result<(), String>,
+{
+    let mut buffer = [0; 512_000];
+    loop {
+        match decoder.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => write(&buffer[..n])?,
+            Err(e) => {
+                return Err(format!(
+                    "unable to write compressed archive: {}",
+                    e.to_string()
+                ));
+            }
+        }
+    }
+}
+
+fn write_archive_chunk(file: &mut fs::File, chunk: &[u8]) -> Result<(), String> {
+    file.write_all(chunk)
+        .map_err(|e| format!("unable to update compressed archive: {}", e.to_string()))
+}
+
 // Wrap a channel into something that impls `io::Read`
 struct ChannelRead {
     rx: flume::Receiver<Vec<u8>>,
@@ -131,6 +181,9 @@ pub async fn download_stacks_dataset_if_required(
     ctx: &Context,
 ) -> Result<bool, String> {
     if config.is_initial_ingestion_required() {
+        if config.storage.offline {
+            return use_local_stacks_dataset_offline(config, ctx);
+        }
         // Download default tsv.
         if config.rely_on_remote_stacks_tsv() && config.should_download_remote_stacks_tsv() {
             let url = config.expected_remote_stacks_tsv_url()?;
@@ -191,5 +244,35 @@ pub async fn download_stacks_dataset_if_required(
     }
 }
 
+/// Resolves the Stacks archive to ingest without performing any network call.
+///
+/// A local `tsv_file_path` source is ingested as usual. Otherwise, a tsv previously downloaded
+/// into the cache directory is reused as is. If neither is present, an error is returned.
+fn use_local_stacks_dataset_offline(config: &mut Config, ctx: &Context) -> Result<bool, String> {
+    let (tsv_file_path, requires_ingestion) = match config.expected_local_stacks_tsv_file() {
+        Ok(tsv_file_path) => (tsv_file_path.clone(), true),
+        Err(_) => {
+            let mut tsv_file_path = config.expected_cache_path();
+            tsv_file_path.push(default_tsv_file_path(&config.network.stacks_network));
+            (tsv_file_path, false)
+        }
+    };
+    if !tsv_file_path.exists() {
+        return Err(format!(
+            "offline mode is enabled, but the required local Stacks archive file {} was not found",
+            tsv_file_path.display()
+        ));
+    }
+    info!(
+        ctx.expect_logger(),
+        "Offline mode enabled, using local Stacks archive file {}",
+        tsv_file_path.display()
+    );
+    if !requires_ingestion {
+        config.add_local_stacks_tsv_source(&tsv_file_path);
+    }
+    Ok(requires_ingestion)
+}
+
 #[cfg(test)]
 pub mod tests;
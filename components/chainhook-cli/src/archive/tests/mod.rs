@@ -2,15 +2,18 @@ use std::{
     fs::{self, File},
     io::Read,
     net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
     thread::sleep,
     time::Duration,
 };
 
 use chainhook_sdk::utils::Context;
+use flate2::read::GzDecoder;
 
 use crate::{
     archive::{
         default_tsv_file_path, default_tsv_sha_file_path, download_stacks_dataset_if_required,
+        spawn_archive_decoder,
     },
     config::{Config, EventSourceConfig, UrlConfig},
     service::tests::helpers::get_free_port,
@@ -88,3 +91,61 @@ async fn it_downloads_stacks_dataset_if_required() {
     tsv_sha_file_path.push(default_tsv_sha_file_path(&config.network.stacks_network));
     fs::remove_file(tsv_sha_file_path).unwrap();
 }
+
+#[tokio::test]
+async fn it_never_reaches_remote_archive_in_offline_mode() {
+    // nothing is listening on this port: any attempt to fetch the archive would fail
+    let port = get_free_port().unwrap();
+    let mut config = Config::default(false, true, false, &None).unwrap();
+    config.storage.offline = true;
+    config.storage.working_dir = format!("{}/{}/offline", env!("CARGO_MANIFEST_DIR"), TMP_DIR);
+    config.event_sources = vec![EventSourceConfig::StacksTsvUrl(UrlConfig {
+        file_url: format!("http://0.0.0.0:{port}/stacks_blocks.tsv"),
+    })];
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let err = download_stacks_dataset_if_required(&mut config.clone(), &ctx)
+        .await
+        .unwrap_err();
+    assert!(err.starts_with("offline mode is enabled"));
+
+    let mut tsv_file_path = config.expected_cache_path();
+    fs::create_dir_all(&tsv_file_path).unwrap();
+    tsv_file_path.push(default_tsv_file_path(&config.network.stacks_network));
+    fs::write(&tsv_file_path, "").unwrap();
+
+    assert!(!download_stacks_dataset_if_required(&mut config, &ctx)
+        .await
+        .unwrap());
+    assert_eq!(config.expected_local_stacks_tsv_file(), Ok(&tsv_file_path));
+
+    fs::remove_dir_all(config.expected_cache_path()).unwrap();
+}
+
+#[test]
+fn it_decodes_archive_regardless_of_buffering() {
+    let compressed = fs::read(format!("{}/{GZ_DIR}", env!("CARGO_MANIFEST_DIR"))).unwrap();
+    let mut expected = vec![];
+    GzDecoder::new(&compressed[..])
+        .read_to_end(&mut expected)
+        .unwrap();
+
+    let tmp_dir = format!("{}/{}/decoder", env!("CARGO_MANIFEST_DIR"), TMP_DIR);
+    fs::create_dir_all(&tmp_dir).unwrap();
+    for (buffer_size, parallel) in [(0, false), (0, true), (64, false), (64, true)] {
+        let destination_path =
+            PathBuf::from(format!("{tmp_dir}/decoded-{buffer_size}-{parallel}.tsv"));
+        let (tx, rx) = flume::bounded(buffer_size);
+        let decoder_thread = spawn_archive_decoder(rx, destination_path.clone(), parallel);
+        for chunk in compressed.chunks(1024) {
+            tx.send(chunk.to_vec()).unwrap();
+        }
+        drop(tx);
+        decoder_thread.join().unwrap().unwrap();
+        assert_eq!(fs::read(&destination_path).unwrap(), expected);
+    }
+    fs::remove_dir_all(tmp_dir).unwrap();
+}
@@ -1,12 +1,9 @@
 use crate::config::generator::generate_config;
-use crate::config::Config;
-use crate::scan::bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate;
-use crate::scan::stacks::{
-    consolidate_local_stacks_chainstate_using_csv, scan_stacks_chainstate_via_csv_using_predicate,
-    scan_stacks_chainstate_via_rocksdb_using_predicate,
-};
+use crate::config::{Config, PredicatesApi};
+use crate::scan::bench::{bench_bitcoin_predicate, load_bitcoin_blocks_from_dir};
+use crate::scan::common::{replay_triggered_blocks, scan_predicate_once};
 use crate::service::http_api::document_predicate_api_server;
-use crate::service::Service;
+use crate::service::{get_registered_http_hooks, Service};
 use crate::storage::{
     delete_confirmed_entry_from_stacks_blocks, delete_unconfirmed_entry_from_stacks_blocks,
     get_last_block_height_inserted, get_last_unconfirmed_block_height_inserted,
@@ -24,6 +21,7 @@ use chainhook_sdk::chainhooks::stacks::StacksChainhookSpecificationNetworkMap;
 use chainhook_sdk::chainhooks::stacks::StacksPredicate;
 use chainhook_sdk::chainhooks::stacks::StacksPrintEventBasedPredicate;
 use chainhook_sdk::chainhooks::types::{ChainhookSpecificationNetworkMap, FileHook, HookAction};
+use chainhook_sdk::observer::dead_letters::{redeliver_dead_letters, DeadLetterStore};
 use chainhook_sdk::types::{BitcoinNetwork, BlockIdentifier, StacksNetwork};
 use chainhook_sdk::utils::{BlockHeights, Context};
 use clap::{Parser, Subcommand};
@@ -71,6 +69,15 @@ enum PredicatesCommand {
     /// Check given predicate
     #[clap(name = "check", bin_name = "check")]
     Check(CheckPredicate),
+    /// Deliver again the occurrences of a registered predicate, on the blocks it previously triggered on
+    #[clap(name = "replay", bin_name = "replay")]
+    Replay(ReplayPredicate),
+    /// Measure the evaluation throughput of a Bitcoin predicate over local blocks
+    #[clap(name = "bench", bin_name = "bench")]
+    Bench(BenchPredicate),
+    /// Deliver again the occurrences recorded in the dead letters directory, dropping the ones delivered
+    #[clap(name = "redeliver", bin_name = "redeliver")]
+    Redeliver(RedeliverDeadLetters),
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -135,6 +142,12 @@ struct ScanPredicate {
         conflicts_with = "testnet"
     )]
     pub config_path: Option<String>,
+    /// First block to scan, overriding the predicate's `start_block`
+    #[clap(long = "start")]
+    pub start_block: Option<u64>,
+    /// Last block to scan, overriding the predicate's `end_block`
+    #[clap(long = "end")]
+    pub end_block: Option<u64>,
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -156,6 +169,45 @@ struct CheckPredicate {
     pub config_path: Option<String>,
 }
 
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct BenchPredicate {
+    /// Chainhook spec file to evaluate (json format)
+    #[clap(long = "predicate")]
+    pub predicate_path: String,
+    /// Directory of blocks to evaluate the predicate on, one json encoded block per file
+    #[clap(long = "blocks")]
+    pub blocks_dir: String,
+    /// Target Testnet network
+    #[clap(long = "testnet", conflicts_with = "mainnet")]
+    pub testnet: bool,
+    /// Target Mainnet network
+    #[clap(long = "mainnet", conflicts_with = "testnet")]
+    pub mainnet: bool,
+    /// Load config file path
+    #[clap(
+        long = "config-path",
+        conflicts_with = "mainnet",
+        conflicts_with = "testnet"
+    )]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ReplayPredicate {
+    /// Uuid of the registered predicate to replay
+    pub predicate_uuid: String,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct RedeliverDeadLetters {
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
 #[derive(Subcommand, PartialEq, Clone, Debug)]
 enum ServiceCommand {
     /// Start chainhook-cli
@@ -398,6 +450,7 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
                             expire_after_occurrence: None,
                             capture_all_events: None,
                             decode_clarity_values: None,
+                            skip_rollback_only_occurrences: None,
                             include_contract_abi: None,
                             action:  HookAction::FileAppend(FileHook {
                                 path: "arkadiko.txt".into()
@@ -415,6 +468,7 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
                             expire_after_occurrence: None,
                             capture_all_events: None,
                             decode_clarity_values: None,
+                            skip_rollback_only_occurrences: None,
                             include_contract_abi: None,
                             action:  HookAction::FileAppend(FileHook {
                                 path: "arkadiko.txt".into()
@@ -425,6 +479,7 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
                             StacksChainhookSpecificationNetworkMap {
                                 uuid: id.to_string(),
                                 owner_uuid: None,
+                                on_expire_url: None,
                                 name: "Hello world".into(),
                                 version: 1,
                                 networks,
@@ -439,6 +494,7 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
                             BitcoinChainhookSpecification {
                                 start_block: Some(767430),
                                 end_block: Some(767430),
+                                end_block_grace_period: None,
                                 blocks: None,
                                 predicate: BitcoinPredicateType::OrdinalsProtocol(
                                     OrdinalOperations::InscriptionFeed(InscriptionFeedData {
@@ -453,6 +509,19 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
                                 include_outputs: None,
                                 include_proof: None,
                                 include_witness: None,
+                                proof_format: None,
+                                amount_format: None,
+                                include_brc20_balance_deltas: None,
+                                include_brc20_operation: None,
+                                max_transactions_per_block: None,
+                                skip_rollback_only_occurrences: None,
+                                digest: None,
+                                omit_empty_arrays: None,
+                                payload_shape: None,
+                                include_empty_blocks: None,
+                                include_size: None,
+                                include_version: None,
+                                include_dropped: None,
                             },
                         );
 
@@ -460,6 +529,7 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
                             BitcoinChainhookSpecificationNetworkMap {
                                 uuid: id.to_string(),
                                 owner_uuid: None,
+                                on_expire_url: None,
                                 name: "Hello world".into(),
                                 version: 1,
                                 networks,
@@ -502,84 +572,105 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
                     }
                 };
             }
-            PredicatesCommand::Scan(cmd) => {
-                let mut config =
-                    Config::default(false, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
-                let predicate = load_predicate_from_path(&cmd.predicate_path)?;
-                predicate.validate()?;
-                match predicate {
-                    ChainhookSpecificationNetworkMap::Bitcoin(predicate) => {
-                        let predicate_spec = match predicate
-                            .into_specification_for_network(&config.network.bitcoin_network)
-                        {
-                            Ok(predicate) => predicate,
-                            Err(e) => {
-                                return Err(format!(
-                                    "Specification missing for network {:?}: {e}",
-                                    config.network.bitcoin_network
-                                ));
-                            }
-                        };
-
-                        scan_bitcoin_chainstate_via_rpc_using_predicate(
-                            &predicate_spec,
-                            None,
-                            &config,
-                            None,
-                            &ctx,
-                        )
-                        .await?;
-                    }
-                    ChainhookSpecificationNetworkMap::Stacks(predicate) => {
-                        let predicate_spec = match predicate
-                            .into_specification_for_network(&config.network.stacks_network)
-                        {
-                            Ok(predicate) => predicate,
-                            Err(e) => {
-                                return Err(format!(
-                                    "Specification missing for network {:?}: {e}",
-                                    config.network.bitcoin_network
-                                ));
-                            }
-                        };
-                        match open_readonly_stacks_db_conn(&config.expected_cache_path(), &ctx) {
-                            Ok(db_conn) => {
-                                let _ = consolidate_local_stacks_chainstate_using_csv(
-                                    &mut config,
-                                    &ctx,
-                                )
-                                .await;
-                                scan_stacks_chainstate_via_rocksdb_using_predicate(
-                                    &predicate_spec,
-                                    None,
-                                    &db_conn,
-                                    &config,
-                                    None,
-                                    &ctx,
-                                )
-                                .await?;
-                            }
-                            Err(e) => {
-                                info!(
-                                    ctx.expect_logger(),
-                                    "Could not open db. This will greatly increase scan times. Error: {}", e
-                                );
-                                scan_stacks_chainstate_via_csv_using_predicate(
-                                    &predicate_spec,
-                                    &mut config,
-                                    &ctx,
-                                )
-                                .await?;
-                            }
-                        };
+            PredicatesCommand::Scan(cmd) => scan_predicate_command(cmd, &ctx).await?,
+            PredicatesCommand::Replay(cmd) => {
+                let mut config = Config::default(false, false, false, &cmd.config_path)?;
+                let summary =
+                    replay_triggered_blocks(&cmd.predicate_uuid, &mut config, &ctx).await?;
+                println!(
+                    "Predicate {} replayed: {} blocks scanned, {} blocks triggering predicate",
+                    cmd.predicate_uuid,
+                    summary.number_of_blocks_scanned,
+                    summary.number_of_times_triggered
+                );
+            }
+            PredicatesCommand::Redeliver(cmd) => {
+                let config = Config::default(false, false, false, &cmd.config_path)?;
+                let Some(ref dead_letters_path) = config.predicates.dead_letters_path else {
+                    return Err(
+                        "predicates.dead_letters_path must be set to redeliver dead letters"
+                            .to_string(),
+                    );
+                };
+                // dead letters don't keep their headers: they're built again from the predicates
+                let PredicatesApi::On(ref api_config) = config.http_api else {
+                    return Err(
+                        "the predicates api must be enabled to redeliver dead letters".to_string(),
+                    );
+                };
+                let store = DeadLetterStore::open(dead_letters_path)?;
+                let predicate_keys = store
+                    .get_dead_letters(&ctx)?
+                    .into_iter()
+                    .map(|dead_letter| dead_letter.predicate_key)
+                    .collect::<Vec<_>>();
+                let hooks = get_registered_http_hooks(api_config, &predicate_keys)?;
+                let (delivered, remaining) = redeliver_dead_letters(
+                    &store,
+                    &hooks,
+                    &config.predicates.http_delivery.signing_secrets,
+                    &config.build_http_delivery_client()?,
+                    &config.predicates.http_delivery.retry_policy,
+                    &ctx,
+                )
+                .await?;
+                println!(
+                    "{} dead letters redelivered, {} remaining in {}",
+                    delivered,
+                    remaining,
+                    dead_letters_path.display()
+                );
+            }
+            PredicatesCommand::Bench(cmd) => {
+                let config = Config::default(false, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
+                let predicate = match load_predicate_from_path(&cmd.predicate_path)? {
+                    ChainhookSpecificationNetworkMap::Bitcoin(predicate) => predicate,
+                    ChainhookSpecificationNetworkMap::Stacks(_) => {
+                        return Err("only Bitcoin predicates can be benchmarked".to_string());
                     }
-                }
+                };
+                let predicate = predicate
+                    .into_specification_for_network(&config.network.bitcoin_network)
+                    .map_err(|e| {
+                        format!(
+                            "Specification missing for network {:?}: {e}",
+                            config.network.bitcoin_network
+                        )
+                    })?;
+                let blocks = load_bitcoin_blocks_from_dir(&PathBuf::from(&cmd.blocks_dir))?;
+                let report = bench_bitcoin_predicate(
+                    &predicate,
+                    &blocks,
+                    config.predicates.descriptor_default_range,
+                    &ctx,
+                );
+                println!(
+                    "Predicate {} ({}): {} transactions evaluated in {} blocks, {} matching",
+                    cmd.predicate_path,
+                    report.predicate_arm,
+                    report.transactions_evaluated,
+                    report.blocks_evaluated,
+                    report.transactions_matched
+                );
+                println!(
+                    "{:.0} evaluations/sec, {:?} per evaluation on average, {:?} for the slowest",
+                    report.evaluations_per_sec(),
+                    report.mean_evaluation(),
+                    report.slowest_evaluation
+                );
+                println!(
+                    "{:?} spent on matching transactions, {:?} on other transactions",
+                    report.matched_elapsed, report.unmatched_elapsed
+                );
             }
             PredicatesCommand::Check(cmd) => {
                 let config = Config::default(false, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
                 let predicate: ChainhookSpecificationNetworkMap =
                     load_predicate_from_path(&cmd.predicate_path)?;
                 predicate.validate()?;
+                for warning in predicate.get_warnings() {
+                    println!("⚠️ {}", warning);
+                }
 
                 match predicate {
                     ChainhookSpecificationNetworkMap::Bitcoin(predicate) => {
@@ -873,6 +964,18 @@ async fn handle_command(opts: Opts, ctx: Context) -> Result<(), String> {
     Ok(())
 }
 
+async fn scan_predicate_command(cmd: ScanPredicate, ctx: &Context) -> Result<(), String> {
+    let mut config = Config::default(false, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
+    let predicate = load_predicate_from_path(&cmd.predicate_path)?;
+    let summary =
+        scan_predicate_once(predicate, cmd.start_block, cmd.end_block, &mut config, ctx).await?;
+    println!(
+        "Predicate {} scanned: {} blocks scanned, {} blocks triggering predicate",
+        cmd.predicate_path, summary.number_of_blocks_scanned, summary.number_of_times_triggered
+    );
+    Ok(())
+}
+
 pub fn load_predicate_from_path(
     predicate_path: &str,
 ) -> Result<ChainhookSpecificationNetworkMap, String> {
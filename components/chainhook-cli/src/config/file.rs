@@ -1,4 +1,5 @@
 use chainhook_sdk::types::BitcoinNetwork;
+use std::collections::HashMap;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ConfigFile {
@@ -8,17 +9,29 @@ pub struct ConfigFile {
     pub limits: LimitsConfigFile,
     pub network: NetworkConfigFile,
     pub monitoring: Option<MonitoringConfigFile>,
+    pub predicates: Option<PredicatesConfigFile>,
+    pub audit_log: Option<AuditLogConfigFile>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct StorageConfigFile {
     pub working_dir: Option<String>,
+    pub offline: Option<bool>,
+    pub archive_download_buffer_size: Option<usize>,
+    pub parallel_archive_decoding: Option<bool>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct PredicatesApiConfigFile {
     pub http_port: Option<u16>,
+    pub database_backend: Option<String>,
     pub database_uri: Option<String>,
+    pub bitcoin_database_uri: Option<String>,
+    pub stacks_database_uri: Option<String>,
+    pub expired_predicates_retention_secs: Option<u64>,
+    pub admin_token: Option<String>,
+    pub owner_tokens: Option<HashMap<String, String>>,
+    pub max_body_size: Option<u64>,
     pub display_logs: Option<bool>,
     pub disabled: Option<bool>,
 }
@@ -42,6 +55,7 @@ pub struct LimitsConfigFile {
     pub max_number_of_processing_threads: Option<usize>,
     pub max_number_of_networking_threads: Option<usize>,
     pub max_caching_memory_size_mb: Option<usize>,
+    pub max_buffered_occurrences_size_mb: Option<usize>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -87,3 +101,31 @@ impl NetworkConfigMode {
 pub struct MonitoringConfigFile {
     pub prometheus_monitoring_port: Option<u16>,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PredicatesConfigFile {
+    pub descriptor_default_range: Option<[u32; 2]>,
+    pub ack_deliveries: Option<bool>,
+    pub disabled_stacks_operations: Option<Vec<String>>,
+    pub deduplicate_reapplied_blocks: Option<bool>,
+    pub dead_letters_path: Option<String>,
+    pub bundle_url: Option<String>,
+    pub bundle_sha256: Option<String>,
+    pub delivery_timeout_secs: Option<u64>,
+    pub delivery_pool_max_idle_per_host: Option<usize>,
+    pub delivery_pool_idle_timeout_secs: Option<u64>,
+    pub delivery_retry_attempts_max: Option<u16>,
+    pub delivery_retry_base_delay_ms: Option<u64>,
+    pub delivery_retry_max_delay_ms: Option<u64>,
+    pub delivery_retry_jitter: Option<bool>,
+    pub delivery_signing_secrets: Option<HashMap<String, String>>,
+    pub delivery_signature_hash: Option<String>,
+    pub deduplication_hash: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AuditLogConfigFile {
+    pub file_path: Option<String>,
+    pub redis_uri: Option<String>,
+    pub redis_stream_key: Option<String>,
+}
@@ -7,6 +7,13 @@ pub fn generate_config(network: &BitcoinNetwork) -> String {
     let conf = format!(
         r#"[storage]
 working_dir = "cache"
+# When enabled, chainhook will never reach out to the remote Stacks archive.
+# A local TSV file must then be provided with `tsv_file_path`.
+# offline = false
+# Number of downloaded chunks of the Stacks archive buffered ahead of the decoder.
+# archive_download_buffer_size = 0
+# When enabled, decompression and disk writes of the Stacks archive run on separate threads.
+# parallel_archive_decoding = false
 
 # The HTTP API allows you to register / deregister
 # predicates dynamically.
@@ -15,6 +22,23 @@ working_dir = "cache"
 # [http_api]
 # http_port = 20456
 # database_uri = "redis://localhost:6379/"
+# Predicates can be stored in SQLite instead of Redis, in which case the database uris are
+# paths to SQLite files (defaults to "<working_dir>/predicates.sqlite").
+# database_backend = "sqlite"
+# Bitcoin and Stacks predicates can be isolated in their own databases.
+# bitcoin_database_uri = "redis://localhost:6379/1"
+# stacks_database_uri = "redis://localhost:6379/2"
+# Expired predicates are kept in the database for the given number of seconds.
+# They are kept forever by default.
+# expired_predicates_retention_secs = 86400
+# Admin routes (e.g. force-expiring a predicate, reloading predicates) require this bearer token.
+# They are disabled by default.
+# admin_token = "<secret>"
+# Scopes predicates to their `owner_uuid`, each bearer token granting access to the predicates
+# of one owner. Predicates are not scoped by default.
+# owner_tokens = {{ "<secret>" = "<owner_uuid>" }}
+# Requests with a body larger than this many bytes are rejected (defaults to 1MB).
+# max_body_size = 1048576
 
 [network]
 mode = "{mode}"
@@ -40,6 +64,8 @@ max_number_of_concurrent_stacks_scans = 10
 max_number_of_processing_threads = 16
 max_number_of_networking_threads = 16
 max_caching_memory_size_mb = 32000
+# Delivers the occurrences of Bitcoin predicates before building the next ones once those waiting to be delivered exceed this size.
+# max_buffered_occurrences_size_mb = 512
 
 # The TSV file is required for downloading historical data for your predicates. 
 # If this is not a requirement, you can comment out the `tsv_file_url` line.
@@ -50,6 +76,58 @@ tsv_file_url = "https://archive.hiro.so/{network}/stacks-blockchain-api/{network
 # This is disabled by default.
 # [monitoring]
 # prometheus_monitoring_port = 20457
+
+# Sets the derivation range used by descriptor predicates that do not define their own `range`.
+# Defaults to [0, 5].
+# [predicates]
+# descriptor_default_range = [0, 5]
+# When enabled, occurrences delivered to `http_post` actions are kept on disk until
+# the consumer returns a 2xx response, and redelivered on restart only. Occurrences
+# delivered while scanning are not covered.
+# ack_deliveries = false
+# Stacks operations that are never parsed out of Bitcoin blocks, among `block_committed`,
+# `leader_registered`, `stx_transferred` and `stx_locked`.
+# disabled_stacks_operations = ["stx_locked"]
+# When enabled, Bitcoin blocks applied again are not delivered twice to the predicates
+# they were already delivered to, unless their rollback was delivered in between.
+# deduplicate_reapplied_blocks = false
+# Bundle of predicates (JSON array) fetched and registered at startup. The bundle is ignored,
+# with a warning, if it can't be fetched or doesn't match its SHA-256 digest.
+# bundle_url = "https://example.com/predicates.json"
+# bundle_sha256 = "<hex encoded sha256 of the bundle>"
+# Timeout (in seconds) of the deliveries to `http_post` actions, and connection pool of the
+# client shared by every delivery. Deliveries never time out by default.
+# delivery_timeout_secs = 30
+# delivery_pool_max_idle_per_host = 32
+# delivery_pool_idle_timeout_secs = 90
+# Deliveries to `http_post` actions failing with a connection error or a 5xx, 408 or 429 status
+# are retried with an exponential backoff, starting at `delivery_retry_base_delay_ms` and doubling
+# up to `delivery_retry_max_delay_ms`. Other 4xx statuses are not retried.
+# delivery_retry_attempts_max = 5
+# delivery_retry_base_delay_ms = 1000
+# delivery_retry_max_delay_ms = 30000
+# delivery_retry_jitter = true
+# Secrets signing the payloads of the `http_post` actions referencing them by id with their
+# `signing_secret_id`. Secrets are never stored along with the predicates.
+# delivery_signing_secrets = { hook-secret = "<secret>" }
+# Hash of the HMAC signing the payloads, among `sha256` and `sha512`. Defaults to `sha256`.
+# delivery_signature_hash = "sha256"
+# Hash of the body deduplicating the messages sent to SQS FIFO queues by `sqs` actions that
+# don't set their own, among `sha256`, `sha512` and `xxh3`, a faster non-cryptographic hash.
+# Defaults to `sha256`.
+# deduplication_hash = "sha256"
+# Deliveries to `http_post` actions that failed for good are written, along with their url and
+# failure, to this directory. Run `chainhook predicates redeliver` to send them again.
+# This is disabled by default.
+# dead_letters_path = "dead_letters"
+
+# Records the registration, update and removal of predicates in an append-only audit log.
+# Entries are appended either to a file, or to a Redis stream.
+# This is disabled by default.
+# [audit_log]
+# file_path = "audit.log"
+# redis_uri = "redis://localhost:6379/"
+# redis_stream_key = "chainhook:audit_log"
 "#,
         mode = mode.as_str(),
         network = network.to_lowercase(),
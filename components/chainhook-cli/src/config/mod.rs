@@ -1,25 +1,35 @@
 pub mod file;
 pub mod generator;
 
-use chainhook_sdk::chainhooks::types::ChainhookStore;
+use chainhook_sdk::chainhooks::types::{
+    build_http_delivery_client, ChainhookStore, ContentHashAlgorithm, HttpDeliveryConfig,
+    SigningSecrets,
+};
+use chainhook_sdk::indexer::bitcoin::StacksOperationsParsing;
 pub use chainhook_sdk::indexer::IndexerConfig;
 use chainhook_sdk::observer::EventObserverConfig;
 use chainhook_sdk::types::{
-    BitcoinBlockSignaling, BitcoinNetwork, StacksNetwork, StacksNodeConfig,
+    BitcoinBlockSignaling, BitcoinNetwork, Chain, StacksNetwork, StacksNodeConfig,
 };
+use chainhook_sdk::utils::HttpRetryPolicy;
 pub use file::ConfigFile;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 const DEFAULT_MAINNET_STACKS_TSV_ARCHIVE: &str =
     "https://archive.hiro.so/mainnet/stacks-blockchain-api/mainnet-stacks-blockchain-api-latest";
 const DEFAULT_TESTNET_STACKS_TSV_ARCHIVE: &str =
     "https://archive.hiro.so/testnet/stacks-blockchain-api/testnet-stacks-blockchain-api-latest";
 pub const DEFAULT_REDIS_URI: &str = "redis://localhost:6379/";
+pub const DEFAULT_SQLITE_PREDICATES_DB: &str = "predicates.sqlite";
+pub const DEFAULT_AUDIT_LOG_STREAM_KEY: &str = "chainhook:audit_log";
 
 pub const DEFAULT_INGESTION_PORT: u16 = 20455;
 pub const DEFAULT_CONTROL_PORT: u16 = 20456;
+pub const DEFAULT_API_MAX_BODY_SIZE: u64 = 1024 * 1024;
 pub const STACKS_SCAN_THREAD_POOL_SIZE: usize = 10;
 pub const BITCOIN_SCAN_THREAD_POOL_SIZE: usize = 10;
 pub const STACKS_MAX_PREDICATE_REGISTRATION: usize = 50;
@@ -33,11 +43,20 @@ pub struct Config {
     pub limits: LimitsConfig,
     pub network: IndexerConfig,
     pub monitoring: MonitoringConfig,
+    pub predicates: PredicatesConfig,
+    pub audit_log: Option<AuditLogConfig>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct StorageConfig {
     pub working_dir: String,
+    pub offline: bool,
+    /// Number of downloaded chunks of the Stacks archive buffered ahead of the decoder. With `0`,
+    /// each chunk is handed over to the decoder before the next one is downloaded.
+    pub archive_download_buffer_size: usize,
+    /// When enabled, the decompressed Stacks archive is written to disk by a dedicated thread, so
+    /// that decompression overlaps with disk writes.
+    pub parallel_archive_decoding: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -46,13 +65,63 @@ pub enum PredicatesApi {
     On(PredicatesApiConfig),
 }
 
+/// Database storing the predicates registered through the predicates API.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PredicatesDatabaseBackend {
+    /// The database uris are the uris of Redis databases.
+    Redis,
+    /// The database uris are the paths of SQLite database files.
+    Sqlite,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct PredicatesApiConfig {
     pub http_port: u16,
+    pub database_backend: PredicatesDatabaseBackend,
     pub database_uri: String,
+    /// Database storing the Bitcoin predicates, defaults to `database_uri`.
+    pub bitcoin_database_uri: Option<String>,
+    /// Database storing the Stacks predicates, defaults to `database_uri`.
+    pub stacks_database_uri: Option<String>,
+    /// When set, expired predicates are evicted from the database after this many seconds.
+    pub expired_predicates_retention_secs: Option<u64>,
+    /// Bearer token required by the admin routes, which are disabled when unset.
+    pub admin_token: Option<String>,
+    /// Owner uuid of the predicates accessible with each bearer token. When set, predicates are
+    /// scoped to their `owner_uuid`: requests must carry one of these tokens, and only reach the
+    /// predicates of its owner. The admin token still reaches every predicate.
+    pub owner_tokens: Option<HashMap<String, String>>,
+    /// Maximum size, in bytes, of the request bodies accepted by the API. Defaults to 1MB.
+    pub max_body_size: Option<u64>,
     pub display_logs: bool,
 }
 
+impl PredicatesApiConfig {
+    pub fn get_database_uri(&self, chain: &Chain) -> &str {
+        let database_uri = match chain {
+            Chain::Bitcoin => &self.bitcoin_database_uri,
+            Chain::Stacks => &self.stacks_database_uri,
+        };
+        database_uri.as_ref().unwrap_or(&self.database_uri)
+    }
+
+    /// Returns the distinct databases storing predicates.
+    pub fn get_database_uris(&self) -> Vec<&str> {
+        let mut database_uris = vec![];
+        for chain in [Chain::Bitcoin, Chain::Stacks].iter() {
+            let database_uri = self.get_database_uri(chain);
+            if !database_uris.contains(&database_uri) {
+                database_uris.push(database_uri);
+            }
+        }
+        database_uris
+    }
+
+    pub fn get_max_body_size(&self) -> u64 {
+        self.max_body_size.unwrap_or(DEFAULT_API_MAX_BODY_SIZE)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum EventSourceConfig {
     StacksTsvPath(PathConfig),
@@ -80,12 +149,54 @@ pub struct LimitsConfig {
     pub max_number_of_processing_threads: usize,
     pub max_number_of_networking_threads: usize,
     pub max_caching_memory_size_mb: usize,
+    pub max_buffered_occurrences_size_mb: Option<usize>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct MonitoringConfig {
     pub prometheus_monitoring_port: Option<u16>,
 }
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PredicatesConfig {
+    /// Derivation range applied to descriptor predicates that do not define their own `range`.
+    pub descriptor_default_range: Option<[u32; 2]>,
+    /// When enabled, `http_post` occurrences are persisted until acknowledged and redelivered on restart.
+    pub ack_deliveries: bool,
+    /// The Stacks operations parsed out of Bitcoin blocks.
+    pub stacks_operations_parsing: StacksOperationsParsing,
+    /// When enabled, Bitcoin blocks re-applied by a re-org are not delivered twice to the same predicate.
+    pub deduplicate_reapplied_blocks: bool,
+    /// Directory receiving the `http_post` occurrences that could not be delivered, for a later replay.
+    pub dead_letters_path: Option<PathBuf>,
+    /// Remote bundle of predicates registered at startup.
+    pub bundle: Option<PredicatesBundleConfig>,
+    /// Timeout and connection pool of the client delivering `http_post` occurrences.
+    pub http_delivery: HttpDeliveryConfig,
+    /// Hash of the deduplication ids of the messages sent to SQS FIFO queues.
+    pub deduplication_hash: ContentHashAlgorithm,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PredicatesBundleConfig {
+    /// Url serving a JSON array of predicates.
+    pub url: String,
+    /// Hex encoded SHA-256 digest the bundle must match.
+    pub sha256: String,
+}
+
+/// Sink of the audit log recording the lifecycle events of predicates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuditLogConfig {
+    /// Appends one JSON entry per line to a file.
+    File(PathConfig),
+    /// Appends entries to a Redis stream.
+    RedisStream {
+        redis_uri: String,
+        stream_key: String,
+    },
+}
+
 impl Config {
     pub fn from_file_path(file_path: &str) -> Result<Config, String> {
         let file = File::open(file_path)
@@ -112,6 +223,15 @@ impl Config {
         }
     }
 
+    /// Builds the client delivering `http_post` occurrences. It is built once by the service, or by
+    /// the command being run, and shared by the observer and every scan so that connections and TLS
+    /// sessions are reused across deliveries.
+    pub fn build_http_delivery_client(&self) -> Result<reqwest::Client, String> {
+        build_http_delivery_client(&self.predicates.http_delivery)
+    }
+
+    /// The settings of the observer. Its `http_client` is left unset, for callers to share the
+    /// client built by [Config::build_http_delivery_client].
     pub fn get_event_observer_config(&self) -> EventObserverConfig {
         EventObserverConfig {
             bitcoin_rpc_proxy_enabled: true,
@@ -124,6 +244,22 @@ impl Config {
             bitcoin_network: self.network.bitcoin_network.clone(),
             stacks_network: self.network.stacks_network.clone(),
             prometheus_monitoring_port: self.monitoring.prometheus_monitoring_port,
+            descriptor_default_range: self.predicates.descriptor_default_range,
+            pending_deliveries_path: match self.predicates.ack_deliveries {
+                true => Some(self.expected_pending_deliveries_path()),
+                false => None,
+            },
+            dead_letters_path: self.predicates.dead_letters_path.clone(),
+            http_client: None,
+            http_retry_policy: self.predicates.http_delivery.retry_policy.clone(),
+            signing_secrets: self.predicates.http_delivery.signing_secrets.clone(),
+            deduplication_hash: self.predicates.deduplication_hash,
+            stacks_operations_parsing: self.predicates.stacks_operations_parsing.clone(),
+            max_buffered_occurrences_size: self
+                .limits
+                .max_buffered_occurrences_size_mb
+                .map(|size| size * 1024 * 1024),
+            deduplicate_reapplied_blocks: self.predicates.deduplicate_reapplied_blocks,
         }
     }
 
@@ -153,23 +289,192 @@ impl Config {
         } else {
             None
         };
-        let config = Config {
-            storage: StorageConfig {
-                working_dir: config_file.storage.working_dir.unwrap_or("cache".into()),
+        let dead_letters_path = config_file
+            .predicates
+            .as_ref()
+            .and_then(|predicates| predicates.dead_letters_path.as_ref())
+            .map(PathBuf::from);
+        let parse_hash = |key: &str, hash: Option<&String>| match hash {
+            Some(hash) => ContentHashAlgorithm::from_str(hash)
+                .map_err(|e| format!("predicates.{}: {}", key, e)),
+            None => Ok(ContentHashAlgorithm::default()),
+        };
+        let deduplication_hash = parse_hash(
+            "deduplication_hash",
+            config_file
+                .predicates
+                .as_ref()
+                .and_then(|predicates| predicates.deduplication_hash.as_ref()),
+        )?;
+        let signature_hash = parse_hash(
+            "delivery_signature_hash",
+            config_file
+                .predicates
+                .as_ref()
+                .and_then(|predicates| predicates.delivery_signature_hash.as_ref()),
+        )?;
+        let (
+            descriptor_default_range,
+            ack_deliveries,
+            disabled_stacks_operations,
+            deduplicate_reapplied_blocks,
+            bundle,
+            http_delivery,
+        ) = match config_file.predicates {
+            Some(predicates) => (
+                predicates.descriptor_default_range,
+                predicates.ack_deliveries.unwrap_or(false),
+                predicates.disabled_stacks_operations.unwrap_or_default(),
+                predicates.deduplicate_reapplied_blocks.unwrap_or(false),
+                match (predicates.bundle_url, predicates.bundle_sha256) {
+                    (None, None) => None,
+                    (Some(url), Some(sha256)) => Some(PredicatesBundleConfig { url, sha256 }),
+                    _ => {
+                        return Err(
+                            "predicates: bundle_url and bundle_sha256 must be set together"
+                                .to_string(),
+                        )
+                    }
+                },
+                HttpDeliveryConfig {
+                    timeout_secs: predicates.delivery_timeout_secs,
+                    pool_max_idle_per_host: predicates.delivery_pool_max_idle_per_host,
+                    pool_idle_timeout_secs: predicates.delivery_pool_idle_timeout_secs,
+                    retry_policy: {
+                        let default = HttpRetryPolicy::default();
+                        HttpRetryPolicy {
+                            attempts_max: predicates
+                                .delivery_retry_attempts_max
+                                .unwrap_or(default.attempts_max),
+                            base_delay_ms: predicates
+                                .delivery_retry_base_delay_ms
+                                .unwrap_or(default.base_delay_ms),
+                            max_delay_ms: predicates
+                                .delivery_retry_max_delay_ms
+                                .unwrap_or(default.max_delay_ms),
+                            jitter: predicates.delivery_retry_jitter.unwrap_or(default.jitter),
+                        }
+                    },
+                    signing_secrets: SigningSecrets::new(
+                        predicates.delivery_signing_secrets.unwrap_or_default(),
+                    )
+                    .with_hash(signature_hash)
+                    .map_err(|e| format!("predicates.delivery_signature_hash: {}", e))?,
+                },
+            ),
+            None => (
+                None,
+                false,
+                vec![],
+                false,
+                None,
+                HttpDeliveryConfig::default(),
+            ),
+        };
+        if let Err(e) = http_delivery.retry_policy.validate() {
+            return Err(format!("predicates.delivery_retry: {}", e));
+        }
+        if let Some(range) = descriptor_default_range {
+            if !(range[0] < range[1]) {
+                return Err(
+                    "predicates.descriptor_default_range: first element must be lower than the second element"
+                        .to_string(),
+                );
+            }
+        }
+        let mut stacks_operations_parsing = StacksOperationsParsing::default();
+        for operation in disabled_stacks_operations.iter() {
+            match operation.as_str() {
+                "block_committed" => stacks_operations_parsing.block_committed = false,
+                "leader_registered" => stacks_operations_parsing.leader_registered = false,
+                "stx_transferred" => stacks_operations_parsing.stx_transferred = false,
+                "stx_locked" => stacks_operations_parsing.stx_locked = false,
+                _ => {
+                    return Err(format!(
+                        "predicates.disabled_stacks_operations: unknown operation '{}'",
+                        operation
+                    ))
+                }
+            }
+        }
+        let audit_log = match config_file.audit_log {
+            None => None,
+            Some(audit_log) => match (audit_log.file_path, audit_log.redis_uri) {
+                (Some(file_path), None) => Some(AuditLogConfig::File(PathConfig {
+                    file_path: PathBuf::from(file_path),
+                })),
+                (None, Some(redis_uri)) => Some(AuditLogConfig::RedisStream {
+                    redis_uri,
+                    stream_key: audit_log
+                        .redis_stream_key
+                        .unwrap_or(DEFAULT_AUDIT_LOG_STREAM_KEY.to_string()),
+                }),
+                _ => {
+                    return Err(
+                        "audit_log: exactly one of file_path or redis_uri must be set".to_string(),
+                    )
+                }
             },
-            http_api: match config_file.http_api {
-                None => PredicatesApi::Off,
-                Some(http_api) => match http_api.disabled {
-                    Some(true) => PredicatesApi::Off,
-                    _ => PredicatesApi::On(PredicatesApiConfig {
+        };
+        let http_api = match config_file.http_api {
+            None => PredicatesApi::Off,
+            Some(http_api) => match http_api.disabled {
+                Some(true) => PredicatesApi::Off,
+                _ => {
+                    let database_backend = match http_api.database_backend.as_deref() {
+                        None | Some("redis") => PredicatesDatabaseBackend::Redis,
+                        Some("sqlite") => PredicatesDatabaseBackend::Sqlite,
+                        Some(backend) => {
+                            return Err(format!(
+                                "http_api.database_backend: unknown backend '{}'",
+                                backend
+                            ))
+                        }
+                    };
+                    let default_database_uri = match database_backend {
+                        PredicatesDatabaseBackend::Redis => DEFAULT_REDIS_URI.to_string(),
+                        PredicatesDatabaseBackend::Sqlite => {
+                            let mut db_path = PathBuf::from(
+                                config_file
+                                    .storage
+                                    .working_dir
+                                    .as_deref()
+                                    .unwrap_or("cache"),
+                            );
+                            db_path.push(DEFAULT_SQLITE_PREDICATES_DB);
+                            db_path.display().to_string()
+                        }
+                    };
+                    PredicatesApi::On(PredicatesApiConfig {
                         http_port: http_api.http_port.unwrap_or(DEFAULT_CONTROL_PORT),
                         display_logs: http_api.display_logs.unwrap_or(true),
-                        database_uri: http_api
-                            .database_uri
-                            .unwrap_or(DEFAULT_REDIS_URI.to_string()),
-                    }),
-                },
+                        database_backend,
+                        database_uri: http_api.database_uri.unwrap_or(default_database_uri),
+                        bitcoin_database_uri: http_api.bitcoin_database_uri,
+                        stacks_database_uri: http_api.stacks_database_uri,
+                        expired_predicates_retention_secs: http_api
+                            .expired_predicates_retention_secs,
+                        admin_token: http_api.admin_token,
+                        owner_tokens: http_api.owner_tokens,
+                        max_body_size: http_api.max_body_size,
+                    })
+                }
             },
+        };
+        let config = Config {
+            storage: StorageConfig {
+                working_dir: config_file.storage.working_dir.unwrap_or("cache".into()),
+                offline: config_file.storage.offline.unwrap_or(false),
+                archive_download_buffer_size: config_file
+                    .storage
+                    .archive_download_buffer_size
+                    .unwrap_or(0),
+                parallel_archive_decoding: config_file
+                    .storage
+                    .parallel_archive_decoding
+                    .unwrap_or(false),
+            },
+            http_api,
             event_sources,
             limits: LimitsConfig {
                 max_number_of_stacks_predicates: config_file
@@ -200,6 +505,9 @@ impl Config {
                     .limits
                     .max_caching_memory_size_mb
                     .unwrap_or(2048),
+                max_buffered_occurrences_size_mb: config_file
+                    .limits
+                    .max_buffered_occurrences_size_mb,
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: config_file.network.bitcoind_rpc_url.to_string(),
@@ -220,6 +528,17 @@ impl Config {
             monitoring: MonitoringConfig {
                 prometheus_monitoring_port,
             },
+            predicates: PredicatesConfig {
+                descriptor_default_range,
+                ack_deliveries,
+                stacks_operations_parsing,
+                deduplicate_reapplied_blocks,
+                dead_letters_path,
+                bundle,
+                http_delivery,
+                deduplication_hash,
+            },
+            audit_log,
         };
         Ok(config)
     }
@@ -243,10 +562,6 @@ impl Config {
             }));
     }
 
-    pub fn expected_api_database_uri(&self) -> &str {
-        &self.expected_api_config().database_uri
-    }
-
     pub fn expected_api_config(&self) -> &PredicatesApiConfig {
         match self.http_api {
             PredicatesApi::On(ref config) => config,
@@ -269,6 +584,12 @@ impl Config {
         destination_path
     }
 
+    pub fn expected_pending_deliveries_path(&self) -> PathBuf {
+        let mut destination_path = self.expected_cache_path();
+        destination_path.push("pending_deliveries");
+        destination_path
+    }
+
     fn expected_remote_stacks_tsv_base_url(&self) -> Result<&String, String> {
         for source in self.event_sources.iter() {
             if let EventSourceConfig::StacksTsvUrl(config) = source {
@@ -331,6 +652,9 @@ impl Config {
         Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
+                offline: false,
+                archive_download_buffer_size: 0,
+                parallel_archive_decoding: false,
             },
             http_api: PredicatesApi::Off,
             event_sources: vec![],
@@ -342,6 +666,7 @@ impl Config {
                 max_number_of_processing_threads: 1.max(num_cpus::get().saturating_sub(1)),
                 max_number_of_networking_threads: 1.max(num_cpus::get().saturating_sub(1)),
                 max_caching_memory_size_mb: 2048,
+                max_buffered_occurrences_size_mb: None,
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:18443".into(),
@@ -356,6 +681,17 @@ impl Config {
             monitoring: MonitoringConfig {
                 prometheus_monitoring_port: None,
             },
+            predicates: PredicatesConfig {
+                descriptor_default_range: None,
+                ack_deliveries: false,
+                stacks_operations_parsing: StacksOperationsParsing::default(),
+                deduplicate_reapplied_blocks: false,
+                dead_letters_path: None,
+                bundle: None,
+                http_delivery: HttpDeliveryConfig::default(),
+                deduplication_hash: ContentHashAlgorithm::default(),
+            },
+            audit_log: None,
         }
     }
 
@@ -363,6 +699,9 @@ impl Config {
         Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
+                offline: false,
+                archive_download_buffer_size: 0,
+                parallel_archive_decoding: false,
             },
             http_api: PredicatesApi::Off,
             event_sources: vec![EventSourceConfig::StacksTsvUrl(UrlConfig {
@@ -376,6 +715,7 @@ impl Config {
                 max_number_of_processing_threads: 1.max(num_cpus::get().saturating_sub(1)),
                 max_number_of_networking_threads: 1.max(num_cpus::get().saturating_sub(1)),
                 max_caching_memory_size_mb: 2048,
+                max_buffered_occurrences_size_mb: None,
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:18332".into(),
@@ -390,6 +730,17 @@ impl Config {
             monitoring: MonitoringConfig {
                 prometheus_monitoring_port: None,
             },
+            predicates: PredicatesConfig {
+                descriptor_default_range: None,
+                ack_deliveries: false,
+                stacks_operations_parsing: StacksOperationsParsing::default(),
+                deduplicate_reapplied_blocks: false,
+                dead_letters_path: None,
+                bundle: None,
+                http_delivery: HttpDeliveryConfig::default(),
+                deduplication_hash: ContentHashAlgorithm::default(),
+            },
+            audit_log: None,
         }
     }
 
@@ -397,6 +748,9 @@ impl Config {
         Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
+                offline: false,
+                archive_download_buffer_size: 0,
+                parallel_archive_decoding: false,
             },
             http_api: PredicatesApi::Off,
             event_sources: vec![EventSourceConfig::StacksTsvUrl(UrlConfig {
@@ -410,6 +764,7 @@ impl Config {
                 max_number_of_processing_threads: 1.max(num_cpus::get().saturating_sub(1)),
                 max_number_of_networking_threads: 1.max(num_cpus::get().saturating_sub(1)),
                 max_caching_memory_size_mb: 2048,
+                max_buffered_occurrences_size_mb: None,
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:8332".into(),
@@ -424,6 +779,17 @@ impl Config {
             monitoring: MonitoringConfig {
                 prometheus_monitoring_port: None,
             },
+            predicates: PredicatesConfig {
+                descriptor_default_range: None,
+                ack_deliveries: false,
+                stacks_operations_parsing: StacksOperationsParsing::default(),
+                deduplicate_reapplied_blocks: false,
+                dead_letters_path: None,
+                bundle: None,
+                http_delivery: HttpDeliveryConfig::default(),
+                deduplication_hash: ContentHashAlgorithm::default(),
+            },
+            audit_log: None,
         }
     }
 }
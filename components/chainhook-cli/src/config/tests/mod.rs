@@ -1,15 +1,18 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use crate::config::{
-    file::{NetworkConfigMode, PredicatesApiConfigFile},
-    PredicatesApi, PredicatesApiConfig,
+    file::{NetworkConfigMode, PredicatesApiConfigFile, PredicatesConfigFile},
+    PredicatesApi, PredicatesApiConfig, PredicatesDatabaseBackend,
 };
 
 use super::{
     file::MonitoringConfigFile, generator::generate_config, Config, ConfigFile, EventSourceConfig,
     PathConfig,
 };
+use chainhook_sdk::chainhooks::types::{ContentHashAlgorithm, HttpDeliveryConfig, SigningSecrets};
 use chainhook_sdk::types::{BitcoinNetwork, StacksNetwork};
+use chainhook_sdk::utils::HttpRetryPolicy;
 use test_case::test_case;
 
 const LOCAL_DIR: &str = env!("CARGO_MANIFEST_DIR");
@@ -37,7 +40,14 @@ fn config_from_file_allows_setting_disabled_fields() {
     // http_api and monitoring are optional, so they are disabled in generated config file
     generated_config_file.http_api = Some(PredicatesApiConfigFile {
         http_port: Some(0),
+        database_backend: None,
         database_uri: Some(format!("")),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
         display_logs: Some(false),
         disabled: Some(false),
     });
@@ -52,6 +62,179 @@ fn config_from_file_allows_setting_disabled_fields() {
     );
 }
 
+#[test]
+fn config_from_file_allows_setting_http_delivery_client() {
+    let generated_config_str = generate_config(&BitcoinNetwork::Regtest);
+    let mut generated_config_file: ConfigFile = toml::from_str(&generated_config_str).unwrap();
+    generated_config_file.predicates = Some(PredicatesConfigFile {
+        descriptor_default_range: None,
+        ack_deliveries: None,
+        disabled_stacks_operations: None,
+        deduplicate_reapplied_blocks: None,
+        dead_letters_path: None,
+        bundle_url: None,
+        bundle_sha256: None,
+        delivery_timeout_secs: Some(30),
+        delivery_pool_max_idle_per_host: Some(32),
+        delivery_pool_idle_timeout_secs: None,
+        delivery_retry_attempts_max: Some(10),
+        delivery_retry_base_delay_ms: None,
+        delivery_retry_max_delay_ms: None,
+        delivery_retry_jitter: Some(false),
+        delivery_signing_secrets: Some(HashMap::from([(
+            "hook-secret".to_string(),
+            "secret".to_string(),
+        )])),
+        delivery_signature_hash: None,
+        deduplication_hash: None,
+    });
+    let generated_config = Config::from_config_file(generated_config_file).unwrap();
+    assert_eq!(
+        generated_config.predicates.http_delivery,
+        HttpDeliveryConfig {
+            timeout_secs: Some(30),
+            pool_max_idle_per_host: Some(32),
+            pool_idle_timeout_secs: None,
+            retry_policy: HttpRetryPolicy {
+                attempts_max: 10,
+                base_delay_ms: 1_000,
+                max_delay_ms: 30_000,
+                jitter: false,
+            },
+            signing_secrets: SigningSecrets::new(HashMap::from([(
+                "hook-secret".to_string(),
+                "secret".to_string(),
+            )])),
+        }
+    );
+    assert!(generated_config.build_http_delivery_client().is_ok());
+}
+
+#[test_case(Some(0), None; "no attempt")]
+#[test_case(None, Some(100); "max delay lower than base delay")]
+fn config_from_file_rejects_invalid_delivery_retry_policy(
+    attempts_max: Option<u16>,
+    max_delay_ms: Option<u64>,
+) {
+    let generated_config_str = generate_config(&BitcoinNetwork::Regtest);
+    let mut generated_config_file: ConfigFile = toml::from_str(&generated_config_str).unwrap();
+    generated_config_file.predicates = Some(PredicatesConfigFile {
+        descriptor_default_range: None,
+        ack_deliveries: None,
+        disabled_stacks_operations: None,
+        deduplicate_reapplied_blocks: None,
+        dead_letters_path: None,
+        bundle_url: None,
+        bundle_sha256: None,
+        delivery_timeout_secs: None,
+        delivery_pool_max_idle_per_host: None,
+        delivery_pool_idle_timeout_secs: None,
+        delivery_retry_attempts_max: attempts_max,
+        delivery_retry_base_delay_ms: None,
+        delivery_retry_max_delay_ms: max_delay_ms,
+        delivery_retry_jitter: None,
+        delivery_signing_secrets: None,
+        delivery_signature_hash: None,
+        deduplication_hash: None,
+    });
+    let err = Config::from_config_file(generated_config_file).unwrap_err();
+    assert!(err.starts_with("predicates.delivery_retry"));
+}
+
+#[test]
+fn config_from_file_selects_content_hashes() {
+    let generated_config_str = generate_config(&BitcoinNetwork::Regtest);
+    let mut generated_config_file: ConfigFile = toml::from_str(&generated_config_str).unwrap();
+    generated_config_file.predicates = Some(PredicatesConfigFile {
+        descriptor_default_range: None,
+        ack_deliveries: None,
+        disabled_stacks_operations: None,
+        deduplicate_reapplied_blocks: None,
+        dead_letters_path: None,
+        bundle_url: None,
+        bundle_sha256: None,
+        delivery_timeout_secs: None,
+        delivery_pool_max_idle_per_host: None,
+        delivery_pool_idle_timeout_secs: None,
+        delivery_retry_attempts_max: None,
+        delivery_retry_base_delay_ms: None,
+        delivery_retry_max_delay_ms: None,
+        delivery_retry_jitter: None,
+        delivery_signing_secrets: Some(HashMap::from([(
+            "hook-secret".to_string(),
+            "Jefe".to_string(),
+        )])),
+        delivery_signature_hash: Some("sha512".into()),
+        deduplication_hash: Some("xxh3".into()),
+    });
+    let generated_config = Config::from_config_file(generated_config_file.clone()).unwrap();
+    assert_eq!(
+        generated_config
+            .get_event_observer_config()
+            .deduplication_hash,
+        ContentHashAlgorithm::Xxh3
+    );
+    // RFC 4231, test case 2
+    assert_eq!(
+        generated_config
+            .predicates
+            .http_delivery
+            .signing_secrets
+            .sign("hook-secret", b"what do ya want for nothing?")
+            .unwrap(),
+        "sha512=164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+    );
+
+    // payloads are only signed with cryptographic hashes
+    let predicates = generated_config_file.predicates.as_mut().unwrap();
+    predicates.delivery_signature_hash = Some("xxh3".into());
+    let err = Config::from_config_file(generated_config_file.clone()).unwrap_err();
+    assert!(err.starts_with("predicates.delivery_signature_hash"));
+
+    let predicates = generated_config_file.predicates.as_mut().unwrap();
+    predicates.delivery_signature_hash = None;
+    predicates.deduplication_hash = Some("md5".into());
+    let err = Config::from_config_file(generated_config_file).unwrap_err();
+    assert!(err.starts_with("predicates.deduplication_hash"));
+}
+
+#[test]
+fn config_from_file_allows_storing_predicates_in_sqlite() {
+    let generated_config_str = generate_config(&BitcoinNetwork::Regtest);
+    let mut generated_config_file: ConfigFile = toml::from_str(&generated_config_str).unwrap();
+    generated_config_file.http_api = Some(PredicatesApiConfigFile {
+        http_port: Some(0),
+        database_backend: Some("sqlite".into()),
+        database_uri: None,
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: Some(false),
+        disabled: Some(false),
+    });
+    let generated_config = Config::from_config_file(generated_config_file.clone()).unwrap();
+    let api_config = generated_config.expected_api_config();
+    assert_eq!(
+        api_config.database_backend,
+        PredicatesDatabaseBackend::Sqlite
+    );
+    assert_eq!(
+        PathBuf::from(&api_config.database_uri),
+        PathBuf::from(&generated_config.storage.working_dir).join("predicates.sqlite")
+    );
+
+    generated_config_file
+        .http_api
+        .as_mut()
+        .unwrap()
+        .database_backend = Some("mysql".into());
+    Config::from_config_file(generated_config_file)
+        .expect_err("Did not reject unknown predicates database backend as expected.");
+}
+
 #[test]
 fn config_from_file_allows_local_tsv_file() {
     let path = format!(
@@ -78,7 +261,14 @@ fn is_http_api_enabled_handles_both_modes() {
     assert!(!config.is_http_api_enabled());
     config.http_api = PredicatesApi::On(PredicatesApiConfig {
         http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
         database_uri: format!(""),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
         display_logs: false,
     });
     assert!(config.is_http_api_enabled());
@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chainhook_sdk::chainhooks::bitcoin::{
+    BitcoinChainhookInstance, BitcoinPredicateType, BlockFeeRates,
+};
+use chainhook_sdk::types::BitcoinBlockData;
+use chainhook_sdk::utils::Context;
+
+/// Timing of the evaluation of a Bitcoin predicate over a set of blocks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PredicateBenchReport {
+    /// The arm of the predicate evaluated, e.g. `outputs.descriptor`.
+    pub predicate_arm: String,
+    pub blocks_evaluated: u64,
+    pub transactions_evaluated: u64,
+    pub transactions_matched: u64,
+    /// Time spent evaluating the transactions matching the predicate.
+    pub matched_elapsed: Duration,
+    /// Time spent evaluating the transactions not matching the predicate.
+    pub unmatched_elapsed: Duration,
+    pub slowest_evaluation: Duration,
+}
+
+impl PredicateBenchReport {
+    pub fn elapsed(&self) -> Duration {
+        self.matched_elapsed + self.unmatched_elapsed
+    }
+
+    pub fn evaluations_per_sec(&self) -> f64 {
+        let elapsed_nanos = self.elapsed().as_nanos().max(1) as f64;
+        self.transactions_evaluated as f64 * 1_000_000_000.0 / elapsed_nanos
+    }
+
+    pub fn mean_evaluation(&self) -> Duration {
+        match self.transactions_evaluated {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.elapsed().as_nanos() / count as u128) as u64),
+        }
+    }
+}
+
+/// Returns the arm of `predicate` as `<scope>.<rule>`, e.g. `outputs.descriptor`, or `<scope>`
+/// for predicates without a rule.
+pub fn get_predicate_arm(predicate: &BitcoinPredicateType) -> String {
+    let value = serde_json::to_value(predicate).unwrap_or_default();
+    let scope = value["scope"].as_str().unwrap_or("unknown").to_string();
+    let rule = value
+        .as_object()
+        .and_then(|fields| fields.keys().find(|key| key.as_str() != "scope").cloned());
+    match rule {
+        Some(rule) => format!("{}.{}", scope, rule),
+        None => scope,
+    }
+}
+
+/// Loads the blocks of `blocks_dir`, one JSON encoded block per `.json` file, sorted by file name.
+pub fn load_bitcoin_blocks_from_dir(blocks_dir: &Path) -> Result<Vec<BitcoinBlockData>, String> {
+    let entries = std::fs::read_dir(blocks_dir).map_err(|e| {
+        format!(
+            "unable to read directory {}: {}",
+            blocks_dir.display(),
+            e.to_string()
+        )
+    })?;
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("unable to read directory entry: {}", e.to_string()))?
+            .path();
+        if path.extension().map_or(false, |ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    let mut blocks = vec![];
+    for path in paths.iter() {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("unable to read file {}: {}", path.display(), e.to_string()))?;
+        let block: BitcoinBlockData = serde_json::from_slice(&bytes).map_err(|e| {
+            format!(
+                "unable to parse block {}: {}",
+                path.display(),
+                e.to_string()
+            )
+        })?;
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+/// Evaluates `predicate` on every transaction of `blocks`, timing each evaluation. Occurrences are
+/// neither built nor delivered. The fee rates of a block, computed once per block for
+/// `fee_rate_percentile` predicates, are not part of the timings.
+pub fn bench_bitcoin_predicate(
+    predicate: &BitcoinChainhookInstance,
+    blocks: &[BitcoinBlockData],
+    descriptor_default_range: Option<[u32; 2]>,
+    ctx: &Context,
+) -> PredicateBenchReport {
+    let mut report = PredicateBenchReport {
+        predicate_arm: get_predicate_arm(&predicate.predicate),
+        ..Default::default()
+    };
+    let uses_fee_rates = matches!(
+        predicate.predicate,
+        BitcoinPredicateType::FeeRatePercentile(_)
+    );
+    for block in blocks.iter() {
+        report.blocks_evaluated += 1;
+        let fee_rates = uses_fee_rates.then(|| BlockFeeRates::new(block));
+        for tx in block.transactions.iter() {
+            let started_at = Instant::now();
+            let matched = predicate.evaluate_transaction(
+                tx,
+                fee_rates.as_ref(),
+                descriptor_default_range,
+                ctx,
+            );
+            let elapsed = started_at.elapsed();
+            report.transactions_evaluated += 1;
+            if matched {
+                report.transactions_matched += 1;
+                report.matched_elapsed += elapsed;
+            } else {
+                report.unmatched_elapsed += elapsed;
+            }
+            report.slowest_evaluation = report.slowest_evaluation.max(elapsed);
+        }
+    }
+    report
+}
@@ -1,39 +1,130 @@
 use crate::config::{Config, PredicatesApi};
 use crate::scan::common::get_block_heights_to_scan;
 use crate::service::{
-    open_readwrite_predicates_db_conn_or_panic, set_confirmed_expiration_status,
-    set_predicate_scanning_status, set_unconfirmed_expiration_status, ScanningData,
+    insert_triggered_block_heights, open_predicate_store_or_panic, set_confirmed_expiration_status,
+    set_unconfirmed_expiration_status, ScanningData, ScanningStatusCache,
 };
 use chainhook_sdk::bitcoincore_rpc::RpcApi;
 use chainhook_sdk::bitcoincore_rpc::{Auth, Client};
+#[cfg(feature = "grpc")]
+use chainhook_sdk::chainhooks::bitcoin::grpc_export::send_grpc_occurrence;
+#[cfg(feature = "kafka")]
+use chainhook_sdk::chainhooks::bitcoin::kafka_export::produce_kafka_message;
+#[cfg(feature = "parquet")]
+use chainhook_sdk::chainhooks::bitcoin::parquet_export::append_parquet_rows;
+#[cfg(feature = "s3")]
+use chainhook_sdk::chainhooks::bitcoin::s3_export::append_s3_object;
+#[cfg(feature = "sqs")]
+use chainhook_sdk::chainhooks::bitcoin::sqs_export::send_sqs_occurrences;
+#[cfg(feature = "websocket")]
+use chainhook_sdk::chainhooks::bitcoin::websocket_export::send_websocket_message;
+use chainhook_sdk::chainhooks::bitcoin::BitcoinChainhookInstance;
 use chainhook_sdk::chainhooks::bitcoin::{
     evaluate_bitcoin_chainhooks_on_chain_event, handle_bitcoin_hook_action,
     BitcoinChainhookOccurrence, BitcoinTriggerChainhook,
 };
-use chainhook_sdk::chainhooks::bitcoin::BitcoinChainhookInstance;
+use chainhook_sdk::chainhooks::types::{build_http_delivery_client, HttpDeliveryConfig};
 use chainhook_sdk::indexer;
 use chainhook_sdk::indexer::bitcoin::{
     build_http_client, download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
 };
 use chainhook_sdk::indexer::fork_scratch_pad::CONFIRMED_SEGMENT_MINIMUM_LENGTH;
+use chainhook_sdk::observer::dead_letters::try_record_dead_letter;
 use chainhook_sdk::observer::{gather_proofs, EventObserverConfig};
 use chainhook_sdk::types::{
     BitcoinBlockData, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData, BlockIdentifier, Chain,
 };
-use chainhook_sdk::utils::{file_append, send_request, Context};
-use std::collections::HashMap;
+use chainhook_sdk::utils::{
+    file_append, send_request_with_verification, unix_socket_send, Context,
+};
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, RwLock};
 
-use super::common::PredicateScanResult;
+use super::common::{PredicateScanResult, ScanSummary};
 
+/// Scans the Bitcoin chainstate for a single predicate, see
+/// [scan_bitcoin_chainstate_via_rpc_using_predicates].
 pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
     predicate_spec: &BitcoinChainhookInstance,
     unfinished_scan_data: Option<ScanningData>,
     config: &Config,
+    delivery_client: &reqwest::Client,
     kill_signal: Option<Arc<RwLock<bool>>>,
     ctx: &Context,
-) -> Result<PredicateScanResult, String> {
-    let predicate_uuid = &predicate_spec.uuid;
+) -> Result<(PredicateScanResult, ScanSummary), String> {
+    let request = BitcoinScanRequest {
+        predicate_spec: predicate_spec.clone(),
+        unfinished_scan_data,
+        kill_signal,
+    };
+    let mut results = scan_bitcoin_chainstate_via_rpc_using_predicates(
+        vec![request],
+        config,
+        delivery_client,
+        ctx,
+    )
+    .await?;
+    match results.pop() {
+        Some((_, result)) => result,
+        None => Err(format!(
+            "scan of predicate {} returned no result",
+            predicate_spec.uuid
+        )),
+    }
+}
+
+/// A predicate taking part in a scan shared with other predicates, see
+/// [scan_bitcoin_chainstate_via_rpc_using_predicates].
+pub struct BitcoinScanRequest {
+    pub predicate_spec: BitcoinChainhookInstance,
+    pub unfinished_scan_data: Option<ScanningData>,
+    pub kill_signal: Option<Arc<RwLock<bool>>>,
+}
+
+struct MultiplexedScanState {
+    request: BitcoinScanRequest,
+    block_heights_to_scan: VecDeque<u64>,
+    number_of_blocks_to_scan: u64,
+    number_of_blocks_scanned: u64,
+    number_of_times_triggered: u64,
+    actions_triggered: u32,
+    err_count: u32,
+    last_block_scanned: BlockIdentifier,
+    last_scanned_block_confirmations: i32,
+    status_cache: ScanningStatusCache,
+    loop_did_trigger: bool,
+    result: Option<Result<(PredicateScanResult, ScanSummary), String>>,
+}
+
+impl MultiplexedScanState {
+    fn summary(&self) -> ScanSummary {
+        ScanSummary {
+            number_of_blocks_scanned: self.number_of_blocks_scanned,
+            number_of_times_triggered: self.number_of_times_triggered,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.result.is_none()
+    }
+}
+
+/// Scans the Bitcoin chainstate for several predicates at once: every block is only fetched once,
+/// and evaluated against each of the predicates whose range covers its height. Status updates
+/// and expirations are still tracked per predicate, and the scan result of each predicate is
+/// returned alongside its specification.
+pub async fn scan_bitcoin_chainstate_via_rpc_using_predicates(
+    requests: Vec<BitcoinScanRequest>,
+    config: &Config,
+    delivery_client: &reqwest::Client,
+    ctx: &Context,
+) -> Result<
+    Vec<(
+        BitcoinChainhookInstance,
+        Result<(PredicateScanResult, ScanSummary), String>,
+    )>,
+    String,
+> {
     let auth = Auth::UserPass(
         config.network.bitcoind_rpc_username.clone(),
         config.network.bitcoind_rpc_password.clone(),
@@ -56,83 +147,84 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
         }
     };
 
-    let block_heights_to_scan = get_block_heights_to_scan(
-        &predicate_spec.blocks,
-        &predicate_spec.start_block,
-        &predicate_spec.end_block,
-        &chain_tip,
-        &unfinished_scan_data,
-    )?;
-    let mut block_heights_to_scan = match block_heights_to_scan {
-        Some(h) => h,
-        // no blocks to scan, go straight to streaming
-        None => return Ok(PredicateScanResult::ChainTipReached),
-    };
+    let mut scans = vec![];
+    for request in requests.into_iter() {
+        let block_heights_to_scan = get_block_heights_to_scan(
+            &request.predicate_spec.blocks,
+            &request.predicate_spec.start_block,
+            &request.predicate_spec.end_block,
+            &chain_tip,
+            &request.unfinished_scan_data,
+        );
+        let (block_heights_to_scan, result) = match block_heights_to_scan {
+            Ok(Some(heights)) => (heights, None),
+            // no blocks to scan, go straight to streaming
+            Ok(None) => (
+                VecDeque::new(),
+                Some(Ok((
+                    PredicateScanResult::ChainTipReached,
+                    ScanSummary::default(),
+                ))),
+            ),
+            Err(e) => (VecDeque::new(), Some(Err(e))),
+        };
+        let (number_of_blocks_to_scan, number_of_blocks_scanned, number_of_times_triggered) =
+            match &request.unfinished_scan_data {
+                Some(scan_data) => (
+                    scan_data.number_of_blocks_to_scan,
+                    scan_data.number_of_blocks_evaluated,
+                    scan_data.number_of_times_triggered,
+                ),
+                None => (block_heights_to_scan.len() as u64, 0, 0u64),
+            };
+        scans.push(MultiplexedScanState {
+            status_cache: ScanningStatusCache::new(&request.predicate_spec.key()),
+            request,
+            block_heights_to_scan,
+            number_of_blocks_to_scan,
+            number_of_blocks_scanned,
+            number_of_times_triggered,
+            actions_triggered: 0,
+            err_count: 0,
+            last_block_scanned: BlockIdentifier::default(),
+            last_scanned_block_confirmations: 0,
+            loop_did_trigger: false,
+            result,
+        });
+    }
 
     let mut predicates_db_conn = match config.http_api {
-        PredicatesApi::On(ref api_config) => {
-            Some(open_readwrite_predicates_db_conn_or_panic(api_config, ctx))
-        }
+        PredicatesApi::On(ref api_config) => Some(open_predicate_store_or_panic(
+            api_config,
+            &Chain::Bitcoin,
+            ctx,
+        )),
         PredicatesApi::Off => None,
     };
 
     debug!(
         ctx.expect_logger(),
-        "Starting predicate evaluation on Bitcoin blocks for predicate {predicate_uuid}",
+        "Starting predicate evaluation on Bitcoin blocks for {} predicates",
+        scans.len()
     );
 
-    let mut last_block_scanned = BlockIdentifier::default();
-    let mut actions_triggered = 0;
-    let mut err_count = 0;
-
-    let event_observer_config = config.get_event_observer_config();
+    let mut event_observer_config = config.get_event_observer_config();
+    event_observer_config.http_client = Some(delivery_client.clone());
     let bitcoin_config = event_observer_config.get_bitcoin_config();
-
-    let (mut number_of_blocks_to_scan, mut number_of_blocks_scanned, mut number_of_times_triggered) = {
-        let number_of_blocks_to_scan = block_heights_to_scan.len() as u64;
-        match &unfinished_scan_data {
-            Some(scan_data) => (
-                scan_data.number_of_blocks_to_scan,
-                scan_data.number_of_blocks_evaluated,
-                scan_data.number_of_times_triggered,
-            ),
-            None => (number_of_blocks_to_scan, 0, 0u64),
-        }
-    };
-    let mut last_scanned_block_confirmations = 0;
     let http_client = build_http_client();
+    // index of the endpoint currently accepting the occurrences of each predicate with failover urls
+    let mut endpoints_health = HashMap::new();
 
-    let mut loop_did_trigger = false;
-    while let Some(current_block_height) = block_heights_to_scan.pop_front() {
-        if let Some(kill_signal) = kill_signal.clone() {
-            match kill_signal.read() {
-                Ok(kill_signal) => {
-                    // if true, we're received the kill signal, so break out of the loop
-                    if *kill_signal {
-                        return Ok(PredicateScanResult::Deregistered);
-                    }
-                }
-                Err(_) => {}
-            }
-        }
-        if let Some(ref mut predicates_db_conn) = predicates_db_conn {
-            if number_of_blocks_scanned % 100 == 0 
-                || number_of_blocks_scanned == 0
-                // if the last loop did trigger a predicate, update the status
-                || loop_did_trigger
-            {
-                set_predicate_scanning_status(
-                    &predicate_spec.key(),
-                    number_of_blocks_to_scan,
-                    number_of_blocks_scanned,
-                    number_of_times_triggered,
-                    current_block_height,
-                    predicates_db_conn,
-                    ctx,
-                );
-            }
-        }
-        loop_did_trigger = false;
+    loop {
+        // the next block to fetch is the lowest height still expected by one of the predicates
+        let Some(current_block_height) = scans
+            .iter()
+            .filter(|scan| scan.is_active())
+            .filter_map(|scan| scan.block_heights_to_scan.front().copied())
+            .min()
+        else {
+            break;
+        };
 
         if current_block_height > chain_tip {
             let prev_chain_tip = chain_tip;
@@ -148,32 +240,85 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
                 }
             };
             // if the chain hasn't progressed, break out so we can enter streaming mode
-            // and put back the block we weren't able to scan
             if current_block_height > chain_tip {
-                block_heights_to_scan.push_front(current_block_height);
                 break;
-            } else {
-                // if the chain has progressed, update our total number of blocks to scan and keep scanning
-                number_of_blocks_to_scan += chain_tip - prev_chain_tip;
+            }
+            // if the chain has progressed, update the number of blocks to scan and keep scanning
+            for scan in scans.iter_mut().filter(|scan| scan.is_active()) {
+                scan.number_of_blocks_to_scan += chain_tip - prev_chain_tip;
             }
         }
 
-        number_of_blocks_scanned += 1;
+        let mut scans_at_height = vec![];
+        for (index, scan) in scans.iter_mut().enumerate() {
+            if !scan.is_active()
+                || scan.block_heights_to_scan.front() != Some(&current_block_height)
+            {
+                continue;
+            }
+            scan.block_heights_to_scan.pop_front();
+
+            if let Some(ref kill_signal) = scan.request.kill_signal {
+                // if true, we're received the kill signal, so stop scanning for this predicate
+                if let Ok(true) = kill_signal.read().map(|signal| *signal) {
+                    scan.result = Some(Ok((PredicateScanResult::Deregistered, scan.summary())));
+                    continue;
+                }
+            }
+            if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
+                if scan.number_of_blocks_scanned % 100 == 0
+                    || scan.number_of_blocks_scanned == 0
+                    // if the last loop did trigger a predicate, update the status
+                    || scan.loop_did_trigger
+                {
+                    scan.status_cache.set_predicate_scanning_status(
+                        scan.number_of_blocks_to_scan,
+                        scan.number_of_blocks_scanned,
+                        scan.number_of_times_triggered,
+                        current_block_height,
+                        predicates_db_conn,
+                        ctx,
+                    );
+                }
+            }
+            scan.loop_did_trigger = false;
+            scan.number_of_blocks_scanned += 1;
+            scans_at_height.push(index);
+        }
+        if scans_at_height.is_empty() {
+            continue;
+        }
 
-        let block_hash = retrieve_block_hash_with_retry(
+        let block_breakdown = match retrieve_block_hash_with_retry(
             &http_client,
             &current_block_height,
             &bitcoin_config,
             ctx,
         )
-        .await?;
-        let block_breakdown =
-            download_and_parse_block_with_retry(&http_client, &block_hash, &bitcoin_config, ctx)
-                .await?;
-        last_scanned_block_confirmations = block_breakdown.confirmations;
+        .await
+        {
+            Ok(block_hash) => {
+                download_and_parse_block_with_retry(&http_client, &block_hash, &bitcoin_config, ctx)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let block_breakdown = match block_breakdown {
+            Ok(block_breakdown) => block_breakdown,
+            Err(e) => {
+                // the node is unreachable, none of the remaining predicates can make progress
+                for scan in scans.iter_mut().filter(|scan| scan.is_active()) {
+                    scan.result = Some(Err(e.clone()));
+                }
+                break;
+            }
+        };
+        let block_hash = block_breakdown.hash.clone();
+        let confirmations = block_breakdown.confirmations;
         let block = match indexer::bitcoin::standardize_bitcoin_block(
             block_breakdown,
             &event_observer_config.bitcoin_network,
+            &event_observer_config.stacks_operations_parsing,
             ctx,
         ) {
             Ok(data) => data,
@@ -185,88 +330,132 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
                 continue;
             }
         };
-        last_block_scanned = block.block_identifier.clone();
 
-        let res = match process_block_with_predicates(
-            block,
-            &vec![&predicate_spec],
-            &event_observer_config,
-            ctx,
-        )
-        .await
-        {
-            Ok(actions) => {
-                if actions > 0 {
-                    number_of_times_triggered += 1;
-                    loop_did_trigger = true
+        for index in scans_at_height.into_iter() {
+            let scan = &mut scans[index];
+            scan.last_scanned_block_confirmations = confirmations;
+            scan.last_block_scanned = block.block_identifier.clone();
+
+            let res = match process_block_with_predicates(
+                block.clone(),
+                &vec![&scan.request.predicate_spec],
+                &event_observer_config,
+                &mut endpoints_health,
+                ctx,
+            )
+            .await
+            {
+                Ok(actions) => {
+                    if actions > 0 {
+                        scan.number_of_times_triggered += 1;
+                        scan.loop_did_trigger = true;
+                        if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
+                            insert_triggered_block_heights(
+                                &scan.request.predicate_spec.key(),
+                                &[current_block_height],
+                                predicates_db_conn,
+                                ctx,
+                            );
+                        }
+                    }
+                    scan.actions_triggered += actions;
+                    Ok(())
                 }
-                actions_triggered += actions;
-                Ok(())
-            }
-            Err(e) => {
-                err_count += 1;
-                Err(e)
-            }
-        };
+                Err(e) => {
+                    scan.err_count += 1;
+                    Err(e)
+                }
+            };
 
-        if err_count >= 3 {
-            if res.is_err() {
-                return Err(format!(
-                    "Scan aborted (consecutive action errors >= 3): {}",
-                    res.unwrap_err()
-                ));
-            } else {
-                return Err(format!("Scan aborted (consecutive action errors >= 3)"));
+            if scan.err_count >= 3 {
+                scan.result = match res {
+                    Err(e) => Some(Err(format!(
+                        "Scan aborted (consecutive action errors >= 3): {}",
+                        e
+                    ))),
+                    Ok(()) => Some(Err(format!(
+                        "Scan aborted (consecutive action errors >= 3)"
+                    ))),
+                };
             }
         }
     }
 
-    info!(
-        ctx.expect_logger(),
-        "Predicate {predicate_uuid} scan completed. {number_of_blocks_scanned} blocks scanned, {actions_triggered} actions triggered."
-    );
-
-    if let Some(ref mut predicates_db_conn) = predicates_db_conn {
-        set_predicate_scanning_status(
-            &predicate_spec.key(),
-            number_of_blocks_to_scan,
-            number_of_blocks_scanned,
-            number_of_times_triggered,
-            last_block_scanned.index,
-            predicates_db_conn,
-            ctx,
-        );
-    }
-    // if an end block was provided, or a fixed number of blocks were set to be scanned,
-    // check to see if we've processed all of the blocks and can expire the predicate.
-    if (predicate_spec.blocks.is_some()
-        || (predicate_spec.end_block.is_some()
-            && predicate_spec.end_block.unwrap() == last_block_scanned.index))
-        && block_heights_to_scan.is_empty()
-    {
-        if let Some(ref mut predicates_db_conn) = predicates_db_conn {
-            set_unconfirmed_expiration_status(
-                &Chain::Bitcoin,
-                number_of_blocks_scanned,
-                last_block_scanned.index,
-                &predicate_spec.key(),
-                predicates_db_conn,
-                ctx,
-            );
-            if last_scanned_block_confirmations >= CONFIRMED_SEGMENT_MINIMUM_LENGTH {
-                set_confirmed_expiration_status(&predicate_spec.key(), predicates_db_conn, ctx);
+    let mut results = vec![];
+    for mut scan in scans.into_iter() {
+        let predicate_spec = &scan.request.predicate_spec;
+        let result = match scan.result.take() {
+            Some(result) => result,
+            None => {
+                info!(
+                    ctx.expect_logger(),
+                    "Predicate {} scan completed. {} blocks scanned, {} actions triggered.",
+                    predicate_spec.uuid,
+                    scan.number_of_blocks_scanned,
+                    scan.actions_triggered
+                );
+                if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
+                    scan.status_cache.set_predicate_scanning_status(
+                        scan.number_of_blocks_to_scan,
+                        scan.number_of_blocks_scanned,
+                        scan.number_of_times_triggered,
+                        scan.last_block_scanned.index,
+                        predicates_db_conn,
+                        ctx,
+                    );
+                }
+                // if an end block was provided, or a fixed number of blocks were set to be scanned,
+                // check to see if we've processed all of the blocks and can expire the predicate.
+                // a predicate whose grace period isn't over yet is streamed, so that the rollbacks
+                // of the blocks scanned are still delivered.
+                if (predicate_spec.blocks.is_some()
+                    || (predicate_spec.end_block.is_some()
+                        && predicate_spec.end_block.unwrap() == scan.last_block_scanned.index
+                        && chain_tip >= predicate_spec.expiration_block()))
+                    && scan.block_heights_to_scan.is_empty()
+                {
+                    if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
+                        set_unconfirmed_expiration_status(
+                            &Chain::Bitcoin,
+                            scan.number_of_blocks_scanned,
+                            scan.last_block_scanned.index,
+                            &predicate_spec.key(),
+                            predicates_db_conn,
+                            ctx,
+                        );
+                        if scan.last_scanned_block_confirmations >= CONFIRMED_SEGMENT_MINIMUM_LENGTH
+                        {
+                            set_confirmed_expiration_status(
+                                &predicate_spec.key(),
+                                config
+                                    .expected_api_config()
+                                    .expired_predicates_retention_secs,
+                                predicates_db_conn,
+                                ctx,
+                            );
+                        }
+                    }
+                    Ok((PredicateScanResult::Expired, scan.summary()))
+                } else {
+                    Ok((PredicateScanResult::ChainTipReached, scan.summary()))
+                }
             }
-        }
-        return Ok(PredicateScanResult::Expired);
+        };
+        results.push((scan.request.predicate_spec, result));
     }
-
-    return Ok(PredicateScanResult::ChainTipReached);
+    Ok(results)
 }
 
+/// Evaluates `predicates` against `block` and executes the resulting actions right away, so that
+/// a long scan delivers its occurrences block by block rather than once the whole range is scanned.
+///
+/// `endpoints_health` tracks the endpoint accepting the occurrences of each predicate with
+/// failover urls, and is meant to be kept across the blocks of a scan.
 pub async fn process_block_with_predicates(
     block: BitcoinBlockData,
     predicates: &Vec<&BitcoinChainhookInstance>,
     event_observer_config: &EventObserverConfig,
+    endpoints_health: &mut HashMap<String, usize>,
     ctx: &Context,
 ) -> Result<u32, String> {
     let chain_event =
@@ -276,24 +465,52 @@ pub async fn process_block_with_predicates(
         });
 
     let (predicates_triggered, _predicates_evaluated, _predicates_expired) =
-        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, predicates, ctx);
+        evaluate_bitcoin_chainhooks_on_chain_event(
+            &chain_event,
+            predicates,
+            event_observer_config.descriptor_default_range,
+            ctx,
+        );
 
-    execute_predicates_action(predicates_triggered, &event_observer_config, &ctx).await
+    execute_predicates_action(
+        predicates_triggered,
+        &event_observer_config,
+        endpoints_health,
+        &ctx,
+    )
+    .await
 }
 
 pub async fn execute_predicates_action<'a>(
     hits: Vec<BitcoinTriggerChainhook<'a>>,
     config: &EventObserverConfig,
+    endpoints_health: &mut HashMap<String, usize>,
     ctx: &Context,
 ) -> Result<u32, String> {
     let mut actions_triggered = 0;
     let mut proofs = HashMap::new();
+    let delivery_client = match config.http_client {
+        Some(ref client) => client.clone(),
+        None => build_http_delivery_client(&HttpDeliveryConfig::default())?,
+    };
+    // sent in batches once every occurrence of the block is built
+    #[cfg(feature = "sqs")]
+    let mut sqs_occurrences = vec![];
     for trigger in hits.into_iter() {
         if trigger.chainhook.include_proof {
             gather_proofs(&trigger, &mut proofs, &config, &ctx);
         }
         let predicate_uuid = &trigger.chainhook.uuid;
-        match handle_bitcoin_hook_action(trigger, &proofs) {
+        let predicate_key = trigger.chainhook.key();
+        let hook_action = &trigger.chainhook.action;
+        let failover_urls = hook_action.failover_urls().to_vec();
+        match handle_bitcoin_hook_action(
+            trigger,
+            &proofs,
+            config.descriptor_default_range,
+            Some(&delivery_client),
+            &config.signing_secrets,
+        ) {
             Err(e) => {
                 warn!(
                     ctx.expect_logger(),
@@ -303,17 +520,80 @@ pub async fn execute_predicates_action<'a>(
             Ok(action) => {
                 actions_triggered += 1;
                 match action {
-                    BitcoinChainhookOccurrence::Http(request, _) => {
-                        send_request(request, 10, 3, &ctx).await?
+                    BitcoinChainhookOccurrence::Http(request, data) => {
+                        let verification = hook_action.build_verification_request(
+                            &delivery_client,
+                            &config.signing_secrets,
+                            &data.intent(),
+                        );
+                        let failed_request = config
+                            .dead_letters_path
+                            .as_ref()
+                            .and_then(|_| request.try_clone());
+                        let healthy_endpoint =
+                            endpoints_health.entry(predicate_key.clone()).or_insert(0);
+                        let res = send_request_with_verification(
+                            verification,
+                            request,
+                            &failover_urls,
+                            healthy_endpoint,
+                            &config.http_retry_policy,
+                            &ctx,
+                        )
+                        .await;
+                        if let (Err(e), Some(dir), Some(failed_request)) =
+                            (&res, config.dead_letters_path.as_ref(), failed_request)
+                        {
+                            try_record_dead_letter(dir, &predicate_key, &failed_request, e, &ctx);
+                        }
+                        res?
                     }
                     BitcoinChainhookOccurrence::File(path, bytes) => {
                         file_append(path, bytes, &ctx)?
                     }
+                    BitcoinChainhookOccurrence::UnixSocket(path, bytes) => {
+                        unix_socket_send(path, bytes, 3, 100, &ctx).await?
+                    }
+                    #[cfg(feature = "websocket")]
+                    BitcoinChainhookOccurrence::WebSocket(url, bytes) => {
+                        send_websocket_message(&url, bytes, &ctx)?
+                    }
+                    #[cfg(feature = "parquet")]
+                    BitcoinChainhookOccurrence::Parquet(hook, rows) => {
+                        append_parquet_rows(&hook, &rows)?
+                    }
+                    #[cfg(feature = "kafka")]
+                    BitcoinChainhookOccurrence::Kafka(hook, key, bytes) => {
+                        produce_kafka_message(&hook, &key, &bytes).await?
+                    }
+                    #[cfg(feature = "grpc")]
+                    BitcoinChainhookOccurrence::Grpc(hook, predicate_uuid, bytes) => {
+                        send_grpc_occurrence(&hook, &predicate_uuid, bytes, &ctx).await?
+                    }
+                    #[cfg(feature = "sqs")]
+                    BitcoinChainhookOccurrence::Sqs(hook, predicate_uuid, bytes) => {
+                        sqs_occurrences.push((hook, predicate_uuid, bytes))
+                    }
+                    #[cfg(feature = "s3")]
+                    BitcoinChainhookOccurrence::S3Append(hook, key, bytes) => {
+                        append_s3_object(&hook, &key, bytes).await?
+                    }
                     BitcoinChainhookOccurrence::Data(_payload) => {}
                 };
             }
         }
     }
+    #[cfg(feature = "sqs")]
+    if let Some((predicate_uuid, e)) =
+        send_sqs_occurrences(sqs_occurrences, config.deduplication_hash)
+            .await
+            .pop()
+    {
+        return Err(format!(
+            "unable to send sqs message for predicate {}: {}",
+            predicate_uuid, e
+        ));
+    }
 
     Ok(actions_triggered)
 }
@@ -1,5 +1,16 @@
-use crate::service::ScanningData;
-use chainhook_sdk::utils::{BlockHeights, BlockHeightsError};
+use crate::config::{Config, PredicatesApi};
+use crate::scan::bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate;
+use crate::scan::stacks::{
+    consolidate_local_stacks_chainstate_using_csv, scan_stacks_chainstate_via_csv_using_predicate,
+    scan_stacks_chainstate_via_rocksdb_using_predicate,
+};
+use crate::service::{find_predicate_store, ScanningData};
+use crate::storage::open_readonly_stacks_db_conn;
+#[cfg(feature = "parquet")]
+use chainhook_sdk::chainhooks::bitcoin::parquet_export::close_parquet_files;
+use chainhook_sdk::chainhooks::stacks::StacksChainhookInstance;
+use chainhook_sdk::chainhooks::types::{ChainhookInstance, ChainhookSpecificationNetworkMap};
+use chainhook_sdk::utils::{BlockHeights, BlockHeightsError, Context};
 use std::collections::VecDeque;
 
 pub fn get_block_heights_to_scan(
@@ -66,8 +77,195 @@ pub fn get_block_heights_to_scan(
     Ok(block_heights_to_scan)
 }
 
+#[derive(Debug, PartialEq)]
 pub enum PredicateScanResult {
     ChainTipReached,
     Expired,
     Deregistered,
 }
+
+/// Counters describing the work done by a scan.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanSummary {
+    pub number_of_blocks_scanned: u64,
+    pub number_of_times_triggered: u64,
+}
+
+/// Scans the chainstate with `predicate`, delivering its occurrences, without registering it
+/// with the service nor tracking its status.
+///
+/// When provided, `start_block` and `end_block` take precedence over the range defined by the
+/// predicate.
+pub async fn scan_predicate_once(
+    predicate: ChainhookSpecificationNetworkMap,
+    start_block: Option<u64>,
+    end_block: Option<u64>,
+    config: &mut Config,
+    ctx: &Context,
+) -> Result<ScanSummary, String> {
+    predicate.validate()?;
+    config.http_api = PredicatesApi::Off;
+    let http_client = config.build_http_delivery_client()?;
+    let summary = match predicate {
+        ChainhookSpecificationNetworkMap::Bitcoin(predicate) => {
+            let mut predicate_spec =
+                match predicate.into_specification_for_network(&config.network.bitcoin_network) {
+                    Ok(predicate) => predicate,
+                    Err(e) => {
+                        return Err(format!(
+                            "Specification missing for network {:?}: {e}",
+                            config.network.bitcoin_network
+                        ));
+                    }
+                };
+            if start_block.is_some() || end_block.is_some() {
+                predicate_spec.blocks = None;
+                predicate_spec.start_block = start_block.or(predicate_spec.start_block);
+                predicate_spec.end_block = end_block.or(predicate_spec.end_block);
+                check_scan_range(predicate_spec.start_block, predicate_spec.end_block)?;
+            }
+
+            let res = scan_bitcoin_chainstate_via_rpc_using_predicate(
+                &predicate_spec,
+                None,
+                &config,
+                &http_client,
+                None,
+                &ctx,
+            )
+            .await;
+            // the scan is over: make the parquet files it wrote readable
+            #[cfg(feature = "parquet")]
+            close_parquet_files()?;
+            let (_, summary) = res?;
+            summary
+        }
+        ChainhookSpecificationNetworkMap::Stacks(predicate) => {
+            let mut predicate_spec =
+                match predicate.into_specification_for_network(&config.network.stacks_network) {
+                    Ok(predicate) => predicate,
+                    Err(e) => {
+                        return Err(format!(
+                            "Specification missing for network {:?}: {e}",
+                            config.network.stacks_network
+                        ));
+                    }
+                };
+            if start_block.is_some() || end_block.is_some() {
+                predicate_spec.blocks = None;
+                predicate_spec.start_block = start_block.or(predicate_spec.start_block);
+                predicate_spec.end_block = end_block.or(predicate_spec.end_block);
+                check_scan_range(predicate_spec.start_block, predicate_spec.end_block)?;
+            }
+
+            scan_stacks_predicate(&predicate_spec, config, &http_client, ctx).await?
+        }
+    };
+    Ok(summary)
+}
+
+/// Fails when the range to scan is inverted, which would otherwise scan no block at all.
+fn check_scan_range(start_block: Option<u64>, end_block: Option<u64>) -> Result<(), String> {
+    match (start_block, end_block) {
+        (Some(start_block), Some(end_block)) if start_block > end_block => Err(format!(
+            "unable to scan blocks {} to {}: the start block must not be greater than the end block",
+            start_block, end_block
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn scan_stacks_predicate(
+    predicate_spec: &StacksChainhookInstance,
+    config: &mut Config,
+    http_client: &reqwest::Client,
+    ctx: &Context,
+) -> Result<ScanSummary, String> {
+    match open_readonly_stacks_db_conn(&config.expected_cache_path(), &ctx) {
+        Ok(db_conn) => {
+            let _ = consolidate_local_stacks_chainstate_using_csv(config, &ctx).await;
+            let (_, summary) = scan_stacks_chainstate_via_rocksdb_using_predicate(
+                &predicate_spec,
+                None,
+                &db_conn,
+                &config,
+                http_client,
+                None,
+                &ctx,
+            )
+            .await?;
+            Ok(summary)
+        }
+        Err(e) => {
+            info!(
+                ctx.expect_logger(),
+                "Could not open db. This will greatly increase scan times. Error: {}", e
+            );
+            scan_stacks_chainstate_via_csv_using_predicate(
+                &predicate_spec,
+                config,
+                http_client,
+                &ctx,
+            )
+            .await
+        }
+    }
+}
+
+/// Delivers again the occurrences of the registered predicate `predicate_uuid`, scanning only
+/// the blocks on which it previously triggered.
+///
+/// The status of the predicate is left untouched by the replay.
+pub async fn replay_triggered_blocks(
+    predicate_uuid: &str,
+    config: &mut Config,
+    ctx: &Context,
+) -> Result<ScanSummary, String> {
+    let PredicatesApi::On(ref api_config) = config.http_api else {
+        return Err(format!(
+            "unable to replay predicate {predicate_uuid}: predicates api is disabled"
+        ));
+    };
+    let predicate_key = ChainhookInstance::either_stx_or_btc_key(predicate_uuid);
+    let Some(mut predicates_db_conn) = find_predicate_store(api_config, &predicate_key)? else {
+        return Err(format!("predicate {predicate_uuid} not found"));
+    };
+    let Some((spec, _)) = predicates_db_conn.get_entry(&predicate_key)? else {
+        return Err(format!("predicate {predicate_uuid} not found"));
+    };
+    let block_heights = predicates_db_conn.get_triggered_block_heights(&predicate_key)?;
+    if block_heights.is_empty() {
+        return Ok(ScanSummary::default());
+    }
+
+    config.http_api = PredicatesApi::Off;
+    let http_client = config.build_http_delivery_client()?;
+    let summary = match spec {
+        ChainhookInstance::Bitcoin(mut predicate_spec) => {
+            predicate_spec.blocks = Some(block_heights);
+            predicate_spec.start_block = None;
+            predicate_spec.end_block = None;
+            let res = scan_bitcoin_chainstate_via_rpc_using_predicate(
+                &predicate_spec,
+                None,
+                &config,
+                &http_client,
+                None,
+                &ctx,
+            )
+            .await;
+            // the scan is over: make the parquet files it wrote readable
+            #[cfg(feature = "parquet")]
+            close_parquet_files()?;
+            let (_, summary) = res?;
+            summary
+        }
+        ChainhookInstance::Stacks(mut predicate_spec) => {
+            predicate_spec.blocks = Some(block_heights);
+            predicate_spec.start_block = None;
+            predicate_spec.end_block = None;
+            scan_stacks_predicate(&predicate_spec, config, &http_client, ctx).await?
+        }
+    };
+    Ok(summary)
+}
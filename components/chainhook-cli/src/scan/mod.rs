@@ -1,3 +1,4 @@
+pub mod bench;
 pub mod bitcoin;
 pub mod common;
 pub mod stacks;
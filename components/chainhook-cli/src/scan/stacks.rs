@@ -1,5 +1,8 @@
 use std::{
     collections::{HashMap, VecDeque},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
     sync::{Arc, RwLock},
 };
 
@@ -8,8 +11,9 @@ use crate::{
     config::{Config, PredicatesApi},
     scan::common::get_block_heights_to_scan,
     service::{
-        open_readwrite_predicates_db_conn_or_panic, set_confirmed_expiration_status,
-        set_predicate_scanning_status, set_unconfirmed_expiration_status, ScanningData,
+        insert_triggered_block_heights, open_predicate_store_or_panic,
+        set_confirmed_expiration_status, set_unconfirmed_expiration_status, ScanningData,
+        ScanningStatusCache,
     },
     storage::{
         get_last_block_height_inserted, get_last_unconfirmed_block_height_inserted,
@@ -21,6 +25,7 @@ use chainhook_sdk::types::{BlockIdentifier, Chain};
 use chainhook_sdk::{
     chainhooks::stacks::evaluate_stacks_chainhook_on_blocks,
     indexer::{self, stacks::standardize_stacks_serialized_block_header, Indexer},
+    observer::dead_letters::try_record_dead_letter,
     utils::Context,
 };
 use chainhook_sdk::{
@@ -28,11 +33,11 @@ use chainhook_sdk::{
         handle_stacks_hook_action, StacksChainhookInstance, StacksChainhookOccurrence,
         StacksTriggerChainhook,
     },
-    utils::{file_append, send_request, AbstractStacksBlock},
+    utils::{file_append, send_request_with_verification, unix_socket_send, AbstractStacksBlock},
 };
 use rocksdb::DB;
 
-use super::common::PredicateScanResult;
+use super::common::{PredicateScanResult, ScanSummary};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum DigestingCommand {
@@ -66,11 +71,62 @@ pub enum RecordKind {
     AttachmentReceived,
 }
 
+/// Position reached in the Stacks archive by the last consolidation. Archives are append-only,
+/// so the next consolidation can resume parsing from `byte_offset` instead of the start of the file.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct StacksTsvCheckpoint {
+    pub byte_offset: u64,
+    pub last_block_height: u64,
+}
+
+const STACKS_TSV_CHECKPOINT_FILE: &str = "stacks_tsv_checkpoint.json";
+
+fn stacks_tsv_checkpoint_path(config: &Config) -> PathBuf {
+    let mut path = config.expected_cache_path();
+    path.push(STACKS_TSV_CHECKPOINT_FILE);
+    path
+}
+
+pub fn read_stacks_tsv_checkpoint(config: &Config) -> Option<StacksTsvCheckpoint> {
+    let bytes = std::fs::read(stacks_tsv_checkpoint_path(config)).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+pub fn write_stacks_tsv_checkpoint(
+    config: &Config,
+    checkpoint: &StacksTsvCheckpoint,
+) -> Result<(), String> {
+    let bytes = serde_json::to_vec(checkpoint)
+        .map_err(|e| format!("unable to serialize tsv checkpoint: {e}"))?;
+    std::fs::write(stacks_tsv_checkpoint_path(config), bytes)
+        .map_err(|e| format!("unable to write tsv checkpoint: {e}"))
+}
+
+/// Returns true if `byte_offset` lands on a row boundary of the tsv file, i.e. the file is at
+/// least that long and the byte preceding the offset is a line feed.
+fn is_valid_tsv_offset(path: &PathBuf, byte_offset: u64) -> bool {
+    if byte_offset == 0 {
+        return true;
+    }
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    match file.metadata() {
+        Ok(metadata) if metadata.len() >= byte_offset => {}
+        _ => return false,
+    }
+    let mut previous_byte = [0u8; 1];
+    file.seek(SeekFrom::Start(byte_offset - 1)).is_ok()
+        && file.read_exact(&mut previous_byte).is_ok()
+        && previous_byte[0] == b'\n'
+}
+
 pub async fn get_canonical_fork_from_tsv(
     config: &mut Config,
     start_block: Option<u64>,
+    start_offset: u64,
     ctx: &Context,
-) -> Result<VecDeque<(BlockIdentifier, BlockIdentifier, String)>, String> {
+) -> Result<(VecDeque<(BlockIdentifier, BlockIdentifier, String)>, u64), String> {
     let seed_tsv_path = config.expected_local_stacks_tsv_file()?.clone();
 
     let (record_tx, record_rx) = std::sync::mpsc::channel();
@@ -82,14 +138,17 @@ pub async fn get_canonical_fork_from_tsv(
     );
     let parsing_handle = hiro_system_kit::thread_named("Stacks chainstate CSV parsing")
         .spawn(move || {
+            let mut file = File::open(&seed_tsv_path).expect("unable to open tsv file");
+            file.seek(SeekFrom::Start(start_offset))
+                .expect("unable to seek tsv file");
             let mut reader_builder = csv::ReaderBuilder::default()
                 .has_headers(false)
                 .delimiter(b'\t')
                 .buffer_capacity(8 * (1 << 10))
-                .from_path(&seed_tsv_path)
-                .expect("unable to create csv reader");
+                .from_reader(file);
 
-            for result in reader_builder.deserialize() {
+            let mut record_iter = reader_builder.deserialize();
+            while let Some(result) = record_iter.next() {
                 let record: Record = result.unwrap();
                 match &record.kind {
                     RecordKind::StacksBlockReceived => match record_tx.send(Some(record)) {
@@ -102,6 +161,8 @@ pub async fn get_canonical_fork_from_tsv(
                 };
             }
             let _ = record_tx.send(None);
+            // position of the first row that has not been parsed yet
+            start_offset + record_iter.reader().position().byte()
         })
         .map_err(|e| format!("unable to spawn thread: {e}"))?;
 
@@ -161,13 +222,14 @@ pub async fn get_canonical_fork_from_tsv(
         }
         canonical_fork
     };
-    let _ = parsing_handle.join();
+    // if parsing was interrupted, don't move past the rows we started from
+    let end_offset = parsing_handle.join().unwrap_or(start_offset);
 
     info!(
         ctx.expect_logger(),
         "Finished parsing tsv file to determine canonical fork"
     );
-    Ok(canonical_fork)
+    Ok((canonical_fork, end_offset))
 }
 
 pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
@@ -175,17 +237,19 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
     unfinished_scan_data: Option<ScanningData>,
     stacks_db_conn: &DB,
     config: &Config,
+    http_client: &reqwest::Client,
     kill_signal: Option<Arc<RwLock<bool>>>,
     ctx: &Context,
-) -> Result<PredicateScanResult, String> {
+) -> Result<(PredicateScanResult, ScanSummary), String> {
     let predicate_uuid = &predicate_spec.uuid;
+    let mut healthy_endpoint = 0;
     let mut chain_tip = match get_last_unconfirmed_block_height_inserted(stacks_db_conn, ctx) {
         Some(chain_tip) => chain_tip,
         None => match get_last_block_height_inserted(stacks_db_conn, ctx) {
             Some(chain_tip) => chain_tip,
             None => {
                 info!(ctx.expect_logger(), "No blocks inserted in db; cannot determine Stacks chain tip. Skipping scan of predicate {}", predicate_uuid);
-                return Ok(PredicateScanResult::ChainTipReached);
+                return Ok((PredicateScanResult::ChainTipReached, ScanSummary::default()));
             }
         },
     };
@@ -205,14 +269,16 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
                 ctx.expect_logger(),
                 "Stacks chainstate scan completed. 0 blocks scanned."
             );
-            return Ok(PredicateScanResult::ChainTipReached);
+            return Ok((PredicateScanResult::ChainTipReached, ScanSummary::default()));
         }
     };
 
     let mut predicates_db_conn = match config.http_api {
-        PredicatesApi::On(ref api_config) => {
-            Some(open_readwrite_predicates_db_conn_or_panic(api_config, ctx))
-        }
+        PredicatesApi::On(ref api_config) => Some(open_predicate_store_or_panic(
+            api_config,
+            &Chain::Stacks,
+            ctx,
+        )),
         PredicatesApi::Off => None,
     };
 
@@ -236,6 +302,7 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
         }
     };
 
+    let mut status_cache = ScanningStatusCache::new(&predicate_spec.key());
     let mut loop_did_trigger = false;
     while let Some(current_block_height) = block_heights_to_scan.pop_front() {
         if let Some(kill_signal) = kill_signal.clone() {
@@ -243,20 +310,25 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
                 Ok(kill_signal) => {
                     // if true, we're received the kill signal, so break out of the loop
                     if *kill_signal {
-                        return Ok(PredicateScanResult::Deregistered);
+                        return Ok((
+                            PredicateScanResult::Deregistered,
+                            ScanSummary {
+                                number_of_blocks_scanned,
+                                number_of_times_triggered,
+                            },
+                        ));
                     }
                 }
                 Err(_) => {}
             }
         }
-        if let Some(ref mut predicates_db_conn) = predicates_db_conn {
+        if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
             if number_of_blocks_scanned % 1000 == 0
                 || number_of_blocks_scanned == 0
                 // if the last loop did trigger a predicate, update the status
                 || loop_did_trigger
             {
-                set_predicate_scanning_status(
-                    &predicate_spec.key(),
+                status_cache.set_predicate_scanning_status(
                     number_of_blocks_to_scan,
                     number_of_blocks_scanned,
                     number_of_times_triggered,
@@ -278,7 +350,13 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
                     Some(chain_tip) => chain_tip,
                     None => {
                         warn!(ctx.expect_logger(), "No blocks inserted in db; cannot determine Stacks chain tip. Skipping scan of predicate {}", predicate_uuid);
-                        return Ok(PredicateScanResult::ChainTipReached);
+                        return Ok((
+                            PredicateScanResult::ChainTipReached,
+                            ScanSummary {
+                                number_of_blocks_scanned,
+                                number_of_times_triggered,
+                            },
+                        ));
                     }
                 },
             };
@@ -336,7 +414,13 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
             apply: hits_per_blocks,
             rollback: vec![],
         };
-        let res = match handle_stacks_hook_action(trigger, &proofs, &ctx) {
+        let res = match handle_stacks_hook_action(
+            trigger,
+            &proofs,
+            Some(http_client),
+            &config.predicates.http_delivery.signing_secrets,
+            &ctx,
+        ) {
             Err(e) => {
                 warn!(
                     ctx.expect_logger(),
@@ -347,11 +431,53 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
             Ok(action) => {
                 number_of_times_triggered += 1;
                 loop_did_trigger = true;
+                if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
+                    insert_triggered_block_heights(
+                        &predicate_spec.key(),
+                        &[current_block_height],
+                        predicates_db_conn,
+                        ctx,
+                    );
+                }
                 let res = match action {
-                    StacksChainhookOccurrence::Http(request, _) => {
-                        send_request(request, 3, 1, &ctx).await
+                    StacksChainhookOccurrence::Http(request, data) => {
+                        let failed_request = config
+                            .predicates
+                            .dead_letters_path
+                            .as_ref()
+                            .and_then(|_| request.try_clone());
+                        let res = send_request_with_verification(
+                            predicate_spec.action.build_verification_request(
+                                http_client,
+                                &config.predicates.http_delivery.signing_secrets,
+                                &data.intent(),
+                            ),
+                            request,
+                            predicate_spec.action.failover_urls(),
+                            &mut healthy_endpoint,
+                            &config.predicates.http_delivery.retry_policy,
+                            &ctx,
+                        )
+                        .await;
+                        if let (Err(e), Some(dir), Some(failed_request)) = (
+                            &res,
+                            config.predicates.dead_letters_path.as_ref(),
+                            failed_request,
+                        ) {
+                            try_record_dead_letter(
+                                dir,
+                                &predicate_spec.key(),
+                                &failed_request,
+                                e,
+                                &ctx,
+                            );
+                        }
+                        res
                     }
                     StacksChainhookOccurrence::File(path, bytes) => file_append(path, bytes, &ctx),
+                    StacksChainhookOccurrence::UnixSocket(path, bytes) => {
+                        unix_socket_send(path, bytes, 3, 100, &ctx).await
+                    }
                     StacksChainhookOccurrence::Data(_payload) => Ok(()),
                 };
                 match res {
@@ -383,9 +509,8 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
         "Predicate {predicate_uuid} scan completed. {number_of_blocks_scanned} blocks scanned, {number_of_times_triggered} blocks triggering predicate.",
     );
 
-    if let Some(ref mut predicates_db_conn) = predicates_db_conn {
-        set_predicate_scanning_status(
-            &predicate_spec.key(),
+    if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
+        status_cache.set_predicate_scanning_status(
             number_of_blocks_to_scan,
             number_of_blocks_scanned,
             number_of_times_triggered,
@@ -395,6 +520,10 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
         );
     }
 
+    let summary = ScanSummary {
+        number_of_blocks_scanned,
+        number_of_times_triggered,
+    };
     // if an end block was provided, or a fixed number of blocks were set to be scanned,
     // check to see if we've processed all of the blocks and can expire the predicate.
     if (predicate_spec.blocks.is_some()
@@ -402,7 +531,7 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
             && predicate_spec.end_block.unwrap() == last_block_scanned.index))
         && block_heights_to_scan.is_empty()
     {
-        if let Some(ref mut predicates_db_conn) = predicates_db_conn {
+        if let Some(predicates_db_conn) = predicates_db_conn.as_deref_mut() {
             let is_confirmed = match get_stacks_block_at_block_height(
                 last_block_scanned.index,
                 true,
@@ -431,20 +560,28 @@ pub async fn scan_stacks_chainstate_via_rocksdb_using_predicate(
                 ctx,
             );
             if is_confirmed {
-                set_confirmed_expiration_status(&predicate_spec.key(), predicates_db_conn, ctx);
+                set_confirmed_expiration_status(
+                    &predicate_spec.key(),
+                    config
+                        .expected_api_config()
+                        .expired_predicates_retention_secs,
+                    predicates_db_conn,
+                    ctx,
+                );
             }
         }
-        return Ok(PredicateScanResult::Expired);
+        return Ok((PredicateScanResult::Expired, summary));
     }
 
-    Ok(PredicateScanResult::ChainTipReached)
+    Ok((PredicateScanResult::ChainTipReached, summary))
 }
 
 pub async fn scan_stacks_chainstate_via_csv_using_predicate(
     predicate_spec: &StacksChainhookInstance,
     config: &mut Config,
+    http_client: &reqwest::Client,
     ctx: &Context,
-) -> Result<BlockIdentifier, String> {
+) -> Result<ScanSummary, String> {
     let start_block = match predicate_spec.start_block {
         Some(start_block) => start_block,
         None => 0,
@@ -459,8 +596,9 @@ pub async fn scan_stacks_chainstate_via_csv_using_predicate(
     }
 
     let _ = download_stacks_dataset_if_required(config, ctx).await?;
+    let mut healthy_endpoint = 0;
 
-    let mut canonical_fork = get_canonical_fork_from_tsv(config, None, ctx).await?;
+    let (mut canonical_fork, _) = get_canonical_fork_from_tsv(config, None, 0, ctx).await?;
 
     let mut indexer = Indexer::new(config.network.clone());
 
@@ -472,7 +610,6 @@ pub async fn scan_stacks_chainstate_via_csv_using_predicate(
         ctx.expect_logger(),
         "Starting predicate evaluation on Stacks blocks"
     );
-    let mut last_block_scanned = BlockIdentifier::default();
     let mut err_count = 0;
     for (block_identifier, _parent_block_identifier, blob) in canonical_fork.drain(..) {
         if block_identifier.index < start_block {
@@ -484,7 +621,12 @@ pub async fn scan_stacks_chainstate_via_csv_using_predicate(
             }
         }
 
-        last_block_scanned = block_identifier;
+        if let Some(ref blocks) = predicate_spec.blocks {
+            if !blocks.contains(&block_identifier.index) {
+                continue;
+            }
+        }
+
         blocks_scanned += 1;
         let block_data = match indexer::stacks::standardize_stacks_serialized_block(
             &indexer.config,
@@ -512,17 +654,57 @@ pub async fn scan_stacks_chainstate_via_csv_using_predicate(
             apply: hits_per_blocks,
             rollback: vec![],
         };
-        match handle_stacks_hook_action(trigger, &proofs, &ctx) {
+        match handle_stacks_hook_action(
+            trigger,
+            &proofs,
+            Some(http_client),
+            &config.predicates.http_delivery.signing_secrets,
+            &ctx,
+        ) {
             Err(e) => {
                 error!(ctx.expect_logger(), "unable to handle action {}", e);
             }
             Ok(action) => {
                 occurrences_found += 1;
                 let res = match action {
-                    StacksChainhookOccurrence::Http(request, _) => {
-                        send_request(request, 10, 3, &ctx).await
+                    StacksChainhookOccurrence::Http(request, data) => {
+                        let failed_request = config
+                            .predicates
+                            .dead_letters_path
+                            .as_ref()
+                            .and_then(|_| request.try_clone());
+                        let res = send_request_with_verification(
+                            predicate_spec.action.build_verification_request(
+                                http_client,
+                                &config.predicates.http_delivery.signing_secrets,
+                                &data.intent(),
+                            ),
+                            request,
+                            predicate_spec.action.failover_urls(),
+                            &mut healthy_endpoint,
+                            &config.predicates.http_delivery.retry_policy,
+                            &ctx,
+                        )
+                        .await;
+                        if let (Err(e), Some(dir), Some(failed_request)) = (
+                            &res,
+                            config.predicates.dead_letters_path.as_ref(),
+                            failed_request,
+                        ) {
+                            try_record_dead_letter(
+                                dir,
+                                &predicate_spec.key(),
+                                &failed_request,
+                                e,
+                                &ctx,
+                            );
+                        }
+                        res
                     }
                     StacksChainhookOccurrence::File(path, bytes) => file_append(path, bytes, &ctx),
+                    StacksChainhookOccurrence::UnixSocket(path, bytes) => {
+                        unix_socket_send(path, bytes, 3, 100, &ctx).await
+                    }
                     StacksChainhookOccurrence::Data(_payload) => unreachable!(),
                 };
                 if res.is_err() {
@@ -542,13 +724,16 @@ pub async fn scan_stacks_chainstate_via_csv_using_predicate(
         "{blocks_scanned} blocks scanned, {occurrences_found} occurrences found"
     );
 
-    Ok(last_block_scanned)
+    Ok(ScanSummary {
+        number_of_blocks_scanned: blocks_scanned,
+        number_of_times_triggered: occurrences_found,
+    })
 }
 
 pub async fn consolidate_local_stacks_chainstate_using_csv(
     config: &mut Config,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<u64, String> {
     info!(
         ctx.expect_logger(),
         "Building local chainstate from Stacks archive file"
@@ -560,7 +745,26 @@ pub async fn consolidate_local_stacks_chainstate_using_csv(
         let stacks_db =
             open_readonly_stacks_db_conn_with_retry(&config.expected_cache_path(), 3, ctx)?;
         let confirmed_tip = get_last_block_height_inserted(&stacks_db, &ctx);
-        let mut canonical_fork = get_canonical_fork_from_tsv(config, confirmed_tip, ctx).await?;
+        let seed_tsv_path = config.expected_local_stacks_tsv_file()?.clone();
+        // only resume from the checkpoint if the rows it covers made it into the db
+        // and the archive still extends past it
+        let start_offset = match read_stacks_tsv_checkpoint(config) {
+            Some(checkpoint)
+                if confirmed_tip.unwrap_or(0) >= checkpoint.last_block_height
+                    && is_valid_tsv_offset(&seed_tsv_path, checkpoint.byte_offset) =>
+            {
+                info!(
+                    ctx.expect_logger(),
+                    "Resuming Stacks archive consolidation from block #{} (byte offset {})",
+                    checkpoint.last_block_height,
+                    checkpoint.byte_offset
+                );
+                checkpoint.byte_offset
+            }
+            _ => 0,
+        };
+        let (mut canonical_fork, end_offset) =
+            get_canonical_fork_from_tsv(config, confirmed_tip, start_offset, ctx).await?;
 
         let mut indexer = Indexer::new(config.network.clone());
         let mut blocks_inserted = 0;
@@ -611,11 +815,21 @@ pub async fn consolidate_local_stacks_chainstate_using_csv(
             ctx.expect_logger(),
             "{blocks_read} Stacks blocks read, {blocks_inserted} inserted"
         );
+
+        let last_block_height = get_last_block_height_inserted(&stacks_db_rw, ctx).unwrap_or(0);
+        write_stacks_tsv_checkpoint(
+            config,
+            &StacksTsvCheckpoint {
+                byte_offset: end_offset,
+                last_block_height,
+            },
+        )?;
+        Ok(blocks_read)
     } else {
         info!(
             ctx.expect_logger(),
             "Skipping database consolidation - no new archive found since last consolidation."
         );
+        Ok(0)
     }
-    Ok(())
 }
@@ -1,11 +1,33 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::str::FromStr;
 
-use chainhook_sdk::utils::MAX_BLOCK_HEIGHTS_ENTRIES;
+use chainhook_sdk::bitcoin::Address;
+use chainhook_sdk::chainhooks::bitcoin::BitcoinChainhookSpecificationNetworkMap;
+use chainhook_sdk::chainhooks::types::ChainhookSpecificationNetworkMap;
+use chainhook_sdk::types::bitcoin::TxOut;
+use chainhook_sdk::types::{
+    BitcoinBlockData, BitcoinBlockMetadata, BitcoinNetwork, BitcoinTransactionData,
+    BitcoinTransactionMetadata, BlockIdentifier, TransactionIdentifier,
+};
+use chainhook_sdk::utils::{Context, MAX_BLOCK_HEIGHTS_ENTRIES};
 use test_case::test_case;
 
+use crate::config::{Config, EventSourceConfig, PathConfig};
+use crate::scan::bench::{bench_bitcoin_predicate, load_bitcoin_blocks_from_dir};
+use crate::scan::bitcoin::process_block_with_predicates;
+use crate::scan::stacks::{
+    consolidate_local_stacks_chainstate_using_csv, read_stacks_tsv_checkpoint,
+};
+use crate::service::tests::helpers::build_predicates::{
+    build_bitcoin_payload, build_stacks_payload,
+};
+use crate::service::tests::helpers::mock_stacks_node::{
+    append_stacks_blocks_to_tsv, create_tmp_working_dir, write_stacks_blocks_to_tsv,
+};
 use crate::service::ScanningData;
 
-use super::common::get_block_heights_to_scan;
+use super::common::{get_block_heights_to_scan, scan_predicate_once, ScanSummary};
 
 fn expect_exceeded_max_entries_error(
     (result, _expected_entries): (Result<Option<VecDeque<u64>>, String>, Option<VecDeque<u64>>),
@@ -112,3 +134,293 @@ fn test_get_block_heights_to_scan(
         expected,
     )
 }
+
+#[tokio::test]
+async fn it_scans_a_fixed_block_range_once() {
+    let (working_dir, tsv_dir) = create_tmp_working_dir().unwrap_or_else(|e| {
+        panic!("test failed with error: {e}");
+    });
+    write_stacks_blocks_to_tsv(100, &tsv_dir).unwrap_or_else(|e| {
+        std::fs::remove_dir_all(&working_dir).unwrap();
+        panic!("test failed with error: {e}");
+    });
+
+    let mut config = Config::devnet_default();
+    config.storage.working_dir = working_dir.clone();
+    config.event_sources = vec![EventSourceConfig::StacksTsvPath(PathConfig {
+        file_path: PathBuf::from(tsv_dir),
+    })];
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+    consolidate_local_stacks_chainstate_using_csv(&mut config, &ctx)
+        .await
+        .unwrap_or_else(|e| {
+            std::fs::remove_dir_all(&working_dir).unwrap();
+            panic!("test failed with error: {e}");
+        });
+
+    let predicate: ChainhookSpecificationNetworkMap = serde_json::from_value(build_stacks_payload(
+        Some("devnet"),
+        Some(json!({"scope":"block_height", "higher_than": 0})),
+        None,
+        None,
+        None,
+    ))
+    .unwrap();
+    let summary = scan_predicate_once(predicate, Some(10), Some(19), &mut config, &ctx).await;
+    std::fs::remove_dir_all(&working_dir).unwrap();
+
+    assert_eq!(
+        summary,
+        Ok(ScanSummary {
+            number_of_blocks_scanned: 10,
+            number_of_times_triggered: 10,
+        })
+    );
+}
+
+#[tokio::test]
+async fn it_rejects_inverted_scan_ranges() {
+    let mut config = Config::devnet_default();
+    let ctx = Context::empty();
+    let predicate: ChainhookSpecificationNetworkMap = serde_json::from_value(build_stacks_payload(
+        Some("devnet"),
+        Some(json!({"scope":"block_height", "higher_than": 0})),
+        None,
+        None,
+        None,
+    ))
+    .unwrap();
+    let summary = scan_predicate_once(predicate, Some(19), Some(10), &mut config, &ctx).await;
+    assert!(summary.is_err());
+}
+
+#[tokio::test]
+async fn it_resumes_stacks_consolidation_from_checkpoint() {
+    let (working_dir, tsv_dir) = create_tmp_working_dir().unwrap_or_else(|e| {
+        panic!("test failed with error: {e}");
+    });
+    write_stacks_blocks_to_tsv(100, &tsv_dir).unwrap_or_else(|e| {
+        std::fs::remove_dir_all(&working_dir).unwrap();
+        panic!("test failed with error: {e}");
+    });
+
+    let mut config = Config::devnet_default();
+    config.storage.working_dir = working_dir.clone();
+    config.event_sources = vec![EventSourceConfig::StacksTsvPath(PathConfig {
+        file_path: PathBuf::from(&tsv_dir),
+    })];
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+    let first_pass = consolidate_local_stacks_chainstate_using_csv(&mut config, &ctx).await;
+    let checkpoint = read_stacks_tsv_checkpoint(&config);
+
+    append_stacks_blocks_to_tsv(101, 20, &tsv_dir).unwrap_or_else(|e| {
+        std::fs::remove_dir_all(&working_dir).unwrap();
+        panic!("test failed with error: {e}");
+    });
+    let second_pass = consolidate_local_stacks_chainstate_using_csv(&mut config, &ctx).await;
+    let tsv_len = std::fs::metadata(&tsv_dir).unwrap().len();
+    let second_checkpoint = read_stacks_tsv_checkpoint(&config);
+    std::fs::remove_dir_all(&working_dir).unwrap();
+
+    assert_eq!(first_pass, Ok(100));
+    assert_eq!(checkpoint.map(|c| c.last_block_height), Some(100));
+    assert_eq!(second_pass, Ok(20));
+    let second_checkpoint = second_checkpoint.unwrap();
+    assert_eq!(second_checkpoint.last_block_height, 120);
+    assert_eq!(second_checkpoint.byte_offset, tsv_len);
+}
+
+fn build_block_paying_to(height: u64, script_pubkey: &str) -> BitcoinBlockData {
+    BitcoinBlockData {
+        block_identifier: BlockIdentifier {
+            index: height,
+            hash: format!("0x{:064x}", height),
+        },
+        parent_block_identifier: BlockIdentifier {
+            index: height - 1,
+            hash: format!("0x{:064x}", height - 1),
+        },
+        timestamp: 0,
+        transactions: vec![BitcoinTransactionData {
+            transaction_identifier: TransactionIdentifier {
+                hash: format!("0x{:064x}", height),
+            },
+            operations: vec![],
+            metadata: BitcoinTransactionMetadata {
+                inputs: vec![],
+                outputs: vec![TxOut {
+                    value: 1000,
+                    script_pubkey: script_pubkey.to_string(),
+                }],
+                stacks_operations: vec![],
+                ordinal_operations: vec![],
+                brc20_operation: None,
+                proof: None,
+                fee: 0,
+                index: 0,
+                weight: 0,
+                vsize: 0,
+                version: 2,
+            },
+        }],
+        metadata: BitcoinBlockMetadata {
+            network: BitcoinNetwork::Testnet,
+        },
+    }
+}
+
+#[tokio::test]
+async fn it_delivers_descriptor_matches_block_by_block() {
+    let (working_dir, _) = create_tmp_working_dir().unwrap_or_else(|e| {
+        panic!("test failed with error: {e}");
+    });
+    let occurrences_path = format!("{working_dir}/occurrences.json");
+    let predicate: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(build_bitcoin_payload(
+            Some("testnet"),
+            Some(json!({"scope": "outputs", "descriptor": {
+                "expression": "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)"
+            }})),
+            Some(json!({"file_append": {"path": occurrences_path}})),
+            None,
+            None,
+        ))
+        .unwrap();
+    let predicate = predicate
+        .into_specification_for_network(&BitcoinNetwork::Testnet)
+        .unwrap();
+    let event_observer_config = Config::devnet_default().get_event_observer_config();
+    let ctx = Context::empty();
+
+    let script_pubkey = Address::from_str("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let script_pubkey = format!("0x{}", hex::encode(script_pubkey.as_bytes()));
+
+    // each block's matches must be delivered as soon as the block is evaluated, and only once
+    let mut delivered_occurrences = vec![];
+    for height in 1..=3 {
+        let block = build_block_paying_to(height, &script_pubkey);
+        process_block_with_predicates(
+            block,
+            &vec![&predicate],
+            &event_observer_config,
+            &mut HashMap::new(),
+            &ctx,
+        )
+        .await
+        .unwrap();
+        let occurrences = std::fs::read_to_string(&occurrences_path).unwrap();
+        delivered_occurrences.push(occurrences.lines().count());
+    }
+    std::fs::remove_dir_all(&working_dir).unwrap();
+
+    assert_eq!(delivered_occurrences, vec![1, 2, 3]);
+}
+
+#[test]
+fn it_reports_predicate_evaluation_throughput() {
+    let (working_dir, _) = create_tmp_working_dir().unwrap_or_else(|e| {
+        panic!("test failed with error: {e}");
+    });
+    let predicate: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(build_bitcoin_payload(
+            Some("testnet"),
+            Some(json!({"scope": "outputs", "descriptor": {
+                "expression": "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)"
+            }})),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+    let predicate = predicate
+        .into_specification_for_network(&BitcoinNetwork::Testnet)
+        .unwrap();
+
+    let script_pubkey = Address::from_str("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let script_pubkey = format!("0x{}", hex::encode(script_pubkey.as_bytes()));
+    for height in 1..=3 {
+        let block = match height {
+            2 => build_block_paying_to(height, "0x00"),
+            _ => build_block_paying_to(height, &script_pubkey),
+        };
+        std::fs::write(
+            format!("{working_dir}/{height}.json"),
+            serde_json::to_vec(&block).unwrap(),
+        )
+        .unwrap();
+    }
+    let blocks = load_bitcoin_blocks_from_dir(&PathBuf::from(&working_dir));
+    std::fs::remove_dir_all(&working_dir).unwrap();
+
+    let report = bench_bitcoin_predicate(&predicate, &blocks.unwrap(), None, &Context::empty());
+    assert_eq!(report.predicate_arm, "outputs.descriptor");
+    assert_eq!(report.blocks_evaluated, 3);
+    assert_eq!(report.transactions_evaluated, 3);
+    assert_eq!(report.transactions_matched, 2);
+    assert!(report.evaluations_per_sec() > 0.0);
+}
+
+#[test]
+fn it_benches_predicates_depending_on_the_block() {
+    let predicate: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(build_bitcoin_payload(
+            Some("testnet"),
+            Some(json!({"scope": "fee_rate_percentile", "min": 91, "max": 100})),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+    let predicate = predicate
+        .into_specification_for_network(&BitcoinNetwork::Testnet)
+        .unwrap();
+    let mut block = build_block_paying_to(1, "0x00");
+    for (index, fee) in [(1, 1_000), (2, 100)] {
+        let mut tx = block.transactions[0].clone();
+        tx.metadata.index = index;
+        tx.metadata.fee = fee;
+        tx.metadata.vsize = 100;
+        block.transactions.push(tx);
+    }
+
+    let report = bench_bitcoin_predicate(&predicate, &[block], None, &Context::empty());
+    assert_eq!(report.transactions_evaluated, 3);
+    assert_eq!(report.transactions_matched, 1);
+}
+
+#[test]
+fn it_benches_predicates_depending_on_the_network() {
+    let predicate: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(build_bitcoin_payload(
+            Some("testnet"),
+            Some(json!({"scope": "stacks_protocol", "operation": "opcode", "opcode": 122})),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+    let predicate = predicate
+        .into_specification_for_network(&BitcoinNetwork::Testnet)
+        .unwrap();
+    // OP_RETURN carrying the testnet magic bytes `T2`, followed by the opcode `z`
+    let blocks = [
+        build_block_paying_to(1, "0x6a0354327a"),
+        build_block_paying_to(2, "0x6a0358327a"),
+    ];
+
+    let report = bench_bitcoin_predicate(&predicate, &blocks, None, &Context::empty());
+    assert_eq!(report.transactions_evaluated, 2);
+    assert_eq!(report.transactions_matched, 1);
+}
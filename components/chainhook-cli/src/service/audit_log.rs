@@ -0,0 +1,211 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(test)]
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chainhook_sdk::chainhooks::types::ChainhookInstance;
+use chainhook_sdk::observer::{
+    ObserverEvent, PredicateDeregisteredEvent, PredicateInterruptedData,
+};
+use chainhook_sdk::types::Chain;
+use chainhook_sdk::utils::Context;
+use hiro_system_kit::slog;
+
+use crate::config::AuditLogConfig;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    Registered,
+    Enabled,
+    ActionUpdated,
+    Reloaded,
+    Deregistered,
+    Interrupted,
+    Expired,
+}
+
+/// The component of chainhook that performed a lifecycle transition. This is not the identity of
+/// the user who requested it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditSource {
+    /// Transitions handled by the event observer, i.e. requested through the predicate
+    /// registration API or resulting from the evaluation of the predicate.
+    Observer,
+    /// Transitions decided by the service itself, such as the expiration of a predicate.
+    Service,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    /// Unix timestamp, in milliseconds.
+    pub timestamp: u64,
+    pub event: AuditEventType,
+    pub uuid: String,
+    pub chain: Option<String>,
+    pub source: AuditSource,
+}
+
+fn chain_name(chain: &Chain) -> String {
+    match chain {
+        Chain::Bitcoin => "bitcoin".to_string(),
+        Chain::Stacks => "stacks".to_string(),
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl AuditLogEntry {
+    pub fn new(
+        event: AuditEventType,
+        uuid: &str,
+        chain: Option<&Chain>,
+        source: AuditSource,
+    ) -> AuditLogEntry {
+        AuditLogEntry {
+            timestamp: now_millis(),
+            event,
+            uuid: uuid.to_string(),
+            chain: chain.map(chain_name),
+            source,
+        }
+    }
+
+    /// Returns the entry recording `event`, or `None` if `event` is not a lifecycle transition.
+    pub fn from_observer_event(event: &ObserverEvent) -> Option<AuditLogEntry> {
+        let from_spec = |event_type, spec: &ChainhookInstance| {
+            AuditLogEntry::new(
+                event_type,
+                spec.uuid(),
+                Some(&spec.chain()),
+                AuditSource::Observer,
+            )
+        };
+        let entry = match event {
+            ObserverEvent::PredicateRegistered(spec) => from_spec(AuditEventType::Registered, spec),
+            ObserverEvent::PredicateEnabled(spec) => from_spec(AuditEventType::Enabled, spec),
+            ObserverEvent::PredicateActionUpdated(spec) => {
+                from_spec(AuditEventType::ActionUpdated, spec)
+            }
+            ObserverEvent::PredicateReloaded(spec) => from_spec(AuditEventType::Reloaded, spec),
+            ObserverEvent::PredicateDeregistered(PredicateDeregisteredEvent {
+                predicate_uuid,
+                chain,
+            }) => AuditLogEntry::new(
+                AuditEventType::Deregistered,
+                predicate_uuid,
+                Some(chain),
+                AuditSource::Observer,
+            ),
+            ObserverEvent::PredicateInterrupted(PredicateInterruptedData {
+                predicate_key, ..
+            }) => AuditLogEntry::new(
+                AuditEventType::Interrupted,
+                predicate_key
+                    .strip_prefix("predicate:")
+                    .unwrap_or(predicate_key),
+                None,
+                AuditSource::Observer,
+            ),
+            _ => return None,
+        };
+        Some(entry)
+    }
+}
+
+pub fn append_audit_log_entry(
+    config: &AuditLogConfig,
+    entry: &AuditLogEntry,
+) -> Result<(), String> {
+    let serialized = serde_json::to_string(entry)
+        .map_err(|e| format!("unable to serialize audit log entry: {}", e.to_string()))?;
+    match config {
+        AuditLogConfig::File(path_config) => {
+            let path = &path_config.file_path;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| {
+                    format!("unable to create directory {}: {}", parent.display(), e)
+                })?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("unable to open file {}: {}", path.display(), e))?;
+            writeln!(file, "{}", serialized)
+                .map_err(|e| format!("unable to write file {}: {}", path.display(), e))
+        }
+        AuditLogConfig::RedisStream {
+            redis_uri,
+            stream_key,
+        } => {
+            let client = redis::Client::open(redis_uri.clone())
+                .map_err(|e| format!("unable to connect to redis: {}", e.to_string()))?;
+            let mut conn = client
+                .get_connection()
+                .map_err(|e| format!("unable to connect to redis: {}", e.to_string()))?;
+            redis::cmd("XADD")
+                .arg(stream_key)
+                .arg("*")
+                .arg("entry")
+                .arg(serialized)
+                .query::<String>(&mut conn)
+                .map(|_| ())
+                .map_err(|e| format!("unable to append to stream {}: {}", stream_key, e))
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn read_audit_log_file(path: &PathBuf) -> Result<Vec<AuditLogEntry>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("unable to read file {}: {}", path.display(), e))?;
+    content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("unable to parse audit log entry: {}", e.to_string()))
+        })
+        .collect()
+}
+
+/// Appends `entry` to the configured audit log, if any. Failures are logged rather than
+/// interrupting the service.
+pub fn record_audit_log_entry(
+    config: &Option<AuditLogConfig>,
+    entry: AuditLogEntry,
+    ctx: &Context,
+) {
+    let Some(config) = config else {
+        return;
+    };
+    if let Err(e) = append_audit_log_entry(config, &entry) {
+        ctx.try_log(|logger| {
+            slog::warn!(
+                logger,
+                "Unable to record {:?} event for predicate {} in audit log: {}",
+                entry.event,
+                entry.uuid,
+                e
+            )
+        });
+    }
+}
+
+pub fn record_observer_event(
+    config: &Option<AuditLogConfig>,
+    event: &ObserverEvent,
+    ctx: &Context,
+) {
+    if let Some(entry) = AuditLogEntry::from_observer_event(event) {
+        record_audit_log_entry(config, entry, ctx);
+    }
+}
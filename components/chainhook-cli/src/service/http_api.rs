@@ -1,32 +1,47 @@
 use std::{
-    collections::HashMap,
     net::{IpAddr, Ipv4Addr},
-    sync::{mpsc::Sender, Arc, Mutex},
+    sync::{mpsc::Sender, Arc, Mutex, RwLock},
 };
 
 use chainhook_sdk::{
-    chainhooks::types::{ChainhookInstance, ChainhookSpecificationNetworkMap},
-    observer::ObserverCommand,
+    bitcoincore_rpc::{Auth, Client, RpcApi},
+    chainhooks::types::{ChainhookInstance, ChainhookSpecificationNetworkMap, HookAction},
+    observer::{HookExpirationData, ObserverCommand, PredicateActionUpdateData},
     utils::Context,
 };
 use hiro_system_kit::slog;
-use redis::{Commands, Connection};
+use rocket::data::{Limits, ToByteUnit};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::{json, Json, Value as JsonValue};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
 use rocket::State;
 use rocket::{
     config::{self, Config, LogLevel},
     Shutdown,
 };
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
 use rocket_okapi::{okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec};
 use std::error::Error;
 
-use crate::config::PredicatesApiConfig;
+use crate::config::{IndexerConfig, PredicatesApiConfig};
 
-use super::{open_readwrite_predicates_db_conn, PredicateStatus};
+use super::{
+    find_predicate_store, open_predicate_stores,
+    predicate_store::PredicateStore,
+    runloops::{BitcoinScanOp, StacksScanOp},
+    set_expired_status, subscribe_to_predicate_status_updates, ChainTips, PredicateStatus,
+};
 
 pub async fn start_predicate_api_server(
     api_config: PredicatesApiConfig,
+    network: Option<IndexerConfig>,
+    chain_tips: Arc<RwLock<ChainTips>>,
     observer_commands_tx: Sender<ObserverCommand>,
+    bitcoin_scan_op_tx: crossbeam_channel::Sender<BitcoinScanOp>,
+    stacks_scan_op_tx: crossbeam_channel::Sender<StacksScanOp>,
     ctx: Context,
 ) -> Result<Shutdown, Box<dyn Error + Send + Sync>> {
     let log_level = LogLevel::Off;
@@ -45,6 +60,7 @@ pub async fn start_predicate_api_server(
         log_level,
         cli_colors: false,
         shutdown: shutdown_config,
+        limits: Limits::default().limit("json", api_config.get_max_body_size().bytes()),
         ..Config::default()
     };
 
@@ -57,6 +73,10 @@ pub async fn start_predicate_api_server(
     let ignite = rocket::custom(control_config)
         .manage(background_job_tx_mutex)
         .manage(api_config)
+        .manage(network)
+        .manage(chain_tips)
+        .manage(bitcoin_scan_op_tx)
+        .manage(stacks_scan_op_tx)
         .manage(ctx_cloned)
         .mount("/", routes)
         .ignite()
@@ -83,26 +103,39 @@ fn handle_ping(ctx: &State<Context>) -> Json<JsonValue> {
 #[openapi(tag = "Managing Predicates")]
 #[get("/v1/chainhooks", format = "application/json")]
 fn handle_get_predicates(
+    token: BearerToken,
     api_config: &State<PredicatesApiConfig>,
+    chain_tips: &State<Arc<RwLock<ChainTips>>>,
     ctx: &State<Context>,
 ) -> Json<JsonValue> {
     ctx.try_log(|logger| slog::info!(logger, "Handling HTTP GET /v1/chainhooks"));
-    match open_readwrite_predicates_db_conn(api_config) {
-        Ok(mut predicates_db_conn) => {
-            let predicates = match get_entries_from_predicates_db(&mut predicates_db_conn, &ctx) {
-                Ok(predicates) => predicates,
-                Err(e) => {
-                    ctx.try_log(|logger| slog::warn!(logger, "unable to retrieve predicates: {e}"));
-                    return Json(json!({
-                        "status": 500,
-                        "message": "unable to retrieve predicates",
-                    }));
-                }
-            };
+    let owner = match authorize_owner(&token, api_config) {
+        Ok(owner) => owner,
+        Err(response) => return response,
+    };
+    match open_predicate_stores(api_config) {
+        Ok(predicates_db_conns) => {
+            let mut predicates = vec![];
+            for mut predicates_db_conn in predicates_db_conns.into_iter() {
+                match get_entries_from_predicates_db(predicates_db_conn.as_mut(), &ctx) {
+                    Ok(mut entries) => predicates.append(&mut entries),
+                    Err(e) => {
+                        ctx.try_log(|logger| {
+                            slog::warn!(logger, "unable to retrieve predicates: {e}")
+                        });
+                        return Json(json!({
+                            "status": 500,
+                            "message": "unable to retrieve predicates",
+                        }));
+                    }
+                };
+            }
 
+            let chain_tips = get_chain_tips(chain_tips);
             let serialized_predicates = predicates
                 .iter()
-                .map(|(p, s)| serialized_predicate_with_status(p, s))
+                .filter(|(p, _)| is_owned_by(p, &owner))
+                .map(|(p, s)| serialized_predicate_with_status(p, s, &chain_tips))
                 .collect::<Vec<_>>();
 
             Json(json!({
@@ -121,20 +154,35 @@ fn handle_get_predicates(
 #[post("/v1/chainhooks", format = "application/json", data = "<predicate>")]
 fn handle_create_predicate(
     predicate: Result<Json<ChainhookSpecificationNetworkMap>, rocket::serde::json::Error>,
+    content_length: ContentLength,
+    token: BearerToken,
     api_config: &State<PredicatesApiConfig>,
     background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
     ctx: &State<Context>,
 ) -> Json<JsonValue> {
     ctx.try_log(|logger| slog::info!(logger, "Handling HTTP POST /v1/chainhooks"));
-    let predicate = match predicate {
+    let owner = match authorize_owner(&token, api_config) {
+        Ok(owner) => owner,
+        Err(response) => return response,
+    };
+    let mut predicate = match predicate {
         Err(e) => {
             return Json(json!({
-                "status": 422,
+                "status": get_json_error_status(&e, &content_length, api_config),
                 "error": e.to_string(),
             }))
         }
         Ok(predicate) => {
             let predicate = predicate.into_inner();
+            // local files can only be referenced by the predicates loaded by the operator
+            if let ChainhookSpecificationNetworkMap::Bitcoin(ref spec) = predicate {
+                if spec.reads_local_files() {
+                    return Json(json!({
+                        "status": 422,
+                        "error": "denylist 'path' is not accepted for predicates registered through the API, list the 'addresses' instead",
+                    }));
+                }
+            }
             if let Err(e) = predicate.validate() {
                 return Json(json!({
                     "status": 422,
@@ -144,23 +192,30 @@ fn handle_create_predicate(
             predicate
         }
     };
+    // predicates registered with an owner token belong to its owner
+    if let Some(ref owner) = owner {
+        if predicate.get_owner_uuid().map_or(false, |o| o != owner) {
+            return Json(json!({
+                "status": 403,
+                "error": "owner_uuid doesn't match the owner of the token",
+            }));
+        }
+        predicate.set_owner_uuid(owner);
+        // uuids are shared by every owner: the ones of owner-scoped predicates are assigned here,
+        // so that registrations can't tell whether another owner's predicate uses a given uuid
+        predicate.set_uuid(&uuid::Uuid::new_v4().to_string());
+    }
 
     let predicate_uuid = predicate.get_uuid().to_string();
-
-    if let Ok(mut predicates_db_conn) = open_readwrite_predicates_db_conn(api_config) {
-        match get_entry_from_predicates_db(
-            &ChainhookInstance::either_stx_or_btc_key(&predicate_uuid),
-            &mut predicates_db_conn,
-            &ctx,
-        ) {
-            Ok(Some(_)) => {
-                return Json(json!({
-                    "status": 409,
-                    "error": "Predicate uuid already in use",
-                }))
-            }
-            _ => {}
-        }
+    // unsatisfiable predicates are still registered, the warnings are only reported to the caller
+    let warnings = predicate.get_warnings();
+
+    let predicate_key = ChainhookInstance::either_stx_or_btc_key(&predicate_uuid);
+    if let Ok(Some(_)) = find_predicate_store(api_config, &predicate_key) {
+        return Json(json!({
+            "status": 409,
+            "error": "Predicate uuid already in use",
+        }));
     }
 
     let background_job_tx = background_job_tx.inner();
@@ -171,17 +226,44 @@ fn handle_create_predicate(
         _ => {}
     };
 
-    Json(json!({
+    let mut response = json!({
         "status": 200,
         "result": predicate_uuid,
-    }))
+    });
+    if !warnings.is_empty() {
+        response["warnings"] = json!(warnings);
+    }
+    Json(response)
+}
+
+/// Bodies exceeding the `max_body_size` limit are truncated by rocket, which reports them as
+/// an unexpected end of file. So are the bodies of clients disconnecting before sending them
+/// entirely: only bodies whose `Content-Length` exceeds the limit are reported as too large.
+fn get_json_error_status(
+    e: &rocket::serde::json::Error,
+    content_length: &ContentLength,
+    api_config: &PredicatesApiConfig,
+) -> u16 {
+    match e {
+        rocket::serde::json::Error::Io(io_error)
+            if io_error.kind() == std::io::ErrorKind::UnexpectedEof
+                && content_length
+                    .0
+                    .map_or(false, |length| length > api_config.get_max_body_size()) =>
+        {
+            413
+        }
+        _ => 422,
+    }
 }
 
 #[openapi(tag = "Managing Predicates")]
 #[get("/v1/chainhooks/<predicate_uuid>", format = "application/json")]
 fn handle_get_predicate(
     predicate_uuid: String,
+    token: BearerToken,
     api_config: &State<PredicatesApiConfig>,
+    chain_tips: &State<Arc<RwLock<ChainTips>>>,
     ctx: &State<Context>,
 ) -> Json<JsonValue> {
     ctx.try_log(|logger| {
@@ -191,22 +273,32 @@ fn handle_get_predicate(
             predicate_uuid
         )
     });
+    let owner = match authorize_owner(&token, api_config) {
+        Ok(owner) => owner,
+        Err(response) => return response,
+    };
 
-    match open_readwrite_predicates_db_conn(api_config) {
-        Ok(mut predicates_db_conn) => {
-            let (predicate, status) = match get_entry_from_predicates_db(
-                &ChainhookInstance::either_stx_or_btc_key(&predicate_uuid),
-                &mut predicates_db_conn,
-                &ctx,
-            ) {
-                Ok(Some(predicate_with_status)) => predicate_with_status,
+    let predicate_key = ChainhookInstance::either_stx_or_btc_key(&predicate_uuid);
+    match find_predicate_store(api_config, &predicate_key) {
+        Ok(predicates_db_conn) => {
+            let Some(mut predicates_db_conn) = predicates_db_conn else {
+                return Json(json!({
+                    "status": 404,
+                }));
+            };
+            // the predicates of other owners are reported as missing
+            let (predicate, status) = match predicates_db_conn.get_entry(&predicate_key) {
+                Ok(Some((predicate, status))) if is_owned_by(&predicate, &owner) => {
+                    (predicate, status)
+                }
                 _ => {
                     return Json(json!({
                         "status": 404,
                     }))
                 }
             };
-            let result = serialized_predicate_with_status(&predicate, &status);
+            let result =
+                serialized_predicate_with_status(&predicate, &status, &get_chain_tips(chain_tips));
             Json(json!({
                 "status": 200,
                 "result": result
@@ -219,10 +311,144 @@ fn handle_get_predicate(
     }
 }
 
+/// Streams the status of a predicate as server-sent events, pushing each status update as it is written.
+#[openapi(skip)]
+#[get("/v1/chainhooks/<predicate_uuid>/status/stream")]
+fn handle_stream_predicate_status(
+    predicate_uuid: String,
+    token: BearerToken,
+    mut shutdown: Shutdown,
+    api_config: &State<PredicatesApiConfig>,
+    ctx: &State<Context>,
+) -> EventStream![] {
+    ctx.try_log(|logger| {
+        slog::info!(
+            logger,
+            "Handling HTTP GET /v1/chainhooks/{}/status/stream",
+            predicate_uuid
+        )
+    });
+
+    let predicate_key = ChainhookInstance::either_stx_or_btc_key(&predicate_uuid);
+    // the stream ends right away for the predicates the token can't access
+    let is_authorized = match authorize_owner(&token, api_config) {
+        Ok(None) => true,
+        Ok(owner) => matches!(
+            get_owned_predicate(api_config, &predicate_key, &owner),
+            Ok(Some(_))
+        ),
+        Err(_) => false,
+    };
+    let mut status_updates = subscribe_to_predicate_status_updates();
+    EventStream! {
+        if is_authorized {
+            loop {
+                let (updated_predicate_key, status) = select! {
+                    update = status_updates.recv() => match update {
+                        Ok(update) => update,
+                        Err(RecvError::Closed) => break,
+                        Err(RecvError::Lagged(_)) => continue,
+                    },
+                    _ = &mut shutdown => break,
+                };
+                if updated_predicate_key == predicate_key {
+                    yield Event::json(&status);
+                }
+            }
+        }
+    }
+}
+
+#[openapi(tag = "Managing Predicates")]
+#[patch(
+    "/v1/chainhooks/<predicate_uuid>/action",
+    format = "application/json",
+    data = "<action>"
+)]
+fn handle_update_predicate_action(
+    predicate_uuid: String,
+    action: Result<Json<HookAction>, rocket::serde::json::Error>,
+    content_length: ContentLength,
+    token: BearerToken,
+    api_config: &State<PredicatesApiConfig>,
+    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| {
+        slog::info!(
+            logger,
+            "Handling HTTP PATCH /v1/chainhooks/{}/action",
+            predicate_uuid
+        )
+    });
+    let owner = match authorize_owner(&token, api_config) {
+        Ok(owner) => owner,
+        Err(response) => return response,
+    };
+    let action = match action {
+        Err(e) => {
+            return Json(json!({
+                "status": get_json_error_status(&e, &content_length, api_config),
+                "error": e.to_string(),
+            }))
+        }
+        Ok(action) => action.into_inner(),
+    };
+
+    let predicate_key = ChainhookInstance::either_stx_or_btc_key(&predicate_uuid);
+    let (predicate, status) = match get_owned_predicate_entry(api_config, &predicate_key, &owner) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => {
+            return Json(json!({
+                "status": 404,
+            }))
+        }
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    if let Err(e) = action.validate_for_chain(&predicate.chain()) {
+        return Json(json!({
+            "status": 422,
+            "error": e,
+        }));
+    }
+    // scans deliver to the action the predicate had when they started
+    if let PredicateStatus::New | PredicateStatus::Scanning(_) = status {
+        return Json(json!({
+            "status": 409,
+            "error": "unable to update the action of a predicate until its scan is done",
+        }));
+    }
+
+    let background_job_tx = background_job_tx.inner();
+    match background_job_tx.lock() {
+        Ok(tx) => {
+            let _ = tx.send(ObserverCommand::UpdatePredicateAction(
+                PredicateActionUpdateData {
+                    predicate_uuid: predicate_uuid.clone(),
+                    action,
+                },
+            ));
+        }
+        _ => {}
+    };
+
+    Json(json!({
+        "status": 200,
+        "result": predicate_uuid,
+    }))
+}
+
 #[openapi(tag = "Managing Predicates")]
 #[delete("/v1/chainhooks/stacks/<predicate_uuid>", format = "application/json")]
 fn handle_delete_stacks_predicate(
     predicate_uuid: String,
+    token: BearerToken,
+    api_config: &State<PredicatesApiConfig>,
     background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
     ctx: &State<Context>,
 ) -> Json<JsonValue> {
@@ -233,6 +459,9 @@ fn handle_delete_stacks_predicate(
             predicate_uuid
         )
     });
+    if let Err(response) = authorize_predicate_deletion(&predicate_uuid, &token, api_config) {
+        return response;
+    }
 
     let background_job_tx = background_job_tx.inner();
     match background_job_tx.lock() {
@@ -252,6 +481,8 @@ fn handle_delete_stacks_predicate(
 #[delete("/v1/chainhooks/bitcoin/<predicate_uuid>", format = "application/json")]
 fn handle_delete_bitcoin_predicate(
     predicate_uuid: String,
+    token: BearerToken,
+    api_config: &State<PredicatesApiConfig>,
     background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
     ctx: &State<Context>,
 ) -> Json<JsonValue> {
@@ -262,6 +493,9 @@ fn handle_delete_bitcoin_predicate(
             predicate_uuid
         )
     });
+    if let Err(response) = authorize_predicate_deletion(&predicate_uuid, &token, api_config) {
+        return response;
+    }
 
     let background_job_tx = background_job_tx.inner();
     match background_job_tx.lock() {
@@ -277,52 +511,412 @@ fn handle_delete_bitcoin_predicate(
     }))
 }
 
-pub fn get_entry_from_predicates_db(
+/// Bearer token sent in the `Authorization` header, if any.
+struct BearerToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BearerToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(|token| token.to_string());
+        request::Outcome::Success(BearerToken(token))
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for BearerToken {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::None)
+    }
+}
+
+/// Length of the body announced in the `Content-Length` header, if any.
+struct ContentLength(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ContentLength {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let length = request
+            .headers()
+            .get_one("Content-Length")
+            .and_then(|header| header.parse::<u64>().ok());
+        request::Outcome::Success(ContentLength(length))
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for ContentLength {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::None)
+    }
+}
+
+/// Ensures that `token` is the admin token, returning the error response of the request otherwise.
+fn authorize_admin(
+    token: &BearerToken,
+    api_config: &PredicatesApiConfig,
+) -> Result<(), Json<JsonValue>> {
+    match api_config.admin_token {
+        None => Err(Json(json!({
+            "status": 403,
+            "message": "admin routes are disabled",
+        }))),
+        Some(ref admin_token) if !is_token(&token.0, admin_token) => Err(Json(json!({
+            "status": 401,
+            "message": "invalid admin token",
+        }))),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Compares `token` with `expected` in a time that doesn't depend on the position of their first
+/// difference, so that a token can't be guessed byte by byte from the response times.
+fn is_token(token: &Option<String>, expected: &str) -> bool {
+    let Some(token) = token else {
+        return false;
+    };
+    if token.len() != expected.len() {
+        return false;
+    }
+    let diff = token
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    std::hint::black_box(diff) == 0
+}
+
+/// Returns the owner whose predicates `token` grants access to, `None` granting access to every
+/// predicate. Predicates are only scoped to their owner when `owner_tokens` is configured, the
+/// admin token still granting access to every predicate.
+fn authorize_owner(
+    token: &BearerToken,
+    api_config: &PredicatesApiConfig,
+) -> Result<Option<String>, Json<JsonValue>> {
+    let Some(ref owner_tokens) = api_config.owner_tokens else {
+        return Ok(None);
+    };
+    if token.0.is_none() {
+        return Err(Json(json!({
+            "status": 401,
+            "message": "missing owner token",
+        })));
+    }
+    if let Some(ref admin_token) = api_config.admin_token {
+        if is_token(&token.0, admin_token) {
+            return Ok(None);
+        }
+    }
+    // every owner token is compared, so that the response time doesn't tell which one matched
+    let owner = owner_tokens
+        .iter()
+        .filter(|(owner_token, _)| is_token(&token.0, owner_token))
+        .fold(None, |owner, (_, token_owner)| owner.or(Some(token_owner)));
+    match owner {
+        Some(owner) => Ok(Some(owner.clone())),
+        None => Err(Json(json!({
+            "status": 401,
+            "message": "invalid owner token",
+        }))),
+    }
+}
+
+fn is_owned_by(predicate: &ChainhookInstance, owner: &Option<String>) -> bool {
+    match owner {
+        Some(owner) => predicate.owner_uuid() == Some(owner.as_str()),
+        None => true,
+    }
+}
+
+/// Returns the predicate stored at `predicate_key`, unless it is missing or belongs to another
+/// owner than `owner`.
+fn get_owned_predicate(
+    api_config: &PredicatesApiConfig,
     predicate_key: &str,
-    predicate_db_conn: &mut Connection,
-    _ctx: &Context,
+    owner: &Option<String>,
+) -> Result<Option<ChainhookInstance>, String> {
+    let entry = get_owned_predicate_entry(api_config, predicate_key, owner)?;
+    Ok(entry.map(|(predicate, _)| predicate))
+}
+
+/// Returns the predicate `predicate_key` along with its status, unless it belongs to another owner.
+fn get_owned_predicate_entry(
+    api_config: &PredicatesApiConfig,
+    predicate_key: &str,
+    owner: &Option<String>,
 ) -> Result<Option<(ChainhookInstance, PredicateStatus)>, String> {
-    let entry: HashMap<String, String> = predicate_db_conn.hgetall(predicate_key).map_err(|e| {
-        format!(
-            "unable to load chainhook associated with key {}: {}",
-            predicate_key,
-            e.to_string()
+    let Some(mut predicates_db_conn) = find_predicate_store(api_config, predicate_key)? else {
+        return Ok(None);
+    };
+    let entry = predicates_db_conn
+        .get_entry(predicate_key)?
+        .filter(|(predicate, _)| is_owned_by(predicate, owner));
+    Ok(entry)
+}
+
+/// Ensures that `token` grants access to the predicate being deleted. Deletions of unknown
+/// predicates are only rejected when predicates are scoped to their owner.
+fn authorize_predicate_deletion(
+    predicate_uuid: &str,
+    token: &BearerToken,
+    api_config: &PredicatesApiConfig,
+) -> Result<(), Json<JsonValue>> {
+    let owner = authorize_owner(token, api_config)?;
+    if owner.is_none() {
+        return Ok(());
+    }
+    let predicate_key = ChainhookInstance::either_stx_or_btc_key(predicate_uuid);
+    match get_owned_predicate(api_config, &predicate_key, &owner) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(Json(json!({
+            "status": 404,
+        }))),
+        Err(e) => Err(Json(json!({
+            "status": 500,
+            "message": e,
+        }))),
+    }
+}
+
+#[openapi(skip)]
+#[post("/v1/chainhooks/<predicate_uuid>/expire")]
+fn handle_expire_predicate(
+    predicate_uuid: String,
+    token: BearerToken,
+    api_config: &State<PredicatesApiConfig>,
+    bitcoin_scan_op_tx: &State<crossbeam_channel::Sender<BitcoinScanOp>>,
+    stacks_scan_op_tx: &State<crossbeam_channel::Sender<StacksScanOp>>,
+    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| {
+        slog::info!(
+            logger,
+            "Handling HTTP POST /v1/chainhooks/{}/expire",
+            predicate_uuid
         )
-    })?;
+    });
+    if let Err(response) = authorize_admin(&token, api_config) {
+        return response;
+    }
 
-    let encoded_spec = match entry.get("specification") {
-        None => return Ok(None),
-        Some(payload) => payload,
+    let predicate_key = ChainhookInstance::either_stx_or_btc_key(&predicate_uuid);
+    let mut predicates_db_conn = match find_predicate_store(api_config, &predicate_key) {
+        Ok(Some(predicates_db_conn)) => predicates_db_conn,
+        Ok(None) => {
+            return Json(json!({
+                "status": 404,
+            }))
+        }
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    let predicate = match predicates_db_conn.get_entry(&predicate_key) {
+        Ok(Some((predicate, _))) => predicate,
+        Ok(None) => {
+            return Json(json!({
+                "status": 404,
+            }))
+        }
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
     };
 
-    let spec = ChainhookInstance::deserialize_specification(&encoded_spec)?;
+    // a killed scan only stops before its next block: the statuses it writes in the meantime
+    // never overwrite the expired status, which is final
+    match predicate {
+        ChainhookInstance::Bitcoin(_) => {
+            let _ = bitcoin_scan_op_tx.send(BitcoinScanOp::KillScan(predicate_uuid.clone()));
+        }
+        ChainhookInstance::Stacks(_) => {
+            let _ = stacks_scan_op_tx.send(StacksScanOp::KillScan(predicate_uuid.clone()));
+        }
+    }
+    let expired_at_block_height = match set_expired_status(
+        &predicate_key,
+        api_config.expired_predicates_retention_secs,
+        predicates_db_conn.as_mut(),
+        &ctx,
+    ) {
+        Ok(expired_at_block_height) => expired_at_block_height,
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    // stop evaluating the predicate if it is already streaming
+    let expiration_data = HookExpirationData {
+        hook_uuid: predicate_key,
+        block_height: expired_at_block_height,
+    };
+    if let Ok(tx) = background_job_tx.lock() {
+        let _ = tx.send(match predicate {
+            ChainhookInstance::Bitcoin(_) => {
+                ObserverCommand::ExpireBitcoinPredicate(expiration_data)
+            }
+            ChainhookInstance::Stacks(_) => ObserverCommand::ExpireStacksPredicate(expiration_data),
+        });
+    }
 
-    let encoded_status = match entry.get("status") {
-        None => Err(format!(
-            "found predicate specification with no status for predicate {}",
-            predicate_key
-        )),
-        Some(payload) => Ok(payload),
-    }?;
+    Json(json!({
+        "status": 200,
+        "result": predicate_uuid,
+    }))
+}
+
+/// Re-reads the predicates specifications from the predicates db, and applies them to the
+/// predicates running in memory, e.g. after they were edited directly in the db. Like on startup,
+/// the predicates whose expiration is confirmed or that were interrupted are not reloaded.
+#[openapi(skip)]
+#[post("/v1/chainhooks/reload")]
+fn handle_reload_predicates(
+    token: BearerToken,
+    api_config: &State<PredicatesApiConfig>,
+    background_job_tx: &State<Arc<Mutex<Sender<ObserverCommand>>>>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| slog::info!(logger, "Handling HTTP POST /v1/chainhooks/reload"));
+    if let Err(response) = authorize_admin(&token, api_config) {
+        return response;
+    }
+
+    let mut predicates = vec![];
+    let predicates_db_conns = match open_predicate_stores(api_config) {
+        Ok(predicates_db_conns) => predicates_db_conns,
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    for mut predicates_db_conn in predicates_db_conns.into_iter() {
+        match get_entries_from_predicates_db(predicates_db_conn.as_mut(), ctx) {
+            Ok(entries) => predicates.extend(entries.into_iter().filter_map(
+                |(predicate, status)| match status {
+                    PredicateStatus::ConfirmedExpiration(_) | PredicateStatus::Interrupted(_) => {
+                        None
+                    }
+                    _ => Some(predicate),
+                },
+            )),
+            Err(e) => {
+                return Json(json!({
+                    "status": 500,
+                    "message": e,
+                }))
+            }
+        }
+    }
 
-    let status = serde_json::from_str(&encoded_status).map_err(|e| format!("{}", e.to_string()))?;
+    let number_of_predicates = predicates.len();
+    let background_job_tx = background_job_tx.inner();
+    match background_job_tx.lock() {
+        Ok(tx) => {
+            let _ = tx.send(ObserverCommand::ReloadPredicates(predicates));
+        }
+        _ => {}
+    };
 
-    Ok(Some((spec, status)))
+    Json(json!({
+        "status": 200,
+        "result": number_of_predicates,
+    }))
+}
+
+#[openapi(tag = "Diagnostics")]
+#[get("/v1/diagnostics/tip", format = "application/json")]
+fn handle_get_chain_tips(
+    chain_tips: &State<Arc<RwLock<ChainTips>>>,
+    network: &State<Option<IndexerConfig>>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| slog::info!(logger, "Handling HTTP GET /v1/diagnostics/tip"));
+    let chain_tips = match chain_tips.read() {
+        Ok(chain_tips) => chain_tips.clone(),
+        Err(_) => {
+            return Json(json!({
+                "status": 500,
+                "message": "unable to retrieve chain tips",
+            }))
+        }
+    };
+
+    // the lag is only reported when bitcoind can be reached
+    let bitcoin_node_tip = match network.inner() {
+        Some(network) => match get_bitcoin_node_tip(network) {
+            Ok(node_tip) => Some(node_tip),
+            Err(e) => {
+                ctx.try_log(|logger| slog::warn!(logger, "{e}"));
+                None
+            }
+        },
+        None => None,
+    };
+    let bitcoin_lag = match (&chain_tips.bitcoin, bitcoin_node_tip) {
+        (Some(tip), Some(node_tip)) => Some(node_tip.saturating_sub(tip.index)),
+        _ => None,
+    };
+
+    Json(json!({
+        "status": 200,
+        "result": {
+            "bitcoin": {
+                "block_identifier": chain_tips.bitcoin,
+                "node_tip_height": bitcoin_node_tip,
+                "lag": bitcoin_lag,
+            },
+            "stacks": {
+                "block_identifier": chain_tips.stacks,
+            },
+        }
+    }))
+}
+
+fn get_bitcoin_node_tip(network: &IndexerConfig) -> Result<u64, String> {
+    let auth = Auth::UserPass(
+        network.bitcoind_rpc_username.clone(),
+        network.bitcoind_rpc_password.clone(),
+    );
+    let bitcoin_rpc = Client::new(&network.bitcoind_rpc_url, auth)
+        .map_err(|e| format!("Bitcoin RPC error: {}", e.to_string()))?;
+    bitcoin_rpc
+        .get_blockchain_info()
+        .map(|info| info.blocks)
+        .map_err(|e| format!("unable to retrieve Bitcoin chain tip ({})", e.to_string()))
 }
 
 pub fn get_entries_from_predicates_db(
-    predicate_db_conn: &mut Connection,
+    predicate_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) -> Result<Vec<(ChainhookInstance, PredicateStatus)>, String> {
-    let chainhooks_to_load: Vec<String> = predicate_db_conn
-        .scan_match(ChainhookInstance::either_stx_or_btc_key("*"))
-        .map_err(|e| format!("unable to connect to redis: {}", e.to_string()))?
-        .into_iter()
-        .collect();
+    let chainhooks_to_load = predicate_db_conn.list()?;
 
     let mut predicates = vec![];
     for predicate_key in chainhooks_to_load.iter() {
-        let chainhook = match get_entry_from_predicates_db(predicate_key, predicate_db_conn, ctx) {
+        let chainhook = match predicate_db_conn.get_entry(predicate_key) {
             Ok(Some((spec, status))) => (spec, status),
             Ok(None) => {
                 warn!(
@@ -346,17 +940,18 @@ pub fn get_entries_from_predicates_db(
     Ok(predicates)
 }
 
-pub fn load_predicates_from_redis(
+pub fn load_predicates_from_db(
     config: &crate::config::Config,
     ctx: &Context,
 ) -> Result<Vec<(ChainhookInstance, PredicateStatus)>, String> {
-    let redis_uri: &str = config.expected_api_database_uri();
-    let client = redis::Client::open(redis_uri)
-        .map_err(|e| format!("unable to connect to redis: {}", e.to_string()))?;
-    let mut predicate_db_conn = client
-        .get_connection()
-        .map_err(|e| format!("unable to connect to redis: {}", e.to_string()))?;
-    get_entries_from_predicates_db(&mut predicate_db_conn, ctx)
+    let mut predicates = vec![];
+    for mut predicate_db_conn in open_predicate_stores(config.expected_api_config())? {
+        predicates.append(&mut get_entries_from_predicates_db(
+            predicate_db_conn.as_mut(),
+            ctx,
+        )?);
+    }
+    Ok(predicates)
 }
 
 pub fn document_predicate_api_server() -> Result<String, String> {
@@ -371,16 +966,32 @@ pub fn get_routes_spec() -> (Vec<rocket::Route>, OpenApi) {
         handle_ping,
         handle_get_predicates,
         handle_get_predicate,
+        handle_stream_predicate_status,
         handle_create_predicate,
+        handle_update_predicate_action,
         handle_delete_bitcoin_predicate,
-        handle_delete_stacks_predicate
+        handle_delete_stacks_predicate,
+        handle_expire_predicate,
+        handle_reload_predicates,
+        handle_get_chain_tips
     ]
 }
 
+/// Returns the chain tips known by the observer, which are all unknown if they can't be read.
+fn get_chain_tips(chain_tips: &State<Arc<RwLock<ChainTips>>>) -> ChainTips {
+    match chain_tips.read() {
+        Ok(chain_tips) => chain_tips.clone(),
+        Err(_) => ChainTips::default(),
+    }
+}
+
 fn serialized_predicate_with_status(
     predicate: &ChainhookInstance,
     status: &PredicateStatus,
+    chain_tips: &ChainTips,
 ) -> JsonValue {
+    let mut status = status.clone();
+    status.set_streaming_lag(&predicate.chain(), chain_tips);
     match (predicate, status) {
         (ChainhookInstance::Stacks(spec), status) => json!({
             "chain": "stacks",
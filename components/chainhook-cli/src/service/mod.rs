@@ -1,9 +1,17 @@
+mod audit_log;
 pub(crate) mod http_api;
+pub(crate) mod predicate_store;
+mod predicates_bundle;
 mod runloops;
 
-use crate::config::{Config, PredicatesApi, PredicatesApiConfig};
+use crate::config::{Config, PredicatesApi, PredicatesApiConfig, PredicatesDatabaseBackend};
 use crate::scan::stacks::consolidate_local_stacks_chainstate_using_csv;
-use crate::service::http_api::{load_predicates_from_redis, start_predicate_api_server};
+use crate::service::audit_log::{
+    record_audit_log_entry, record_observer_event, AuditEventType, AuditLogEntry, AuditSource,
+};
+use crate::service::http_api::{load_predicates_from_db, start_predicate_api_server};
+use crate::service::predicate_store::{PredicateStore, SqlitePredicateStore};
+use crate::service::predicates_bundle::fetch_predicates_bundle;
 use crate::service::runloops::{start_bitcoin_scan_runloop, start_stacks_scan_runloop};
 use crate::storage::{
     confirm_entries_in_stacks_blocks, draft_entries_in_stacks_blocks, get_all_unconfirmed_blocks,
@@ -11,7 +19,9 @@ use crate::storage::{
     open_readwrite_stacks_db_conn,
 };
 
-use chainhook_sdk::chainhooks::types::{ChainhookSpecificationNetworkMap, ChainhookStore};
+use chainhook_sdk::chainhooks::types::{
+    ChainhookSpecificationNetworkMap, ChainhookStore, HttpHook,
+};
 
 use chainhook_sdk::chainhooks::types::ChainhookInstance;
 use chainhook_sdk::observer::{
@@ -19,39 +29,70 @@ use chainhook_sdk::observer::{
     PredicateDeregisteredEvent, PredicateEvaluationReport, PredicateInterruptedData,
     StacksObserverStartupContext,
 };
-use chainhook_sdk::types::{Chain, StacksBlockData, StacksChainEvent};
+use chainhook_sdk::types::{
+    BitcoinChainEvent, BlockIdentifier, Chain, StacksBlockData, StacksChainEvent,
+};
 use chainhook_sdk::utils::Context;
-use redis::{Commands, Connection};
+use redis::Connection;
 
+use std::collections::{BTreeMap, HashMap};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
 
-use self::http_api::get_entry_from_predicates_db;
 use self::runloops::{BitcoinScanOp, StacksScanOp};
 
 pub struct Service {
     config: Config,
     ctx: Context,
+    chain_tips: Arc<RwLock<ChainTips>>,
 }
 
 impl Service {
     pub fn new(config: Config, ctx: Context) -> Self {
-        Self { config, ctx }
+        Self {
+            config,
+            ctx,
+            chain_tips: Arc::new(RwLock::new(ChainTips::default())),
+        }
     }
 
     pub async fn run(
         &mut self,
-        predicates_from_startup: Vec<ChainhookSpecificationNetworkMap>,
+        mut predicates_from_startup: Vec<ChainhookSpecificationNetworkMap>,
         observer_commands_tx_rx: Option<(Sender<ObserverCommand>, Receiver<ObserverCommand>)>,
     ) -> Result<(), String> {
         let mut chainhook_store = ChainhookStore::new();
 
-        // store all predicates from Redis that were in the process of scanning when
+        // predicates from the remote bundle are registered along the ones from the command line
+        if let Some(bundle) = &self.config.predicates.bundle {
+            match fetch_predicates_bundle(bundle).await {
+                Ok(mut predicates) => {
+                    info!(
+                        self.ctx.expect_logger(),
+                        "Loaded {} predicates from bundle {}",
+                        predicates.len(),
+                        bundle.url
+                    );
+                    predicates_from_startup.append(&mut predicates);
+                }
+                Err(e) => {
+                    warn!(
+                        self.ctx.expect_logger(),
+                        "Unable to load predicates bundle, only registering predicates from other sources: {}",
+                        e
+                    );
+                }
+            }
+        }
+
+        // store all predicates from the predicates db that were in the process of scanning when
         // chainhook was shutdown - we need to resume where we left off
         let mut leftover_scans = vec![];
-        // retrieve predicates from Redis, and register each in memory
+        // retrieve predicates from the predicates db, and register each in memory
         if self.config.is_http_api_enabled() {
-            let registered_predicates = match load_predicates_from_redis(&self.config, &self.ctx) {
+            let registered_predicates = match load_predicates_from_db(&self.config, &self.ctx) {
                 Ok(predicates) => predicates,
                 Err(e) => {
                     error!(
@@ -112,22 +153,14 @@ impl Service {
         // For each predicate found, register in memory.
         for predicate in predicates_from_startup.into_iter() {
             if let PredicatesApi::On(api_config) = &self.config.http_api {
-                if let Ok(mut predicates_db_conn) = open_readwrite_predicates_db_conn(api_config) {
-                    let uuid = predicate.get_uuid();
-                    match get_entry_from_predicates_db(
-                        &ChainhookInstance::either_stx_or_btc_key(&uuid),
-                        &mut predicates_db_conn,
-                        &self.ctx,
-                    ) {
-                        Ok(Some(_)) => {
-                            warn!(
-                                self.ctx.expect_logger(),
-                                "Predicate uuid already in use: {uuid}",
-                            );
-                            continue;
-                        }
-                        _ => {}
-                    }
+                let uuid = predicate.get_uuid();
+                let predicate_key = ChainhookInstance::either_stx_or_btc_key(&uuid);
+                if let Ok(Some(_)) = find_predicate_store(api_config, &predicate_key) {
+                    warn!(
+                        self.ctx.expect_logger(),
+                        "Predicate uuid already in use: {uuid}",
+                    );
+                    continue;
                 };
             }
             match chainhook_store.register_instance_from_network_map(
@@ -160,8 +193,11 @@ impl Service {
         let (observer_event_tx, observer_event_rx) = crossbeam_channel::unbounded();
         // let (ordinal_indexer_command_tx, ordinal_indexer_command_rx) = channel();
 
+        // shared by the observer and every scan
+        let http_client = self.config.build_http_delivery_client()?;
         let mut event_observer_config = self.config.get_event_observer_config();
         event_observer_config.registered_chainhooks = chainhook_store;
+        event_observer_config.http_client = Some(http_client.clone());
 
         // Download and ingest a Stacks dump
         if self.config.rely_on_remote_stacks_tsv() {
@@ -173,11 +209,13 @@ impl Service {
         let (stacks_scan_op_tx, stacks_scan_op_rx) = crossbeam_channel::unbounded();
         let ctx = self.ctx.clone();
         let config = self.config.clone();
+        let moved_http_client = http_client.clone();
         let observer_command_tx_moved = observer_command_tx.clone();
         let _ = hiro_system_kit::thread_named("Stacks scan runloop")
             .spawn(move || {
                 start_stacks_scan_runloop(
                     &config,
+                    &moved_http_client,
                     stacks_scan_op_rx,
                     observer_command_tx_moved.clone(),
                     &ctx,
@@ -192,11 +230,13 @@ impl Service {
         let (bitcoin_scan_op_tx, bitcoin_scan_op_rx) = crossbeam_channel::unbounded();
         let ctx = self.ctx.clone();
         let config = self.config.clone();
+        let moved_http_client = http_client.clone();
         let observer_command_tx_moved = observer_command_tx.clone();
         let _ = hiro_system_kit::thread_named("Bitcoin scan runloop")
             .spawn(move || {
                 start_bitcoin_scan_runloop(
                     &config,
+                    &moved_http_client,
                     bitcoin_scan_op_rx,
                     observer_command_tx_moved.clone(),
                     &ctx,
@@ -216,13 +256,21 @@ impl Service {
             );
             let ctx = self.ctx.clone();
             let api_config = api_config.clone();
+            let network = config.network.clone();
+            let chain_tips = self.chain_tips.clone();
             let moved_observer_command_tx = observer_command_tx.clone();
+            let moved_bitcoin_scan_op_tx = bitcoin_scan_op_tx.clone();
+            let moved_stacks_scan_op_tx = stacks_scan_op_tx.clone();
             // Test and initialize a database connection
             let res = hiro_system_kit::thread_named("HTTP Predicate API")
                 .spawn(move || {
                     let future = start_predicate_api_server(
                         api_config,
+                        Some(network),
+                        chain_tips,
                         moved_observer_command_tx.clone(),
+                        moved_bitcoin_scan_op_tx,
+                        moved_stacks_scan_op_tx,
                         ctx.clone(),
                     );
                     hiro_system_kit::nestable_block_on(future)
@@ -297,8 +345,8 @@ impl Service {
         let ctx = self.ctx.clone();
         match self.config.http_api {
             PredicatesApi::On(ref api_config) => {
-                // Test redis connection
-                open_readwrite_predicates_db_conn(api_config)?;
+                // Test the predicates db connections
+                open_predicate_stores(api_config)?;
             }
             PredicatesApi::Off => {}
         };
@@ -336,6 +384,7 @@ impl Service {
                     break;
                 }
             };
+            record_observer_event(&self.config.audit_log, &event, &self.ctx);
             match event {
                 ObserverEvent::PredicateRegistered(spec) => {
                     // If start block specified, use it.
@@ -343,20 +392,20 @@ impl Service {
                     // - contract-id
                     if let PredicatesApi::On(ref config) = self.config.http_api {
                         let Ok(mut predicates_db_conn) =
-                            open_readwrite_predicates_db_conn_verbose(&config, &ctx)
+                            open_predicate_store_verbose(&config, &spec.chain(), &ctx)
                         else {
                             continue;
                         };
                         update_predicate_spec(
                             &spec.key(),
                             &spec,
-                            &mut predicates_db_conn,
+                            predicates_db_conn.as_mut(),
                             &self.ctx,
                         );
                         update_predicate_status(
                             &spec.key(),
                             PredicateStatus::New,
-                            &mut predicates_db_conn,
+                            predicates_db_conn.as_mut(),
                             &self.ctx,
                         );
                     }
@@ -378,31 +427,48 @@ impl Service {
                 ObserverEvent::PredicateEnabled(spec) => {
                     if let PredicatesApi::On(ref config) = self.config.http_api {
                         let Ok(mut predicates_db_conn) =
-                            open_readwrite_predicates_db_conn_verbose(&config, &ctx)
+                            open_predicate_store_verbose(&config, &spec.chain(), &ctx)
                         else {
                             continue;
                         };
                         update_predicate_spec(
                             &spec.key(),
                             &spec,
-                            &mut predicates_db_conn,
+                            predicates_db_conn.as_mut(),
                             &self.ctx,
                         );
                         set_predicate_streaming_status(
                             StreamingDataType::FinishedScanning,
                             &spec.key(),
-                            &mut predicates_db_conn,
+                            predicates_db_conn.as_mut(),
                             &ctx,
                         );
                     }
                 }
+                ObserverEvent::PredicateActionUpdated(spec) => {
+                    // only the specification is updated: the predicate keeps its status
+                    // and resumes its scan / streaming where it left off
+                    if let PredicatesApi::On(ref config) = self.config.http_api {
+                        let Ok(mut predicates_db_conn) =
+                            open_predicate_store_verbose(&config, &spec.chain(), &ctx)
+                        else {
+                            continue;
+                        };
+                        update_predicate_spec(
+                            &spec.key(),
+                            &spec,
+                            predicates_db_conn.as_mut(),
+                            &self.ctx,
+                        );
+                    }
+                }
                 ObserverEvent::PredicateDeregistered(PredicateDeregisteredEvent {
                     predicate_uuid,
                     chain,
                 }) => {
                     if let PredicatesApi::On(ref config) = self.config.http_api {
                         let Ok(mut predicates_db_conn) =
-                            open_readwrite_predicates_db_conn_verbose(&config, &ctx)
+                            open_predicate_store_verbose(&config, &chain, &ctx)
                         else {
                             continue;
                         };
@@ -420,22 +486,22 @@ impl Service {
 
                         let predicate_key =
                             ChainhookInstance::either_stx_or_btc_key(&predicate_uuid);
-                        let res: Result<(), redis::RedisError> =
-                            predicates_db_conn.del(predicate_key.clone());
-                        if let Err(e) = res {
+                        if let Err(e) = predicates_db_conn.delete(&predicate_key) {
                             warn!(
                                 self.ctx.expect_logger(),
-                                "unable to delete predicate {predicate_key}: {}",
-                                e.to_string()
+                                "unable to delete predicate {predicate_key}: {}", e
                             );
                         }
                     }
                 }
                 ObserverEvent::BitcoinChainEvent((chain_update, report)) => {
                     debug!(self.ctx.expect_logger(), "Bitcoin update not stored");
+                    if let Ok(mut chain_tips) = self.chain_tips.write() {
+                        chain_tips.update_with_bitcoin_chain_event(&chain_update);
+                    }
                     if let PredicatesApi::On(ref config) = self.config.http_api {
                         let Ok(mut predicates_db_conn) =
-                            open_readwrite_predicates_db_conn_verbose(&config, &ctx)
+                            open_predicate_store_verbose(&config, &Chain::Bitcoin, &ctx)
                         else {
                             continue;
                         };
@@ -448,11 +514,22 @@ impl Service {
                                     match expire_predicates_for_block(
                                         &Chain::Bitcoin,
                                         confirmed_block.block_identifier.index,
-                                        &mut predicates_db_conn,
+                                        config.expired_predicates_retention_secs,
+                                        predicates_db_conn.as_mut(),
                                         &ctx,
                                     ) {
                                         Some(expired_predicate_uuids) => {
                                             for uuid in expired_predicate_uuids.into_iter() {
+                                                record_audit_log_entry(
+                                                    &self.config.audit_log,
+                                                    AuditLogEntry::new(
+                                                        AuditEventType::Expired,
+                                                        &uuid,
+                                                        Some(&Chain::Bitcoin),
+                                                        AuditSource::Service,
+                                                    ),
+                                                    &self.ctx,
+                                                );
                                                 let _ = observer_command_tx.send(
                                                     ObserverCommand::ExpireBitcoinPredicate(
                                                         HookExpirationData {
@@ -476,11 +553,22 @@ impl Service {
                                     match expire_predicates_for_block(
                                         &Chain::Bitcoin,
                                         confirmed_block.block_identifier.index,
-                                        &mut predicates_db_conn,
+                                        config.expired_predicates_retention_secs,
+                                        predicates_db_conn.as_mut(),
                                         &ctx,
                                     ) {
                                         Some(expired_predicate_uuids) => {
                                             for uuid in expired_predicate_uuids.into_iter() {
+                                                record_audit_log_entry(
+                                                    &self.config.audit_log,
+                                                    AuditLogEntry::new(
+                                                        AuditEventType::Expired,
+                                                        &uuid,
+                                                        Some(&Chain::Bitcoin),
+                                                        AuditSource::Service,
+                                                    ),
+                                                    &self.ctx,
+                                                );
                                                 let _ = observer_command_tx.send(
                                                     ObserverCommand::ExpireBitcoinPredicate(
                                                         HookExpirationData {
@@ -501,12 +589,15 @@ impl Service {
                         update_status_from_report(
                             Chain::Bitcoin,
                             report,
-                            &mut predicates_db_conn,
+                            predicates_db_conn.as_mut(),
                             &ctx,
                         );
                     }
                 }
                 ObserverEvent::StacksChainEvent((chain_event, report)) => {
+                    if let Ok(mut chain_tips) = self.chain_tips.write() {
+                        chain_tips.update_with_stacks_chain_event(&chain_event);
+                    }
                     match open_readwrite_stacks_db_conn(
                         &self.config.expected_cache_path(),
                         &self.ctx,
@@ -571,7 +662,7 @@ impl Service {
 
                     if let PredicatesApi::On(ref config) = self.config.http_api {
                         let Ok(mut predicates_db_conn) =
-                            open_readwrite_predicates_db_conn_verbose(&config, &ctx)
+                            open_predicate_store_verbose(&config, &Chain::Stacks, &ctx)
                         else {
                             continue;
                         };
@@ -583,11 +674,22 @@ impl Service {
                                     match expire_predicates_for_block(
                                         &Chain::Stacks,
                                         confirmed_block.block_identifier.index,
-                                        &mut predicates_db_conn,
+                                        config.expired_predicates_retention_secs,
+                                        predicates_db_conn.as_mut(),
                                         &ctx,
                                     ) {
                                         Some(expired_predicate_uuids) => {
                                             for uuid in expired_predicate_uuids.into_iter() {
+                                                record_audit_log_entry(
+                                                    &self.config.audit_log,
+                                                    AuditLogEntry::new(
+                                                        AuditEventType::Expired,
+                                                        &uuid,
+                                                        Some(&Chain::Stacks),
+                                                        AuditSource::Service,
+                                                    ),
+                                                    &self.ctx,
+                                                );
                                                 let _ = observer_command_tx.send(
                                                     ObserverCommand::ExpireStacksPredicate(
                                                         HookExpirationData {
@@ -609,11 +711,22 @@ impl Service {
                                     match expire_predicates_for_block(
                                         &Chain::Stacks,
                                         confirmed_block.block_identifier.index,
-                                        &mut predicates_db_conn,
+                                        config.expired_predicates_retention_secs,
+                                        predicates_db_conn.as_mut(),
                                         &ctx,
                                     ) {
                                         Some(expired_predicate_uuids) => {
                                             for uuid in expired_predicate_uuids.into_iter() {
+                                                record_audit_log_entry(
+                                                    &self.config.audit_log,
+                                                    AuditLogEntry::new(
+                                                        AuditEventType::Expired,
+                                                        &uuid,
+                                                        Some(&Chain::Stacks),
+                                                        AuditSource::Service,
+                                                    ),
+                                                    &self.ctx,
+                                                );
                                                 let _ = observer_command_tx.send(
                                                     ObserverCommand::ExpireStacksPredicate(
                                                         HookExpirationData {
@@ -636,7 +749,7 @@ impl Service {
                         update_status_from_report(
                             Chain::Stacks,
                             report,
-                            &mut predicates_db_conn,
+                            predicates_db_conn.as_mut(),
                             &ctx,
                         );
                     };
@@ -657,7 +770,7 @@ impl Service {
                                         "Failed to update database from archive: {e}"
                                     )
                                 }
-                                Ok(()) => {}
+                                Ok(_) => {}
                             };
                         }
                     }
@@ -668,14 +781,14 @@ impl Service {
                 }) => {
                     if let PredicatesApi::On(ref config) = self.config.http_api {
                         let Ok(mut predicates_db_conn) =
-                            open_readwrite_predicates_db_conn_verbose(&config, &ctx)
+                            open_predicate_store_for_key_verbose(&config, &predicate_key, &ctx)
                         else {
                             continue;
                         };
                         set_predicate_interrupted_status(
                             error,
                             &predicate_key,
-                            &mut predicates_db_conn,
+                            predicates_db_conn.as_mut(),
                             &ctx,
                         );
                     }
@@ -714,6 +827,137 @@ pub enum PredicateStatus {
     New,
 }
 
+impl PredicateStatus {
+    /// Returns the `(number_of_blocks_evaluated, number_of_times_triggered)` counters of the status, if any.
+    pub fn get_counters(&self) -> Option<(u64, u64)> {
+        match self {
+            PredicateStatus::Scanning(ScanningData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            })
+            | PredicateStatus::Streaming(StreamingData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            })
+            | PredicateStatus::UnconfirmedExpiration(ExpiredData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            })
+            | PredicateStatus::ConfirmedExpiration(ExpiredData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            }) => Some((*number_of_blocks_evaluated, *number_of_times_triggered)),
+            PredicateStatus::Interrupted(_) | PredicateStatus::New => None,
+        }
+    }
+
+    /// Returns whether the status is final: once interrupted or expired, a predicate isn't
+    /// evaluated anymore.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            PredicateStatus::Interrupted(_) | PredicateStatus::ConfirmedExpiration(_)
+        )
+    }
+
+    fn set_counters(&mut self, blocks_evaluated: u64, times_triggered: u64) {
+        match self {
+            PredicateStatus::Scanning(ScanningData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            })
+            | PredicateStatus::Streaming(StreamingData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            })
+            | PredicateStatus::UnconfirmedExpiration(ExpiredData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            })
+            | PredicateStatus::ConfirmedExpiration(ExpiredData {
+                number_of_blocks_evaluated,
+                number_of_times_triggered,
+                ..
+            }) => {
+                *number_of_blocks_evaluated = blocks_evaluated;
+                *number_of_times_triggered = times_triggered;
+            }
+            PredicateStatus::Interrupted(_) | PredicateStatus::New => {}
+        }
+    }
+
+    /// Sets the lag of a `Streaming` status, from the tip of `chain` known by the observer.
+    pub fn set_streaming_lag(&mut self, chain: &Chain, chain_tips: &ChainTips) {
+        if let PredicateStatus::Streaming(data) = self {
+            data.lag = chain_tips.get_lag(chain, data.last_evaluated_block_height);
+        }
+    }
+}
+
+/// Overrides the counters of `status` with the ones stored in their own fields of the predicate entry.
+fn apply_predicate_status_counters(
+    status: &mut PredicateStatus,
+    number_of_blocks_evaluated: Option<u64>,
+    number_of_times_triggered: Option<u64>,
+) {
+    let Some((blocks_evaluated, times_triggered)) = status.get_counters() else {
+        return;
+    };
+    status.set_counters(
+        number_of_blocks_evaluated.unwrap_or(blocks_evaluated),
+        number_of_times_triggered.unwrap_or(times_triggered),
+    );
+}
+
+/// Latest blocks evaluated by the service, reported by the diagnostics endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ChainTips {
+    pub bitcoin: Option<BlockIdentifier>,
+    pub stacks: Option<BlockIdentifier>,
+}
+
+impl ChainTips {
+    pub fn update_with_bitcoin_chain_event(&mut self, chain_event: &BitcoinChainEvent) {
+        let new_tip = match chain_event {
+            BitcoinChainEvent::ChainUpdatedWithBlocks(data) => data.new_blocks.last(),
+            BitcoinChainEvent::ChainUpdatedWithReorg(data) => data.blocks_to_apply.last(),
+        };
+        if let Some(block) = new_tip {
+            self.bitcoin = Some(block.block_identifier.clone());
+        }
+    }
+
+    pub fn update_with_stacks_chain_event(&mut self, chain_event: &StacksChainEvent) {
+        let new_tip = match chain_event {
+            StacksChainEvent::ChainUpdatedWithBlocks(data) => data.new_blocks.last(),
+            StacksChainEvent::ChainUpdatedWithReorg(data) => data.blocks_to_apply.last(),
+            StacksChainEvent::ChainUpdatedWithMicroblocks(_)
+            | StacksChainEvent::ChainUpdatedWithMicroblocksReorg(_) => None,
+        };
+        if let Some(update) = new_tip {
+            self.stacks = Some(update.block.block_identifier.clone());
+        }
+    }
+
+    /// Returns the number of blocks between the tip of `chain` and `block_height`, or `None` if
+    /// no block of `chain` was observed yet.
+    pub fn get_lag(&self, chain: &Chain, block_height: u64) -> Option<u64> {
+        let tip = match chain {
+            Chain::Bitcoin => &self.bitcoin,
+            Chain::Stacks => &self.stacks,
+        };
+        tip.as_ref()
+            .map(|tip| tip.index.saturating_sub(block_height))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct ScanningData {
     pub number_of_blocks_to_scan: u64,
@@ -730,6 +974,11 @@ pub struct StreamingData {
     pub number_of_times_triggered: u64,
     pub number_of_blocks_evaluated: u64,
     pub last_evaluated_block_height: u64,
+    /// Number of blocks between the chain tip known by the observer and
+    /// `last_evaluated_block_height`. It is computed when the status is retrieved rather than
+    /// stored, and is unset while the tip is unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lag: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -741,54 +990,113 @@ pub struct ExpiredData {
     pub expired_at_block_height: u64,
 }
 
+/// The streaming status changes of a predicate resulting from a chain event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamingStatusUpdate {
+    /// Heights of the applied blocks on which the predicate triggered.
+    pub triggered_heights: Vec<u64>,
+    /// Number of applied blocks evaluated without triggering the predicate.
+    pub evaluated_count: u64,
+    /// Height of the last block evaluated once the event is applied.
+    pub last_evaluated_height: Option<u64>,
+}
+
+/// Reduces a report to the final status changes of each predicate, so that a re-org rolling back
+/// and applying blocks for the same predicate results in a single consistent status:
+/// - applied blocks win over rolled back ones, and the last evaluated height is the highest applied
+///   block, whether the predicate triggered on it or not
+/// - blocks rolled back without any block applied lower the last evaluated height below them
+/// - blocks on which the predicate expired are left to the expiration status
+pub fn get_streaming_status_updates(
+    report: &PredicateEvaluationReport,
+) -> BTreeMap<String, StreamingStatusUpdate> {
+    let mut updates: BTreeMap<String, StreamingStatusUpdate> = BTreeMap::new();
+    for (predicate_uuid, blocks_ids) in report.predicates_triggered.iter() {
+        let update = updates.entry(predicate_uuid.clone()).or_default();
+        update.triggered_heights = blocks_ids.iter().map(|b| b.index).collect();
+        update.last_evaluated_height = blocks_ids.last().map(|b| b.index);
+    }
+    for (predicate_uuid, blocks_ids) in report.predicates_evaluated.iter() {
+        // any triggered or expired predicate was also evaluated, but these blocks are already
+        // accounted for
+        let triggered_block_ids = report.predicates_triggered.get(predicate_uuid);
+        let expired_block_ids = report.predicates_expired.get(predicate_uuid);
+        let evaluated_block_ids = blocks_ids
+            .iter()
+            .filter(|b| !triggered_block_ids.map_or(false, |ids| ids.contains(b)))
+            .filter(|b| !expired_block_ids.map_or(false, |ids| ids.contains(b)))
+            .collect::<Vec<_>>();
+        let Some(last_evaluated_block) = evaluated_block_ids.last() else {
+            continue;
+        };
+        let update = updates.entry(predicate_uuid.clone()).or_default();
+        update.evaluated_count = evaluated_block_ids.len().try_into().unwrap_or(0);
+        update.last_evaluated_height = update
+            .last_evaluated_height
+            .max(Some(last_evaluated_block.index));
+    }
+    for (predicate_uuid, blocks_ids) in report.predicates_rolled_back.iter() {
+        if updates.contains_key(predicate_uuid)
+            || report.predicates_expired.contains_key(predicate_uuid)
+        {
+            continue;
+        }
+        if let Some(lowest_rolled_back_block) = blocks_ids.first() {
+            updates.insert(
+                predicate_uuid.clone(),
+                StreamingStatusUpdate {
+                    triggered_heights: vec![],
+                    evaluated_count: 0,
+                    last_evaluated_height: Some(lowest_rolled_back_block.index.saturating_sub(1)),
+                },
+            );
+        }
+    }
+    updates
+}
+
 fn update_status_from_report(
     chain: Chain,
     report: PredicateEvaluationReport,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
-    for (predicate_uuid, blocks_ids) in report.predicates_triggered.iter() {
-        if let Some(last_triggered_height) = blocks_ids.last().and_then(|b| Some(b.index)) {
-            let triggered_count = blocks_ids.len().try_into().unwrap_or(0);
+    for (predicate_uuid, update) in get_streaming_status_updates(&report).into_iter() {
+        let predicate_key = ChainhookInstance::either_stx_or_btc_key(&predicate_uuid);
+        if let Some(last_triggered_height) = update.triggered_heights.last() {
+            let triggered_count = update.triggered_heights.len().try_into().unwrap_or(0);
             set_predicate_streaming_status(
                 StreamingDataType::Occurrence {
-                    last_triggered_height,
+                    last_triggered_height: *last_triggered_height,
                     triggered_count,
                 },
-                &(ChainhookInstance::either_stx_or_btc_key(predicate_uuid)),
+                &predicate_key,
                 predicates_db_conn,
                 &ctx,
             );
-        }
-    }
-
-    for (predicate_uuid, blocks_ids) in report.predicates_evaluated.iter() {
-        // clone so we don't actually update the report
-        let mut blocks_ids = blocks_ids.clone();
-        // any triggered or expired predicate was also evaluated. But we already updated the status for that block,
-        // so remove those matching blocks from the list of evaluated predicates
-        if let Some(triggered_block_ids) = report.predicates_triggered.get(predicate_uuid) {
-            for triggered_id in triggered_block_ids {
-                blocks_ids.remove(triggered_id);
-            }
-        }
-        if let Some(expired_block_ids) = report.predicates_expired.get(predicate_uuid) {
-            for expired_id in expired_block_ids {
-                blocks_ids.remove(expired_id);
-            }
-        }
-        if let Some(last_evaluated_height) = blocks_ids.last().and_then(|b| Some(b.index)) {
-            let evaluated_count = blocks_ids.len().try_into().unwrap_or(0);
-            set_predicate_streaming_status(
-                StreamingDataType::Evaluation {
-                    last_evaluated_height,
-                    evaluated_count,
-                },
-                &(ChainhookInstance::either_stx_or_btc_key(predicate_uuid)),
+            insert_triggered_block_heights(
+                &predicate_key,
+                &update.triggered_heights,
                 predicates_db_conn,
                 &ctx,
             );
         }
+        // written last, so that the status ends up reflecting the final tip
+        if let Some(last_evaluated_height) = update.last_evaluated_height {
+            if update.evaluated_count > 0
+                || update.triggered_heights.last() != Some(&last_evaluated_height)
+            {
+                set_predicate_streaming_status(
+                    StreamingDataType::Evaluation {
+                        last_evaluated_height,
+                        evaluated_count: update.evaluated_count,
+                    },
+                    &predicate_key,
+                    predicates_db_conn,
+                    &ctx,
+                );
+            }
+        }
     }
     for (predicate_uuid, blocks_ids) in report.predicates_expired.iter() {
         if let Some(last_evaluated_height) = blocks_ids.last().and_then(|b| Some(b.index)) {
@@ -808,7 +1116,7 @@ fn update_status_from_report(
 fn set_predicate_interrupted_status(
     error: String,
     predicate_key: &str,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
     let status = PredicateStatus::Interrupted(error);
@@ -836,7 +1144,7 @@ pub enum StreamingDataType {
 fn set_predicate_streaming_status(
     streaming_data_type: StreamingDataType,
     predicate_key: &str,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
     let now_secs = SystemTime::now()
@@ -858,6 +1166,7 @@ fn set_predicate_streaming_status(
                     number_of_times_triggered,
                     last_evaluated_block_height,
                     last_evaluation: _,
+                    lag: _,
                 }) => (
                     last_occurrence,
                     number_of_blocks_evaluated,
@@ -897,119 +1206,188 @@ fn set_predicate_streaming_status(
             None => (None, 0, 0, 0),
         }
     };
-    let (
-        last_occurrence,
-        number_of_times_triggered,
-        number_of_blocks_evaluated,
-        last_evaluated_block_height,
-    ) = match streaming_data_type {
-        StreamingDataType::Occurrence {
-            last_triggered_height,
-            triggered_count,
-        } => (
-            Some(now_secs.clone()),
-            number_of_times_triggered + triggered_count,
-            number_of_blocks_evaluated + triggered_count,
-            last_triggered_height,
-        ),
-        StreamingDataType::Evaluation {
-            last_evaluated_height,
-            evaluated_count,
-        } => (
-            last_occurrence,
-            number_of_times_triggered,
-            number_of_blocks_evaluated + evaluated_count,
-            last_evaluated_height,
-        ),
-        StreamingDataType::FinishedScanning => (
-            last_occurrence,
-            number_of_times_triggered,
-            number_of_blocks_evaluated,
-            last_evaluated_block_height,
-        ),
+    let (last_occurrence, triggered_increment, evaluated_increment, last_evaluated_block_height) =
+        match streaming_data_type {
+            StreamingDataType::Occurrence {
+                last_triggered_height,
+                triggered_count,
+            } => (
+                Some(now_secs.clone()),
+                triggered_count,
+                triggered_count,
+                last_triggered_height,
+            ),
+            StreamingDataType::Evaluation {
+                last_evaluated_height,
+                evaluated_count,
+            } => (last_occurrence, 0, evaluated_count, last_evaluated_height),
+            StreamingDataType::FinishedScanning => {
+                (last_occurrence, 0, 0, last_evaluated_block_height)
+            }
+        };
+
+    // Counters are incremented by the predicates db rather than in a read-modify-write of the status,
+    // so that concurrent updates of the same predicate (e.g. a scan finishing while streaming starts)
+    // are not lost.
+    let (number_of_blocks_evaluated, number_of_times_triggered) = match predicates_db_conn
+        .increment_status_counters(
+            predicate_key,
+            (number_of_blocks_evaluated, number_of_times_triggered),
+            (evaluated_increment, triggered_increment),
+        ) {
+        Ok(counters) => counters,
+        Err(e) => {
+            warn!(
+                ctx.expect_logger(),
+                "Error updating status counters for {}: {}", predicate_key, e
+            );
+            return;
+        }
     };
 
-    update_predicate_status(
+    write_predicate_status(
         predicate_key,
-        PredicateStatus::Streaming(StreamingData {
+        &PredicateStatus::Streaming(StreamingData {
             last_occurrence,
             last_evaluation: now_secs,
             number_of_times_triggered,
             last_evaluated_block_height,
             number_of_blocks_evaluated,
+            lag: None,
         }),
+        false,
         predicates_db_conn,
         &ctx,
     );
 }
 
-/// Updates a predicate's status to `Scanning`.
+/// Number of scanning status updates served from memory before the cached status of a
+/// predicate is read back from the predicates db.
+const SCANNING_STATUS_RECONCILIATION_INTERVAL: u64 = 100;
+
+/// Keeps the last known status of a predicate being scanned in memory.
 ///
-/// Sets the `last_occurrence` time to the current time if a new trigger has occurred since the last status update.
-pub fn set_predicate_scanning_status(
-    predicate_key: &str,
-    number_of_blocks_to_scan: u64,
-    number_of_blocks_evaluated: u64,
-    number_of_times_triggered: u64,
-    current_block_height: u64,
-    predicates_db_conn: &mut Connection,
-    ctx: &Context,
-) {
-    let now_secs = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Could not get current time in ms")
-        .as_secs();
-    let current_status = retrieve_predicate_status(&predicate_key, predicates_db_conn);
-    let last_occurrence = match current_status {
-        Some(status) => match status {
-            PredicateStatus::Scanning(scanning_data) => {
-                if number_of_times_triggered > scanning_data.number_of_times_triggered {
-                    Some(now_secs)
-                } else {
-                    scanning_data.last_occurrence
+/// Updating the `Scanning` status requires the previous status in order to compute
+/// `last_occurrence`. During a scan, that status is the one written by the previous update,
+/// so it is served from memory and only periodically reconciled with the predicates db.
+pub struct ScanningStatusCache {
+    predicate_key: String,
+    last_known_status: Option<PredicateStatus>,
+    updates_since_reconciliation: u64,
+    number_of_status_reads: u64,
+}
+
+impl ScanningStatusCache {
+    pub fn new(predicate_key: &str) -> ScanningStatusCache {
+        ScanningStatusCache {
+            predicate_key: predicate_key.to_string(),
+            last_known_status: None,
+            updates_since_reconciliation: 0,
+            number_of_status_reads: 0,
+        }
+    }
+
+    /// Returns the number of times the status was read from the predicates db.
+    #[cfg(test)]
+    pub fn get_number_of_status_reads(&self) -> u64 {
+        self.number_of_status_reads
+    }
+
+    /// Updates a predicate's status to `Scanning`.
+    ///
+    /// Sets the `last_occurrence` time to the current time if a new trigger has occurred since the last status update.
+    pub fn set_predicate_scanning_status(
+        &mut self,
+        number_of_blocks_to_scan: u64,
+        number_of_blocks_evaluated: u64,
+        number_of_times_triggered: u64,
+        current_block_height: u64,
+        predicates_db_conn: &mut dyn PredicateStore,
+        ctx: &Context,
+    ) {
+        if self.last_known_status.is_none()
+            || self.updates_since_reconciliation >= SCANNING_STATUS_RECONCILIATION_INTERVAL
+        {
+            self.last_known_status =
+                retrieve_predicate_status(&self.predicate_key, predicates_db_conn);
+            self.number_of_status_reads += 1;
+            self.updates_since_reconciliation = 0;
+        }
+        self.updates_since_reconciliation += 1;
+
+        let now_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not get current time in ms")
+            .as_secs();
+        let last_occurrence = match &self.last_known_status {
+            Some(status) => match status {
+                PredicateStatus::Scanning(scanning_data) => {
+                    if number_of_times_triggered > scanning_data.number_of_times_triggered {
+                        Some(now_secs)
+                    } else {
+                        scanning_data.last_occurrence
+                    }
                 }
-            }
-            PredicateStatus::Streaming(streaming_data) => {
-                if number_of_times_triggered > streaming_data.number_of_times_triggered {
-                    Some(now_secs)
-                } else {
-                    streaming_data.last_occurrence
+                PredicateStatus::Streaming(streaming_data) => {
+                    if number_of_times_triggered > streaming_data.number_of_times_triggered {
+                        Some(now_secs)
+                    } else {
+                        streaming_data.last_occurrence
+                    }
                 }
-            }
-            PredicateStatus::UnconfirmedExpiration(expired_data) => {
-                if number_of_times_triggered > expired_data.number_of_times_triggered {
-                    Some(now_secs)
-                } else {
-                    expired_data.last_occurrence
+                PredicateStatus::UnconfirmedExpiration(expired_data) => {
+                    if number_of_times_triggered > expired_data.number_of_times_triggered {
+                        Some(now_secs)
+                    } else {
+                        expired_data.last_occurrence
+                    }
                 }
-            }
-            PredicateStatus::New => {
-                if number_of_times_triggered > 0 {
-                    Some(now_secs)
-                } else {
-                    None
+                PredicateStatus::New => {
+                    if number_of_times_triggered > 0 {
+                        Some(now_secs)
+                    } else {
+                        None
+                    }
                 }
-            }
-            PredicateStatus::ConfirmedExpiration(_) | PredicateStatus::Interrupted(_) => {
-                warn!(ctx.expect_logger(), "Attempting to set Scanning status when previous status was {:?} for predicate {}", status, predicate_key);
-                return;
-            }
-        },
-        None => None,
-    };
+                PredicateStatus::ConfirmedExpiration(_) | PredicateStatus::Interrupted(_) => {
+                    warn!(ctx.expect_logger(), "Attempting to set Scanning status when previous status was {:?} for predicate {}", status, self.predicate_key);
+                    return;
+                }
+            },
+            None => None,
+        };
 
-    update_predicate_status(
-        predicate_key,
-        PredicateStatus::Scanning(ScanningData {
+        let status = PredicateStatus::Scanning(ScanningData {
             number_of_blocks_to_scan,
             number_of_blocks_evaluated,
             number_of_times_triggered,
             last_occurrence,
             last_evaluated_block_height: current_block_height,
-        }),
-        predicates_db_conn,
-        &ctx,
-    );
+        });
+        // the predicate may have been interrupted or expired by another writer since the cached
+        // status was read: it is only overwritten if it is still not final
+        match predicates_db_conn.update_status_unless_final(&self.predicate_key, &status) {
+            Ok(true) => {
+                notify_predicate_status_update(&self.predicate_key, &status, ctx);
+                self.last_known_status = Some(status);
+            }
+            Ok(false) => {
+                warn!(
+                    ctx.expect_logger(),
+                    "Not setting Scanning status for predicate {}, which was interrupted or expired",
+                    self.predicate_key
+                );
+                // reconciled on the next update
+                self.last_known_status = None;
+            }
+            Err(e) => {
+                warn!(
+                    ctx.expect_logger(),
+                    "Error updating status for {}: {}", self.predicate_key, e
+                );
+            }
+        }
+    }
 }
 
 /// Updates a predicate's status to `UnconfirmedExpiration`.
@@ -1018,7 +1396,7 @@ pub fn set_unconfirmed_expiration_status(
     number_of_new_blocks_evaluated: u64,
     last_evaluated_block_height: u64,
     predicate_key: &str,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
     let current_status = retrieve_predicate_status(&predicate_key, predicates_db_conn);
@@ -1049,6 +1427,7 @@ pub fn set_unconfirmed_expiration_status(
                 number_of_times_triggered,
                 number_of_blocks_evaluated,
                 last_evaluated_block_height,
+                lag: _,
             }) => (
                 number_of_blocks_evaluated + number_of_new_blocks_evaluated,
                 number_of_times_triggered,
@@ -1101,9 +1480,12 @@ pub fn set_unconfirmed_expiration_status(
     }
 }
 
+/// Marks the predicate as expired. When `expired_predicates_retention_secs` is set, the predicate
+/// is then evicted from the database once the retention window is over.
 pub fn set_confirmed_expiration_status(
     predicate_key: &str,
-    predicates_db_conn: &mut Connection,
+    expired_predicates_retention_secs: Option<u64>,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
     let current_status = retrieve_predicate_status(&predicate_key, predicates_db_conn);
@@ -1124,30 +1506,145 @@ pub fn set_confirmed_expiration_status(
             return;
         }
     };
+    let status = PredicateStatus::ConfirmedExpiration(expired_data);
+    update_predicate_status(predicate_key, status.clone(), predicates_db_conn, &ctx);
+    match predicates_db_conn.get_entry(predicate_key) {
+        Ok(Some((spec, _))) => notify_predicate_expiration(&spec, &status, ctx),
+        Ok(None) => warn!(
+            ctx.expect_logger(),
+            "Unable to notify expiration of predicate {}: specification not found", predicate_key
+        ),
+        Err(e) => warn!(
+            ctx.expect_logger(),
+            "Unable to notify expiration of predicate {}: {}", predicate_key, e
+        ),
+    }
+    if let Some(retention_secs) = expired_predicates_retention_secs {
+        if let Err(e) = predicates_db_conn.set_eviction(&predicate_key, retention_secs) {
+            warn!(
+                ctx.expect_logger(),
+                "Error setting expiry for {}: {}", predicate_key, e
+            );
+        }
+    }
+}
+
+/// Posts the uuid and the final `status` of an expired predicate to its `on_expire_url`, if any.
+/// The request is sent from its own thread, so that a slow consumer doesn't hold the evaluation
+/// of other predicates.
+pub fn notify_predicate_expiration(
+    spec: &ChainhookInstance,
+    status: &PredicateStatus,
+    ctx: &Context,
+) {
+    let Some(url) = spec.on_expire_url() else {
+        return;
+    };
+    let url = url.to_string();
+    let uuid = spec.uuid().to_string();
+    let payload = json!({
+        "uuid": uuid,
+        "status": status,
+    });
+    let moved_ctx = ctx.clone();
+    let res = hiro_system_kit::thread_named("Predicate expiration callback").spawn(move || {
+        let res = reqwest::blocking::Client::new()
+            .post(&url)
+            .timeout(std::time::Duration::from_secs(10))
+            .json(&payload)
+            .send()
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = res {
+            warn!(
+                moved_ctx.expect_logger(),
+                "Unable to notify expiration of predicate {} to {}: {}", uuid, url, e
+            );
+        }
+    });
+    if let Err(e) = res {
+        warn!(
+            ctx.expect_logger(),
+            "Unable to spawn expiration callback thread: {}",
+            e.to_string()
+        );
+    }
+}
+
+/// Forces a predicate into the `ConfirmedExpiration` status, whatever its current status, and
+/// returns the height it expired at. Used by operators to retire predicates stuck in `Scanning`.
+pub fn set_expired_status(
+    predicate_key: &str,
+    expired_predicates_retention_secs: Option<u64>,
+    predicates_db_conn: &mut dyn PredicateStore,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let expired_data = match retrieve_predicate_status(&predicate_key, predicates_db_conn) {
+        Some(PredicateStatus::Scanning(ScanningData {
+            number_of_blocks_to_scan: _,
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+            last_occurrence,
+            last_evaluated_block_height,
+        }))
+        | Some(PredicateStatus::Streaming(StreamingData {
+            last_occurrence,
+            last_evaluation: _,
+            number_of_times_triggered,
+            number_of_blocks_evaluated,
+            last_evaluated_block_height,
+            lag: _,
+        })) => ExpiredData {
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+            last_occurrence,
+            last_evaluated_block_height,
+            expired_at_block_height: last_evaluated_block_height,
+        },
+        Some(PredicateStatus::UnconfirmedExpiration(expired_data)) => expired_data,
+        Some(PredicateStatus::ConfirmedExpiration(expired_data)) => {
+            return Ok(expired_data.expired_at_block_height)
+        }
+        Some(PredicateStatus::New) | Some(PredicateStatus::Interrupted(_)) => ExpiredData {
+            number_of_blocks_evaluated: 0,
+            number_of_times_triggered: 0,
+            last_occurrence: None,
+            last_evaluated_block_height: 0,
+            expired_at_block_height: 0,
+        },
+        None => return Err(format!("no status found for predicate {}", predicate_key)),
+    };
+    let expired_at_block_height = expired_data.expired_at_block_height;
     update_predicate_status(
         predicate_key,
-        PredicateStatus::ConfirmedExpiration(expired_data),
+        PredicateStatus::UnconfirmedExpiration(expired_data),
         predicates_db_conn,
         &ctx,
     );
+    set_confirmed_expiration_status(
+        predicate_key,
+        expired_predicates_retention_secs,
+        predicates_db_conn,
+        ctx,
+    );
+    Ok(expired_at_block_height)
 }
 
-fn get_predicate_expiration_key(chain: &Chain, block_height: u64) -> String {
-    match chain {
-        Chain::Bitcoin => format!("expires_at:bitcoin_block:{}", block_height),
-        Chain::Stacks => format!("expires_at:stacks_block:{}", block_height),
-    }
-}
 fn expire_predicates_for_block(
     chain: &Chain,
     confirmed_block_index: u64,
-    predicates_db_conn: &mut Connection,
+    expired_predicates_retention_secs: Option<u64>,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) -> Option<Vec<String>> {
     match get_predicates_expiring_at_block(chain, confirmed_block_index, predicates_db_conn, ctx) {
         Some(predicates_to_expire) => {
             for predicate_key in predicates_to_expire.iter() {
-                set_confirmed_expiration_status(predicate_key, predicates_db_conn, ctx);
+                set_confirmed_expiration_status(
+                    predicate_key,
+                    expired_predicates_retention_secs,
+                    predicates_db_conn,
+                    ctx,
+                );
             }
             Some(predicates_to_expire)
         }
@@ -1159,22 +1656,15 @@ fn insert_predicate_expiration(
     chain: &Chain,
     expired_at_block_height: u64,
     predicate_key: &str,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
-    let key = get_predicate_expiration_key(chain, expired_at_block_height);
-    let mut predicates_expiring_at_block =
-        get_predicates_expiring_at_block(chain, expired_at_block_height, predicates_db_conn, &ctx)
-            .unwrap_or(vec![]);
-    predicates_expiring_at_block.push(predicate_key.to_owned());
-    let serialized_expiring_predicates = json!(predicates_expiring_at_block).to_string();
     if let Err(e) =
-        predicates_db_conn.hset::<_, _, _, ()>(&key, "predicates", &serialized_expiring_predicates)
+        predicates_db_conn.insert_expiration(chain, expired_at_block_height, predicate_key)
     {
         warn!(
             ctx.expect_logger(),
-            "Error updating expired predicates index: {}",
-            e.to_string()
+            "Error updating expired predicates index: {}", e
         );
     } else {
         debug!(
@@ -1187,105 +1677,226 @@ fn insert_predicate_expiration(
 fn get_predicates_expiring_at_block(
     chain: &Chain,
     block_index: u64,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) -> Option<Vec<String>> {
-    let key = get_predicate_expiration_key(chain, block_index);
-    match predicates_db_conn.hget::<_, _, String>(key.to_string(), "predicates") {
-        Ok(ref payload) => match serde_json::from_str(payload) {
-            Ok(data) => {
-                if let Err(e) = predicates_db_conn.hdel::<_, _, u64>(key.to_string(), "predicates")
-                {
-                    warn!(
-                        ctx.expect_logger(),
-                        "Error removing expired predicates index: {}",
-                        e.to_string()
-                    );
-                }
-                Some(data)
-            }
-            Err(_) => None,
-        },
-        Err(_) => None,
+    match predicates_db_conn.take_expirations(chain, block_index) {
+        Ok(predicates_to_expire) if !predicates_to_expire.is_empty() => Some(predicates_to_expire),
+        Ok(_) => None,
+        Err(e) => {
+            warn!(
+                ctx.expect_logger(),
+                "Error retrieving expired predicates index: {}", e
+            );
+            None
+        }
     }
 }
 
-pub fn update_predicate_status(
+/// Records the heights of the blocks on which the predicate `predicate_key` triggered, so that
+/// they can later be replayed with [crate::scan::common::replay_triggered_blocks].
+pub fn insert_triggered_block_heights(
     predicate_key: &str,
-    status: PredicateStatus,
-    predicates_db_conn: &mut Connection,
+    block_heights: &[u64],
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
-    let serialized_status = json!(status).to_string();
-    if let Err(e) =
-        predicates_db_conn.hset::<_, _, _, ()>(&predicate_key, "status", &serialized_status)
+    if block_heights.is_empty() {
+        return;
+    }
+    if let Err(e) = predicates_db_conn.insert_triggered_block_heights(predicate_key, block_heights)
     {
         warn!(
             ctx.expect_logger(),
-            "Error updating status for {}: {}",
-            predicate_key,
-            e.to_string()
+            "Error updating triggered blocks for {}: {}", predicate_key, e
         );
-    } else {
-        debug!(
+    }
+}
+
+/// Number of status updates buffered for each subscriber. Slower subscribers skip the oldest updates.
+const PREDICATE_STATUS_UPDATES_CAPACITY: usize = 256;
+
+/// The `(predicate_key, status)` pairs written to the predicates db, broadcasted to the subscribers
+/// of [subscribe_to_predicate_status_updates].
+static PREDICATE_STATUS_UPDATES: OnceLock<broadcast::Sender<(String, PredicateStatus)>> =
+    OnceLock::new();
+
+fn get_predicate_status_updates_tx() -> &'static broadcast::Sender<(String, PredicateStatus)> {
+    PREDICATE_STATUS_UPDATES.get_or_init(|| broadcast::channel(PREDICATE_STATUS_UPDATES_CAPACITY).0)
+}
+
+/// Subscribes to the status updates of every predicate, received as they are written to the predicates db.
+pub fn subscribe_to_predicate_status_updates() -> broadcast::Receiver<(String, PredicateStatus)> {
+    get_predicate_status_updates_tx().subscribe()
+}
+
+pub fn update_predicate_status(
+    predicate_key: &str,
+    status: PredicateStatus,
+    predicates_db_conn: &mut dyn PredicateStore,
+    ctx: &Context,
+) {
+    write_predicate_status(predicate_key, &status, true, predicates_db_conn, ctx);
+}
+
+/// Writes the `status` field of a predicate entry. Unless they were already incremented by the caller,
+/// the counter fields are overwritten with the ones of `status` in the same transaction.
+fn write_predicate_status(
+    predicate_key: &str,
+    status: &PredicateStatus,
+    overwrite_counters: bool,
+    predicates_db_conn: &mut dyn PredicateStore,
+    ctx: &Context,
+) {
+    if let Err(e) = predicates_db_conn.update_status(predicate_key, status, overwrite_counters) {
+        warn!(
             ctx.expect_logger(),
-            "Updating predicate {predicate_key} status: {serialized_status}"
+            "Error updating status for {}: {}", predicate_key, e
         );
+    } else {
+        notify_predicate_status_update(predicate_key, status, ctx);
     }
 }
 
+/// Logs a status written to the predicates db, and broadcasts it to the status subscribers.
+fn notify_predicate_status_update(predicate_key: &str, status: &PredicateStatus, ctx: &Context) {
+    debug!(
+        ctx.expect_logger(),
+        "Updating predicate {predicate_key} status: {}",
+        json!(status)
+    );
+    // sending only fails when nobody is subscribed
+    let _ = get_predicate_status_updates_tx().send((predicate_key.to_string(), status.clone()));
+}
+
 fn update_predicate_spec(
     predicate_key: &str,
     spec: &ChainhookInstance,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
     ctx: &Context,
 ) {
-    let serialized_spec = json!(spec).to_string();
-    if let Err(e) =
-        predicates_db_conn.hset::<_, _, _, ()>(&predicate_key, "specification", &serialized_spec)
-    {
+    if let Err(e) = predicates_db_conn.update_spec(predicate_key, spec) {
         warn!(
             ctx.expect_logger(),
-            "Error updating status for {}: {}",
-            predicate_key,
-            e.to_string()
+            "Error updating spec for {}: {}", predicate_key, e
         );
     } else {
         debug!(
             ctx.expect_logger(),
-            "Updating predicate {predicate_key} with spec: {serialized_spec}"
+            "Updating predicate {predicate_key} with spec: {}",
+            json!(spec)
         );
     }
 }
 
 fn retrieve_predicate_status(
     predicate_key: &str,
-    predicates_db_conn: &mut Connection,
+    predicates_db_conn: &mut dyn PredicateStore,
 ) -> Option<PredicateStatus> {
-    match predicates_db_conn.hget::<_, _, String>(predicate_key.to_string(), "status") {
-        Ok(ref payload) => match serde_json::from_str(payload) {
-            Ok(data) => Some(data),
-            Err(_) => None,
-        },
-        Err(_) => None,
-    }
+    predicates_db_conn.get_status(predicate_key).ok().flatten()
 }
 
-pub fn open_readwrite_predicates_db_conn(
-    config: &PredicatesApiConfig,
-) -> Result<Connection, String> {
-    let redis_uri = &config.database_uri;
-    let client = redis::Client::open(redis_uri.clone()).unwrap();
+fn open_readwrite_predicates_db_conn_at_uri(redis_uri: &str) -> Result<Connection, String> {
+    let client = redis::Client::open(redis_uri)
+        .map_err(|e| format!("unable to open db {}: {}", redis_uri, e.to_string()))?;
     client
         .get_connection()
         .map_err(|e| format!("unable to connect to db: {}", e.to_string()))
 }
 
-pub fn open_readwrite_predicates_db_conn_verbose(
+/// Opens the predicates db at `database_uri`, using the backend selected in `config`.
+fn open_predicate_store_at_uri(
+    config: &PredicatesApiConfig,
+    database_uri: &str,
+) -> Result<Box<dyn PredicateStore>, String> {
+    match config.database_backend {
+        PredicatesDatabaseBackend::Redis => Ok(Box::new(open_readwrite_predicates_db_conn_at_uri(
+            database_uri,
+        )?)),
+        PredicatesDatabaseBackend::Sqlite => {
+            Ok(Box::new(SqlitePredicateStore::open(database_uri)?))
+        }
+    }
+}
+
+/// Opens the predicates db storing the predicates of `chain`.
+pub fn open_predicate_store(
+    config: &PredicatesApiConfig,
+    chain: &Chain,
+) -> Result<Box<dyn PredicateStore>, String> {
+    open_predicate_store_at_uri(config, config.get_database_uri(chain))
+}
+
+/// Opens every predicates db.
+pub fn open_predicate_stores(
+    config: &PredicatesApiConfig,
+) -> Result<Vec<Box<dyn PredicateStore>>, String> {
+    config
+        .get_database_uris()
+        .into_iter()
+        .map(|database_uri| open_predicate_store_at_uri(config, database_uri))
+        .collect()
+}
+
+/// Looks up which of the predicates dbs stores `predicate_key`, or returns `None` if the
+/// predicate can't be found.
+pub fn find_predicate_store(
+    config: &PredicatesApiConfig,
+    predicate_key: &str,
+) -> Result<Option<Box<dyn PredicateStore>>, String> {
+    for mut predicates_db_conn in open_predicate_stores(config)? {
+        if predicates_db_conn.contains(predicate_key)? {
+            return Ok(Some(predicates_db_conn));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the `http_post` actions of the registered predicates among `predicate_keys`, by
+/// predicate key. Predicates that are not registered, or post elsewhere, are left out.
+pub fn get_registered_http_hooks(
+    config: &PredicatesApiConfig,
+    predicate_keys: &[String],
+) -> Result<HashMap<String, HttpHook>, String> {
+    let mut hooks = HashMap::new();
+    for predicate_key in predicate_keys.iter() {
+        if hooks.contains_key(predicate_key) {
+            continue;
+        }
+        let Some(mut predicates_db_conn) = find_predicate_store(config, predicate_key)? else {
+            continue;
+        };
+        if let Some((spec, _)) = predicates_db_conn.get_entry(predicate_key)? {
+            if let Some(hook) = spec.action().http_hook() {
+                hooks.insert(predicate_key.clone(), hook.clone());
+            }
+        }
+    }
+    Ok(hooks)
+}
+
+pub fn open_predicate_store_verbose(
+    config: &PredicatesApiConfig,
+    chain: &Chain,
+    ctx: &Context,
+) -> Result<Box<dyn PredicateStore>, String> {
+    let res = open_predicate_store(config, chain);
+    if let Err(ref e) = res {
+        error!(ctx.expect_logger(), "{}", e.to_string());
+    }
+    res
+}
+
+pub fn open_predicate_store_for_key_verbose(
     config: &PredicatesApiConfig,
+    predicate_key: &str,
     ctx: &Context,
-) -> Result<Connection, String> {
-    let res = open_readwrite_predicates_db_conn(config);
+) -> Result<Box<dyn PredicateStore>, String> {
+    let res = match find_predicate_store(config, predicate_key) {
+        Ok(Some(predicates_db_conn)) => Ok(predicates_db_conn),
+        Ok(None) => open_predicate_store_at_uri(config, &config.database_uri),
+        Err(e) => Err(e),
+    };
     if let Err(ref e) = res {
         error!(ctx.expect_logger(), "{}", e.to_string());
     }
@@ -1293,11 +1904,12 @@ pub fn open_readwrite_predicates_db_conn_verbose(
 }
 
 // todo: evaluate expects
-pub fn open_readwrite_predicates_db_conn_or_panic(
+pub fn open_predicate_store_or_panic(
     config: &PredicatesApiConfig,
+    chain: &Chain,
     ctx: &Context,
-) -> Connection {
-    open_readwrite_predicates_db_conn_verbose(config, ctx).expect("unable to open redis conn")
+) -> Box<dyn PredicateStore> {
+    open_predicate_store_verbose(config, chain, ctx).expect("unable to open predicates db conn")
 }
 
 #[cfg(test)]
@@ -0,0 +1,842 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chainhook_sdk::chainhooks::types::ChainhookInstance;
+use chainhook_sdk::types::Chain;
+use redis::{Commands, Connection};
+use rusqlite::{params, OptionalExtension};
+
+use super::{apply_predicate_status_counters, PredicateStatus};
+
+/// Fields of a predicate entry holding the counters of its status. They are incremented atomically
+/// with `HINCRBY`, and take precedence over the counters serialized in the `status` field.
+const NUMBER_OF_BLOCKS_EVALUATED_FIELD: &str = "number_of_blocks_evaluated";
+const NUMBER_OF_TIMES_TRIGGERED_FIELD: &str = "number_of_times_triggered";
+
+/// Storage of the specifications and statuses of the predicates registered through the predicates API.
+pub trait PredicateStore {
+    /// Returns the status of a predicate, or `None` if it has no status stored.
+    fn get_status(&mut self, predicate_key: &str) -> Result<Option<PredicateStatus>, String>;
+
+    /// Writes the status of a predicate. Unless they were already incremented with
+    /// [PredicateStore::increment_status_counters], the counters are overwritten with the ones of `status`.
+    fn update_status(
+        &mut self,
+        predicate_key: &str,
+        status: &PredicateStatus,
+        overwrite_counters: bool,
+    ) -> Result<(), String>;
+
+    /// Writes the status of a predicate, and overwrites its counters, unless the stored status is
+    /// `Interrupted` or `ConfirmedExpiration`: the status is read and written in the same
+    /// transaction, so that it never overwrites a final status set by a concurrent writer.
+    /// Returns whether the status was written.
+    fn update_status_unless_final(
+        &mut self,
+        predicate_key: &str,
+        status: &PredicateStatus,
+    ) -> Result<bool, String>;
+
+    /// Writes the specification of a predicate, and cancels its pending eviction, if any.
+    fn update_spec(&mut self, predicate_key: &str, spec: &ChainhookInstance) -> Result<(), String>;
+
+    /// Deletes a predicate, along with the heights of the blocks it triggered on.
+    fn delete(&mut self, predicate_key: &str) -> Result<(), String>;
+
+    /// Returns the keys of the stored predicates.
+    fn list(&mut self) -> Result<Vec<String>, String>;
+
+    /// Returns the specification and status of a predicate, or `None` if it has no specification stored.
+    fn get_entry(
+        &mut self,
+        predicate_key: &str,
+    ) -> Result<Option<(ChainhookInstance, PredicateStatus)>, String>;
+
+    fn contains(&mut self, predicate_key: &str) -> Result<bool, String>;
+
+    /// Atomically increments the `(number_of_blocks_evaluated, number_of_times_triggered)` counters of a
+    /// predicate, seeding them with `initial_counters` if they are not stored yet. Returns the updated counters.
+    fn increment_status_counters(
+        &mut self,
+        predicate_key: &str,
+        initial_counters: (u64, u64),
+        increments: (u64, u64),
+    ) -> Result<(u64, u64), String>;
+
+    /// Evicts a predicate once `retention_secs` have elapsed.
+    fn set_eviction(&mut self, predicate_key: &str, retention_secs: u64) -> Result<(), String>;
+
+    /// Records that a predicate expires once the block at `block_height` is confirmed.
+    fn insert_expiration(
+        &mut self,
+        chain: &Chain,
+        block_height: u64,
+        predicate_key: &str,
+    ) -> Result<(), String>;
+
+    /// Returns, and forgets, the keys of the predicates expiring at `block_height`.
+    fn take_expirations(&mut self, chain: &Chain, block_height: u64)
+        -> Result<Vec<String>, String>;
+
+    fn insert_triggered_block_heights(
+        &mut self,
+        predicate_key: &str,
+        block_heights: &[u64],
+    ) -> Result<(), String>;
+
+    /// Returns the sorted heights of the blocks on which a predicate triggered.
+    fn get_triggered_block_heights(&mut self, predicate_key: &str) -> Result<Vec<u64>, String>;
+}
+
+fn get_predicate_expiration_key(chain: &Chain, block_height: u64) -> String {
+    match chain {
+        Chain::Bitcoin => format!("expires_at:bitcoin_block:{}", block_height),
+        Chain::Stacks => format!("expires_at:stacks_block:{}", block_height),
+    }
+}
+
+fn get_triggered_blocks_key(predicate_key: &str) -> String {
+    format!("triggered_blocks:{}", predicate_key)
+}
+
+/// Queues the commands writing the status of a predicate, and optionally its counters, to `pipeline`.
+fn queue_status_update(
+    pipeline: &mut redis::Pipeline,
+    predicate_key: &str,
+    status: &PredicateStatus,
+    overwrite_counters: bool,
+) {
+    let serialized_status = json!(status).to_string();
+    pipeline
+        .hset(predicate_key, "status", &serialized_status)
+        .ignore();
+    if overwrite_counters {
+        match status.get_counters() {
+            Some((number_of_blocks_evaluated, number_of_times_triggered)) => pipeline
+                .hset(
+                    predicate_key,
+                    NUMBER_OF_BLOCKS_EVALUATED_FIELD,
+                    number_of_blocks_evaluated,
+                )
+                .ignore()
+                .hset(
+                    predicate_key,
+                    NUMBER_OF_TIMES_TRIGGERED_FIELD,
+                    number_of_times_triggered,
+                )
+                .ignore(),
+            None => pipeline
+                .hdel(
+                    predicate_key,
+                    vec![
+                        NUMBER_OF_BLOCKS_EVALUATED_FIELD,
+                        NUMBER_OF_TIMES_TRIGGERED_FIELD,
+                    ],
+                )
+                .ignore(),
+        };
+    }
+}
+
+impl PredicateStore for Connection {
+    fn get_status(&mut self, predicate_key: &str) -> Result<Option<PredicateStatus>, String> {
+        let (payload, number_of_blocks_evaluated, number_of_times_triggered) = self
+            .hget::<_, _, (Option<String>, Option<u64>, Option<u64>)>(
+                predicate_key,
+                vec![
+                    "status",
+                    NUMBER_OF_BLOCKS_EVALUATED_FIELD,
+                    NUMBER_OF_TIMES_TRIGGERED_FIELD,
+                ],
+            )
+            .map_err(|e| {
+                format!(
+                    "unable to load status of predicate {}: {}",
+                    predicate_key,
+                    e.to_string()
+                )
+            })?;
+        let Some(payload) = payload else {
+            return Ok(None);
+        };
+        let mut status = serde_json::from_str(&payload).map_err(|e| {
+            format!(
+                "unable to parse status of predicate {}: {}",
+                predicate_key,
+                e.to_string()
+            )
+        })?;
+        apply_predicate_status_counters(
+            &mut status,
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+        );
+        Ok(Some(status))
+    }
+
+    fn update_status(
+        &mut self,
+        predicate_key: &str,
+        status: &PredicateStatus,
+        overwrite_counters: bool,
+    ) -> Result<(), String> {
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        queue_status_update(&mut pipeline, predicate_key, status, overwrite_counters);
+        pipeline.query::<()>(self).map_err(|e| e.to_string())
+    }
+
+    fn update_status_unless_final(
+        &mut self,
+        predicate_key: &str,
+        status: &PredicateStatus,
+    ) -> Result<bool, String> {
+        // the transaction is retried if the predicate is updated between the read and the write
+        redis::transaction(self, &[predicate_key], |conn, pipeline| {
+            let current_status: Option<String> = conn.hget(predicate_key, "status")?;
+            if current_status
+                .and_then(|payload| serde_json::from_str::<PredicateStatus>(&payload).ok())
+                .map_or(false, |status| status.is_final())
+            {
+                return Ok(Some(false));
+            }
+            queue_status_update(pipeline, predicate_key, status, true);
+            pipeline
+                .query::<Option<()>>(conn)
+                .map(|written| written.map(|_| true))
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn update_spec(&mut self, predicate_key: &str, spec: &ChainhookInstance) -> Result<(), String> {
+        let serialized_spec = json!(spec).to_string();
+        self.hset::<_, _, _, ()>(predicate_key, "specification", &serialized_spec)
+            .map_err(|e| e.to_string())?;
+        // a predicate registered again while its expired record was still retained must not
+        // inherit the pending eviction
+        self.persist::<_, ()>(predicate_key)
+            .map_err(|e| format!("unable to clear expiry: {}", e.to_string()))
+    }
+
+    fn delete(&mut self, predicate_key: &str) -> Result<(), String> {
+        self.del::<_, ()>(predicate_key)
+            .and_then(|_| self.del::<_, ()>(get_triggered_blocks_key(predicate_key)))
+            .map_err(|e| e.to_string())
+    }
+
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        let predicate_keys: Vec<String> = self
+            .scan_match(ChainhookInstance::either_stx_or_btc_key("*"))
+            .map_err(|e| format!("unable to connect to redis: {}", e.to_string()))?
+            .collect();
+        Ok(predicate_keys)
+    }
+
+    fn get_entry(
+        &mut self,
+        predicate_key: &str,
+    ) -> Result<Option<(ChainhookInstance, PredicateStatus)>, String> {
+        let entry: HashMap<String, String> = self.hgetall(predicate_key).map_err(|e| {
+            format!(
+                "unable to load chainhook associated with key {}: {}",
+                predicate_key,
+                e.to_string()
+            )
+        })?;
+
+        let encoded_spec = match entry.get("specification") {
+            None => return Ok(None),
+            Some(payload) => payload,
+        };
+
+        let spec = ChainhookInstance::deserialize_specification(&encoded_spec)?;
+
+        let encoded_status = match entry.get("status") {
+            None => Err(format!(
+                "found predicate specification with no status for predicate {}",
+                predicate_key
+            )),
+            Some(payload) => Ok(payload),
+        }?;
+
+        let mut status =
+            serde_json::from_str(&encoded_status).map_err(|e| format!("{}", e.to_string()))?;
+        apply_predicate_status_counters(
+            &mut status,
+            entry
+                .get(NUMBER_OF_BLOCKS_EVALUATED_FIELD)
+                .and_then(|v| v.parse().ok()),
+            entry
+                .get(NUMBER_OF_TIMES_TRIGGERED_FIELD)
+                .and_then(|v| v.parse().ok()),
+        );
+
+        Ok(Some((spec, status)))
+    }
+
+    fn contains(&mut self, predicate_key: &str) -> Result<bool, String> {
+        self.exists(predicate_key).map_err(|e| {
+            format!(
+                "unable to look up predicate {predicate_key}: {}",
+                e.to_string()
+            )
+        })
+    }
+
+    fn increment_status_counters(
+        &mut self,
+        predicate_key: &str,
+        initial_counters: (u64, u64),
+        increments: (u64, u64),
+    ) -> Result<(u64, u64), String> {
+        let (initial_blocks_evaluated, initial_times_triggered) = initial_counters;
+        let (blocks_evaluated_increment, times_triggered_increment) = increments;
+        self.hset_nx::<_, _, _, ()>(
+            predicate_key,
+            NUMBER_OF_BLOCKS_EVALUATED_FIELD,
+            initial_blocks_evaluated,
+        )
+        .and_then(|_| {
+            self.hset_nx::<_, _, _, ()>(
+                predicate_key,
+                NUMBER_OF_TIMES_TRIGGERED_FIELD,
+                initial_times_triggered,
+            )
+        })
+        .map_err(|e| e.to_string())?;
+        let number_of_blocks_evaluated: u64 = self
+            .hincr(
+                predicate_key,
+                NUMBER_OF_BLOCKS_EVALUATED_FIELD,
+                blocks_evaluated_increment,
+            )
+            .map_err(|e| e.to_string())?;
+        let number_of_times_triggered: u64 = self
+            .hincr(
+                predicate_key,
+                NUMBER_OF_TIMES_TRIGGERED_FIELD,
+                times_triggered_increment,
+            )
+            .map_err(|e| e.to_string())?;
+        Ok((number_of_blocks_evaluated, number_of_times_triggered))
+    }
+
+    fn set_eviction(&mut self, predicate_key: &str, retention_secs: u64) -> Result<(), String> {
+        self.expire::<_, ()>(predicate_key, retention_secs as usize)
+            .map_err(|e| e.to_string())
+    }
+
+    fn insert_expiration(
+        &mut self,
+        chain: &Chain,
+        block_height: u64,
+        predicate_key: &str,
+    ) -> Result<(), String> {
+        let key = get_predicate_expiration_key(chain, block_height);
+        let payload: Option<String> = self.hget(&key, "predicates").map_err(|e| e.to_string())?;
+        let mut predicates_expiring_at_block: Vec<String> = payload
+            .and_then(|payload| serde_json::from_str(&payload).ok())
+            .unwrap_or(vec![]);
+        predicates_expiring_at_block.push(predicate_key.to_owned());
+        let serialized_expiring_predicates = json!(predicates_expiring_at_block).to_string();
+        self.hset::<_, _, _, ()>(&key, "predicates", &serialized_expiring_predicates)
+            .map_err(|e| e.to_string())
+    }
+
+    fn take_expirations(
+        &mut self,
+        chain: &Chain,
+        block_height: u64,
+    ) -> Result<Vec<String>, String> {
+        let key = get_predicate_expiration_key(chain, block_height);
+        let payload: Option<String> = self.hget(&key, "predicates").map_err(|e| e.to_string())?;
+        let Some(payload) = payload else {
+            return Ok(vec![]);
+        };
+        let predicates_expiring_at_block = serde_json::from_str(&payload).map_err(|e| {
+            format!(
+                "unable to parse expired predicates index: {}",
+                e.to_string()
+            )
+        })?;
+        self.hdel::<_, _, u64>(&key, "predicates").map_err(|e| {
+            format!(
+                "unable to remove expired predicates index: {}",
+                e.to_string()
+            )
+        })?;
+        Ok(predicates_expiring_at_block)
+    }
+
+    fn insert_triggered_block_heights(
+        &mut self,
+        predicate_key: &str,
+        block_heights: &[u64],
+    ) -> Result<(), String> {
+        self.sadd::<_, _, ()>(get_triggered_blocks_key(predicate_key), block_heights)
+            .map_err(|e| e.to_string())
+    }
+
+    fn get_triggered_block_heights(&mut self, predicate_key: &str) -> Result<Vec<u64>, String> {
+        let mut block_heights: Vec<u64> = self
+            .smembers(get_triggered_blocks_key(predicate_key))
+            .map_err(|e| {
+                format!(
+                    "unable to retrieve triggered blocks for {}: {}",
+                    predicate_key,
+                    e.to_string()
+                )
+            })?;
+        block_heights.sort();
+        Ok(block_heights)
+    }
+}
+
+/// How long a connection waits for the lock held by a concurrent writer before failing.
+const SQLITE_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Predicates stored in a SQLite database, for deployments where running Redis is not worth it.
+pub struct SqlitePredicateStore {
+    conn: rusqlite::Connection,
+}
+
+impl SqlitePredicateStore {
+    pub fn open(db_path: &str) -> Result<SqlitePredicateStore, String> {
+        let conn = rusqlite::Connection::open(db_path)
+            .map_err(|e| format!("unable to open db {}: {}", db_path, e.to_string()))?;
+        conn.busy_timeout(SQLITE_BUSY_TIMEOUT)
+            .map_err(|e| format!("unable to open db {}: {}", db_path, e.to_string()))?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+            CREATE TABLE IF NOT EXISTS predicates (
+                predicate_key TEXT NOT NULL PRIMARY KEY,
+                specification TEXT,
+                status TEXT,
+                number_of_blocks_evaluated INTEGER,
+                number_of_times_triggered INTEGER,
+                evict_at INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS predicate_expirations (
+                chain TEXT NOT NULL,
+                block_height INTEGER NOT NULL,
+                predicate_key TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS predicate_expirations_block
+                ON predicate_expirations (chain, block_height);
+            CREATE TABLE IF NOT EXISTS triggered_blocks (
+                predicate_key TEXT NOT NULL,
+                block_height INTEGER NOT NULL,
+                PRIMARY KEY (predicate_key, block_height)
+            );",
+        )
+        .map_err(|e| format!("unable to initialize db {}: {}", db_path, e.to_string()))?;
+        Ok(SqlitePredicateStore { conn })
+    }
+
+    /// Deletes the predicates whose retention window is over, as Redis does for expiring keys.
+    fn evict_predicates(&mut self) -> Result<(), String> {
+        self.conn
+            .execute(
+                "DELETE FROM predicates WHERE evict_at IS NOT NULL AND evict_at <= ?1",
+                params![get_now_secs()],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("unable to evict expired predicates: {}", e.to_string()))
+    }
+}
+
+fn get_now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Could not get current time in ms")
+        .as_secs()
+}
+
+fn get_chain_column(chain: &Chain) -> &'static str {
+    match chain {
+        Chain::Bitcoin => "bitcoin",
+        Chain::Stacks => "stacks",
+    }
+}
+
+/// Writes the status of a predicate, and optionally its counters, with `conn` or a transaction.
+fn write_sqlite_status(
+    conn: &rusqlite::Connection,
+    predicate_key: &str,
+    status: &PredicateStatus,
+    overwrite_counters: bool,
+) -> Result<(), String> {
+    let serialized_status = json!(status).to_string();
+    let res = if overwrite_counters {
+        let (number_of_blocks_evaluated, number_of_times_triggered) = status
+            .get_counters()
+            .map_or((None, None), |(evaluated, triggered)| {
+                (Some(evaluated), Some(triggered))
+            });
+        conn.execute(
+            "INSERT INTO predicates
+                (predicate_key, status, number_of_blocks_evaluated, number_of_times_triggered)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (predicate_key) DO UPDATE SET
+                status = excluded.status,
+                number_of_blocks_evaluated = excluded.number_of_blocks_evaluated,
+                number_of_times_triggered = excluded.number_of_times_triggered",
+            params![
+                predicate_key,
+                serialized_status,
+                number_of_blocks_evaluated,
+                number_of_times_triggered
+            ],
+        )
+    } else {
+        conn.execute(
+            "INSERT INTO predicates (predicate_key, status) VALUES (?1, ?2)
+            ON CONFLICT (predicate_key) DO UPDATE SET status = excluded.status",
+            params![predicate_key, serialized_status],
+        )
+    };
+    res.map(|_| ()).map_err(|e| e.to_string())
+}
+
+impl PredicateStore for SqlitePredicateStore {
+    fn get_status(&mut self, predicate_key: &str) -> Result<Option<PredicateStatus>, String> {
+        self.evict_predicates()?;
+        let row = self
+            .conn
+            .query_row(
+                "SELECT status, number_of_blocks_evaluated, number_of_times_triggered
+                FROM predicates WHERE predicate_key = ?1",
+                params![predicate_key],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<u64>>(1)?,
+                        row.get::<_, Option<u64>>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| {
+                format!(
+                    "unable to load status of predicate {}: {}",
+                    predicate_key,
+                    e.to_string()
+                )
+            })?;
+        let Some((Some(payload), number_of_blocks_evaluated, number_of_times_triggered)) = row
+        else {
+            return Ok(None);
+        };
+        let mut status = serde_json::from_str(&payload).map_err(|e| {
+            format!(
+                "unable to parse status of predicate {}: {}",
+                predicate_key,
+                e.to_string()
+            )
+        })?;
+        apply_predicate_status_counters(
+            &mut status,
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+        );
+        Ok(Some(status))
+    }
+
+    fn update_status(
+        &mut self,
+        predicate_key: &str,
+        status: &PredicateStatus,
+        overwrite_counters: bool,
+    ) -> Result<(), String> {
+        write_sqlite_status(&self.conn, predicate_key, status, overwrite_counters)
+    }
+
+    fn update_status_unless_final(
+        &mut self,
+        predicate_key: &str,
+        status: &PredicateStatus,
+    ) -> Result<bool, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let current_status = tx
+            .query_row(
+                "SELECT status FROM predicates WHERE predicate_key = ?1",
+                params![predicate_key],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .flatten();
+        if current_status
+            .and_then(|payload| serde_json::from_str::<PredicateStatus>(&payload).ok())
+            .map_or(false, |status| status.is_final())
+        {
+            return Ok(false);
+        }
+        write_sqlite_status(&tx, predicate_key, status, true)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    fn update_spec(&mut self, predicate_key: &str, spec: &ChainhookInstance) -> Result<(), String> {
+        let serialized_spec = json!(spec).to_string();
+        self.evict_predicates()?;
+        // a predicate registered again while its expired record was still retained must not
+        // inherit the pending eviction
+        self.conn
+            .execute(
+                "INSERT INTO predicates (predicate_key, specification) VALUES (?1, ?2)
+                ON CONFLICT (predicate_key) DO UPDATE SET
+                    specification = excluded.specification,
+                    evict_at = NULL",
+                params![predicate_key, serialized_spec],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn delete(&mut self, predicate_key: &str) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM predicates WHERE predicate_key = ?1",
+            params![predicate_key],
+        )
+        .and_then(|_| {
+            tx.execute(
+                "DELETE FROM triggered_blocks WHERE predicate_key = ?1",
+                params![predicate_key],
+            )
+        })
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        self.evict_predicates()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT predicate_key FROM predicates")
+            .map_err(|e| format!("unable to list predicates: {}", e.to_string()))?;
+        let predicate_keys = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("unable to list predicates: {}", e.to_string()))?;
+        Ok(predicate_keys)
+    }
+
+    fn get_entry(
+        &mut self,
+        predicate_key: &str,
+    ) -> Result<Option<(ChainhookInstance, PredicateStatus)>, String> {
+        self.evict_predicates()?;
+        let row = self
+            .conn
+            .query_row(
+                "SELECT specification, status, number_of_blocks_evaluated, number_of_times_triggered
+                FROM predicates WHERE predicate_key = ?1",
+                params![predicate_key],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, Option<u64>>(2)?,
+                        row.get::<_, Option<u64>>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| {
+                format!(
+                    "unable to load chainhook associated with key {}: {}",
+                    predicate_key,
+                    e.to_string()
+                )
+            })?;
+        let Some((
+            Some(encoded_spec),
+            encoded_status,
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+        )) = row
+        else {
+            return Ok(None);
+        };
+
+        let spec = ChainhookInstance::deserialize_specification(&encoded_spec)?;
+
+        let encoded_status = encoded_status.ok_or(format!(
+            "found predicate specification with no status for predicate {}",
+            predicate_key
+        ))?;
+
+        let mut status =
+            serde_json::from_str(&encoded_status).map_err(|e| format!("{}", e.to_string()))?;
+        apply_predicate_status_counters(
+            &mut status,
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+        );
+
+        Ok(Some((spec, status)))
+    }
+
+    fn contains(&mut self, predicate_key: &str) -> Result<bool, String> {
+        self.evict_predicates()?;
+        self.conn
+            .query_row(
+                "SELECT 1 FROM predicates WHERE predicate_key = ?1",
+                params![predicate_key],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(|e| {
+                format!(
+                    "unable to look up predicate {predicate_key}: {}",
+                    e.to_string()
+                )
+            })
+    }
+
+    fn increment_status_counters(
+        &mut self,
+        predicate_key: &str,
+        initial_counters: (u64, u64),
+        increments: (u64, u64),
+    ) -> Result<(u64, u64), String> {
+        let (initial_blocks_evaluated, initial_times_triggered) = initial_counters;
+        let (blocks_evaluated_increment, times_triggered_increment) = increments;
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let counters = tx
+            .execute(
+                "INSERT INTO predicates (predicate_key) VALUES (?1)
+                ON CONFLICT (predicate_key) DO NOTHING",
+                params![predicate_key],
+            )
+            .and_then(|_| {
+                tx.execute(
+                    "UPDATE predicates SET
+                        number_of_blocks_evaluated = COALESCE(number_of_blocks_evaluated, ?2) + ?3,
+                        number_of_times_triggered = COALESCE(number_of_times_triggered, ?4) + ?5
+                    WHERE predicate_key = ?1",
+                    params![
+                        predicate_key,
+                        initial_blocks_evaluated,
+                        blocks_evaluated_increment,
+                        initial_times_triggered,
+                        times_triggered_increment
+                    ],
+                )
+            })
+            .and_then(|_| {
+                tx.query_row(
+                    "SELECT number_of_blocks_evaluated, number_of_times_triggered
+                    FROM predicates WHERE predicate_key = ?1",
+                    params![predicate_key],
+                    |row| Ok((row.get::<_, u64>(0)?, row.get::<_, u64>(1)?)),
+                )
+            })
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(counters)
+    }
+
+    fn set_eviction(&mut self, predicate_key: &str, retention_secs: u64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE predicates SET evict_at = ?2 WHERE predicate_key = ?1",
+                params![predicate_key, get_now_secs() + retention_secs],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn insert_expiration(
+        &mut self,
+        chain: &Chain,
+        block_height: u64,
+        predicate_key: &str,
+    ) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO predicate_expirations (chain, block_height, predicate_key)
+                VALUES (?1, ?2, ?3)",
+                params![get_chain_column(chain), block_height, predicate_key],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn take_expirations(
+        &mut self,
+        chain: &Chain,
+        block_height: u64,
+    ) -> Result<Vec<String>, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let predicate_keys = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT predicate_key FROM predicate_expirations
+                    WHERE chain = ?1 AND block_height = ?2",
+                )
+                .map_err(|e| e.to_string())?;
+            stmt.query_map(params![get_chain_column(chain), block_height], |row| {
+                row.get::<_, String>(0)
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| {
+                format!(
+                    "unable to retrieve expired predicates index: {}",
+                    e.to_string()
+                )
+            })?
+        };
+        tx.execute(
+            "DELETE FROM predicate_expirations WHERE chain = ?1 AND block_height = ?2",
+            params![get_chain_column(chain), block_height],
+        )
+        .map_err(|e| {
+            format!(
+                "unable to remove expired predicates index: {}",
+                e.to_string()
+            )
+        })?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(predicate_keys)
+    }
+
+    fn insert_triggered_block_heights(
+        &mut self,
+        predicate_key: &str,
+        block_heights: &[u64],
+    ) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        for block_height in block_heights.iter() {
+            tx.execute(
+                "INSERT OR IGNORE INTO triggered_blocks (predicate_key, block_height)
+                VALUES (?1, ?2)",
+                params![predicate_key, block_height],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn get_triggered_block_heights(&mut self, predicate_key: &str) -> Result<Vec<u64>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT block_height FROM triggered_blocks
+                WHERE predicate_key = ?1 ORDER BY block_height",
+            )
+            .map_err(|e| e.to_string())?;
+        stmt.query_map(params![predicate_key], |row| row.get::<_, u64>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| {
+                format!(
+                    "unable to retrieve triggered blocks for {}: {}",
+                    predicate_key,
+                    e.to_string()
+                )
+            })
+    }
+}
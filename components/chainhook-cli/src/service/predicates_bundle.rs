@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use chainhook_sdk::bitcoincore_rpc_json::bitcoin::hashes::{sha256, Hash};
+use chainhook_sdk::chainhooks::types::ChainhookSpecificationNetworkMap;
+
+use crate::config::PredicatesBundleConfig;
+
+/// Time allowed to fetch the bundle, so that an unresponsive server doesn't hold the startup.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetches the bundle of predicates served at `bundle.url`, a JSON array of predicates. The bundle
+/// is rejected as a whole if it doesn't match the configured SHA-256 digest.
+pub async fn fetch_predicates_bundle(
+    bundle: &PredicatesBundleConfig,
+) -> Result<Vec<ChainhookSpecificationNetworkMap>, String> {
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(|e| format!("unable to build http client: {}", e.to_string()))?;
+    let response = client
+        .get(&bundle.url)
+        .send()
+        .await
+        .map_err(|e| format!("unable to fetch {}: {}", bundle.url, e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "unable to fetch {}: status {}",
+            bundle.url,
+            response.status()
+        ));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("unable to fetch {}: {}", bundle.url, e.to_string()))?;
+    let checksum = sha256::Hash::hash(&body).to_string();
+    let expected_checksum = bundle.sha256.strip_prefix("0x").unwrap_or(&bundle.sha256);
+    if !checksum.eq_ignore_ascii_case(expected_checksum) {
+        return Err(format!(
+            "checksum mismatch for {}: expected {}, got {}",
+            bundle.url, expected_checksum, checksum
+        ));
+    }
+    serde_json::from_slice(&body)
+        .map_err(|e| format!("unable to parse predicates bundle: {}", e.to_string()))
+}
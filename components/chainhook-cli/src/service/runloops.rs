@@ -9,6 +9,7 @@ use chainhook_sdk::{
         types::ChainhookInstance,
     },
     observer::ObserverCommand,
+    types::Chain,
     utils::Context,
 };
 use threadpool::ThreadPool;
@@ -16,10 +17,14 @@ use threadpool::ThreadPool;
 use crate::{
     config::{Config, PredicatesApi},
     scan::{
-        bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate, common::PredicateScanResult,
+        bitcoin::{
+            scan_bitcoin_chainstate_via_rpc_using_predicate,
+            scan_bitcoin_chainstate_via_rpc_using_predicates, BitcoinScanRequest,
+        },
+        common::{PredicateScanResult, ScanSummary},
         stacks::scan_stacks_chainstate_via_rocksdb_using_predicate,
     },
-    service::{open_readwrite_predicates_db_conn_or_panic, set_predicate_interrupted_status},
+    service::{open_predicate_store_or_panic, set_predicate_interrupted_status},
     storage::open_readonly_stacks_db_conn,
 };
 
@@ -35,6 +40,7 @@ pub enum StacksScanOp {
 
 pub fn start_stacks_scan_runloop(
     config: &Config,
+    http_client: &reqwest::Client,
     stacks_scan_op_rx: crossbeam_channel::Receiver<StacksScanOp>,
     observer_command_tx: Sender<ObserverCommand>,
     ctx: &Context,
@@ -50,6 +56,7 @@ pub fn start_stacks_scan_runloop(
             } => {
                 let moved_ctx = ctx.clone();
                 let moved_config = config.clone();
+                let moved_http_client = http_client.clone();
                 let observer_command_tx = observer_command_tx.clone();
                 let kill_signal = Arc::new(RwLock::new(false));
                 kill_signals.insert(predicate_spec.uuid.clone(), kill_signal.clone());
@@ -77,14 +84,15 @@ pub fn start_stacks_scan_runloop(
                         unfinished_scan_data,
                         &stacks_db_conn,
                         &moved_config,
+                        &moved_http_client,
                         Some(kill_signal),
                         &moved_ctx,
                     );
                     let res = hiro_system_kit::nestable_block_on(op);
                     match res {
-                        Ok(PredicateScanResult::Expired)
-                        | Ok(PredicateScanResult::Deregistered) => {}
-                        Ok(PredicateScanResult::ChainTipReached) => {
+                        Ok((PredicateScanResult::Expired, _))
+                        | Ok((PredicateScanResult::Deregistered, _)) => {}
+                        Ok((PredicateScanResult::ChainTipReached, _)) => {
                             let _ = observer_command_tx.send(ObserverCommand::EnablePredicate(
                                 ChainhookInstance::Stacks(predicate_spec),
                             ));
@@ -100,14 +108,15 @@ pub fn start_stacks_scan_runloop(
                                 let error = format!(
                                     "Unable to evaluate predicate on Stacks chainstate: {e}"
                                 );
-                                let mut predicates_db_conn =
-                                    open_readwrite_predicates_db_conn_or_panic(
-                                        api_config, &moved_ctx,
-                                    );
+                                let mut predicates_db_conn = open_predicate_store_or_panic(
+                                    api_config,
+                                    &Chain::Stacks,
+                                    &moved_ctx,
+                                );
                                 set_predicate_interrupted_status(
                                     error,
                                     &predicate_spec.key(),
-                                    &mut predicates_db_conn,
+                                    predicates_db_conn.as_mut(),
                                     &moved_ctx,
                                 );
                             }
@@ -139,6 +148,7 @@ pub enum BitcoinScanOp {
 
 pub fn start_bitcoin_scan_runloop(
     config: &Config,
+    http_client: &reqwest::Client,
     bitcoin_scan_op_rx: crossbeam_channel::Receiver<BitcoinScanOp>,
     observer_command_tx: Sender<ObserverCommand>,
     ctx: &Context,
@@ -147,69 +157,141 @@ pub fn start_bitcoin_scan_runloop(
     let mut kill_signals = HashMap::new();
 
     while let Ok(op) = bitcoin_scan_op_rx.recv() {
-        match op {
-            BitcoinScanOp::StartScan {
-                predicate_spec,
-                unfinished_scan_data,
-            } => {
-                let moved_ctx = ctx.clone();
-                let moved_config = config.clone();
-                let observer_command_tx = observer_command_tx.clone();
-                let kill_signal = Arc::new(RwLock::new(false));
-                kill_signals.insert(predicate_spec.uuid.clone(), kill_signal.clone());
-
-                bitcoin_scan_pool.execute(move || {
-                    let op = scan_bitcoin_chainstate_via_rpc_using_predicate(
-                        &predicate_spec,
+        // scans requested together (e.g. when resuming predicates at startup) are batched, so
+        // that the blocks they have in common are only fetched once
+        let mut ops = vec![op];
+        while let Ok(op) = bitcoin_scan_op_rx.try_recv() {
+            ops.push(op);
+        }
+        let mut requests = vec![];
+        for op in ops.into_iter() {
+            match op {
+                BitcoinScanOp::StartScan {
+                    predicate_spec,
+                    unfinished_scan_data,
+                } => {
+                    let kill_signal = Arc::new(RwLock::new(false));
+                    kill_signals.insert(predicate_spec.uuid.clone(), kill_signal.clone());
+                    requests.push(BitcoinScanRequest {
+                        predicate_spec,
                         unfinished_scan_data,
-                        &moved_config,
-                        Some(kill_signal),
-                        &moved_ctx,
-                    );
-
-                    match hiro_system_kit::nestable_block_on(op) {
-                        Ok(PredicateScanResult::Expired)
-                        | Ok(PredicateScanResult::Deregistered) => {}
-                        Ok(PredicateScanResult::ChainTipReached) => {
-                            let _ = observer_command_tx.send(ObserverCommand::EnablePredicate(
-                                ChainhookInstance::Bitcoin(predicate_spec),
-                            ));
-                        }
-                        Err(e) => {
-                            warn!(
-                                moved_ctx.expect_logger(),
-                                "Unable to evaluate predicate on Bitcoin chainstate: {e}",
-                            );
-
-                            // Update predicate status in redis
-                            if let PredicatesApi::On(ref api_config) = moved_config.http_api {
-                                let error = format!(
-                                    "Unable to evaluate predicate on Bitcoin chainstate: {e}"
-                                );
-                                let mut predicates_db_conn =
-                                    open_readwrite_predicates_db_conn_or_panic(
-                                        api_config, &moved_ctx,
-                                    );
-                                set_predicate_interrupted_status(
-                                    error,
-                                    &predicate_spec.key(),
-                                    &mut predicates_db_conn,
-                                    &moved_ctx,
-                                )
-                            }
-                            return;
-                        }
+                        kill_signal: Some(kill_signal),
+                    });
+                }
+                BitcoinScanOp::KillScan(predicate_uuid) => {
+                    let Some(kill_signal) = kill_signals.remove(&predicate_uuid) else {
+                        continue;
                     };
-                });
-            }
-            BitcoinScanOp::KillScan(predicate_uuid) => {
-                let Some(kill_signal) = kill_signals.remove(&predicate_uuid) else {
-                    continue;
-                };
-                let mut kill_signal_writer = kill_signal.write().unwrap();
-                *kill_signal_writer = true;
+                    let mut kill_signal_writer = kill_signal.write().unwrap();
+                    *kill_signal_writer = true;
+                }
             }
         }
+        if requests.is_empty() {
+            continue;
+        }
+
+        let moved_ctx = ctx.clone();
+        let moved_config = config.clone();
+        let moved_http_client = http_client.clone();
+        let observer_command_tx = observer_command_tx.clone();
+        bitcoin_scan_pool.execute(move || {
+            if requests.len() == 1 {
+                let BitcoinScanRequest {
+                    predicate_spec,
+                    unfinished_scan_data,
+                    kill_signal,
+                } = requests.pop().unwrap();
+                let op = scan_bitcoin_chainstate_via_rpc_using_predicate(
+                    &predicate_spec,
+                    unfinished_scan_data,
+                    &moved_config,
+                    &moved_http_client,
+                    kill_signal,
+                    &moved_ctx,
+                );
+                let result = hiro_system_kit::nestable_block_on(op);
+                handle_bitcoin_scan_result(
+                    predicate_spec,
+                    result,
+                    &moved_config,
+                    &observer_command_tx,
+                    &moved_ctx,
+                );
+                return;
+            }
+
+            let predicate_specs = requests
+                .iter()
+                .map(|request| request.predicate_spec.clone())
+                .collect::<Vec<_>>();
+            let op = scan_bitcoin_chainstate_via_rpc_using_predicates(
+                requests,
+                &moved_config,
+                &moved_http_client,
+                &moved_ctx,
+            );
+            match hiro_system_kit::nestable_block_on(op) {
+                Ok(results) => {
+                    for (predicate_spec, result) in results.into_iter() {
+                        handle_bitcoin_scan_result(
+                            predicate_spec,
+                            result,
+                            &moved_config,
+                            &observer_command_tx,
+                            &moved_ctx,
+                        );
+                    }
+                }
+                Err(e) => {
+                    for predicate_spec in predicate_specs.into_iter() {
+                        handle_bitcoin_scan_result(
+                            predicate_spec,
+                            Err(e.clone()),
+                            &moved_config,
+                            &observer_command_tx,
+                            &moved_ctx,
+                        );
+                    }
+                }
+            }
+        });
     }
     let _ = bitcoin_scan_pool.join();
 }
+
+fn handle_bitcoin_scan_result(
+    predicate_spec: BitcoinChainhookInstance,
+    result: Result<(PredicateScanResult, ScanSummary), String>,
+    config: &Config,
+    observer_command_tx: &Sender<ObserverCommand>,
+    ctx: &Context,
+) {
+    match result {
+        Ok((PredicateScanResult::Expired, _)) | Ok((PredicateScanResult::Deregistered, _)) => {}
+        Ok((PredicateScanResult::ChainTipReached, _)) => {
+            let _ = observer_command_tx.send(ObserverCommand::EnablePredicate(
+                ChainhookInstance::Bitcoin(predicate_spec),
+            ));
+        }
+        Err(e) => {
+            warn!(
+                ctx.expect_logger(),
+                "Unable to evaluate predicate on Bitcoin chainstate: {e}",
+            );
+
+            // Update predicate status in redis
+            if let PredicatesApi::On(ref api_config) = config.http_api {
+                let error = format!("Unable to evaluate predicate on Bitcoin chainstate: {e}");
+                let mut predicates_db_conn =
+                    open_predicate_store_or_panic(api_config, &Chain::Bitcoin, ctx);
+                set_predicate_interrupted_status(
+                    error,
+                    &predicate_spec.key(),
+                    predicates_db_conn.as_mut(),
+                    ctx,
+                )
+            }
+        }
+    };
+}
@@ -84,12 +84,19 @@ fn handle_increment_chain_tip(
 fn handle_rpc(
     rpc: Json<Rpc>,
     fork_tracker_rw_lock: &State<Arc<RwLock<HashMap<BranchKey, ForkData>>>>,
+    getblock_requests: &State<GetBlockRequests>,
 ) -> Value {
     let rpc = rpc.into_inner();
     let fork_tracker = fork_tracker_rw_lock.inner().read().unwrap();
     match rpc.method.as_str() {
         "getblock" => {
             let hash = rpc.params[0].as_str().unwrap();
+            *getblock_requests
+                .inner()
+                .write()
+                .unwrap()
+                .entry(hash.to_string())
+                .or_insert(0) += 1;
             let mut chars = hash.chars();
             let branch = chars.next().unwrap();
             let prefix = chars.take_while(|&ch| ch == '0').collect::<String>();
@@ -144,6 +151,8 @@ fn handle_rpc(
                         address: None,
                     },
                 }],
+                weight: 0,
+                version: 2,
             };
             let tx = BitcoinTransactionFullBreakdown {
                 txid: branch_and_height_to_hash_str(Some(branch), height + 1),
@@ -156,6 +165,7 @@ fn handle_rpc(
                     prevout: Some(BitcoinTransactionInputPrevoutFullBreakdown {
                         height: height,
                         value: Amount::ZERO,
+                        script_pub_key: None,
                     }),
                 }],
                 vout: vec![BitcoinTransactionOutputFullBreakdown {
@@ -170,6 +180,8 @@ fn handle_rpc(
                         address: None,
                     },
                 }],
+                weight: 0,
+                version: 2,
             };
             let block = BitcoinBlockFullBreakdown {
                 hash: hash.into(),
@@ -289,7 +301,19 @@ type BranchKey = char;
 type Height = u64;
 type ForkPoint = (BranchKey, Height);
 type ForkData = (Height, Option<ForkPoint>);
+/// Number of `getblock` requests received, per block hash.
+pub type GetBlockRequests = Arc<RwLock<HashMap<String, u64>>>;
+
 pub async fn mock_bitcoin_rpc(port: u16, starting_chain_tip: u64) {
+    mock_bitcoin_rpc_with_getblock_requests(port, starting_chain_tip, GetBlockRequests::default())
+        .await
+}
+
+pub async fn mock_bitcoin_rpc_with_getblock_requests(
+    port: u16,
+    starting_chain_tip: u64,
+    getblock_requests: GetBlockRequests,
+) {
     let config = Config::figment()
         .merge(("port", port))
         .merge(("address", IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))))
@@ -300,6 +324,7 @@ pub async fn mock_bitcoin_rpc(port: u16, starting_chain_tip: u64) {
     let _rocket = rocket::build()
         .configure(config)
         .manage(fork_tracker_rw_lock)
+        .manage(getblock_requests)
         .mount("/", routes![handle_rpc, handle_increment_chain_tip])
         .launch()
         .await
@@ -1,16 +1,21 @@
 use crate::config::{
     Config, EventSourceConfig, LimitsConfig, MonitoringConfig, PathConfig, PredicatesApi,
-    PredicatesApiConfig, StorageConfig, DEFAULT_REDIS_URI,
+    PredicatesApiConfig, PredicatesConfig, PredicatesDatabaseBackend, StorageConfig,
+    DEFAULT_REDIS_URI,
 };
 use crate::scan::stacks::consolidate_local_stacks_chainstate_using_csv;
 use crate::service::{
-    http_api::start_predicate_api_server, update_predicate_spec, update_predicate_status,
-    PredicateStatus, Service,
+    http_api::start_predicate_api_server,
+    runloops::{BitcoinScanOp, StacksScanOp},
+    update_predicate_spec, update_predicate_status, ChainTips, PredicateStatus, Service,
 };
 use chainhook_sdk::{
     chainhooks::stacks::StacksChainhookSpecificationNetworkMap,
-    chainhooks::types::{ChainhookInstance, ChainhookSpecificationNetworkMap},
-    indexer::IndexerConfig,
+    chainhooks::types::{
+        ChainhookInstance, ChainhookSpecificationNetworkMap, ContentHashAlgorithm,
+        HttpDeliveryConfig,
+    },
+    indexer::{bitcoin::StacksOperationsParsing, IndexerConfig},
     observer::ObserverCommand,
     types::{BitcoinBlockSignaling, BitcoinNetwork, Chain, StacksNetwork, StacksNodeConfig},
     utils::Context,
@@ -26,6 +31,7 @@ use std::sync::mpsc;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
 
 use super::get_free_port;
 use super::mock_bitcoin_rpc::mock_bitcoin_rpc;
@@ -126,6 +132,24 @@ pub async fn call_register_predicate(
     call_observer_svc(&url, Method::POST, Some(predicate)).await
 }
 
+pub async fn call_register_predicate_with_token(
+    predicate: &JsonValue,
+    token: &str,
+    port: u16,
+) -> Result<JsonValue, String> {
+    let url = format!("http://localhost:{port}/v1/chainhooks");
+    reqwest::Client::new()
+        .post(&url)
+        .header("Authorization", format!("Bearer {token}"))
+        .json(predicate)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to make POST request to {url}: {e}",))?
+        .json::<JsonValue>()
+        .await
+        .map_err(|e| format!("Failed to deserialize response of POST request to {url}: {e}",))
+}
+
 pub async fn call_deregister_predicate(
     chain: &Chain,
     predicate_uuid: &str,
@@ -139,6 +163,36 @@ pub async fn call_deregister_predicate(
     call_observer_svc(&url, Method::DELETE, None).await
 }
 
+pub async fn call_expire_predicate(
+    predicate_uuid: &str,
+    admin_token: &str,
+    port: u16,
+) -> Result<JsonValue, String> {
+    let url = format!("http://localhost:{port}/v1/chainhooks/{predicate_uuid}/expire");
+    reqwest::Client::new()
+        .post(&url)
+        .header("Authorization", format!("Bearer {admin_token}"))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to make POST request to {url}: {e}",))?
+        .json::<JsonValue>()
+        .await
+        .map_err(|e| format!("Failed to deserialize response of POST request to {url}: {e}",))
+}
+
+pub async fn call_reload_predicates(admin_token: &str, port: u16) -> Result<JsonValue, String> {
+    let url = format!("http://localhost:{port}/v1/chainhooks/reload");
+    reqwest::Client::new()
+        .post(&url)
+        .header("Authorization", format!("Bearer {admin_token}"))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to make POST request to {url}: {e}",))?
+        .json::<JsonValue>()
+        .await
+        .map_err(|e| format!("Failed to deserialize response of POST request to {url}: {e}",))
+}
+
 pub async fn call_get_predicate(predicate_uuid: &str, port: u16) -> Result<JsonValue, String> {
     let url = format!("http://localhost:{port}/v1/chainhooks/{predicate_uuid}");
     call_observer_svc(&url, Method::GET, None).await
@@ -149,6 +203,37 @@ pub async fn call_get_predicates(port: u16) -> Result<JsonValue, String> {
     call_observer_svc(&url, Method::GET, None).await
 }
 
+pub async fn call_get_predicate_with_token(
+    predicate_uuid: &str,
+    token: &str,
+    port: u16,
+) -> Result<JsonValue, String> {
+    let url = format!("http://localhost:{port}/v1/chainhooks/{predicate_uuid}");
+    call_observer_svc_with_token(&url, token).await
+}
+
+pub async fn call_get_predicates_with_token(token: &str, port: u16) -> Result<JsonValue, String> {
+    let url = format!("http://localhost:{port}/v1/chainhooks");
+    call_observer_svc_with_token(&url, token).await
+}
+
+async fn call_observer_svc_with_token(url: &str, token: &str) -> Result<JsonValue, String> {
+    reqwest::Client::new()
+        .get(url)
+        .header("Authorization", format!("Bearer {token}"))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to make GET request to {url}: {e}",))?
+        .json::<JsonValue>()
+        .await
+        .map_err(|e| format!("Failed to deserialize response of GET request to {url}: {e}",))
+}
+
+pub async fn call_get_chain_tips(port: u16) -> Result<JsonValue, String> {
+    let url = format!("http://localhost:{port}/v1/diagnostics/tip");
+    call_observer_svc(&url, Method::GET, None).await
+}
+
 pub async fn call_observer_svc(
     url: &str,
     method: Method,
@@ -199,20 +284,59 @@ pub async fn call_prometheus(port: u16) -> Result<String, String> {
 }
 
 pub async fn build_predicate_api_server(port: u16) -> (Receiver<ObserverCommand>, Shutdown) {
-    let ctx = Context {
-        logger: None,
-        tracer: false,
-    };
+    build_predicate_api_server_with_chain_tips(port, Arc::new(RwLock::new(ChainTips::default())))
+        .await
+}
+
+pub async fn build_predicate_api_server_with_chain_tips(
+    port: u16,
+    chain_tips: Arc<RwLock<ChainTips>>,
+) -> (Receiver<ObserverCommand>, Shutdown) {
     let api_config = PredicatesApiConfig {
         http_port: port,
+        database_backend: PredicatesDatabaseBackend::Redis,
         display_logs: true,
         database_uri: DEFAULT_REDIS_URI.to_string(),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
     };
+    let (rx, _, _, shutdown) = build_predicate_api_server_with_config(api_config, chain_tips).await;
+    (rx, shutdown)
+}
+
+pub async fn build_predicate_api_server_with_config(
+    api_config: PredicatesApiConfig,
+    chain_tips: Arc<RwLock<ChainTips>>,
+) -> (
+    Receiver<ObserverCommand>,
+    crossbeam_channel::Receiver<BitcoinScanOp>,
+    crossbeam_channel::Receiver<StacksScanOp>,
+    Shutdown,
+) {
+    let ctx = Context {
+        logger: None,
+        tracer: false,
+    };
+    let port = api_config.http_port;
 
     let (tx, rx) = channel();
-    let shutdown = start_predicate_api_server(api_config, tx, ctx)
-        .await
-        .unwrap();
+    let (bitcoin_scan_op_tx, bitcoin_scan_op_rx) = crossbeam_channel::unbounded();
+    let (stacks_scan_op_tx, stacks_scan_op_rx) = crossbeam_channel::unbounded();
+    let shutdown = start_predicate_api_server(
+        api_config,
+        None,
+        chain_tips,
+        tx,
+        bitcoin_scan_op_tx,
+        stacks_scan_op_tx,
+        ctx,
+    )
+    .await
+    .unwrap();
 
     // Loop to check if the server is ready
     let mut attempts = 0;
@@ -233,7 +357,7 @@ pub async fn build_predicate_api_server(port: u16) -> (Receiver<ObserverCommand>
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
         attempts += 1;
     }
-    (rx, shutdown)
+    (rx, bitcoin_scan_op_rx, stacks_scan_op_rx, shutdown)
 }
 
 pub async fn start_redis(port: u16) -> Result<Child, String> {
@@ -292,13 +416,23 @@ pub fn get_chainhook_config(
 ) -> Config {
     let api_config = PredicatesApiConfig {
         http_port: chainhook_port,
+        database_backend: PredicatesDatabaseBackend::Redis,
         display_logs: true,
         database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
     };
     Config {
         http_api: PredicatesApi::On(api_config),
         storage: StorageConfig {
             working_dir: working_dir.into(),
+            offline: false,
+            archive_download_buffer_size: 0,
+            parallel_archive_decoding: false,
         },
         event_sources: vec![EventSourceConfig::StacksTsvPath(PathConfig {
             file_path: PathBuf::from(tsv_dir),
@@ -311,6 +445,7 @@ pub fn get_chainhook_config(
             max_number_of_processing_threads: 16,
             max_number_of_networking_threads: 16,
             max_caching_memory_size_mb: 32000,
+            max_buffered_occurrences_size_mb: None,
         },
         network: IndexerConfig {
             bitcoin_network: BitcoinNetwork::Regtest,
@@ -326,6 +461,17 @@ pub fn get_chainhook_config(
         monitoring: MonitoringConfig {
             prometheus_monitoring_port: prometheus_port,
         },
+        predicates: PredicatesConfig {
+            descriptor_default_range: None,
+            ack_deliveries: false,
+            stacks_operations_parsing: StacksOperationsParsing::default(),
+            deduplicate_reapplied_blocks: false,
+            dead_letters_path: None,
+            bundle: None,
+            http_delivery: HttpDeliveryConfig::default(),
+            deduplication_hash: ContentHashAlgorithm::default(),
+        },
+        audit_log: None,
     }
 }
 
@@ -296,6 +296,32 @@ pub fn write_stacks_blocks_to_tsv(block_count: u64, dir: &str) -> Result<(), Str
     Ok(())
 }
 
+pub fn append_stacks_blocks_to_tsv(
+    start_height: u64,
+    block_count: u64,
+    dir: &str,
+) -> Result<(), String> {
+    let file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(dir)
+        .map_err(|e| format!("failed to open tsv file: {}", e.to_string()))?;
+    let mut writer = csv::WriterBuilder::default()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .double_quote(false)
+        .quote(b'\'')
+        .buffer_capacity(8 * (1 << 10))
+        .from_writer(file);
+    for i in start_height..start_height + block_count {
+        writer
+            .serialize(create_stacks_block_received_record(0, i, 0, i + 100)?)
+            .map_err(|e| format!("failed to write tsv file: {}", e.to_string()))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("failed to write tsv file: {}", e.to_string()))
+}
+
 pub async fn mine_stacks_block(
     port: u16,
     fork_id: u8,
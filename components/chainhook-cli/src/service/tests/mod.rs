@@ -1,41 +1,90 @@
-use chainhook_sdk::chainhooks::types::ChainhookSpecificationNetworkMap;
-use chainhook_sdk::types::Chain;
+use chainhook_sdk::bitcoincore_rpc_json::bitcoin::hashes::{sha256, Hash};
+use chainhook_sdk::chainhooks::bitcoin::BitcoinChainhookSpecificationNetworkMap;
+use chainhook_sdk::chainhooks::stacks::StacksChainhookSpecificationNetworkMap;
+use chainhook_sdk::chainhooks::types::{
+    ChainhookInstance, ChainhookSpecificationNetworkMap, ChainhookStore,
+};
+use chainhook_sdk::types::{
+    BitcoinBlockData, BitcoinBlockMetadata, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData,
+    BitcoinNetwork, BlockIdentifier, Chain, StacksNetwork,
+};
 use chainhook_sdk::utils::Context;
-use rocket::serde::json::Value as JsonValue;
-use rocket::Shutdown;
+use redis::Connection;
+use rocket::serde::json::{Json, Value as JsonValue};
+use rocket::{Config as RocketConfig, Shutdown, State};
+use std::collections::HashMap;
 use std::fs::{self};
-use std::net::TcpListener;
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
 use std::path::PathBuf;
 use std::process::Child;
+use std::sync::{Arc, RwLock};
 use std::thread::sleep;
 use std::time::Duration;
 use test_case::test_case;
 
-use chainhook_sdk::observer::ObserverCommand;
+use chainhook_sdk::observer::{
+    HookExpirationData, ObserverCommand, ObserverEvent, PredicateDeregisteredEvent,
+    PredicateEvaluationReport,
+};
 
 use self::helpers::build_predicates::{build_bitcoin_payload, build_stacks_payload, DEFAULT_UUID};
+use self::helpers::get_free_port;
 use self::helpers::mock_service::{
     call_deregister_predicate, filter_predicate_status_from_all_predicates, flush_redis,
-    start_chainhook_service,
+    start_chainhook_service, start_redis,
 };
 use self::helpers::mock_stacks_node::{mine_burn_block, mine_stacks_block};
-use crate::config::PredicatesApi;
+use crate::config::{
+    AuditLogConfig, Config, EventSourceConfig, PathConfig, PredicatesApi, PredicatesApiConfig,
+    PredicatesBundleConfig, PredicatesDatabaseBackend, DEFAULT_REDIS_URI,
+    DEFAULT_SQLITE_PREDICATES_DB,
+};
+use crate::scan::common::replay_triggered_blocks;
+use crate::scan::stacks::consolidate_local_stacks_chainstate_using_csv;
+use crate::service::audit_log::{
+    read_audit_log_file, record_observer_event, AuditEventType, AuditSource,
+};
+use crate::service::predicate_store::{PredicateStore, SqlitePredicateStore};
+use crate::service::runloops::BitcoinScanOp;
 use crate::service::tests::helpers::build_predicates::get_random_uuid;
 use crate::service::tests::helpers::mock_service::{
-    build_predicate_api_server, call_get_predicate, call_ping, call_register_predicate,
+    build_predicate_api_server, build_predicate_api_server_with_chain_tips,
+    build_predicate_api_server_with_config, call_expire_predicate, call_get_chain_tips,
+    call_get_predicate, call_get_predicate_with_token, call_get_predicates_with_token, call_ping,
+    call_register_predicate, call_register_predicate_with_token, call_reload_predicates,
     get_chainhook_config, get_predicate_status, setup_bitcoin_chainhook_test,
     setup_stacks_chainhook_test, TestSetupResult,
 };
-use crate::service::tests::helpers::mock_stacks_node::create_burn_fork_at;
-use crate::service::{PredicateStatus, PredicateStatus::*, ScanningData, StreamingData};
+use crate::service::tests::helpers::mock_stacks_node::{
+    create_burn_fork_at, create_tmp_working_dir, write_stacks_blocks_to_tsv,
+};
+use crate::service::{
+    get_streaming_status_updates, insert_triggered_block_heights,
+    open_readwrite_predicates_db_conn_at_uri, retrieve_predicate_status,
+    set_confirmed_expiration_status, set_predicate_streaming_status, update_predicate_spec,
+    update_predicate_status, ChainTips, ExpiredData, PredicateStatus, PredicateStatus::*,
+    ScanningData, ScanningStatusCache, StreamingData, StreamingDataType, StreamingStatusUpdate,
+};
 use crate::storage::{get_all_unconfirmed_blocks, open_readonly_stacks_db_conn};
 
 use super::http_api::document_predicate_api_server;
+use super::predicates_bundle::fetch_predicates_bundle;
 
 pub mod helpers;
 mod observer_tests;
 mod runloop_tests;
 
+fn open_readwrite_predicates_db_conn(config: &PredicatesApiConfig) -> Result<Connection, String> {
+    open_readwrite_predicates_db_conn_at_uri(&config.database_uri)
+}
+
+fn open_readwrite_predicates_db_conn_for_chain(
+    config: &PredicatesApiConfig,
+    chain: &Chain,
+) -> Result<Connection, String> {
+    open_readwrite_predicates_db_conn_at_uri(config.get_database_uri(chain))
+}
+
 async fn test_register_predicate(predicate: JsonValue) -> Result<(), (String, Shutdown)> {
     // perhaps a little janky, we bind to the port 0 to find an open one, then
     // drop the listener to free up that port
@@ -118,13 +167,14 @@ async fn it_handles_bitcoin_predicates_with_network(network: &str) {
 #[test_case(json!({"scope": "outputs","op_return": {"equals": "0x69bd04208265aca9424d0337dac7d9e84371a2c91ece1891d67d3554bd9fdbe60afc6924d4b0773d90000006700010000006600012"}}) ; "with scope outputs type op_return equal match")]
 #[test_case(json!({"scope": "outputs","op_return": {"starts_with": "X2["}}) ; "with scope outputs type op_return starts_with match")]
 #[test_case(json!({"scope": "outputs","op_return": {"ends_with": "0x76a914000000000000000000000000000000000000000088ac"}}) ; "with scope outputs type op_return ends_with match")]
-#[test_case(json!({"scope": "outputs","p2pkh": {"equals": "mr1iPkD9N3RJZZxXRk7xF9d36gffa6exNC"}}) ; "with scope outputs type p2pkh")]
-#[test_case(json!({ "scope": "outputs","p2sh": {"equals": "2MxDJ723HBJtEMa2a9vcsns4qztxBuC8Zb2"}}) ; "with scope outputs type p2sh")]
-#[test_case(json!({"scope": "outputs","p2wpkh": {"equals": "bcrt1qnxknq3wqtphv7sfwy07m7e4sr6ut9yt6ed99jg"}}) ; "with scope outputs type p2wpkh")]
+#[test_case(json!({"scope": "outputs","p2pkh": {"equals": "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"}}) ; "with scope outputs type p2pkh")]
+#[test_case(json!({ "scope": "outputs","p2sh": {"equals": "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"}}) ; "with scope outputs type p2sh")]
+#[test_case(json!({"scope": "outputs","p2wpkh": {"equals": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"}}) ; "with scope outputs type p2wpkh")]
 #[test_case(json!({"scope": "outputs","p2wsh": {"equals": "bc1qklpmx03a8qkv263gy8te36w0z9yafxplc5kwzc"}}) ; "with scope outputs type p2wsh")]
 #[test_case(json!({"scope": "outputs","descriptor": {"expression": "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)", "range": [0,3]}}) ; "with scope outputs type descriptor")]
 #[test_case(json!({"scope": "stacks_protocol","operation": "stacker_rewarded"}) ; "with scope stacks_protocol operation stacker_rewarded")]
 #[test_case(json!({"scope": "stacks_protocol","operation": "block_committed"}) ; "with scope stacks_protocol operation block_committed")]
+#[test_case(json!({"scope": "stacks_protocol","operation": "block_committed","parent_block_ptr": 100,"parent_vtxindex": 1}) ; "with scope stacks_protocol operation block_committed with parent")]
 #[test_case(json!({"scope": "stacks_protocol","operation": "leader_registered"}) ; "with scope stacks_protocol operation leader_registered")]
 #[test_case(json!({"scope": "stacks_protocol","operation": "stx_transferred"}) ; "with scope stacks_protocol operation stx_transferred")]
 #[test_case(json!({"scope": "stacks_protocol","operation": "stx_locked"}) ; "with scope stacks_protocol operation stx_locked")]
@@ -168,7 +218,7 @@ async fn it_handles_bitcoin_then_that_predicates(then_that: JsonValue) {
 #[test_case(json!({"include_proof": true}) ; "include_proof filter")]
 #[test_case(json!({"include_inputs": true}) ; "include_inputs filter")]
 #[test_case(json!({"include_outputs": true}) ; "include_outputs filter")]
-#[test_case(json!({"include_witness": true}) ; "include_witness filter")]
+#[test_case(json!({"include_inputs": true,"include_witness": true}) ; "include_witness filter")]
 #[tokio::test]
 async fn it_handles_bitcoin_predicates_with_filters(filters: JsonValue) {
     let predicate = build_bitcoin_payload(None, None, None, Some(filters), None);
@@ -712,14 +762,16 @@ async fn test_deregister_predicate(chain: Chain) -> Result<(), String> {
     number_of_times_triggered: 0,
     last_occurrence: None,
     last_evaluation: 0,
-    last_evaluated_block_height: 4
+    last_evaluated_block_height: 4,
+    lag: None
 }), 6 => using assert_confirmed_expiration_status; "preloaded predicate with streaming status and last evaluated height below tip should get scanned until completion")]
 #[test_case(Streaming(StreamingData {
     number_of_blocks_evaluated: 5,
     number_of_times_triggered: 0,
     last_occurrence: None,
     last_evaluation: 0,
-    last_evaluated_block_height: 5
+    last_evaluated_block_height: 5,
+    lag: None
 }), 5 => using assert_streaming_status; "preloaded predicate with streaming status and last evaluated height at tip should be streamed")]
 #[tokio::test]
 #[cfg_attr(not(feature = "redis_tests"), ignore)]
@@ -856,6 +908,86 @@ fn it_generates_open_api_spec() {
     )
 }
 
+#[tokio::test]
+async fn it_reports_the_last_processed_blocks_as_chain_tips() {
+    let port = get_free_port().unwrap();
+    let chain_tips = Arc::new(RwLock::new(ChainTips::default()));
+    let (_rx, shutdown) =
+        build_predicate_api_server_with_chain_tips(port, chain_tips.clone()).await;
+
+    let new_blocks = (1..=3)
+        .map(|index| BitcoinBlockData {
+            block_identifier: BlockIdentifier {
+                index,
+                hash: format!("0x{:064x}", index),
+            },
+            parent_block_identifier: BlockIdentifier {
+                index: index - 1,
+                hash: format!("0x{:064x}", index - 1),
+            },
+            timestamp: 0,
+            transactions: vec![],
+            metadata: BitcoinBlockMetadata {
+                network: BitcoinNetwork::Regtest,
+            },
+        })
+        .collect::<Vec<_>>();
+    let last_block_identifier = new_blocks[2].block_identifier.clone();
+    chain_tips.write().unwrap().update_with_bitcoin_chain_event(
+        &BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks,
+            confirmed_blocks: vec![],
+        }),
+    );
+
+    let res = call_get_chain_tips(port).await;
+    shutdown.notify();
+    let res = res.unwrap();
+
+    assert_eq!(res["status"], json!(200));
+    assert_eq!(
+        res["result"]["bitcoin"]["block_identifier"],
+        json!(last_block_identifier)
+    );
+    assert_eq!(res["result"]["stacks"]["block_identifier"], JsonValue::Null);
+}
+
+#[test]
+fn it_computes_streaming_lag_from_chain_tips() {
+    let mut status = Streaming(StreamingData {
+        last_occurrence: None,
+        last_evaluation: 0,
+        number_of_times_triggered: 0,
+        number_of_blocks_evaluated: 10,
+        last_evaluated_block_height: 95,
+        lag: None,
+    });
+    let mut chain_tips = ChainTips::default();
+
+    // the tip is not known until a block is observed
+    status.set_streaming_lag(&Chain::Bitcoin, &chain_tips);
+    assert!(matches!(
+        &status,
+        Streaming(StreamingData { lag: None, .. })
+    ));
+
+    chain_tips.bitcoin = Some(BlockIdentifier {
+        index: 100,
+        hash: format!("0x{:064x}", 100),
+    });
+    status.set_streaming_lag(&Chain::Bitcoin, &chain_tips);
+    assert!(matches!(
+        &status,
+        Streaming(StreamingData { lag: Some(5), .. })
+    ));
+    // lags are computed from the tip of the predicate's chain
+    status.set_streaming_lag(&Chain::Stacks, &chain_tips);
+    assert!(matches!(
+        &status,
+        Streaming(StreamingData { lag: None, .. })
+    ));
+}
+
 #[tokio::test]
 #[cfg_attr(not(feature = "redis_tests"), ignore)]
 async fn it_seeds_block_pool_on_startup() -> Result<(), String> {
@@ -998,6 +1130,728 @@ async fn it_seeds_block_pool_on_startup() -> Result<(), String> {
     Ok(())
 }
 
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_serves_scanning_status_reads_from_memory() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let api_config = PredicatesApiConfig {
+        http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let predicate_key = format!("predicate:{DEFAULT_UUID}");
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::New,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+
+    let number_of_updates = 1000;
+    let mut status_cache = ScanningStatusCache::new(&predicate_key);
+    for block_height in 0..number_of_updates {
+        status_cache.set_predicate_scanning_status(
+            number_of_updates,
+            block_height + 1,
+            block_height / 10,
+            block_height,
+            &mut predicates_db_conn,
+            &ctx,
+        );
+    }
+    let number_of_status_reads = status_cache.get_number_of_status_reads();
+
+    let status: String =
+        redis::Commands::hget(&mut predicates_db_conn, &predicate_key, "status")
+            .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+    let status: PredicateStatus = serde_json::from_str(&status)
+        .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+    cleanup("", redis_port, &mut redis_process);
+
+    assert!(number_of_status_reads <= number_of_updates / 100);
+    match status {
+        PredicateStatus::Scanning(ScanningData {
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+            last_occurrence,
+            last_evaluated_block_height,
+            ..
+        }) => {
+            assert_eq!(number_of_blocks_evaluated, number_of_updates);
+            assert_eq!(number_of_times_triggered, 99);
+            assert!(last_occurrence.is_some());
+            assert_eq!(last_evaluated_block_height, number_of_updates - 1);
+        }
+        status => panic!("expected scanning status, found {:?}", status),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_does_not_lose_concurrent_streaming_status_increments() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let api_config = PredicatesApiConfig {
+        http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let predicate_key = format!("predicate:{DEFAULT_UUID}");
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::New,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+
+    // half of the threads report evaluations while the other half report occurrences
+    let number_of_threads = 8;
+    let number_of_updates = 100;
+    let handles = (0..number_of_threads)
+        .map(|thread_index| {
+            let api_config = api_config.clone();
+            let predicate_key = predicate_key.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || -> Result<(), String> {
+                let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)?;
+                for block_height in 0..number_of_updates {
+                    let streaming_data_type = match thread_index % 2 {
+                        0 => StreamingDataType::Evaluation {
+                            last_evaluated_height: block_height,
+                            evaluated_count: 1,
+                        },
+                        _ => StreamingDataType::Occurrence {
+                            last_triggered_height: block_height,
+                            triggered_count: 1,
+                        },
+                    };
+                    set_predicate_streaming_status(
+                        streaming_data_type,
+                        &predicate_key,
+                        &mut predicates_db_conn,
+                        &ctx,
+                    );
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| {
+                cleanup_err(
+                    "unable to join thread".into(),
+                    "",
+                    redis_port,
+                    &mut redis_process,
+                )
+            })?
+            .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    }
+
+    let status = retrieve_predicate_status(&predicate_key, &mut predicates_db_conn);
+    cleanup("", redis_port, &mut redis_process);
+
+    match status {
+        Some(PredicateStatus::Streaming(StreamingData {
+            number_of_blocks_evaluated,
+            number_of_times_triggered,
+            ..
+        })) => {
+            assert_eq!(
+                number_of_blocks_evaluated,
+                number_of_threads * number_of_updates
+            );
+            assert_eq!(
+                number_of_times_triggered,
+                number_of_threads / 2 * number_of_updates
+            );
+        }
+        status => panic!("expected streaming status, found {:?}", status),
+    }
+    Ok(())
+}
+
+#[test]
+fn it_reduces_reorg_reports_to_a_consistent_streaming_status() {
+    let block = |index: u64, fork: &str| BlockIdentifier {
+        index,
+        hash: format!("0x{fork}{index}"),
+    };
+    let mut report = PredicateEvaluationReport::new();
+    // the predicate triggered on blocks 100a and 101a, rolled back in favor of 100b to 102b
+    report.track_rollback("reorg", &vec![&block(100, "a"), &block(101, "a")]);
+    report.track_trigger("reorg", &vec![&block(100, "b")]);
+    report.track_evaluation("reorg", &block(100, "b"));
+    report.track_evaluation("reorg", &block(102, "b"));
+    // this predicate triggered on block 101a, and no block was evaluated in its stead
+    report.track_rollback("rollback_only", &vec![&block(101, "a")]);
+
+    let updates = get_streaming_status_updates(&report);
+    assert_eq!(
+        updates.get("reorg"),
+        Some(&StreamingStatusUpdate {
+            triggered_heights: vec![100],
+            evaluated_count: 1,
+            last_evaluated_height: Some(102),
+        })
+    );
+    assert_eq!(
+        updates.get("rollback_only"),
+        Some(&StreamingStatusUpdate {
+            triggered_heights: vec![],
+            evaluated_count: 0,
+            last_evaluated_height: Some(100),
+        })
+    );
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_stores_bitcoin_and_stacks_predicates_in_separate_databases() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let api_config = PredicatesApiConfig {
+        http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: Some(format!("redis://localhost:{redis_port}/1")),
+        stacks_database_uri: Some(format!("redis://localhost:{redis_port}/2")),
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: false,
+    };
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let bitcoin_predicate_key = format!("predicate:{}", get_random_uuid());
+    let stacks_predicate_key = format!("predicate:{}", get_random_uuid());
+    for (chain, predicate_key) in [
+        (Chain::Bitcoin, &bitcoin_predicate_key),
+        (Chain::Stacks, &stacks_predicate_key),
+    ] {
+        let mut predicates_db_conn =
+            open_readwrite_predicates_db_conn_for_chain(&api_config, &chain)
+                .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+        update_predicate_status(
+            predicate_key,
+            PredicateStatus::New,
+            &mut predicates_db_conn,
+            &ctx,
+        );
+    }
+
+    let mut found = vec![];
+    for chain in [Chain::Bitcoin, Chain::Stacks] {
+        let mut predicates_db_conn =
+            open_readwrite_predicates_db_conn_for_chain(&api_config, &chain)
+                .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+        for predicate_key in [&bitcoin_predicate_key, &stacks_predicate_key] {
+            let exists: bool = redis::Commands::exists(&mut predicates_db_conn, predicate_key)
+                .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+            found.push(exists);
+        }
+    }
+    let mut default_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let default_db_keys: Vec<String> =
+        redis::Commands::keys(&mut default_db_conn, "predicate:*")
+            .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+    cleanup("", redis_port, &mut redis_process);
+
+    assert_eq!(found, vec![true, false, false, true]);
+    assert!(default_db_keys.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_sets_a_ttl_on_expired_predicates() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let api_config = PredicatesApiConfig {
+        http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: Some(3600),
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let predicate = build_bitcoin_payload(None, None, None, None, None);
+    let bitcoin_spec: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up bitcoin chanhook spec for test");
+    let bitcoin_spec = bitcoin_spec
+        .into_specification_for_network(&BitcoinNetwork::Mainnet)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let spec = ChainhookInstance::Bitcoin(bitcoin_spec);
+    let predicate_key = spec.key();
+    update_predicate_spec(&predicate_key, &spec, &mut predicates_db_conn, &ctx);
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::UnconfirmedExpiration(ExpiredData {
+            number_of_blocks_evaluated: 1,
+            number_of_times_triggered: 1,
+            last_occurrence: None,
+            last_evaluated_block_height: 1,
+            expired_at_block_height: 1,
+        }),
+        &mut predicates_db_conn,
+        &ctx,
+    );
+    set_confirmed_expiration_status(
+        &predicate_key,
+        api_config.expired_predicates_retention_secs,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+    let ttl_after_expiration: i64 =
+        redis::Commands::ttl(&mut predicates_db_conn, &predicate_key)
+            .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+
+    // registering the predicate again clears the pending eviction
+    update_predicate_spec(&predicate_key, &spec, &mut predicates_db_conn, &ctx);
+    let ttl_after_registration: i64 = redis::Commands::ttl(&mut predicates_db_conn, &predicate_key)
+        .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+    cleanup("", redis_port, &mut redis_process);
+
+    assert!(ttl_after_expiration > 0 && ttl_after_expiration <= 3600);
+    assert_eq!(ttl_after_registration, -1);
+    Ok(())
+}
+
+#[post("/expired", format = "application/json", data = "<payload>")]
+fn post_expired_predicate(
+    payload: Json<JsonValue>,
+    tx: &State<crossbeam_channel::Sender<JsonValue>>,
+) {
+    let _ = tx.send(payload.into_inner());
+}
+
+async fn start_expiration_callback_server(port: u16, tx: crossbeam_channel::Sender<JsonValue>) {
+    let config = RocketConfig::figment()
+        .merge(("port", port))
+        .merge(("address", IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))))
+        .merge(("log_level", "off"));
+    let _rocket = rocket::build()
+        .configure(config)
+        .manage(tx)
+        .mount("/", routes![post_expired_predicate])
+        .launch()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_notifies_the_on_expire_url_of_expired_predicates() -> Result<(), String> {
+    let callback_port = get_free_port()?;
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let _ = hiro_system_kit::thread_named("Start expiration callback server")
+        .spawn(move || {
+            let future = start_expiration_callback_server(callback_port, tx);
+            let _ = hiro_system_kit::nestable_block_on(future);
+        })
+        .expect("unable to spawn thread");
+    sleep(Duration::new(1, 0));
+
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let api_config = PredicatesApiConfig {
+        http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let mut predicate = build_bitcoin_payload(
+        Some("regtest"),
+        None,
+        None,
+        Some(json!({"start_block": 1, "end_block": 3})),
+        None,
+    );
+    predicate["on_expire_url"] = json!(format!("http://localhost:{callback_port}/expired"));
+    let bitcoin_spec: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up bitcoin chanhook spec for test");
+    let bitcoin_spec = bitcoin_spec
+        .into_specification_for_network(&BitcoinNetwork::Regtest)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let spec = ChainhookInstance::Bitcoin(bitcoin_spec);
+    let predicate_key = spec.key();
+    let expired_data = ExpiredData {
+        number_of_blocks_evaluated: 3,
+        number_of_times_triggered: 3,
+        last_occurrence: None,
+        last_evaluated_block_height: 3,
+        expired_at_block_height: 3,
+    };
+    update_predicate_spec(&predicate_key, &spec, &mut predicates_db_conn, &ctx);
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::UnconfirmedExpiration(expired_data.clone()),
+        &mut predicates_db_conn,
+        &ctx,
+    );
+    // the end_block of the predicate is confirmed
+    set_confirmed_expiration_status(&predicate_key, None, &mut predicates_db_conn, &ctx);
+    let payload = rx
+        .recv_timeout(Duration::from_secs(5))
+        .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+    cleanup("", redis_port, &mut redis_process);
+
+    assert_eq!(
+        payload,
+        json!({
+            "uuid": DEFAULT_UUID,
+            "status": PredicateStatus::ConfirmedExpiration(expired_data),
+        })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_force_expires_a_scanning_predicate() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let chainhook_port = get_free_port()?;
+    let api_config = PredicatesApiConfig {
+        http_port: chainhook_port,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: Some("admin-token".into()),
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let predicate = build_bitcoin_payload(None, None, None, None, Some(DEFAULT_UUID));
+    let bitcoin_spec: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up bitcoin chanhook spec for test");
+    let bitcoin_spec = bitcoin_spec
+        .into_specification_for_network(&BitcoinNetwork::Mainnet)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let spec = ChainhookInstance::Bitcoin(bitcoin_spec);
+    let predicate_key = spec.key();
+    update_predicate_spec(&predicate_key, &spec, &mut predicates_db_conn, &ctx);
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::Scanning(ScanningData {
+            number_of_blocks_to_scan: 10,
+            number_of_blocks_evaluated: 4,
+            number_of_times_triggered: 1,
+            last_occurrence: None,
+            last_evaluated_block_height: 4,
+        }),
+        &mut predicates_db_conn,
+        &ctx,
+    );
+
+    let (observer_command_rx, bitcoin_scan_op_rx, _, shutdown) =
+        build_predicate_api_server_with_config(
+            api_config,
+            Arc::new(RwLock::new(ChainTips::default())),
+        )
+        .await;
+    let unauthorized = call_expire_predicate(DEFAULT_UUID, "wrong-token", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let result = call_expire_predicate(DEFAULT_UUID, "admin-token", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let status = get_predicate_status(DEFAULT_UUID, chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let scan_op = bitcoin_scan_op_rx.try_recv();
+    let observer_command = observer_command_rx.try_recv();
+    shutdown.notify();
+    cleanup("", redis_port, &mut redis_process);
+
+    assert_eq!(unauthorized.get("status"), Some(&json!(401)));
+    assert_eq!(result.get("status"), Some(&json!(200)));
+    assert_eq!(
+        status,
+        ConfirmedExpiration(ExpiredData {
+            number_of_blocks_evaluated: 4,
+            number_of_times_triggered: 1,
+            last_occurrence: None,
+            last_evaluated_block_height: 4,
+            expired_at_block_height: 4,
+        })
+    );
+    match scan_op {
+        Ok(BitcoinScanOp::KillScan(uuid)) => assert_eq!(uuid, DEFAULT_UUID),
+        _ => panic!("expected the predicate scan to be killed"),
+    }
+    // the predicate also stops being evaluated if it was already streaming
+    match observer_command {
+        Ok(ObserverCommand::ExpireBitcoinPredicate(expiration_data)) => assert_eq!(
+            expiration_data,
+            HookExpirationData {
+                hook_uuid: predicate_key,
+                block_height: 4,
+            }
+        ),
+        _ => panic!("expected the predicate to be expired in the observer"),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_reloads_predicates_edited_in_redis() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let chainhook_port = get_free_port()?;
+    let api_config = PredicatesApiConfig {
+        http_port: chainhook_port,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: Some("admin-token".into()),
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let predicate = build_bitcoin_payload(None, None, None, None, Some(DEFAULT_UUID));
+    let bitcoin_spec: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up bitcoin chanhook spec for test");
+    let mut bitcoin_spec = bitcoin_spec
+        .into_specification_for_network(&BitcoinNetwork::Mainnet)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    bitcoin_spec.enabled = true;
+    let mut chainhook_store = ChainhookStore::new();
+    chainhook_store
+        .bitcoin_chainhooks
+        .push(bitcoin_spec.clone());
+
+    // the spec is edited in redis, without going through the predicate registration API
+    bitcoin_spec.enabled = false;
+    bitcoin_spec.expire_after_occurrence = Some(3);
+    let spec = ChainhookInstance::Bitcoin(bitcoin_spec);
+    update_predicate_spec(&spec.key(), &spec, &mut predicates_db_conn, &ctx);
+
+    let (observer_commands_rx, _, _, shutdown) = build_predicate_api_server_with_config(
+        api_config,
+        Arc::new(RwLock::new(ChainTips::default())),
+    )
+    .await;
+    let unauthorized = call_reload_predicates("wrong-token", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let result = call_reload_predicates("admin-token", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let command = observer_commands_rx.recv_timeout(Duration::from_secs(5));
+    shutdown.notify();
+    cleanup("", redis_port, &mut redis_process);
+
+    assert_eq!(unauthorized.get("status"), Some(&json!(401)));
+    assert_eq!(result.get("status"), Some(&json!(200)));
+    assert_eq!(result.get("result"), Some(&json!(1)));
+    let Ok(ObserverCommand::ReloadPredicates(instances)) = command else {
+        panic!("expected a ReloadPredicates command");
+    };
+    let (updated, added, removed) = chainhook_store.reload_instances(instances.clone());
+    assert_eq!(updated.len(), 1);
+    assert!(added.is_empty());
+    assert!(removed.is_empty());
+    // the running predicate picks up the edited spec, and keeps its state
+    let reloaded = &chainhook_store.bitcoin_chainhooks[0];
+    assert_eq!(reloaded.expire_after_occurrence, Some(3));
+    assert!(reloaded.enabled);
+
+    // unchanged predicates are left untouched
+    let (updated, added, removed) = chainhook_store.reload_instances(instances.clone());
+    assert!(updated.is_empty() && added.is_empty() && removed.is_empty());
+
+    // predicates waiting for their scan may not be written yet, and are kept
+    chainhook_store.bitcoin_chainhooks[0].enabled = false;
+    let (_, _, removed) = chainhook_store.reload_instances(vec![]);
+    assert!(removed.is_empty());
+    assert_eq!(chainhook_store.bitcoin_chainhooks.len(), 1);
+    // while enabled predicates missing from the db are deregistered
+    chainhook_store.bitcoin_chainhooks[0].enabled = true;
+    let (_, _, removed) = chainhook_store.reload_instances(vec![]);
+    assert_eq!(removed.len(), 1);
+    assert!(chainhook_store.bitcoin_chainhooks.is_empty());
+    // and the predicates only found in the db are registered, disabled until they're scanned
+    let (_, added, _) = chainhook_store.reload_instances(instances);
+    assert_eq!(added.len(), 1);
+    assert!(!chainhook_store.bitcoin_chainhooks[0].enabled);
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_scopes_predicates_to_their_owner() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let chainhook_port = get_free_port()?;
+    let api_config = PredicatesApiConfig {
+        http_port: chainhook_port,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: Some("admin-token".into()),
+        owner_tokens: Some(HashMap::from([
+            ("token-a".to_string(), "owner-a".to_string()),
+            ("token-b".to_string(), "owner-b".to_string()),
+        ])),
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+
+    let predicate = build_bitcoin_payload(None, None, None, None, Some(DEFAULT_UUID));
+    let bitcoin_spec: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up bitcoin chanhook spec for test");
+    let mut bitcoin_spec = bitcoin_spec
+        .into_specification_for_network(&BitcoinNetwork::Mainnet)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    bitcoin_spec.owner_uuid = Some("owner-a".into());
+    let spec = ChainhookInstance::Bitcoin(bitcoin_spec);
+    let predicate_key = spec.key();
+    update_predicate_spec(&predicate_key, &spec, &mut predicates_db_conn, &ctx);
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::New,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+
+    let (_, _, _, shutdown) = build_predicate_api_server_with_config(
+        api_config,
+        Arc::new(RwLock::new(ChainTips::default())),
+    )
+    .await;
+    let owner_response = call_get_predicate_with_token(DEFAULT_UUID, "token-a", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let other_owner_response =
+        call_get_predicate_with_token(DEFAULT_UUID, "token-b", chainhook_port)
+            .await
+            .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let admin_response = call_get_predicate_with_token(DEFAULT_UUID, "admin-token", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let unauthorized_response = call_get_predicate(DEFAULT_UUID, chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let owner_predicates = call_get_predicates_with_token("token-a", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let other_owner_predicates = call_get_predicates_with_token("token-b", chainhook_port)
+        .await
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let other_owner_registration = call_register_predicate_with_token(
+        &build_bitcoin_payload(None, None, None, None, Some(DEFAULT_UUID)),
+        "token-b",
+        chainhook_port,
+    )
+    .await
+    .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    shutdown.notify();
+    cleanup("", redis_port, &mut redis_process);
+
+    assert_eq!(owner_response.get("status"), Some(&json!(200)));
+    assert_eq!(owner_response["result"]["uuid"], json!(DEFAULT_UUID));
+    // the predicates of other owners are reported as missing
+    assert_eq!(other_owner_response, json!({ "status": 404 }));
+    assert_eq!(admin_response.get("status"), Some(&json!(200)));
+    assert_eq!(unauthorized_response.get("status"), Some(&json!(401)));
+    assert_eq!(
+        owner_predicates["result"].as_array().map(|p| p.len()),
+        Some(1)
+    );
+    assert_eq!(other_owner_predicates["result"], json!([]));
+    // registrations don't reveal the uuids in use, owner-scoped predicates get a new one
+    assert_eq!(other_owner_registration.get("status"), Some(&json!(200)));
+    assert_ne!(other_owner_registration["result"], json!(DEFAULT_UUID));
+    Ok(())
+}
+
 pub fn cleanup_err(
     error: String,
     working_dir: &str,
@@ -1013,3 +1867,462 @@ pub fn cleanup(working_dir: &str, redis_port: u16, redis_process: &mut Child) {
     flush_redis(redis_port);
     redis_process.kill().unwrap();
 }
+
+#[tokio::test]
+async fn it_rejects_oversized_predicate_bodies() {
+    let port = get_free_port().unwrap();
+    let api_config = PredicatesApiConfig {
+        http_port: port,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: DEFAULT_REDIS_URI.to_string(),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: Some(1024),
+        display_logs: false,
+    };
+    let (rx, _, _, shutdown) = build_predicate_api_server_with_config(
+        api_config,
+        Arc::new(RwLock::new(ChainTips::default())),
+    )
+    .await;
+
+    let mut predicate = build_bitcoin_payload(None, None, None, None, Some(DEFAULT_UUID));
+    predicate["name"] = JsonValue::String("a".repeat(2048));
+    let res = call_register_predicate(&predicate, port).await;
+    shutdown.notify();
+
+    let res = res.unwrap_or_else(|e| panic!("test failed with error: {e}"));
+    assert_eq!(res.get("status"), Some(&JsonValue::from(413)));
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn it_rejects_denylist_files_in_registered_predicates() {
+    let port = get_free_port().unwrap();
+    let (rx, shutdown) = build_predicate_api_server(port).await;
+
+    let predicate = build_bitcoin_payload(
+        None,
+        Some(json!({"scope":"outputs", "denylist": {"path": "/etc/passwd"}})),
+        None,
+        None,
+        Some(DEFAULT_UUID),
+    );
+    let res = call_register_predicate(&predicate, port).await;
+    shutdown.notify();
+
+    let res = res.unwrap_or_else(|e| panic!("test failed with error: {e}"));
+    assert_eq!(res.get("status"), Some(&JsonValue::from(422)));
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn it_warns_when_registering_unsatisfiable_predicates() {
+    let port = get_free_port().unwrap();
+    let (rx, shutdown) = build_predicate_api_server(port).await;
+
+    let predicate = build_stacks_payload(
+        Some("mainnet"),
+        Some(json!({"scope":"block_height", "equals": 5})),
+        None,
+        Some(json!({"start_block": 10})),
+        Some(DEFAULT_UUID),
+    );
+    let res = call_register_predicate(&predicate, port).await;
+    shutdown.notify();
+
+    let res = res.unwrap_or_else(|e| panic!("test failed with error: {e}"));
+    assert_eq!(res.get("status"), Some(&JsonValue::from(200)));
+    assert_eq!(
+        res.get("warnings"),
+        Some(&json!(["Stacks predicate 'test' for network mainnet can never match: 'block_height' rule only matches blocks outside of the range defined by `start_block` and `end_block`"]))
+    );
+    match rx.try_recv() {
+        Ok(ObserverCommand::RegisterPredicate(_)) => {}
+        _ => panic!("expected the predicate to be registered"),
+    }
+}
+
+#[tokio::test]
+async fn it_rejects_predicates_starting_after_the_chain_tip_and_ending_before_it() {
+    let port = get_free_port().unwrap();
+    let (rx, shutdown) = build_predicate_api_server(port).await;
+
+    // the bounds contradict each other whatever the chain tip, so the predicate is rejected
+    // rather than registered with a warning
+    let predicate = build_stacks_payload(
+        Some("mainnet"),
+        None,
+        None,
+        Some(json!({"start_block": 1_000_000_000, "end_block": 10})),
+        Some(DEFAULT_UUID),
+    );
+    let res = call_register_predicate(&predicate, port).await;
+    shutdown.notify();
+
+    let res = res.unwrap_or_else(|e| panic!("test failed with error: {e}"));
+    assert_eq!(res.get("status"), Some(&JsonValue::from(422)));
+    let error = res
+        .get("error")
+        .and_then(|e| e.as_str())
+        .unwrap_or_default();
+    assert!(
+        error.contains("`end_block` should be greater than `start_block`"),
+        "{}",
+        error
+    );
+    assert!(res.get("warnings").is_none());
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn it_records_predicate_lifecycle_events_in_audit_log() {
+    let (working_dir, _) = create_tmp_working_dir().unwrap();
+    let audit_log_path = PathBuf::from(&working_dir).join("audit.log");
+    let audit_log = Some(AuditLogConfig::File(PathConfig {
+        file_path: audit_log_path.clone(),
+    }));
+    let ctx = Context::empty();
+
+    let predicate = build_bitcoin_payload(None, None, None, None, Some(DEFAULT_UUID));
+    let bitcoin_spec: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up bitcoin chanhook spec for test");
+    let bitcoin_spec = bitcoin_spec
+        .into_specification_for_network(&BitcoinNetwork::Regtest)
+        .unwrap();
+    record_observer_event(
+        &audit_log,
+        &ObserverEvent::PredicateRegistered(ChainhookInstance::Bitcoin(bitcoin_spec)),
+        &ctx,
+    );
+    record_observer_event(
+        &audit_log,
+        &ObserverEvent::PredicateDeregistered(PredicateDeregisteredEvent {
+            predicate_uuid: DEFAULT_UUID.to_string(),
+            chain: Chain::Bitcoin,
+        }),
+        &ctx,
+    );
+
+    let entries = read_audit_log_file(&audit_log_path).unwrap();
+    fs::remove_dir_all(&working_dir).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].event, AuditEventType::Registered);
+    assert_eq!(entries[1].event, AuditEventType::Deregistered);
+    for entry in entries.iter() {
+        assert_eq!(entry.uuid, DEFAULT_UUID);
+        assert_eq!(entry.chain, Some("bitcoin".to_string()));
+        assert_eq!(entry.source, AuditSource::Observer);
+    }
+}
+
+#[test]
+fn it_rejects_malformed_predicates_db_uris() {
+    let result = open_readwrite_predicates_db_conn_at_uri("localhost:6379");
+    assert!(result.is_err());
+    assert!(result
+        .err()
+        .unwrap()
+        .starts_with("unable to open db localhost:6379"));
+}
+
+#[test]
+fn it_stores_predicates_in_sqlite() {
+    let (working_dir, _) = create_tmp_working_dir().unwrap();
+    let db_path = PathBuf::from(&working_dir).join(DEFAULT_SQLITE_PREDICATES_DB);
+    let mut predicates_db_conn =
+        SqlitePredicateStore::open(&db_path.display().to_string()).unwrap();
+    let ctx = Context::empty();
+
+    let predicate = build_bitcoin_payload(None, None, None, None, Some(DEFAULT_UUID));
+    let bitcoin_spec: BitcoinChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up bitcoin chanhook spec for test");
+    let bitcoin_spec = bitcoin_spec
+        .into_specification_for_network(&BitcoinNetwork::Regtest)
+        .unwrap();
+    let spec = ChainhookInstance::Bitcoin(bitcoin_spec);
+    let predicate_key = spec.key();
+    update_predicate_spec(&predicate_key, &spec, &mut predicates_db_conn, &ctx);
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::New,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+    insert_triggered_block_heights(&predicate_key, &[17, 12, 15], &mut predicates_db_conn, &ctx);
+    let counters = predicates_db_conn
+        .increment_status_counters(&predicate_key, (0, 0), (3, 1))
+        .unwrap();
+    predicates_db_conn
+        .insert_expiration(&Chain::Bitcoin, 20, &predicate_key)
+        .unwrap();
+
+    let keys = predicates_db_conn.list().unwrap();
+    let entry = predicates_db_conn.get_entry(&predicate_key).unwrap();
+    let triggered_block_heights = predicates_db_conn
+        .get_triggered_block_heights(&predicate_key)
+        .unwrap();
+    let expirations = predicates_db_conn
+        .take_expirations(&Chain::Bitcoin, 20)
+        .unwrap();
+    let expirations_after_take = predicates_db_conn
+        .take_expirations(&Chain::Bitcoin, 20)
+        .unwrap();
+    predicates_db_conn.set_eviction(&predicate_key, 0).unwrap();
+    let keys_after_eviction = predicates_db_conn.list().unwrap();
+    fs::remove_dir_all(&working_dir).unwrap();
+
+    assert_eq!(keys, vec![predicate_key.clone()]);
+    assert_eq!(counters, (3, 1));
+    assert_eq!(entry, Some((spec, PredicateStatus::New)));
+    assert_eq!(triggered_block_heights, vec![12, 15, 17]);
+    assert_eq!(expirations, vec![predicate_key]);
+    assert!(expirations_after_take.is_empty());
+    assert!(keys_after_eviction.is_empty());
+}
+
+#[test]
+fn it_does_not_overwrite_final_statuses_with_cached_scanning_statuses() {
+    let (working_dir, _) = create_tmp_working_dir().unwrap();
+    let db_path = PathBuf::from(&working_dir).join(DEFAULT_SQLITE_PREDICATES_DB);
+    let mut predicates_db_conn =
+        SqlitePredicateStore::open(&db_path.display().to_string()).unwrap();
+    let ctx = Context::empty();
+    let predicate_key = format!("predicate:{DEFAULT_UUID}");
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::New,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+
+    let mut status_cache = ScanningStatusCache::new(&predicate_key);
+    status_cache.set_predicate_scanning_status(10, 1, 0, 0, &mut predicates_db_conn, &ctx);
+    // interrupted by another writer while the scan is served from the cached status
+    let interrupted = PredicateStatus::Interrupted("interrupted".into());
+    update_predicate_status(
+        &predicate_key,
+        interrupted.clone(),
+        &mut predicates_db_conn,
+        &ctx,
+    );
+    status_cache.set_predicate_scanning_status(10, 2, 0, 1, &mut predicates_db_conn, &ctx);
+    let status = predicates_db_conn.get_status(&predicate_key).unwrap();
+    fs::remove_dir_all(&working_dir).unwrap();
+
+    assert_eq!(status, Some(interrupted));
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_replays_only_previously_triggered_blocks() -> Result<(), String> {
+    let (working_dir, tsv_dir) = create_tmp_working_dir()?;
+    write_stacks_blocks_to_tsv(100, &tsv_dir)?;
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let api_config = PredicatesApiConfig {
+        http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, &working_dir, redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+    let mut config = Config::devnet_default();
+    config.storage.working_dir = working_dir.clone();
+    config.event_sources = vec![EventSourceConfig::StacksTsvPath(PathConfig {
+        file_path: PathBuf::from(&tsv_dir),
+    })];
+    config.http_api = PredicatesApi::On(api_config);
+    consolidate_local_stacks_chainstate_using_csv(&mut config, &ctx)
+        .await
+        .map_err(|e| cleanup_err(e, &working_dir, redis_port, &mut redis_process))?;
+
+    let occurrences_path = format!("{working_dir}/occurrences.json");
+    fs::File::create(&occurrences_path)
+        .map_err(|e| cleanup_err(e.to_string(), &working_dir, redis_port, &mut redis_process))?;
+    let predicate = build_stacks_payload(
+        Some("devnet"),
+        Some(json!({"scope":"block_height", "higher_than": 0})),
+        Some(json!({"file_append": {"path": occurrences_path}})),
+        None,
+        Some(DEFAULT_UUID),
+    );
+    let stacks_spec: StacksChainhookSpecificationNetworkMap =
+        serde_json::from_value(predicate).expect("failed to set up stacks chanhook spec for test");
+    let stacks_spec = stacks_spec
+        .into_specification_for_network(&StacksNetwork::Devnet)
+        .map_err(|e| cleanup_err(e, &working_dir, redis_port, &mut redis_process))?;
+    let spec = ChainhookInstance::Stacks(stacks_spec);
+    let predicate_key = spec.key();
+    update_predicate_spec(&predicate_key, &spec, &mut predicates_db_conn, &ctx);
+    update_predicate_status(
+        &predicate_key,
+        PredicateStatus::New,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+    insert_triggered_block_heights(&predicate_key, &[17, 12, 15], &mut predicates_db_conn, &ctx);
+
+    let summary = replay_triggered_blocks(DEFAULT_UUID, &mut config, &ctx)
+        .await
+        .map_err(|e| cleanup_err(e, &working_dir, redis_port, &mut redis_process))?;
+    let occurrences = fs::read_to_string(&occurrences_path)
+        .map_err(|e| cleanup_err(e.to_string(), &working_dir, redis_port, &mut redis_process))?;
+    let status = retrieve_predicate_status(&predicate_key, &mut predicates_db_conn);
+    cleanup(&working_dir, redis_port, &mut redis_process);
+
+    let replayed_heights = occurrences
+        .lines()
+        .map(|line| {
+            let occurrence: JsonValue = serde_json::from_str(line).unwrap();
+            occurrence["apply"][0]["block_identifier"]["index"]
+                .as_u64()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(replayed_heights, vec![12, 15, 17]);
+    assert_eq!(summary.number_of_blocks_scanned, 3);
+    assert_eq!(summary.number_of_times_triggered, 3);
+    assert_eq!(status, Some(PredicateStatus::New));
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "redis_tests"), ignore)]
+async fn it_streams_predicate_status_updates() -> Result<(), String> {
+    let redis_port = get_free_port()?;
+    let mut redis_process = start_redis(redis_port).await?;
+    let api_config = PredicatesApiConfig {
+        http_port: 0,
+        database_backend: PredicatesDatabaseBackend::Redis,
+        database_uri: format!("redis://localhost:{redis_port}/"),
+        bitcoin_database_uri: None,
+        stacks_database_uri: None,
+        expired_predicates_retention_secs: None,
+        admin_token: None,
+        owner_tokens: None,
+        max_body_size: None,
+        display_logs: false,
+    };
+    let mut predicates_db_conn = open_readwrite_predicates_db_conn(&api_config)
+        .map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let ctx = Context {
+        logger: Some(hiro_system_kit::log::setup_logger()),
+        tracer: false,
+    };
+    let port = get_free_port().map_err(|e| cleanup_err(e, "", redis_port, &mut redis_process))?;
+    let (_, shutdown) = build_predicate_api_server(port).await;
+
+    let uuid = get_random_uuid();
+    let mut response = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{port}/v1/chainhooks/{uuid}/status/stream"
+        ))
+        .send()
+        .await
+        .map_err(|e| cleanup_err(e.to_string(), "", redis_port, &mut redis_process))?;
+
+    update_predicate_status(
+        &format!("predicate:{uuid}"),
+        PredicateStatus::New,
+        &mut predicates_db_conn,
+        &ctx,
+    );
+    let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk()).await;
+    shutdown.notify();
+    cleanup("", redis_port, &mut redis_process);
+
+    let chunk = chunk
+        .map_err(|_| "timed out waiting for a status event".to_string())?
+        .map_err(|e| e.to_string())?
+        .ok_or("status stream ended without any event".to_string())?;
+    let event = String::from_utf8_lossy(&chunk);
+    let data = event
+        .trim()
+        .strip_prefix("data:")
+        .unwrap_or_else(|| panic!("expected a data event, found {event}"));
+    assert_eq!(
+        serde_json::from_str::<JsonValue>(data).unwrap(),
+        json!({ "type": "new" })
+    );
+    Ok(())
+}
+
+#[get("/predicates.json")]
+fn get_predicates_bundle(bundle: &State<String>) -> String {
+    bundle.inner().clone()
+}
+
+async fn start_predicates_bundle_server(port: u16, bundle: String) {
+    let config = RocketConfig::figment()
+        .merge(("port", port))
+        .merge(("address", IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))))
+        .merge(("log_level", "off"));
+    let _rocket = rocket::build()
+        .configure(config)
+        .manage(bundle)
+        .mount("/", routes![get_predicates_bundle])
+        .launch()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn it_registers_predicates_from_a_remote_bundle() {
+    let bundle = json!([
+        build_bitcoin_payload(Some("regtest"), None, None, None, Some("bitcoin-uuid")),
+        build_stacks_payload(Some("devnet"), None, None, None, Some("stacks-uuid")),
+    ])
+    .to_string();
+    let sha256 = sha256::Hash::hash(bundle.as_bytes()).to_string();
+    let port = get_free_port().unwrap();
+    let _ = hiro_system_kit::thread_named("Start predicates bundle server")
+        .spawn(move || {
+            let future = start_predicates_bundle_server(port, bundle);
+            let _ = hiro_system_kit::nestable_block_on(future);
+        })
+        .expect("unable to spawn thread");
+    sleep(Duration::new(1, 0));
+    let url = format!("http://localhost:{port}/predicates.json");
+
+    let predicates = fetch_predicates_bundle(&PredicatesBundleConfig {
+        url: url.clone(),
+        sha256,
+    })
+    .await
+    .unwrap();
+    let mut chainhook_store = ChainhookStore::new();
+    for predicate in predicates.into_iter() {
+        chainhook_store
+            .register_instance_from_network_map(
+                (&BitcoinNetwork::Regtest, &StacksNetwork::Devnet),
+                predicate,
+            )
+            .unwrap();
+    }
+    assert_eq!(chainhook_store.bitcoin_chainhooks[0].uuid, "bitcoin-uuid");
+    assert_eq!(chainhook_store.stacks_chainhooks[0].uuid, "stacks-uuid");
+
+    // a tampered bundle is rejected as a whole
+    let err = fetch_predicates_bundle(&PredicatesBundleConfig {
+        url,
+        sha256: "00".repeat(32),
+    })
+    .await
+    .unwrap_err();
+    assert!(err.starts_with("checksum mismatch"));
+}
@@ -1,10 +1,11 @@
 use std::{sync::mpsc::channel, thread::sleep, time::Duration};
 
 use chainhook_sdk::{
-    chainhooks::types::ChainhookStore,
+    chainhooks::types::{ChainhookStore, ContentHashAlgorithm, SigningSecrets},
+    indexer::bitcoin::StacksOperationsParsing,
     observer::{start_event_observer, EventObserverConfig},
     types::{BitcoinNetwork, StacksNodeConfig},
-    utils::Context,
+    utils::{Context, HttpRetryPolicy},
 };
 use reqwest::Method;
 use serde_json::Value;
@@ -204,6 +205,16 @@ async fn it_responds_200_for_unimplemented_endpoints(
         bitcoin_network: BitcoinNetwork::Regtest,
         stacks_network: chainhook_sdk::types::StacksNetwork::Devnet,
         prometheus_monitoring_port: None,
+        descriptor_default_range: None,
+        pending_deliveries_path: None,
+        dead_letters_path: None,
+        http_client: None,
+        http_retry_policy: HttpRetryPolicy::default(),
+        signing_secrets: SigningSecrets::default(),
+        deduplication_hash: ContentHashAlgorithm::default(),
+        stacks_operations_parsing: StacksOperationsParsing::default(),
+        max_buffered_occurrences_size: None,
+        deduplicate_reapplied_blocks: false,
     };
     start_and_ping_event_observer(config, ingestion_port).await;
     let url = format!("http://localhost:{ingestion_port}{endpoint}");
@@ -2,7 +2,10 @@ use std::{path::PathBuf, sync::mpsc::channel, thread::sleep, time::Duration};
 
 use chainhook_sdk::{
     chainhooks::{
-        bitcoin::{BitcoinChainhookInstance, BitcoinPredicateType},
+        bitcoin::{
+            AmountFormat, BitcoinChainhookInstance, BitcoinPredicateType, PayloadShape,
+            PredicateAddress, ProofFormat,
+        },
         stacks::{StacksChainhookInstance, StacksPredicate},
         types::{BlockIdentifierIndexRule, HookAction},
     },
@@ -12,13 +15,20 @@ use chainhook_sdk::{
 
 use crate::{
     config::{Config, EventSourceConfig, PathConfig},
-    scan::stacks::consolidate_local_stacks_chainstate_using_csv,
+    scan::{
+        bitcoin::{scan_bitcoin_chainstate_via_rpc_using_predicates, BitcoinScanRequest},
+        common::{PredicateScanResult, ScanSummary},
+        stacks::consolidate_local_stacks_chainstate_using_csv,
+    },
     service::{
         runloops::{
             start_bitcoin_scan_runloop, start_stacks_scan_runloop, BitcoinScanOp, StacksScanOp,
         },
         tests::helpers::{
-            mock_bitcoin_rpc::mock_bitcoin_rpc, mock_service::setup_chainhook_service_ports,
+            mock_bitcoin_rpc::{
+                mock_bitcoin_rpc, mock_bitcoin_rpc_with_getblock_requests, GetBlockRequests,
+            },
+            mock_service::setup_chainhook_service_ports,
         },
     },
 };
@@ -59,9 +69,16 @@ async fn test_stacks_runloop_kill_scan() {
     let (scan_op_tx, scan_op_rx) = crossbeam_channel::unbounded();
     let (observer_command_tx, _observer_command_rx) = channel();
 
+    let http_client = config.build_http_delivery_client().unwrap();
     let _ = hiro_system_kit::thread_named("Stacks scan runloop")
         .spawn(move || {
-            start_stacks_scan_runloop(&config, scan_op_rx, observer_command_tx.clone(), &ctx);
+            start_stacks_scan_runloop(
+                &config,
+                &http_client,
+                scan_op_rx,
+                observer_command_tx.clone(),
+                &ctx,
+            );
         })
         .expect("unable to spawn thread");
 
@@ -69,6 +86,7 @@ async fn test_stacks_runloop_kill_scan() {
     let predicate_spec = StacksChainhookInstance {
         uuid: uuid.clone(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "idc".to_string(),
         network: StacksNetwork::Devnet,
         version: 0,
@@ -78,6 +96,7 @@ async fn test_stacks_runloop_kill_scan() {
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: None,
+        skip_rollback_only_occurrences: None,
         include_contract_abi: None,
         predicate: StacksPredicate::BlockHeight(BlockIdentifierIndexRule::LowerThan(0)),
         action: HookAction::Noop,
@@ -127,9 +146,16 @@ async fn test_stacks_bitcoin_kill_scan() {
     let (scan_op_tx, scan_op_rx) = crossbeam_channel::unbounded();
     let (observer_command_tx, _observer_command_rx) = channel();
 
+    let http_client = config.build_http_delivery_client().unwrap();
     let _ = hiro_system_kit::thread_named("Stacks scan runloop")
         .spawn(move || {
-            start_bitcoin_scan_runloop(&config, scan_op_rx, observer_command_tx.clone(), &ctx);
+            start_bitcoin_scan_runloop(
+                &config,
+                &http_client,
+                scan_op_rx,
+                observer_command_tx.clone(),
+                &ctx,
+            );
         })
         .expect("unable to spawn thread");
 
@@ -137,12 +163,14 @@ async fn test_stacks_bitcoin_kill_scan() {
     let predicate_spec = BitcoinChainhookInstance {
         uuid: uuid.clone(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "idc".to_string(),
         network: BitcoinNetwork::Regtest,
         version: 0,
         blocks: None,
         start_block: Some(1),
         end_block: Some(1_000),
+        end_block_grace_period: None,
         expire_after_occurrence: None,
         predicate: BitcoinPredicateType::Block,
         action: HookAction::Noop,
@@ -152,6 +180,20 @@ async fn test_stacks_bitcoin_kill_scan() {
         include_inputs: false,
         include_outputs: false,
         include_witness: false,
+        proof_format: ProofFormat::Hex,
+        amount_format: AmountFormat::Number,
+        include_brc20_balance_deltas: false,
+        include_brc20_operation: false,
+        max_transactions_per_block: None,
+        skip_rollback_only_occurrences: false,
+        digest: None,
+        omit_empty_arrays: false,
+        payload_shape: PayloadShape::Block,
+        include_empty_blocks: false,
+        include_size: false,
+        include_version: false,
+        include_dropped: false,
+        parsed_address: PredicateAddress::default(),
     };
 
     let op = BitcoinScanOp::StartScan {
@@ -168,3 +210,187 @@ async fn test_stacks_bitcoin_kill_scan() {
     // more data on these threads. When this is done we should update these tests
     // to do some actual verification that the predicate is no longer being scanned
 }
+
+fn build_bitcoin_block_range_predicate(
+    uuid: &str,
+    start_block: u64,
+    end_block: u64,
+) -> BitcoinChainhookInstance {
+    BitcoinChainhookInstance {
+        uuid: uuid.to_string(),
+        owner_uuid: None,
+        on_expire_url: None,
+        name: uuid.to_string(),
+        network: BitcoinNetwork::Regtest,
+        version: 0,
+        blocks: None,
+        start_block: Some(start_block),
+        end_block: Some(end_block),
+        end_block_grace_period: None,
+        expire_after_occurrence: None,
+        predicate: BitcoinPredicateType::Block,
+        action: HookAction::Noop,
+        enabled: false,
+        expired_at: None,
+        include_proof: false,
+        include_inputs: false,
+        include_outputs: false,
+        include_witness: false,
+        proof_format: ProofFormat::Hex,
+        amount_format: AmountFormat::Number,
+        include_brc20_balance_deltas: false,
+        include_brc20_operation: false,
+        max_transactions_per_block: None,
+        skip_rollback_only_occurrences: false,
+        digest: None,
+        omit_empty_arrays: false,
+        payload_shape: PayloadShape::Block,
+        include_empty_blocks: false,
+        include_size: false,
+        include_version: false,
+        include_dropped: false,
+        parsed_address: PredicateAddress::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_bitcoin_multiplexed_scan_fetches_blocks_once() {
+    let (_, _, _, _, bitcoin_rpc_port, _) =
+        setup_chainhook_service_ports().unwrap_or_else(|e| panic!("test failed with error: {e}"));
+
+    let getblock_requests = GetBlockRequests::default();
+    let moved_getblock_requests = getblock_requests.clone();
+    let _ = hiro_system_kit::thread_named("Bitcoin rpc service")
+        .spawn(move || {
+            let future = mock_bitcoin_rpc_with_getblock_requests(
+                bitcoin_rpc_port,
+                1_000,
+                moved_getblock_requests,
+            );
+            let _ = hiro_system_kit::nestable_block_on(future);
+        })
+        .expect("unable to spawn thread");
+
+    sleep(Duration::new(1, 0));
+    let mut config = Config::devnet_default();
+    config.network.bitcoind_rpc_url = format!("http://0.0.0.0:{bitcoin_rpc_port}");
+    let ctx = Context {
+        logger: None,
+        tracer: false,
+    };
+
+    let requests = vec![
+        BitcoinScanRequest {
+            predicate_spec: build_bitcoin_block_range_predicate("first", 1, 10),
+            unfinished_scan_data: None,
+            kill_signal: None,
+        },
+        BitcoinScanRequest {
+            predicate_spec: build_bitcoin_block_range_predicate("second", 5, 15),
+            unfinished_scan_data: None,
+            kill_signal: None,
+        },
+    ];
+    let http_client = config.build_http_delivery_client().unwrap();
+    let results =
+        scan_bitcoin_chainstate_via_rpc_using_predicates(requests, &config, &http_client, &ctx)
+            .await
+            .unwrap_or_else(|e| panic!("test failed with error: {e}"));
+
+    let results = results
+        .into_iter()
+        .map(|(predicate_spec, result)| (predicate_spec.uuid, result))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        vec![
+            (
+                "first".to_string(),
+                Ok((
+                    PredicateScanResult::Expired,
+                    ScanSummary {
+                        number_of_blocks_scanned: 10,
+                        number_of_times_triggered: 10,
+                    }
+                ))
+            ),
+            (
+                "second".to_string(),
+                Ok((
+                    PredicateScanResult::Expired,
+                    ScanSummary {
+                        number_of_blocks_scanned: 11,
+                        number_of_times_triggered: 11,
+                    }
+                ))
+            ),
+        ]
+    );
+    // blocks #5 to #10 are covered by both predicates, but only requested once
+    let getblock_requests = getblock_requests.read().unwrap();
+    assert_eq!(getblock_requests.len(), 15);
+    assert!(getblock_requests.values().all(|count| *count == 1));
+}
+
+#[tokio::test]
+async fn test_bitcoin_scan_streams_predicates_during_end_block_grace_period() {
+    let (_, _, _, _, bitcoin_rpc_port, _) =
+        setup_chainhook_service_ports().unwrap_or_else(|e| panic!("test failed with error: {e}"));
+
+    let _ = hiro_system_kit::thread_named("Bitcoin rpc service")
+        .spawn(move || {
+            let future = mock_bitcoin_rpc(bitcoin_rpc_port, 1_000);
+            let _ = hiro_system_kit::nestable_block_on(future);
+        })
+        .expect("unable to spawn thread");
+
+    sleep(Duration::new(1, 0));
+    let mut config = Config::devnet_default();
+    config.network.bitcoind_rpc_url = format!("http://0.0.0.0:{bitcoin_rpc_port}");
+    let ctx = Context {
+        logger: None,
+        tracer: false,
+    };
+
+    // the chain tip, #1000, is past the end block of both predicates, but only past the grace
+    // period of the first one
+    let mut in_grace_period = build_bitcoin_block_range_predicate("in_grace_period", 990, 995);
+    in_grace_period.end_block_grace_period = Some(10);
+    let mut past_grace_period = build_bitcoin_block_range_predicate("past_grace_period", 990, 995);
+    past_grace_period.end_block_grace_period = Some(5);
+    let requests = vec![
+        BitcoinScanRequest {
+            predicate_spec: in_grace_period,
+            unfinished_scan_data: None,
+            kill_signal: None,
+        },
+        BitcoinScanRequest {
+            predicate_spec: past_grace_period,
+            unfinished_scan_data: None,
+            kill_signal: None,
+        },
+    ];
+    let http_client = config.build_http_delivery_client().unwrap();
+    let results =
+        scan_bitcoin_chainstate_via_rpc_using_predicates(requests, &config, &http_client, &ctx)
+            .await
+            .unwrap_or_else(|e| panic!("test failed with error: {e}"));
+
+    let results = results
+        .into_iter()
+        .map(|(predicate_spec, result)| (predicate_spec.uuid, result.map(|(result, _)| result)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        vec![
+            (
+                "in_grace_period".to_string(),
+                Ok(PredicateScanResult::ChainTipReached)
+            ),
+            (
+                "past_grace_period".to_string(),
+                Ok(PredicateScanResult::Expired)
+            ),
+        ]
+    );
+}
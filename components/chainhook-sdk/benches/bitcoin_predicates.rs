@@ -0,0 +1,127 @@
+use chainhook_sdk::chainhooks::bitcoin::{
+    evaluate_bitcoin_chainhooks_on_chain_event, BitcoinChainhookInstance,
+    BitcoinChainhookSpecificationNetworkMap,
+};
+use chainhook_sdk::types::bitcoin::TxOut;
+use chainhook_sdk::types::{
+    BitcoinBlockData, BitcoinBlockMetadata, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData,
+    BitcoinNetwork, BitcoinTransactionData, BitcoinTransactionMetadata, BlockIdentifier,
+    TransactionIdentifier,
+};
+use chainhook_sdk::utils::Context;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const NUMBER_OF_PREDICATES: usize = 500;
+const NUMBER_OF_TRANSACTIONS: usize = 2_000;
+
+fn build_transaction(index: usize) -> BitcoinTransactionData {
+    BitcoinTransactionData {
+        transaction_identifier: TransactionIdentifier {
+            hash: format!("0x{:064x}", index),
+        },
+        operations: vec![],
+        metadata: BitcoinTransactionMetadata {
+            inputs: vec![],
+            outputs: vec![
+                TxOut {
+                    value: 1_000,
+                    script_pubkey: "0x76a914751e76e8199196d454941c45d1b3a323f1433bd688ac".into(),
+                },
+                TxOut {
+                    value: 0,
+                    script_pubkey: format!("0x6a08{:016x}", index),
+                },
+            ],
+            stacks_operations: vec![],
+            ordinal_operations: vec![],
+            brc20_operation: None,
+            proof: None,
+            fee: 0,
+            index: index as u32,
+            weight: 0,
+            vsize: 0,
+            version: 2,
+        },
+    }
+}
+
+fn build_chain_event() -> BitcoinChainEvent {
+    let block = BitcoinBlockData {
+        block_identifier: BlockIdentifier {
+            index: 1,
+            hash: format!("0x{:064x}", 1),
+        },
+        parent_block_identifier: BlockIdentifier {
+            index: 0,
+            hash: format!("0x{:064x}", 0),
+        },
+        timestamp: 0,
+        transactions: (0..NUMBER_OF_TRANSACTIONS).map(build_transaction).collect(),
+        metadata: BitcoinBlockMetadata {
+            network: BitcoinNetwork::Mainnet,
+        },
+    };
+    BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+        new_blocks: vec![block],
+        confirmed_blocks: vec![],
+    })
+}
+
+/// Predicates alternating between op_return, p2pkh and txid rules, so that only a few of them
+/// trigger on the chain event.
+fn build_predicates() -> Vec<BitcoinChainhookInstance> {
+    (0..NUMBER_OF_PREDICATES)
+        .map(|i| {
+            let if_this = match i % 3 {
+                0 => serde_json::json!({
+                    "scope": "outputs",
+                    "op_return": { "starts_with": format!("0x{:016x}", i) },
+                }),
+                1 => serde_json::json!({
+                    "scope": "outputs",
+                    "p2pkh": { "equals": "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH" },
+                }),
+                _ => serde_json::json!({
+                    "scope": "txid",
+                    "equals": format!("0x{:064x}", i),
+                }),
+            };
+            let spec: BitcoinChainhookSpecificationNetworkMap =
+                serde_json::from_value(serde_json::json!({
+                    "uuid": format!("{}", i),
+                    "name": format!("predicate {}", i),
+                    "chain": "bitcoin",
+                    "version": 1,
+                    "networks": {
+                        "mainnet": {
+                            "if_this": if_this,
+                            "then_that": "noop",
+                        }
+                    }
+                }))
+                .expect("unable to build predicate");
+            spec.into_specification_for_network(&BitcoinNetwork::Mainnet)
+                .expect("unable to build predicate")
+        })
+        .collect()
+}
+
+fn bench_evaluate_bitcoin_chainhooks(c: &mut Criterion) {
+    let chain_event = build_chain_event();
+    let predicates = build_predicates();
+    let active_chainhooks = predicates.iter().collect::<Vec<_>>();
+    let ctx = Context::empty();
+    c.bench_function("evaluate_bitcoin_chainhooks_on_chain_event", |b| {
+        b.iter(|| {
+            evaluate_bitcoin_chainhooks_on_chain_event(
+                black_box(&chain_event),
+                &active_chainhooks,
+                None,
+                &ctx,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, bench_evaluate_bitcoin_chainhooks);
+criterion_main!(benches);
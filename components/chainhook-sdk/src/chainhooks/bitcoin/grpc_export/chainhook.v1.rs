@@ -0,0 +1,99 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Occurrence {
+    /// Uuid of the predicate that triggered the occurrence.
+    #[prost(string, tag = "1")]
+    pub predicate_uuid: ::prost::alloc::string::String,
+    /// JSON payload, as delivered by the `http_post` action.
+    #[prost(bytes = "vec", tag = "2")]
+    pub payload: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StreamOccurrencesResponse {
+    /// Number of occurrences received over the stream.
+    #[prost(uint64, tag = "1")]
+    pub received: u64,
+}
+/// Generated client implementations.
+pub mod occurrence_sink_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// Receives the occurrences of the predicates using a `grpc` action.
+    #[derive(Debug, Clone)]
+    pub struct OccurrenceSinkClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl OccurrenceSinkClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> OccurrenceSinkClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Long-lived stream, carrying the occurrences of every predicate streaming to the same endpoint.
+        /// The stream is only closed by the client, when Chainhook shuts down.
+        pub async fn stream_occurrences(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::Occurrence>,
+        ) -> std::result::Result<
+            tonic::Response<super::StreamOccurrencesResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/chainhook.v1.OccurrenceSink/StreamOccurrences",
+            );
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("chainhook.v1.OccurrenceSink", "StreamOccurrences"),
+                );
+            self.inner.client_streaming(req, path, codec).await
+        }
+    }
+}
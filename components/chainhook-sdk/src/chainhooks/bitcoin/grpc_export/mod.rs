@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use hiro_system_kit::slog;
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::chainhooks::types::GrpcHook;
+use crate::utils::Context;
+
+/// Bindings of `proto/chainhook/v1/occurrences.proto`, generated with `tonic-build` and checked in
+/// so that building Chainhook does not require `protoc`.
+pub mod proto {
+    include!("chainhook.v1.rs");
+}
+
+use proto::occurrence_sink_client::OccurrenceSinkClient;
+use proto::Occurrence;
+
+/// Number of occurrences buffered in a stream, waiting to be sent.
+const STREAM_BUFFER_SIZE: usize = 1024;
+
+static GRPC_STREAMS: OnceLock<Mutex<HashMap<String, Sender<Occurrence>>>> = OnceLock::new();
+
+fn grpc_streams() -> &'static Mutex<HashMap<String, Sender<Occurrence>>> {
+    GRPC_STREAMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Returns the stream opened to `endpoint`, opening a new one when none is, or when the previous
+/// one was dropped. `https` endpoints are verified against the webpki root certificates.
+async fn get_grpc_stream(endpoint: &str, ctx: &Context) -> Result<Sender<Occurrence>, String> {
+    let stream = grpc_streams()
+        .lock()
+        .map_err(|e| format!("unable to lock grpc streams: {}", e.to_string()))?
+        .get(endpoint)
+        .filter(|stream| !stream.is_closed())
+        .cloned();
+    if let Some(stream) = stream {
+        return Ok(stream);
+    }
+    let mut client = OccurrenceSinkClient::connect(endpoint.to_string())
+        .await
+        .map_err(|e| format!("unable to connect to {}: {}", endpoint, e.to_string()))?;
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER_SIZE);
+    let occurrences = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|occurrence| (occurrence, rx))
+    });
+    // the receiver is dropped with the call, closing the stream for the senders
+    let ctx = ctx.clone();
+    let stream_endpoint = endpoint.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = client.stream_occurrences(occurrences).await {
+            ctx.try_log(|logger| {
+                slog::warn!(
+                    logger,
+                    "gRPC stream to {} ended with error: {}",
+                    stream_endpoint,
+                    e
+                )
+            });
+        }
+    });
+    grpc_streams()
+        .lock()
+        .map_err(|e| format!("unable to lock grpc streams: {}", e.to_string()))?
+        .insert(endpoint.to_string(), tx.clone());
+    Ok(tx)
+}
+
+/// Sends `bytes` over the stream opened to the endpoint of `hook`, shared by every predicate
+/// streaming to the same endpoint. When the stream was dropped, a new one is opened and the
+/// occurrence is sent again, once.
+pub async fn send_grpc_occurrence(
+    hook: &GrpcHook,
+    predicate_uuid: &str,
+    bytes: Vec<u8>,
+    ctx: &Context,
+) -> Result<(), String> {
+    let mut occurrence = Occurrence {
+        predicate_uuid: predicate_uuid.to_string(),
+        payload: bytes,
+    };
+    for _ in 0..2 {
+        let stream = get_grpc_stream(&hook.endpoint, ctx).await?;
+        match stream.send(occurrence).await {
+            Ok(()) => return Ok(()),
+            Err(mpsc::error::SendError(unsent_occurrence)) => occurrence = unsent_occurrence,
+        }
+    }
+    Err(format!("stream to {} dropped", hook.endpoint))
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+
+use crate::chainhooks::types::KafkaHook;
+
+const DELIVERY_TIMEOUT_SEC: u64 = 10;
+
+static KAFKA_PRODUCERS: OnceLock<Mutex<HashMap<String, FutureProducer>>> = OnceLock::new();
+
+/// Returns the producer connected to `brokers`, creating it on first use. Producers are shared by
+/// every predicate producing to the same brokers, whether they are evaluated by the observer or by
+/// a scan.
+fn get_kafka_producer(brokers: &[String]) -> Result<FutureProducer, String> {
+    let brokers = brokers.join(",");
+    let mut producers = KAFKA_PRODUCERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|e| format!("unable to lock kafka producers: {}", e.to_string()))?;
+    if let Some(producer) = producers.get(&brokers) {
+        return Ok(producer.clone());
+    }
+    let producer = ClientConfig::new()
+        .set("bootstrap.servers", &brokers)
+        .set("enable.idempotence", "true")
+        .create::<FutureProducer>()
+        .map_err(|e| format!("unable to create kafka producer: {}", e.to_string()))?;
+    producers.insert(brokers, producer.clone());
+    Ok(producer)
+}
+
+/// Produces `bytes` to the topic of `hook`, keyed with `key`, and waits for the brokers to
+/// acknowledge it.
+pub async fn produce_kafka_message(
+    hook: &KafkaHook,
+    key: &str,
+    bytes: &[u8],
+) -> Result<(), String> {
+    let producer = get_kafka_producer(&hook.brokers)?;
+    let record = FutureRecord::to(&hook.topic).key(key).payload(bytes);
+    producer
+        .send(record, Duration::from_secs(DELIVERY_TIMEOUT_SEC))
+        .await
+        .map(|_| ())
+        .map_err(|(e, _)| {
+            format!(
+                "unable to produce message to topic {}: {}",
+                hook.topic,
+                e.to_string()
+            )
+        })
+}
@@ -1,33 +1,73 @@
 use super::types::{
-    append_error_context, validate_txid, ChainhookInstance, ExactMatchingRule, HookAction,
-    MatchingRule,
+    append_error_context, build_delivery_intent, build_http_delivery_client, validate_txid,
+    ChainhookInstance, ExactMatchingRule, HookAction, HttpDeliveryConfig, MatchingRegex,
+    MatchingRule, SigningSecrets,
 };
 use crate::utils::{Context, MAX_BLOCK_HEIGHTS_ENTRIES};
 
-use bitcoincore_rpc_json::bitcoin::{address::Payload, Address};
+use bitcoincore_rpc_json::bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoincore_rpc_json::bitcoin::merkle_tree::{MerkleBlock, PartialMerkleTree};
+use bitcoincore_rpc_json::bitcoin::{
+    address::Payload, block::Header, script::Instruction, Address, Network, PublicKey, Script,
+    ScriptBuf, TxMerkleNode, Txid,
+};
+use chainhook_types::bitcoin::{TxIn, TxOut};
 use chainhook_types::{
-    BitcoinBlockData, BitcoinChainEvent, BitcoinNetwork, BitcoinTransactionData, BlockIdentifier,
+    BitcoinBlockData, BitcoinChainEvent, BitcoinNetwork, BitcoinTransactionData,
+    BitcoinTransactionMetadata, BlockIdentifier, Brc20Operation, OrdinalOperation,
     StacksBaseChainOperation, TransactionIdentifier,
 };
 use schemars::JsonSchema;
 
 use hiro_system_kit::slog;
 
-use miniscript::bitcoin::secp256k1::Secp256k1;
+use miniscript::bitcoin::secp256k1::{Secp256k1, SignOnly, VerifyOnly};
 use miniscript::Descriptor;
 
-use reqwest::{Client, Method};
+use reqwest::Client;
 use serde::{de, Deserialize, Deserializer};
 use serde_json::Value as JsonValue;
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     str::FromStr,
+    sync::{Arc, OnceLock, RwLock},
+    time::SystemTime,
 };
 
 use reqwest::RequestBuilder;
 
 use hex::FromHex;
 
+#[cfg(feature = "parquet")]
+pub mod parquet_export;
+#[cfg(feature = "parquet")]
+use super::types::ParquetHook;
+#[cfg(feature = "parquet")]
+use parquet_export::BitcoinParquetRow;
+
+#[cfg(feature = "kafka")]
+pub mod kafka_export;
+#[cfg(feature = "kafka")]
+use super::types::KafkaHook;
+
+#[cfg(feature = "grpc")]
+pub mod grpc_export;
+#[cfg(feature = "grpc")]
+use super::types::GrpcHook;
+
+#[cfg(feature = "sqs")]
+pub mod sqs_export;
+#[cfg(feature = "sqs")]
+use super::types::SqsHook;
+
+#[cfg(feature = "s3")]
+pub mod s3_export;
+#[cfg(feature = "s3")]
+use super::types::S3AppendHook;
+
+#[cfg(feature = "websocket")]
+pub mod websocket_export;
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct BitcoinChainhookSpecification {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -37,6 +77,8 @@ pub struct BitcoinChainhookSpecification {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_block: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_block_grace_period: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub expire_after_occurrence: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub include_proof: Option<bool>,
@@ -46,6 +88,32 @@ pub struct BitcoinChainhookSpecification {
     pub include_outputs: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub include_witness: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof_format: Option<ProofFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_format: Option<AmountFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_brc20_balance_deltas: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_brc20_operation: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_transactions_per_block: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_rollback_only_occurrences: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<DigestConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omit_empty_arrays: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_shape: Option<PayloadShape>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_empty_blocks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_size: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_version: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_dropped: Option<bool>,
     #[serde(rename = "if_this")]
     pub predicate: BitcoinPredicateType,
     #[serde(rename = "then_that")]
@@ -58,11 +126,25 @@ impl BitcoinChainhookSpecification {
             blocks: None,
             start_block: None,
             end_block: None,
+            end_block_grace_period: None,
             expire_after_occurrence: None,
             include_proof: None,
             include_inputs: None,
             include_outputs: None,
             include_witness: None,
+            proof_format: None,
+            amount_format: None,
+            include_brc20_balance_deltas: None,
+            include_brc20_operation: None,
+            max_transactions_per_block: None,
+            skip_rollback_only_occurrences: None,
+            digest: None,
+            omit_empty_arrays: None,
+            payload_shape: None,
+            include_empty_blocks: None,
+            include_size: None,
+            include_version: None,
+            include_dropped: None,
             predicate,
             action,
         }
@@ -83,6 +165,11 @@ impl BitcoinChainhookSpecification {
         self
     }
 
+    pub fn end_block_grace_period(&mut self, blocks: u64) -> &mut Self {
+        self.end_block_grace_period = Some(blocks);
+        self
+    }
+
     pub fn expire_after_occurrence(&mut self, occurrence: u64) -> &mut Self {
         self.expire_after_occurrence = Some(occurrence);
         self
@@ -108,6 +195,71 @@ impl BitcoinChainhookSpecification {
         self
     }
 
+    pub fn proof_format(&mut self, format: ProofFormat) -> &mut Self {
+        self.proof_format = Some(format);
+        self
+    }
+
+    pub fn amount_format(&mut self, format: AmountFormat) -> &mut Self {
+        self.amount_format = Some(format);
+        self
+    }
+
+    pub fn include_brc20_balance_deltas(&mut self, do_include: bool) -> &mut Self {
+        self.include_brc20_balance_deltas = Some(do_include);
+        self
+    }
+
+    pub fn include_brc20_operation(&mut self, do_include: bool) -> &mut Self {
+        self.include_brc20_operation = Some(do_include);
+        self
+    }
+
+    pub fn max_transactions_per_block(&mut self, max: u64) -> &mut Self {
+        self.max_transactions_per_block = Some(max);
+        self
+    }
+
+    pub fn skip_rollback_only_occurrences(&mut self, do_skip: bool) -> &mut Self {
+        self.skip_rollback_only_occurrences = Some(do_skip);
+        self
+    }
+
+    pub fn digest(&mut self, digest: DigestConfig) -> &mut Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    pub fn omit_empty_arrays(&mut self, do_omit: bool) -> &mut Self {
+        self.omit_empty_arrays = Some(do_omit);
+        self
+    }
+
+    pub fn payload_shape(&mut self, shape: PayloadShape) -> &mut Self {
+        self.payload_shape = Some(shape);
+        self
+    }
+
+    pub fn include_empty_blocks(&mut self, do_include: bool) -> &mut Self {
+        self.include_empty_blocks = Some(do_include);
+        self
+    }
+
+    pub fn include_size(&mut self, do_include: bool) -> &mut Self {
+        self.include_size = Some(do_include);
+        self
+    }
+
+    pub fn include_version(&mut self, do_include: bool) -> &mut Self {
+        self.include_version = Some(do_include);
+        self
+    }
+
+    pub fn include_dropped(&mut self, do_include: bool) -> &mut Self {
+        self.include_dropped = Some(do_include);
+        self
+    }
+
     pub fn validate(&self) -> Result<(), Vec<String>> {
         let mut errors = vec![];
         if let Err(e) = self.action.validate() {
@@ -128,12 +280,106 @@ impl BitcoinChainhookSpecification {
                 errors.push(format!("Chainhook specification exceeds max number of blocks to scan. Maximum: {}, Attempted: {}", MAX_BLOCK_HEIGHTS_ENTRIES, (end_block - start_block)));
             }
         }
+        if self.max_transactions_per_block == Some(0) {
+            errors.push(
+                "Chainhook specification field `max_transactions_per_block` should be greater than 0.".into()
+            );
+        }
+        // witnesses are serialized as part of the inputs, they would silently be left out
+        if self.include_witness == Some(true) && self.include_inputs != Some(true) {
+            errors.push(
+                "Chainhook specification field `include_witness` requires `include_inputs` to be enabled.".into()
+            );
+        }
+        if let Some(ref digest) = self.digest {
+            if let Err(e) = digest.validate() {
+                errors.append(&mut append_error_context("invalid 'digest' value", e));
+            }
+            #[cfg(feature = "parquet")]
+            if let HookAction::Parquet(_) = self.action {
+                errors.push(
+                    "Chainhook specification field `digest` can't be used with a 'parquet' action."
+                        .into(),
+                );
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Validates the specification, and ensures that any address used in the predicate belongs to `network`.
+    pub fn validate_for_network(&self, network: &BitcoinNetwork) -> Result<(), Vec<String>> {
+        let mut errors = match self.validate() {
+            Ok(()) => vec![],
+            Err(errors) => errors,
+        };
+        if let Err(e) = self.predicate.validate_for_network(network) {
+            errors.append(&mut append_error_context("invalid 'if_this' value", e));
+        }
         if errors.is_empty() {
             Ok(())
         } else {
             Err(errors)
         }
     }
+
+    /// Best-effort detection of valid specifications that can never match any transaction.
+    /// Returns the reasons why the specification is unsatisfiable, if any.
+    pub fn get_unsatisfiable_conditions(&self) -> Vec<String> {
+        let mut conditions = vec![];
+        if let Some(ref blocks) = self.blocks {
+            if blocks.is_empty() {
+                conditions.push("Chainhook specification field `blocks` is empty.".into());
+            }
+        }
+        if let BitcoinPredicateType::Outputs(OutputPredicate::OpReturn(rule)) = &self.predicate {
+            if let Some(condition) = get_unsatisfiable_op_return_condition(rule) {
+                conditions.push(condition);
+            }
+        }
+        conditions
+    }
+}
+
+/// Maximum size of the data carried by an OP_RETURN output. Since Bitcoin Core v30 relayed
+/// OP_RETURN outputs are no longer limited to 80 bytes, so data is only bounded by the 4M weight
+/// units of a block, at 4 weight units per byte of output.
+const MAX_OP_RETURN_DATA_SIZE: usize = 1_000_000;
+
+/// OP_RETURN data is compared to the lowercase hex encoding of the pattern, see [OpReturn].
+fn get_unsatisfiable_op_return_condition(rule: &MatchingRule) -> Option<String> {
+    let (pattern, is_exact_match, offset) = match rule {
+        MatchingRule::Equals(pattern) => (pattern, true, 0),
+        MatchingRule::StartsWith(pattern) | MatchingRule::EndsWith(pattern) => (pattern, false, 0),
+        MatchingRule::AtOffset(rule) => (&rule.value, false, rule.offset),
+        MatchingRule::Regex(_) => return None,
+    };
+    let data_size = match pattern.strip_prefix("0x") {
+        Some(hex) => {
+            if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Some(format!(
+                    "'op_return' pattern {pattern} is not a valid hex string"
+                ));
+            }
+            // a partial byte can still match the beginning or the end of the data
+            if is_exact_match && hex.len() % 2 != 0 {
+                return Some(format!(
+                    "'op_return' pattern {pattern} does not encode a whole number of bytes"
+                ));
+            }
+            hex.len() / 2
+        }
+        None => pattern.len(),
+    };
+    if (data_size as u64).saturating_add(offset) > MAX_OP_RETURN_DATA_SIZE as u64 {
+        return Some(format!(
+            "'op_return' pattern {pattern} exceeds the {MAX_OP_RETURN_DATA_SIZE} bytes an OP_RETURN output can carry in a block"
+        ));
+    }
+    None
 }
 
 /// Maps some [BitcoinChainhookSpecification] to a corresponding [BitcoinNetwork]. This allows maintaining one
@@ -180,12 +426,28 @@ pub struct BitcoinChainhookSpecificationNetworkMap {
     pub uuid: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner_uuid: Option<String>,
+    /// Url receiving a POST request with the final status of the predicate when it expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_expire_url: Option<String>,
     pub name: String,
     pub version: u32,
     pub networks: BTreeMap<BitcoinNetwork, BitcoinChainhookSpecification>,
 }
 
 impl BitcoinChainhookSpecificationNetworkMap {
+    /// Whether one of the specifications reads a local file, i.e. a denylist `path`.
+    pub fn reads_local_files(&self) -> bool {
+        self.networks.values().any(|spec| {
+            matches!(
+                spec.predicate,
+                BitcoinPredicateType::Outputs(OutputPredicate::Denylist(DenylistMatchingRule {
+                    path: Some(_),
+                    ..
+                }))
+            )
+        })
+    }
+
     pub fn into_specification_for_network(
         mut self,
         network: &BitcoinNetwork,
@@ -194,22 +456,46 @@ impl BitcoinChainhookSpecificationNetworkMap {
             .networks
             .remove(network)
             .ok_or("Network unknown".to_string())?;
+        if let Err(e) = spec.predicate.validate_for_network(network) {
+            return Err(format!(
+                "invalid Bitcoin predicate '{}' for network {}: {}",
+                self.name,
+                network,
+                e.join(", ")
+            ));
+        }
         Ok(BitcoinChainhookInstance {
             uuid: self.uuid,
             owner_uuid: self.owner_uuid,
+            on_expire_url: self.on_expire_url,
             name: self.name,
             network: network.clone(),
             version: self.version,
             start_block: spec.start_block,
             end_block: spec.end_block,
+            end_block_grace_period: spec.end_block_grace_period,
             blocks: spec.blocks,
             expire_after_occurrence: spec.expire_after_occurrence,
+            parsed_address: PredicateAddress::new(&spec.predicate),
             predicate: spec.predicate,
             action: spec.action,
             include_proof: spec.include_proof.unwrap_or(false),
             include_inputs: spec.include_inputs.unwrap_or(false),
             include_outputs: spec.include_outputs.unwrap_or(false),
             include_witness: spec.include_witness.unwrap_or(false),
+            proof_format: spec.proof_format.unwrap_or_default(),
+            amount_format: spec.amount_format.unwrap_or_default(),
+            include_brc20_balance_deltas: spec.include_brc20_balance_deltas.unwrap_or(false),
+            include_brc20_operation: spec.include_brc20_operation.unwrap_or(false),
+            max_transactions_per_block: spec.max_transactions_per_block,
+            skip_rollback_only_occurrences: spec.skip_rollback_only_occurrences.unwrap_or(false),
+            digest: spec.digest,
+            omit_empty_arrays: spec.omit_empty_arrays.unwrap_or(false),
+            payload_shape: spec.payload_shape.unwrap_or_default(),
+            include_empty_blocks: spec.include_empty_blocks.unwrap_or(false),
+            include_size: spec.include_size.unwrap_or(false),
+            include_version: spec.include_version.unwrap_or(false),
+            include_dropped: spec.include_dropped.unwrap_or(false),
             enabled: false,
             expired_at: None,
         })
@@ -221,15 +507,26 @@ pub struct BitcoinChainhookInstance {
     pub uuid: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner_uuid: Option<String>,
+    /// Url receiving a POST request with the final status of the predicate when it expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_expire_url: Option<String>,
     pub name: String,
     pub network: BitcoinNetwork,
     pub version: u32,
+    /// Heights of the only blocks evaluated, an empty list matching nothing. Scans only go through
+    /// these heights, while listed heights outside of `start_block` and `end_block` are never
+    /// evaluated.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub blocks: Option<Vec<u64>>,
+    /// Blocks below `start_block` are neither evaluated nor reported as evaluated.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_block: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_block: Option<u64>,
+    /// Number of blocks past `end_block` during which the predicate is kept active, so that
+    /// rollbacks of blocks up to `end_block` are still delivered before it expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_block_grace_period: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expire_after_occurrence: Option<u64>,
     pub predicate: BitcoinPredicateType,
@@ -238,14 +535,309 @@ pub struct BitcoinChainhookInstance {
     pub include_inputs: bool,
     pub include_outputs: bool,
     pub include_witness: bool,
+    #[serde(default)]
+    pub proof_format: ProofFormat,
+    #[serde(default)]
+    pub amount_format: AmountFormat,
+    /// Adds the balance changes resulting from matched BRC-20 operations to the payload.
+    #[serde(default)]
+    pub include_brc20_balance_deltas: bool,
+    /// Adds matched BRC-20 operations to the payload of predicates that aren't ordinals
+    /// predicates, which always include them.
+    #[serde(default)]
+    pub include_brc20_operation: bool,
+    /// Maximum number of matching transactions serialized per block, the payload of blocks
+    /// exceeding it is flagged as `truncated`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_transactions_per_block: Option<u64>,
+    /// Drops the occurrences of reorgs in which the predicate only matches rolled back blocks,
+    /// for consumers only acting on new data.
+    #[serde(default)]
+    pub skip_rollback_only_occurrences: bool,
+    /// Aggregates the occurrences of the predicate over a window, delivered as a single digest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<DigestConfig>,
+    /// Omits the keys of the transaction metadata holding an empty array, such as `inputs` when
+    /// inputs are not included, to reduce the size of the payload.
+    #[serde(default)]
+    pub omit_empty_arrays: bool,
+    #[serde(default)]
+    pub payload_shape: PayloadShape,
+    /// Lets `block` predicates trigger on empty blocks, without any transaction besides the
+    /// coinbase.
+    #[serde(default)]
+    pub include_empty_blocks: bool,
+    /// Adds the `weight`, `vsize` and `witness_discount` of the transactions to the payload.
+    #[serde(default)]
+    pub include_size: bool,
+    /// Adds the `version` (`nVersion`) of the transactions to the payload.
+    #[serde(default)]
+    pub include_version: bool,
+    /// Adds the `dropped` transactions of reorgs to the payload: the rolled back transactions
+    /// that were not mined again in any of the blocks applied by the same reorg.
+    #[serde(default)]
+    pub include_dropped: bool,
     pub enabled: bool,
     pub expired_at: Option<u64>,
+    #[serde(skip)]
+    pub parsed_address: PredicateAddress,
 }
 
 impl BitcoinChainhookInstance {
     pub fn key(&self) -> String {
         ChainhookInstance::bitcoin_key(&self.uuid)
     }
+
+    /// Evaluates the predicate on `tx`. Unlike
+    /// [BitcoinPredicateType::evaluate_transaction_predicate], it supports the predicates depending
+    /// on the block or the network of the transaction: `fee_rates` holds the fee rates of the
+    /// block, only used by `fee_rate_percentile` predicates.
+    pub fn evaluate_transaction(
+        &self,
+        tx: &BitcoinTransactionData,
+        fee_rates: Option<&BlockFeeRates>,
+        descriptor_default_range: Option<[u32; 2]>,
+        ctx: &Context,
+    ) -> bool {
+        match &self.predicate {
+            BitcoinPredicateType::FeeRatePercentile(rule) => fee_rates
+                .and_then(|fee_rates| fee_rates.get_percentile(tx))
+                .map_or(false, |percentile| rule.evaluate(percentile)),
+            BitcoinPredicateType::StacksProtocol(StacksOperations::Opcode { opcode }) => {
+                tx.metadata.outputs.iter().any(|output| {
+                    match get_stacks_opcode(&output.script_pubkey, &self.network) {
+                        Some(Ok(known_opcode)) => known_opcode as u8 == *opcode,
+                        Some(Err(raw_opcode)) => raw_opcode == *opcode,
+                        None => false,
+                    }
+                })
+            }
+            predicate if predicate.get_target_address().is_some() => self
+                .parsed_address
+                .get_or_parse(predicate)
+                .map_or(false, |address| {
+                    predicate.evaluate_target_address(tx, address)
+                }),
+            predicate => {
+                predicate.evaluate_transaction_predicate(tx, descriptor_default_range, ctx)
+            }
+        }
+    }
+
+    /// Height past which the predicate is considered expired: `end_block`, extended by the
+    /// `end_block_grace_period` if any.
+    pub fn expiration_block(&self) -> u64 {
+        match self.end_block {
+            Some(end_block) => end_block.saturating_add(self.end_block_grace_period.unwrap_or(0)),
+            None => u64::MAX,
+        }
+    }
+
+    /// Whether the block at `block_height` is part of the `blocks` allowlist, if any. An empty
+    /// allowlist doesn't include any block.
+    pub fn includes_block(&self, block_height: u64) -> bool {
+        match self.blocks {
+            Some(ref blocks) => blocks.contains(&block_height),
+            None => true,
+        }
+    }
+
+    /// BRC-20 operations are only serialized for ordinals predicates, unless explicitly requested.
+    pub fn includes_brc20_operation(&self) -> bool {
+        self.include_brc20_operation
+            || matches!(self.predicate, BitcoinPredicateType::OrdinalsProtocol(_))
+    }
+}
+
+/// Encoding used for the `proof` attached to each transaction of a payload.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofFormat {
+    /// Raw proof bytes as a `0x` prefixed hex string.
+    #[default]
+    Hex,
+    /// Raw proof bytes as a base64 string.
+    Base64,
+    /// Proof broken down into its block header, merkle branch hashes and flag bits.
+    Structured,
+}
+
+/// Encoding used for the satoshi amounts (`fee`, input and output `value`) of a payload.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AmountFormat {
+    /// Amounts as JSON numbers.
+    #[default]
+    Number,
+    /// Amounts as decimal strings, for consumers whose JSON parsers lose precision above 2^53.
+    String,
+}
+
+impl AmountFormat {
+    pub fn encode(&self, amount: u64) -> JsonValue {
+        match self {
+            AmountFormat::Number => json!(amount),
+            AmountFormat::String => json!(amount.to_string()),
+        }
+    }
+}
+
+/// Layout of the `apply` and `rollback` lists of a payload.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadShape {
+    /// Blocks, each nesting its matching transactions.
+    #[default]
+    Block,
+    /// Matching transactions, each annotated with the identifier of its block. The `truncated`
+    /// and `total_matches` fields of blocks are left out.
+    ///
+    /// The identifiers of every block applied and rolled back, including the ones without any
+    /// matching transaction, are listed in `apply_blocks` and `rollback_blocks`, so that reorgs
+    /// are still signaled.
+    Transaction,
+}
+
+/// Default number of transaction ids sampled in a [BitcoinOccurrenceDigest].
+pub const DEFAULT_DIGEST_SAMPLE_TXIDS: usize = 10;
+
+/// Window over which the occurrences of a predicate are aggregated into a single
+/// [BitcoinOccurrenceDigest], instead of being delivered one by one. The window opens with the
+/// first occurrence, and closes once it covers `blocks` block heights or is `seconds` old,
+/// whichever comes first, or when the predicate expires.
+///
+/// Digests are best-effort: open windows are only kept in memory.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct DigestConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<u64>,
+    /// Time windows are closed when the next chain event is processed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seconds: Option<u64>,
+    /// Maximum number of transaction ids sampled in the digest, 10 by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sample_txids: Option<usize>,
+}
+
+impl DigestConfig {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.blocks.is_none() && self.seconds.is_none() {
+            errors.push("at least one of `blocks` or `seconds` should be specified".into());
+        }
+        if self.blocks == Some(0) {
+            errors.push("`blocks` should be greater than 0".into());
+        }
+        if self.seconds == Some(0) {
+            errors.push("`seconds` should be greater than 0".into());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn get_max_sample_txids(&self) -> usize {
+        self.max_sample_txids.unwrap_or(DEFAULT_DIGEST_SAMPLE_TXIDS)
+    }
+}
+
+/// Summary of the occurrences of a predicate over a [DigestConfig] window.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BitcoinOccurrenceDigest {
+    /// Number of occurrences aggregated in the digest.
+    pub occurrences: u64,
+    pub applied_blocks: u64,
+    pub rolled_back_blocks: u64,
+    /// Number of matching transactions, across applied blocks.
+    pub transactions: u64,
+    /// Lowest block applied or rolled back over the window.
+    pub start_block: BlockIdentifier,
+    /// Highest block applied or rolled back over the window.
+    pub end_block: BlockIdentifier,
+    /// The first matching transactions of the window.
+    pub sample_txids: Vec<TransactionIdentifier>,
+}
+
+impl BitcoinOccurrenceDigest {
+    /// Starts a digest with the occurrence opening its window. Returns `None` if the occurrence
+    /// doesn't include any block.
+    pub fn from_trigger<'a>(
+        trigger: &BitcoinTriggerChainhook<'a>,
+        max_sample_txids: usize,
+    ) -> Option<BitcoinOccurrenceDigest> {
+        let (_, block) = trigger.apply.first().or(trigger.rollback.first())?;
+        let mut digest = BitcoinOccurrenceDigest {
+            occurrences: 0,
+            applied_blocks: 0,
+            rolled_back_blocks: 0,
+            transactions: 0,
+            start_block: block.block_identifier.clone(),
+            end_block: block.block_identifier.clone(),
+            sample_txids: vec![],
+        };
+        digest.add_trigger(trigger, max_sample_txids);
+        Some(digest)
+    }
+
+    pub fn add_trigger<'a>(
+        &mut self,
+        trigger: &BitcoinTriggerChainhook<'a>,
+        max_sample_txids: usize,
+    ) {
+        self.occurrences += 1;
+        self.applied_blocks += trigger.apply.len() as u64;
+        self.rolled_back_blocks += trigger.rollback.len() as u64;
+        for (_, block) in trigger.apply.iter().chain(trigger.rollback.iter()) {
+            if block.block_identifier.index < self.start_block.index {
+                self.start_block = block.block_identifier.clone();
+            }
+            if block.block_identifier.index > self.end_block.index {
+                self.end_block = block.block_identifier.clone();
+            }
+        }
+        for (transactions, _) in trigger.apply.iter() {
+            self.transactions += transactions.len() as u64;
+            for transaction in transactions.iter() {
+                if self.sample_txids.len() >= max_sample_txids {
+                    break;
+                }
+                self.sample_txids
+                    .push(transaction.transaction_identifier.clone());
+            }
+        }
+    }
+
+    /// Number of block heights covered by the digest.
+    pub fn number_of_blocks_covered(&self) -> u64 {
+        self.end_block.index - self.start_block.index + 1
+    }
+}
+
+/// What the bytes of a proof hold, emitted as the `proof_kind` of the transactions carrying a
+/// proof, whatever its [ProofFormat].
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BitcoinProofKind {
+    /// A merkle block, as returned by `gettxoutproof`: the block header followed by the partial
+    /// merkle tree.
+    MerkleBlock,
+    /// A bare partial merkle tree, as built by [compute_proof], verified against the merkle root
+    /// of the block.
+    PartialMerkleTree,
+}
+
+/// Decomposition of a merkle block proof, as emitted with [ProofFormat::Structured]. `header` is
+/// omitted for proofs built with [compute_proof], which only carry the partial merkle tree.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BitcoinStructuredProof {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    pub number_of_transactions: u32,
+    pub positions: Vec<u32>,
+    pub hashes: Vec<String>,
+    pub flags: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -263,12 +855,22 @@ impl BitcoinTransactionFilterPredicate {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "scope")]
 pub enum BitcoinPredicateType {
+    /// Matches every transaction. Empty blocks, without any transaction besides the coinbase, only
+    /// trigger the predicate when `include_empty_blocks` is set.
     Block,
     Txid(ExactMatchingRule),
     Inputs(InputPredicate),
     Outputs(OutputPredicate),
     StacksProtocol(StacksOperations),
     OrdinalsProtocol(OrdinalOperations),
+    TransactionSize(TransactionSizePredicate),
+    TransactionVersion(VersionMatchingRule),
+    /// Matches the position of a transaction in its block, the coinbase transaction being at
+    /// index 0.
+    TransactionIndex(IndexMatchingRule),
+    /// Matches transactions by the percentile of their fee rate among the transactions of their
+    /// block.
+    FeeRatePercentile(FeeRatePercentileRule),
 }
 
 impl BitcoinPredicateType {
@@ -301,6 +903,61 @@ impl BitcoinPredicateType {
             }
             BitcoinPredicateType::StacksProtocol(_) => {}
             BitcoinPredicateType::OrdinalsProtocol(_) => {}
+            BitcoinPredicateType::TransactionSize(size) => {
+                if let Err(e) = size.validate() {
+                    return Err(append_error_context(
+                        "invalid predicate for scope 'transaction_size'",
+                        vec![e],
+                    ));
+                }
+            }
+            BitcoinPredicateType::TransactionVersion(rule) => {
+                if let Err(e) = rule.validate() {
+                    return Err(append_error_context(
+                        "invalid predicate for scope 'transaction_version'",
+                        vec![e],
+                    ));
+                }
+            }
+            BitcoinPredicateType::TransactionIndex(rule) => {
+                if let Err(e) = rule.validate() {
+                    return Err(append_error_context(
+                        "invalid predicate for scope 'transaction_index'",
+                        vec![e],
+                    ));
+                }
+            }
+            BitcoinPredicateType::FeeRatePercentile(rule) => {
+                if let Err(e) = rule.validate() {
+                    return Err(append_error_context(
+                        "invalid predicate for scope 'fee_rate_percentile'",
+                        vec![e],
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate_for_network(&self, network: &BitcoinNetwork) -> Result<(), Vec<String>> {
+        match self {
+            BitcoinPredicateType::Outputs(outputs) => {
+                if let Err(e) = outputs.validate_for_network(network) {
+                    return Err(append_error_context(
+                        "invalid predicate for scope 'outputs'",
+                        vec![e],
+                    ));
+                }
+            }
+            BitcoinPredicateType::Inputs(input) => {
+                if let Err(e) = input.validate_for_network(network) {
+                    return Err(append_error_context(
+                        "invalid predicate for scope 'inputs'",
+                        vec![e],
+                    ));
+                }
+            }
+            _ => {}
         }
         Ok(())
     }
@@ -310,6 +967,9 @@ pub struct BitcoinTriggerChainhook<'a> {
     pub chainhook: &'a BitcoinChainhookInstance,
     pub apply: Vec<(Vec<&'a BitcoinTransactionData>, &'a BitcoinBlockData)>,
     pub rollback: Vec<(Vec<&'a BitcoinTransactionData>, &'a BitcoinBlockData)>,
+    /// Transactions being rolled back that are not included in any of the blocks applied by
+    /// the same re-org, i.e. transactions dropped from the canonical chain.
+    pub dropped: Vec<&'a TransactionIdentifier>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -321,13 +981,22 @@ pub struct BitcoinTransactionPayload {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BitcoinChainhookPayload {
     pub uuid: String,
+    #[serde(default)]
+    pub name: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum InputPredicate {
     Txid(TxinPredicate),
+    /// Matches the items of the witness of SegWit inputs, hex encoded. Inputs with an empty
+    /// witness, such as legacy ones, never match.
     WitnessScript(MatchingRule),
+    /// Matches the hex encoded `script_sig` of legacy inputs. Inputs with an empty `script_sig`,
+    /// such as the ones spending SegWit outputs, never match.
+    ScriptSig(MatchingRule),
+    /// Matches transactions spending more than `more_than` inputs from `address`.
+    Consolidation(ConsolidationPredicate),
 }
 
 impl InputPredicate {
@@ -335,7 +1004,49 @@ impl InputPredicate {
         match self {
             InputPredicate::Txid(txin) => txin.validate(),
             InputPredicate::WitnessScript(_) => Ok(()),
+            InputPredicate::ScriptSig(rule) => {
+                let pattern = match rule {
+                    MatchingRule::Equals(pattern)
+                    | MatchingRule::StartsWith(pattern)
+                    | MatchingRule::EndsWith(pattern) => pattern,
+                    MatchingRule::AtOffset(rule) => &rule.value,
+                    // regexes are validated when deserialized
+                    MatchingRule::Regex(_) => return Ok(()),
+                };
+                // patterns without the `0x` prefix would be matched as ASCII, see `encoded_pattern`
+                let Some(hex) = pattern.strip_prefix("0x") else {
+                    return Err(vec![format!(
+                        "'script_sig' pattern {pattern} must be a hex string prefixed with 0x"
+                    )]);
+                };
+                if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(vec![format!(
+                        "'script_sig' pattern {pattern} is not a valid hex string"
+                    )]);
+                }
+                Ok(())
+            }
+            InputPredicate::Consolidation(predicate) => {
+                parse_output_address(&predicate.address).map_err(|e| vec![e])?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Ensures that the address targeted by a `consolidation` rule is encoded for `network`.
+    pub fn validate_for_network(&self, network: &BitcoinNetwork) -> Result<(), String> {
+        let InputPredicate::Consolidation(predicate) = self else {
+            return Ok(());
+        };
+        let address = Address::from_str(&predicate.address)
+            .map_err(|e| format!("invalid address {}: {}", predicate.address, e))?;
+        if !address.is_valid_for_network(get_bitcoin_network(network)) {
+            return Err(format!(
+                "address {} is not valid for network {}",
+                predicate.address, network
+            ));
         }
+        Ok(())
     }
 }
 
@@ -343,22 +1054,104 @@ impl InputPredicate {
 #[serde(rename_all = "snake_case")]
 pub enum OutputPredicate {
     OpReturn(MatchingRule),
+    OpReturnProtocol(OpReturnProtocol),
+    P2pk(ExactMatchingRule),
     P2pkh(ExactMatchingRule),
     P2sh(ExactMatchingRule),
     P2wpkh(ExactMatchingRule),
     P2wsh(ExactMatchingRule),
     Descriptor(DescriptorMatchingRule),
+    Denylist(DenylistMatchingRule),
+    AddressType(AddressType),
+    /// Matches outputs whose value, in sats, satisfies the rule.
+    Value(ValuePredicate),
+    /// Matches outputs paying any address of the set, whatever their types.
+    AnyAddress(AddressSetRule),
 }
 
 impl OutputPredicate {
     pub fn validate(&self) -> Result<(), String> {
         match self {
             OutputPredicate::OpReturn(_) => {}
+            OutputPredicate::OpReturnProtocol(_) => {}
+            OutputPredicate::P2pk(ExactMatchingRule::Equals(_p2pk)) => {}
             OutputPredicate::P2pkh(ExactMatchingRule::Equals(_p2pkh)) => {}
             OutputPredicate::P2sh(ExactMatchingRule::Equals(_p2sh)) => {}
             OutputPredicate::P2wpkh(ExactMatchingRule::Equals(_p2wpkh)) => {}
             OutputPredicate::P2wsh(ExactMatchingRule::Equals(_p2wsh)) => {}
             OutputPredicate::Descriptor(descriptor) => descriptor.validate()?,
+            OutputPredicate::Denylist(denylist) => denylist.validate()?,
+            OutputPredicate::AddressType(_) => {}
+            OutputPredicate::Value(rule) => rule.validate()?,
+            OutputPredicate::AnyAddress(rule) => rule.validate()?,
+        }
+        Ok(())
+    }
+
+    /// Ensures that the address targeted by a `p2pk`, `p2pkh`, `p2sh`, `p2wpkh` or `p2wsh` rule
+    /// is valid and encoded for `network`: an address from another network would never match.
+    pub fn validate_for_network(&self, network: &BitcoinNetwork) -> Result<(), String> {
+        let encoded_address = match self {
+            OutputPredicate::P2pk(ExactMatchingRule::Equals(address)) => {
+                // public keys are not tied to a network
+                let hex = address.strip_prefix("0x").unwrap_or(address);
+                if Vec::<u8>::from_hex(hex).is_ok() {
+                    return Ok(());
+                }
+                address
+            }
+            OutputPredicate::P2pkh(ExactMatchingRule::Equals(address))
+            | OutputPredicate::P2sh(ExactMatchingRule::Equals(address)) => {
+                parse_output_address(address)?;
+                address
+            }
+            OutputPredicate::P2wpkh(ExactMatchingRule::Equals(address))
+            | OutputPredicate::P2wsh(ExactMatchingRule::Equals(address)) => {
+                if !parse_output_address(address)?.is_witness_program {
+                    return Err(format!("address {} is not a segwit address", address));
+                }
+                address
+            }
+            OutputPredicate::Denylist(denylist) => {
+                for encoded_address in denylist.read_addresses()?.iter() {
+                    let Ok(address) = Address::from_str(encoded_address) else {
+                        continue;
+                    };
+                    if !address.is_valid_for_network(get_bitcoin_network(network)) {
+                        return Err(format!(
+                            "denylisted address {} is not valid for network {}",
+                            encoded_address, network
+                        ));
+                    }
+                }
+                return Ok(());
+            }
+            OutputPredicate::AnyAddress(rule) => {
+                for encoded_address in rule.addresses.iter() {
+                    let address = Address::from_str(encoded_address)
+                        .map_err(|e| format!("invalid address {}: {}", encoded_address, e))?;
+                    if !address.is_valid_for_network(get_bitcoin_network(network)) {
+                        return Err(format!(
+                            "address {} is not valid for network {}",
+                            encoded_address, network
+                        ));
+                    }
+                }
+                return Ok(());
+            }
+            OutputPredicate::OpReturn(_)
+            | OutputPredicate::OpReturnProtocol(_)
+            | OutputPredicate::Descriptor(_)
+            | OutputPredicate::AddressType(_)
+            | OutputPredicate::Value(_) => return Ok(()),
+        };
+        let address = Address::from_str(encoded_address)
+            .map_err(|e| format!("invalid address {}: {}", encoded_address, e))?;
+        if !address.is_valid_for_network(get_bitcoin_network(network)) {
+            return Err(format!(
+                "address {} is not valid for network {}",
+                encoded_address, network
+            ));
         }
         Ok(())
     }
@@ -367,11 +1160,40 @@ impl OutputPredicate {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "operation")]
 pub enum StacksOperations {
+    /// Matches every transaction carrying a Stacks operation, whatever its kind.
+    All,
     StackerRewarded,
-    BlockCommitted,
+    /// Matches block commits. When `parent_block_ptr` or `parent_vtxindex` are set, only the commits
+    /// building on the Stacks block committed at that position are matched.
+    BlockCommitted {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent_block_ptr: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent_vtxindex: Option<u16>,
+    },
     LeaderRegistered,
-    StxTransferred,
+    /// Matches STX transfers. When `min_amount`, `sender` or `recipient` are set, only the transfers
+    /// of at least `min_amount` micro-STX, from `sender` or to `recipient`, are matched. The sender
+    /// owns the output spent by the first input and the recipient owns the second output, both
+    /// given as Bitcoin addresses.
+    StxTransferred {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min_amount: Option<u128>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        recipient: Option<String>,
+    },
     StxLocked,
+    /// Matches the block commits of Nakamoto miners, each of them starting a new Stacks tenure if it
+    /// wins the sortition.
+    TenureChanged,
+    /// Matches transactions carrying the given opcode byte in their OP_RETURN, right after the Stacks
+    /// magic bytes of the predicate's network. Useful for operations that are not yet modeled by
+    /// [StacksOpcodes].
+    Opcode {
+        opcode: u8,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq, JsonSchema)]
@@ -382,24 +1204,371 @@ pub enum OrdinalsMetaProtocol {
     Brc20,
 }
 
+/// Registry of the well-known protocols embedding their data in OP_RETURN outputs, identified
+/// by the prefix of that data. Protocols missing from this list can still be matched with a
+/// raw `op_return` rule.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
-pub struct InscriptionFeedData {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub meta_protocols: Option<HashSet<OrdinalsMetaProtocol>>,
+#[serde(rename_all = "snake_case")]
+pub enum OpReturnProtocol {
+    Omni,
+    Stacks,
+    StacksTestnet,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+impl OpReturnProtocol {
+    pub fn get_prefix(&self) -> &'static [u8] {
+        match self {
+            OpReturnProtocol::Omni => b"omni",
+            OpReturnProtocol::Stacks => b"X2",
+            OpReturnProtocol::StacksTestnet => b"T2",
+        }
+    }
+
+    pub fn get_matching_rule(&self) -> MatchingRule {
+        MatchingRule::StartsWith(format!("0x{}", hex::encode(self.get_prefix())))
+    }
+}
+
+/// Standard types of output scripts, matched regardless of the address they pay to.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+}
+
+impl AddressType {
+    /// Classifies a hex encoded `script_pubkey`, returning `None` for non-standard scripts.
+    pub fn from_script_pubkey(script_pubkey: &str) -> Option<AddressType> {
+        let bytes =
+            Vec::<u8>::from_hex(script_pubkey.strip_prefix("0x").unwrap_or(script_pubkey)).ok()?;
+        let script = ScriptBuf::from_bytes(bytes);
+        if script.is_p2pk() {
+            Some(AddressType::P2pk)
+        } else if script.is_p2pkh() {
+            Some(AddressType::P2pkh)
+        } else if script.is_p2sh() {
+            Some(AddressType::P2sh)
+        } else if script.is_p2wpkh() {
+            Some(AddressType::P2wpkh)
+        } else if script.is_p2wsh() {
+            Some(AddressType::P2wsh)
+        } else if script.is_p2tr() {
+            Some(AddressType::P2tr)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct InscriptionFeedData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta_protocols: Option<HashSet<OrdinalsMetaProtocol>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "operation")]
 pub enum OrdinalOperations {
     InscriptionFeed(InscriptionFeedData),
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionSizePredicate {
+    Weight(SizeMatchingRule),
+    Vsize(SizeMatchingRule),
+    /// Matches the total size, in bytes, of the content inscribed by a transaction, summed over
+    /// all the inscriptions it reveals.
+    ///
+    /// Only meant for SDK embedders indexing ordinals, which populate the `ordinal_operations` of
+    /// transactions: the chainhook service doesn't, so the predicate never matches there.
+    InscriptionContent(SizeMatchingRule),
+}
+
+impl TransactionSizePredicate {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            TransactionSizePredicate::Weight(rule)
+            | TransactionSizePredicate::Vsize(rule)
+            | TransactionSizePredicate::InscriptionContent(rule) => rule.validate(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeMatchingRule {
+    HigherThan(u64),
+    LowerThan(u64),
+    /// Matches sizes within the bounds, inclusive.
+    Between(u64, u64),
+}
+
+impl SizeMatchingRule {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            SizeMatchingRule::HigherThan(_) => {}
+            SizeMatchingRule::LowerThan(val) => {
+                if val.eq(&0) {
+                    return Err("'lower_than' filter must be greater than 0".into());
+                }
+            }
+            SizeMatchingRule::Between(lhs, rhs) => {
+                if lhs > rhs {
+                    return Err(
+                        "'between' filter must have left-hand-side value lower than or equal to right-hand-side value"
+                            .into(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&self, size: u64) -> bool {
+        match self {
+            SizeMatchingRule::HigherThan(a) => size.gt(a),
+            SizeMatchingRule::LowerThan(a) => size.lt(a),
+            SizeMatchingRule::Between(a, b) => size.ge(a) && size.le(b),
+        }
+    }
+}
+
+/// Matches the value of an output, in sats. Outputs with a zero value, such as `OP_RETURN` ones,
+/// are compared like any other output.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ValuePredicate {
+    GreaterThan(u64),
+    LessThan(u64),
+    /// Matches values within the bounds, inclusive.
+    Between(u64, u64),
+}
+
+impl ValuePredicate {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ValuePredicate::GreaterThan(_) => {}
+            ValuePredicate::LessThan(val) => {
+                if val.eq(&0) {
+                    return Err("'less_than' filter must be greater than 0".into());
+                }
+            }
+            ValuePredicate::Between(lhs, rhs) => {
+                if lhs > rhs {
+                    return Err(
+                        "'between' filter must have left-hand-side value lower than or equal to right-hand-side value"
+                            .into(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&self, value: u64) -> bool {
+        match self {
+            ValuePredicate::GreaterThan(a) => value.gt(a),
+            ValuePredicate::LessThan(a) => value.lt(a),
+            ValuePredicate::Between(a, b) => value.ge(a) && value.le(b),
+        }
+    }
+}
+
+/// Matches the version (`nVersion`) of a transaction, e.g. version 2 transactions opting into
+/// BIP68 relative lock-times.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionMatchingRule {
+    Equals(i32),
+    HigherThan(i32),
+    LowerThan(i32),
+}
+
+impl VersionMatchingRule {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            VersionMatchingRule::LowerThan(i32::MIN) => Err(format!(
+                "'lower_than' filter must be greater than {}",
+                i32::MIN
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn evaluate(&self, version: i32) -> bool {
+        match self {
+            VersionMatchingRule::Equals(a) => version.eq(a),
+            VersionMatchingRule::HigherThan(a) => version.gt(a),
+            VersionMatchingRule::LowerThan(a) => version.lt(a),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexMatchingRule {
+    Equals(u32),
+    HigherThan(u32),
+    LowerThan(u32),
+    /// Matches indexes within the bounds, inclusive.
+    Between(u32, u32),
+}
+
+impl IndexMatchingRule {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            IndexMatchingRule::LowerThan(0) => {
+                Err("'lower_than' filter must be greater than 0".into())
+            }
+            IndexMatchingRule::Between(lhs, rhs) if lhs > rhs => Err(
+                "'between' filter must have left-hand-side value lower than or equal to right-hand-side value"
+                    .into(),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn evaluate(&self, index: u32) -> bool {
+        match self {
+            IndexMatchingRule::Equals(a) => index.eq(a),
+            IndexMatchingRule::HigherThan(a) => index.gt(a),
+            IndexMatchingRule::LowerThan(a) => index.lt(a),
+            IndexMatchingRule::Between(a, b) => index.ge(a) && index.le(b),
+        }
+    }
+}
+
+/// Matches the transactions whose fee rate percentile is within `min` and `max`, inclusive.
+///
+/// The percentile of a transaction is the share of the transactions of its block paying a lower or
+/// equal fee rate, so that the highest fee rate scores 100 and `{ "min": 91, "max": 100 }` matches
+/// the top decile.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct FeeRatePercentileRule {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl FeeRatePercentileRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max > 100 {
+            return Err("'max' percentile must be lower than or equal to 100".into());
+        }
+        if self.min > self.max {
+            return Err("'min' percentile must be lower than or equal to 'max' percentile".into());
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&self, percentile: u8) -> bool {
+        percentile >= self.min && percentile <= self.max
+    }
+}
+
+/// The fee rates paid by the transactions of a block, computed once per chain event for the
+/// predicates with a `fee_rate_percentile` scope.
+///
+/// Fee rates are expressed in millisatoshis per virtual byte. The coinbase transaction, paying no
+/// fee, is left out of the distribution.
+pub struct BlockFeeRates {
+    sorted_fee_rates: Vec<u64>,
+}
+
+impl BlockFeeRates {
+    pub fn new(block: &BitcoinBlockData) -> BlockFeeRates {
+        let mut sorted_fee_rates = block
+            .transactions
+            .iter()
+            .filter_map(BlockFeeRates::get_fee_rate)
+            .collect::<Vec<_>>();
+        sorted_fee_rates.sort_unstable();
+        BlockFeeRates { sorted_fee_rates }
+    }
+
+    fn get_fee_rate(tx: &BitcoinTransactionData) -> Option<u64> {
+        if tx.metadata.index == 0 || tx.metadata.vsize == 0 {
+            return None;
+        }
+        Some(tx.metadata.fee.saturating_mul(1000) / tx.metadata.vsize)
+    }
+
+    /// Returns the percentile of the fee rate of `tx`, or `None` for transactions left out of the
+    /// distribution.
+    fn get_percentile(&self, tx: &BitcoinTransactionData) -> Option<u8> {
+        let fee_rate = BlockFeeRates::get_fee_rate(tx)?;
+        let rank = self
+            .sorted_fee_rates
+            .partition_point(|rate| *rate <= fee_rate);
+        Some((rank * 100 / self.sorted_fee_rates.len()) as u8)
+    }
+}
+
+/// Stacks has no canonical deployment on Signet, whose Stacks operations are expected to carry the
+/// testnet magic bytes.
 pub fn get_stacks_canonical_magic_bytes(network: &BitcoinNetwork) -> [u8; 2] {
     match network {
         BitcoinNetwork::Mainnet => *b"X2",
-        BitcoinNetwork::Testnet => *b"T2",
+        BitcoinNetwork::Testnet | BitcoinNetwork::Signet => *b"T2",
         BitcoinNetwork::Regtest => *b"id",
-        BitcoinNetwork::Signet => unreachable!(),
+    }
+}
+
+/// The script_pubkey an address targeted by a predicate is paid to.
+#[derive(Clone, Debug)]
+struct ParsedOutputAddress {
+    script_pubkey: String,
+    is_witness_program: bool,
+}
+
+fn parse_output_address(encoded_address: &str) -> Result<ParsedOutputAddress, String> {
+    let address = Address::from_str(encoded_address)
+        .map_err(|e| format!("invalid address {}: {}", encoded_address, e))?
+        .assume_checked();
+    Ok(ParsedOutputAddress {
+        script_pubkey: hex::encode(address.script_pubkey().as_bytes()),
+        is_witness_program: matches!(address.payload(), Payload::WitnessProgram(_)),
+    })
+}
+
+/// The address targeted by the predicate of an instance, parsed when the instance is registered
+/// so that evaluating a transaction only compares script_pubkeys. Instances read back from the
+/// predicates db parse it on their first evaluation. It's derived from the predicate, hence left
+/// out of comparisons.
+#[derive(Clone, Debug, Default)]
+pub struct PredicateAddress(Arc<OnceLock<Option<ParsedOutputAddress>>>);
+
+impl PartialEq for PredicateAddress {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl PredicateAddress {
+    pub fn new(predicate: &BitcoinPredicateType) -> PredicateAddress {
+        let address = PredicateAddress::default();
+        let _ = address.get_or_parse(predicate);
+        address
+    }
+
+    fn get_or_parse(&self, predicate: &BitcoinPredicateType) -> Option<&ParsedOutputAddress> {
+        self.0
+            .get_or_init(|| predicate.parse_target_address().ok().flatten())
+            .as_ref()
+    }
+}
+
+pub fn get_bitcoin_network(network: &BitcoinNetwork) -> Network {
+    match network {
+        BitcoinNetwork::Mainnet => Network::Bitcoin,
+        BitcoinNetwork::Testnet => Network::Testnet,
+        BitcoinNetwork::Regtest => Network::Regtest,
+        BitcoinNetwork::Signet => Network::Signet,
     }
 }
 
@@ -423,6 +1592,18 @@ impl PoxConfig {
         (block_height.saturating_sub(self.genesis_block_height)) % self.get_pox_cycle_len()
     }
 
+    /// Returns true if `block_height` belongs to the prepare phase of its PoX cycle, during
+    /// which no rewards are distributed. The prepare phase spans the last `prepare_phase_len`
+    /// blocks of a cycle, the first block of a cycle being at position 1 (position 0 being the
+    /// last block of the previous cycle).
+    pub fn is_in_prepare_phase(&self, block_height: u64) -> bool {
+        if block_height <= self.genesis_block_height {
+            return false;
+        }
+        let pos_in_pox_cycle = self.get_pos_in_pox_cycle(block_height);
+        pos_in_pox_cycle == 0 || pos_in_pox_cycle > self.reward_phase_len
+    }
+
     pub fn get_burn_address(&self) -> &str {
         match self.genesis_block_height {
             666050 => "1111111111111111111114oLvT2",
@@ -445,6 +1626,15 @@ const POX_CONFIG_TESTNET: PoxConfig = PoxConfig {
     rewarded_addresses_per_block: 2,
 };
 
+/// Stacks has no canonical deployment on Signet: PoX cycles have the testnet lengths, and start at
+/// the Signet genesis block.
+const POX_CONFIG_SIGNET: PoxConfig = PoxConfig {
+    genesis_block_height: 0,
+    prepare_phase_len: 50,
+    reward_phase_len: 1050,
+    rewarded_addresses_per_block: 2,
+};
+
 const POX_CONFIG_DEVNET: PoxConfig = PoxConfig {
     genesis_block_height: 100,
     prepare_phase_len: 4,
@@ -457,7 +1647,7 @@ pub fn get_canonical_pox_config(network: &BitcoinNetwork) -> PoxConfig {
         BitcoinNetwork::Mainnet => POX_CONFIG_MAINNET,
         BitcoinNetwork::Testnet => POX_CONFIG_TESTNET,
         BitcoinNetwork::Regtest => POX_CONFIG_DEVNET,
-        BitcoinNetwork::Signet => unreachable!(),
+        BitcoinNetwork::Signet => POX_CONFIG_SIGNET,
     }
 }
 
@@ -491,6 +1681,11 @@ impl TryFrom<u8> for StacksOpcodes {
 pub struct TxinPredicate {
     pub txid: String,
     pub vout: u32,
+    /// When enabled, transactions spending an output of a transaction already matched by this
+    /// predicate are matched as well. Matched transactions are only tracked within the blocks of a
+    /// single chain event, and are forgotten once the event is evaluated.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub chained: bool,
 }
 
 impl TxinPredicate {
@@ -502,34 +1697,89 @@ impl TxinPredicate {
     }
 }
 
+/// Detects the consolidation of the funds of a watched wallet. The address spent by each input is
+/// reconstructed from its unlocking data, which supports p2pkh, p2sh, p2wpkh and p2wsh spends:
+/// taproot inputs are never counted.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub struct ConsolidationPredicate {
+    pub address: String,
+    pub more_than: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub struct DescriptorMatchingRule {
     // expression defines the bitcoin descriptor.
     pub expression: String,
     #[serde(default, deserialize_with = "deserialize_descriptor_range")]
     pub range: Option<[u32; 2]>,
+    // collect_all_matches makes the evaluation report every matching output instead of
+    // stopping at the first one.
+    #[serde(default)]
+    pub collect_all_matches: bool,
+    // script_pubkeys caches the script_pubkeys derived from the descriptor, along with the
+    // default range they were derived with, so that they're not derived for every transaction.
+    #[serde(skip)]
+    script_pubkeys: Arc<RwLock<Option<(Option<[u32; 2]>, Arc<HashSet<String>>)>>>,
+}
+
+impl PartialEq for DescriptorMatchingRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
+            && self.range == other.range
+            && self.collect_all_matches == other.collect_all_matches
+    }
 }
 
+/// Derivation range used for descriptor predicates that do not define a `range`, unless
+/// overridden globally.
+pub const DEFAULT_DESCRIPTOR_RANGE: [u32; 2] = [0, 5];
+
+/// Secp256k1 contexts used to parse and derive descriptors. Allocating a context is expensive, so
+/// they are created once and shared by every evaluation.
+static SECP_SIGNING_CONTEXT: OnceLock<Secp256k1<SignOnly>> = OnceLock::new();
+static SECP_VERIFICATION_CONTEXT: OnceLock<Secp256k1<VerifyOnly>> = OnceLock::new();
+
 impl DescriptorMatchingRule {
+    pub fn new(
+        expression: String,
+        range: Option<[u32; 2]>,
+        collect_all_matches: bool,
+    ) -> DescriptorMatchingRule {
+        DescriptorMatchingRule {
+            expression,
+            range,
+            collect_all_matches,
+            script_pubkeys: Arc::new(RwLock::new(None)),
+        }
+    }
+
     pub fn validate(&self) -> Result<(), String> {
-        let _ = self.derive_script_pubkeys()?;
+        let _ = self.derive_script_pubkeys(None)?;
         Ok(())
     }
 
-    pub fn derive_script_pubkeys(&self) -> Result<Vec<String>, String> {
-        let DescriptorMatchingRule { expression, range } = self;
+    pub fn derive_script_pubkeys(
+        &self,
+        default_range: Option<[u32; 2]>,
+    ) -> Result<Vec<String>, String> {
+        let DescriptorMatchingRule {
+            expression, range, ..
+        } = self;
         // To derive from descriptors, we need to provide a secp context.
-        let (sig, ver) = (&Secp256k1::signing_only(), &Secp256k1::verification_only());
-        let (desc, _) = Descriptor::parse_descriptor(&sig, expression)
+        let sig = SECP_SIGNING_CONTEXT.get_or_init(Secp256k1::signing_only);
+        let ver = SECP_VERIFICATION_CONTEXT.get_or_init(Secp256k1::verification_only);
+        let (desc, _) = Descriptor::parse_descriptor(sig, expression)
             .map_err(|e| format!("invalid descriptor: {}", e.to_string()))?;
 
         // If the descriptor is derivable (`has_wildcard()`), we rely on the `range` field
-        // defined by the predicate OR fallback to a default range of [0,5] when not set.
+        // defined by the predicate OR fallback to the provided default range when not set,
+        // and to [0,5] when no default range is provided either.
         // When the descriptor is not derivable we force to create a unique iteration by
         // ranging over [0,1].
         let range = if desc.has_wildcard() {
-            range.unwrap_or([0, 5])
+            range.or(default_range).unwrap_or(DEFAULT_DESCRIPTOR_RANGE)
         } else {
             [0, 1]
         };
@@ -538,7 +1788,7 @@ impl DescriptorMatchingRule {
         // Derive the addresses and try to match them against the outputs.
         for i in range[0]..range[1] {
             let derived = desc
-                .derived_descriptor(&ver, i)
+                .derived_descriptor(ver, i)
                 .map_err(|e| format!("error deriving descriptor: {}", e))?;
 
             // Extract and encode the derived pubkey.
@@ -546,6 +1796,232 @@ impl DescriptorMatchingRule {
         }
         Ok(script_pubkeys)
     }
+
+    /// Returns the script_pubkeys derived from the descriptor, which are only derived again when
+    /// `default_range` changes.
+    pub fn get_script_pubkeys(
+        &self,
+        default_range: Option<[u32; 2]>,
+    ) -> Result<Arc<HashSet<String>>, String> {
+        if let Ok(cache) = self.script_pubkeys.read() {
+            if let Some((range, ref script_pubkeys)) = *cache {
+                if range == default_range {
+                    return Ok(script_pubkeys.clone());
+                }
+            }
+        }
+        let script_pubkeys = Arc::new(
+            self.derive_script_pubkeys(default_range)?
+                .into_iter()
+                .collect::<HashSet<_>>(),
+        );
+        if let Ok(mut cache) = self.script_pubkeys.write() {
+            *cache = Some((default_range, script_pubkeys.clone()));
+        }
+        Ok(script_pubkeys)
+    }
+
+    /// Returns the indices of the outputs paying to one of the script_pubkeys derived from
+    /// the descriptor. Unless `collect_all_matches` is set, only the first match is returned.
+    pub fn get_matching_output_indices(
+        &self,
+        outputs: &Vec<TxOut>,
+        default_range: Option<[u32; 2]>,
+    ) -> Result<Vec<usize>, String> {
+        let script_pubkeys = self.get_script_pubkeys(default_range)?;
+        let mut matching_indices = vec![];
+        for (index, output) in outputs.iter().enumerate() {
+            let Some(script_pubkey) = output.script_pubkey.get(2..) else {
+                continue;
+            };
+            if script_pubkeys.contains(script_pubkey) {
+                matching_indices.push(index);
+                if !self.collect_all_matches {
+                    break;
+                }
+            }
+        }
+        Ok(matching_indices)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DenylistMatchingRule {
+    // addresses lists the denylisted addresses inline.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    // path points to a file listing additional addresses, one per line. The file is
+    // reloaded whenever it is modified, without having to register the predicate again.
+    // Predicates registered through the HTTP API can't use it, since it reads a local file.
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(skip)]
+    cache: Arc<RwLock<DenylistCache>>,
+}
+
+#[derive(Debug, Default)]
+struct DenylistCache {
+    script_pubkeys: Option<Arc<HashSet<String>>>,
+    file_modified_at: Option<SystemTime>,
+}
+
+impl PartialEq for DenylistMatchingRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.addresses == other.addresses && self.path == other.path
+    }
+}
+
+impl DenylistMatchingRule {
+    pub fn new(addresses: Vec<String>, path: Option<String>) -> DenylistMatchingRule {
+        DenylistMatchingRule {
+            addresses,
+            path,
+            cache: Arc::new(RwLock::new(DenylistCache::default())),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.addresses.is_empty() && self.path.is_none() {
+            return Err(format!(
+                "denylist must define at least one of 'addresses' or 'path'"
+            ));
+        }
+        self.reload_if_modified()
+    }
+
+    fn read_addresses(&self) -> Result<Vec<String>, String> {
+        let mut addresses = self.addresses.clone();
+        addresses.append(&mut self.read_file_addresses()?);
+        Ok(addresses)
+    }
+
+    /// Reads the addresses listed in the denylist file, if any. Errors don't echo the content
+    /// of the file, nor the underlying io error, since they may be reported to API users.
+    fn read_file_addresses(&self) -> Result<Vec<String>, String> {
+        let Some(ref path) = self.path else {
+            return Ok(vec![]);
+        };
+        let content = std::fs::read_to_string(path)
+            .map_err(|_| format!("unable to read denylist file {path}"))?;
+        let mut addresses = vec![];
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if Address::from_str(line).is_err() {
+                return Err(format!(
+                    "invalid denylisted address on line {} of {path}",
+                    i + 1
+                ));
+            }
+            addresses.push(line.to_string());
+        }
+        Ok(addresses)
+    }
+
+    fn load_script_pubkeys(&self) -> Result<HashSet<String>, String> {
+        let mut script_pubkeys = HashSet::new();
+        for encoded_address in self.read_addresses()?.iter() {
+            let address = Address::from_str(encoded_address)
+                .map_err(|e| format!("invalid denylisted address {encoded_address}: {e}"))?;
+            script_pubkeys.insert(hex::encode(
+                address.assume_checked().script_pubkey().as_bytes(),
+            ));
+        }
+        Ok(script_pubkeys)
+    }
+
+    fn file_modified_at(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Loads the script_pubkeys of the denylisted addresses, unless they were already loaded and
+    /// the denylist file wasn't modified since. Called once per chain event rather than once per
+    /// transaction evaluated.
+    pub fn reload_if_modified(&self) -> Result<(), String> {
+        let file_modified_at = self.file_modified_at();
+        if let Ok(cache) = self.cache.read() {
+            if cache.script_pubkeys.is_some()
+                && (self.path.is_none() || cache.file_modified_at == file_modified_at)
+            {
+                return Ok(());
+            }
+        }
+        let script_pubkeys = Arc::new(self.load_script_pubkeys()?);
+        if let Ok(mut cache) = self.cache.write() {
+            cache.script_pubkeys = Some(script_pubkeys);
+            cache.file_modified_at = file_modified_at;
+        }
+        Ok(())
+    }
+
+    /// Returns the script_pubkeys of the denylisted addresses, as last loaded.
+    pub fn get_script_pubkeys(&self) -> Result<Arc<HashSet<String>>, String> {
+        if let Ok(cache) = self.cache.read() {
+            if let Some(ref script_pubkeys) = cache.script_pubkeys {
+                return Ok(script_pubkeys.clone());
+            }
+        }
+        self.reload_if_modified()?;
+        self.cache
+            .read()
+            .map_err(|e| format!("unable to read denylist cache: {}", e.to_string()))?
+            .script_pubkeys
+            .clone()
+            .ok_or(format!("denylist not loaded"))
+    }
+}
+
+/// Set of addresses, legacy and SegWit ones alike, whose script_pubkeys are computed when the
+/// predicate is registered so that matching an output is a single hash lookup.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AddressSetRule {
+    pub addresses: HashSet<String>,
+    #[serde(skip)]
+    script_pubkeys: Arc<OnceLock<HashSet<String>>>,
+}
+
+impl PartialEq for AddressSetRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.addresses == other.addresses
+    }
+}
+
+impl AddressSetRule {
+    pub fn new(addresses: HashSet<String>) -> AddressSetRule {
+        AddressSetRule {
+            addresses,
+            script_pubkeys: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.addresses.is_empty() {
+            return Err("'any_address' must list at least one address".into());
+        }
+        let _ = self.get_script_pubkeys()?;
+        Ok(())
+    }
+
+    /// Returns the hex encoded script_pubkeys of the addresses, computed once.
+    pub fn get_script_pubkeys(&self) -> Result<&HashSet<String>, String> {
+        if let Some(script_pubkeys) = self.script_pubkeys.get() {
+            return Ok(script_pubkeys);
+        }
+        let mut script_pubkeys = HashSet::new();
+        for encoded_address in self.addresses.iter() {
+            let address = Address::from_str(encoded_address)
+                .map_err(|e| format!("invalid address {encoded_address}: {e}"))?;
+            script_pubkeys.insert(hex::encode(
+                address.assume_checked().script_pubkey().as_bytes(),
+            ));
+        }
+        Ok(self.script_pubkeys.get_or_init(|| script_pubkeys))
+    }
 }
 
 // deserialize_descriptor_range makes sure that the range value is valid.
@@ -567,7 +2043,12 @@ where
 pub struct BitcoinChainhookOccurrencePayload {
     pub apply: Vec<BitcoinTransactionPayload>,
     pub rollback: Vec<BitcoinTransactionPayload>,
+    #[serde(default)]
+    pub dropped: Vec<TransactionIdentifier>,
     pub chainhook: BitcoinChainhookPayload,
+    /// Set instead of `apply` and `rollback` for the predicates delivering digests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<BitcoinOccurrenceDigest>,
 }
 
 impl BitcoinChainhookOccurrencePayload {
@@ -599,163 +2080,630 @@ impl BitcoinChainhookOccurrencePayload {
                     BitcoinTransactionPayload { block }
                 })
                 .collect::<Vec<_>>(),
+            dropped: trigger.dropped.into_iter().cloned().collect(),
             chainhook: BitcoinChainhookPayload {
                 uuid: trigger.chainhook.uuid.clone(),
+                name: trigger.chainhook.name.clone(),
+            },
+            digest: None,
+        }
+    }
+
+    pub fn from_digest(
+        chainhook: &BitcoinChainhookInstance,
+        digest: BitcoinOccurrenceDigest,
+    ) -> BitcoinChainhookOccurrencePayload {
+        BitcoinChainhookOccurrencePayload {
+            apply: vec![],
+            rollback: vec![],
+            dropped: vec![],
+            chainhook: BitcoinChainhookPayload {
+                uuid: chainhook.uuid.clone(),
+                name: chainhook.name.clone(),
             },
+            digest: Some(digest),
         }
     }
+
+    /// The intent posted to the `verification_url` of the predicate before the occurrence.
+    pub fn intent(&self) -> JsonValue {
+        build_delivery_intent(
+            &self.chainhook.uuid,
+            &self.chainhook.name,
+            self.apply
+                .iter()
+                .map(|p| &p.block.block_identifier)
+                .collect(),
+            self.rollback
+                .iter()
+                .map(|p| &p.block.block_identifier)
+                .collect(),
+        )
+    }
 }
 
 pub enum BitcoinChainhookOccurrence {
     Http(RequestBuilder, BitcoinChainhookOccurrencePayload),
     File(String, Vec<u8>),
+    UnixSocket(String, Vec<u8>),
+    /// Payload to push with [websocket_export::send_websocket_message], along with the url.
+    #[cfg(feature = "websocket")]
+    WebSocket(String, Vec<u8>),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetHook, Vec<BitcoinParquetRow>),
+    /// Payload to produce with [kafka_export::produce_kafka_message], along with its message key.
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaHook, String, Vec<u8>),
+    /// Payload to send with [grpc_export::send_grpc_occurrence], along with the uuid of the predicate.
+    #[cfg(feature = "grpc")]
+    Grpc(GrpcHook, String, Vec<u8>),
+    /// Payload to send with [sqs_export::send_sqs_occurrences], along with the predicate uuid.
+    #[cfg(feature = "sqs")]
+    Sqs(SqsHook, String, Vec<u8>),
+    /// Payload to write with [s3_export::append_s3_object], along with the key of the object.
+    #[cfg(feature = "s3")]
+    S3Append(S3AppendHook, String, Vec<u8>),
     Data(BitcoinChainhookOccurrencePayload),
 }
 
-pub fn evaluate_bitcoin_chainhooks_on_chain_event<'a>(
-    chain_event: &'a BitcoinChainEvent,
-    active_chainhooks: &Vec<&'a BitcoinChainhookInstance>,
-    ctx: &Context,
-) -> (
-    Vec<BitcoinTriggerChainhook<'a>>,
-    BTreeMap<&'a str, &'a BlockIdentifier>,
-    BTreeMap<&'a str, &'a BlockIdentifier>,
-) {
-    let mut evaluated_predicates = BTreeMap::new();
+impl BitcoinChainhookOccurrence {
+    /// Converts the occurrence into a record that can be serialized and routed independently
+    /// of the action it was built for.
+    pub fn to_record(&self) -> Result<BitcoinChainhookOccurrenceRecord, String> {
+        let record = match self {
+            BitcoinChainhookOccurrence::Http(request, payload) => {
+                let url = request
+                    .try_clone()
+                    .and_then(|request| request.build().ok())
+                    .map(|request| request.url().to_string())
+                    .ok_or(format!("unable to retrieve url from http request"))?;
+                BitcoinChainhookOccurrenceRecord {
+                    action: BitcoinChainhookOccurrenceAction::HttpPost,
+                    url: Some(url),
+                    path: None,
+                    payload: serde_json::to_value(payload)
+                        .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?,
+                }
+            }
+            BitcoinChainhookOccurrence::File(path, bytes) => BitcoinChainhookOccurrenceRecord {
+                action: BitcoinChainhookOccurrenceAction::FileAppend,
+                url: None,
+                path: Some(path.clone()),
+                payload: serde_json::from_slice(bytes)
+                    .map_err(|e| format!("unable to deserialize payload {}", e.to_string()))?,
+            },
+            BitcoinChainhookOccurrence::UnixSocket(path, bytes) => {
+                BitcoinChainhookOccurrenceRecord {
+                    action: BitcoinChainhookOccurrenceAction::UnixSocket,
+                    url: None,
+                    path: Some(path.clone()),
+                    payload: serde_json::from_slice(bytes)
+                        .map_err(|e| format!("unable to deserialize payload {}", e.to_string()))?,
+                }
+            }
+            #[cfg(feature = "websocket")]
+            BitcoinChainhookOccurrence::WebSocket(url, bytes) => BitcoinChainhookOccurrenceRecord {
+                action: BitcoinChainhookOccurrenceAction::WebSocket,
+                url: Some(url.clone()),
+                path: None,
+                payload: serde_json::from_slice(bytes)
+                    .map_err(|e| format!("unable to deserialize payload {}", e.to_string()))?,
+            },
+            #[cfg(feature = "parquet")]
+            BitcoinChainhookOccurrence::Parquet(hook, rows) => BitcoinChainhookOccurrenceRecord {
+                action: BitcoinChainhookOccurrenceAction::Parquet,
+                url: None,
+                path: Some(hook.path.clone()),
+                payload: serde_json::to_value(rows)
+                    .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?,
+            },
+            #[cfg(feature = "kafka")]
+            BitcoinChainhookOccurrence::Kafka(hook, _key, bytes) => {
+                BitcoinChainhookOccurrenceRecord {
+                    action: BitcoinChainhookOccurrenceAction::Kafka,
+                    url: None,
+                    path: Some(hook.topic.clone()),
+                    payload: serde_json::from_slice(bytes)
+                        .map_err(|e| format!("unable to deserialize payload {}", e.to_string()))?,
+                }
+            }
+            #[cfg(feature = "grpc")]
+            BitcoinChainhookOccurrence::Grpc(hook, _predicate_uuid, bytes) => {
+                BitcoinChainhookOccurrenceRecord {
+                    action: BitcoinChainhookOccurrenceAction::Grpc,
+                    url: Some(hook.endpoint.clone()),
+                    path: None,
+                    payload: serde_json::from_slice(bytes)
+                        .map_err(|e| format!("unable to deserialize payload {}", e.to_string()))?,
+                }
+            }
+            #[cfg(feature = "sqs")]
+            BitcoinChainhookOccurrence::Sqs(hook, _predicate_uuid, bytes) => {
+                BitcoinChainhookOccurrenceRecord {
+                    action: BitcoinChainhookOccurrenceAction::Sqs,
+                    url: Some(hook.queue_url.clone()),
+                    path: None,
+                    payload: serde_json::from_slice(bytes)
+                        .map_err(|e| format!("unable to deserialize payload {}", e.to_string()))?,
+                }
+            }
+            #[cfg(feature = "s3")]
+            BitcoinChainhookOccurrence::S3Append(hook, key, bytes) => {
+                BitcoinChainhookOccurrenceRecord {
+                    action: BitcoinChainhookOccurrenceAction::S3Append,
+                    url: None,
+                    path: Some(format!("s3://{}/{}", hook.bucket, key)),
+                    payload: serde_json::from_slice(bytes)
+                        .map_err(|e| format!("unable to deserialize payload {}", e.to_string()))?,
+                }
+            }
+            BitcoinChainhookOccurrence::Data(payload) => BitcoinChainhookOccurrenceRecord {
+                action: BitcoinChainhookOccurrenceAction::Noop,
+                url: None,
+                path: None,
+                payload: serde_json::to_value(payload)
+                    .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?,
+            },
+        };
+        Ok(record)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BitcoinChainhookOccurrenceAction {
+    HttpPost,
+    FileAppend,
+    UnixSocket,
+    #[cfg(feature = "websocket")]
+    #[serde(rename = "websocket")]
+    WebSocket,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    #[cfg(feature = "kafka")]
+    Kafka,
+    #[cfg(feature = "grpc")]
+    Grpc,
+    #[cfg(feature = "sqs")]
+    Sqs,
+    #[cfg(feature = "s3")]
+    S3Append,
+    Noop,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BitcoinChainhookOccurrenceRecord {
+    pub action: BitcoinChainhookOccurrenceAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub payload: JsonValue,
+}
+
+/// Returns the transactions of `block` matched by `chainhook`, or `None` if the block doesn't
+/// trigger it.
+///
+/// `chained_txids` holds the transactions matched so far in the chain event, and is only used and
+/// updated by `inputs.txid` predicates with `chained` enabled. `fee_rates` is only used by
+/// `fee_rate_percentile` predicates.
+fn evaluate_bitcoin_block<'a>(
+    chainhook: &BitcoinChainhookInstance,
+    block: &'a BitcoinBlockData,
+    chained_txids: &mut HashSet<String>,
+    fee_rates: Option<&BlockFeeRates>,
+    descriptor_default_range: Option<[u32; 2]>,
+    ctx: &Context,
+) -> Option<Vec<&'a BitcoinTransactionData>> {
+    if let BitcoinPredicateType::Block = chainhook.predicate {
+        // Empty blocks don't hold any transaction besides the coinbase.
+        let is_empty = block.transactions.iter().all(|tx| tx.metadata.index == 0);
+        if is_empty {
+            return chainhook
+                .include_empty_blocks
+                .then(|| block.transactions.iter().collect());
+        }
+    }
+    if block.transactions.is_empty() {
+        return None;
+    }
+    let chained = matches!(
+        chainhook.predicate,
+        BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { chained: true, .. }))
+    );
+    let mut hits = vec![];
+    for tx in block.transactions.iter() {
+        let spends_chained_output = chained
+            && tx
+                .metadata
+                .inputs
+                .iter()
+                .any(|input| chained_txids.contains(&input.previous_output.txid.hash));
+        let matched = spends_chained_output
+            || chainhook.evaluate_transaction(tx, fee_rates, descriptor_default_range, ctx);
+        if matched {
+            if chained {
+                chained_txids.insert(tx.transaction_identifier.hash.clone());
+            }
+            hits.push(tx);
+        }
+    }
+    if hits.is_empty() {
+        None
+    } else {
+        Some(hits)
+    }
+}
+
+/// Outcome of the evaluation of a single predicate on a chain event.
+struct BitcoinChainhookEvaluation<'a> {
+    chainhook: &'a BitcoinChainhookInstance,
+    trigger: Option<BitcoinTriggerChainhook<'a>>,
+    /// Blocks evaluated, in the order of the chain event.
+    evaluated_blocks: Vec<&'a BlockIdentifier>,
+    /// Blocks past the expiration block of the predicate, in the order of the chain event.
+    expired_blocks: Vec<&'a BlockIdentifier>,
+}
+
+pub fn evaluate_bitcoin_chainhooks_on_chain_event<'a>(
+    chain_event: &'a BitcoinChainEvent,
+    active_chainhooks: &Vec<&'a BitcoinChainhookInstance>,
+    descriptor_default_range: Option<[u32; 2]>,
+    ctx: &Context,
+) -> (
+    Vec<BitcoinTriggerChainhook<'a>>,
+    BTreeMap<&'a str, &'a BlockIdentifier>,
+    BTreeMap<&'a str, &'a BlockIdentifier>,
+) {
+    let reapplied_transactions = match chain_event {
+        BitcoinChainEvent::ChainUpdatedWithBlocks(_) => HashSet::new(),
+        BitcoinChainEvent::ChainUpdatedWithReorg(event) => event
+            .blocks_to_apply
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .map(|tx| &tx.transaction_identifier)
+            .collect::<HashSet<_>>(),
+    };
+    // the fee rate distribution of each block is computed once, and shared by the predicates
+    let mut block_fee_rates = HashMap::new();
+    let uses_fee_rate_percentiles = active_chainhooks.iter().any(|chainhook| {
+        matches!(
+            chainhook.predicate,
+            BitcoinPredicateType::FeeRatePercentile(_)
+        )
+    });
+    if uses_fee_rate_percentiles {
+        let blocks: Vec<&BitcoinBlockData> = match chain_event {
+            BitcoinChainEvent::ChainUpdatedWithBlocks(event) => event.new_blocks.iter().collect(),
+            BitcoinChainEvent::ChainUpdatedWithReorg(event) => event
+                .blocks_to_rollback
+                .iter()
+                .chain(event.blocks_to_apply.iter())
+                .collect(),
+        };
+        for block in blocks.into_iter() {
+            block_fee_rates.insert(
+                block.block_identifier.hash.as_str(),
+                BlockFeeRates::new(block),
+            );
+        }
+    }
+    // denylist files modified since they were last loaded are reloaded once per chain event
+    for chainhook in active_chainhooks.iter() {
+        if let BitcoinPredicateType::Outputs(OutputPredicate::Denylist(ref denylist)) =
+            chainhook.predicate
+        {
+            if let Err(e) = denylist.reload_if_modified() {
+                ctx.try_log(|logger| {
+                    slog::warn!(logger, "Denylist: unable to load addresses: {}", e)
+                });
+            }
+        }
+    }
+    let evaluate = |chainhook: &&'a BitcoinChainhookInstance| {
+        evaluate_bitcoin_chainhook_on_chain_event(
+            chainhook,
+            chain_event,
+            &reapplied_transactions,
+            &block_fee_rates,
+            descriptor_default_range,
+            ctx,
+        )
+    };
+    #[cfg(feature = "parallel")]
+    let evaluations = {
+        use rayon::prelude::*;
+        active_chainhooks
+            .par_iter()
+            .map(evaluate)
+            .collect::<Vec<_>>()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let evaluations = active_chainhooks.iter().map(evaluate).collect::<Vec<_>>();
+
+    // evaluations are merged in the order of the active chainhooks, so that the outcome is the
+    // same whether they were computed serially or in parallel
+    let mut evaluated_predicates = BTreeMap::new();
     let mut triggered_predicates = vec![];
     let mut expired_predicates = BTreeMap::new();
+    for evaluation in evaluations.into_iter() {
+        let uuid = evaluation.chainhook.uuid.as_str();
+        for block_identifier in evaluation.evaluated_blocks.into_iter() {
+            evaluated_predicates.insert(uuid, block_identifier);
+        }
+        for block_identifier in evaluation.expired_blocks.into_iter() {
+            expired_predicates.insert(uuid, block_identifier);
+        }
+        if let Some(trigger) = evaluation.trigger {
+            triggered_predicates.push(trigger);
+        }
+    }
+    (
+        triggered_predicates,
+        evaluated_predicates,
+        expired_predicates,
+    )
+}
+
+/// Evaluates a single predicate on a chain event. `reapplied_transactions` holds the transactions
+/// of the blocks applied by a reorg, which aren't reported as dropped when rolled back.
+/// `block_fee_rates` holds the fee rates of the blocks of the event, keyed by block hash, when
+/// needed by a `fee_rate_percentile` predicate.
+fn evaluate_bitcoin_chainhook_on_chain_event<'a>(
+    chainhook: &'a BitcoinChainhookInstance,
+    chain_event: &'a BitcoinChainEvent,
+    reapplied_transactions: &HashSet<&'a TransactionIdentifier>,
+    block_fee_rates: &HashMap<&'a str, BlockFeeRates>,
+    descriptor_default_range: Option<[u32; 2]>,
+    ctx: &Context,
+) -> BitcoinChainhookEvaluation<'a> {
+    let mut evaluation = BitcoinChainhookEvaluation {
+        chainhook,
+        trigger: None,
+        evaluated_blocks: vec![],
+        expired_blocks: vec![],
+    };
+    let start_block = chainhook.start_block.unwrap_or(0);
+    let end_block = chainhook.end_block.unwrap_or(u64::MAX);
+    let expiration_block = chainhook.expiration_block();
 
     match chain_event {
         BitcoinChainEvent::ChainUpdatedWithBlocks(event) => {
-            for chainhook in active_chainhooks.iter() {
-                let mut apply = vec![];
-                let rollback = vec![];
-                let end_block = chainhook.end_block.unwrap_or(u64::MAX);
-
-                for block in event.new_blocks.iter() {
-                    evaluated_predicates.insert(chainhook.uuid.as_str(), &block.block_identifier);
-                    if end_block >= block.block_identifier.index {
-                        let mut hits = vec![];
-                        for tx in block.transactions.iter() {
-                            if chainhook.predicate.evaluate_transaction_predicate(&tx, ctx) {
-                                hits.push(tx);
-                            }
-                        }
-                        if hits.len() > 0 {
-                            apply.push((hits, block));
-                        }
-                    } else {
-                        expired_predicates.insert(chainhook.uuid.as_str(), &block.block_identifier);
-                    }
+            let mut apply = vec![];
+            let rollback = vec![];
+            let mut chained_txids = HashSet::new();
+
+            for block in event.new_blocks.iter() {
+                // blocks below `start_block` are ignored, as if they were never evaluated
+                if block.block_identifier.index < start_block {
+                    continue;
                 }
-
-                if !apply.is_empty() {
-                    triggered_predicates.push(BitcoinTriggerChainhook {
+                evaluation.evaluated_blocks.push(&block.block_identifier);
+                if end_block >= block.block_identifier.index {
+                    if !chainhook.includes_block(block.block_identifier.index) {
+                        continue;
+                    }
+                    if let Some(hits) = evaluate_bitcoin_block(
                         chainhook,
-                        apply,
-                        rollback,
-                    })
+                        block,
+                        &mut chained_txids,
+                        block_fee_rates.get(block.block_identifier.hash.as_str()),
+                        descriptor_default_range,
+                        ctx,
+                    ) {
+                        apply.push((hits, block));
+                    }
+                } else if block.block_identifier.index > expiration_block {
+                    evaluation.expired_blocks.push(&block.block_identifier);
                 }
             }
+
+            if !apply.is_empty() {
+                evaluation.trigger = Some(BitcoinTriggerChainhook {
+                    chainhook,
+                    apply,
+                    rollback,
+                    dropped: vec![],
+                })
+            }
         }
         BitcoinChainEvent::ChainUpdatedWithReorg(event) => {
-            for chainhook in active_chainhooks.iter() {
-                let mut apply = vec![];
-                let mut rollback = vec![];
-                let end_block = chainhook.end_block.unwrap_or(u64::MAX);
-
-                for block in event.blocks_to_rollback.iter() {
-                    if end_block >= block.block_identifier.index {
-                        let mut hits = vec![];
-                        for tx in block.transactions.iter() {
-                            if chainhook.predicate.evaluate_transaction_predicate(&tx, ctx) {
-                                hits.push(tx);
-                            }
-                        }
-                        if hits.len() > 0 {
-                            rollback.push((hits, block));
-                        }
-                    } else {
-                        expired_predicates.insert(chainhook.uuid.as_str(), &block.block_identifier);
-                    }
+            let mut apply = vec![];
+            let mut rollback = vec![];
+            let mut rolled_back_chained_txids = HashSet::new();
+            let mut chained_txids = HashSet::new();
+
+            for block in event.blocks_to_rollback.iter() {
+                if block.block_identifier.index < start_block {
+                    continue;
                 }
-                for block in event.blocks_to_apply.iter() {
-                    evaluated_predicates.insert(chainhook.uuid.as_str(), &block.block_identifier);
-                    if end_block >= block.block_identifier.index {
-                        let mut hits = vec![];
-                        for tx in block.transactions.iter() {
-                            if chainhook.predicate.evaluate_transaction_predicate(&tx, ctx) {
-                                hits.push(tx);
-                            }
-                        }
-                        if hits.len() > 0 {
-                            apply.push((hits, block));
-                        }
-                    } else {
-                        expired_predicates.insert(chainhook.uuid.as_str(), &block.block_identifier);
+                if end_block >= block.block_identifier.index {
+                    if !chainhook.includes_block(block.block_identifier.index) {
+                        continue;
                     }
+                    if let Some(hits) = evaluate_bitcoin_block(
+                        chainhook,
+                        block,
+                        &mut rolled_back_chained_txids,
+                        block_fee_rates.get(block.block_identifier.hash.as_str()),
+                        descriptor_default_range,
+                        ctx,
+                    ) {
+                        rollback.push((hits, block));
+                    }
+                } else if block.block_identifier.index > expiration_block {
+                    evaluation.expired_blocks.push(&block.block_identifier);
+                }
+            }
+            for block in event.blocks_to_apply.iter() {
+                if block.block_identifier.index < start_block {
+                    continue;
                 }
-                if !apply.is_empty() || !rollback.is_empty() {
-                    triggered_predicates.push(BitcoinTriggerChainhook {
+                evaluation.evaluated_blocks.push(&block.block_identifier);
+                if end_block >= block.block_identifier.index {
+                    if !chainhook.includes_block(block.block_identifier.index) {
+                        continue;
+                    }
+                    if let Some(hits) = evaluate_bitcoin_block(
                         chainhook,
-                        apply,
-                        rollback,
-                    })
+                        block,
+                        &mut chained_txids,
+                        block_fee_rates.get(block.block_identifier.hash.as_str()),
+                        descriptor_default_range,
+                        ctx,
+                    ) {
+                        apply.push((hits, block));
+                    }
+                } else if block.block_identifier.index > expiration_block {
+                    evaluation.expired_blocks.push(&block.block_identifier);
                 }
             }
+            if apply.is_empty() && chainhook.skip_rollback_only_occurrences {
+                return evaluation;
+            }
+            if !apply.is_empty() || !rollback.is_empty() {
+                let dropped = if chainhook.include_dropped {
+                    rollback
+                        .iter()
+                        .flat_map(|(transactions, _)| transactions.iter())
+                        .map(|tx| &tx.transaction_identifier)
+                        .filter(|txid| !reapplied_transactions.contains(txid))
+                        .collect()
+                } else {
+                    vec![]
+                };
+                evaluation.trigger = Some(BitcoinTriggerChainhook {
+                    chainhook,
+                    apply,
+                    rollback,
+                    dropped,
+                })
+            }
         }
     }
-    (
-        triggered_predicates,
-        evaluated_predicates,
-        expired_predicates,
-    )
+    evaluation
 }
 
 pub fn serialize_bitcoin_payload_to_json<'a>(
     trigger: &BitcoinTriggerChainhook<'a>,
     proofs: &HashMap<&'a TransactionIdentifier, String>,
+    descriptor_default_range: Option<[u32; 2]>,
 ) -> JsonValue {
     let predicate_spec = trigger.chainhook;
-    json!({
-        "apply": trigger.apply.iter().map(|(transactions, block)| {
-            json!({
-                "block_identifier": block.block_identifier,
-                "parent_block_identifier": block.parent_block_identifier,
-                "timestamp": block.timestamp,
-                "transactions": serialize_bitcoin_transactions_to_json(&predicate_spec, &transactions, proofs),
-                "metadata": block.metadata,
-            })
-        }).collect::<Vec<_>>(),
-        "rollback": trigger.rollback.iter().map(|(transactions, block)| {
-            json!({
-                "block_identifier": block.block_identifier,
-                "parent_block_identifier": block.parent_block_identifier,
-                "timestamp": block.timestamp,
-                "transactions": serialize_bitcoin_transactions_to_json(&predicate_spec, &transactions, proofs),
-                "metadata": block.metadata,
-            })
-        }).collect::<Vec<_>>(),
+    let serialize_block = |(transactions, block): &(
+        Vec<&BitcoinTransactionData>,
+        &BitcoinBlockData,
+    )| {
+        let mut serialized_block = json!({
+            "block_identifier": block.block_identifier,
+            "parent_block_identifier": block.parent_block_identifier,
+            "timestamp": block.timestamp,
+            "transactions": serialize_bitcoin_transactions_to_json(&predicate_spec, &transactions, proofs, descriptor_default_range),
+            "metadata": block.metadata,
+        });
+        if let Some(max_transactions) = predicate_spec.max_transactions_per_block {
+            serialized_block["truncated"] = json!(transactions.len() as u64 > max_transactions);
+            serialized_block["total_matches"] = json!(transactions.len());
+        }
+        serialized_block
+    };
+    let serialize_transactions =
+        |(transactions, block): &(Vec<&BitcoinTransactionData>, &BitcoinBlockData)| {
+            let mut serialized_transactions = serialize_bitcoin_transactions_to_json(
+                &predicate_spec,
+                &transactions,
+                proofs,
+                descriptor_default_range,
+            );
+            for transaction in serialized_transactions.iter_mut() {
+                transaction["block_identifier"] = json!(block.block_identifier);
+            }
+            serialized_transactions
+        };
+    let block_identifiers = |blocks: &Vec<(Vec<&BitcoinTransactionData>, &BitcoinBlockData)>| {
+        blocks
+            .iter()
+            .map(|(_, block)| json!(block.block_identifier))
+            .collect::<Vec<_>>()
+    };
+    let (apply, rollback) = match predicate_spec.payload_shape {
+        PayloadShape::Block => (
+            trigger
+                .apply
+                .iter()
+                .map(serialize_block)
+                .collect::<Vec<_>>(),
+            trigger
+                .rollback
+                .iter()
+                .map(serialize_block)
+                .collect::<Vec<_>>(),
+        ),
+        PayloadShape::Transaction => (
+            trigger
+                .apply
+                .iter()
+                .flat_map(serialize_transactions)
+                .collect::<Vec<_>>(),
+            trigger
+                .rollback
+                .iter()
+                .flat_map(serialize_transactions)
+                .collect::<Vec<_>>(),
+        ),
+    };
+    let mut payload = json!({
+        "apply": apply,
+        "rollback": rollback,
         "chainhook": {
             "uuid": trigger.chainhook.uuid,
+            "name": trigger.chainhook.name,
             "predicate": trigger.chainhook.predicate,
             "is_streaming_blocks": trigger.chainhook.enabled
         }
-    })
+    });
+    if predicate_spec.include_dropped {
+        payload["dropped"] = json!(trigger.dropped);
+    }
+    if let PayloadShape::Transaction = predicate_spec.payload_shape {
+        payload["apply_blocks"] = json!(block_identifiers(&trigger.apply));
+        payload["rollback_blocks"] = json!(block_identifiers(&trigger.rollback));
+    }
+    payload
 }
 
 pub fn serialize_bitcoin_transactions_to_json<'a>(
     predicate_spec: &BitcoinChainhookInstance,
     transactions: &Vec<&BitcoinTransactionData>,
     proofs: &HashMap<&'a TransactionIdentifier, String>,
+    descriptor_default_range: Option<[u32; 2]>,
 ) -> Vec<JsonValue> {
+    let max_transactions = predicate_spec
+        .max_transactions_per_block
+        .map(|max| max as usize)
+        .unwrap_or(usize::MAX);
     transactions
         .into_iter()
+        .take(max_transactions)
         .map(|transaction| {
             let mut metadata = serde_json::Map::new();
 
-            metadata.insert("fee".into(), json!(transaction.metadata.fee));
+            metadata.insert(
+                "fee".into(),
+                predicate_spec.amount_format.encode(transaction.metadata.fee),
+            );
             metadata.insert("index".into(), json!(transaction.metadata.index));
+            if predicate_spec.include_size {
+                metadata.insert("weight".into(), json!(transaction.metadata.weight));
+                metadata.insert("vsize".into(), json!(transaction.metadata.vsize));
+                metadata.insert(
+                    "witness_discount".into(),
+                    json!(get_witness_discount(&transaction.metadata)),
+                );
+            }
+            if predicate_spec.include_version {
+                metadata.insert("version".into(), json!(transaction.metadata.version));
+            }
 
             let inputs = if predicate_spec.include_inputs {
                 transaction
@@ -772,7 +2720,7 @@ pub fn serialize_bitcoin_transactions_to_json<'a>(
                             "previous_output": {
                                 "txin": input.previous_output.txid.hash.to_string(),
                                 "vout": input.previous_output.vout,
-                                "value": input.previous_output.value,
+                                "value": predicate_spec.amount_format.encode(input.previous_output.value),
                                 "block_height": input.previous_output.block_height,
                             },
                             "script_sig": input.script_sig,
@@ -787,12 +2735,36 @@ pub fn serialize_bitcoin_transactions_to_json<'a>(
             metadata.insert("inputs".into(), json!(inputs));
 
             let outputs = if predicate_spec.include_outputs {
-                transaction.metadata.outputs.clone()
+                transaction
+                    .metadata
+                    .outputs
+                    .iter()
+                    .map(|output| {
+                        json!({
+                            "value": predicate_spec.amount_format.encode(output.value),
+                            "script_pubkey": output.script_pubkey,
+                        })
+                    })
+                    .collect::<Vec<_>>()
             } else {
                 vec![]
             };
             metadata.insert("outputs".into(), json!(outputs));
 
+            if let BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(descriptor)) =
+                &predicate_spec.predicate
+            {
+                if descriptor.collect_all_matches {
+                    let matching_indices = descriptor
+                        .get_matching_output_indices(
+                            &transaction.metadata.outputs,
+                            descriptor_default_range,
+                        )
+                        .unwrap_or_default();
+                    metadata.insert("matched_outputs".into(), json!(matching_indices));
+                }
+            }
+
             let stacks_ops = if transaction.metadata.stacks_operations.is_empty() {
                 vec![]
             } else {
@@ -808,13 +2780,32 @@ pub fn serialize_bitcoin_transactions_to_json<'a>(
             metadata.insert("ordinal_operations".into(), json!(ordinals_ops));
 
             if let Some(ref brc20) = transaction.metadata.brc20_operation {
-                metadata.insert("brc20_operation".into(), json!(brc20));
+                if predicate_spec.includes_brc20_operation() {
+                    metadata.insert("brc20_operation".into(), json!(brc20));
+                }
+                if predicate_spec.include_brc20_balance_deltas {
+                    metadata.insert(
+                        "brc20_balance_deltas".into(),
+                        json!(get_brc20_balance_deltas(brc20)),
+                    );
+                }
             }
 
+            let proof = proofs.get(&transaction.transaction_identifier);
             metadata.insert(
                 "proof".into(),
-                json!(proofs.get(&transaction.transaction_identifier)),
+                json!(proof.and_then(
+                    |proof| encode_bitcoin_proof(proof, &predicate_spec.proof_format).ok()
+                )),
             );
+            if let Some(proof_kind) = proof.and_then(|proof| get_bitcoin_proof_kind(proof).ok()) {
+                metadata.insert("proof_kind".into(), json!(proof_kind));
+            }
+            if predicate_spec.omit_empty_arrays {
+                metadata.retain(|_, value| {
+                    !matches!(value, JsonValue::Array(items) if items.is_empty())
+                });
+            }
             json!({
                 "transaction_identifier": transaction.transaction_identifier,
                 "operations": transaction.operations,
@@ -824,42 +2815,554 @@ pub fn serialize_bitcoin_transactions_to_json<'a>(
         .collect::<Vec<_>>()
 }
 
+/// Discount granted to the witness data of a transaction, which is counted for 1 weight unit per
+/// byte instead of 4 for the rest of the transaction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WitnessDiscount {
+    /// `true` when the transaction carries witness data, and is serialized with the SegWit marker.
+    pub segwit: bool,
+    /// Size in bytes of the witness data, including the SegWit marker and flag.
+    pub witness_size: u64,
+    /// Size in bytes of the transaction without its witness data.
+    pub stripped_size: u64,
+    /// Weight units saved by the discount, compared to counting the witness data as the rest of
+    /// the transaction.
+    pub discount_weight: u64,
+}
+
+/// Size of the compact size integer encoding `value`.
+fn compact_size_len(value: u64) -> u64 {
+    match value {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffffffff => 5,
+        _ => 9,
+    }
+}
+
+/// Computes the witness discount of a transaction from the witness of its inputs and its weight.
+/// Legacy inputs of a SegWit transaction still carry an empty witness, counted as 1 byte.
+pub fn get_witness_discount(metadata: &BitcoinTransactionMetadata) -> WitnessDiscount {
+    let segwit = metadata
+        .inputs
+        .iter()
+        .any(|input| !input.witness.is_empty());
+    let witness_size = if segwit {
+        // marker and flag
+        let mut size = 2;
+        for input in metadata.inputs.iter() {
+            size += compact_size_len(input.witness.len() as u64);
+            for item in input.witness.iter() {
+                let item_size = (item.strip_prefix("0x").unwrap_or(item).len() / 2) as u64;
+                size += compact_size_len(item_size) + item_size;
+            }
+        }
+        size
+    } else {
+        0
+    };
+    WitnessDiscount {
+        segwit,
+        witness_size,
+        stripped_size: metadata.weight.saturating_sub(witness_size) / 4,
+        discount_weight: 3 * witness_size,
+    }
+}
+
+/// Change of the BRC-20 balances of an address resulting from an operation. Amounts are
+/// decimal strings, negative when the balance decreases.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Brc20BalanceDelta {
+    pub tick: String,
+    pub address: String,
+    pub available_balance: String,
+    pub transferrable_balance: String,
+    pub overall_balance: String,
+}
+
+/// Computes the balance changes resulting from a BRC-20 operation:
+/// - `deploy` doesn't change any balance
+/// - `mint` credits the available balance of the minter
+/// - `transfer` moves the inscribed amount from the available to the transferrable balance
+/// - `transfer_send` debits the transferrable balance of the sender and credits the available
+///   balance of the receiver
+pub fn get_brc20_balance_deltas(operation: &Brc20Operation) -> Vec<Brc20BalanceDelta> {
+    let negate = |amt: &str| format!("-{}", amt);
+    match operation {
+        Brc20Operation::Deploy(_) => vec![],
+        Brc20Operation::Mint(data) => vec![Brc20BalanceDelta {
+            tick: data.tick.clone(),
+            address: data.address.clone(),
+            available_balance: data.amt.clone(),
+            transferrable_balance: "0".into(),
+            overall_balance: data.amt.clone(),
+        }],
+        Brc20Operation::Transfer(data) => vec![Brc20BalanceDelta {
+            tick: data.tick.clone(),
+            address: data.address.clone(),
+            available_balance: negate(&data.amt),
+            transferrable_balance: data.amt.clone(),
+            overall_balance: "0".into(),
+        }],
+        Brc20Operation::TransferSend(data) => vec![
+            Brc20BalanceDelta {
+                tick: data.tick.clone(),
+                address: data.sender_address.clone(),
+                available_balance: "0".into(),
+                transferrable_balance: negate(&data.amt),
+                overall_balance: negate(&data.amt),
+            },
+            Brc20BalanceDelta {
+                tick: data.tick.clone(),
+                address: data.receiver_address.clone(),
+                available_balance: data.amt.clone(),
+                transferrable_balance: "0".into(),
+                overall_balance: data.amt.clone(),
+            },
+        ],
+    }
+}
+
+/// Builds a merkle proof of `transaction_identifiers` out of the transactions of `block`, hex
+/// encoded as the partial merkle tree of the merkle block returned by `gettxoutproof`. The block
+/// header is left out, as [BitcoinBlockData] does not carry it: the proof is verified against the
+/// merkle root of the block.
+///
+/// The tree matches every transaction of `transaction_identifiers`, so that it is built once for
+/// all the transactions of a block.
+pub fn compute_proof(
+    block: &BitcoinBlockData,
+    transaction_identifiers: &[&TransactionIdentifier],
+) -> Result<String, String> {
+    let transaction_identifiers = transaction_identifiers
+        .iter()
+        .copied()
+        .collect::<HashSet<_>>();
+    let mut txids = vec![];
+    let mut matches = vec![];
+    for transaction in block.transactions.iter() {
+        let txid = Txid::from_str(transaction.transaction_identifier.get_hash_bytes_str())
+            .map_err(|e| {
+                format!(
+                    "unable to decode txid {}: {}",
+                    transaction.transaction_identifier.hash,
+                    e.to_string()
+                )
+            })?;
+        txids.push(txid);
+        matches.push(transaction_identifiers.contains(&transaction.transaction_identifier));
+    }
+    if matches.iter().filter(|is_match| **is_match).count() < transaction_identifiers.len() {
+        let found = block
+            .transactions
+            .iter()
+            .map(|transaction| &transaction.transaction_identifier)
+            .collect::<HashSet<_>>();
+        let missing = transaction_identifiers
+            .iter()
+            .filter(|transaction_identifier| !found.contains(*transaction_identifier))
+            .map(|transaction_identifier| transaction_identifier.hash.as_str())
+            .collect::<Vec<_>>();
+        return Err(format!(
+            "transactions {} not found in block {}",
+            missing.join(", "),
+            block.block_identifier.hash
+        ));
+    }
+    let bytes = serialize(&PartialMerkleTree::from_txids(&txids, &matches));
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+/// Tells whether a hex proof is a merkle block, as returned by `gettxoutproof`, or a bare partial
+/// merkle tree, as built by [compute_proof].
+pub fn get_bitcoin_proof_kind(proof: &str) -> Result<BitcoinProofKind, String> {
+    let bytes = hex::decode(proof.trim_start_matches("0x"))
+        .map_err(|e| format!("unable to decode proof: {}", e.to_string()))?;
+    match extract_proof_matches(&bytes, &mut vec![], &mut vec![])? {
+        (Some(_), _) => Ok(BitcoinProofKind::MerkleBlock),
+        (None, _) => Ok(BitcoinProofKind::PartialMerkleTree),
+    }
+}
+
+/// Re-encodes a hex proof, as returned by `gettxoutproof` or [compute_proof], in the requested
+/// format.
+pub fn encode_bitcoin_proof(proof: &str, format: &ProofFormat) -> Result<JsonValue, String> {
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::engine::Engine as _;
+
+    let bytes = hex::decode(proof.trim_start_matches("0x"))
+        .map_err(|e| format!("unable to decode proof: {}", e.to_string()))?;
+    match format {
+        ProofFormat::Hex => Ok(json!(format!("0x{}", hex::encode(&bytes)))),
+        ProofFormat::Base64 => Ok(json!(BASE64.encode(&bytes))),
+        ProofFormat::Structured => {
+            let mut positions = vec![];
+            let (header, partial_merkle_tree) =
+                extract_proof_matches(&bytes, &mut vec![], &mut positions)?;
+            let flags = partial_merkle_tree
+                .bits()
+                .chunks(8)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |byte, (i, bit)| byte | ((*bit as u8) << i))
+                })
+                .collect::<Vec<u8>>();
+            let structured_proof = BitcoinStructuredProof {
+                header: header.map(|header| format!("0x{}", hex::encode(serialize(&header)))),
+                number_of_transactions: partial_merkle_tree.num_transactions(),
+                positions,
+                hashes: partial_merkle_tree
+                    .hashes()
+                    .iter()
+                    .map(|hash| hash.to_string())
+                    .collect(),
+                flags: format!("0x{}", hex::encode(flags)),
+            };
+            Ok(json!(structured_proof))
+        }
+    }
+}
+
+/// Converts a proof encoded with [encode_bitcoin_proof] back to its raw bytes, checking that
+/// they still describe a valid merkle block, or partial merkle tree.
+pub fn decode_bitcoin_proof(proof: &JsonValue, format: &ProofFormat) -> Result<Vec<u8>, String> {
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::engine::Engine as _;
+
+    let bytes = match format {
+        ProofFormat::Hex => {
+            let proof = proof
+                .as_str()
+                .ok_or(format!("expected hex proof to be a string"))?;
+            hex::decode(proof.trim_start_matches("0x"))
+                .map_err(|e| format!("unable to decode proof: {}", e.to_string()))?
+        }
+        ProofFormat::Base64 => {
+            let proof = proof
+                .as_str()
+                .ok_or(format!("expected base64 proof to be a string"))?;
+            BASE64
+                .decode(proof)
+                .map_err(|e| format!("unable to decode proof: {}", e.to_string()))?
+        }
+        ProofFormat::Structured => {
+            let structured_proof: BitcoinStructuredProof = serde_json::from_value(proof.clone())
+                .map_err(|e| format!("unable to decode proof: {}", e.to_string()))?;
+            let mut bytes = match structured_proof.header {
+                Some(ref header) => hex::decode(header.trim_start_matches("0x"))
+                    .map_err(|e| format!("unable to decode proof header: {}", e.to_string()))?,
+                None => vec![],
+            };
+            let mut hashes = vec![];
+            for hash in structured_proof.hashes.iter() {
+                let hash = TxMerkleNode::from_str(hash)
+                    .map_err(|e| format!("unable to decode proof hash: {}", e.to_string()))?;
+                hashes.push(hash);
+            }
+            let flags = hex::decode(structured_proof.flags.trim_start_matches("0x"))
+                .map_err(|e| format!("unable to decode proof flags: {}", e.to_string()))?;
+            bytes.append(&mut serialize(&structured_proof.number_of_transactions));
+            bytes.append(&mut serialize(&hashes));
+            bytes.append(&mut serialize(&flags));
+            bytes
+        }
+    };
+    extract_proof_matches(&bytes, &mut vec![], &mut vec![])?;
+    Ok(bytes)
+}
+
+/// Decodes a proof, either a merkle block or a bare partial merkle tree, and extracts the
+/// transactions it matches. The merkle root of a merkle block must match its header.
+fn extract_proof_matches(
+    bytes: &[u8],
+    matches: &mut Vec<Txid>,
+    positions: &mut Vec<u32>,
+) -> Result<(Option<Header>, PartialMerkleTree), String> {
+    let (header, partial_merkle_tree) = match deserialize::<MerkleBlock>(bytes) {
+        Ok(merkle_block) => (Some(merkle_block.header), merkle_block.txn),
+        Err(_) => (
+            None,
+            deserialize::<PartialMerkleTree>(bytes)
+                .map_err(|e| format!("unable to decode proof: {}", e.to_string()))?,
+        ),
+    };
+    let merkle_root = partial_merkle_tree
+        .extract_matches(matches, positions)
+        .map_err(|e| format!("invalid proof: {}", e.to_string()))?;
+    if let Some(ref header) = header {
+        if header.merkle_root != merkle_root {
+            return Err(format!("invalid proof: merkle root mismatch"));
+        }
+    }
+    Ok((header, partial_merkle_tree))
+}
+
+/// Completes `proofs` with a proof computed out of the applied blocks for every transaction of
+/// `trigger` that is missing one, e.g. when the bitcoind node could not provide it. The
+/// transactions of a block missing a proof share a single proof matching all of them.
+fn complete_proofs<'a>(
+    trigger: &BitcoinTriggerChainhook<'a>,
+    proofs: &HashMap<&'a TransactionIdentifier, String>,
+) -> HashMap<&'a TransactionIdentifier, String> {
+    let mut proofs = proofs.clone();
+    for (transactions, block) in trigger.apply.iter() {
+        let missing_proofs = transactions
+            .iter()
+            .map(|transaction| &transaction.transaction_identifier)
+            .filter(|transaction_identifier| !proofs.contains_key(transaction_identifier))
+            .collect::<Vec<_>>();
+        if missing_proofs.is_empty() {
+            continue;
+        }
+        if let Ok(proof) = compute_proof(block, &missing_proofs) {
+            for transaction_identifier in missing_proofs.into_iter() {
+                proofs.insert(transaction_identifier, proof.clone());
+            }
+        }
+    }
+    proofs
+}
+
+/// Builds the occurrence of `trigger`. `http_post` occurrences are sent with `http_client` when provided,
+/// or with a client built with the default delivery settings otherwise, and signed with
+/// `signing_secrets`.
 pub fn handle_bitcoin_hook_action<'a>(
     trigger: BitcoinTriggerChainhook<'a>,
     proofs: &HashMap<&'a TransactionIdentifier, String>,
+    descriptor_default_range: Option<[u32; 2]>,
+    http_client: Option<&Client>,
+    signing_secrets: &SigningSecrets,
 ) -> Result<BitcoinChainhookOccurrence, String> {
+    let computed_proofs;
+    let proofs = if trigger.chainhook.include_proof {
+        computed_proofs = complete_proofs(&trigger, proofs);
+        &computed_proofs
+    } else {
+        proofs
+    };
     match &trigger.chainhook.action {
         HookAction::HttpPost(http) => {
-            let client = Client::builder()
-                .build()
-                .map_err(|e| format!("unable to build http client: {}", e.to_string()))?;
-            let host = format!("{}", http.url);
-            let method = Method::POST;
-            let body = serde_json::to_vec(&serialize_bitcoin_payload_to_json(&trigger, proofs))
-                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
-            let request = client
-                .request(method, &host)
-                .header("Content-Type", "application/json")
-                .header("Authorization", http.authorization_header.clone())
-                .body(body);
+            let client = match http_client {
+                Some(client) => client.clone(),
+                None => build_http_delivery_client(&HttpDeliveryConfig::default())?,
+            };
+            let body = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            let request = http.build_request(&client, signing_secrets, body)?;
 
             let data = BitcoinChainhookOccurrencePayload::from_trigger(trigger);
             Ok(BitcoinChainhookOccurrence::Http(request, data))
         }
         HookAction::FileAppend(disk) => {
-            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(&trigger, proofs))
-                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
             Ok(BitcoinChainhookOccurrence::File(
                 disk.path.to_string(),
                 bytes,
             ))
         }
+        HookAction::UnixSocket(socket) => {
+            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::UnixSocket(
+                socket.path.to_string(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "websocket")]
+        HookAction::WebSocket(websocket) => {
+            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::WebSocket(
+                websocket.url.to_string(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "parquet")]
+        HookAction::Parquet(parquet) => {
+            let rows = BitcoinParquetRow::from_trigger(&trigger)?;
+            Ok(BitcoinChainhookOccurrence::Parquet(parquet.clone(), rows))
+        }
+        #[cfg(feature = "kafka")]
+        HookAction::Kafka(kafka) => {
+            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            let key = kafka.render_key(&trigger.chainhook.uuid, &trigger.chainhook.name);
+            Ok(BitcoinChainhookOccurrence::Kafka(kafka.clone(), key, bytes))
+        }
+        #[cfg(feature = "grpc")]
+        HookAction::Grpc(grpc) => {
+            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::Grpc(
+                grpc.clone(),
+                trigger.chainhook.uuid.clone(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "sqs")]
+        HookAction::Sqs(sqs) => {
+            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::Sqs(
+                sqs.clone(),
+                trigger.chainhook.uuid.clone(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "s3")]
+        HookAction::S3Append(s3) => {
+            let bytes = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+                &trigger,
+                proofs,
+                descriptor_default_range,
+            ))
+            .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            let block_identifier = trigger
+                .apply
+                .first()
+                .or(trigger.rollback.first())
+                .map(|(_, block)| block.block_identifier.clone())
+                .unwrap_or_default();
+            let key = s3_export::build_s3_key(
+                s3,
+                &trigger.chainhook.uuid,
+                &block_identifier,
+                s3_export::get_timestamp_ms(),
+            );
+            Ok(BitcoinChainhookOccurrence::S3Append(s3.clone(), key, bytes))
+        }
         HookAction::Noop => Ok(BitcoinChainhookOccurrence::Data(
             BitcoinChainhookOccurrencePayload::from_trigger(trigger),
         )),
     }
 }
 
+/// Builds the delivery of a digest, the counterpart of [handle_bitcoin_hook_action] for the
+/// predicates aggregating their occurrences.
+pub fn handle_bitcoin_digest_action(
+    chainhook: &BitcoinChainhookInstance,
+    digest: BitcoinOccurrenceDigest,
+    http_client: Option<&Client>,
+    signing_secrets: &SigningSecrets,
+) -> Result<BitcoinChainhookOccurrence, String> {
+    let data = BitcoinChainhookOccurrencePayload::from_digest(chainhook, digest);
+    match &chainhook.action {
+        HookAction::HttpPost(http) => {
+            let client = match http_client {
+                Some(client) => client.clone(),
+                None => build_http_delivery_client(&HttpDeliveryConfig::default())?,
+            };
+            let body = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            let request = http.build_request(&client, signing_secrets, body)?;
+            Ok(BitcoinChainhookOccurrence::Http(request, data))
+        }
+        HookAction::FileAppend(disk) => {
+            let bytes = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::File(
+                disk.path.to_string(),
+                bytes,
+            ))
+        }
+        HookAction::UnixSocket(socket) => {
+            let bytes = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::UnixSocket(
+                socket.path.to_string(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "websocket")]
+        HookAction::WebSocket(websocket) => {
+            let bytes = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::WebSocket(
+                websocket.url.to_string(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "parquet")]
+        HookAction::Parquet(_) => Err("digests can't be exported to parquet".into()),
+        #[cfg(feature = "kafka")]
+        HookAction::Kafka(kafka) => {
+            let bytes = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            let key = kafka.render_key(&chainhook.uuid, &chainhook.name);
+            Ok(BitcoinChainhookOccurrence::Kafka(kafka.clone(), key, bytes))
+        }
+        #[cfg(feature = "grpc")]
+        HookAction::Grpc(grpc) => {
+            let bytes = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::Grpc(
+                grpc.clone(),
+                chainhook.uuid.clone(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "sqs")]
+        HookAction::Sqs(sqs) => {
+            let bytes = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(BitcoinChainhookOccurrence::Sqs(
+                sqs.clone(),
+                chainhook.uuid.clone(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "s3")]
+        HookAction::S3Append(s3) => {
+            let bytes = serde_json::to_vec(&data)
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            let block_identifier = data
+                .digest
+                .as_ref()
+                .map(|digest| digest.end_block.clone())
+                .unwrap_or_default();
+            let key = s3_export::build_s3_key(
+                s3,
+                &chainhook.uuid,
+                &block_identifier,
+                s3_export::get_timestamp_ms(),
+            );
+            Ok(BitcoinChainhookOccurrence::S3Append(s3.clone(), key, bytes))
+        }
+        HookAction::Noop => Ok(BitcoinChainhookOccurrence::Data(data)),
+    }
+}
+
 struct OpReturn(String);
 impl OpReturn {
     fn from_string(hex: &String) -> Result<String, String> {
@@ -867,22 +3370,222 @@ impl OpReturn {
         let hex = hex.strip_prefix("0x").unwrap_or(hex);
 
         // Parse the hex bytes.
-        let bytes = Vec::<u8>::from_hex(hex).unwrap();
-        match bytes.as_slice() {
-            // An OpReturn is composed by:
-            // - OP_RETURN 0x6a
-            // - Data length <N> (ignored)
-            // - The data
-            [0x6a, _, rest @ ..] => Ok(hex::encode(rest)),
-            _ => Err(String::from("not an OP_RETURN")),
+        let bytes = Vec::<u8>::from_hex(hex)
+            .map_err(|e| format!("invalid script_pubkey {}: {}", hex, e.to_string()))?;
+        // An OpReturn is composed by:
+        // - OP_RETURN 0x6a
+        // - One or more data pushes, either direct (0x01-0x4b) or using OP_PUSHDATA1 (0x4c),
+        //   OP_PUSHDATA2 (0x4d) or OP_PUSHDATA4 (0x4e)
+        // Other opcodes, such as the OP_13 tagging Runestones, are skipped. Scripts that can't be
+        // read as a sequence of instructions (e.g. truncated pushes) carry their raw bytes.
+        let [0x6a, pushes @ ..] = bytes.as_slice() else {
+            return Err(String::from("not an OP_RETURN"));
+        };
+        let mut data = vec![];
+        for instruction in Script::from_bytes(pushes).instructions() {
+            match instruction {
+                Ok(Instruction::PushBytes(push)) => data.extend_from_slice(push.as_bytes()),
+                Ok(Instruction::Op(_)) => {}
+                Err(_) => return Ok(hex::encode(pushes)),
+            }
+        }
+        Ok(hex::encode(data))
+    }
+}
+
+/// Extracts the opcode following the Stacks magic bytes of `network` from an OP_RETURN output.
+/// Opcodes not modeled by [StacksOpcodes] are returned as their raw byte.
+fn get_stacks_opcode(
+    script_pubkey: &String,
+    network: &BitcoinNetwork,
+) -> Option<Result<StacksOpcodes, u8>> {
+    let data = Vec::<u8>::from_hex(&OpReturn::from_string(script_pubkey).ok()?).ok()?;
+    match data.as_slice() {
+        [magic_0, magic_1, opcode, ..]
+            if get_stacks_canonical_magic_bytes(network) == [*magic_0, *magic_1] =>
+        {
+            Some(StacksOpcodes::try_from(*opcode).map_err(|_| *opcode))
+        }
+        _ => None,
+    }
+}
+
+/// Extracts the public key of a P2PK output, whose script is `<pubkey> OP_CHECKSIG`.
+fn get_p2pk_pubkey(script_pubkey: &str) -> Option<Vec<u8>> {
+    let hex = script_pubkey.strip_prefix("0x").unwrap_or(script_pubkey);
+    let bytes = Vec::<u8>::from_hex(hex).ok()?;
+    match bytes.as_slice() {
+        // compressed (33 bytes) or uncompressed (65 bytes) public key, followed by OP_CHECKSIG 0xac
+        [len @ (0x21 | 0x41), pubkey @ .., 0xac] if pubkey.len() == *len as usize => {
+            Some(pubkey.to_vec())
+        }
+        _ => None,
+    }
+}
+
+/// Reconstructs the hex encoded script_pubkey of the output spent by `input` from its unlocking
+/// data, since inputs don't carry the output they spend. Returns `None` when the spent script can't
+/// be derived, e.g. for taproot key path spends or bare scripts.
+fn get_input_script_pubkey(input: &TxIn) -> Option<String> {
+    let decode = |hex: &String| Vec::<u8>::from_hex(hex.strip_prefix("0x").unwrap_or(hex)).ok();
+    let mut pushes = vec![];
+    for instruction in Script::from_bytes(&decode(&input.script_sig)?).instructions() {
+        match instruction.ok()? {
+            Instruction::PushBytes(push) => pushes.push(push.as_bytes().to_vec()),
+            Instruction::Op(_) => return None,
+        }
+    }
+    let witness = input
+        .witness
+        .iter()
+        .map(decode)
+        .collect::<Option<Vec<_>>>()?;
+    let script_pubkey = if witness.is_empty() {
+        match pushes.as_slice() {
+            // p2pkh: <signature> <pubkey>
+            [_, pubkey] if PublicKey::from_slice(pubkey).is_ok() => {
+                ScriptBuf::new_p2pkh(&PublicKey::from_slice(pubkey).ok()?.pubkey_hash())
+            }
+            // p2sh: <unlocking data> <redeem script>
+            [.., redeem_script] => {
+                ScriptBuf::new_p2sh(&Script::from_bytes(redeem_script).script_hash())
+            }
+            [] => return None,
+        }
+    } else {
+        let witness_program = match witness.as_slice() {
+            // p2wpkh: <signature> <compressed pubkey>
+            [_, pubkey] if pubkey.len() == 33 => {
+                ScriptBuf::new_v0_p2wpkh(&PublicKey::from_slice(pubkey).ok()?.wpubkey_hash()?)
+            }
+            // p2wsh: <unlocking data> <witness script>
+            [_, .., witness_script] => {
+                ScriptBuf::new_v0_p2wsh(&Script::from_bytes(witness_script).wscript_hash())
+            }
+            _ => return None,
+        };
+        match pushes.as_slice() {
+            [] => witness_program,
+            // nested segwit, the script_sig pushing the witness program as redeem script
+            [redeem_script] if redeem_script.as_slice() == witness_program.as_bytes() => {
+                ScriptBuf::new_p2sh(&witness_program.script_hash())
+            }
+            _ => return None,
         }
+    };
+    Some(hex::encode(script_pubkey.as_bytes()))
+}
+
+/// Returns the lowercase hex representation of a `MatchingRule` pattern: patterns prefixed with
+/// `0x` are hex strings, others are treated as ASCII.
+fn encoded_pattern(pattern: &str) -> String {
+    match pattern.strip_prefix("0x") {
+        Some(hex) => hex.to_lowercase(),
+        None => hex::encode(pattern),
+    }
+}
+
+/// Matches a lowercase hex encoded pattern against the lowercase hex encoded data found at byte
+/// `offset`. Offsets beyond the end of the data don't match.
+fn matches_at_offset(hex: &str, offset: u64, encoded_pattern: &str) -> bool {
+    let Some(start) = usize::try_from(offset).ok().and_then(|o| o.checked_mul(2)) else {
+        return false;
+    };
+    match hex.get(start..) {
+        Some(data) => data.starts_with(encoded_pattern),
+        None => false,
+    }
+}
+
+/// Matches a regex against lowercase hex encoded data, without `0x` prefix, and against the ASCII
+/// decoding of the data. The data matches if any of the two representations does.
+fn regex_matches(regex: &MatchingRegex, hex: &str) -> bool {
+    if regex.is_match(hex) {
+        return true;
+    }
+    match Vec::<u8>::from_hex(hex) {
+        Ok(bytes) => regex.is_match(&String::from_utf8_lossy(&bytes)),
+        Err(_) => false,
     }
 }
 
 impl BitcoinPredicateType {
+    /// Returns the address targeted by `p2pkh`, `p2sh`, `p2wpkh`, `p2wsh` and `consolidation`
+    /// predicates.
+    fn get_target_address(&self) -> Option<&str> {
+        match self {
+            BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(ExactMatchingRule::Equals(
+                encoded_address,
+            )))
+            | BitcoinPredicateType::Outputs(OutputPredicate::P2sh(ExactMatchingRule::Equals(
+                encoded_address,
+            )))
+            | BitcoinPredicateType::Outputs(OutputPredicate::P2wpkh(ExactMatchingRule::Equals(
+                encoded_address,
+            )))
+            | BitcoinPredicateType::Outputs(OutputPredicate::P2wsh(ExactMatchingRule::Equals(
+                encoded_address,
+            ))) => Some(encoded_address),
+            BitcoinPredicateType::Inputs(InputPredicate::Consolidation(predicate)) => {
+                Some(&predicate.address)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_target_address(&self) -> Result<Option<ParsedOutputAddress>, String> {
+        let Some(encoded_address) = self.get_target_address() else {
+            return Ok(None);
+        };
+        let address = parse_output_address(encoded_address)?;
+        if matches!(
+            self,
+            BitcoinPredicateType::Outputs(OutputPredicate::P2wpkh(_))
+                | BitcoinPredicateType::Outputs(OutputPredicate::P2wsh(_))
+        ) && !address.is_witness_program
+        {
+            return Err(format!(
+                "address {} is not a segwit address",
+                encoded_address
+            ));
+        }
+        Ok(Some(address))
+    }
+
+    /// Evaluates a predicate targeting an address against `tx`, given the parsed address.
+    fn evaluate_target_address(
+        &self,
+        tx: &BitcoinTransactionData,
+        address: &ParsedOutputAddress,
+    ) -> bool {
+        if let BitcoinPredicateType::Inputs(InputPredicate::Consolidation(predicate)) = self {
+            let spent_from_address = tx
+                .metadata
+                .inputs
+                .iter()
+                .filter(|input| {
+                    get_input_script_pubkey(input).as_deref()
+                        == Some(address.script_pubkey.as_str())
+                })
+                .count();
+            return spent_from_address as u64 > predicate.more_than;
+        }
+        for output in tx.metadata.outputs.iter() {
+            // skip malformed script_pubkeys, too short to hold a `0x` prefix
+            let Some(script_pubkey) = output.script_pubkey.get(2..) else {
+                continue;
+            };
+            if script_pubkey == address.script_pubkey {
+                return true;
+            }
+        }
+        false
+    }
+
     pub fn evaluate_transaction_predicate(
         &self,
         tx: &BitcoinTransactionData,
+        descriptor_default_range: Option<[u32; 2]>,
         ctx: &Context,
     ) -> bool {
         // TODO(lgalabru): follow-up on this implementation
@@ -891,6 +3594,9 @@ impl BitcoinPredicateType {
             BitcoinPredicateType::Txid(ExactMatchingRule::Equals(txid)) => {
                 tx.transaction_identifier.hash.eq(txid)
             }
+            // no output predicate can match a transaction without outputs: bail out before
+            // deriving descriptors or loading addresses
+            BitcoinPredicateType::Outputs(_) if tx.metadata.outputs.is_empty() => false,
             BitcoinPredicateType::Outputs(OutputPredicate::OpReturn(rule)) => {
                 for output in tx.metadata.outputs.iter() {
                     // opret contains the op_return data section prefixed with `0x`.
@@ -899,24 +3605,6 @@ impl BitcoinPredicateType {
                         Err(_) => continue,
                     };
 
-                    // encoded_pattern takes a predicate pattern and return its lowercase hex
-                    // representation.
-                    fn encoded_pattern(pattern: &str) -> String {
-                        // If the pattern starts with 0x, return it in lowercase and without the 0x
-                        // prefix.
-                        if pattern.starts_with("0x") {
-                            return pattern
-                                .strip_prefix("0x")
-                                .unwrap()
-                                .to_lowercase()
-                                .to_string();
-                        }
-
-                        // In this case it should be trated as ASCII so let's return its hex
-                        // representation.
-                        hex::encode(pattern)
-                    }
-
                     match rule {
                         MatchingRule::StartsWith(pattern) => {
                             if opret.starts_with(&encoded_pattern(pattern)) {
@@ -933,78 +3621,154 @@ impl BitcoinPredicateType {
                                 return true;
                             }
                         }
+                        MatchingRule::Regex(regex) => {
+                            if regex_matches(regex, &opret) {
+                                return true;
+                            }
+                        }
+                        MatchingRule::AtOffset(rule) => {
+                            if matches_at_offset(&opret, rule.offset, &encoded_pattern(&rule.value))
+                            {
+                                return true;
+                            }
+                        }
                     }
                 }
                 false
             }
-            BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(ExactMatchingRule::Equals(
-                encoded_address,
-            )))
-            | BitcoinPredicateType::Outputs(OutputPredicate::P2sh(ExactMatchingRule::Equals(
-                encoded_address,
+            BitcoinPredicateType::Outputs(OutputPredicate::OpReturnProtocol(protocol)) => {
+                BitcoinPredicateType::Outputs(OutputPredicate::OpReturn(
+                    protocol.get_matching_rule(),
+                ))
+                .evaluate_transaction_predicate(tx, descriptor_default_range, ctx)
+            }
+            BitcoinPredicateType::Outputs(OutputPredicate::P2pk(ExactMatchingRule::Equals(
+                expected,
             ))) => {
-                let address = match Address::from_str(encoded_address) {
-                    Ok(address) => address.assume_checked(),
-                    Err(_) => return false,
+                // the rule either holds the public key itself, or the address derived from it
+                let expected_pubkey =
+                    Vec::<u8>::from_hex(expected.strip_prefix("0x").unwrap_or(expected)).ok();
+                let expected_pubkey_hash = match Address::from_str(expected) {
+                    Ok(address) => match address.assume_checked().payload() {
+                        Payload::PubkeyHash(pubkey_hash) => Some(*pubkey_hash),
+                        _ => None,
+                    },
+                    Err(_) => None,
                 };
-                let address_bytes = hex::encode(address.script_pubkey().as_bytes());
                 for output in tx.metadata.outputs.iter() {
-                    if output.script_pubkey[2..] == address_bytes {
+                    let Some(pubkey) = get_p2pk_pubkey(&output.script_pubkey) else {
+                        continue;
+                    };
+                    if expected_pubkey.as_ref() == Some(&pubkey) {
                         return true;
                     }
+                    if let (Some(expected_pubkey_hash), Ok(pubkey)) =
+                        (&expected_pubkey_hash, PublicKey::from_slice(&pubkey))
+                    {
+                        if pubkey.pubkey_hash().eq(expected_pubkey_hash) {
+                            return true;
+                        }
+                    }
                 }
                 false
             }
-            BitcoinPredicateType::Outputs(OutputPredicate::P2wpkh(ExactMatchingRule::Equals(
-                encoded_address,
-            )))
-            | BitcoinPredicateType::Outputs(OutputPredicate::P2wsh(ExactMatchingRule::Equals(
-                encoded_address,
-            ))) => {
-                let address = match Address::from_str(encoded_address) {
-                    Ok(address) => {
-                        let checked_address = address.assume_checked();
-                        match checked_address.payload() {
-                            Payload::WitnessProgram(_) => checked_address,
-                            _ => return false,
-                        }
+            BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(_))
+            | BitcoinPredicateType::Outputs(OutputPredicate::P2sh(_))
+            | BitcoinPredicateType::Outputs(OutputPredicate::P2wpkh(_))
+            | BitcoinPredicateType::Outputs(OutputPredicate::P2wsh(_))
+            | BitcoinPredicateType::Inputs(InputPredicate::Consolidation(_)) => {
+                match self.parse_target_address() {
+                    Ok(Some(address)) => self.evaluate_target_address(tx, &address),
+                    _ => false,
+                }
+            }
+            BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(descriptor)) => {
+                let matching_indices = match descriptor
+                    .get_matching_output_indices(&tx.metadata.outputs, descriptor_default_range)
+                {
+                    Ok(matching_indices) => matching_indices,
+                    Err(e) => {
+                        ctx.try_log(|logger| {
+                            slog::warn!(
+                                logger,
+                                "Descriptor: unable to evaluate {}: {}",
+                                descriptor.expression,
+                                e
+                            )
+                        });
+                        return false;
+                    }
+                };
+
+                for index in matching_indices.iter() {
+                    ctx.try_log(|logger| {
+                        slog::debug!(
+                            logger,
+                            "Descriptor: Matched pubkey {:?} on tx {:?} output {}",
+                            tx.metadata.outputs[*index].script_pubkey,
+                            tx.transaction_identifier.get_hash_bytes_str(),
+                            index,
+                        )
+                    });
+                }
+
+                !matching_indices.is_empty()
+            }
+            BitcoinPredicateType::Outputs(OutputPredicate::Denylist(denylist)) => {
+                let script_pubkeys = match denylist.get_script_pubkeys() {
+                    Ok(script_pubkeys) => script_pubkeys,
+                    Err(e) => {
+                        ctx.try_log(|logger| {
+                            slog::warn!(logger, "Denylist: unable to load addresses: {}", e)
+                        });
+                        return false;
                     }
-                    Err(_) => return false,
                 };
-                let address_bytes = hex::encode(address.script_pubkey().as_bytes());
                 for output in tx.metadata.outputs.iter() {
-                    if output.script_pubkey[2..] == address_bytes {
+                    let script_pubkey = output
+                        .script_pubkey
+                        .strip_prefix("0x")
+                        .unwrap_or(&output.script_pubkey);
+                    if script_pubkeys.contains(&script_pubkey.to_string()) {
                         return true;
                     }
                 }
                 false
             }
-            BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(descriptor)) => {
-                let script_pubkeys = descriptor.derive_script_pubkeys().unwrap();
-
-                for script_pubkey in script_pubkeys {
-                    // Match the script against the tx outputs.
-                    for (index, output) in tx.metadata.outputs.iter().enumerate() {
-                        if output.script_pubkey[2..] == script_pubkey {
-                            ctx.try_log(|logger| {
-                                slog::debug!(
-                                    logger,
-                                    "Descriptor: Matched pubkey {:?} on tx {:?} output {}",
-                                    script_pubkey,
-                                    tx.transaction_identifier.get_hash_bytes_str(),
-                                    index,
-                                )
-                            });
-
-                            return true;
-                        }
+            BitcoinPredicateType::Outputs(OutputPredicate::AnyAddress(rule)) => {
+                let script_pubkeys = match rule.get_script_pubkeys() {
+                    Ok(script_pubkeys) => script_pubkeys,
+                    Err(e) => {
+                        ctx.try_log(|logger| {
+                            slog::warn!(logger, "AnyAddress: unable to parse addresses: {}", e)
+                        });
+                        return false;
+                    }
+                };
+                tx.metadata.outputs.iter().any(|output| {
+                    let script_pubkey = output
+                        .script_pubkey
+                        .strip_prefix("0x")
+                        .unwrap_or(&output.script_pubkey);
+                    script_pubkeys.contains(script_pubkey)
+                })
+            }
+            BitcoinPredicateType::Outputs(OutputPredicate::AddressType(address_type)) => {
+                for output in tx.metadata.outputs.iter() {
+                    if AddressType::from_script_pubkey(&output.script_pubkey).as_ref()
+                        == Some(address_type)
+                    {
+                        return true;
                     }
                 }
-
                 false
             }
+            BitcoinPredicateType::Outputs(OutputPredicate::Value(rule)) => tx
+                .metadata
+                .outputs
+                .iter()
+                .any(|output| rule.evaluate(output.value)),
             BitcoinPredicateType::Inputs(InputPredicate::Txid(predicate)) => {
-                // TODO(lgalabru): add support for transaction chainhing, if enabled
                 for input in tx.metadata.inputs.iter() {
                     if input.previous_output.txid.hash.eq(&predicate.txid)
                         && input.previous_output.vout.eq(&predicate.vout)
@@ -1014,9 +3778,65 @@ impl BitcoinPredicateType {
                 }
                 false
             }
-            BitcoinPredicateType::Inputs(InputPredicate::WitnessScript(_)) => {
-                // TODO(lgalabru)
-                unimplemented!()
+            BitcoinPredicateType::Inputs(InputPredicate::WitnessScript(rule)) => {
+                for input in tx.metadata.inputs.iter() {
+                    // legacy inputs have an empty witness, and are skipped
+                    for item in input.witness.iter() {
+                        let item = item.strip_prefix("0x").unwrap_or(item).to_lowercase();
+                        let matches = match rule {
+                            MatchingRule::Equals(pattern) => item.eq(&encoded_pattern(pattern)),
+                            MatchingRule::StartsWith(pattern) => {
+                                item.starts_with(&encoded_pattern(pattern))
+                            }
+                            MatchingRule::EndsWith(pattern) => {
+                                item.ends_with(&encoded_pattern(pattern))
+                            }
+                            MatchingRule::Regex(regex) => regex_matches(regex, &item),
+                            MatchingRule::AtOffset(rule) => {
+                                matches_at_offset(&item, rule.offset, &encoded_pattern(&rule.value))
+                            }
+                        };
+                        if matches {
+                            return true;
+                        }
+                    }
+                }
+                false
+            }
+            BitcoinPredicateType::Inputs(InputPredicate::ScriptSig(rule)) => {
+                for input in tx.metadata.inputs.iter() {
+                    let script_sig = input
+                        .script_sig
+                        .strip_prefix("0x")
+                        .unwrap_or(&input.script_sig)
+                        .to_lowercase();
+                    // SegWit inputs carry their unlocking data in the witness
+                    if script_sig.is_empty() {
+                        continue;
+                    }
+                    let matches = match rule {
+                        MatchingRule::Equals(pattern) => script_sig.eq(&encoded_pattern(pattern)),
+                        MatchingRule::StartsWith(pattern) => {
+                            script_sig.starts_with(&encoded_pattern(pattern))
+                        }
+                        MatchingRule::EndsWith(pattern) => {
+                            script_sig.ends_with(&encoded_pattern(pattern))
+                        }
+                        MatchingRule::Regex(regex) => regex_matches(regex, &script_sig),
+                        MatchingRule::AtOffset(rule) => matches_at_offset(
+                            &script_sig,
+                            rule.offset,
+                            &encoded_pattern(&rule.value),
+                        ),
+                    };
+                    if matches {
+                        return true;
+                    }
+                }
+                false
+            }
+            BitcoinPredicateType::StacksProtocol(StacksOperations::All) => {
+                !tx.metadata.stacks_operations.is_empty()
             }
             BitcoinPredicateType::StacksProtocol(StacksOperations::StackerRewarded) => {
                 for op in tx.metadata.stacks_operations.iter() {
@@ -1026,10 +3846,29 @@ impl BitcoinPredicateType {
                 }
                 false
             }
-            BitcoinPredicateType::StacksProtocol(StacksOperations::BlockCommitted) => {
+            BitcoinPredicateType::StacksProtocol(StacksOperations::BlockCommitted {
+                parent_block_ptr,
+                parent_vtxindex,
+            }) => {
                 for op in tx.metadata.stacks_operations.iter() {
-                    if let StacksBaseChainOperation::BlockCommitted(_) = op {
-                        return true;
+                    if let StacksBaseChainOperation::BlockCommitted(data) = op {
+                        let parent_matches = parent_block_ptr
+                            .map_or(true, |ptr| data.parent_block_ptr == Some(ptr))
+                            && parent_vtxindex
+                                .map_or(true, |vtxindex| data.parent_vtxindex == Some(vtxindex));
+                        if parent_matches {
+                            return true;
+                        }
+                    }
+                }
+                false
+            }
+            BitcoinPredicateType::StacksProtocol(StacksOperations::TenureChanged) => {
+                for op in tx.metadata.stacks_operations.iter() {
+                    if let StacksBaseChainOperation::BlockCommitted(data) = op {
+                        if data.anchors_tenure_change {
+                            return true;
+                        }
                     }
                 }
                 false
@@ -1042,10 +3881,23 @@ impl BitcoinPredicateType {
                 }
                 false
             }
-            BitcoinPredicateType::StacksProtocol(StacksOperations::StxTransferred) => {
+            BitcoinPredicateType::StacksProtocol(StacksOperations::StxTransferred {
+                min_amount,
+                sender,
+                recipient,
+            }) => {
                 for op in tx.metadata.stacks_operations.iter() {
-                    if let StacksBaseChainOperation::StxTransferred(_) = op {
-                        return true;
+                    if let StacksBaseChainOperation::StxTransferred(data) = op {
+                        let amount_matches = min_amount.map_or(true, |min_amount| {
+                            data.amount
+                                .parse::<u128>()
+                                .map_or(false, |amount| amount >= min_amount)
+                        });
+                        let parties_match = sender.as_ref().map_or(true, |s| data.sender.eq(s))
+                            && recipient.as_ref().map_or(true, |r| data.recipient.eq(r));
+                        if amount_matches && parties_match {
+                            return true;
+                        }
                     }
                 }
                 false
@@ -1058,6 +3910,9 @@ impl BitcoinPredicateType {
                 }
                 false
             }
+            // stacks opcodes follow the magic bytes of the predicate's network, and are evaluated
+            // by `BitcoinChainhookInstance::evaluate_transaction`
+            BitcoinPredicateType::StacksProtocol(StacksOperations::Opcode { .. }) => false,
             BitcoinPredicateType::OrdinalsProtocol(OrdinalOperations::InscriptionFeed(
                 feed_data,
             )) => match &feed_data.meta_protocols {
@@ -1076,6 +3931,31 @@ impl BitcoinPredicateType {
                 }
                 None => !tx.metadata.ordinal_operations.is_empty(),
             },
+            BitcoinPredicateType::TransactionSize(TransactionSizePredicate::Weight(rule)) => {
+                rule.evaluate(tx.metadata.weight)
+            }
+            BitcoinPredicateType::TransactionSize(TransactionSizePredicate::Vsize(rule)) => {
+                rule.evaluate(tx.metadata.vsize)
+            }
+            BitcoinPredicateType::TransactionSize(
+                TransactionSizePredicate::InscriptionContent(rule),
+            ) => {
+                let content_length: usize = tx
+                    .metadata
+                    .ordinal_operations
+                    .iter()
+                    .map(|op| match op {
+                        OrdinalOperation::InscriptionRevealed(reveal) => reveal.content_length,
+                        OrdinalOperation::InscriptionTransferred(_) => 0,
+                    })
+                    .sum();
+                rule.evaluate(content_length as u64)
+            }
+            BitcoinPredicateType::TransactionVersion(rule) => rule.evaluate(tx.metadata.version),
+            BitcoinPredicateType::TransactionIndex(rule) => rule.evaluate(tx.metadata.index),
+            // fee rate percentiles depend on the other transactions of the block, and are
+            // evaluated by `BitcoinChainhookInstance::evaluate_transaction`
+            BitcoinPredicateType::FeeRatePercentile(_) => false,
         }
     }
 }
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ::parquet::arrow::ArrowWriter;
+use arrow_array::{ArrayRef, BooleanArray, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use serde::{Deserialize, Serialize};
+
+use super::BitcoinTriggerChainhook;
+use crate::chainhooks::types::ParquetHook;
+
+const DEFAULT_MAX_FILE_SIZE_MB: u64 = 128;
+const DEFAULT_MAX_FILE_AGE_SEC: u64 = 3600;
+
+static PARQUET_WRITERS: OnceLock<Mutex<BitcoinParquetWriters>> = OnceLock::new();
+
+/// A transaction matched by a Bitcoin predicate, as written to Parquet files.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BitcoinParquetRow {
+    pub predicate_uuid: String,
+    pub block_height: u64,
+    pub block_hash: String,
+    pub txid: String,
+    pub fee: u64,
+    /// `true` when the transaction is rolled back by a re-org.
+    pub rollback: bool,
+    /// The JSON encoded `if_this` predicate that matched the transaction.
+    pub matched_predicate: String,
+}
+
+impl BitcoinParquetRow {
+    pub fn from_trigger(
+        trigger: &BitcoinTriggerChainhook,
+    ) -> Result<Vec<BitcoinParquetRow>, String> {
+        let matched_predicate = serde_json::to_string(&trigger.chainhook.predicate)
+            .map_err(|e| format!("unable to serialize predicate {}", e.to_string()))?;
+        let mut rows = vec![];
+        for (rollback, blocks) in [(true, &trigger.rollback), (false, &trigger.apply)] {
+            for (transactions, block) in blocks.iter() {
+                for transaction in transactions.iter() {
+                    rows.push(BitcoinParquetRow {
+                        predicate_uuid: trigger.chainhook.uuid.clone(),
+                        block_height: block.block_identifier.index,
+                        block_hash: block.block_identifier.hash.clone(),
+                        txid: transaction.transaction_identifier.hash.clone(),
+                        fee: transaction.metadata.fee,
+                        rollback,
+                        matched_predicate: matched_predicate.clone(),
+                    });
+                }
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// The schema of the Parquet files written for Bitcoin predicates. Columns are only ever appended
+/// to this schema, so that files written by older versions remain readable with the same queries.
+pub fn bitcoin_parquet_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("predicate_uuid", DataType::Utf8, false),
+        Field::new("block_height", DataType::UInt64, false),
+        Field::new("block_hash", DataType::Utf8, false),
+        Field::new("txid", DataType::Utf8, false),
+        Field::new("fee", DataType::UInt64, false),
+        Field::new("rollback", DataType::Boolean, false),
+        Field::new("matched_predicate", DataType::Utf8, false),
+    ])
+}
+
+fn build_record_batch(
+    schema: SchemaRef,
+    rows: &[BitcoinParquetRow],
+) -> Result<RecordBatch, String> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| r.predicate_uuid.as_str()),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            rows.iter().map(|r| r.block_height),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| r.block_hash.as_str()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| r.txid.as_str()),
+        )),
+        Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.fee))),
+        Arc::new(BooleanArray::from(
+            rows.iter().map(|r| r.rollback).collect::<Vec<_>>(),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| r.matched_predicate.as_str()),
+        )),
+    ];
+    RecordBatch::try_new(schema, columns)
+        .map_err(|e| format!("unable to build record batch: {}", e.to_string()))
+}
+
+struct RollingParquetFile {
+    writer: ArrowWriter<File>,
+    /// The path the file is written to, renamed to `path` without its `.tmp` extension once closed.
+    tmp_path: PathBuf,
+    opened_at: Instant,
+    max_size: u64,
+    max_age: Duration,
+}
+
+impl RollingParquetFile {
+    fn is_expired(&self) -> bool {
+        self.writer.bytes_written() as u64 >= self.max_size
+            || self.opened_at.elapsed() >= self.max_age
+    }
+}
+
+/// The Parquet files currently written, by directory.
+///
+/// Rows are appended to the current file of a directory as new row groups. A Parquet file can only
+/// be read once closed: the current file is written with a `.tmp` extension, and renamed once
+/// closed. It is closed, and a new one started, when it exceeds the size or the age configured by
+/// the [ParquetHook], or when [close_parquet_files] is called. The age of a file is checked when
+/// rows are appended, and by [close_expired_parquet_files].
+///
+/// The writers are held in a static, which is never dropped: the observer and the scans call
+/// [close_parquet_files] when they stop, otherwise the current files are left unreadable.
+pub struct BitcoinParquetWriters {
+    schema: SchemaRef,
+    files: HashMap<String, RollingParquetFile>,
+}
+
+impl BitcoinParquetWriters {
+    pub fn new() -> Self {
+        BitcoinParquetWriters {
+            schema: Arc::new(bitcoin_parquet_schema()),
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn append(&mut self, hook: &ParquetHook, rows: &[BitcoinParquetRow]) -> Result<(), String> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let expired = match self.files.get(&hook.path) {
+            Some(file) => file.is_expired(),
+            None => false,
+        };
+        if expired {
+            self.close(&hook.path)?;
+        }
+        if !self.files.contains_key(&hook.path) {
+            let file = self.open(hook)?;
+            self.files.insert(hook.path.clone(), file);
+        }
+        let batch = build_record_batch(self.schema.clone(), rows)?;
+        let file = self.files.get_mut(&hook.path).unwrap();
+        file.writer
+            .write(&batch)
+            .map_err(|e| format!("unable to write parquet rows: {}", e.to_string()))?;
+        file.writer
+            .flush()
+            .map_err(|e| format!("unable to flush parquet rows: {}", e.to_string()))
+    }
+
+    fn open(&self, hook: &ParquetHook) -> Result<RollingParquetFile, String> {
+        let dir = &hook.path;
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("unable to create directory {}: {}", dir, e.to_string()))?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not get current time in ms")
+            .as_millis();
+        let mut sequence = 0;
+        let tmp_path = loop {
+            let file_path =
+                PathBuf::from(dir).join(format!("chainhook-{}-{}.parquet", now, sequence));
+            let tmp_path = file_path.with_extension("parquet.tmp");
+            if !file_path.exists() && !tmp_path.exists() {
+                break tmp_path;
+            }
+            sequence += 1;
+        };
+        let file = File::create(&tmp_path).map_err(|e| {
+            format!(
+                "unable to create file {}: {}",
+                tmp_path.display(),
+                e.to_string()
+            )
+        })?;
+        let writer = ArrowWriter::try_new(file, self.schema.clone(), None)
+            .map_err(|e| format!("unable to create parquet writer: {}", e.to_string()))?;
+        Ok(RollingParquetFile {
+            writer,
+            tmp_path,
+            opened_at: Instant::now(),
+            max_size: hook.max_file_size_mb.unwrap_or(DEFAULT_MAX_FILE_SIZE_MB) * 1024 * 1024,
+            max_age: Duration::from_secs(hook.max_file_age_sec.unwrap_or(DEFAULT_MAX_FILE_AGE_SEC)),
+        })
+    }
+
+    fn close(&mut self, dir: &str) -> Result<(), String> {
+        if let Some(file) = self.files.remove(dir) {
+            file.writer
+                .close()
+                .map_err(|e| format!("unable to close parquet file: {}", e.to_string()))?;
+            let path = file.tmp_path.with_extension("");
+            fs::rename(&file.tmp_path, &path).map_err(|e| {
+                format!(
+                    "unable to rename file {}: {}",
+                    file.tmp_path.display(),
+                    e.to_string()
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Closes the files that exceeded their size or age, even if no row is appended to them.
+    pub fn close_expired(&mut self) -> Result<(), String> {
+        let dirs = self
+            .files
+            .iter()
+            .filter(|(_, file)| file.is_expired())
+            .map(|(dir, _)| dir.clone())
+            .collect::<Vec<_>>();
+        for dir in dirs.iter() {
+            self.close(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the current file of every directory, making them readable.
+    pub fn close_all(&mut self) -> Result<(), String> {
+        let dirs = self.files.keys().cloned().collect::<Vec<_>>();
+        for dir in dirs.iter() {
+            self.close(dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for BitcoinParquetWriters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parquet_writers() -> &'static Mutex<BitcoinParquetWriters> {
+    PARQUET_WRITERS.get_or_init(|| Mutex::new(BitcoinParquetWriters::new()))
+}
+
+/// Appends `rows` to the current Parquet file of `hook`. The files are shared by every predicate
+/// writing to the same directory, whether they are evaluated by the observer or by a scan.
+pub fn append_parquet_rows(hook: &ParquetHook, rows: &[BitcoinParquetRow]) -> Result<(), String> {
+    parquet_writers()
+        .lock()
+        .map_err(|e| format!("unable to lock parquet writers: {}", e.to_string()))?
+        .append(hook, rows)
+}
+
+/// Closes the Parquet files that exceeded the size or the age configured by their [ParquetHook].
+pub fn close_expired_parquet_files() -> Result<(), String> {
+    parquet_writers()
+        .lock()
+        .map_err(|e| format!("unable to lock parquet writers: {}", e.to_string()))?
+        .close_expired()
+}
+
+/// Closes the Parquet files currently written. Subsequent rows are written to new files.
+pub fn close_parquet_files() -> Result<(), String> {
+    parquet_writers()
+        .lock()
+        .map_err(|e| format!("unable to lock parquet writers: {}", e.to_string()))?
+        .close_all()
+}
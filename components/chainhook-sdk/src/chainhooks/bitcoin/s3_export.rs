@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+
+use chainhook_types::BlockIdentifier;
+
+use crate::chainhooks::types::{S3AppendHook, S3KeyLayout};
+
+static S3_CLIENTS: OnceLock<Mutex<HashMap<String, Client>>> = OnceLock::new();
+
+/// Returns the client of `region`, loading the AWS credentials from the environment on first use.
+async fn get_s3_client(region: &str) -> Result<Client, String> {
+    let client = S3_CLIENTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|e| format!("unable to lock s3 clients: {}", e.to_string()))?
+        .get(region)
+        .cloned();
+    if let Some(client) = client {
+        return Ok(client);
+    }
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .region(Region::new(region.to_string()))
+        .load()
+        .await;
+    let client = Client::new(&config);
+    S3_CLIENTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|e| format!("unable to lock s3 clients: {}", e.to_string()))?
+        .insert(region.to_string(), client.clone());
+    Ok(client)
+}
+
+/// Builds the key of the object the occurrence of `predicate_uuid`, triggered by `block` and
+/// built at `timestamp_ms`, is written to. The hash of the block is part of the per-occurrence
+/// keys, so that occurrences built within the same millisecond don't overwrite each other.
+pub fn build_s3_key(
+    hook: &S3AppendHook,
+    predicate_uuid: &str,
+    block: &BlockIdentifier,
+    timestamp_ms: u128,
+) -> String {
+    let block_hash = block.hash.trim_start_matches("0x");
+    let key = match hook.key_layout.unwrap_or(S3KeyLayout::Timestamp) {
+        S3KeyLayout::Timestamp => {
+            format!("{}-{}-{}.json", timestamp_ms, block_hash, predicate_uuid)
+        }
+        S3KeyLayout::BlockHeightRange { range_size } => {
+            let range_start = block.index - block.index % range_size;
+            format!(
+                "{}-{}/{}-{}-{}-{}.json",
+                range_start,
+                range_start + range_size - 1,
+                block.index,
+                block_hash,
+                timestamp_ms,
+                predicate_uuid
+            )
+        }
+        S3KeyLayout::Daily => format!(
+            "{}/{}-{}-{}.json",
+            format_date(timestamp_ms),
+            timestamp_ms,
+            block_hash,
+            predicate_uuid
+        ),
+    };
+    match hook.key_prefix.trim_end_matches('/') {
+        "" => key,
+        prefix => format!("{}/{}", prefix, key),
+    }
+}
+
+pub fn get_timestamp_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Could not get current time in ms")
+        .as_millis()
+}
+
+/// Formats the UTC date of `timestamp_ms` as `YYYY-MM-DD`.
+fn format_date(timestamp_ms: u128) -> String {
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp_ms / 86_400_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Writes `bytes` to the object `key` of the bucket of `hook`. Every occurrence has its own key:
+/// S3 objects can't be appended to, and concurrent writes to a shared object would be lost.
+pub async fn append_s3_object(
+    hook: &S3AppendHook,
+    key: &str,
+    bytes: Vec<u8>,
+) -> Result<(), String> {
+    let client = get_s3_client(&hook.region).await?;
+    client
+        .put_object()
+        .bucket(&hook.bucket)
+        .key(key)
+        .content_type("application/json")
+        .body(ByteStream::from(bytes))
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("unable to put s3 object {}: {}", key, e.to_string()))
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_sqs::types::SendMessageBatchRequestEntry;
+use aws_sdk_sqs::Client;
+
+use crate::chainhooks::types::{ContentHashAlgorithm, SqsHook};
+
+/// Maximum number of messages sent by a `SendMessageBatch` call.
+const MAX_BATCH_MESSAGES: usize = 10;
+/// Maximum size of the messages sent by a `SendMessageBatch` call, summed.
+const MAX_BATCH_SIZE_BYTES: usize = 256 * 1024;
+/// Maximum size of a single message accepted by SQS.
+pub const MAX_MESSAGE_SIZE_BYTES: usize = 256 * 1024;
+
+static SQS_CLIENTS: OnceLock<Mutex<HashMap<String, Client>>> = OnceLock::new();
+
+/// Returns the client of `region`, loading the AWS credentials from the environment on first use.
+async fn get_sqs_client(region: &str) -> Result<Client, String> {
+    let client = SQS_CLIENTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|e| format!("unable to lock sqs clients: {}", e.to_string()))?
+        .get(region)
+        .cloned();
+    if let Some(client) = client {
+        return Ok(client);
+    }
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .region(Region::new(region.to_string()))
+        .load()
+        .await;
+    let client = Client::new(&config);
+    SQS_CLIENTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|e| format!("unable to lock sqs clients: {}", e.to_string()))?
+        .insert(region.to_string(), client.clone());
+    Ok(client)
+}
+
+/// Sends `occurrences`, built from the same chain event, grouped by queue in `SendMessageBatch`
+/// calls. Messages sent to FIFO queues are grouped by predicate uuid, so that the occurrences of a
+/// predicate are consumed in order, and deduplicated by the hash of their body, computed with
+/// `deduplication_hash` unless the action sets its own. Returns the uuids of the predicates whose
+/// occurrences could not be sent, along with the error.
+///
+/// Occurrences exceeding [MAX_MESSAGE_SIZE_BYTES] are rejected up front, without being sent.
+pub async fn send_sqs_occurrences(
+    occurrences: Vec<(SqsHook, String, Vec<u8>)>,
+    deduplication_hash: ContentHashAlgorithm,
+) -> Vec<(String, String)> {
+    let mut queues: Vec<(SqsHook, Vec<(String, String)>)> = vec![];
+    let mut failures = vec![];
+    for (hook, predicate_uuid, bytes) in occurrences.into_iter() {
+        let body = match String::from_utf8(bytes) {
+            Ok(body) => body,
+            Err(e) => {
+                failures.push((
+                    predicate_uuid,
+                    format!("unable to encode sqs message: {}", e.to_string()),
+                ));
+                continue;
+            }
+        };
+        if body.len() > MAX_MESSAGE_SIZE_BYTES {
+            failures.push((
+                predicate_uuid,
+                format!(
+                    "unable to send sqs message of {} bytes, exceeding the {} bytes limit of sqs: use the s3_append action for large occurrences",
+                    body.len(),
+                    MAX_MESSAGE_SIZE_BYTES
+                ),
+            ));
+            continue;
+        }
+        match queues.iter_mut().find(|(queue, _)| queue.eq(&hook)) {
+            Some((_, messages)) => messages.push((predicate_uuid, body)),
+            None => queues.push((hook, vec![(predicate_uuid, body)])),
+        }
+    }
+    for (hook, messages) in queues.into_iter() {
+        for batch in build_batches(messages) {
+            failures.append(&mut send_sqs_batch(&hook, batch, deduplication_hash).await);
+        }
+    }
+    failures
+}
+
+/// Splits `messages` in batches of at most [MAX_BATCH_MESSAGES] messages and
+/// [MAX_BATCH_SIZE_BYTES] bytes, preserving their order.
+pub fn build_batches(messages: Vec<(String, String)>) -> Vec<Vec<(String, String)>> {
+    let mut batches: Vec<Vec<(String, String)>> = vec![];
+    let mut batch_size = 0;
+    for message in messages.into_iter() {
+        let message_size = message.1.len();
+        let fits = match batches.last() {
+            Some(batch) => {
+                batch.len() < MAX_BATCH_MESSAGES
+                    && batch_size + message_size <= MAX_BATCH_SIZE_BYTES
+            }
+            None => false,
+        };
+        if fits {
+            batch_size += message_size;
+            batches.last_mut().unwrap().push(message);
+        } else {
+            batch_size = message_size;
+            batches.push(vec![message]);
+        }
+    }
+    batches
+}
+
+/// Sends `batch` with a single `SendMessageBatch` call, returning the messages that failed.
+async fn send_sqs_batch(
+    hook: &SqsHook,
+    batch: Vec<(String, String)>,
+    deduplication_hash: ContentHashAlgorithm,
+) -> Vec<(String, String)> {
+    let fail_batch = |batch: &[(String, String)], e: String| {
+        batch
+            .iter()
+            .map(|(predicate_uuid, _)| (predicate_uuid.clone(), e.clone()))
+            .collect::<Vec<_>>()
+    };
+    let client = match get_sqs_client(&hook.region).await {
+        Ok(client) => client,
+        Err(e) => return fail_batch(&batch, e),
+    };
+    let mut entries = vec![];
+    for (i, (predicate_uuid, body)) in batch.iter().enumerate() {
+        let mut entry = SendMessageBatchRequestEntry::builder()
+            .id(i.to_string())
+            .message_body(body);
+        if hook.is_fifo() {
+            // required by FIFO queues, unless content-based deduplication is enabled
+            let deduplication_id = hook.get_deduplication_id(body, deduplication_hash);
+            entry = entry
+                .message_group_id(predicate_uuid)
+                .message_deduplication_id(deduplication_id);
+        }
+        match entry.build() {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                return fail_batch(
+                    &batch,
+                    format!("unable to build sqs message: {}", e.to_string()),
+                )
+            }
+        }
+    }
+    let output = match client
+        .send_message_batch()
+        .queue_url(&hook.queue_url)
+        .set_entries(Some(entries))
+        .send()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) => {
+            let e = format!(
+                "unable to send messages to {}: {}",
+                hook.queue_url,
+                e.to_string()
+            );
+            return fail_batch(&batch, e);
+        }
+    };
+    // entries are identified by their position in the batch
+    output
+        .failed()
+        .iter()
+        .filter_map(|failure| {
+            let (predicate_uuid, _) = batch.get(failure.id().parse::<usize>().ok()?)?;
+            let e = format!(
+                "unable to send message to {}: {}",
+                hook.queue_url,
+                failure.message().unwrap_or(failure.code())
+            );
+            Some((predicate_uuid.clone(), e))
+        })
+        .collect()
+}
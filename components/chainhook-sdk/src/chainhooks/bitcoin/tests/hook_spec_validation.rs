@@ -22,13 +22,14 @@ lazy_static! {
     static ref INVALID_TXID_PREDICATE: BitcoinPredicateType =
         BitcoinPredicateType::Txid(ExactMatchingRule::Equals("test".into()));
     static ref INVALID_HOOK_ACTION: HookAction = 
-        HookAction::HttpPost(HttpHook { url: "".into(), authorization_header: "\n".into() });
+        HookAction::HttpPost(HttpHook { url: "".into(), authorization_header: "\n".into(), failover_urls: vec![], signing_secret_id: None, verification_url: None });
     static ref ALL_INVALID_SPEC: BitcoinChainhookSpecification = BitcoinChainhookSpecification::new(INVALID_TXID_PREDICATE.clone(), INVALID_HOOK_ACTION.clone());
     static ref ALL_INVALID_SPEC_NETWORK_MAP: ChainhookSpecificationNetworkMap = 
         ChainhookSpecificationNetworkMap::Bitcoin(
             BitcoinChainhookSpecificationNetworkMap { 
                 uuid: "test".into(), 
                 owner_uuid: None, 
+                on_expire_url: None, 
                 name: "test".into(), 
                 version: 1, 
                 networks: BTreeMap::from([
@@ -66,30 +67,50 @@ lazy_static! {
 )]
 // BitcoinPredicateType::Inputs
 #[test_case(
-    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_NO_PREFIX.clone(), vout: 0})), 
+    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_NO_PREFIX.clone(), vout: 0, chained: false})), 
     Some(vec![INPUT_TXID_ERR.clone()]); "inputs txid without 0x"
 )]
 #[test_case(
-    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_NOT_HEX.clone(), vout: 0})), 
+    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_NOT_HEX.clone(), vout: 0, chained: false})), 
     Some(vec![INPUT_TXID_ERR.clone()]); "inputs txid not hex"
 )]
 #[test_case(
-    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_SHORT.clone(), vout: 0})), 
+    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_SHORT.clone(), vout: 0, chained: false})), 
     Some(vec![INPUT_TXID_ERR.clone()]); "inputs txid too short"
 )]
 #[test_case(
-    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_LONG.clone(), vout: 0})), 
+    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_LONG.clone(), vout: 0, chained: false})), 
     Some(vec![INPUT_TXID_ERR.clone()]); "inputs txid too long"
 )]
 #[test_case(
-    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_VALID.clone(), vout: 0})), 
+    &BitcoinPredicateType::Inputs(InputPredicate::Txid(TxinPredicate { txid: TXID_VALID.clone(), vout: 0, chained: false})), 
     None; "inputs txid just right"
 )]
+#[test_case(
+    &BitcoinPredicateType::Inputs(InputPredicate::ScriptSig(MatchingRule::StartsWith("0x4830zz".into()))), 
+    Some(vec!["invalid predicate for scope 'inputs': 'script_sig' pattern 0x4830zz is not a valid hex string".into()]); "inputs script_sig not hex"
+)]
+#[test_case(
+    &BitcoinPredicateType::Inputs(InputPredicate::ScriptSig(MatchingRule::StartsWith("0x483045".into()))), 
+    None; "inputs script_sig hex"
+)]
+#[test_case(
+    &BitcoinPredicateType::Inputs(InputPredicate::ScriptSig(MatchingRule::StartsWith("483045".into()))), 
+    Some(vec!["invalid predicate for scope 'inputs': 'script_sig' pattern 483045 must be a hex string prefixed with 0x".into()]); "inputs script_sig without prefix"
+)]
+#[test_case(
+    &BitcoinPredicateType::Inputs(InputPredicate::Consolidation(ConsolidationPredicate { address: "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH".into(), more_than: 10 })), 
+    None; "inputs consolidation"
+)]
 // BitcoinPredicateType::Outputs
 #[test_case(
     &BitcoinPredicateType::Outputs(OutputPredicate::OpReturn(MatchingRule::Equals("".into()))), 
     None; "outputs opreturn"
 )]
+#[test_case(
+    &BitcoinPredicateType::Outputs(OutputPredicate::P2pk(ExactMatchingRule::Equals("".into()))), 
+    None; "outputs p2pk"
+)]
 #[test_case(
     &BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(ExactMatchingRule::Equals("".into()))), 
     None; "outputs p2pkh"
@@ -106,37 +127,70 @@ lazy_static! {
     &BitcoinPredicateType::Outputs(OutputPredicate::P2wsh(ExactMatchingRule::Equals("".into()))), 
     None; "outputs p2wsh"
 )]
+#[test_case(
+    &BitcoinPredicateType::Outputs(OutputPredicate::AnyAddress(AddressSetRule::new(HashSet::new()))), 
+    Some(vec!["invalid predicate for scope 'outputs': 'any_address' must list at least one address".into()]); "outputs any_address empty"
+)]
 #[test_case(
     &BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(
-        DescriptorMatchingRule { 
-            expression: "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)".into(), 
-            range: None
-        }
+        DescriptorMatchingRule::new("wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)".into(), None, false)
     )), 
     None; "outputs descriptor ok"
 )]
 #[test_case(
     &BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(
-        DescriptorMatchingRule { 
-            expression: "wpkh(0)".into(), 
-            range: None
-        }
+        DescriptorMatchingRule::new("wpkh(0)".into(), None, false)
     )), 
     Some(vec![DESCRIPTOR_KEY_SHORT_ERR.clone()]); "outputs descriptor too short"
 )]
 #[test_case(
     &BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(
-        DescriptorMatchingRule { 
-            expression: "0".into(), 
-            range: None
-        }
+        DescriptorMatchingRule::new("0".into(), None, false)
     )), 
     Some(vec![INVALID_DESCRIPTOR_ERR.clone()]); "outputs invalid descriptor"
 )]
+#[test_case(&BitcoinPredicateType::Outputs(OutputPredicate::Value(ValuePredicate::Between(1_000, 1_000))), None; "outputs value ok")]
+#[test_case(
+    &BitcoinPredicateType::Outputs(OutputPredicate::Value(ValuePredicate::Between(2_000, 1_000))),
+    Some(vec!["invalid predicate for scope 'outputs': 'between' filter must have left-hand-side value lower than or equal to right-hand-side value".into()]); "outputs value invalid between"
+)]
 // BitcoinPredicateType::StacksProtocol
 #[test_case(&BitcoinPredicateType::StacksProtocol(StacksOperations::StackerRewarded), None; "stacks protocol")]
 // BitcoinPredicateType::OrdinalsProtocol
 #[test_case(&BitcoinPredicateType::OrdinalsProtocol(OrdinalOperations::InscriptionFeed(InscriptionFeedData { meta_protocols: None})), None; "ordinals protocol")]
+// BitcoinPredicateType::TransactionSize
+#[test_case(&BitcoinPredicateType::TransactionSize(TransactionSizePredicate::Vsize(SizeMatchingRule::Between(100, 200))), None; "transaction size ok")]
+#[test_case(
+    &BitcoinPredicateType::TransactionSize(TransactionSizePredicate::Weight(SizeMatchingRule::Between(200, 100))),
+    Some(vec!["invalid predicate for scope 'transaction_size': 'between' filter must have left-hand-side value lower than or equal to right-hand-side value".into()]); "transaction size invalid between"
+)]
+#[test_case(
+    &BitcoinPredicateType::TransactionSize(TransactionSizePredicate::InscriptionContent(SizeMatchingRule::LowerThan(0))),
+    Some(vec!["invalid predicate for scope 'transaction_size': 'lower_than' filter must be greater than 0".into()]); "inscription content size invalid lower_than"
+)]
+// BitcoinPredicateType::TransactionVersion
+#[test_case(&BitcoinPredicateType::TransactionVersion(VersionMatchingRule::Equals(2)), None; "transaction version ok")]
+#[test_case(&BitcoinPredicateType::TransactionVersion(VersionMatchingRule::LowerThan(0)), None; "transaction version negative lower_than ok")]
+#[test_case(
+    &BitcoinPredicateType::TransactionVersion(VersionMatchingRule::LowerThan(i32::MIN)),
+    Some(vec!["invalid predicate for scope 'transaction_version': 'lower_than' filter must be greater than -2147483648".into()]); "transaction version invalid lower_than"
+)]
+// BitcoinPredicateType::TransactionIndex
+#[test_case(&BitcoinPredicateType::TransactionIndex(IndexMatchingRule::Between(1, 1)), None; "transaction index ok")]
+#[test_case(
+    &BitcoinPredicateType::TransactionIndex(IndexMatchingRule::Between(3, 1)),
+    Some(vec!["invalid predicate for scope 'transaction_index': 'between' filter must have left-hand-side value lower than or equal to right-hand-side value".into()]); "transaction index invalid between"
+)]
+// BitcoinPredicateType::FeeRatePercentile
+#[test_case(&BitcoinPredicateType::FeeRatePercentile(FeeRatePercentileRule { min: 90, max: 100 }), None; "fee rate percentile ok")]
+#[test_case(
+    &BitcoinPredicateType::FeeRatePercentile(FeeRatePercentileRule { min: 90, max: 101 }),
+    Some(vec!["invalid predicate for scope 'fee_rate_percentile': 'max' percentile must be lower than or equal to 100".into()]); "fee rate percentile invalid max"
+)]
+#[test_case(
+    &BitcoinPredicateType::FeeRatePercentile(FeeRatePercentileRule { min: 50, max: 10 }),
+    Some(vec!["invalid predicate for scope 'fee_rate_percentile': 'min' percentile must be lower than or equal to 'max' percentile".into()]); "fee rate percentile invalid bounds"
+)]
 fn it_validates_bitcoin_predicates(predicate: &BitcoinPredicateType, expected_err: Option<Vec<String>>) {
     if let Err(e) = predicate.validate() {
         if let Some(expected) = expected_err {
@@ -196,3 +250,160 @@ fn it_validates_bitcoin_chainhook_specs(
         );
     }
 }
+
+#[test_case(
+    OutputPredicate::P2pkh(ExactMatchingRule::Equals("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".into())),
+    &BitcoinNetwork::Mainnet,
+    None; "mainnet p2pkh on mainnet"
+)]
+#[test_case(
+    OutputPredicate::P2pkh(ExactMatchingRule::Equals("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn".into())),
+    &BitcoinNetwork::Mainnet,
+    Some("address mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn is not valid for network mainnet".into()); "testnet p2pkh on mainnet"
+)]
+#[test_case(
+    OutputPredicate::P2pkh(ExactMatchingRule::Equals("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".into())),
+    &BitcoinNetwork::Testnet,
+    Some("address 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2 is not valid for network testnet".into()); "mainnet p2pkh on testnet"
+)]
+#[test_case(
+    OutputPredicate::P2wpkh(ExactMatchingRule::Equals("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem".into())),
+    &BitcoinNetwork::Testnet,
+    None; "testnet p2wpkh on testnet"
+)]
+#[test_case(
+    OutputPredicate::P2wpkh(ExactMatchingRule::Equals("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem".into())),
+    &BitcoinNetwork::Mainnet,
+    Some("address tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem is not valid for network mainnet".into()); "testnet p2wpkh on mainnet"
+)]
+#[test_case(
+    OutputPredicate::P2wpkh(ExactMatchingRule::Equals("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem".into())),
+    &BitcoinNetwork::Regtest,
+    Some("address tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem is not valid for network regtest".into()); "testnet p2wpkh on regtest"
+)]
+#[test_case(
+    OutputPredicate::P2pk(ExactMatchingRule::Equals("0x0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798".into())),
+    &BitcoinNetwork::Testnet,
+    None; "public key p2pk on testnet"
+)]
+#[test_case(
+    OutputPredicate::P2pk(ExactMatchingRule::Equals("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH".into())),
+    &BitcoinNetwork::Testnet,
+    Some("address 1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH is not valid for network testnet".into()); "mainnet p2pk on testnet"
+)]
+#[test_case(
+    OutputPredicate::P2wpkh(ExactMatchingRule::Equals("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".into())),
+    &BitcoinNetwork::Mainnet,
+    Some("address 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2 is not a segwit address".into()); "p2pkh address in p2wpkh rule"
+)]
+fn it_validates_output_addresses_for_network(
+    output: OutputPredicate,
+    network: &BitcoinNetwork,
+    expected_err: Option<String>,
+) {
+    assert_eq!(output.validate_for_network(network).err(), expected_err);
+}
+
+#[test]
+fn it_rejects_network_mismatched_address_on_registration() {
+    let spec = BitcoinChainhookSpecification::new(
+        BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(ExactMatchingRule::Equals(
+            "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn".into(),
+        ))),
+        HookAction::Noop,
+    );
+    let network_map = BitcoinChainhookSpecificationNetworkMap {
+        uuid: "test".into(),
+        owner_uuid: None,
+        on_expire_url: None,
+        name: "test".into(),
+        version: 1,
+        networks: BTreeMap::from([
+            (BitcoinNetwork::Mainnet, spec.clone()),
+            (BitcoinNetwork::Testnet, spec),
+        ]),
+    };
+    assert_eq!(
+        ChainhookSpecificationNetworkMap::Bitcoin(network_map.clone()).validate(),
+        Err("invalid Bitcoin predicate 'test' for network mainnet: invalid 'if_this' value: invalid predicate for scope 'outputs': address mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn is not valid for network mainnet".into())
+    );
+    assert!(network_map
+        .clone()
+        .into_specification_for_network(&BitcoinNetwork::Testnet)
+        .is_ok());
+    assert_eq!(
+        network_map
+            .into_specification_for_network(&BitcoinNetwork::Mainnet)
+            .err(),
+        Some("invalid Bitcoin predicate 'test' for network mainnet: invalid predicate for scope 'outputs': address mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn is not valid for network mainnet".into())
+    );
+}
+
+#[test_case(Some(true), Some(true), None; "witness with inputs")]
+#[test_case(None, Some(false), None; "witness disabled")]
+#[test_case(Some(false), Some(true), Some(vec!["Chainhook specification field `include_witness` requires `include_inputs` to be enabled.".into()]); "witness with inputs disabled")]
+#[test_case(None, Some(true), Some(vec!["Chainhook specification field `include_witness` requires `include_inputs` to be enabled.".into()]); "witness without inputs")]
+fn it_validates_include_witness_requires_inputs(
+    include_inputs: Option<bool>,
+    include_witness: Option<bool>,
+    expected_err: Option<Vec<String>>,
+) {
+    let mut spec =
+        BitcoinChainhookSpecification::new(BitcoinPredicateType::Block, HookAction::Noop);
+    if let Some(include_inputs) = include_inputs {
+        spec.include_inputs(include_inputs);
+    }
+    if let Some(include_witness) = include_witness {
+        spec.include_witness(include_witness);
+    }
+    assert_eq!(spec.validate().err(), expected_err);
+}
+
+#[test]
+fn it_rejects_invalid_address_on_registration() {
+    let spec = BitcoinChainhookSpecification::new(
+        BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(ExactMatchingRule::Equals(
+            "not-an-address".into(),
+        ))),
+        HookAction::Noop,
+    );
+    let network_map = BitcoinChainhookSpecificationNetworkMap {
+        uuid: "test".into(),
+        owner_uuid: None,
+        on_expire_url: None,
+        name: "test".into(),
+        version: 1,
+        networks: BTreeMap::from([(BitcoinNetwork::Mainnet, spec)]),
+    };
+    let err = network_map
+        .into_specification_for_network(&BitcoinNetwork::Mainnet)
+        .unwrap_err();
+    assert!(err.contains("invalid address not-an-address"), "{}", err);
+}
+
+#[test_case(MatchingRule::Equals("0x69d3".into()), vec![]; "hex pattern")]
+#[test_case(MatchingRule::StartsWith("0x6".into()), vec![]; "partial byte prefix")]
+#[test_case(MatchingRule::Equals("0x6".into()), vec!["'op_return' pattern 0x6 does not encode a whole number of bytes".into()]; "partial byte equals")]
+#[test_case(MatchingRule::EndsWith("0xzz".into()), vec!["'op_return' pattern 0xzz is not a valid hex string".into()]; "invalid hex")]
+#[test_case(MatchingRule::StartsWith("a".repeat(81)), vec![]; "ascii pattern above the former 80 bytes standardness limit")]
+#[test_case(MatchingRule::StartsWith("a".repeat(1_000_001)), vec![format!("'op_return' pattern {} exceeds the 1000000 bytes an OP_RETURN output can carry in a block", "a".repeat(1_000_001))]; "oversized ascii pattern")]
+fn it_detects_unsatisfiable_op_return_rules(rule: MatchingRule, expected_conditions: Vec<String>) {
+    let spec = BitcoinChainhookSpecification::new(
+        BitcoinPredicateType::Outputs(OutputPredicate::OpReturn(rule)),
+        HookAction::Noop,
+    );
+    assert!(spec.validate().is_ok());
+    assert_eq!(spec.get_unsatisfiable_conditions(), expected_conditions);
+}
+
+#[test]
+fn it_rejects_invalid_regex_on_registration() {
+    let err = serde_json::from_value::<BitcoinPredicateType>(serde_json::json!({
+        "scope": "outputs",
+        "op_return": {
+            "regex": "^(6a"
+        }
+    }))
+    .unwrap_err();
+    assert!(err.to_string().contains("invalid regex ^(6a"), "{}", err);
+}
@@ -1,70 +1,323 @@
 use std::collections::HashSet;
+use std::sync::Arc;
 
-use super::super::types::MatchingRule;
+#[cfg(feature = "sqs")]
+use super::super::types::SqsHook;
+#[cfg(feature = "websocket")]
+use super::super::types::WebSocketHook;
+use super::super::types::{
+    sign_payload, ContentHashAlgorithm, FileHook, HttpHook, MatchingRegex, MatchingRule,
+    OffsetMatchingRule, UnixSocketHook, HOOK_SIGNATURE_HEADER,
+};
+#[cfg(feature = "s3")]
+use super::super::types::{S3AppendHook, S3KeyLayout};
 use super::*;
 use crate::chainhooks::bitcoin::InscriptionFeedData;
 use crate::indexer::tests::helpers::accounts;
 use crate::indexer::tests::helpers::bitcoin_blocks::generate_test_bitcoin_block;
 use crate::indexer::tests::helpers::transactions::generate_test_tx_bitcoin_p2pkh_transfer;
 use crate::types::BitcoinTransactionMetadata;
-use chainhook_types::bitcoin::TxOut;
+use chainhook_types::bitcoin::{OutPoint, TxIn, TxOut};
 
-use chainhook_types::{BitcoinNetwork, Brc20Operation, Brc20TokenDeployData};
+use chainhook_types::{
+    BitcoinChainUpdatedWithBlocksData, BitcoinChainUpdatedWithReorgData, BitcoinNetwork,
+    Brc20BalanceData, Brc20Operation, Brc20TokenDeployData, KeyRegistrationData,
+    OrdinalInscriptionNumber, OrdinalInscriptionRevealData, OrdinalOperation,
+    StacksBlockCommitmentData, TransferSTXData,
+};
 use test_case::test_case;
 mod hook_spec_validation;
 
+/// Returns a mainnet predicate evaluating `predicate`, with every option disabled, for tests to
+/// override the fields they exercise.
+fn get_test_bitcoin_chainhook(predicate: BitcoinPredicateType) -> BitcoinChainhookInstance {
+    BitcoinChainhookInstance {
+        uuid: "uuid".into(),
+        owner_uuid: None,
+        on_expire_url: None,
+        name: "name".into(),
+        network: BitcoinNetwork::Mainnet,
+        version: 0,
+        blocks: None,
+        start_block: None,
+        end_block: None,
+        end_block_grace_period: None,
+        expire_after_occurrence: None,
+        predicate,
+        action: HookAction::Noop,
+        include_proof: false,
+        include_inputs: false,
+        include_outputs: false,
+        include_witness: false,
+        proof_format: ProofFormat::Hex,
+        amount_format: AmountFormat::Number,
+        include_brc20_balance_deltas: false,
+        include_brc20_operation: false,
+        max_transactions_per_block: None,
+        skip_rollback_only_occurrences: false,
+        digest: None,
+        omit_empty_arrays: false,
+        payload_shape: PayloadShape::Block,
+        include_empty_blocks: false,
+        include_size: false,
+        include_version: false,
+        include_dropped: false,
+        enabled: true,
+        expired_at: None,
+        parsed_address: PredicateAddress::default(),
+    }
+}
+
 #[test_case(
-    "0x6affAAAA",
+    "0x6a02AAAA",
      MatchingRule::Equals(String::from("0xAAAA")),
     true;
     "OpReturn: Equals matches Hex value"
 )]
 #[test_case(
-    "0x60ff0000",
+    "0x60020000",
      MatchingRule::Equals(String::from("0x0000")),
     false;
     "OpReturn: Invalid OP_RETURN opcode"
 )]
 #[test_case(
-    "0x6aff012345",
+    "0x6a03012345",
      MatchingRule::Equals(String::from("0x0000")),
     false;
     "OpReturn: Equals does not match Hex value"
 )]
 #[test_case(
-    "0x6aff68656C6C6F",
+    "0x6a0568656C6C6F",
      MatchingRule::Equals(String::from("hello")),
     true;
     "OpReturn: Equals matches ASCII value"
 )]
 #[test_case(
-    "0x6affAA0000",
+    "0x6a03AA0000",
      MatchingRule::StartsWith(String::from("0xAA")),
     true;
     "OpReturn: StartsWith matches Hex value"
 )]
 #[test_case(
-    "0x6aff585858", // 0x585858 => XXX
+    "0x6a03585858", // 0x585858 => XXX
      MatchingRule::StartsWith(String::from("X")),
     true;
     "OpReturn: StartsWith matches ASCII value"
 )]
 #[test_case(
-    "0x6aff0000AA",
+    "0x6a030000AA",
      MatchingRule::EndsWith(String::from("0xAA")),
     true;
     "OpReturn: EndsWith matches Hex value"
 )]
 #[test_case(
-    "0x6aff000058",
+    "0x6a03000058",
      MatchingRule::EndsWith(String::from("X")),
     true;
     "OpReturn: EndsWith matches ASCII value"
 )]
+#[test_case(
+    "0x6a0568656C6C6F",
+     MatchingRule::Regex(MatchingRegex::try_from(String::from("^68656c")).unwrap()),
+    true;
+    "OpReturn: Regex matches Hex value"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+     MatchingRule::Regex(MatchingRegex::try_from(String::from("^hel+o$")).unwrap()),
+    true;
+    "OpReturn: Regex matches ASCII value"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+     MatchingRule::Regex(MatchingRegex::try_from(String::from("ell")).unwrap()),
+    true;
+    "OpReturn: Regex is not implicitly anchored"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+     MatchingRule::Regex(MatchingRegex::try_from(String::from("^ell")).unwrap()),
+    false;
+    "OpReturn: Regex is anchored by the pattern"
+)]
+#[test_case(
+    "0x6affzz",
+     MatchingRule::StartsWith(String::from("0x")),
+    false;
+    "OpReturn: non-hex script_pubkey does not match"
+)]
+#[test_case(
+    "0x6a4c0568656C6C6F",
+     MatchingRule::Equals(String::from("hello")),
+    true;
+    "OpReturn: Equals matches OP_PUSHDATA1 value"
+)]
+#[test_case(
+    "0x6a4d050068656C6C6F",
+     MatchingRule::Equals(String::from("hello")),
+    true;
+    "OpReturn: Equals matches OP_PUSHDATA2 value"
+)]
+#[test_case(
+    "0x6a0268654c036C6C6F",
+     MatchingRule::Equals(String::from("hello")),
+    true;
+    "OpReturn: Equals matches the data of multiple pushes"
+)]
+#[test_case(
+    "0x6a4c0568656C",
+     MatchingRule::StartsWith(String::from("he")),
+    false;
+    "OpReturn: truncated push does not match"
+)]
+#[test_case(
+    "0x6a4c0568656C",
+     MatchingRule::Equals(String::from("0x4c0568656c")),
+    true;
+    "OpReturn: truncated push matches the raw bytes"
+)]
+#[test_case(
+    "0x6a5d0568656C6C6F",
+     MatchingRule::Equals(String::from("hello")),
+    true;
+    "OpReturn: Equals skips the Runestone OP_13 tag"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+    MatchingRule::AtOffset(OffsetMatchingRule { offset: 2, value: String::from("ll") }),
+    true;
+    "OpReturn: AtOffset matches ASCII value at offset"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+    MatchingRule::AtOffset(OffsetMatchingRule { offset: 3, value: String::from("0x6C6F") }),
+    true;
+    "OpReturn: AtOffset matches hex value at offset"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+    MatchingRule::AtOffset(OffsetMatchingRule { offset: 1, value: String::from("ll") }),
+    false;
+    "OpReturn: AtOffset does not match value at another offset"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+    MatchingRule::AtOffset(OffsetMatchingRule { offset: 4, value: String::from("oo") }),
+    false;
+    "OpReturn: AtOffset does not match value overflowing the data"
+)]
+#[test_case(
+    "0x6a0568656C6C6F",
+    MatchingRule::AtOffset(OffsetMatchingRule { offset: 6, value: String::from("") }),
+    false;
+    "OpReturn: AtOffset does not match beyond the data length"
+)]
 fn test_opreturn_evaluation(script_pubkey: &str, rule: MatchingRule, matches: bool) {
     script_pubkey_evaluation(OutputPredicate::OpReturn(rule), script_pubkey, matches)
 }
 
+#[test_case(
+    "0x6a146f6d6e69000000000000001f000000002faf0800",
+    OpReturnProtocol::Omni,
+    true;
+    "OpReturnProtocol: matches Omni prefix"
+)]
+#[test_case(
+    "0x6a0458325b00",
+    OpReturnProtocol::Stacks,
+    true;
+    "OpReturnProtocol: matches Stacks prefix"
+)]
+#[test_case(
+    "0x6a0454325b00",
+    OpReturnProtocol::Stacks,
+    false;
+    "OpReturnProtocol: does not match Stacks testnet prefix"
+)]
+#[test_case(
+    "0x6a0454325b00",
+    OpReturnProtocol::StacksTestnet,
+    true;
+    "OpReturnProtocol: matches Stacks testnet prefix"
+)]
+fn test_opreturn_protocol_evaluation(
+    script_pubkey: &str,
+    protocol: OpReturnProtocol,
+    matches: bool,
+) {
+    script_pubkey_evaluation(
+        OutputPredicate::OpReturnProtocol(protocol),
+        script_pubkey,
+        matches,
+    )
+}
+
+#[test_case(
+    "0x210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac",
+    "0x0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    true;
+    "P2pk: matches compressed public key"
+)]
+#[test_case(
+    "0x210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac",
+    "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+    true;
+    "P2pk: matches address of compressed public key"
+)]
+#[test_case(
+    "0x410479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8ac",
+    "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+    true;
+    "P2pk: matches address of uncompressed public key"
+)]
+#[test_case(
+    "0x210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac",
+    "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+    false;
+    "P2pk: does not match address of another public key"
+)]
+#[test_case(
+    "0x76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+    "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+    false;
+    "P2pk: does not match p2pkh output"
+)]
+fn test_p2pk_evaluation(script_pubkey: &str, expected: &str, matches: bool) {
+    script_pubkey_evaluation(
+        OutputPredicate::P2pk(ExactMatchingRule::Equals(expected.to_string())),
+        script_pubkey,
+        matches,
+    )
+}
+
+#[test_case(
+    OutputPredicate::P2pkh(ExactMatchingRule::Equals("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH".into())),
+    "0x76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+    true;
+    "P2pkh: matches output paying to address"
+)]
+#[test_case(
+    OutputPredicate::P2pkh(ExactMatchingRule::Equals("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH".into())),
+    "0",
+    false;
+    "P2pkh: skips malformed script_pubkey"
+)]
+#[test_case(
+    OutputPredicate::P2wpkh(ExactMatchingRule::Equals("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".into())),
+    "0x0014751e76e8199196d454941c45d1b3a323f1433bd6",
+    true;
+    "P2wpkh: matches output paying to address"
+)]
+#[test_case(
+    OutputPredicate::P2wpkh(ExactMatchingRule::Equals("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".into())),
+    "0",
+    false;
+    "P2wpkh: skips malformed script_pubkey"
+)]
+fn test_address_evaluation(output: OutputPredicate, script_pubkey: &str, matches: bool) {
+    script_pubkey_evaluation(output, script_pubkey, matches)
+}
+
 // Descriptor test cases have been taken from
 // https://github.com/bitcoin/bitcoin/blob/master/doc/descriptors.md#examples
 // To generate the address run:
@@ -92,11 +345,8 @@ fn test_descriptor_evaluation(addr: &str, expr: &str) {
         .script_pubkey();
     let matching_script_pubkey = format!("0x{}", hex::encode(script_pubkey));
 
-    let rule = DescriptorMatchingRule {
-        expression: expr.to_string(),
-        // TODO: test ranges
-        range: None,
-    };
+    // TODO: test ranges
+    let rule = DescriptorMatchingRule::new(expr.to_string(), None, false);
 
     // matching against the script_pubkey generated from the address should match.
     script_pubkey_evaluation(
@@ -109,15 +359,272 @@ fn test_descriptor_evaluation(addr: &str, expr: &str) {
     script_pubkey_evaluation(OutputPredicate::Descriptor(rule.clone()), "0xffff", false);
 }
 
-// script_pubkey_evaluation is a helper that evaluates a a script_pubkey against a transaction predicate.
-fn script_pubkey_evaluation(output: OutputPredicate, script_pubkey: &str, matches: bool) {
-    let predicate = BitcoinPredicateType::Outputs(output);
+#[test]
+fn it_does_not_match_on_invalid_descriptor() {
+    let rule = DescriptorMatchingRule::new(
+        "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036)".to_string(),
+        None,
+        false,
+    );
+    // registering the predicate is rejected, but evaluating it must not panic either
+    assert!(rule.validate().is_err());
+    script_pubkey_evaluation(OutputPredicate::Descriptor(rule), "0xffff", false);
+}
 
-    let outputs = vec![TxOut {
-        value: 0,
-        script_pubkey: String::from(script_pubkey),
-    }];
+#[test]
+fn it_derives_descriptors_once() {
+    let rule = DescriptorMatchingRule::new(
+        "wpkh(xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/0/*)"
+            .to_string(),
+        None,
+        false,
+    );
+    let script_pubkeys = rule.get_script_pubkeys(None).unwrap();
+    assert_eq!(script_pubkeys.len(), 5);
+    // clones of the predicate share the derived script_pubkeys
+    assert!(Arc::ptr_eq(
+        &rule.clone().get_script_pubkeys(None).unwrap(),
+        &script_pubkeys
+    ));
+    // which are derived again when the default range changes
+    let script_pubkeys = rule.get_script_pubkeys(Some([0, 10])).unwrap();
+    assert_eq!(script_pubkeys.len(), 10);
+}
+
+#[test_case(false, vec![0]; "Descriptor: stops at the first matching output by default")]
+#[test_case(true, vec![0, 2]; "Descriptor: collects all matching outputs")]
+fn test_descriptor_matching_outputs(collect_all_matches: bool, expected_indices: Vec<usize>) {
+    let script_pubkey = Address::from_str("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let matching_script_pubkey = format!("0x{}", hex::encode(script_pubkey));
+
+    let rule = DescriptorMatchingRule::new(
+        "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)".to_string(),
+        None,
+        collect_all_matches,
+    );
+    let outputs = vec![
+        TxOut {
+            value: 1000,
+            script_pubkey: matching_script_pubkey.clone(),
+        },
+        TxOut {
+            value: 2000,
+            script_pubkey: String::from("0xffff"),
+        },
+        TxOut {
+            value: 3000,
+            script_pubkey: matching_script_pubkey,
+        },
+    ];
+    assert_eq!(
+        rule.get_matching_output_indices(&outputs, None).unwrap(),
+        expected_indices
+    );
+
+    let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    transaction.metadata.outputs = outputs;
+    let chainhook = BitcoinChainhookInstance {
+        network: BitcoinNetwork::Testnet,
+        include_outputs: true,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(
+            rule,
+        )))
+    };
+    let ctx = Context::empty();
+    assert!(chainhook
+        .predicate
+        .evaluate_transaction_predicate(&transaction, None, &ctx));
+
+    let serialized = serialize_bitcoin_transactions_to_json(
+        &chainhook,
+        &vec![&transaction],
+        &HashMap::new(),
+        None,
+    );
+    let matched_outputs = serialized[0]["metadata"].get("matched_outputs");
+    if collect_all_matches {
+        assert_eq!(matched_outputs, Some(&json!(expected_indices)));
+    } else {
+        assert_eq!(matched_outputs, None);
+    }
+}
+
+#[test_case("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem", true; "Denylist: matches a denylisted address")]
+#[test_case("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", false; "Denylist: ignores other addresses")]
+fn test_denylist_evaluation(paid_address: &str, matches: bool) {
+    let script_pubkey = Address::from_str(paid_address)
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let script_pubkey = format!("0x{}", hex::encode(script_pubkey));
+
+    let rule = DenylistMatchingRule::new(
+        vec![
+            "tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem".into(),
+            "2NBtBzAJ84E3sTy1KooEHYVwmMhUVdJAyEa".into(),
+        ],
+        None,
+    );
+    script_pubkey_evaluation(OutputPredicate::Denylist(rule), &script_pubkey, matches);
+}
+
+#[test_case("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", true; "AnyAddress: matches a legacy address")]
+#[test_case("2NBtBzAJ84E3sTy1KooEHYVwmMhUVdJAyEa", true; "AnyAddress: matches a p2sh address")]
+#[test_case("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem", true; "AnyAddress: matches a segwit address")]
+#[test_case("n1PJ7G8k1LqzW8eEuEBeJmT7dTPrcTbF1v", false; "AnyAddress: ignores other addresses")]
+fn test_any_address_evaluation(paid_address: &str, matches: bool) {
+    let script_pubkey = Address::from_str(paid_address)
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let script_pubkey = format!("0x{}", hex::encode(script_pubkey));
+
+    let rule = AddressSetRule::new(HashSet::from([
+        "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn".into(),
+        "2NBtBzAJ84E3sTy1KooEHYVwmMhUVdJAyEa".into(),
+        "tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem".into(),
+    ]));
+    assert!(rule.validate().is_ok());
+    script_pubkey_evaluation(OutputPredicate::AnyAddress(rule), &script_pubkey, matches);
+}
+
+#[test]
+fn test_denylist_reloads_modified_file() {
+    let path = std::env::temp_dir().join(format!("chainhook-denylist-{}.txt", std::process::id()));
+    std::fs::write(&path, "# flagged\n2NBtBzAJ84E3sTy1KooEHYVwmMhUVdJAyEa\n").unwrap();
+    let rule = DenylistMatchingRule::new(vec![], Some(path.to_string_lossy().to_string()));
+    assert!(rule.validate().is_ok());
+
+    let address = Address::from_str("tb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem")
+        .unwrap()
+        .assume_checked();
+    let script_pubkey = hex::encode(address.script_pubkey());
+    assert!(!rule.get_script_pubkeys().unwrap().contains(&script_pubkey));
+
+    // the modification time needs to move forward for the change to be picked up
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    std::fs::write(
+        &path,
+        "2NBtBzAJ84E3sTy1KooEHYVwmMhUVdJAyEa\ntb1q0ht9tyks4vh7p5p904t340cr9nvahy7um9zdem\n",
+    )
+    .unwrap();
+    // the file is only checked for modifications once per chain event, not on every lookup
+    assert!(!rule.get_script_pubkeys().unwrap().contains(&script_pubkey));
+    let reloaded = rule.reload_if_modified();
+    let _ = std::fs::remove_file(&path);
+    assert!(reloaded.is_ok());
+    assert!(rule.get_script_pubkeys().unwrap().contains(&script_pubkey));
+}
+
+#[test]
+fn test_denylist_file_errors_dont_echo_its_content() {
+    let path = std::env::temp_dir().join(format!(
+        "chainhook-invalid-denylist-{}.txt",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        "2NBtBzAJ84E3sTy1KooEHYVwmMhUVdJAyEa\nsecret-content\n",
+    )
+    .unwrap();
+    let rule = DenylistMatchingRule::new(vec![], Some(path.to_string_lossy().to_string()));
+    let result = rule.validate();
+    let _ = std::fs::remove_file(&path);
+    let e = result.unwrap_err();
+    assert!(e.contains("line 2"));
+    assert!(!e.contains("secret-content"));
+}
+
+#[test_case(99, false; "before genesis")]
+#[test_case(100, false; "at genesis")]
+#[test_case(101, false; "first block of the reward phase")]
+#[test_case(110, false; "last block of the reward phase")]
+#[test_case(111, true; "first block of the prepare phase")]
+#[test_case(114, true; "last block of the prepare phase")]
+#[test_case(115, false; "first block of the next reward phase")]
+#[test_case(125, true; "first block of the next prepare phase")]
+fn test_pox_prepare_phase(block_height: u64, in_prepare_phase: bool) {
+    // devnet: genesis at 100, 10 blocks of reward phase, 4 blocks of prepare phase
+    let pox_config = get_canonical_pox_config(&BitcoinNetwork::Regtest);
+    assert_eq!(
+        pox_config.is_in_prepare_phase(block_height),
+        in_prepare_phase
+    );
+}
+
+#[test]
+fn test_signet_stacks_parameters() {
+    assert_eq!(
+        get_stacks_canonical_magic_bytes(&BitcoinNetwork::Signet),
+        *b"T2"
+    );
+    let pox_config = get_canonical_pox_config(&BitcoinNetwork::Signet);
+    assert_eq!(pox_config.get_pox_cycle_len(), 1100);
+    assert_eq!(pox_config.get_pox_cycle_id(200_000), 181);
+    assert!(!pox_config.is_in_prepare_phase(1050));
+    assert!(pox_config.is_in_prepare_phase(1051));
+    assert_eq!(pox_config.get_burn_address(), "burn-address");
+}
 
+#[test]
+fn test_stacks_protocol_all_operations() {
+    let mut transactions = vec![];
+    let stacks_operations = vec![
+        vec![StacksBaseChainOperation::LeaderRegistered(
+            KeyRegistrationData,
+        )],
+        vec![],
+        vec![StacksBaseChainOperation::StxTransferred(TransferSTXData {
+            sender: accounts::wallet_1_stx_address(),
+            recipient: accounts::wallet_2_stx_address(),
+            amount: "1000".into(),
+        })],
+    ];
+    for (index, operations) in stacks_operations.iter().enumerate() {
+        let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            index as u64,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        );
+        transaction.metadata.stacks_operations = operations.clone();
+        transactions.push(transaction);
+    }
+    let block = generate_test_bitcoin_block(0, 0, transactions, None);
+    let chainhook =
+        get_test_bitcoin_chainhook(BitcoinPredicateType::StacksProtocol(StacksOperations::All));
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![block],
+            confirmed_blocks: vec![],
+        });
+    let ctx = Context::empty();
+    let (triggered, _, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+
+    assert_eq!(triggered.len(), 1);
+    let emitted_operations = triggered[0].apply[0]
+        .0
+        .iter()
+        .flat_map(|tx| tx.metadata.stacks_operations.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(emitted_operations, stacks_operations.concat());
+}
+
+#[test]
+fn test_outputs_predicates_skip_transactions_without_outputs() {
+    // an invalid descriptor would fail to derive, and panic, if evaluated
+    let predicate = BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(
+        DescriptorMatchingRule::new("not-a-descriptor".to_string(), None, false),
+    ));
     let tx = BitcoinTransactionData {
         transaction_identifier: TransactionIdentifier {
             hash: String::from(""),
@@ -126,97 +633,1692 @@ fn script_pubkey_evaluation(output: OutputPredicate, script_pubkey: &str, matche
         metadata: BitcoinTransactionMetadata {
             fee: 0,
             index: 0,
+            weight: 0,
+            vsize: 0,
+            version: 1,
             proof: None,
             inputs: vec![],
             stacks_operations: vec![],
             ordinal_operations: vec![],
             brc20_operation: None,
-            outputs,
+            outputs: vec![],
         },
     };
-
     let ctx = Context {
         logger: None,
         tracer: false,
     };
 
-    assert_eq!(matches, predicate.evaluate_transaction_predicate(&tx, &ctx));
+    assert!(!predicate.evaluate_transaction_predicate(&tx, None, &ctx));
 }
 
-#[test_case(
-    true, true, true, true;
-    "including all optional fields"
-)]
-#[test_case(
-    false, false, false, false;
-    "omitting all optional fields"
-)]
+#[test_case("0x76a914000000000000000000000000000000000000000088ac", Some(AddressType::P2pkh); "AddressType: classifies p2pkh")]
+#[test_case("0xa914000000000000000000000000000000000000000087", Some(AddressType::P2sh); "AddressType: classifies p2sh")]
+#[test_case("0x00140000000000000000000000000000000000000000", Some(AddressType::P2wpkh); "AddressType: classifies p2wpkh")]
+#[test_case("0x00200000000000000000000000000000000000000000000000000000000000000000", Some(AddressType::P2wsh); "AddressType: classifies p2wsh")]
+#[test_case("0x51200000000000000000000000000000000000000000000000000000000000000000", Some(AddressType::P2tr); "AddressType: classifies p2tr")]
+#[test_case("0x210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac", Some(AddressType::P2pk); "AddressType: classifies p2pk")]
+#[test_case("0x6a0358325b", None; "AddressType: does not classify op_return")]
+fn test_address_type_classification(script_pubkey: &str, address_type: Option<AddressType>) {
+    assert_eq!(AddressType::from_script_pubkey(script_pubkey), address_type);
+}
 
-fn it_serdes_occurrence_payload(
-    include_proof: bool,
-    include_inputs: bool,
-    include_outputs: bool,
-    include_witness: bool,
+#[test]
+fn test_address_type_matches_all_p2wpkh_outputs() {
+    let script_pubkeys = vec![
+        "0x00141111111111111111111111111111111111111111",
+        "0x76a914222222222222222222222222222222222222222288ac",
+        "0x00143333333333333333333333333333333333333333",
+        "0x51204444444444444444444444444444444444444444444444444444444444444444",
+    ];
+    let mut transactions = vec![];
+    for (index, script_pubkey) in script_pubkeys.iter().enumerate() {
+        let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            index as u64,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        );
+        transaction.metadata.outputs = vec![TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.to_string(),
+        }];
+        transactions.push(transaction);
+    }
+    let block = generate_test_bitcoin_block(0, 0, transactions.clone(), None);
+    let chainhook = get_test_bitcoin_chainhook(BitcoinPredicateType::Outputs(
+        OutputPredicate::AddressType(AddressType::P2wpkh),
+    ));
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![block],
+            confirmed_blocks: vec![],
+        });
+    let ctx = Context::empty();
+    let (triggered, _, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+
+    assert_eq!(triggered.len(), 1);
+    let matched_transactions = triggered[0].apply[0]
+        .0
+        .iter()
+        .map(|tx| tx.transaction_identifier.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        matched_transactions,
+        vec![
+            transactions[0].transaction_identifier.clone(),
+            transactions[2].transaction_identifier.clone(),
+        ]
+    );
+}
+
+#[test_case(None, None, false; "Descriptor: falls back to [0,5] without a default range")]
+#[test_case(None, Some([0, 10]), true; "Descriptor: applies the default range when the predicate omits one")]
+#[test_case(Some([0, 5]), Some([0, 10]), false; "Descriptor: predicate range takes precedence over the default range")]
+fn test_descriptor_default_range(
+    range: Option<[u32; 2]>,
+    default_range: Option<[u32; 2]>,
+    matches: bool,
 ) {
-    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+    let expression = "wpkh(xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/0/*)".to_string();
+    // derive the script_pubkey at index 7, outside of the [0,5] fallback range.
+    let (descriptor, _) =
+        Descriptor::parse_descriptor(&Secp256k1::signing_only(), &expression).unwrap();
+    let script_pubkey = descriptor
+        .derived_descriptor(&Secp256k1::verification_only(), 7)
+        .unwrap()
+        .script_pubkey();
+
+    let predicate = BitcoinPredicateType::Outputs(OutputPredicate::Descriptor(
+        DescriptorMatchingRule::new(expression, range, false),
+    ));
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
         0,
         &accounts::wallet_1_btc_address(),
         &accounts::wallet_3_btc_address(),
         3,
     );
-    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
-    let chainhook = &BitcoinChainhookInstance {
-        uuid: "uuid".into(),
-        owner_uuid: None,
-        name: "name".into(),
-        network: BitcoinNetwork::Mainnet,
-        version: 0,
-        blocks: None,
-        start_block: None,
-        end_block: None,
-        expire_after_occurrence: None,
-        predicate: BitcoinPredicateType::Block,
-        action: HookAction::Noop,
-        include_proof,
-        include_inputs,
-        include_outputs,
-        include_witness,
-        enabled: true,
-        expired_at: None,
-    };
-    let trigger = BitcoinTriggerChainhook {
-        chainhook,
-        apply: vec![(vec![&transaction], &block)],
-        rollback: vec![],
-    };
-    let payload = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
-        &trigger,
-        &HashMap::new(),
-    ))
-    .unwrap();
+    tx.metadata.outputs = vec![TxOut {
+        value: 1000,
+        script_pubkey: format!("0x{}", hex::encode(script_pubkey.as_bytes())),
+    }];
 
-    let _: BitcoinChainhookOccurrencePayload = serde_json::from_slice(&payload[..]).unwrap();
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, default_range, &ctx)
+    );
 }
 
-#[test_case(
-    "pepe".to_string();
-    "including brc20 data"
-)]
-fn it_serdes_brc20_payload(tick: String) {
-    let transaction = BitcoinTransactionData {
-        transaction_identifier: TransactionIdentifier {
-            hash: "0xc6191000459e4c58611103216e44547e512c01ee04119462644ee09ce9d8e8bb".to_string(),
-        },
-        operations: vec![],
-        metadata: BitcoinTransactionMetadata {
-            inputs: vec![],
-            outputs: vec![],
-            ordinal_operations: vec![],
-            stacks_operations: vec![],
-            brc20_operation: Some(Brc20Operation::Deploy(Brc20TokenDeployData {
-                tick,
-                max: "21000000.000000".to_string(),
-                lim: "1000.000000".to_string(),
+#[test_case(TransactionSizePredicate::Weight(SizeMatchingRule::HigherThan(4_000)), 561, false; "Weight: small transaction is not higher than threshold")]
+#[test_case(TransactionSizePredicate::Weight(SizeMatchingRule::HigherThan(4_000)), 400_000, true; "Weight: large transaction is higher than threshold")]
+#[test_case(TransactionSizePredicate::Vsize(SizeMatchingRule::LowerThan(1_000)), 561, true; "Vsize: small transaction is lower than threshold")]
+#[test_case(TransactionSizePredicate::Vsize(SizeMatchingRule::LowerThan(1_000)), 400_000, false; "Vsize: large transaction is not lower than threshold")]
+#[test_case(TransactionSizePredicate::Vsize(SizeMatchingRule::Between(100, 200)), 561, true; "Vsize: small transaction is within range")]
+#[test_case(TransactionSizePredicate::Vsize(SizeMatchingRule::Between(100, 200)), 400_000, false; "Vsize: large transaction is out of range")]
+#[test_case(TransactionSizePredicate::Vsize(SizeMatchingRule::Between(100, 200)), 400, true; "Vsize: lower bound is within range")]
+#[test_case(TransactionSizePredicate::Vsize(SizeMatchingRule::Between(100, 200)), 800, true; "Vsize: upper bound is within range")]
+#[test_case(TransactionSizePredicate::Vsize(SizeMatchingRule::Between(100, 200)), 801, false; "Vsize: past upper bound is out of range")]
+#[test_case(TransactionSizePredicate::Weight(SizeMatchingRule::Between(561, 561)), 561, true; "Weight: single value range")]
+fn test_transaction_size_evaluation(size: TransactionSizePredicate, weight: u64, matches: bool) {
+    let predicate = BitcoinPredicateType::TransactionSize(size);
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.weight = weight;
+    tx.metadata.vsize = weight.div_ceil(4);
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+fn build_inscription_revealed(content_length: usize) -> OrdinalOperation {
+    OrdinalOperation::InscriptionRevealed(OrdinalInscriptionRevealData {
+        content_bytes: "0x".to_string(),
+        content_type: "image/png".to_string(),
+        content_length,
+        inscription_number: OrdinalInscriptionNumber::zero(),
+        inscription_fee: 0,
+        inscription_output_value: 546,
+        inscription_id: format!("{}i0", "0".repeat(64)),
+        inscription_input_index: 0,
+        inscription_pointer: None,
+        inscriber_address: None,
+        delegate: None,
+        metaprotocol: None,
+        metadata: None,
+        parent: None,
+        ordinal_number: 0,
+        ordinal_block_height: 0,
+        ordinal_offset: 0,
+        tx_index: 0,
+        transfers_pre_inscription: 0,
+        satpoint_post_inscription: format!("{}:0:0", "0".repeat(64)),
+        curse_type: None,
+    })
+}
+
+#[test_case(vec![], false; "no inscription")]
+#[test_case(vec![1_000], false; "small inscription is not higher than threshold")]
+#[test_case(vec![390_000], true; "large inscription is higher than threshold")]
+#[test_case(vec![60_000, 60_000], true; "inscription sizes are summed")]
+fn test_inscription_content_size_evaluation(content_lengths: Vec<usize>, matches: bool) {
+    let predicate = BitcoinPredicateType::TransactionSize(
+        TransactionSizePredicate::InscriptionContent(SizeMatchingRule::HigherThan(100_000)),
+    );
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.ordinal_operations = content_lengths
+        .into_iter()
+        .map(build_inscription_revealed)
+        .collect();
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+#[test_case(ValuePredicate::GreaterThan(10_000), vec![546, 20_000], true; "GreaterThan: matches the first output above threshold")]
+#[test_case(ValuePredicate::GreaterThan(10_000), vec![546, 10_000], false; "GreaterThan: threshold is exclusive")]
+#[test_case(ValuePredicate::LessThan(546), vec![0, 20_000], true; "LessThan: matches zero value outputs")]
+#[test_case(ValuePredicate::LessThan(546), vec![546, 20_000], false; "LessThan: threshold is exclusive")]
+#[test_case(ValuePredicate::Between(546, 1_000), vec![1_000], true; "Between: bounds are inclusive")]
+#[test_case(ValuePredicate::Between(546, 1_000), vec![545, 1_001], false; "Between: values out of range")]
+fn test_output_value_evaluation(rule: ValuePredicate, values: Vec<u64>, matches: bool) {
+    let predicate = BitcoinPredicateType::Outputs(OutputPredicate::Value(rule));
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.outputs = values
+        .into_iter()
+        .map(|value| TxOut {
+            value,
+            script_pubkey: String::from("0x76a914000000000000000000000000000000000000000088ac"),
+        })
+        .collect();
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+#[test_case(VersionMatchingRule::Equals(2), 1, false; "Equals: version 1 transaction does not match version 2")]
+#[test_case(VersionMatchingRule::Equals(2), 2, true; "Equals: version 2 transaction matches version 2")]
+#[test_case(VersionMatchingRule::HigherThan(1), 1, false; "HigherThan: version 1 transaction is not higher than 1")]
+#[test_case(VersionMatchingRule::HigherThan(1), 2, true; "HigherThan: version 2 transaction is higher than 1")]
+#[test_case(VersionMatchingRule::LowerThan(2), 1, true; "LowerThan: version 1 transaction is lower than 2")]
+#[test_case(VersionMatchingRule::LowerThan(2), 2, false; "LowerThan: version 2 transaction is not lower than 2")]
+#[test_case(VersionMatchingRule::LowerThan(0), -1, true; "LowerThan: version -1 transaction is lower than 0")]
+fn test_transaction_version_evaluation(rule: VersionMatchingRule, version: i32, matches: bool) {
+    let predicate = BitcoinPredicateType::TransactionVersion(rule);
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.version = version;
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+#[test_case(IndexMatchingRule::Equals(0), 0, true; "Equals: coinbase transaction matches index 0")]
+#[test_case(IndexMatchingRule::Equals(0), 1, false; "Equals: first transaction after coinbase does not match index 0")]
+#[test_case(IndexMatchingRule::HigherThan(0), 1, true; "HigherThan: skips coinbase transaction")]
+#[test_case(IndexMatchingRule::LowerThan(2), 2, false; "LowerThan: index 2 is not lower than 2")]
+#[test_case(IndexMatchingRule::Between(1, 3), 1, true; "Between: lower bound is inclusive")]
+#[test_case(IndexMatchingRule::Between(1, 3), 3, true; "Between: upper bound is inclusive")]
+#[test_case(IndexMatchingRule::Between(1, 3), 4, false; "Between: index out of range")]
+fn test_transaction_index_evaluation(rule: IndexMatchingRule, index: u32, matches: bool) {
+    let predicate = BitcoinPredicateType::TransactionIndex(rule);
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.index = index;
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+#[test_case(91, 100, vec![10]; "top decile")]
+#[test_case(100, 100, vec![10]; "highest fee rate scores 100")]
+#[test_case(0, 10, vec![1]; "bottom decile")]
+#[test_case(40, 60, vec![4, 6, 5]; "bounds are inclusive")]
+fn fee_rate_percentile_evaluation(min: u8, max: u8, expected_fee_rates: Vec<u64>) {
+    // a coinbase transaction, followed by ten transactions paying 1 to 10 sat/vB in shuffled order
+    let fee_rates = [0, 4, 9, 1, 7, 10, 2, 6, 3, 8, 5];
+    let transactions = fee_rates
+        .iter()
+        .enumerate()
+        .map(|(index, fee_rate)| {
+            let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+                index as u64,
+                &accounts::wallet_1_btc_address(),
+                &accounts::wallet_3_btc_address(),
+                3,
+            );
+            tx.metadata.index = index as u32;
+            tx.metadata.vsize = 141;
+            tx.metadata.fee = fee_rate * 141;
+            tx
+        })
+        .collect::<Vec<_>>();
+    let chainhook = get_test_bitcoin_chainhook(BitcoinPredicateType::FeeRatePercentile(
+        FeeRatePercentileRule { min, max },
+    ));
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![generate_test_bitcoin_block(0, 1, transactions, None)],
+            confirmed_blocks: vec![],
+        });
+    let ctx = Context::empty();
+    let (triggered, _, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+
+    assert_eq!(triggered.len(), 1);
+    let matched_fee_rates = triggered[0]
+        .apply
+        .iter()
+        .flat_map(|(transactions, _)| transactions.iter())
+        .map(|tx| tx.metadata.fee / tx.metadata.vsize)
+        .collect::<Vec<_>>();
+    assert_eq!(matched_fee_rates, expected_fee_rates);
+}
+
+#[test_case("0x6a0358325b", BitcoinNetwork::Mainnet, StacksOpcodes::BlockCommit as u8, true; "Opcode: matches block commit on mainnet")]
+#[test_case("0x6a0369645b", BitcoinNetwork::Regtest, StacksOpcodes::BlockCommit as u8, true; "Opcode: matches block commit on devnet")]
+#[test_case("0x6a0369645b", BitcoinNetwork::Mainnet, StacksOpcodes::BlockCommit as u8, false; "Opcode: does not match the magic bytes of another network")]
+#[test_case("0x6a0358325b", BitcoinNetwork::Mainnet, StacksOpcodes::TransferStx as u8, false; "Opcode: does not match another opcode")]
+#[test_case("0x6a036f6d5b", BitcoinNetwork::Mainnet, StacksOpcodes::BlockCommit as u8, false; "Opcode: does not match without Stacks magic bytes")]
+#[test_case("0x6a0358327a", BitcoinNetwork::Mainnet, b'z', true; "Opcode: matches an opcode not modeled by StacksOpcodes")]
+fn test_stacks_opcode_evaluation(
+    script_pubkey: &str,
+    network: BitcoinNetwork,
+    opcode: u8,
+    matches: bool,
+) {
+    let mut chainhook = get_test_bitcoin_chainhook(BitcoinPredicateType::StacksProtocol(
+        StacksOperations::Opcode { opcode },
+    ));
+    chainhook.network = network;
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.outputs = vec![TxOut {
+        value: 0,
+        script_pubkey: String::from(script_pubkey),
+    }];
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![generate_test_bitcoin_block(0, 1, vec![tx], None)],
+            confirmed_blocks: vec![],
+        });
+
+    let ctx = Context::empty();
+    let (triggered, _, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+    assert_eq!(matches, !triggered.is_empty());
+}
+
+#[test_case(None, None, true; "BlockCommitted: matches any parent")]
+#[test_case(Some(103), Some(1), true; "BlockCommitted: matches the given parent")]
+#[test_case(Some(103), None, true; "BlockCommitted: matches the given parent block")]
+#[test_case(Some(104), Some(1), false; "BlockCommitted: does not match another parent block")]
+#[test_case(Some(103), Some(2), false; "BlockCommitted: does not match another parent vtxindex")]
+fn test_block_committed_parent_evaluation(
+    parent_block_ptr: Option<u32>,
+    parent_vtxindex: Option<u16>,
+    matches: bool,
+) {
+    let predicate = BitcoinPredicateType::StacksProtocol(StacksOperations::BlockCommitted {
+        parent_block_ptr,
+        parent_vtxindex,
+    });
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.stacks_operations = vec![StacksBaseChainOperation::BlockCommitted(
+        StacksBlockCommitmentData {
+            block_hash: "0x5b1681995f8e568287e0e4f5cbc1d6727dafb5e3a7822a77c69bd04208265aca".into(),
+            pox_cycle_index: 0,
+            pox_cycle_length: 0,
+            pox_cycle_position: 0,
+            pox_cycle_in_prepare_phase: false,
+            pox_sats_burnt: 10000,
+            pox_sats_transferred: vec![],
+            mining_address_post_commit: None,
+            mining_sats_left: 0,
+            parent_block_ptr: Some(103),
+            parent_vtxindex: Some(1),
+            anchors_tenure_change: false,
+        },
+    )];
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+#[test_case(true, true; "TenureChanged: matches a Nakamoto block commit")]
+#[test_case(false, false; "TenureChanged: does not match an earlier block commit")]
+fn test_tenure_changed_evaluation(anchors_tenure_change: bool, matches: bool) {
+    let predicate = BitcoinPredicateType::StacksProtocol(StacksOperations::TenureChanged);
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.stacks_operations = vec![StacksBaseChainOperation::BlockCommitted(
+        StacksBlockCommitmentData {
+            block_hash: "0x5b1681995f8e568287e0e4f5cbc1d6727dafb5e3a7822a77c69bd04208265aca".into(),
+            pox_cycle_index: 0,
+            pox_cycle_length: 0,
+            pox_cycle_position: 0,
+            pox_cycle_in_prepare_phase: false,
+            pox_sats_burnt: 10000,
+            pox_sats_transferred: vec![],
+            mining_address_post_commit: None,
+            mining_sats_left: 0,
+            parent_block_ptr: Some(103),
+            parent_vtxindex: Some(1),
+            anchors_tenure_change,
+        },
+    )];
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+#[test_case(None, None, None, true; "StxTransferred: matches any transfer")]
+#[test_case(Some(1000), None, None, true; "StxTransferred: matches a transfer above the minimum amount")]
+#[test_case(Some(1001), None, None, false; "StxTransferred: does not match a transfer below the minimum amount")]
+#[test_case(None, Some(accounts::wallet_1_stx_address()), None, true; "StxTransferred: matches the given sender")]
+#[test_case(None, Some(accounts::wallet_2_stx_address()), None, false; "StxTransferred: does not match another sender")]
+#[test_case(Some(1000), None, Some(accounts::wallet_2_stx_address()), true; "StxTransferred: matches the given recipient")]
+#[test_case(None, None, Some(accounts::wallet_3_stx_address()), false; "StxTransferred: does not match another recipient")]
+fn test_stx_transferred_evaluation(
+    min_amount: Option<u64>,
+    sender: Option<String>,
+    recipient: Option<String>,
+    matches: bool,
+) {
+    let predicate = BitcoinPredicateType::StacksProtocol(StacksOperations::StxTransferred {
+        min_amount,
+        sender,
+        recipient,
+    });
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.stacks_operations =
+        vec![StacksBaseChainOperation::StxTransferred(TransferSTXData {
+            sender: accounts::wallet_1_stx_address(),
+            recipient: accounts::wallet_2_stx_address(),
+            amount: "1000".into(),
+        })];
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+// script_pubkey_evaluation is a helper that evaluates a a script_pubkey against a transaction predicate.
+fn script_pubkey_evaluation(output: OutputPredicate, script_pubkey: &str, matches: bool) {
+    let predicate = BitcoinPredicateType::Outputs(output);
+
+    let outputs = vec![TxOut {
+        value: 0,
+        script_pubkey: String::from(script_pubkey),
+    }];
+
+    let tx = BitcoinTransactionData {
+        transaction_identifier: TransactionIdentifier {
+            hash: String::from(""),
+        },
+        operations: vec![],
+        metadata: BitcoinTransactionMetadata {
+            fee: 0,
+            index: 0,
+            weight: 0,
+            vsize: 0,
+            version: 1,
+            proof: None,
+            inputs: vec![],
+            stacks_operations: vec![],
+            ordinal_operations: vec![],
+            brc20_operation: None,
+            outputs,
+        },
+    };
+
+    let ctx = Context {
+        logger: None,
+        tracer: false,
+    };
+
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+// p2pkh spend: <sig> <pubkey>
+const P2PKH_SCRIPT_SIG: &str = "0x483045022100a20f90e9e3c3bb7e558ad4fa65902d8cf6ce4bff1f5af0ac0a323b547385069c022021b9877abbc9d1eef175c7f712ac1b2d8f5ce566be542714effe42711e75b83801210239810ebf35e6f6c26062c99f3e183708d377720617c90a986859ec9c95d00be9";
+
+#[test_case(
+    P2PKH_SCRIPT_SIG,
+    MatchingRule::StartsWith(String::from("0x483045022100")),
+    true;
+    "ScriptSig: StartsWith matches signature push prefix"
+)]
+#[test_case(
+    P2PKH_SCRIPT_SIG,
+    MatchingRule::StartsWith(String::from("0x483045022100A20F")),
+    true;
+    "ScriptSig: StartsWith matches uppercase pattern"
+)]
+#[test_case(
+    P2PKH_SCRIPT_SIG,
+    MatchingRule::StartsWith(String::from("0x473044")),
+    false;
+    "ScriptSig: StartsWith does not match another prefix"
+)]
+#[test_case(
+    P2PKH_SCRIPT_SIG,
+    MatchingRule::EndsWith(String::from("0x0239810ebf35e6f6c26062c99f3e183708d377720617c90a986859ec9c95d00be9")),
+    true;
+    "ScriptSig: EndsWith matches public key"
+)]
+#[test_case(
+    P2PKH_SCRIPT_SIG,
+    MatchingRule::Equals(String::from(P2PKH_SCRIPT_SIG)),
+    true;
+    "ScriptSig: Equals matches whole script"
+)]
+#[test_case(
+    "0x",
+    MatchingRule::StartsWith(String::from("0x")),
+    false;
+    "ScriptSig: skips segwit inputs with empty script_sig"
+)]
+fn script_sig_evaluation(script_sig: &str, rule: MatchingRule, matches: bool) {
+    let predicate = BitcoinPredicateType::Inputs(InputPredicate::ScriptSig(rule));
+
+    let inputs = vec![TxIn {
+        previous_output: OutPoint {
+            txid: TransactionIdentifier {
+                hash: String::from(""),
+            },
+            vout: 0,
+            value: 0,
+            block_height: 0,
+        },
+        script_sig: String::from(script_sig),
+        sequence: 0,
+        witness: vec![],
+    }];
+
+    let tx = BitcoinTransactionData {
+        transaction_identifier: TransactionIdentifier {
+            hash: String::from(""),
+        },
+        operations: vec![],
+        metadata: BitcoinTransactionMetadata {
+            fee: 0,
+            index: 0,
+            weight: 0,
+            vsize: 0,
+            version: 1,
+            proof: None,
+            inputs,
+            stacks_operations: vec![],
+            ordinal_operations: vec![],
+            brc20_operation: None,
+            outputs: vec![],
+        },
+    };
+
+    let ctx = Context {
+        logger: None,
+        tracer: false,
+    };
+
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+const P2WPKH_PUBKEY_WITNESS: &str =
+    "0x0239810ebf35e6f6c26062c99f3e183708d377720617c90a986859ec9c95d00be9";
+
+#[test_case(
+    vec![P2WPKH_PUBKEY_WITNESS],
+    MatchingRule::StartsWith(String::from("0x0239810E")),
+    true;
+    "WitnessScript: StartsWith matches hex value case insensitively"
+)]
+#[test_case(
+    vec!["0x3044", P2WPKH_PUBKEY_WITNESS],
+    MatchingRule::EndsWith(String::from("0x9c95d00be9")),
+    true;
+    "WitnessScript: EndsWith matches any witness item"
+)]
+#[test_case(
+    vec![P2WPKH_PUBKEY_WITNESS],
+    MatchingRule::Equals(String::from(P2WPKH_PUBKEY_WITNESS)),
+    true;
+    "WitnessScript: Equals matches whole witness item"
+)]
+#[test_case(
+    vec!["0x68656c6c6f"],
+    MatchingRule::Equals(String::from("hello")),
+    true;
+    "WitnessScript: Equals matches ASCII value"
+)]
+#[test_case(
+    vec![P2WPKH_PUBKEY_WITNESS],
+    MatchingRule::Equals(String::from("0x0239810ebf")),
+    false;
+    "WitnessScript: Equals does not match witness item prefix"
+)]
+#[test_case(
+    vec![],
+    MatchingRule::StartsWith(String::from("0x")),
+    false;
+    "WitnessScript: skips legacy inputs with empty witness"
+)]
+fn witness_script_evaluation(witness: Vec<&str>, rule: MatchingRule, matches: bool) {
+    let predicate = BitcoinPredicateType::Inputs(InputPredicate::WitnessScript(rule));
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.inputs = vec![TxIn {
+        previous_output: OutPoint {
+            txid: TransactionIdentifier {
+                hash: String::from(""),
+            },
+            vout: 0,
+            value: 0,
+            block_height: 0,
+        },
+        script_sig: String::from("0x"),
+        sequence: 0,
+        witness: witness.into_iter().map(String::from).collect(),
+    }];
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+const CONSOLIDATION_PUBKEY: &str =
+    "0239810ebf35e6f6c26062c99f3e183708d377720617c90a986859ec9c95d00be9";
+const OTHER_PUBKEY: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+const SIGNATURE: &str = "3045022100a20f90e9e3c3bb7e558ad4fa65902d8cf6ce4bff1f5af0ac0a323b547385069c022021b9877abbc9d1eef175c7f712ac1b2d8f5ce566be542714effe42711e75b83801";
+
+/// Builds an input spending a p2pkh output locked to `pubkey`: <sig> <pubkey>
+fn p2pkh_input(pubkey: &str) -> TxIn {
+    TxIn {
+        previous_output: OutPoint {
+            txid: TransactionIdentifier {
+                hash: String::from(""),
+            },
+            vout: 0,
+            value: 0,
+            block_height: 0,
+        },
+        script_sig: format!("0x48{}21{}", SIGNATURE, pubkey),
+        sequence: 0,
+        witness: vec![],
+    }
+}
+
+/// Builds an input spending a p2wpkh output locked to `pubkey`, from an empty `script_sig`.
+fn p2wpkh_input(pubkey: &str) -> TxIn {
+    TxIn {
+        script_sig: String::from("0x"),
+        witness: vec![format!("0x{}", SIGNATURE), format!("0x{}", pubkey)],
+        ..p2pkh_input(pubkey)
+    }
+}
+
+#[test_case(vec![p2pkh_input(CONSOLIDATION_PUBKEY); 3], 2, true; "p2pkh inputs exceeding threshold")]
+#[test_case(vec![p2pkh_input(CONSOLIDATION_PUBKEY); 3], 3, false; "p2pkh inputs at threshold")]
+#[test_case(
+    vec![
+        p2pkh_input(CONSOLIDATION_PUBKEY),
+        p2pkh_input(OTHER_PUBKEY),
+        p2pkh_input(OTHER_PUBKEY),
+    ],
+    1,
+    false;
+    "inputs from other addresses are not counted"
+)]
+#[test_case(vec![p2wpkh_input(CONSOLIDATION_PUBKEY); 3], 2, false; "p2wpkh inputs spend another address")]
+fn consolidation_evaluation(inputs: Vec<TxIn>, more_than: u64, matches: bool) {
+    let pubkey = PublicKey::from_str(CONSOLIDATION_PUBKEY).unwrap();
+    let predicate =
+        BitcoinPredicateType::Inputs(InputPredicate::Consolidation(ConsolidationPredicate {
+            address: Address::p2pkh(&pubkey, Network::Bitcoin).to_string(),
+            more_than,
+        }));
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.inputs = inputs;
+
+    let ctx = Context::empty();
+    assert_eq!(
+        matches,
+        predicate.evaluate_transaction_predicate(&tx, None, &ctx)
+    );
+}
+
+#[test]
+fn consolidation_evaluation_reconstructs_p2wpkh_addresses() {
+    let pubkey = PublicKey::from_str(CONSOLIDATION_PUBKEY).unwrap();
+    let predicate =
+        BitcoinPredicateType::Inputs(InputPredicate::Consolidation(ConsolidationPredicate {
+            address: Address::p2wpkh(&pubkey, Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            more_than: 1,
+        }));
+    let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    tx.metadata.inputs = vec![p2wpkh_input(CONSOLIDATION_PUBKEY); 2];
+
+    let ctx = Context::empty();
+    assert!(predicate.evaluate_transaction_predicate(&tx, None, &ctx));
+}
+
+const CHAINED_TXID: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+
+#[test_case(true, vec![0, 1, 2]; "chained spends are matched")]
+#[test_case(false, vec![0]; "chained spends are ignored by default")]
+fn inputs_txid_chained_evaluation(chained: bool, expected_indices: Vec<usize>) {
+    let mut transactions = vec![];
+    // the first transaction spends the watched output, the next ones each spend the previous one,
+    // and the last one is unrelated
+    for index in 0..4 {
+        let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            index as u64 + 1,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        );
+        let previous_txid = match index {
+            0 => CHAINED_TXID.to_string(),
+            3 => String::from("0x2222222222222222222222222222222222222222222222222222222222222222"),
+            _ => transactions
+                .last()
+                .map(|tx: &BitcoinTransactionData| tx.transaction_identifier.hash.clone())
+                .unwrap(),
+        };
+        transaction.metadata.inputs = vec![TxIn {
+            previous_output: OutPoint {
+                txid: TransactionIdentifier {
+                    hash: previous_txid,
+                },
+                vout: 0,
+                value: 0,
+                block_height: 0,
+            },
+            script_sig: String::from("0x"),
+            sequence: 0,
+            witness: vec![],
+        }];
+        transactions.push(transaction);
+    }
+    // the chain is split across blocks of the same event
+    let blocks = vec![
+        generate_test_bitcoin_block(0, 1, transactions[0..2].to_vec(), None),
+        generate_test_bitcoin_block(0, 2, transactions[2..4].to_vec(), None),
+    ];
+    let chainhook = get_test_bitcoin_chainhook(BitcoinPredicateType::Inputs(InputPredicate::Txid(
+        TxinPredicate {
+            txid: CHAINED_TXID.to_string(),
+            vout: 0,
+            chained,
+        },
+    )));
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: blocks,
+            confirmed_blocks: vec![],
+        });
+    let ctx = Context::empty();
+    let (triggered, _, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+
+    assert_eq!(triggered.len(), 1);
+    let matched_transactions = triggered[0]
+        .apply
+        .iter()
+        .flat_map(|(transactions, _)| transactions.iter())
+        .map(|tx| tx.transaction_identifier.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        matched_transactions,
+        expected_indices
+            .into_iter()
+            .map(|index| transactions[index].transaction_identifier.clone())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test_case(
+    true, true, true, true;
+    "including all optional fields"
+)]
+#[test_case(
+    false, false, false, false;
+    "omitting all optional fields"
+)]
+
+fn it_serdes_occurrence_payload(
+    include_proof: bool,
+    include_inputs: bool,
+    include_outputs: bool,
+    include_witness: bool,
+) {
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
+    let chainhook = &BitcoinChainhookInstance {
+        include_proof,
+        include_inputs,
+        include_outputs,
+        include_witness,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let payload = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
+        &trigger,
+        &HashMap::new(),
+        None,
+    ))
+    .unwrap();
+
+    let _: BitcoinChainhookOccurrencePayload = serde_json::from_slice(&payload[..]).unwrap();
+}
+
+#[test_case(
+    HookAction::HttpPost(HttpHook {
+        url: "http://localhost:20455/chainhook".into(),
+        authorization_header: "Bearer token".into(),
+        failover_urls: vec![],
+        signing_secret_id: None,
+        verification_url: None,
+    }),
+    BitcoinChainhookOccurrenceAction::HttpPost;
+    "http post action"
+)]
+#[test_case(
+    HookAction::FileAppend(FileHook {
+        path: "occurrences.json".into(),
+    }),
+    BitcoinChainhookOccurrenceAction::FileAppend;
+    "file append action"
+)]
+#[test_case(
+    HookAction::UnixSocket(UnixSocketHook {
+        path: "chainhook.sock".into(),
+    }),
+    BitcoinChainhookOccurrenceAction::UnixSocket;
+    "unix socket action"
+)]
+#[cfg_attr(feature = "websocket", test_case(
+    HookAction::WebSocket(WebSocketHook {
+        url: "ws://localhost:20456".into(),
+    }),
+    BitcoinChainhookOccurrenceAction::WebSocket;
+    "websocket action"
+))]
+#[test_case(HookAction::Noop, BitcoinChainhookOccurrenceAction::Noop; "noop action")]
+fn it_converts_occurrence_to_record(
+    action: HookAction,
+    expected_action: BitcoinChainhookOccurrenceAction,
+) {
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
+    let chainhook = &BitcoinChainhookInstance {
+        action: action.clone(),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        None,
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let record = occurrence.to_record().unwrap();
+    assert_eq!(record.action, expected_action);
+
+    match (&occurrence, &action) {
+        (BitcoinChainhookOccurrence::Http(_, payload), HookAction::HttpPost(http)) => {
+            assert_eq!(record.url.as_ref(), Some(&http.url));
+            assert_eq!(record.path, None);
+            assert_eq!(record.payload, serde_json::to_value(payload).unwrap());
+        }
+        (BitcoinChainhookOccurrence::File(path, bytes), HookAction::FileAppend(_))
+        | (BitcoinChainhookOccurrence::UnixSocket(path, bytes), HookAction::UnixSocket(_)) => {
+            assert_eq!(record.url, None);
+            assert_eq!(record.path.as_ref(), Some(path));
+            assert_eq!(&serde_json::to_vec(&record.payload).unwrap(), bytes);
+        }
+        #[cfg(feature = "websocket")]
+        (BitcoinChainhookOccurrence::WebSocket(url, bytes), HookAction::WebSocket(_)) => {
+            assert_eq!(record.url.as_ref(), Some(url));
+            assert_eq!(record.path, None);
+            assert_eq!(&serde_json::to_vec(&record.payload).unwrap(), bytes);
+        }
+        (BitcoinChainhookOccurrence::Data(payload), HookAction::Noop) => {
+            assert_eq!(record.url, None);
+            assert_eq!(record.path, None);
+            assert_eq!(record.payload, serde_json::to_value(payload).unwrap());
+        }
+        _ => panic!("unexpected occurrence for action {:?}", action),
+    }
+
+    let serialized_record = serde_json::to_vec(&record).unwrap();
+    let deserialized_record: BitcoinChainhookOccurrenceRecord =
+        serde_json::from_slice(&serialized_record).unwrap();
+    assert_eq!(deserialized_record, record);
+}
+
+#[test]
+fn it_signs_http_payloads() {
+    // RFC 4231, test case 2
+    let body = b"what do ya want for nothing?".to_vec();
+    let expected_signature =
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+    assert_eq!(
+        sign_payload("Jefe", &body, ContentHashAlgorithm::Sha256).unwrap(),
+        expected_signature
+    );
+
+    let signing_secrets = SigningSecrets::new(HashMap::from([(
+        "hook-secret".to_string(),
+        "Jefe".to_string(),
+    )]));
+    assert!(!format!("{:?}", signing_secrets).contains("Jefe"));
+    let http = HttpHook {
+        url: "http://localhost:20455/chainhook".into(),
+        authorization_header: "Bearer token".into(),
+        failover_urls: vec![],
+        signing_secret_id: Some("hook-secret".into()),
+        verification_url: None,
+    };
+    // only the id of the secret is persisted along with the predicate
+    assert!(!serde_json::to_string(&http).unwrap().contains("Jefe"));
+    let request = http
+        .build_request(&Client::new(), &signing_secrets, body.clone())
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(
+        request.headers()[HOOK_SIGNATURE_HEADER].to_str().unwrap(),
+        expected_signature
+    );
+
+    // secrets missing from the server configuration fail the delivery
+    assert!(http
+        .build_request(&Client::new(), &SigningSecrets::default(), body.clone())
+        .is_err());
+
+    let http = HttpHook {
+        signing_secret_id: None,
+        ..http
+    };
+    let request = http
+        .build_request(&Client::new(), &SigningSecrets::default(), body)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert!(request.headers().get(HOOK_SIGNATURE_HEADER).is_none());
+}
+
+#[test]
+fn it_signs_http_payloads_with_the_configured_hash() {
+    // RFC 4231, test case 2
+    let body = b"what do ya want for nothing?".to_vec();
+    let signing_secrets = SigningSecrets::new(HashMap::from([(
+        "hook-secret".to_string(),
+        "Jefe".to_string(),
+    )]));
+    assert_eq!(
+        signing_secrets
+            .clone()
+            .with_hash(ContentHashAlgorithm::Sha512)
+            .unwrap()
+            .sign("hook-secret", &body)
+            .unwrap(),
+        "sha512=164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+    );
+    // non-cryptographic hashes can't sign payloads
+    assert!(signing_secrets
+        .with_hash(ContentHashAlgorithm::Xxh3)
+        .is_err());
+    assert!(sign_payload("Jefe", &body, ContentHashAlgorithm::Xxh3).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn it_writes_occurrences_to_unix_socket() {
+    use crate::utils::unix_socket_send;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+
+    let socket_path =
+        std::env::temp_dir().join(format!("chainhook-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let reader = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        line
+    });
+
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::UnixSocket(UnixSocketHook {
+            path: socket_path.display().to_string(),
+        }),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        None,
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let BitcoinChainhookOccurrence::UnixSocket(path, bytes) = occurrence else {
+        panic!("expected a unix socket occurrence");
+    };
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(unix_socket_send(
+            path,
+            bytes.clone(),
+            3,
+            10,
+            &Context::empty(),
+        ))
+        .unwrap();
+
+    let line = reader.join().unwrap();
+    let _ = std::fs::remove_file(&socket_path);
+    assert!(line.ends_with('\n'));
+    assert_eq!(line.trim_end().as_bytes(), &bytes[..]);
+    let payload: BitcoinChainhookOccurrencePayload = serde_json::from_str(&line).unwrap();
+    assert_eq!(payload.chainhook.uuid, "uuid");
+}
+
+#[cfg(feature = "websocket")]
+#[test]
+fn it_pushes_occurrences_to_websocket_in_order() {
+    use super::websocket_export::send_websocket_message;
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let hook = WebSocketHook {
+        url: format!("ws://127.0.0.1:{}", port),
+    };
+    assert!(HookAction::WebSocket(hook.clone()).validate().is_ok());
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::WebSocket(hook.clone()),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+
+    // occurrences are pushed before the server is up: they are buffered until the feed connects
+    let mut pushed = vec![];
+    for height in 1..=3 {
+        let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            height,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        );
+        let block = generate_test_bitcoin_block(0, height, vec![transaction.clone()], None);
+        let trigger = BitcoinTriggerChainhook {
+            chainhook,
+            apply: vec![(vec![&transaction], &block)],
+            rollback: vec![],
+            dropped: vec![],
+        };
+        let occurrence = handle_bitcoin_hook_action(
+            trigger,
+            &HashMap::new(),
+            None,
+            None,
+            &SigningSecrets::default(),
+        )
+        .unwrap();
+        let BitcoinChainhookOccurrence::WebSocket(url, bytes) = occurrence else {
+            panic!("expected a websocket occurrence");
+        };
+        assert_eq!(url, hook.url);
+        send_websocket_message(&url, bytes.clone(), &Context::empty()).unwrap();
+        pushed.push(String::from_utf8(bytes).unwrap());
+    }
+
+    let received = tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut websocket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut received = vec![];
+        while received.len() < 3 {
+            if let Some(Ok(Message::Text(text))) = websocket.next().await {
+                received.push(text);
+            }
+        }
+        received
+    });
+    assert_eq!(received, pushed);
+    let payload: BitcoinChainhookOccurrencePayload = serde_json::from_str(&received[2]).unwrap();
+    assert_eq!(payload.apply[0].block.block_identifier.index, 3);
+}
+
+#[test]
+fn it_sends_http_post_occurrences_with_injected_client() {
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = vec![];
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+        String::from_utf8_lossy(&request).to_lowercase()
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert("x-embedder", HeaderValue::from_static("injected"));
+    let http_client = Client::builder().default_headers(headers).build().unwrap();
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::HttpPost(HttpHook {
+            url: format!("http://127.0.0.1:{port}/chainhook"),
+            authorization_header: "Bearer token".into(),
+            failover_urls: vec![],
+            signing_secret_id: None,
+            verification_url: None,
+        }),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        Some(&http_client),
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let BitcoinChainhookOccurrence::Http(request, _) = occurrence else {
+        panic!("expected an http occurrence");
+    };
+    let _ = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(request.send());
+
+    let received = server.join().unwrap();
+    assert!(received.starts_with("post /chainhook"));
+    assert!(received.contains("x-embedder: injected"));
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn it_writes_matched_transactions_to_parquet_files() {
+    use super::parquet_export::BitcoinParquetWriters;
+    use crate::chainhooks::types::ParquetHook;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use arrow_array::{Array, StringArray, UInt64Array};
+
+    let dir = std::env::temp_dir().join(format!("chainhook-test-{}-parquet", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let transactions = (0..3)
+        .map(|i| {
+            generate_test_tx_bitcoin_p2pkh_transfer(
+                i,
+                &accounts::wallet_1_btc_address(),
+                &accounts::wallet_3_btc_address(),
+                3,
+            )
+        })
+        .collect::<Vec<_>>();
+    let block = generate_test_bitcoin_block(0, 42, transactions.clone(), None);
+    let hook = ParquetHook {
+        path: dir.display().to_string(),
+        max_file_size_mb: None,
+        max_file_age_sec: None,
+    };
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::Parquet(hook.clone()),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(transactions.iter().collect(), &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        None,
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let BitcoinChainhookOccurrence::Parquet(occurrence_hook, rows) = occurrence else {
+        panic!("expected a parquet occurrence");
+    };
+    assert_eq!(occurrence_hook, hook);
+    let list_files = || {
+        std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>()
+    };
+    let mut writers = BitcoinParquetWriters::new();
+    writers.append(&hook, &rows[..2]).unwrap();
+    writers.append(&hook, &rows[2..]).unwrap();
+    // the file being written can't be mistaken for a readable one
+    let files = list_files();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].display().to_string().ends_with(".parquet.tmp"));
+    writers.close_all().unwrap();
+
+    let files = list_files();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].extension().unwrap(), "parquet");
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&files[0]).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut txids = vec![];
+    for batch in reader {
+        let batch = batch.unwrap();
+        let heights = batch
+            .column_by_name("block_height")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert!(heights.iter().all(|height| height == Some(42)));
+        let batch_txids = batch
+            .column_by_name("txid")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        txids.extend(batch_txids.iter().map(|txid| txid.unwrap().to_string()));
+    }
+    let expected_txids = transactions
+        .iter()
+        .map(|tx| tx.transaction_identifier.hash.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(txids, expected_txids);
+
+    // files exceeding their age are closed without waiting for the next rows
+    let hook = ParquetHook {
+        max_file_age_sec: Some(0),
+        ..hook
+    };
+    writers.append(&hook, &rows).unwrap();
+    writers.close_expired().unwrap();
+    let files = list_files();
+    assert_eq!(files.len(), 2);
+    assert!(files
+        .iter()
+        .all(|file| file.extension().unwrap() == "parquet"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn it_fails_over_to_the_next_http_endpoint() {
+    use crate::utils::{send_request_with_failover, HttpRetryPolicy};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let serve = |status: &'static str| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buffer = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+            let _ = stream.write_all(response.as_bytes());
+            String::from_utf8_lossy(&request).to_lowercase()
+        });
+        (port, server)
+    };
+    let (primary_port, primary) = serve("500 Internal Server Error");
+    let (secondary_port, secondary) = serve("200 OK");
+
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::HttpPost(HttpHook {
+            url: format!("http://127.0.0.1:{primary_port}/chainhook"),
+            authorization_header: "Bearer token".into(),
+            failover_urls: vec![format!("http://127.0.0.1:{secondary_port}/failover")],
+            signing_secret_id: None,
+            verification_url: None,
+        }),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        None,
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let BitcoinChainhookOccurrence::Http(request, _) = occurrence else {
+        panic!("expected an http occurrence");
+    };
+    let mut healthy_endpoint = 0;
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(send_request_with_failover(
+            request,
+            chainhook.action.failover_urls(),
+            &mut healthy_endpoint,
+            &HttpRetryPolicy {
+                attempts_max: 1,
+                ..HttpRetryPolicy::default()
+            },
+            &Context::empty(),
+        ))
+        .unwrap();
+
+    assert!(primary.join().unwrap().starts_with("post /chainhook"));
+    let received = secondary.join().unwrap();
+    assert!(received.starts_with("post /failover"));
+    assert!(received.contains("authorization: bearer token"));
+    assert_eq!(healthy_endpoint, 1);
+}
+
+#[test]
+fn it_skips_deliveries_rejected_by_the_verification_url() {
+    use crate::utils::{send_request_with_verification, HttpRetryPolicy};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let verifier = TcpListener::bind("127.0.0.1:0").unwrap();
+    let verification_port = verifier.local_addr().unwrap().port();
+    let verification = std::thread::spawn(move || {
+        let (mut stream, _) = verifier.accept().unwrap();
+        let mut request = vec![];
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        let _ = stream.write_all(b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n");
+        String::from_utf8_lossy(&request).to_lowercase()
+    });
+    let primary = TcpListener::bind("127.0.0.1:0").unwrap();
+    primary.set_nonblocking(true).unwrap();
+    let primary_port = primary.local_addr().unwrap().port();
+
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::HttpPost(HttpHook {
+            url: format!("http://127.0.0.1:{primary_port}/chainhook"),
+            authorization_header: "Bearer token".into(),
+            failover_urls: vec![],
+            signing_secret_id: None,
+            verification_url: Some(format!("http://127.0.0.1:{verification_port}/verify")),
+        }),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        None,
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let BitcoinChainhookOccurrence::Http(request, data) = occurrence else {
+        panic!("expected an http occurrence");
+    };
+    let http_client = build_http_delivery_client(&HttpDeliveryConfig::default()).unwrap();
+    let verification_request = chainhook.action.build_verification_request(
+        &http_client,
+        &SigningSecrets::default(),
+        &data.intent(),
+    );
+    assert!(verification_request.is_some());
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(send_request_with_verification(
+            verification_request,
+            request,
+            chainhook.action.failover_urls(),
+            &mut 0,
+            &HttpRetryPolicy {
+                attempts_max: 1,
+                ..HttpRetryPolicy::default()
+            },
+            &Context::empty(),
+        ))
+        .unwrap();
+
+    let received = verification.join().unwrap();
+    assert!(received.starts_with("post /verify"));
+    assert!(received.contains("authorization: bearer token"));
+    // the full payload is never posted to the primary url
+    assert_eq!(
+        primary.accept().map(|_| ()).unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+}
+
+#[test]
+fn it_fails_deliveries_whose_verification_url_is_unreachable() {
+    use crate::utils::{send_request_with_verification, HttpRetryPolicy};
+    use std::net::TcpListener;
+
+    let verification_port = {
+        let verifier = TcpListener::bind("127.0.0.1:0").unwrap();
+        verifier.local_addr().unwrap().port()
+    };
+    let primary = TcpListener::bind("127.0.0.1:0").unwrap();
+    primary.set_nonblocking(true).unwrap();
+    let primary_port = primary.local_addr().unwrap().port();
+
+    let http_client = build_http_delivery_client(&HttpDeliveryConfig::default()).unwrap();
+    let verification_request =
+        Some(http_client.post(format!("http://127.0.0.1:{verification_port}/verify")));
+    let request = http_client.post(format!("http://127.0.0.1:{primary_port}/chainhook"));
+    let res = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(send_request_with_verification(
+            verification_request,
+            request,
+            &[],
+            &mut 0,
+            &HttpRetryPolicy {
+                attempts_max: 1,
+                ..HttpRetryPolicy::default()
+            },
+            &Context::empty(),
+        ));
+    assert!(res.is_err());
+    assert_eq!(
+        primary.accept().map(|_| ()).unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+}
+
+#[test_case(1, 1_000; "first retry waits the base delay")]
+#[test_case(3, 4_000; "delay doubles after each failure")]
+#[test_case(6, 30_000; "delay is capped")]
+#[test_case(u16::MAX, 30_000; "delay does not overflow")]
+fn it_computes_http_retry_backoff_delays(failures: u16, expected_delay_ms: u64) {
+    use crate::utils::HttpRetryPolicy;
+
+    let retry_policy = HttpRetryPolicy {
+        attempts_max: 5,
+        base_delay_ms: 1_000,
+        max_delay_ms: 30_000,
+        jitter: false,
+    };
+    assert_eq!(
+        retry_policy.get_backoff_delay_ms(failures),
+        expected_delay_ms
+    );
+}
+
+#[test_case(vec!["503 Service Unavailable", "200 OK"], None; "retries server errors")]
+#[test_case(vec!["429 Too Many Requests", "200 OK"], None; "retries rate limiting")]
+#[test_case(vec!["400 Bad Request"], Some("rejected without retry"); "fails fast on client errors")]
+#[test_case(
+    vec!["503 Service Unavailable", "503 Service Unavailable"],
+    Some("after 2 attempts");
+    "gives up after max attempts"
+)]
+fn it_retries_http_deliveries(statuses: Vec<&'static str>, expected_err: Option<&str>) {
+    use crate::utils::{send_request, HttpRetryPolicy};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        for status in statuses.into_iter() {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buffer = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+            let _ = stream.write_all(response.as_bytes());
+        }
+        // no further attempt is expected
+        listener.set_nonblocking(true).unwrap();
+        listener.accept().is_err()
+    });
+
+    let request = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{port}/chainhook"))
+        .body("{}");
+    let retry_policy = HttpRetryPolicy {
+        attempts_max: 2,
+        base_delay_ms: 1,
+        max_delay_ms: 1,
+        jitter: false,
+    };
+    let result = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(send_request(request, &retry_policy, &Context::empty()));
+
+    assert!(server.join().unwrap());
+    match expected_err {
+        None => result.unwrap(),
+        Some(expected_err) => assert!(result.unwrap_err().contains(expected_err)),
+    }
+}
+
+#[test_case(
+    true, false, true;
+    "including brc20 data"
+)]
+#[test_case(
+    false, false, false;
+    "omitting brc20 data from non-ordinals predicates"
+)]
+#[test_case(
+    false, true, true;
+    "including requested brc20 data in non-ordinals predicates"
+)]
+fn it_serdes_brc20_payload(
+    ordinals_predicate: bool,
+    include_brc20_operation: bool,
+    includes_brc20_data: bool,
+) {
+    let transaction = BitcoinTransactionData {
+        transaction_identifier: TransactionIdentifier {
+            hash: "0xc6191000459e4c58611103216e44547e512c01ee04119462644ee09ce9d8e8bb".to_string(),
+        },
+        operations: vec![],
+        metadata: BitcoinTransactionMetadata {
+            inputs: vec![],
+            outputs: vec![],
+            ordinal_operations: vec![],
+            stacks_operations: vec![],
+            brc20_operation: Some(Brc20Operation::Deploy(Brc20TokenDeployData {
+                tick: "pepe".to_string(),
+                max: "21000000.000000".to_string(),
+                lim: "1000.000000".to_string(),
                 dec: "6".to_string(),
                 address: "3P4WqXDbSLRhzo2H6MT6YFbvBKBDPLbVtQ".to_string(),
                 inscription_id:
@@ -226,49 +2328,1117 @@ fn it_serdes_brc20_payload(tick: String) {
             proof: None,
             fee: 0,
             index: 0,
+            weight: 0,
+            vsize: 0,
+            version: 1,
         },
     };
     let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
     let mut meta_protocols = HashSet::<OrdinalsMetaProtocol>::new();
     meta_protocols.insert(OrdinalsMetaProtocol::Brc20);
-    let chainhook = &BitcoinChainhookInstance {
-        uuid: "uuid".into(),
-        owner_uuid: None,
-        name: "name".into(),
-        network: BitcoinNetwork::Mainnet,
-        version: 0,
-        blocks: None,
-        start_block: None,
-        end_block: None,
-        expire_after_occurrence: None,
-        predicate: BitcoinPredicateType::OrdinalsProtocol(OrdinalOperations::InscriptionFeed(
+    let predicate = if ordinals_predicate {
+        BitcoinPredicateType::OrdinalsProtocol(OrdinalOperations::InscriptionFeed(
             InscriptionFeedData {
                 meta_protocols: Some(meta_protocols),
             },
-        )),
-        action: HookAction::Noop,
-        include_proof: false,
-        include_inputs: false,
-        include_outputs: false,
-        include_witness: false,
-        enabled: true,
-        expired_at: None,
+        ))
+    } else {
+        BitcoinPredicateType::Block
+    };
+    let chainhook = &BitcoinChainhookInstance {
+        include_brc20_operation,
+        ..get_test_bitcoin_chainhook(predicate)
     };
     let trigger = BitcoinTriggerChainhook {
         chainhook,
         apply: vec![(vec![&transaction], &block)],
         rollback: vec![],
+        dropped: vec![],
     };
     let payload = serde_json::to_vec(&serialize_bitcoin_payload_to_json(
         &trigger,
         &HashMap::new(),
+        None,
     ))
     .unwrap();
 
     let deserialized: BitcoinChainhookOccurrencePayload =
         serde_json::from_slice(&payload[..]).unwrap();
-    assert!(deserialized.apply[0].block.transactions[0]
-        .metadata
-        .brc20_operation
-        .is_some());
+    assert_eq!(
+        deserialized.apply[0].block.transactions[0]
+            .metadata
+            .brc20_operation
+            .is_some(),
+        includes_brc20_data
+    );
+}
+
+fn build_brc20_balance_data() -> Brc20BalanceData {
+    Brc20BalanceData {
+        tick: "pepe".to_string(),
+        amt: "1000.000000".to_string(),
+        address: "3P4WqXDbSLRhzo2H6MT6YFbvBKBDPLbVtQ".to_string(),
+        inscription_id: "c6191000459e4c58611103216e44547e512c01ee04119462644ee09ce9d8e8bbi0"
+            .to_string(),
+    }
+}
+
+#[test_case(
+    Brc20Operation::Mint(build_brc20_balance_data()),
+    ("1000.000000", "0", "1000.000000");
+    "mint credits the available balance"
+)]
+#[test_case(
+    Brc20Operation::Transfer(build_brc20_balance_data()),
+    ("-1000.000000", "1000.000000", "0");
+    "transfer moves the amount to the transferrable balance"
+)]
+fn it_computes_brc20_balance_deltas(operation: Brc20Operation, expected: (&str, &str, &str)) {
+    let (available_balance, transferrable_balance, overall_balance) = expected;
+    assert_eq!(
+        get_brc20_balance_deltas(&operation),
+        vec![Brc20BalanceDelta {
+            tick: "pepe".to_string(),
+            address: "3P4WqXDbSLRhzo2H6MT6YFbvBKBDPLbVtQ".to_string(),
+            available_balance: available_balance.to_string(),
+            transferrable_balance: transferrable_balance.to_string(),
+            overall_balance: overall_balance.to_string(),
+        }]
+    );
+}
+
+#[test]
+fn it_computes_witness_discount_of_mixed_transaction() {
+    let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let input = |witness: Vec<String>| TxIn {
+        previous_output: OutPoint {
+            txid: TransactionIdentifier {
+                hash: String::from(""),
+            },
+            vout: 0,
+            value: 0,
+            block_height: 0,
+        },
+        script_sig: String::from("0x"),
+        sequence: 0,
+        witness,
+    };
+    // a legacy input, and a p2wpkh input with a 72 bytes signature and a 33 bytes public key
+    transaction.metadata.inputs = vec![
+        input(vec![]),
+        input(vec![
+            format!("0x{}", "30".repeat(72)),
+            String::from(P2WPKH_PUBKEY_WITNESS),
+        ]),
+    ];
+    // marker and flag, the empty witness of the legacy input, and the p2wpkh witness
+    let witness_size = 2 + 1 + (1 + (1 + 72) + (1 + 33));
+    transaction.metadata.weight = 200 * 4 + witness_size;
+
+    assert_eq!(
+        get_witness_discount(&transaction.metadata),
+        WitnessDiscount {
+            segwit: true,
+            witness_size,
+            stripped_size: 200,
+            discount_weight: 3 * witness_size,
+        }
+    );
+
+    transaction.metadata.inputs = vec![input(vec![])];
+    transaction.metadata.weight = 200 * 4;
+    assert_eq!(
+        get_witness_discount(&transaction.metadata),
+        WitnessDiscount {
+            segwit: false,
+            witness_size: 0,
+            stripped_size: 200,
+            discount_weight: 0,
+        }
+    );
+}
+
+#[test]
+fn it_truncates_transactions_exceeding_max_transactions_per_block() {
+    let transactions = (0..3)
+        .map(|index| {
+            generate_test_tx_bitcoin_p2pkh_transfer(
+                index,
+                &accounts::wallet_1_btc_address(),
+                &accounts::wallet_3_btc_address(),
+                3,
+            )
+        })
+        .collect::<Vec<_>>();
+    let block = generate_test_bitcoin_block(0, 0, transactions.clone(), None);
+    let chainhook = &BitcoinChainhookInstance {
+        max_transactions_per_block: Some(2),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(transactions.iter().collect(), &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let payload = serialize_bitcoin_payload_to_json(&trigger, &HashMap::new(), None);
+
+    let apply = &payload["apply"][0];
+    assert_eq!(apply["transactions"].as_array().unwrap().len(), 2);
+    assert_eq!(apply["truncated"], json!(true));
+    assert_eq!(apply["total_matches"], json!(3));
+}
+
+#[test]
+fn it_includes_predicate_name_in_payload() {
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 0, vec![transaction.clone()], None);
+    let chainhook = &BitcoinChainhookInstance {
+        name: "wrap-btc".into(),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let payload = serialize_bitcoin_payload_to_json(&trigger, &HashMap::new(), None);
+    assert_eq!(payload["chainhook"]["uuid"], json!("uuid"));
+    assert_eq!(payload["chainhook"]["name"], json!("wrap-btc"));
+
+    let occurrence = BitcoinChainhookOccurrencePayload::from_trigger(trigger);
+    assert_eq!(occurrence.chainhook.name, "wrap-btc");
+}
+
+#[cfg(feature = "kafka")]
+#[test_case(None, "uuid"; "keyed by predicate uuid by default")]
+#[test_case(Some("{name}/{uuid}"), "name/uuid"; "keyed by template")]
+fn it_produces_kafka_messages_keyed_by_predicate(key_template: Option<&str>, expected_key: &str) {
+    use crate::chainhooks::types::KafkaHook;
+
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 42, vec![transaction.clone()], None);
+    let hook = KafkaHook {
+        brokers: vec!["localhost:9092".into()],
+        topic: "chainhook".into(),
+        key_template: key_template.map(|template| template.to_string()),
+    };
+    assert!(HookAction::Kafka(hook.clone()).validate().is_ok());
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::Kafka(hook.clone()),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        None,
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let record = occurrence.to_record().unwrap();
+    let BitcoinChainhookOccurrence::Kafka(occurrence_hook, key, bytes) = occurrence else {
+        panic!("expected a kafka occurrence");
+    };
+    assert_eq!(occurrence_hook, hook);
+    assert_eq!(key, expected_key);
+    let payload: JsonValue = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(payload["chainhook"]["uuid"], json!("uuid"));
+    assert_eq!(record.action, BitcoinChainhookOccurrenceAction::Kafka);
+    assert_eq!(record.path, Some("chainhook".into()));
+
+    let invalid_hook = KafkaHook {
+        brokers: vec![],
+        topic: "".into(),
+        key_template: Some("{block_height}".into()),
+    };
+    assert_eq!(invalid_hook.validate().unwrap_err().len(), 3);
+}
+
+#[cfg(feature = "grpc")]
+#[test]
+fn it_builds_grpc_occurrences() {
+    use crate::chainhooks::types::GrpcHook;
+
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 42, vec![transaction.clone()], None);
+    let hook = GrpcHook {
+        endpoint: "http://localhost:50051".into(),
+    };
+    assert!(HookAction::Grpc(hook.clone()).validate().is_ok());
+    let chainhook = &BitcoinChainhookInstance {
+        action: HookAction::Grpc(hook.clone()),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook,
+        apply: vec![(vec![&transaction], &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    let occurrence = handle_bitcoin_hook_action(
+        trigger,
+        &HashMap::new(),
+        None,
+        None,
+        &SigningSecrets::default(),
+    )
+    .unwrap();
+    let record = occurrence.to_record().unwrap();
+    let BitcoinChainhookOccurrence::Grpc(occurrence_hook, uuid, bytes) = occurrence else {
+        panic!("expected a grpc occurrence");
+    };
+    assert_eq!(occurrence_hook, hook);
+    assert_eq!(uuid, "uuid");
+    let payload: JsonValue = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(payload["chainhook"]["uuid"], json!("uuid"));
+    assert_eq!(record.action, BitcoinChainhookOccurrenceAction::Grpc);
+    assert_eq!(record.url, Some(hook.endpoint.clone()));
+
+    let invalid_hook = GrpcHook {
+        endpoint: "unix:///tmp/chainhook.sock".into(),
+    };
+    assert!(invalid_hook.validate().is_err());
+}
+
+#[cfg(feature = "sqs")]
+#[test]
+fn it_batches_sqs_occurrences() {
+    use super::sqs_export::build_batches;
+
+    let messages = (0..23)
+        .map(|i| (format!("uuid-{}", i), "{}".to_string()))
+        .collect::<Vec<_>>();
+    let batches = build_batches(messages.clone());
+    assert_eq!(
+        batches.iter().map(|batch| batch.len()).collect::<Vec<_>>(),
+        vec![10, 10, 3]
+    );
+    assert_eq!(batches.concat(), messages);
+
+    // batches are also capped to 256KiB
+    let large_messages = (0..3)
+        .map(|i| (format!("uuid-{}", i), "a".repeat(100 * 1024)))
+        .collect::<Vec<_>>();
+    assert_eq!(build_batches(large_messages).len(), 2);
+
+    let hook = SqsHook {
+        queue_url: "https://sqs.us-east-1.amazonaws.com/123456789012/chainhook.fifo".into(),
+        region: "us-east-1".into(),
+        deduplication_hash: None,
+    };
+    assert!(HookAction::Sqs(hook.clone()).validate().is_ok());
+    assert!(hook.is_fifo());
+    let invalid_hook = SqsHook {
+        queue_url: "chainhook".into(),
+        region: "".into(),
+        deduplication_hash: None,
+    };
+    assert_eq!(invalid_hook.validate().unwrap_err().len(), 2);
+}
+
+#[cfg(feature = "sqs")]
+#[test]
+fn it_rejects_oversized_sqs_occurrences() {
+    use super::sqs_export::{send_sqs_occurrences, MAX_MESSAGE_SIZE_BYTES};
+
+    let hook = SqsHook {
+        queue_url: "https://sqs.us-east-1.amazonaws.com/123456789012/chainhook.fifo".into(),
+        region: "us-east-1".into(),
+        deduplication_hash: None,
+    };
+    // rejected before any call to sqs
+    let failures = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(send_sqs_occurrences(
+            vec![(hook, "uuid".into(), vec![b'a'; MAX_MESSAGE_SIZE_BYTES + 1])],
+            ContentHashAlgorithm::default(),
+        ));
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, "uuid");
+    assert!(failures[0].1.contains("s3_append"));
+}
+
+#[cfg(feature = "sqs")]
+#[test_case(None, ContentHashAlgorithm::Sha256, "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"; "sha256 by default")]
+#[test_case(None, ContentHashAlgorithm::Xxh3, "dc7048f8f8747f561349cde127705c16"; "server hash")]
+#[test_case(Some(ContentHashAlgorithm::Sha256), ContentHashAlgorithm::Xxh3, "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"; "action hash over server hash")]
+#[test_case(Some(ContentHashAlgorithm::Xxh3), ContentHashAlgorithm::Sha256, "dc7048f8f8747f561349cde127705c16"; "xxh3")]
+fn it_selects_the_sqs_deduplication_hash(
+    deduplication_hash: Option<ContentHashAlgorithm>,
+    server_hash: ContentHashAlgorithm,
+    expected_id: &str,
+) {
+    let hook = SqsHook {
+        queue_url: "https://sqs.us-east-1.amazonaws.com/123456789012/chainhook.fifo".into(),
+        region: "us-east-1".into(),
+        deduplication_hash,
+    };
+    assert_eq!(hook.get_deduplication_id("{}", server_hash), expected_id);
+}
+
+#[cfg(feature = "s3")]
+#[test_case(None, "archive/1700000000000-00ab-uuid.json"; "timestamp layout")]
+#[test_case(
+    Some(S3KeyLayout::BlockHeightRange { range_size: 1000 }),
+    "archive/817000-817999/817042-00ab-1700000000000-uuid.json";
+    "block height range layout"
+)]
+#[test_case(
+    Some(S3KeyLayout::Daily),
+    "archive/2023-11-14/1700000000000-00ab-uuid.json";
+    "daily layout"
+)]
+fn it_builds_s3_keys(key_layout: Option<S3KeyLayout>, expected_key: &str) {
+    use super::s3_export::build_s3_key;
+
+    let hook = S3AppendHook {
+        bucket: "chainhook".into(),
+        key_prefix: "archive/".into(),
+        region: "us-east-1".into(),
+        key_layout,
+    };
+    assert!(HookAction::S3Append(hook.clone()).validate().is_ok());
+    let block = BlockIdentifier {
+        index: 817_042,
+        hash: "0x00ab".into(),
+    };
+    assert_eq!(
+        build_s3_key(&hook, "uuid", &block, 1_700_000_000_000),
+        expected_key
+    );
+}
+
+fn build_merkle_block_proof() -> String {
+    use bitcoincore_rpc_json::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc_json::bitcoin::blockdata::constants::genesis_block;
+
+    let mut block = genesis_block(Network::Bitcoin);
+    let coinbase = block.txdata[0].clone();
+    for i in 1..5 {
+        let mut tx = coinbase.clone();
+        tx.lock_time = LockTime::from_consensus(i);
+        block.txdata.push(tx);
+    }
+    block.header.merkle_root = block.compute_merkle_root().unwrap();
+    let matched_txid = block.txdata[3].txid();
+    let merkle_block = MerkleBlock::from_block_with_predicate(&block, |txid| *txid == matched_txid);
+    format!("0x{}", hex::encode(serialize(&merkle_block)))
+}
+
+#[test_case(ProofFormat::Hex; "hex")]
+#[test_case(ProofFormat::Base64; "base64")]
+#[test_case(ProofFormat::Structured; "structured")]
+fn it_round_trips_proof_formats(format: ProofFormat) {
+    let proof = build_merkle_block_proof();
+    assert_eq!(
+        get_bitcoin_proof_kind(&proof).unwrap(),
+        BitcoinProofKind::MerkleBlock
+    );
+    let encoded = encode_bitcoin_proof(&proof, &format).unwrap();
+    let decoded = decode_bitcoin_proof(&encoded, &format).unwrap();
+    assert_eq!(format!("0x{}", hex::encode(&decoded)), proof);
+
+    let merkle_block: MerkleBlock = deserialize(&decoded).unwrap();
+    let mut matches = vec![];
+    let mut positions = vec![];
+    merkle_block
+        .extract_matches(&mut matches, &mut positions)
+        .unwrap();
+    assert_eq!(positions, vec![3]);
+    if let ProofFormat::Structured = format {
+        assert_eq!(encoded["positions"], json!([3]));
+        assert_eq!(encoded["number_of_transactions"], json!(5));
+    }
+}
+
+#[test]
+fn it_computes_verifiable_proofs() {
+    // block 170, holding the first bitcoin transfer, from Satoshi to Hal Finney
+    let txids = [
+        "b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082",
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+    ];
+    let merkle_root = "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff";
+    let transactions = txids
+        .iter()
+        .enumerate()
+        .map(|(i, txid)| {
+            let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+                i as u64,
+                &accounts::wallet_1_btc_address(),
+                &accounts::wallet_3_btc_address(),
+                3,
+            );
+            transaction.transaction_identifier = TransactionIdentifier {
+                hash: format!("0x{}", txid),
+            };
+            transaction
+        })
+        .collect::<Vec<_>>();
+    let block = generate_test_bitcoin_block(0, 170, transactions, None);
+    let transaction_identifier = &block.transactions[1].transaction_identifier;
+
+    let proof = compute_proof(&block, &[transaction_identifier]).unwrap();
+    let bytes = hex::decode(proof.trim_start_matches("0x")).unwrap();
+    let partial_merkle_tree: PartialMerkleTree = deserialize(&bytes).unwrap();
+    let mut matches = vec![];
+    let mut positions = vec![];
+    let root = partial_merkle_tree
+        .extract_matches(&mut matches, &mut positions)
+        .unwrap();
+    assert_eq!(root.to_string(), merkle_root);
+    assert_eq!(matches[0].to_string(), txids[1]);
+    assert_eq!(positions, vec![1]);
+
+    // computed proofs carry no header, but still round trip through every format
+    assert_eq!(
+        get_bitcoin_proof_kind(&proof).unwrap(),
+        BitcoinProofKind::PartialMerkleTree
+    );
+    let encoded = encode_bitcoin_proof(&proof, &ProofFormat::Structured).unwrap();
+    assert_eq!(encoded.get("header"), None);
+    assert_eq!(encoded["positions"], json!([1]));
+    assert_eq!(encoded["hashes"], json!(txids));
+    let decoded = decode_bitcoin_proof(&encoded, &ProofFormat::Structured).unwrap();
+    assert_eq!(decoded, bytes);
+
+    let unknown_transaction = TransactionIdentifier {
+        hash: format!("0x{}", merkle_root),
+    };
+    assert!(compute_proof(&block, &[&unknown_transaction]).is_err());
+
+    // a single tree proves every transaction of the block
+    let transaction_identifiers = block
+        .transactions
+        .iter()
+        .map(|transaction| &transaction.transaction_identifier)
+        .collect::<Vec<_>>();
+    let proof = compute_proof(&block, &transaction_identifiers).unwrap();
+    let bytes = hex::decode(proof.trim_start_matches("0x")).unwrap();
+    let partial_merkle_tree: PartialMerkleTree = deserialize(&bytes).unwrap();
+    let mut matches = vec![];
+    let root = partial_merkle_tree
+        .extract_matches(&mut matches, &mut vec![])
+        .unwrap();
+    assert_eq!(root.to_string(), merkle_root);
+    assert_eq!(
+        matches
+            .iter()
+            .map(|txid| txid.to_string())
+            .collect::<Vec<_>>(),
+        txids
+    );
+}
+
+#[test_case(None, true; "expires past end_block without grace period")]
+#[test_case(Some(2), false; "stays active within the grace period")]
+fn it_keeps_predicates_active_during_end_block_grace_period(
+    end_block_grace_period: Option<u64>,
+    expect_expired: bool,
+) {
+    let generate_block = |fork_id: u8, height: u64| {
+        let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            height,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        );
+        generate_test_bitcoin_block(fork_id, height, vec![transaction], None)
+    };
+    let chainhook = BitcoinChainhookInstance {
+        end_block: Some(1),
+        end_block_grace_period,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let ctx = Context::empty();
+
+    // a block past end_block only expires the predicate once the grace period is over
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![generate_block(0, 2)],
+            confirmed_blocks: vec![],
+        });
+    let (triggered, _, expired) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+    assert!(triggered.is_empty());
+    assert_eq!(expired.contains_key("uuid"), expect_expired);
+
+    // a reorg within the grace period still delivers the rollback of the last matching block
+    let chain_event = BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+        blocks_to_rollback: vec![generate_block(0, 2), generate_block(0, 1)],
+        blocks_to_apply: vec![generate_block(1, 1), generate_block(1, 2)],
+        confirmed_blocks: vec![],
+    });
+    let (triggered, _, expired) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+    assert_eq!(triggered.len(), 1);
+    assert_eq!(triggered[0].rollback.len(), 1);
+    assert_eq!(triggered[0].rollback[0].1.block_identifier.index, 1);
+    assert_eq!(triggered[0].apply.len(), 1);
+    assert_eq!(triggered[0].apply[0].1.block_identifier.index, 1);
+    assert_eq!(expired.contains_key("uuid"), expect_expired);
+
+    // past the grace period, the predicate expires
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![generate_block(1, 4)],
+            confirmed_blocks: vec![],
+        });
+    let (_, _, expired) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+    assert!(expired.contains_key("uuid"));
+}
+
+#[test_case(false, true; "delivers rollback-only occurrences by default")]
+#[test_case(true, false; "skips rollback-only occurrences when requested")]
+fn it_evaluates_rollback_only_reorgs(skip_rollback_only_occurrences: bool, expect_trigger: bool) {
+    let generate_tx = |txid: u64| {
+        generate_test_tx_bitcoin_p2pkh_transfer(
+            txid,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        )
+    };
+    let chainhook = BitcoinChainhookInstance {
+        skip_rollback_only_occurrences,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Txid(ExactMatchingRule::Equals(
+            generate_tx(2).transaction_identifier.hash,
+        )))
+    };
+    // the matching tx 2 is rolled back and not mined again in the replacement block
+    let chain_event = BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+        blocks_to_rollback: vec![generate_test_bitcoin_block(
+            0,
+            1,
+            vec![generate_tx(1), generate_tx(2)],
+            None,
+        )],
+        blocks_to_apply: vec![generate_test_bitcoin_block(
+            1,
+            1,
+            vec![generate_tx(1), generate_tx(3)],
+            None,
+        )],
+        confirmed_blocks: vec![],
+    });
+    let (triggered, evaluated, _) = evaluate_bitcoin_chainhooks_on_chain_event(
+        &chain_event,
+        &vec![&chainhook],
+        None,
+        &Context::empty(),
+    );
+    assert!(evaluated.contains_key("uuid"));
+    assert_eq!(triggered.len(), expect_trigger as usize);
+    if expect_trigger {
+        assert!(triggered[0].apply.is_empty());
+        assert_eq!(triggered[0].rollback.len(), 1);
+    }
+}
+
+#[test_case(None, vec![1, 2, 3]; "evaluates every block without allowlist")]
+#[test_case(Some(vec![2, 3, 5]), vec![2, 3]; "only evaluates the listed blocks")]
+#[test_case(Some(vec![]), vec![]; "empty allowlist matches nothing")]
+fn it_evaluates_blocks_allowlist(blocks: Option<Vec<u64>>, expected_heights: Vec<u64>) {
+    let chainhook = BitcoinChainhookInstance {
+        blocks,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let new_blocks = (1..=3)
+        .map(|height| {
+            let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+                height,
+                &accounts::wallet_1_btc_address(),
+                &accounts::wallet_3_btc_address(),
+                3,
+            );
+            generate_test_bitcoin_block(0, height, vec![transaction], None)
+        })
+        .collect();
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks,
+            confirmed_blocks: vec![],
+        });
+    let (triggered, _, _) = evaluate_bitcoin_chainhooks_on_chain_event(
+        &chain_event,
+        &vec![&chainhook],
+        None,
+        &Context::empty(),
+    );
+    let triggered_heights = triggered
+        .iter()
+        .flat_map(|trigger| trigger.apply.iter())
+        .map(|(_, block)| block.block_identifier.index)
+        .collect::<Vec<_>>();
+    assert_eq!(triggered_heights, expected_heights);
+}
+
+#[test_case(None, vec![1, 2, 3]; "evaluates every block without start_block")]
+#[test_case(Some(2), vec![2, 3]; "evaluates the block at start_block")]
+#[test_case(Some(4), vec![]; "ignores blocks below start_block")]
+fn it_honors_start_block(start_block: Option<u64>, expected_heights: Vec<u64>) {
+    let chainhook = BitcoinChainhookInstance {
+        start_block,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let generate_block = |fork_id: u8, height: u64| {
+        let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            height,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        );
+        generate_test_bitcoin_block(fork_id, height, vec![transaction], None)
+    };
+    let ctx = Context::empty();
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: (1..=3).map(|height| generate_block(0, height)).collect(),
+            confirmed_blocks: vec![],
+        });
+    let (triggered, evaluated, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+    let triggered_heights = triggered
+        .iter()
+        .flat_map(|trigger| trigger.apply.iter())
+        .map(|(_, block)| block.block_identifier.index)
+        .collect::<Vec<_>>();
+    assert_eq!(triggered_heights, expected_heights);
+    // blocks below start_block are not reported as evaluated either
+    assert_eq!(
+        evaluated.get("uuid").map(|block| block.index),
+        expected_heights.last().cloned()
+    );
+
+    // the same goes for the blocks of a re-org
+    let chain_event = BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+        blocks_to_rollback: (1..=3)
+            .rev()
+            .map(|height| generate_block(0, height))
+            .collect(),
+        blocks_to_apply: (1..=3).map(|height| generate_block(1, height)).collect(),
+        confirmed_blocks: vec![],
+    });
+    let (triggered, _, _) =
+        evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+    let rolled_back_heights = triggered
+        .iter()
+        .flat_map(|trigger| trigger.rollback.iter())
+        .map(|(_, block)| block.block_identifier.index)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rolled_back_heights,
+        expected_heights.iter().rev().cloned().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn it_reports_triggered_predicates_in_registration_order() {
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let predicates = (0..50)
+        .map(|i| BitcoinChainhookInstance {
+            uuid: format!("uuid-{i}"),
+            // every other predicate ends before the block evaluated
+            end_block: if i % 2 == 0 { None } else { Some(0) },
+            ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+        })
+        .collect::<Vec<_>>();
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![generate_test_bitcoin_block(0, 1, vec![transaction], None)],
+            confirmed_blocks: vec![],
+        });
+    let (triggered, evaluated, expired) = evaluate_bitcoin_chainhooks_on_chain_event(
+        &chain_event,
+        &predicates.iter().collect(),
+        None,
+        &Context::empty(),
+    );
+    let triggered_uuids = triggered
+        .iter()
+        .map(|trigger| trigger.chainhook.uuid.clone())
+        .collect::<Vec<_>>();
+    let expected_uuids = (0..50)
+        .step_by(2)
+        .map(|i| format!("uuid-{i}"))
+        .collect::<Vec<_>>();
+    assert_eq!(triggered_uuids, expected_uuids);
+    assert_eq!(evaluated.len(), 50);
+    assert_eq!(expired.len(), 25);
+}
+
+#[test]
+fn it_flags_transactions_dropped_by_reorg() {
+    let generate_tx = |txid: u64| {
+        generate_test_tx_bitcoin_p2pkh_transfer(
+            txid,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_3_btc_address(),
+            3,
+        )
+    };
+    let chainhook = BitcoinChainhookInstance {
+        include_dropped: true,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    // tx 1 is mined again in the replacement block, tx 2 is not
+    let chain_event = BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+        blocks_to_rollback: vec![generate_test_bitcoin_block(
+            0,
+            1,
+            vec![generate_tx(1), generate_tx(2)],
+            None,
+        )],
+        blocks_to_apply: vec![generate_test_bitcoin_block(
+            1,
+            1,
+            vec![generate_tx(1), generate_tx(3)],
+            None,
+        )],
+        confirmed_blocks: vec![],
+    });
+    let (mut triggered, _, _) = evaluate_bitcoin_chainhooks_on_chain_event(
+        &chain_event,
+        &vec![&chainhook],
+        None,
+        &Context::empty(),
+    );
+    assert_eq!(triggered.len(), 1);
+    assert_eq!(
+        triggered[0].dropped,
+        vec![&generate_tx(2).transaction_identifier]
+    );
+
+    let payload = serialize_bitcoin_payload_to_json(&triggered[0], &HashMap::new(), None);
+    assert_eq!(
+        payload["dropped"],
+        json!([generate_tx(2).transaction_identifier])
+    );
+    let occurrence = BitcoinChainhookOccurrencePayload::from_trigger(triggered.remove(0));
+    assert_eq!(
+        occurrence.dropped,
+        vec![generate_tx(2).transaction_identifier]
+    );
+
+    // dropped transactions are left out of the payloads of predicates not including them
+    let chainhook = get_test_bitcoin_chainhook(BitcoinPredicateType::Block);
+    let (triggered, _, _) = evaluate_bitcoin_chainhooks_on_chain_event(
+        &chain_event,
+        &vec![&chainhook],
+        None,
+        &Context::empty(),
+    );
+    let payload = serialize_bitcoin_payload_to_json(&triggered[0], &HashMap::new(), None);
+    assert!(payload.get("dropped").is_none());
+}
+
+#[test_case(vec![], false, false; "without transactions, skipped by default")]
+#[test_case(vec![], true, true; "without transactions, triggering when opted in")]
+#[test_case(vec![0], false, false; "with a coinbase only, skipped by default")]
+#[test_case(vec![0], true, true; "with a coinbase only, triggering when opted in")]
+#[test_case(vec![0, 1], false, true; "with transactions")]
+fn it_evaluates_empty_blocks(
+    indices: Vec<u32>,
+    include_empty_blocks: bool,
+    expect_triggered: bool,
+) {
+    let chainhook = BitcoinChainhookInstance {
+        include_empty_blocks,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let transactions = indices
+        .iter()
+        .map(|index| {
+            let mut tx = generate_test_tx_bitcoin_p2pkh_transfer(
+                *index as u64,
+                &accounts::wallet_1_btc_address(),
+                &accounts::wallet_3_btc_address(),
+                3,
+            );
+            tx.metadata.index = *index;
+            tx
+        })
+        .collect::<Vec<_>>();
+    let block = generate_test_bitcoin_block(0, 1, transactions, None);
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![block.clone()],
+            confirmed_blocks: vec![],
+        });
+    let (triggered, evaluated, _) = evaluate_bitcoin_chainhooks_on_chain_event(
+        &chain_event,
+        &vec![&chainhook],
+        None,
+        &Context::empty(),
+    );
+
+    // the evaluation of the block is recorded whether or not it triggers the predicate
+    assert_eq!(evaluated.get("uuid"), Some(&&block.block_identifier));
+    assert_eq!(!triggered.is_empty(), expect_triggered);
+    if expect_triggered {
+        assert_eq!(triggered[0].apply.len(), 1);
+        assert_eq!(triggered[0].apply[0].0.len(), indices.len());
+        assert_eq!(
+            triggered[0].apply[0].1.block_identifier,
+            block.block_identifier
+        );
+    }
+}
+
+#[test_case(accounts::wallet_3_btc_address(), true; "paying the address")]
+#[test_case(accounts::wallet_2_btc_address(), false; "paying another address")]
+fn it_evaluates_the_address_parsed_at_registration(address: String, expect_triggered: bool) {
+    let predicate =
+        BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(ExactMatchingRule::Equals(address)));
+    let chainhook = BitcoinChainhookInstance {
+        parsed_address: PredicateAddress::new(&predicate),
+        ..get_test_bitcoin_chainhook(predicate)
+    };
+    assert!(chainhook.parsed_address.0.get().is_some());
+    // the parsed address is derived from the predicate, and ignored by comparisons
+    assert_eq!(
+        chainhook,
+        BitcoinChainhookInstance {
+            parsed_address: PredicateAddress::default(),
+            ..chainhook.clone()
+        }
+    );
+
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let block = generate_test_bitcoin_block(0, 1, vec![transaction], None);
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![block],
+            confirmed_blocks: vec![],
+        });
+    let (triggered, _, _) = evaluate_bitcoin_chainhooks_on_chain_event(
+        &chain_event,
+        &vec![&chainhook],
+        None,
+        &Context::empty(),
+    );
+    assert_eq!(!triggered.is_empty(), expect_triggered);
+}
+
+#[test_case(AmountFormat::Number, json!(9_007_199_254_740_993u64); "as numbers")]
+#[test_case(AmountFormat::String, json!("9007199254740993"); "as strings")]
+fn it_serializes_amounts_with_format(amount_format: AmountFormat, expected_amount: JsonValue) {
+    // 2^53 + 1, the first integer that can't be represented exactly by a double
+    let amount = 9_007_199_254_740_993;
+    let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    transaction.metadata.fee = amount;
+    transaction.metadata.outputs[0].value = amount;
+    let chainhook = BitcoinChainhookInstance {
+        include_outputs: true,
+        amount_format,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+
+    let serialized = serialize_bitcoin_transactions_to_json(
+        &chainhook,
+        &vec![&transaction],
+        &HashMap::new(),
+        None,
+    );
+    let metadata = &serialized[0]["metadata"];
+    assert_eq!(metadata["fee"], expected_amount);
+    assert_eq!(metadata["outputs"][0]["value"], expected_amount);
+}
+
+#[test_case(false; "omitted by default")]
+#[test_case(true; "included when enabled")]
+fn it_serializes_transaction_sizes(include_size: bool) {
+    let mut transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    transaction.metadata.weight = 561;
+    transaction.metadata.vsize = 141;
+    let chainhook = BitcoinChainhookInstance {
+        include_size,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+
+    let serialized = serialize_bitcoin_transactions_to_json(
+        &chainhook,
+        &vec![&transaction],
+        &HashMap::new(),
+        None,
+    );
+    let metadata = serialized[0]["metadata"].as_object().unwrap();
+    assert_eq!(
+        metadata.get("weight").cloned(),
+        include_size.then(|| json!(561))
+    );
+    assert_eq!(
+        metadata.get("vsize").cloned(),
+        include_size.then(|| json!(141))
+    );
+    assert_eq!(metadata.contains_key("witness_discount"), include_size);
+}
+
+#[test_case(false; "omitted by default")]
+#[test_case(true; "included when enabled")]
+fn it_serializes_transaction_versions(include_version: bool) {
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let chainhook = BitcoinChainhookInstance {
+        include_version,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+
+    let serialized = serialize_bitcoin_transactions_to_json(
+        &chainhook,
+        &vec![&transaction],
+        &HashMap::new(),
+        None,
+    );
+    let metadata = serialized[0]["metadata"].as_object().unwrap();
+    assert_eq!(
+        metadata.get("version").cloned(),
+        include_version.then(|| json!(transaction.metadata.version))
+    );
+}
+
+#[test_case(false; "emitted by default")]
+#[test_case(true; "omitted when enabled")]
+fn it_serializes_empty_arrays(omit_empty_arrays: bool) {
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        0,
+        &accounts::wallet_1_btc_address(),
+        &accounts::wallet_3_btc_address(),
+        3,
+    );
+    let chainhook = BitcoinChainhookInstance {
+        include_outputs: true,
+        omit_empty_arrays,
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+
+    let serialized = serialize_bitcoin_transactions_to_json(
+        &chainhook,
+        &vec![&transaction],
+        &HashMap::new(),
+        None,
+    );
+    let metadata = serialized[0]["metadata"].as_object().unwrap();
+    for key in ["inputs", "stacks_operations", "ordinal_operations"] {
+        assert_eq!(metadata.contains_key(key), !omit_empty_arrays, "{}", key);
+    }
+    // non empty arrays are always emitted
+    assert!(!metadata["outputs"].as_array().unwrap().is_empty());
+}
+
+#[test_case(PayloadShape::Block; "nested under blocks")]
+#[test_case(PayloadShape::Transaction; "flattened into transactions")]
+fn it_serializes_payload_shape(payload_shape: PayloadShape) {
+    let transactions = (0..2)
+        .map(|index| {
+            generate_test_tx_bitcoin_p2pkh_transfer(
+                index,
+                &accounts::wallet_1_btc_address(),
+                &accounts::wallet_3_btc_address(),
+                3,
+            )
+        })
+        .collect::<Vec<_>>();
+    let block = generate_test_bitcoin_block(0, 0, transactions.clone(), None);
+    let rolled_back_block = generate_test_bitcoin_block(1, 0, vec![], None);
+    let chainhook = BitcoinChainhookInstance {
+        payload_shape: payload_shape.clone(),
+        ..get_test_bitcoin_chainhook(BitcoinPredicateType::Block)
+    };
+    let trigger = BitcoinTriggerChainhook {
+        chainhook: &chainhook,
+        apply: vec![(transactions.iter().collect(), &block)],
+        rollback: vec![(vec![], &rolled_back_block)],
+        dropped: vec![],
+    };
+
+    let payload = serialize_bitcoin_payload_to_json(&trigger, &HashMap::new(), None);
+    let txids = transactions
+        .iter()
+        .map(|tx| json!(tx.transaction_identifier))
+        .collect::<Vec<_>>();
+    let apply = payload["apply"].as_array().unwrap();
+    match payload_shape {
+        PayloadShape::Block => {
+            assert_eq!(apply.len(), 1);
+            assert_eq!(apply[0]["block_identifier"], json!(block.block_identifier));
+            let serialized_transactions = apply[0]["transactions"].as_array().unwrap();
+            assert_eq!(serialized_transactions.len(), 2);
+            for (transaction, txid) in serialized_transactions.iter().zip(txids.iter()) {
+                assert_eq!(&transaction["transaction_identifier"], txid);
+                assert!(transaction.get("block_identifier").is_none());
+            }
+            assert!(payload.get("rollback_blocks").is_none());
+        }
+        PayloadShape::Transaction => {
+            assert_eq!(apply.len(), 2);
+            for (transaction, txid) in apply.iter().zip(txids.iter()) {
+                assert_eq!(&transaction["transaction_identifier"], txid);
+                assert_eq!(
+                    transaction["block_identifier"],
+                    json!(block.block_identifier)
+                );
+            }
+            // the rolled back block has no matching transaction, but the reorg is still signaled
+            assert_eq!(payload["rollback"], json!([]));
+            assert_eq!(
+                payload["rollback_blocks"],
+                json!([rolled_back_block.block_identifier])
+            );
+            assert_eq!(payload["apply_blocks"], json!([block.block_identifier]));
+        }
+    }
 }
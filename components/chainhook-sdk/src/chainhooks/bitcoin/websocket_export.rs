@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use hiro_system_kit::slog;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::utils::Context;
+
+/// Number of messages buffered for a url while its connection is being re-established. Once
+/// full, new messages are rejected rather than dropped.
+const FEED_BUFFER_SIZE: usize = 10_000;
+const MIN_RECONNECT_DELAY_MS: u64 = 100;
+const MAX_RECONNECT_DELAY_MS: u64 = 10_000;
+
+static WEBSOCKET_FEEDS: OnceLock<Mutex<HashMap<String, Sender<String>>>> = OnceLock::new();
+
+/// Pushes `bytes` as a text frame to the websocket at `url`. Messages are buffered and sent in
+/// order by a feed maintaining a persistent connection to `url`, shared by every predicate
+/// pushing to the same url, whether they are evaluated by the observer or by a scan.
+pub fn send_websocket_message(url: &str, bytes: Vec<u8>, ctx: &Context) -> Result<(), String> {
+    let message = String::from_utf8(bytes)
+        .map_err(|e| format!("unable to encode websocket message: {}", e.to_string()))?;
+    let mut feeds = WEBSOCKET_FEEDS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|e| format!("unable to lock websocket feeds: {}", e.to_string()))?;
+    let feed = match feeds.get(url) {
+        Some(feed) if !feed.is_closed() => feed.clone(),
+        _ => {
+            let feed = spawn_websocket_feed(url, ctx)?;
+            feeds.insert(url.to_string(), feed.clone());
+            feed
+        }
+    };
+    feed.try_send(message).map_err(|e| match e {
+        TrySendError::Full(_) => format!("websocket buffer of {} is full", url),
+        TrySendError::Closed(_) => format!("websocket feed of {} is closed", url),
+    })
+}
+
+/// Drops the feeds of the urls missing from `urls`, e.g. once the predicates pushing to them are
+/// deregistered. A dropped feed still sends the messages it buffered, then closes its connection.
+pub fn retain_websocket_feeds(urls: &HashSet<&str>) {
+    let Some(feeds) = WEBSOCKET_FEEDS.get() else {
+        return;
+    };
+    if let Ok(mut feeds) = feeds.lock() {
+        feeds.retain(|url, _| urls.contains(url.as_str()));
+    }
+}
+
+fn spawn_websocket_feed(url: &str, ctx: &Context) -> Result<Sender<String>, String> {
+    let (tx, rx) = mpsc::channel(FEED_BUFFER_SIZE);
+    let url = url.to_string();
+    let ctx = ctx.clone();
+    std::thread::Builder::new()
+        .name("websocket-feed".into())
+        .spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("unable to build websocket feed runtime");
+            runtime.block_on(run_websocket_feed(url, rx, ctx));
+        })
+        .map_err(|e| format!("unable to spawn websocket feed: {}", e.to_string()))?;
+    Ok(tx)
+}
+
+/// Sends the messages of `rx` to `url`, in order. The message taken off the buffer is held until
+/// it is written to the connection: when the connection drops, it is re-established with an
+/// exponential backoff, and the message is sent again. A message written just before the
+/// connection drops can still be lost, as the server doesn't acknowledge them.
+async fn run_websocket_feed(url: String, mut rx: Receiver<String>, ctx: Context) {
+    let mut connection: Option<WebSocketStream<MaybeTlsStream<TcpStream>>> = None;
+    let mut reconnect_delay = MIN_RECONNECT_DELAY_MS;
+    loop {
+        let message = match connection {
+            Some(ref mut stream) => tokio::select! {
+                message = rx.recv() => message,
+                // incoming frames are only read to notice the connections closed by the server
+                frame = stream.next() => {
+                    if matches!(frame, None | Some(Err(_)) | Some(Ok(Message::Close(_)))) {
+                        connection = None;
+                    }
+                    continue;
+                }
+            },
+            None => rx.recv().await,
+        };
+        let Some(message) = message else {
+            return;
+        };
+        loop {
+            if let Some(ref mut stream) = connection {
+                if stream.send(Message::Text(message.clone())).await.is_ok() {
+                    break;
+                }
+                connection = None;
+            }
+            match connect_async(url.as_str()).await {
+                Ok((stream, _)) => {
+                    connection = Some(stream);
+                    reconnect_delay = MIN_RECONNECT_DELAY_MS;
+                }
+                Err(e) => {
+                    ctx.try_log(|logger| {
+                        slog::warn!(
+                            logger,
+                            "unable to connect to websocket {}, retrying in {}ms: {}",
+                            url,
+                            reconnect_delay,
+                            e
+                        )
+                    });
+                    tokio::time::sleep(Duration::from_millis(reconnect_delay)).await;
+                    reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY_MS);
+                }
+            }
+        }
+    }
+}
@@ -2,17 +2,18 @@ use crate::utils::{AbstractStacksBlock, Context, MAX_BLOCK_HEIGHTS_ENTRIES};
 
 use super::types::validate_txid;
 use super::types::{
-    append_error_context, BlockIdentifierIndexRule, ChainhookInstance, ExactMatchingRule,
-    HookAction,
+    append_error_context, build_delivery_intent, build_http_delivery_client,
+    BlockIdentifierIndexRule, ChainhookInstance, ExactMatchingRule, HookAction, HttpDeliveryConfig,
+    SigningSecrets,
 };
 use chainhook_types::{
-    BlockIdentifier, StacksChainEvent, StacksNetwork, StacksTransactionData,
+    BlockIdentifier, Chain, StacksChainEvent, StacksNetwork, StacksTransactionData,
     StacksTransactionEvent, StacksTransactionEventPayload, StacksTransactionKind,
     TransactionIdentifier,
 };
 use hiro_system_kit::slog;
 use regex::Regex;
-use reqwest::{Client, Method};
+use reqwest::Client;
 use schemars::JsonSchema;
 use serde_json::Value as JsonValue;
 use stacks_codec::clarity::codec::StacksMessageCodec;
@@ -41,6 +42,8 @@ pub struct StacksChainhookSpecification {
     pub decode_clarity_values: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub include_contract_abi: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_rollback_only_occurrences: Option<bool>,
     #[serde(rename = "if_this")]
     pub predicate: StacksPredicate,
     #[serde(rename = "then_that")]
@@ -57,6 +60,7 @@ impl StacksChainhookSpecification {
             capture_all_events: None,
             include_contract_abi: None,
             decode_clarity_values: None,
+            skip_rollback_only_occurrences: None,
             predicate,
             action,
         }
@@ -97,9 +101,14 @@ impl StacksChainhookSpecification {
         self
     }
 
+    pub fn skip_rollback_only_occurrences(&mut self, do_skip: bool) -> &mut Self {
+        self.skip_rollback_only_occurrences = Some(do_skip);
+        self
+    }
+
     pub fn validate(&self) -> Result<(), Vec<String>> {
         let mut errors = vec![];
-        if let Err(e) = self.action.validate() {
+        if let Err(e) = self.action.validate_for_chain(&Chain::Stacks) {
             errors.append(&mut append_error_context("invalid 'then_that' value", e));
         }
         if let Err(e) = self.predicate.validate() {
@@ -123,6 +132,38 @@ impl StacksChainhookSpecification {
             Err(errors)
         }
     }
+
+    /// Best-effort detection of valid specifications that can never match any block.
+    /// Returns the reasons why the specification is unsatisfiable, if any.
+    pub fn get_unsatisfiable_conditions(&self) -> Vec<String> {
+        let mut conditions = vec![];
+        if let Some(ref blocks) = self.blocks {
+            if blocks.is_empty() {
+                conditions.push("Chainhook specification field `blocks` is empty.".into());
+            }
+        }
+        if let StacksPredicate::BlockHeight(rule) = &self.predicate {
+            // bounds of the heights matched by the rule, both inclusive
+            let (lowest, highest) = match rule {
+                BlockIdentifierIndexRule::Equals(a) => (*a, *a),
+                BlockIdentifierIndexRule::HigherThan(a) => (a.saturating_add(1), u64::MAX),
+                BlockIdentifierIndexRule::LowerThan(a) => (0, a.saturating_sub(1)),
+                BlockIdentifierIndexRule::Between(a, b) => {
+                    (a.saturating_add(1), b.saturating_sub(1))
+                }
+            };
+            if lowest > highest {
+                conditions.push("'block_height' rule does not match any block height".into());
+            } else if lowest > self.end_block.unwrap_or(u64::MAX)
+                || highest < self.start_block.unwrap_or(0)
+            {
+                conditions.push(
+                    "'block_height' rule only matches blocks outside of the range defined by `start_block` and `end_block`".into(),
+                );
+            }
+        }
+        conditions
+    }
 }
 
 /// Maps some [StacksChainhookSpecification] to a corresponding [StacksNetwork]. This allows maintaining one
@@ -169,6 +210,9 @@ pub struct StacksChainhookSpecificationNetworkMap {
     pub uuid: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner_uuid: Option<String>,
+    /// Url receiving a POST request with the final status of the predicate when it expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_expire_url: Option<String>,
     pub name: String,
     pub version: u32,
     pub networks: BTreeMap<StacksNetwork, StacksChainhookSpecification>,
@@ -186,6 +230,7 @@ impl StacksChainhookSpecificationNetworkMap {
         Ok(StacksChainhookInstance {
             uuid: self.uuid,
             owner_uuid: self.owner_uuid,
+            on_expire_url: self.on_expire_url,
             name: self.name,
             network: network.clone(),
             version: self.version,
@@ -196,6 +241,7 @@ impl StacksChainhookSpecificationNetworkMap {
             decode_clarity_values: spec.decode_clarity_values,
             expire_after_occurrence: spec.expire_after_occurrence,
             include_contract_abi: spec.include_contract_abi,
+            skip_rollback_only_occurrences: spec.skip_rollback_only_occurrences,
             predicate: spec.predicate,
             action: spec.action,
             enabled: false,
@@ -209,6 +255,9 @@ pub struct StacksChainhookInstance {
     pub uuid: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner_uuid: Option<String>,
+    /// Url receiving a POST request with the final status of the predicate when it expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_expire_url: Option<String>,
     pub name: String,
     pub network: StacksNetwork,
     pub version: u32,
@@ -225,6 +274,10 @@ pub struct StacksChainhookInstance {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub decode_clarity_values: Option<bool>,
     pub include_contract_abi: Option<bool>,
+    /// Drops the occurrences of reorgs in which the predicate only matches rolled back blocks,
+    /// for consumers only acting on new data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_rollback_only_occurrences: Option<bool>,
     #[serde(rename = "predicate")]
     pub predicate: StacksPredicate,
     pub action: HookAction,
@@ -472,6 +525,7 @@ pub struct StacksRollbackTransactionPayload {
 #[derive(Clone, Debug)]
 pub struct StacksChainhookPayload {
     pub uuid: String,
+    pub name: String,
 }
 
 #[derive(Clone, Debug)]
@@ -516,13 +570,25 @@ impl StacksChainhookOccurrencePayload {
                 .collect::<Vec<_>>(),
             chainhook: StacksChainhookPayload {
                 uuid: trigger.chainhook.uuid.clone(),
+                name: trigger.chainhook.name.clone(),
             },
         }
     }
+
+    /// The intent posted to the `verification_url` of the predicate before the occurrence.
+    pub fn intent(&self) -> JsonValue {
+        build_delivery_intent(
+            &self.chainhook.uuid,
+            &self.chainhook.name,
+            self.apply.iter().map(|p| &p.block_identifier).collect(),
+            self.rollback.iter().map(|p| &p.block_identifier).collect(),
+        )
+    }
 }
 pub enum StacksChainhookOccurrence {
     Http(RequestBuilder, StacksChainhookOccurrencePayload),
     File(String, Vec<u8>),
+    UnixSocket(String, Vec<u8>),
     Data(StacksChainhookOccurrencePayload),
 }
 
@@ -652,6 +718,9 @@ pub fn evaluate_stacks_chainhooks_on_chain_event<'a>(
                     rollback.append(&mut occurrences);
                     expired_predicates.append(&mut expirations);
                 }
+                if apply.is_empty() && chainhook.skip_rollback_only_occurrences.unwrap_or(false) {
+                    continue;
+                }
                 if !apply.is_empty() || !rollback.is_empty() {
                     triggered_predicates.push(StacksTriggerChainhook {
                         chainhook,
@@ -713,6 +782,9 @@ pub fn evaluate_stacks_chainhooks_on_chain_event<'a>(
                     rollback.append(&mut occurrences);
                     expired_predicates.append(&mut expirations);
                 }
+                if apply.is_empty() && chainhook.skip_rollback_only_occurrences.unwrap_or(false) {
+                    continue;
+                }
                 if !apply.is_empty() || !rollback.is_empty() {
                     triggered_predicates.push(StacksTriggerChainhook {
                         chainhook,
@@ -1323,24 +1395,29 @@ pub fn serialize_stacks_payload_to_json<'a>(
         }).collect::<Vec<_>>(),
         "chainhook": {
             "uuid": trigger.chainhook.uuid,
+            "name": trigger.chainhook.name,
             "predicate": trigger.chainhook.predicate,
             "is_streaming_blocks": trigger.chainhook.enabled
         }
     })
 }
 
+/// Builds the occurrence of `trigger`. `http_post` occurrences are sent with `http_client` when provided,
+/// or with a client built with the default delivery settings otherwise, and signed with
+/// `signing_secrets`.
 pub fn handle_stacks_hook_action<'a>(
     trigger: StacksTriggerChainhook<'a>,
     proofs: &HashMap<&'a TransactionIdentifier, String>,
+    http_client: Option<&Client>,
+    signing_secrets: &SigningSecrets,
     ctx: &Context,
 ) -> Result<StacksChainhookOccurrence, String> {
     match &trigger.chainhook.action {
         HookAction::HttpPost(http) => {
-            let client = Client::builder()
-                .build()
-                .map_err(|e| format!("unable to build http client: {}", e.to_string()))?;
-            let host = format!("{}", http.url);
-            let method = Method::POST;
+            let client = match http_client {
+                Some(client) => client.clone(),
+                None => build_http_delivery_client(&HttpDeliveryConfig::default())?,
+            };
             let body = serde_json::to_vec(&serialize_stacks_payload_to_json(
                 trigger.clone(),
                 proofs,
@@ -1348,11 +1425,7 @@ pub fn handle_stacks_hook_action<'a>(
             ))
             .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
             Ok(StacksChainhookOccurrence::Http(
-                client
-                    .request(method, &host)
-                    .header("Content-Type", "application/json")
-                    .header("Authorization", http.authorization_header.clone())
-                    .body(body),
+                http.build_request(&client, signing_secrets, body)?,
                 StacksChainhookOccurrencePayload::from_trigger(trigger),
             ))
         }
@@ -1364,6 +1437,38 @@ pub fn handle_stacks_hook_action<'a>(
                 bytes,
             ))
         }
+        HookAction::UnixSocket(socket) => {
+            let bytes = serde_json::to_vec(&serialize_stacks_payload_to_json(trigger, proofs, ctx))
+                .map_err(|e| format!("unable to serialize payload {}", e.to_string()))?;
+            Ok(StacksChainhookOccurrence::UnixSocket(
+                socket.path.to_string(),
+                bytes,
+            ))
+        }
+        #[cfg(feature = "websocket")]
+        HookAction::WebSocket(_) => Err(format!(
+            "the websocket action is only supported by bitcoin predicates"
+        )),
+        #[cfg(feature = "parquet")]
+        HookAction::Parquet(_) => Err(format!(
+            "the parquet action is only supported by bitcoin predicates"
+        )),
+        #[cfg(feature = "kafka")]
+        HookAction::Kafka(_) => Err(format!(
+            "the kafka action is only supported by bitcoin predicates"
+        )),
+        #[cfg(feature = "grpc")]
+        HookAction::Grpc(_) => Err(format!(
+            "the grpc action is only supported by bitcoin predicates"
+        )),
+        #[cfg(feature = "sqs")]
+        HookAction::Sqs(_) => Err(format!(
+            "the sqs action is only supported by bitcoin predicates"
+        )),
+        #[cfg(feature = "s3")]
+        HookAction::S3Append(_) => Err(format!(
+            "the s3_append action is only supported by bitcoin predicates"
+        )),
         HookAction::Noop => Ok(StacksChainhookOccurrence::Data(
             StacksChainhookOccurrencePayload::from_trigger(trigger),
         )),
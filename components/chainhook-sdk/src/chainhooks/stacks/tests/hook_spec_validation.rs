@@ -35,13 +35,14 @@ lazy_static! {
     
     static ref INVALID_PREDICATE: StacksPredicate = StacksPredicate::PrintEvent(StacksPrintEventBasedPredicate::MatchesRegex { contract_identifier: CONTRACT_ID_INVALID_ADDRESS.clone(), regex:  INVALID_REGEX.clone() });
     static ref INVALID_HOOK_ACTION: HookAction = 
-        HookAction::HttpPost(HttpHook { url: "".into(), authorization_header: "\n".into() });
+        HookAction::HttpPost(HttpHook { url: "".into(), authorization_header: "\n".into(), failover_urls: vec![], signing_secret_id: None, verification_url: None });
     static ref ALL_INVALID_SPEC: StacksChainhookSpecification = StacksChainhookSpecification::new(INVALID_PREDICATE.clone(), INVALID_HOOK_ACTION.clone());
     static ref ALL_INVALID_SPEC_NETWORK_MAP: ChainhookSpecificationNetworkMap = 
         ChainhookSpecificationNetworkMap::Stacks(
             StacksChainhookSpecificationNetworkMap { 
                 uuid: "test".into(), 
                 owner_uuid: None, 
+                on_expire_url: None, 
                 name: "test".into(), 
                 version: 1, 
                 networks: BTreeMap::from([
@@ -209,3 +210,47 @@ fn it_validates_stacks_chainhook_specs(
         );
     }
 }
+
+#[test_case(BlockIdentifierIndexRule::Equals(5), None, None, vec![]; "equals without bounds")]
+#[test_case(BlockIdentifierIndexRule::Equals(5), Some(10), None, vec!["'block_height' rule only matches blocks outside of the range defined by `start_block` and `end_block`".into()]; "equals below start_block")]
+#[test_case(BlockIdentifierIndexRule::HigherThan(20), None, Some(20), vec!["'block_height' rule only matches blocks outside of the range defined by `start_block` and `end_block`".into()]; "higher_than above end_block")]
+#[test_case(BlockIdentifierIndexRule::LowerThan(10), Some(9), None, vec![]; "lower_than reaching start_block")]
+#[test_case(BlockIdentifierIndexRule::Between(5, 6), None, None, vec!["'block_height' rule does not match any block height".into()]; "between consecutive heights")]
+fn it_detects_unsatisfiable_stacks_block_height_rules(
+    rule: BlockIdentifierIndexRule,
+    start_block: Option<u64>,
+    end_block: Option<u64>,
+    expected_conditions: Vec<String>,
+) {
+    let mut spec =
+        StacksChainhookSpecification::new(StacksPredicate::BlockHeight(rule), HookAction::Noop);
+    if let Some(start_block) = start_block {
+        spec.start_block(start_block);
+    }
+    if let Some(end_block) = end_block {
+        spec.end_block(end_block);
+    }
+    assert!(spec.validate().is_ok());
+    assert_eq!(spec.get_unsatisfiable_conditions(), expected_conditions);
+}
+
+#[cfg(any(feature = "parquet", feature = "kafka", feature = "grpc", feature = "websocket", feature = "sqs", feature = "s3"))]
+#[cfg_attr(feature = "parquet", test_case(HookAction::Parquet(ParquetHook { path: "matches.parquet".into(), max_file_size_mb: None, max_file_age_sec: None }), "parquet"; "parquet"))]
+#[cfg_attr(feature = "kafka", test_case(HookAction::Kafka(KafkaHook { brokers: vec!["localhost:9092".into()], topic: "occurrences".into(), key_template: None }), "kafka"; "kafka"))]
+#[cfg_attr(feature = "grpc", test_case(HookAction::Grpc(GrpcHook { endpoint: "http://localhost:50051".into() }), "grpc"; "grpc"))]
+#[cfg_attr(feature = "websocket", test_case(HookAction::WebSocket(WebSocketHook { url: "ws://localhost:8080".into() }), "websocket"; "websocket"))]
+#[cfg_attr(feature = "sqs", test_case(HookAction::Sqs(SqsHook { queue_url: "https://sqs.us-east-1.amazonaws.com/123456789012/chainhook".into(), region: "us-east-1".into(), deduplication_hash: None }), "sqs"; "sqs"))]
+#[cfg_attr(feature = "s3", test_case(HookAction::S3Append(S3AppendHook { bucket: "chainhook".into(), key_prefix: "archive".into(), region: "us-east-1".into(), key_layout: None }), "s3_append"; "s3_append"))]
+fn it_rejects_bitcoin_only_actions(action: HookAction, name: &str) {
+    let spec = StacksChainhookSpecification::new(
+        StacksPredicate::BlockHeight(BlockIdentifierIndexRule::HigherThan(0)),
+        action,
+    );
+    assert_eq!(
+        spec.validate().unwrap_err(),
+        vec![format!(
+            "invalid 'then_that' value: the {} action is only supported by bitcoin predicates",
+            name
+        )]
+    );
+}
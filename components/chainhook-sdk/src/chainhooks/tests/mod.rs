@@ -10,7 +10,7 @@ use super::{
         StacksNftEventBasedPredicate, StacksPredicate, StacksPrintEventBasedPredicate,
         StacksStxEventBasedPredicate, StacksTrait, StacksTriggerChainhook,
     },
-    types::{ExactMatchingRule, FileHook},
+    types::{ExactMatchingRule, FileHook, SigningSecrets},
 };
 use crate::{chainhooks::stacks::serialize_stacks_payload_to_json, utils::Context};
 use crate::{
@@ -390,6 +390,7 @@ fn test_stacks_predicates(
     let chainhook = StacksChainhookInstance {
         uuid: "".to_string(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "".to_string(),
         network: StacksNetwork::Testnet,
         version: 1,
@@ -399,6 +400,7 @@ fn test_stacks_predicates(
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: None,
+        skip_rollback_only_occurrences: None,
         include_contract_abi: None,
         predicate: predicate,
         action: HookAction::Noop,
@@ -470,6 +472,7 @@ fn test_stacks_predicate_contract_deploy(predicate: StacksPredicate, expected_ap
     let chainhook = StacksChainhookInstance {
         uuid: "".to_string(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "".to_string(),
         network: StacksNetwork::Testnet,
         version: 1,
@@ -479,6 +482,7 @@ fn test_stacks_predicate_contract_deploy(predicate: StacksPredicate, expected_ap
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: None,
+        skip_rollback_only_occurrences: None,
         include_contract_abi: None,
         predicate: predicate,
         action: HookAction::Noop,
@@ -525,6 +529,7 @@ fn verify_optional_addition_of_contract_abi() {
     let mut contract_deploy_chainhook = StacksChainhookInstance {
         uuid: "contract-deploy".to_string(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "".to_string(),
         network: StacksNetwork::Testnet,
         version: 1,
@@ -534,6 +539,7 @@ fn verify_optional_addition_of_contract_abi() {
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: None,
+        skip_rollback_only_occurrences: None,
         include_contract_abi: Some(true),
         predicate: StacksPredicate::ContractDeployment(
             StacksContractDeploymentPredicate::Deployer("*".to_string()),
@@ -545,6 +551,7 @@ fn verify_optional_addition_of_contract_abi() {
     let contract_call_chainhook = StacksChainhookInstance {
         uuid: "contract-call".to_string(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "".to_string(),
         network: StacksNetwork::Testnet,
         version: 1,
@@ -554,6 +561,7 @@ fn verify_optional_addition_of_contract_abi() {
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: None,
+        skip_rollback_only_occurrences: None,
         include_contract_abi: Some(true),
         predicate: StacksPredicate::ContractCall(StacksContractCallBasedPredicate {
             contract_identifier: "ST13F481SBR0R7Z6NMMH8YV2FJJYXA5JPA0AD3HP9.subnet-v1".to_string(),
@@ -664,6 +672,7 @@ fn test_stacks_predicate_contract_call(predicate: StacksPredicate, expected_appl
     let chainhook = StacksChainhookInstance {
         uuid: "".to_string(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "".to_string(),
         network: StacksNetwork::Testnet,
         version: 1,
@@ -673,6 +682,7 @@ fn test_stacks_predicate_contract_call(predicate: StacksPredicate, expected_appl
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: None,
+        skip_rollback_only_occurrences: None,
         include_contract_abi: None,
         predicate: predicate,
         action: HookAction::Noop,
@@ -699,6 +709,7 @@ fn test_stacks_hook_action_noop() {
     let chainhook = StacksChainhookInstance {
         uuid: "".to_string(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "".to_string(),
         network: StacksNetwork::Testnet,
         version: 1,
@@ -708,6 +719,7 @@ fn test_stacks_hook_action_noop() {
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: None,
+        skip_rollback_only_occurrences: None,
         include_contract_abi: None,
         predicate: StacksPredicate::Txid(ExactMatchingRule::Equals(
             "0xb92c2ade84a8b85f4c72170680ae42e65438aea4db72ba4b2d6a6960f4141ce8".to_string(),
@@ -735,7 +747,9 @@ fn test_stacks_hook_action_noop() {
         logger: None,
         tracer: false,
     };
-    let occurrence = handle_stacks_hook_action(trigger, &proofs, &ctx).unwrap();
+    let occurrence =
+        handle_stacks_hook_action(trigger, &proofs, None, &SigningSecrets::default(), &ctx)
+            .unwrap();
     if let StacksChainhookOccurrence::Data(data) = occurrence {
         assert_eq!(data.apply.len(), 1);
         assert_eq!(
@@ -757,6 +771,7 @@ fn test_stacks_hook_action_file_append() {
     let chainhook = StacksChainhookInstance {
         uuid: "".to_string(),
         owner_uuid: None,
+        on_expire_url: None,
         name: "".to_string(),
         network: StacksNetwork::Testnet,
         version: 1,
@@ -766,6 +781,7 @@ fn test_stacks_hook_action_file_append() {
         expire_after_occurrence: None,
         capture_all_events: None,
         decode_clarity_values: Some(true),
+        skip_rollback_only_occurrences: None,
         include_contract_abi: None,
         predicate: StacksPredicate::Txid(ExactMatchingRule::Equals(
             "0xb92c2ade84a8b85f4c72170680ae42e65438aea4db72ba4b2d6a6960f4141ce8".to_string(),
@@ -812,7 +828,9 @@ fn test_stacks_hook_action_file_append() {
         logger: None,
         tracer: false,
     };
-    let occurrence = handle_stacks_hook_action(trigger, &proofs, &ctx).unwrap();
+    let occurrence =
+        handle_stacks_hook_action(trigger, &proofs, None, &SigningSecrets::default(), &ctx)
+            .unwrap();
     if let StacksChainhookOccurrence::File(path, bytes) = occurrence {
         assert_eq!(path, "./".to_string());
         let json: JsonValue = serde_json::from_slice(&bytes).unwrap();
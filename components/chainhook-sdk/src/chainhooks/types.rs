@@ -1,8 +1,15 @@
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Duration;
 
-use chainhook_types::{BitcoinNetwork, StacksNetwork};
+use bitcoincore_rpc_json::bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoincore_rpc_json::bitcoin::hashes::{sha256, sha512, Hash, HashEngine};
+use chainhook_types::{BitcoinNetwork, BlockIdentifier, Chain, StacksNetwork};
+use regex::Regex;
+use reqwest::{Client, Method, RequestBuilder};
 use serde::ser::{SerializeSeq, Serializer};
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 
 use schemars::JsonSchema;
 
@@ -10,6 +17,7 @@ use crate::chainhooks::bitcoin::BitcoinChainhookInstance;
 use crate::chainhooks::bitcoin::BitcoinChainhookSpecificationNetworkMap;
 use crate::chainhooks::stacks::StacksChainhookInstance;
 use crate::chainhooks::stacks::StacksChainhookSpecificationNetworkMap;
+use crate::utils::HttpRetryPolicy;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ChainhookStore {
@@ -51,7 +59,9 @@ impl ChainhookStore {
                 for spec in self.stacks_chainhooks.iter_mut() {
                     if spec.uuid.eq(&spec_to_enable.uuid) {
                         spec.enabled = true;
-                        spec_to_enable.enabled = true;
+                        // the registered instance is the source of truth (e.g. it may have
+                        // been reloaded while it was scanning)
+                        *spec_to_enable = spec.clone();
                         break;
                     }
                 }
@@ -60,7 +70,9 @@ impl ChainhookStore {
                 for spec in self.bitcoin_chainhooks.iter_mut() {
                     if spec.uuid.eq(&spec_to_enable.uuid) {
                         spec.enabled = true;
-                        spec_to_enable.enabled = true;
+                        // the registered instance is the source of truth (e.g. it may have
+                        // been reloaded while it was scanning)
+                        *spec_to_enable = spec.clone();
                         break;
                     }
                 }
@@ -68,6 +80,114 @@ impl ChainhookStore {
         };
     }
 
+    /// Replaces the action of the registered instance `predicate_uuid`. Fails when the instance is
+    /// unknown, or when its chain doesn't support `action`.
+    pub fn update_instance_action(
+        &mut self,
+        predicate_uuid: &str,
+        action: HookAction,
+    ) -> Result<ChainhookInstance, String> {
+        for spec in self.stacks_chainhooks.iter_mut() {
+            if spec.uuid.eq(predicate_uuid) {
+                action
+                    .validate_for_chain(&Chain::Stacks)
+                    .map_err(|e| e.join(", "))?;
+                spec.action = action;
+                return Ok(ChainhookInstance::Stacks(spec.clone()));
+            }
+        }
+        for spec in self.bitcoin_chainhooks.iter_mut() {
+            if spec.uuid.eq(predicate_uuid) {
+                action
+                    .validate_for_chain(&Chain::Bitcoin)
+                    .map_err(|e| e.join(", "))?;
+                spec.action = action;
+                return Ok(ChainhookInstance::Bitcoin(spec.clone()));
+            }
+        }
+        Err(format!("unknown predicate {}", predicate_uuid))
+    }
+
+    /// Reconciles the registered instances with `instances`, read from the predicates db, only
+    /// touching the instances that differ:
+    /// - the registered instances are replaced by their version in `instances` when it changed,
+    ///   keeping their `enabled` and `expired_at` state;
+    /// - the enabled instances missing from `instances` are deregistered. Disabled ones are kept,
+    ///   since they may not be written to the db yet while waiting for their scan;
+    /// - the instances that are not registered are registered, disabled until they're scanned.
+    ///
+    /// Returns the updated instances, the registered ones and the deregistered ones.
+    pub fn reload_instances(
+        &mut self,
+        instances: Vec<ChainhookInstance>,
+    ) -> (
+        Vec<ChainhookInstance>,
+        Vec<ChainhookInstance>,
+        Vec<ChainhookInstance>,
+    ) {
+        let mut instances = instances
+            .into_iter()
+            .map(|instance| (instance.key(), instance))
+            .collect::<HashMap<_, _>>();
+        let mut updated = vec![];
+        let mut removed = vec![];
+        let mut i = 0;
+        while i < self.stacks_chainhooks.len() {
+            let spec = &mut self.stacks_chainhooks[i];
+            match instances.remove(&spec.key()) {
+                Some(ChainhookInstance::Stacks(mut reloaded)) => {
+                    reloaded.enabled = spec.enabled;
+                    reloaded.expired_at = spec.expired_at;
+                    if reloaded != *spec {
+                        *spec = reloaded.clone();
+                        updated.push(ChainhookInstance::Stacks(reloaded));
+                    }
+                    i += 1;
+                }
+                _ if spec.enabled => {
+                    let spec = self.stacks_chainhooks.remove(i);
+                    removed.push(ChainhookInstance::Stacks(spec));
+                }
+                _ => i += 1,
+            }
+        }
+        let mut i = 0;
+        while i < self.bitcoin_chainhooks.len() {
+            let spec = &mut self.bitcoin_chainhooks[i];
+            match instances.remove(&spec.key()) {
+                Some(ChainhookInstance::Bitcoin(mut reloaded)) => {
+                    reloaded.enabled = spec.enabled;
+                    reloaded.expired_at = spec.expired_at;
+                    if reloaded != *spec {
+                        *spec = reloaded.clone();
+                        updated.push(ChainhookInstance::Bitcoin(reloaded));
+                    }
+                    i += 1;
+                }
+                _ if spec.enabled => {
+                    let spec = self.bitcoin_chainhooks.remove(i);
+                    removed.push(ChainhookInstance::Bitcoin(spec));
+                }
+                _ => i += 1,
+            }
+        }
+        let mut added = instances.into_values().collect::<Vec<_>>();
+        added.sort_by(|a, b| a.key().cmp(&b.key()));
+        for instance in added.iter_mut() {
+            match instance {
+                ChainhookInstance::Stacks(spec) => {
+                    spec.enabled = false;
+                    self.stacks_chainhooks.push(spec.clone());
+                }
+                ChainhookInstance::Bitcoin(spec) => {
+                    spec.enabled = false;
+                    self.bitcoin_chainhooks.push(spec.clone());
+                }
+            }
+        }
+        (updated, added, removed)
+    }
+
     pub fn register_instance(&mut self, spec: ChainhookInstance) -> Result<(), String> {
         match spec {
             ChainhookInstance::Stacks(spec) => {
@@ -193,6 +313,34 @@ impl ChainhookInstance {
             Self::Stacks(data) => &data.uuid,
         }
     }
+
+    pub fn chain(&self) -> Chain {
+        match &self {
+            Self::Bitcoin(_) => Chain::Bitcoin,
+            Self::Stacks(_) => Chain::Stacks,
+        }
+    }
+
+    pub fn on_expire_url(&self) -> Option<&str> {
+        match &self {
+            Self::Bitcoin(data) => data.on_expire_url.as_deref(),
+            Self::Stacks(data) => data.on_expire_url.as_deref(),
+        }
+    }
+
+    pub fn owner_uuid(&self) -> Option<&str> {
+        match &self {
+            Self::Bitcoin(data) => data.owner_uuid.as_deref(),
+            Self::Stacks(data) => data.owner_uuid.as_deref(),
+        }
+    }
+
+    pub fn action(&self) -> &HookAction {
+        match &self {
+            Self::Bitcoin(data) => &data.action,
+            Self::Stacks(data) => &data.action,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -203,12 +351,43 @@ pub enum ChainhookSpecificationNetworkMap {
 }
 
 impl ChainhookSpecificationNetworkMap {
+    /// Returns a warning for each network on which the predicate, although valid, can never match.
+    pub fn get_warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        match &self {
+            Self::Bitcoin(data) => {
+                for (network, spec) in data.networks.iter() {
+                    for condition in spec.get_unsatisfiable_conditions() {
+                        warnings.push(format!(
+                            "Bitcoin predicate '{}' for network {} can never match: {}",
+                            data.name, network, condition
+                        ));
+                    }
+                }
+            }
+            Self::Stacks(data) => {
+                for (network, spec) in data.networks.iter() {
+                    for condition in spec.get_unsatisfiable_conditions() {
+                        warnings.push(format!(
+                            "Stacks predicate '{}' for network {} can never match: {}",
+                            data.name, network, condition
+                        ));
+                    }
+                }
+            }
+        }
+        warnings
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         match &self {
             Self::Bitcoin(data) => {
                 let mut errors = vec![];
+                if let Err(e) = validate_on_expire_url(&data.on_expire_url) {
+                    errors.push(format!("invalid Bitcoin predicate '{}': {}", data.name, e));
+                }
                 for (network, spec) in data.networks.iter() {
-                    if let Err(e) = spec.validate() {
+                    if let Err(e) = spec.validate_for_network(network) {
                         errors.append(&mut append_error_context(
                             &format!(
                                 "invalid Bitcoin predicate '{}' for network {}",
@@ -224,6 +403,9 @@ impl ChainhookSpecificationNetworkMap {
             }
             Self::Stacks(data) => {
                 let mut errors = vec![];
+                if let Err(e) = validate_on_expire_url(&data.on_expire_url) {
+                    errors.push(format!("invalid Stacks predicate '{}': {}", data.name, e));
+                }
                 for (network, spec) in data.networks.iter() {
                     if let Err(e) = spec.validate() {
                         errors.append(&mut append_error_context(
@@ -250,6 +432,27 @@ impl ChainhookSpecificationNetworkMap {
         }
     }
 
+    pub fn get_owner_uuid(&self) -> Option<&str> {
+        match &self {
+            Self::Bitcoin(data) => data.owner_uuid.as_deref(),
+            Self::Stacks(data) => data.owner_uuid.as_deref(),
+        }
+    }
+
+    pub fn set_uuid(&mut self, uuid: &str) {
+        match self {
+            Self::Bitcoin(data) => data.uuid = uuid.to_string(),
+            Self::Stacks(data) => data.uuid = uuid.to_string(),
+        }
+    }
+
+    pub fn set_owner_uuid(&mut self, owner_uuid: &str) {
+        match self {
+            Self::Bitcoin(data) => data.owner_uuid = Some(owner_uuid.to_string()),
+            Self::Stacks(data) => data.owner_uuid = Some(owner_uuid.to_string()),
+        }
+    }
+
     pub fn deserialize_specification(
         spec: &str,
         _key: &str,
@@ -260,11 +463,37 @@ impl ChainhookSpecificationNetworkMap {
     }
 }
 
+fn validate_on_expire_url(on_expire_url: &Option<String>) -> Result<(), String> {
+    if let Some(url) = on_expire_url {
+        if let Err(e) = reqwest::Url::from_str(url) {
+            return Err(format!(
+                "on_expire_url must be a valid Url: {}",
+                e.to_string()
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HookAction {
     HttpPost(HttpHook),
     FileAppend(FileHook),
+    UnixSocket(UnixSocketHook),
+    #[cfg(feature = "websocket")]
+    #[serde(rename = "websocket")]
+    WebSocket(WebSocketHook),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetHook),
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaHook),
+    #[cfg(feature = "grpc")]
+    Grpc(GrpcHook),
+    #[cfg(feature = "sqs")]
+    Sqs(SqsHook),
+    #[cfg(feature = "s3")]
+    S3Append(S3AppendHook),
     Noop,
 }
 
@@ -277,10 +506,242 @@ impl HookAction {
                 }
             }
             HookAction::FileAppend(_) => {}
+            HookAction::UnixSocket(spec) => {
+                if let Err(e) = spec.validate() {
+                    return Err(append_error_context("invalid 'unix_socket' data", e));
+                }
+            }
+            #[cfg(feature = "websocket")]
+            HookAction::WebSocket(spec) => {
+                if let Err(e) = spec.validate() {
+                    return Err(append_error_context("invalid 'websocket' data", e));
+                }
+            }
+            #[cfg(feature = "parquet")]
+            HookAction::Parquet(spec) => {
+                if let Err(e) = spec.validate() {
+                    return Err(append_error_context("invalid 'parquet' data", e));
+                }
+            }
+            #[cfg(feature = "kafka")]
+            HookAction::Kafka(spec) => {
+                if let Err(e) = spec.validate() {
+                    return Err(append_error_context("invalid 'kafka' data", e));
+                }
+            }
+            #[cfg(feature = "grpc")]
+            HookAction::Grpc(spec) => {
+                if let Err(e) = spec.validate() {
+                    return Err(append_error_context("invalid 'grpc' data", e));
+                }
+            }
+            #[cfg(feature = "sqs")]
+            HookAction::Sqs(spec) => {
+                if let Err(e) = spec.validate() {
+                    return Err(append_error_context("invalid 'sqs' data", e));
+                }
+            }
+            #[cfg(feature = "s3")]
+            HookAction::S3Append(spec) => {
+                if let Err(e) = spec.validate() {
+                    return Err(append_error_context("invalid 's3_append' data", e));
+                }
+            }
             HookAction::Noop => {}
         }
         Ok(())
     }
+
+    /// Validates the action for the predicates of `chain`, some actions being only supported by
+    /// Bitcoin predicates.
+    pub fn validate_for_chain(&self, chain: &Chain) -> Result<(), Vec<String>> {
+        self.validate()?;
+        match (chain, self.get_bitcoin_only_name()) {
+            (Chain::Stacks, Some(name)) => Err(vec![format!(
+                "the {} action is only supported by bitcoin predicates",
+                name
+            )]),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the name of the action when only Bitcoin predicates support it.
+    pub fn get_bitcoin_only_name(&self) -> Option<&str> {
+        match &self {
+            HookAction::HttpPost(_)
+            | HookAction::FileAppend(_)
+            | HookAction::UnixSocket(_)
+            | HookAction::Noop => None,
+            #[cfg(feature = "parquet")]
+            HookAction::Parquet(_) => Some("parquet"),
+            #[cfg(feature = "kafka")]
+            HookAction::Kafka(_) => Some("kafka"),
+            #[cfg(feature = "grpc")]
+            HookAction::Grpc(_) => Some("grpc"),
+            #[cfg(feature = "sqs")]
+            HookAction::Sqs(_) => Some("sqs"),
+            #[cfg(feature = "s3")]
+            HookAction::S3Append(_) => Some("s3_append"),
+            #[cfg(feature = "websocket")]
+            HookAction::WebSocket(_) => Some("websocket"),
+        }
+    }
+
+    pub fn http_hook(&self) -> Option<&HttpHook> {
+        match &self {
+            HookAction::HttpPost(spec) => Some(spec),
+            _ => None,
+        }
+    }
+
+    pub fn failover_urls(&self) -> &[String] {
+        match &self {
+            HookAction::HttpPost(spec) => &spec.failover_urls,
+            _ => &[],
+        }
+    }
+
+    /// Builds the request posting `intent` to the `verification_url` of `http_post` actions, sent
+    /// with `http_client`.
+    pub fn build_verification_request(
+        &self,
+        http_client: &Client,
+        signing_secrets: &SigningSecrets,
+        intent: &JsonValue,
+    ) -> Option<RequestBuilder> {
+        let HookAction::HttpPost(http) = self else {
+            return None;
+        };
+        http.build_verification_request(http_client, signing_secrets, intent)
+    }
+}
+
+/// The intent posted to the `verification_url` of a predicate before one of its occurrences: the
+/// predicate, and the blocks applied and rolled back by the occurrence.
+pub fn build_delivery_intent(
+    uuid: &str,
+    name: &str,
+    apply: Vec<&BlockIdentifier>,
+    rollback: Vec<&BlockIdentifier>,
+) -> JsonValue {
+    json!({
+        "chainhook": {
+            "uuid": uuid,
+            "name": name,
+        },
+        "apply": apply,
+        "rollback": rollback,
+    })
+}
+
+/// Header carrying the signature of the payloads posted by predicates with a `signing_secret_id`.
+pub const HOOK_SIGNATURE_HEADER: &str = "X-Hook-Signature";
+
+/// Hash functions computing the content hashes of payloads, configured on the server: the
+/// deduplication ids of queued messages and the signatures of `http_post` payloads. `xxh3` is a
+/// fast non-cryptographic hash, suited to high-throughput deduplication but not to signatures.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentHashAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+    Xxh3,
+}
+
+impl ContentHashAlgorithm {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ContentHashAlgorithm::Sha256 => "sha256",
+            ContentHashAlgorithm::Sha512 => "sha512",
+            ContentHashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// Returns the hex encoded hash of `bytes`.
+    pub fn hash(&self, bytes: &[u8]) -> String {
+        match self {
+            ContentHashAlgorithm::Sha256 => sha256::Hash::hash(bytes).to_string(),
+            ContentHashAlgorithm::Sha512 => sha512::Hash::hash(bytes).to_string(),
+            ContentHashAlgorithm::Xxh3 => {
+                format!("{:032x}", xxhash_rust::xxh3::xxh3_128(bytes))
+            }
+        }
+    }
+
+    pub fn is_cryptographic(&self) -> bool {
+        match self {
+            ContentHashAlgorithm::Sha256 | ContentHashAlgorithm::Sha512 => true,
+            ContentHashAlgorithm::Xxh3 => false,
+        }
+    }
+}
+
+impl FromStr for ContentHashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(ContentHashAlgorithm::Sha256),
+            "sha512" => Ok(ContentHashAlgorithm::Sha512),
+            "xxh3" => Ok(ContentHashAlgorithm::Xxh3),
+            _ => Err(format!("unknown hash '{}'", s)),
+        }
+    }
+}
+
+/// Secrets signing the payloads of `http_post` actions, configured on the server and keyed by the
+/// `signing_secret_id` predicates reference them with. Predicates never hold the secrets
+/// themselves, so that they're neither persisted with the predicates nor logged.
+#[derive(Clone, Default, PartialEq)]
+pub struct SigningSecrets {
+    secrets: HashMap<String, String>,
+    hash: ContentHashAlgorithm,
+}
+
+impl SigningSecrets {
+    /// Secrets signing payloads with HMAC-SHA256.
+    pub fn new(secrets: HashMap<String, String>) -> Self {
+        SigningSecrets {
+            secrets,
+            hash: ContentHashAlgorithm::Sha256,
+        }
+    }
+
+    /// Signs payloads with the HMAC of `hash` instead. Fails with non-cryptographic hashes.
+    pub fn with_hash(self, hash: ContentHashAlgorithm) -> Result<Self, String> {
+        if !hash.is_cryptographic() {
+            return Err(format!(
+                "{} is not a cryptographic hash and can't sign payloads",
+                hash.as_str()
+            ));
+        }
+        Ok(SigningSecrets { hash, ..self })
+    }
+
+    pub fn get(&self, secret_id: &str) -> Option<&str> {
+        self.secrets.get(secret_id).map(|secret| secret.as_str())
+    }
+
+    /// Signs `body` with the secret `secret_id`. Fails when that secret is not configured.
+    pub fn sign(&self, secret_id: &str, body: &[u8]) -> Result<String, String> {
+        let secret = self
+            .get(secret_id)
+            .ok_or(format!("unknown signing secret {}", secret_id))?;
+        sign_payload(secret, body, self.hash)
+    }
+}
+
+// only the ids of the secrets are printed, so that they never end up in the logs
+impl std::fmt::Debug for SigningSecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut secret_ids = self.secrets.keys().collect::<Vec<_>>();
+        secret_ids.sort();
+        f.debug_struct("SigningSecrets")
+            .field("secret_ids", &secret_ids)
+            .field("hash", &self.hash)
+            .finish()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -288,14 +749,122 @@ impl HookAction {
 pub struct HttpHook {
     pub url: String,
     pub authorization_header: String,
+    /// Urls tried in order when an occurrence can't be delivered to `url`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failover_urls: Vec<String>,
+    /// Id of the secret signing the payloads, among the signing secrets configured on the server.
+    /// The signature is sent in the `X-Hook-Signature` header as the name of the hash configured
+    /// on the server, `sha256` by default, followed by `=` and the hex encoded HMAC of the body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_secret_id: Option<String>,
+    /// Url receiving a lightweight intent before each occurrence, which is only delivered to `url`
+    /// once the intent is answered with a 2xx status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification_url: Option<String>,
+}
+
+/// Signs `body` with `secret`: the name of `hash` followed by `=` and the hex encoded HMAC of the
+/// body, such as `sha256=<HMAC-SHA256>`. Fails with non-cryptographic hashes.
+pub fn sign_payload(
+    secret: &str,
+    body: &[u8],
+    hash: ContentHashAlgorithm,
+) -> Result<String, String> {
+    let signature = match hash {
+        ContentHashAlgorithm::Sha256 => {
+            let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+            engine.input(body);
+            Hmac::<sha256::Hash>::from_engine(engine)
+                .to_byte_array()
+                .to_vec()
+        }
+        ContentHashAlgorithm::Sha512 => {
+            let mut engine = HmacEngine::<sha512::Hash>::new(secret.as_bytes());
+            engine.input(body);
+            Hmac::<sha512::Hash>::from_engine(engine)
+                .to_byte_array()
+                .to_vec()
+        }
+        ContentHashAlgorithm::Xxh3 => {
+            return Err(format!(
+                "unable to sign payload with non-cryptographic hash {}",
+                hash.as_str()
+            ))
+        }
+    };
+    Ok(format!("{}={}", hash.as_str(), hex::encode(signature)))
 }
 
 impl HttpHook {
+    /// Builds the request posting `body` to `url`, signed with the secret of `signing_secrets`
+    /// referenced by `signing_secret_id`, if any. Fails when that secret is not configured.
+    pub fn build_request(
+        &self,
+        client: &Client,
+        signing_secrets: &SigningSecrets,
+        body: Vec<u8>,
+    ) -> Result<RequestBuilder, String> {
+        self.build_request_to(client, signing_secrets, &self.url, body)
+    }
+
+    /// Builds the request posting `intent` to the `verification_url`, if any.
+    pub fn build_verification_request(
+        &self,
+        client: &Client,
+        signing_secrets: &SigningSecrets,
+        intent: &JsonValue,
+    ) -> Option<RequestBuilder> {
+        let url = self.verification_url.as_ref()?;
+        let body = serde_json::to_vec(intent).ok()?;
+        self.build_request_to(client, signing_secrets, url, body)
+            .ok()
+    }
+
+    /// Builds the request posting `body` to `url` with the headers of the hook, e.g. to redeliver an
+    /// occurrence to the endpoint it was first posted to.
+    pub fn build_request_to(
+        &self,
+        client: &Client,
+        signing_secrets: &SigningSecrets,
+        url: &str,
+        body: Vec<u8>,
+    ) -> Result<RequestBuilder, String> {
+        let mut request = client
+            .request(Method::POST, url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", self.authorization_header.clone());
+        if let Some(ref secret_id) = self.signing_secret_id {
+            request = request.header(
+                HOOK_SIGNATURE_HEADER,
+                signing_secrets.sign(secret_id, &body)?,
+            );
+        }
+        Ok(request.body(body))
+    }
+
     pub fn validate(&self) -> Result<(), Vec<String>> {
         let mut errors = vec![];
         if let Err(e) = reqwest::Url::from_str(&self.url) {
             errors.push(format!("url string must be a valid Url: {}", e.to_string()));
         }
+        for url in self.failover_urls.iter() {
+            if let Err(e) = reqwest::Url::from_str(url) {
+                errors.push(format!(
+                    "failover url {} must be a valid Url: {}",
+                    url,
+                    e.to_string()
+                ));
+            }
+        }
+        if let Some(ref url) = self.verification_url {
+            if let Err(e) = reqwest::Url::from_str(url) {
+                errors.push(format!(
+                    "verification url {} must be a valid Url: {}",
+                    url,
+                    e.to_string()
+                ));
+            }
+        }
         if let Err(e) = reqwest::header::HeaderValue::from_str(&self.authorization_header) {
             errors.push(format!(
                 "auth header must be a valid header value: {}",
@@ -311,11 +880,313 @@ impl HttpHook {
     }
 }
 
+/// Settings of the client delivering `http_post` occurrences.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HttpDeliveryConfig {
+    /// Timeout of a delivery, from connecting to reading the response. Deliveries never time out when unset.
+    pub timeout_secs: Option<u64>,
+    /// Maximum number of idle connections kept open to each consumer.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Idle connections are closed after this many seconds.
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// Retries of the deliveries failing with a connection error or a retryable status.
+    pub retry_policy: HttpRetryPolicy,
+    /// Secrets signing the payloads of the predicates referencing them.
+    pub signing_secrets: SigningSecrets,
+}
+
+pub fn build_http_delivery_client(config: &HttpDeliveryConfig) -> Result<Client, String> {
+    let mut builder = Client::builder();
+    if let Some(timeout_secs) = config.timeout_secs {
+        builder = builder.timeout(Duration::from_secs(timeout_secs));
+    }
+    if let Some(max_idle) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(idle_timeout_secs) = config.pool_idle_timeout_secs {
+        builder = builder.pool_idle_timeout(Duration::from_secs(idle_timeout_secs));
+    }
+    builder
+        .build()
+        .map_err(|e| format!("unable to build http client: {}", e.to_string()))
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct FileHook {
     pub path: String,
 }
+
+/// Pushes occurrences as JSON text frames over a persistent connection to the websocket at `url`.
+#[cfg(feature = "websocket")]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct WebSocketHook {
+    pub url: String,
+}
+
+#[cfg(feature = "websocket")]
+impl WebSocketHook {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        match reqwest::Url::from_str(&self.url) {
+            Ok(url) if url.scheme() != "ws" && url.scheme() != "wss" => {
+                errors.push(format!("url scheme must be ws or wss"));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                errors.push(format!("url string must be a valid Url: {}", e.to_string()));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct UnixSocketHook {
+    pub path: String,
+}
+
+impl UnixSocketHook {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if cfg!(not(unix)) {
+            errors.push(format!("unix sockets are not supported on this platform"));
+        }
+        if self.path.is_empty() {
+            errors.push(format!("path must not be empty"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Appends the transactions matched by a Bitcoin predicate to rolling Parquet files written in `path`.
+#[cfg(feature = "parquet")]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ParquetHook {
+    pub path: String,
+    /// Size, in megabytes, after which the current file is closed and a new one is started. Defaults to 128.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size_mb: Option<u64>,
+    /// Age, in seconds, after which the current file is closed and a new one is started. Defaults to 3600.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_age_sec: Option<u64>,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetHook {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.path.is_empty() {
+            errors.push(format!("path must not be empty"));
+        }
+        if self.max_file_size_mb == Some(0) {
+            errors.push(format!("max_file_size_mb must be greater than 0"));
+        }
+        if self.max_file_age_sec == Some(0) {
+            errors.push(format!("max_file_age_sec must be greater than 0"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+/// Produces occurrences as messages to the Kafka `topic` of the cluster reachable through `brokers`.
+#[cfg(feature = "kafka")]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KafkaHook {
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// Key of the messages, where `{uuid}` and `{name}` are replaced by the uuid and the name of the
+    /// predicate. Messages sharing a key land in the same partition, and are consumed in order.
+    /// Defaults to `{uuid}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_template: Option<String>,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaHook {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.brokers.is_empty() {
+            errors.push(format!("brokers must not be empty"));
+        }
+        if self.brokers.iter().any(|broker| broker.is_empty()) {
+            errors.push(format!("brokers must not contain empty addresses"));
+        }
+        if self.topic.is_empty() {
+            errors.push(format!("topic must not be empty"));
+        }
+        if let Some(ref key_template) = self.key_template {
+            let unknown_placeholder = key_template
+                .replace("{uuid}", "")
+                .replace("{name}", "")
+                .contains(['{', '}']);
+            if unknown_placeholder {
+                errors.push(format!(
+                    "key_template only admits the {{uuid}} and {{name}} placeholders"
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Renders the key of the messages produced for the predicate `uuid` named `name`.
+    pub fn render_key(&self, uuid: &str, name: &str) -> String {
+        match self.key_template {
+            Some(ref key_template) => key_template.replace("{uuid}", uuid).replace("{name}", name),
+            None => uuid.to_string(),
+        }
+    }
+}
+
+/// Streams occurrences to the `OccurrenceSink` service served at `endpoint`, as defined in
+/// `proto/chainhook/v1/occurrences.proto`.
+#[cfg(feature = "grpc")]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct GrpcHook {
+    pub endpoint: String,
+}
+
+#[cfg(feature = "grpc")]
+impl GrpcHook {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        match reqwest::Url::from_str(&self.endpoint) {
+            Ok(url) if url.scheme() != "http" && url.scheme() != "https" => {
+                errors.push(format!("endpoint scheme must be http or https"));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                errors.push(format!(
+                    "endpoint string must be a valid Url: {}",
+                    e.to_string()
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Sends occurrences as messages to the AWS SQS queue at `queue_url`, with the credentials found
+/// in the environment.
+#[cfg(feature = "sqs")]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SqsHook {
+    pub queue_url: String,
+    pub region: String,
+    /// Hash of the body used as the deduplication id of the messages sent to FIFO queues.
+    /// Defaults to the deduplication hash configured on the server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deduplication_hash: Option<ContentHashAlgorithm>,
+}
+
+#[cfg(feature = "sqs")]
+impl SqsHook {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if let Err(e) = reqwest::Url::from_str(&self.queue_url) {
+            errors.push(format!(
+                "queue_url string must be a valid Url: {}",
+                e.to_string()
+            ));
+        }
+        if self.region.is_empty() {
+            errors.push(format!("region must not be empty"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// FIFO queues are the queues whose name ends with `.fifo`.
+    pub fn is_fifo(&self) -> bool {
+        self.queue_url.ends_with(".fifo")
+    }
+
+    /// Returns the deduplication id of a message holding `body`, hashed with the
+    /// `deduplication_hash` of the action or, when unset, with `default_hash`.
+    pub fn get_deduplication_id(&self, body: &str, default_hash: ContentHashAlgorithm) -> String {
+        self.deduplication_hash
+            .unwrap_or(default_hash)
+            .hash(body.as_bytes())
+    }
+}
+
+/// Writes occurrences as objects of the AWS S3 `bucket`, under `key_prefix`, with the credentials
+/// found in the environment.
+#[cfg(feature = "s3")]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct S3AppendHook {
+    pub bucket: String,
+    pub key_prefix: String,
+    pub region: String,
+    /// Layout of the keys of the objects written. Defaults to `timestamp`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_layout: Option<S3KeyLayout>,
+}
+
+#[cfg(feature = "s3")]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum S3KeyLayout {
+    /// One `<timestamp>-<block_hash>-<predicate_uuid>.json` object per occurrence.
+    Timestamp,
+    /// One object per occurrence, grouped by ranges of `range_size` blocks:
+    /// `<range_start>-<range_end>/<block_height>-<block_hash>-<timestamp>-<predicate_uuid>.json`.
+    BlockHeightRange { range_size: u64 },
+    /// One object per occurrence, grouped by UTC day:
+    /// `<date>/<timestamp>-<block_hash>-<predicate_uuid>.json`.
+    Daily,
+}
+
+#[cfg(feature = "s3")]
+impl S3AppendHook {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.bucket.is_empty() {
+            errors.push(format!("bucket must not be empty"));
+        }
+        if self.region.is_empty() {
+            errors.push(format!("region must not be empty"));
+        }
+        if let Some(S3KeyLayout::BlockHeightRange { range_size: 0 }) = self.key_layout {
+            errors.push(format!("key_layout range_size must be greater than 0"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
 // todo: can we remove this struct?
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct ScriptTemplate {
@@ -403,6 +1274,56 @@ pub enum MatchingRule {
     Equals(String),
     StartsWith(String),
     EndsWith(String),
+    /// Matches a regular expression, which is only anchored by its own `^` and `$`.
+    Regex(#[schemars(with = "String")] MatchingRegex),
+    /// Matches the data found at a byte offset.
+    AtOffset(OffsetMatchingRule),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct OffsetMatchingRule {
+    /// Offset, in bytes, of the data compared to `value`. Offsets beyond the end of the data
+    /// don't match.
+    pub offset: u64,
+    pub value: String,
+}
+
+/// A regular expression, compiled once when the predicate is deserialized so that invalid
+/// patterns are rejected when the predicate is registered.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MatchingRegex(Regex);
+
+impl MatchingRegex {
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.0.is_match(haystack)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl TryFrom<String> for MatchingRegex {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Regex::new(&pattern)
+            .map(MatchingRegex)
+            .map_err(|e| format!("invalid regex {}: {}", pattern, e.to_string()))
+    }
+}
+
+impl From<MatchingRegex> for String {
+    fn from(regex: MatchingRegex) -> Self {
+        regex.as_str().to_string()
+    }
+}
+
+impl PartialEq for MatchingRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -1,7 +1,8 @@
 use std::time::Duration;
 
 use crate::chainhooks::bitcoin::{
-    get_canonical_pox_config, get_stacks_canonical_magic_bytes, PoxConfig, StacksOpcodes,
+    get_bitcoin_network, get_canonical_pox_config, get_stacks_canonical_magic_bytes, PoxConfig,
+    StacksOpcodes,
 };
 use crate::observer::BitcoinConfig;
 use crate::utils::Context;
@@ -64,6 +65,26 @@ pub struct BitcoinTransactionFullBreakdown {
     pub txid: String,
     pub vin: Vec<BitcoinTransactionInputFullBreakdown>,
     pub vout: Vec<BitcoinTransactionOutputFullBreakdown>,
+    #[serde(default)]
+    pub weight: u64,
+    #[serde(default, deserialize_with = "deserialize_transaction_version")]
+    pub version: i32,
+}
+
+/// `nVersion` is a signed integer, reported as an unsigned one by Bitcoin Core since v28: both
+/// representations are read as the same signed integer.
+fn deserialize_transaction_version<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let version = i64::deserialize(deserializer)?;
+    if version < i32::MIN as i64 || version > u32::MAX as i64 {
+        return Err(serde::de::Error::custom(format!(
+            "invalid transaction version {}",
+            version
+        )));
+    }
+    Ok(version as u32 as i32)
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -104,6 +125,9 @@ pub struct BitcoinTransactionInputPrevoutFullBreakdown {
     pub height: u64,
     #[serde(with = "bitcoin::amount::serde::as_btc")]
     pub value: Amount,
+    /// Only provided by nodes reporting the script of the spent outputs.
+    #[serde(default)]
+    pub script_pub_key: Option<GetRawTransactionResultVoutScriptPubKey>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -341,9 +365,60 @@ pub async fn download_and_parse_block(
     parse_downloaded_block(response)
 }
 
+/// The Stacks operations parsed out of Bitcoin transactions when standardizing blocks. Every
+/// operation is parsed by default, since blocks carry them whatever the predicates evaluated:
+/// operators can only opt out of some of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StacksOperationsParsing {
+    pub block_committed: bool,
+    pub leader_registered: bool,
+    pub stx_transferred: bool,
+    pub stx_locked: bool,
+}
+
+impl Default for StacksOperationsParsing {
+    fn default() -> Self {
+        StacksOperationsParsing {
+            block_committed: true,
+            leader_registered: true,
+            stx_transferred: true,
+            stx_locked: true,
+        }
+    }
+}
+
+impl StacksOperationsParsing {
+    pub fn none() -> Self {
+        StacksOperationsParsing {
+            block_committed: false,
+            leader_registered: false,
+            stx_transferred: false,
+            stx_locked: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &StacksOperationsParsing::none()
+    }
+
+    fn is_enabled(&self, opcode: &StacksOpcodes) -> bool {
+        match opcode {
+            StacksOpcodes::BlockCommit => self.block_committed,
+            StacksOpcodes::KeyRegister => self.leader_registered,
+            StacksOpcodes::TransferStx => self.stx_transferred,
+            StacksOpcodes::StackStx => self.stx_locked,
+            // pre-stx operations are never reported
+            StacksOpcodes::PreStx => false,
+        }
+    }
+}
+
+/// Only the Stacks operations enabled in `stacks_operations` are parsed: the transactions of the
+/// returned block don't carry the other ones.
 pub fn standardize_bitcoin_block(
     block: BitcoinBlockFullBreakdown,
     network: &BitcoinNetwork,
+    stacks_operations: &StacksOperationsParsing,
     ctx: &Context,
 ) -> Result<BitcoinBlockData, (String, bool)> {
     let mut transactions = vec![];
@@ -356,16 +431,20 @@ pub fn standardize_bitcoin_block(
     for (tx_index, mut tx) in block.tx.into_iter().enumerate() {
         let txid = tx.txid.to_string();
 
-        let mut stacks_operations = vec![];
-        if let Some(op) = try_parse_stacks_operation(
-            block_height,
-            &tx.vin,
-            &tx.vout,
-            &pox_config,
-            &expected_magic_bytes,
-            ctx,
-        ) {
-            stacks_operations.push(op);
+        let mut parsed_stacks_operations = vec![];
+        if !stacks_operations.is_empty() {
+            if let Some(op) = try_parse_stacks_operation(
+                block_height,
+                &tx.vin,
+                &tx.vout,
+                network,
+                &pox_config,
+                &expected_magic_bytes,
+                stacks_operations,
+                ctx,
+            ) {
+                parsed_stacks_operations.push(op);
+            }
         }
 
         let mut inputs = vec![];
@@ -446,12 +525,16 @@ pub fn standardize_bitcoin_block(
             metadata: BitcoinTransactionMetadata {
                 inputs,
                 outputs,
-                stacks_operations,
+                stacks_operations: parsed_stacks_operations,
                 ordinal_operations: vec![],
                 brc20_operation: None,
                 proof: None,
                 fee: sats_in.saturating_sub(sats_out),
                 index: tx_index as u32,
+                weight: tx.weight,
+                // virtual size, as defined by BIP141: weight / 4, rounded up.
+                vsize: tx.weight.div_ceil(4),
+                version: tx.version,
             },
         };
         transactions.push(tx);
@@ -484,10 +567,12 @@ pub fn standardize_bitcoin_block(
 
 fn try_parse_stacks_operation(
     block_height: u64,
-    _inputs: &Vec<BitcoinTransactionInputFullBreakdown>,
+    inputs: &Vec<BitcoinTransactionInputFullBreakdown>,
     outputs: &Vec<BitcoinTransactionOutputFullBreakdown>,
+    network: &BitcoinNetwork,
     pox_config: &PoxConfig,
     expected_magic_bytes: &[u8; 2],
+    stacks_operations: &StacksOperationsParsing,
     ctx: &Context,
 ) -> Option<StacksBaseChainOperation> {
     if outputs.is_empty() {
@@ -518,6 +603,9 @@ fn try_parse_stacks_operation(
             return None;
         }
     };
+    if !stacks_operations.is_enabled(&op_type) {
+        return None;
+    }
     let op = match op_type {
         StacksOpcodes::KeyRegister => {
             let res = try_parse_key_register_op(&op_return_output[6..])?;
@@ -528,7 +616,7 @@ fn try_parse_stacks_operation(
             return None;
         }
         StacksOpcodes::TransferStx => {
-            let res = try_parse_transfer_stx_op(&op_return_output[6..])?;
+            let res = try_parse_transfer_stx_op(&op_return_output[6..], inputs, outputs, network)?;
             StacksBaseChainOperation::StxTransferred(res)
         }
         StacksOpcodes::StackStx => {
@@ -536,7 +624,7 @@ fn try_parse_stacks_operation(
             StacksBaseChainOperation::StxLocked(res)
         }
         StacksOpcodes::BlockCommit => {
-            let res = try_parse_block_commit_op(&op_return_output[5..])?;
+            let res = try_parse_block_commit_op(&op_return_output[6..])?;
             let mut pox_sats_burnt = 0;
             let mut pox_sats_transferred = vec![];
 
@@ -647,17 +735,24 @@ fn try_parse_stacks_operation(
             let pox_cycle_index = pox_config.get_pox_cycle_id(block_height);
             let pox_cycle_length = pox_config.get_pox_cycle_len();
             let pox_cycle_position = pox_config.get_pos_in_pox_cycle(block_height);
+            let pox_cycle_in_prepare_phase = pox_config.is_in_prepare_phase(block_height);
 
             StacksBaseChainOperation::BlockCommitted(StacksBlockCommitmentData {
                 block_hash: res.stacks_block_hash,
                 pox_cycle_index,
                 pox_cycle_length,
                 pox_cycle_position,
+                pox_cycle_in_prepare_phase,
                 pox_sats_burnt,
                 pox_sats_transferred,
                 // mining_address_pre_commit: None,
                 mining_address_post_commit,
                 mining_sats_left,
+                parent_block_ptr: res.parent_block_ptr,
+                parent_vtxindex: res.parent_vtxindex,
+                anchors_tenure_change: res
+                    .epoch_marker
+                    .map_or(false, |marker| marker >= STACKS_EPOCH_3_0_MARKER),
             })
         }
     };
@@ -665,13 +760,35 @@ fn try_parse_stacks_operation(
     Some(op)
 }
 
+/// Epoch marker of the block commits sent by Nakamoto (Stacks 3.0) miners.
+const STACKS_EPOCH_3_0_MARKER: u8 = 0x0b;
+
 fn try_parse_block_commit_op(bytes: &[u8]) -> Option<BlockCommitmentData> {
     if bytes.len() < 32 {
         return None;
     }
 
+    // The hash of the Stacks block is followed by the VRF seed (32 bytes), then by the pointer to
+    // the commit of the parent Stacks block: its Bitcoin block height (4 bytes) and its position
+    // within that block (2 bytes).
+    let (parent_block_ptr, parent_vtxindex) = match bytes.get(64..70) {
+        Some(parent) => (
+            Some(u32::from_be_bytes([
+                parent[0], parent[1], parent[2], parent[3],
+            ])),
+            Some(u16::from_be_bytes([parent[4], parent[5]])),
+        ),
+        None => (None, None),
+    };
+    // It is followed by the pointer to the leader key (6 bytes), then by the memo, whose 5 high
+    // bits are the marker of the Stacks epoch the miner is running.
+    let epoch_marker = bytes.get(76).map(|memo| memo >> 3);
+
     Some(BlockCommitmentData {
         stacks_block_hash: format!("0x{}", hex::encode(&bytes[0..32])),
+        parent_block_ptr,
+        parent_vtxindex,
+        epoch_marker,
     })
 }
 
@@ -683,16 +800,35 @@ fn try_parse_pre_stx_op(_bytes: &[u8]) -> Option<()> {
     None
 }
 
-fn try_parse_transfer_stx_op(bytes: &[u8]) -> Option<TransferSTXData> {
+fn try_parse_transfer_stx_op(
+    bytes: &[u8],
+    inputs: &Vec<BitcoinTransactionInputFullBreakdown>,
+    outputs: &Vec<BitcoinTransactionOutputFullBreakdown>,
+    network: &BitcoinNetwork,
+) -> Option<TransferSTXData> {
     if bytes.len() < 16 {
         return None;
     }
 
-    // todo(lgalabru)
+    // The amount of micro-STX transferred (16 bytes) is followed by an optional memo.
+    let amount = u128::from_be_bytes(bytes[0..16].try_into().ok()?);
+
+    // The STX are sent by the owner of the output spent by input 0, to the owner of output 1.
+    let sender_script = inputs
+        .first()
+        .and_then(|input| input.prevout.as_ref())
+        .and_then(|prevout| prevout.script_pub_key.as_ref())?
+        .script()
+        .ok()?;
+    let sender = Address::from_script(&sender_script, get_bitcoin_network(network)).ok()?;
+
+    let recipient_script = outputs.get(1)?.script_pub_key.script().ok()?;
+    let recipient = Address::from_script(&recipient_script, get_bitcoin_network(network)).ok()?;
+
     Some(TransferSTXData {
-        sender: "".into(),
-        recipient: "".into(),
-        amount: "".into(),
+        sender: sender.to_string(),
+        recipient: recipient.to_string(),
+        amount: amount.to_string(),
     })
 }
 
@@ -1,4 +1,13 @@
+use chainhook_types::{BitcoinNetwork, StacksBaseChainOperation};
+use test_case::test_case;
+
 use super::super::tests::{helpers, process_bitcoin_blocks_and_check_expectations};
+use super::{
+    try_parse_stacks_operation, BitcoinTransactionFullBreakdown,
+    BitcoinTransactionOutputFullBreakdown, StacksOperationsParsing,
+};
+use crate::chainhooks::bitcoin::{get_canonical_pox_config, get_stacks_canonical_magic_bytes};
+use crate::utils::Context;
 
 #[test]
 fn test_bitcoin_vector_001() {
@@ -204,3 +213,278 @@ fn test_bitcoin_vector_040() {
 // fn test_bitcoin_vector_041() {
 //     process_bitcoin_blocks_and_check_expectations(helpers::shapes::get_vector_041());
 // }
+
+fn get_stack_stx_outputs() -> Vec<BitcoinTransactionOutputFullBreakdown> {
+    // OP_RETURN PUSHDATA1(19) "id" 'x' <16 bytes>
+    let output = serde_json::json!({
+        "value": 0.0,
+        "n": 0,
+        "scriptPubKey": {
+            "asm": "",
+            "hex": format!("6a4c13696478{}", "00".repeat(16)),
+            "type": "nulldata"
+        }
+    });
+    vec![serde_json::from_value(output).unwrap()]
+}
+
+#[test]
+fn disabled_stacks_operations_are_not_parsed() {
+    let network = BitcoinNetwork::Regtest;
+    let outputs = get_stack_stx_outputs();
+    let parse = |stacks_operations: &StacksOperationsParsing| {
+        try_parse_stacks_operation(
+            100,
+            &vec![],
+            &outputs,
+            &network,
+            &get_canonical_pox_config(&network),
+            &get_stacks_canonical_magic_bytes(&network),
+            stacks_operations,
+            &Context::empty(),
+        )
+    };
+
+    assert!(matches!(
+        parse(&StacksOperationsParsing::default()),
+        Some(StacksBaseChainOperation::StxLocked(_))
+    ));
+    let stacks_operations = StacksOperationsParsing {
+        stx_locked: false,
+        ..StacksOperationsParsing::default()
+    };
+    assert_eq!(parse(&stacks_operations), None);
+    assert_eq!(parse(&StacksOperationsParsing::none()), None);
+}
+
+fn get_block_commit_outputs() -> Vec<BitcoinTransactionOutputFullBreakdown> {
+    // outputs of the block commit 59193c24cb2325cd2271b89f790f958dcd4065088680ffbc201a0ebb2f3cbf25,
+    // mined in block 104 of a regtest devnet
+    let outputs = serde_json::json!([
+        {
+            "value": 0.0,
+            "n": 0,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": "6a4c5069645b1681995f8e568287e0e4f5cbc1d6727dafb5e3a7822a77c69bd04208265aca9424d0337dac7d9e84371a2c91ece1891d67d3554bd9fdbe60afc6924d4b0773d90000006700010000006600012b",
+                "type": "nulldata"
+            }
+        },
+        {
+            "value": 0.0001,
+            "n": 1,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": "76a914000000000000000000000000000000000000000088ac",
+                "type": "pubkeyhash"
+            }
+        },
+        {
+            "value": 0.0001,
+            "n": 2,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": "76a914000000000000000000000000000000000000000088ac",
+                "type": "pubkeyhash"
+            }
+        },
+        {
+            "value": 49.9990485,
+            "n": 3,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": "76a914ee9369fb719c0ba43ddf4d94638a970b84775f4788ac",
+                "type": "pubkeyhash"
+            }
+        }
+    ]);
+    serde_json::from_value(outputs).unwrap()
+}
+
+#[test]
+fn block_commit_parent_is_parsed() {
+    let network = BitcoinNetwork::Regtest;
+    let op = try_parse_stacks_operation(
+        104,
+        &vec![],
+        &get_block_commit_outputs(),
+        &network,
+        &get_canonical_pox_config(&network),
+        &get_stacks_canonical_magic_bytes(&network),
+        &StacksOperationsParsing::default(),
+        &Context::empty(),
+    );
+    let Some(StacksBaseChainOperation::BlockCommitted(commit)) = op else {
+        panic!("expected a block commit, got {:?}", op);
+    };
+    // the payload starts after the opcode ('['): block hash, VRF seed, then the pointer to the
+    // commit of the parent block (103, 1)
+    assert_eq!(
+        commit.block_hash,
+        "0x1681995f8e568287e0e4f5cbc1d6727dafb5e3a7822a77c69bd04208265aca94"
+    );
+    assert_eq!(commit.parent_block_ptr, Some(103));
+    assert_eq!(commit.parent_vtxindex, Some(1));
+    // the memo of pre-Nakamoto commits carries the epoch 2.05 marker
+    assert!(!commit.anchors_tenure_change);
+}
+
+fn get_block_commit_outputs_with_memo(memo: u8) -> Vec<BitcoinTransactionOutputFullBreakdown> {
+    // OP_RETURN PUSHDATA1(80) "id" '[' <block hash> <VRF seed> <parent block ptr> <parent vtxindex>
+    // <key block ptr> <key vtxindex> <memo>, followed by the 2 PoX reward outputs
+    let op_return = serde_json::json!({
+        "value": 0.0,
+        "n": 0,
+        "scriptPubKey": {
+            "asm": "",
+            "hex": format!(
+                "6a4c5069645b{}{}000000670001000000660001{:02x}",
+                "11".repeat(32),
+                "22".repeat(32),
+                memo
+            ),
+            "type": "nulldata"
+        }
+    });
+    let mut outputs = vec![serde_json::from_value(op_return).unwrap()];
+    for n in 1..3 {
+        let reward = serde_json::json!({
+            "value": 0.0001,
+            "n": n,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": format!("76a914{}88ac", format!("{:02x}", n).repeat(20)),
+                "type": "pubkeyhash"
+            }
+        });
+        outputs.push(serde_json::from_value(reward).unwrap());
+    }
+    outputs
+}
+
+#[test]
+fn nakamoto_block_commits_anchor_tenure_changes() {
+    let network = BitcoinNetwork::Regtest;
+    let parse = |memo: u8| match try_parse_stacks_operation(
+        100,
+        &vec![],
+        &get_block_commit_outputs_with_memo(memo),
+        &network,
+        &get_canonical_pox_config(&network),
+        &get_stacks_canonical_magic_bytes(&network),
+        &StacksOperationsParsing::default(),
+        &Context::empty(),
+    ) {
+        Some(StacksBaseChainOperation::BlockCommitted(data)) => data,
+        op => panic!("expected a block commit, got {:?}", op),
+    };
+
+    // epoch 3.0 marker, burn parent modulus 2
+    let commit = parse(0x5a);
+    assert!(commit.anchors_tenure_change);
+    assert_eq!(commit.block_hash, format!("0x{}", "11".repeat(32)));
+    assert_eq!(commit.parent_block_ptr, Some(103));
+    // epoch 2.5 marker, burn parent modulus 2
+    assert!(!parse(0x52).anchors_tenure_change);
+}
+
+#[test]
+fn unknown_stacks_opcodes_are_skipped() {
+    let network = BitcoinNetwork::Regtest;
+    // OP_RETURN PUSHDATA1(19) "id" 'v' <16 bytes>, an opcode not modeled by StacksOpcodes
+    let output = serde_json::json!({
+        "value": 0.0,
+        "n": 0,
+        "scriptPubKey": {
+            "asm": "",
+            "hex": format!("6a4c13696476{}", "00".repeat(16)),
+            "type": "nulldata"
+        }
+    });
+    let op = try_parse_stacks_operation(
+        100,
+        &vec![],
+        &vec![serde_json::from_value(output).unwrap()],
+        &network,
+        &get_canonical_pox_config(&network),
+        &get_stacks_canonical_magic_bytes(&network),
+        &StacksOperationsParsing::default(),
+        &Context::empty(),
+    );
+    assert_eq!(op, None);
+}
+
+#[test]
+fn stx_transfers_are_parsed() {
+    let network = BitcoinNetwork::Regtest;
+    // input 0 spends an output of the sender, 1000000 micro-STX being sent to the owner of output 1
+    let inputs = serde_json::json!([
+        {
+            "txid": "00".repeat(32),
+            "vout": 1,
+            "scriptSig": { "hex": "" },
+            "sequence": 4294967295u32,
+            "prevout": {
+                "height": 99,
+                "value": 0.0001,
+                "scriptPubKey": {
+                    "asm": "",
+                    "hex": format!("76a914{}88ac", "11".repeat(20)),
+                    "type": "pubkeyhash"
+                }
+            }
+        }
+    ]);
+    // OP_RETURN PUSHDATA1(19) "id" '$' <amount>
+    let outputs = serde_json::json!([
+        {
+            "value": 0.0,
+            "n": 0,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": format!("6a4c13696424{:032x}", 1000000),
+                "type": "nulldata"
+            }
+        },
+        {
+            "value": 0.0001,
+            "n": 1,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": format!("76a914{}88ac", "22".repeat(20)),
+                "type": "pubkeyhash"
+            }
+        }
+    ]);
+    let op = try_parse_stacks_operation(
+        100,
+        &serde_json::from_value(inputs).unwrap(),
+        &serde_json::from_value(outputs).unwrap(),
+        &network,
+        &get_canonical_pox_config(&network),
+        &get_stacks_canonical_magic_bytes(&network),
+        &StacksOperationsParsing::default(),
+        &Context::empty(),
+    );
+    let Some(StacksBaseChainOperation::StxTransferred(transfer)) = op else {
+        panic!("expected an STX transfer, got {:?}", op);
+    };
+    assert_eq!(transfer.amount, "1000000");
+    // addresses are encoded for the network of the block
+    assert_eq!(transfer.sender, "mh5CE8Nbj38iND267s4XnvhSmhDW7yWc6Q");
+    assert_eq!(transfer.recipient, "midSACfDe3qAxJZZXA9gkwBZgPqJJUpy1w");
+}
+
+#[test_case(2, 2; "version 2")]
+#[test_case(-1, -1; "signed negative version")]
+#[test_case(4294967295, -1; "unsigned negative version")]
+fn transaction_versions_are_signed(reported_version: i64, expected_version: i32) {
+    let tx: BitcoinTransactionFullBreakdown = serde_json::from_value(serde_json::json!({
+        "txid": "00".repeat(32),
+        "vin": [],
+        "vout": [],
+        "version": reported_version,
+    }))
+    .unwrap();
+    assert_eq!(tx.version, expected_version);
+}
@@ -131,7 +131,11 @@ pub fn generate_test_tx_bitcoin_p2pkh_transfer(
             brc20_operation: None,
             proof: None,
             fee: 0,
-            index: 0,
+            // past the coinbase transaction, always at index 0
+            index: 1,
+            weight: 0,
+            vsize: 0,
+            version: 1,
         },
     }
 }
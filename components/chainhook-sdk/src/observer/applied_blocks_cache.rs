@@ -0,0 +1,74 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::chainhooks::bitcoin::BitcoinTriggerChainhook;
+
+/// The number of (predicate, block hash) pairs remembered by default.
+pub const DEFAULT_APPLIED_BLOCKS_CACHE_CAPACITY: usize = 10_000;
+
+/// The (predicate, block hash) pairs of the most recent apply occurrences.
+///
+/// When a block that was already applied is applied again, e.g. by a chain event delivered twice,
+/// the apply is identical to the one previously delivered: the cache lets the observer suppress it.
+/// A block is forgotten once its rollback is delivered, so that consumers receive it again when a
+/// re-org switches back to it. Once `capacity` pairs are remembered, the oldest ones are forgotten.
+pub struct AppliedBlocksCache {
+    capacity: usize,
+    entries: HashSet<(String, String)>,
+    insertion_order: VecDeque<(String, String)>,
+}
+
+impl AppliedBlocksCache {
+    pub fn new(capacity: usize) -> Self {
+        AppliedBlocksCache {
+            capacity,
+            entries: HashSet::new(),
+            insertion_order: VecDeque::new(),
+        }
+    }
+
+    /// Records that `block_hash` was applied for `predicate_uuid`, returning `false` if it already was.
+    pub fn insert(&mut self, predicate_uuid: &str, block_hash: &str) -> bool {
+        let entry = (predicate_uuid.to_string(), block_hash.to_string());
+        if self.entries.contains(&entry) {
+            return false;
+        }
+        if self.insertion_order.len() >= self.capacity {
+            if let Some(oldest) = self.insertion_order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(entry.clone());
+        self.insertion_order.push_back(entry);
+        true
+    }
+
+    /// Forgets that `block_hash` was applied for `predicate_uuid`.
+    pub fn remove(&mut self, predicate_uuid: &str, block_hash: &str) {
+        let entry = (predicate_uuid.to_string(), block_hash.to_string());
+        if self.entries.remove(&entry) {
+            self.insertion_order.retain(|e| e != &entry);
+        }
+    }
+
+    /// Removes the blocks already applied for the predicate from `trigger`, returning `None` if
+    /// nothing is left to deliver. The blocks rolled back by `trigger` are forgotten, since their
+    /// rollback is delivered along with it.
+    pub fn deduplicate<'a>(
+        &mut self,
+        mut trigger: BitcoinTriggerChainhook<'a>,
+    ) -> Option<BitcoinTriggerChainhook<'a>> {
+        let chainhook = trigger.chainhook;
+        let predicate_uuid = &chainhook.uuid;
+        for (_, block) in trigger.rollback.iter() {
+            self.remove(predicate_uuid, &block.block_identifier.hash);
+        }
+        trigger
+            .apply
+            .retain(|(_, block)| self.insert(predicate_uuid, &block.block_identifier.hash));
+        if trigger.apply.is_empty() && trigger.rollback.is_empty() && trigger.dropped.is_empty() {
+            None
+        } else {
+            Some(trigger)
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hiro_system_kit::slog;
+use reqwest::{Client as HttpClient, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::chainhooks::types::{HttpHook, SigningSecrets};
+use crate::utils::{send_request, Context, HttpRetryPolicy};
+
+/// An occurrence whose `http_post` delivery failed for good, kept for a later replay.
+///
+/// The headers of the request are not kept, so that credentials and signatures are never written
+/// to disk: they're built again from the `http_post` action of the predicate when redelivered.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: u64,
+    pub predicate_key: String,
+    pub url: String,
+    /// The error the delivery ended with.
+    pub reason: String,
+    /// Unix timestamp, in seconds, of the failure.
+    pub failed_at: u64,
+    /// The occurrence payload, e.g. a serialized `BitcoinChainhookOccurrencePayload`.
+    pub payload: JsonValue,
+}
+
+impl DeadLetter {
+    pub fn from_request(
+        id: u64,
+        predicate_key: &str,
+        request_builder: &RequestBuilder,
+        reason: &str,
+    ) -> Result<DeadLetter, String> {
+        let request = request_builder
+            .try_clone()
+            .ok_or(format!("unable to clone request builder"))?
+            .build()
+            .map_err(|e| format!("unable to build request: {}", e.to_string()))?;
+        let payload = match request.body().and_then(|b| b.as_bytes()) {
+            Some(bytes) => serde_json::from_slice(bytes)
+                .map_err(|e| format!("unable to decode request body: {}", e.to_string()))?,
+            None => JsonValue::Null,
+        };
+        let failed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(DeadLetter {
+            id,
+            predicate_key: predicate_key.to_string(),
+            url: request.url().to_string(),
+            reason: reason.to_string(),
+            failed_at,
+            payload,
+        })
+    }
+
+    /// The request posting the payload again to its url, authorized and signed as configured by
+    /// `hook`.
+    pub fn to_request(
+        &self,
+        http_client: &HttpClient,
+        hook: &HttpHook,
+        signing_secrets: &SigningSecrets,
+    ) -> Result<RequestBuilder, String> {
+        let body = serde_json::to_vec(&self.payload)
+            .map_err(|e| format!("unable to serialize payload: {}", e.to_string()))?;
+        hook.build_request_to(http_client, signing_secrets, &self.url, body)
+    }
+}
+
+/// Persists each [DeadLetter] as its own JSON file in a directory, until it is redelivered.
+///
+/// The directory is shared by the observer and the scans, which can record dead letters at the
+/// same time: ids are derived from the time of the failure and claimed by exclusively creating
+/// their file, so that a dead letter never overwrites another one.
+pub struct DeadLetterStore {
+    dir: PathBuf,
+}
+
+impl DeadLetterStore {
+    pub fn open(dir: &PathBuf) -> Result<DeadLetterStore, String> {
+        fs::create_dir_all(dir).map_err(|e| {
+            format!(
+                "unable to create dead letters directory {}: {}",
+                dir.display(),
+                e.to_string()
+            )
+        })?;
+        Ok(DeadLetterStore { dir: dir.clone() })
+    }
+
+    fn get_dead_letter_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.json", id))
+    }
+
+    fn get_tmp_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.json.tmp", id))
+    }
+
+    /// Returns the first id, starting from the current time in nanoseconds, whose temporary file
+    /// could be created and which isn't taken by a dead letter yet, along with that file.
+    fn claim_id(&self) -> Result<(u64, fs::File), String> {
+        let mut id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        loop {
+            let tmp_path = self.get_tmp_path(id);
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp_path)
+            {
+                Ok(file) => {
+                    // a dead letter is only renamed from its temporary file once written, so no
+                    // other writer can create it while we hold that file
+                    if !self.get_dead_letter_path(id).exists() {
+                        return Ok((id, file));
+                    }
+                    drop(file);
+                    let _ = fs::remove_file(&tmp_path);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    return Err(format!(
+                        "unable to create file {}: {}",
+                        tmp_path.display(),
+                        e.to_string()
+                    ))
+                }
+            }
+            id += 1;
+        }
+    }
+
+    /// Records the failed delivery of `request_builder`, which ended with `reason`.
+    pub fn record(
+        &mut self,
+        predicate_key: &str,
+        request_builder: &RequestBuilder,
+        reason: &str,
+    ) -> Result<DeadLetter, String> {
+        // Write to a temporary file first so that a crash never leaves a truncated entry behind.
+        let (id, mut file) = self.claim_id()?;
+        let tmp_path = self.get_tmp_path(id);
+        let result = DeadLetter::from_request(id, predicate_key, request_builder, reason).and_then(
+            |dead_letter| {
+                let bytes = serde_json::to_vec_pretty(&dead_letter)
+                    .map_err(|e| format!("unable to serialize dead letter: {}", e.to_string()))?;
+                file.write_all(&bytes)
+                    .and_then(|_| file.sync_all())
+                    .map_err(|e| format!("unable to write file {}: {}", tmp_path.display(), e))?;
+                let path = self.get_dead_letter_path(id);
+                fs::rename(&tmp_path, &path)
+                    .map_err(|e| format!("unable to write file {}: {}", path.display(), e))?;
+                Ok(dead_letter)
+            },
+        );
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result
+    }
+
+    /// Drops a dead letter once redelivered.
+    pub fn remove(&self, id: u64) -> Result<(), String> {
+        let path = self.get_dead_letter_path(id);
+        fs::remove_file(&path)
+            .map_err(|e| format!("unable to remove file {}: {}", path.display(), e))
+    }
+
+    /// Returns the dead letters, oldest first. Files that can't be read are logged and skipped, so
+    /// that a corrupt entry doesn't hold back the other ones.
+    pub fn get_dead_letters(&self, ctx: &Context) -> Result<Vec<DeadLetter>, String> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| {
+            format!(
+                "unable to read directory {}: {}",
+                self.dir.display(),
+                e.to_string()
+            )
+        })?;
+        let mut dead_letters = vec![];
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("unable to read directory entry: {}", e.to_string()))?
+                .path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let dead_letter = fs::read(&path)
+                .map_err(|e| format!("unable to read file {}: {}", path.display(), e))
+                .and_then(|bytes| {
+                    serde_json::from_slice::<DeadLetter>(&bytes).map_err(|e| {
+                        format!(
+                            "unable to parse dead letter {}: {}",
+                            path.display(),
+                            e.to_string()
+                        )
+                    })
+                });
+            match dead_letter {
+                Ok(dead_letter) => dead_letters.push(dead_letter),
+                Err(e) => ctx.try_log(|logger| slog::warn!(logger, "Skipping dead letter: {}", e)),
+            }
+        }
+        dead_letters.sort_by_key(|dead_letter| dead_letter.id);
+        Ok(dead_letters)
+    }
+
+    /// Records a failed delivery, returning whether it was recorded.
+    pub fn try_record(
+        &mut self,
+        predicate_key: &str,
+        request_builder: &RequestBuilder,
+        reason: &str,
+        ctx: &Context,
+    ) -> bool {
+        match self.record(predicate_key, request_builder, reason) {
+            Ok(dead_letter) => {
+                ctx.try_log(|logger| {
+                    slog::info!(
+                        logger,
+                        "Failed delivery for predicate {} recorded as dead letter #{}",
+                        predicate_key,
+                        dead_letter.id
+                    )
+                });
+                true
+            }
+            Err(e) => {
+                ctx.try_log(|logger| slog::warn!(logger, "Unable to record dead letter: {}", e));
+                false
+            }
+        }
+    }
+}
+
+/// Records a failed delivery in the dead letters directory at `dir`, for the deliveries that don't
+/// keep a [DeadLetterStore] open, such as the ones of scans.
+pub fn try_record_dead_letter(
+    dir: &PathBuf,
+    predicate_key: &str,
+    request_builder: &RequestBuilder,
+    reason: &str,
+    ctx: &Context,
+) -> bool {
+    match DeadLetterStore::open(dir) {
+        Ok(mut store) => store.try_record(predicate_key, request_builder, reason, ctx),
+        Err(e) => {
+            ctx.try_log(|logger| slog::warn!(logger, "Unable to record dead letter: {}", e));
+            false
+        }
+    }
+}
+
+/// Sends every dead letter of `store` again, dropping the ones delivered. Dead letters that still
+/// fail are kept, and so are the ones of the predicates missing from `hooks`, the `http_post`
+/// actions of the registered predicates by predicate key.
+///
+/// Returns the number of delivered and remaining dead letters.
+pub async fn redeliver_dead_letters(
+    store: &DeadLetterStore,
+    hooks: &HashMap<String, HttpHook>,
+    signing_secrets: &SigningSecrets,
+    http_client: &HttpClient,
+    retry_policy: &HttpRetryPolicy,
+    ctx: &Context,
+) -> Result<(usize, usize), String> {
+    let dead_letters = store.get_dead_letters(ctx)?;
+    let mut delivered = 0;
+    for dead_letter in dead_letters.iter() {
+        let result = match hooks.get(&dead_letter.predicate_key) {
+            Some(hook) => match dead_letter.to_request(http_client, hook, signing_secrets) {
+                Ok(request) => send_request(request, retry_policy, ctx).await,
+                Err(e) => Err(e),
+            },
+            None => Err(format!("predicate has no registered http_post action")),
+        };
+        match result {
+            Ok(_) => {
+                store.remove(dead_letter.id)?;
+                delivered += 1;
+            }
+            Err(e) => ctx.try_log(|logger| {
+                slog::warn!(
+                    logger,
+                    "Unable to redeliver dead letter #{} for predicate {}: {}",
+                    dead_letter.id,
+                    dead_letter.predicate_key,
+                    e
+                )
+            }),
+        }
+    }
+    Ok((delivered, dead_letters.len() - delivered))
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use crate::chainhooks::bitcoin::{
+    BitcoinChainhookInstance, BitcoinOccurrenceDigest, BitcoinTriggerChainhook,
+};
+
+struct DigestWindow {
+    opened_at: u64,
+    digest: BitcoinOccurrenceDigest,
+}
+
+/// The occurrences of the predicates configured with a `digest`, accumulated until their window
+/// closes.
+///
+/// Windows are keyed by predicate uuid. A window opens with the first occurrence of its predicate,
+/// and is closed either by an occurrence extending it past `blocks` block heights, by
+/// [BitcoinDigestAccumulator::close_windows] once the chain tip or the clock moves past it, or by
+/// [BitcoinDigestAccumulator::close_window] when its predicate expires.
+///
+/// Digests are best-effort: windows are only kept in memory, so the occurrences accumulated in
+/// open windows are lost when the observer restarts, or when their predicate is deregistered.
+#[derive(Default)]
+pub struct BitcoinDigestAccumulator {
+    windows: HashMap<String, DigestWindow>,
+}
+
+impl BitcoinDigestAccumulator {
+    pub fn new() -> Self {
+        BitcoinDigestAccumulator {
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Adds `trigger` to the window of its predicate, `now` being the current unix timestamp in
+    /// seconds. Returns the digest of the window if the occurrence closes it.
+    pub fn accumulate<'a>(
+        &mut self,
+        trigger: &BitcoinTriggerChainhook<'a>,
+        now: u64,
+    ) -> Option<BitcoinOccurrenceDigest> {
+        let config = trigger.chainhook.digest.as_ref()?;
+        let max_sample_txids = config.get_max_sample_txids();
+        let uuid = &trigger.chainhook.uuid;
+        match self.windows.get_mut(uuid) {
+            Some(window) => window.digest.add_trigger(trigger, max_sample_txids),
+            None => {
+                let digest = BitcoinOccurrenceDigest::from_trigger(trigger, max_sample_txids)?;
+                self.windows.insert(
+                    uuid.clone(),
+                    DigestWindow {
+                        opened_at: now,
+                        digest,
+                    },
+                );
+            }
+        }
+        let window = self.windows.get(uuid)?;
+        match config.blocks {
+            Some(blocks) if window.digest.number_of_blocks_covered() >= blocks => {
+                self.windows.remove(uuid).map(|window| window.digest)
+            }
+            _ => None,
+        }
+    }
+
+    /// Closes the windows ending before `tip_height` or older than their `seconds`, returning their
+    /// digests along with their predicate. The windows of predicates missing from `chainhooks`,
+    /// i.e. deregistered or disabled, are discarded.
+    pub fn close_windows<'a>(
+        &mut self,
+        chainhooks: &[&'a BitcoinChainhookInstance],
+        tip_height: u64,
+        now: u64,
+    ) -> Vec<(&'a BitcoinChainhookInstance, BitcoinOccurrenceDigest)> {
+        let mut closed = vec![];
+        let mut uuids = self.windows.keys().cloned().collect::<Vec<_>>();
+        uuids.sort();
+        for uuid in uuids.into_iter() {
+            let Some(window) = self.windows.get(&uuid) else {
+                continue;
+            };
+            let Some(chainhook) = chainhooks.iter().find(|c| c.uuid == uuid) else {
+                self.windows.remove(&uuid);
+                continue;
+            };
+            let Some(config) = chainhook.digest.as_ref() else {
+                self.windows.remove(&uuid);
+                continue;
+            };
+            let is_closed_by_blocks = config.blocks.map_or(false, |blocks| {
+                tip_height >= window.digest.start_block.index.saturating_add(blocks)
+            });
+            let is_closed_by_time = config.seconds.map_or(false, |seconds| {
+                now >= window.opened_at.saturating_add(seconds)
+            });
+            if is_closed_by_blocks || is_closed_by_time {
+                if let Some(window) = self.windows.remove(&uuid) {
+                    closed.push((*chainhook, window.digest));
+                }
+            }
+        }
+        closed
+    }
+
+    /// Closes the window of the predicate `uuid`, returning its digest if the window was open.
+    pub fn close_window(&mut self, uuid: &str) -> Option<BitcoinOccurrenceDigest> {
+        self.windows.remove(uuid).map(|window| window.digest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+}
@@ -1,27 +1,54 @@
+pub mod applied_blocks_cache;
+pub mod dead_letters;
+pub mod digests;
 mod http;
+pub mod occurrences_buffer;
+pub mod pending_deliveries;
 #[cfg(feature = "zeromq")]
 mod zmq;
 
+#[cfg(feature = "grpc")]
+use crate::chainhooks::bitcoin::grpc_export::send_grpc_occurrence;
+#[cfg(feature = "kafka")]
+use crate::chainhooks::bitcoin::kafka_export::produce_kafka_message;
+#[cfg(feature = "parquet")]
+use crate::chainhooks::bitcoin::parquet_export::{
+    append_parquet_rows, close_expired_parquet_files, close_parquet_files,
+};
+#[cfg(feature = "s3")]
+use crate::chainhooks::bitcoin::s3_export::append_s3_object;
+#[cfg(feature = "sqs")]
+use crate::chainhooks::bitcoin::sqs_export::send_sqs_occurrences;
+#[cfg(feature = "websocket")]
+use crate::chainhooks::bitcoin::websocket_export::{
+    retain_websocket_feeds, send_websocket_message,
+};
 use crate::chainhooks::bitcoin::{
-    evaluate_bitcoin_chainhooks_on_chain_event, handle_bitcoin_hook_action,
-    BitcoinChainhookInstance, BitcoinChainhookOccurrence, BitcoinChainhookOccurrencePayload,
-    BitcoinTriggerChainhook,
+    evaluate_bitcoin_chainhooks_on_chain_event, handle_bitcoin_digest_action,
+    handle_bitcoin_hook_action, BitcoinChainhookInstance, BitcoinChainhookOccurrence,
+    BitcoinChainhookOccurrencePayload, BitcoinTriggerChainhook,
 };
 use crate::chainhooks::stacks::{
     evaluate_stacks_chainhooks_on_chain_event, handle_stacks_hook_action, StacksChainhookInstance,
     StacksChainhookOccurrence, StacksChainhookOccurrencePayload,
 };
 use crate::chainhooks::types::{
-    ChainhookInstance, ChainhookSpecificationNetworkMap, ChainhookStore,
+    build_http_delivery_client, ChainhookInstance, ChainhookSpecificationNetworkMap,
+    ChainhookStore, ContentHashAlgorithm, HookAction, HttpDeliveryConfig, HttpHook, SigningSecrets,
 };
 
 use crate::indexer::bitcoin::{
     build_http_client, download_and_parse_block_with_retry, standardize_bitcoin_block,
-    BitcoinBlockFullBreakdown,
+    BitcoinBlockFullBreakdown, StacksOperationsParsing,
 };
 use crate::indexer::{Indexer, IndexerConfig};
 use crate::monitoring::{start_serving_prometheus_metrics, PrometheusMonitoring};
-use crate::utils::{send_request, Context};
+use crate::utils::{send_request_with_verification, unix_socket_send, Context, HttpRetryPolicy};
+use applied_blocks_cache::{AppliedBlocksCache, DEFAULT_APPLIED_BLOCKS_CACHE_CAPACITY};
+use dead_letters::DeadLetterStore;
+use digests::BitcoinDigestAccumulator;
+use occurrences_buffer::BitcoinOccurrencesBuffer;
+use pending_deliveries::{redeliver_pending_deliveries, PendingDeliveryStore};
 
 use bitcoincore_rpc::bitcoin::{BlockHash, Txid};
 use bitcoincore_rpc::{Auth, Client, RpcApi};
@@ -33,17 +60,20 @@ use chainhook_types::{
 };
 use hiro_system_kit;
 use hiro_system_kit::slog;
+use reqwest::{Client as HttpClient, RequestBuilder};
 use rocket::config::{self, Config, LogLevel};
 use rocket::data::{Limits, ToByteUnit};
 use rocket::serde::Deserialize;
 use rocket::Shutdown;
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
 use std::str;
 use std::str::FromStr;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const DEFAULT_INGESTION_PORT: u16 = 20445;
 
@@ -78,6 +108,33 @@ pub struct EventObserverConfig {
     pub bitcoin_network: BitcoinNetwork,
     pub stacks_network: StacksNetwork,
     pub prometheus_monitoring_port: Option<u16>,
+    pub descriptor_default_range: Option<[u32; 2]>,
+    /// When set, `http_post` occurrences are tracked in this directory until acknowledged with a 2xx response,
+    /// and unacknowledged occurrences are redelivered when the observer restarts, which is the only
+    /// time they are retried. The occurrences of deregistered predicates are dropped.
+    pub pending_deliveries_path: Option<PathBuf>,
+    /// When set, `http_post` occurrences that could not be delivered are written to this directory, to be
+    /// redelivered later on.
+    pub dead_letters_path: Option<PathBuf>,
+    /// When set, this client is used to deliver `http_post` occurrences instead of the one built by the observer,
+    /// letting embedders control TLS, proxies and connection pooling.
+    pub http_client: Option<HttpClient>,
+    /// Retries of the `http_post` occurrences that could not be delivered.
+    pub http_retry_policy: HttpRetryPolicy,
+    /// Secrets signing the `http_post` occurrences of the predicates referencing them.
+    pub signing_secrets: SigningSecrets,
+    /// Hash of the deduplication ids of the messages sent to SQS FIFO queues, unless the action
+    /// sets its own.
+    pub deduplication_hash: ContentHashAlgorithm,
+    /// The Stacks operations parsed out of Bitcoin blocks. Every operation is parsed unless disabled
+    /// here, whether or not a registered predicate targets it.
+    pub stacks_operations_parsing: StacksOperationsParsing,
+    /// When set, the occurrences of the Bitcoin predicates triggered by a chain event are delivered as soon as
+    /// the ones waiting to be delivered exceed this size, in bytes, before the next ones are built.
+    pub max_buffered_occurrences_size: Option<usize>,
+    /// When enabled, blocks applied again are not delivered twice to the Bitcoin predicates they were
+    /// already delivered to, unless their rollback was delivered in between.
+    pub deduplicate_reapplied_blocks: bool,
 }
 
 /// A builder that is used to create a general purpose [EventObserverConfig].
@@ -107,6 +164,9 @@ pub struct EventObserverConfigBuilder {
     pub bitcoin_network: Option<String>,
     pub stacks_network: Option<String>,
     pub prometheus_monitoring_port: Option<u16>,
+    pub descriptor_default_range: Option<[u32; 2]>,
+    pub pending_deliveries_path: Option<PathBuf>,
+    pub dead_letters_path: Option<PathBuf>,
 }
 
 impl EventObserverConfigBuilder {
@@ -122,6 +182,9 @@ impl EventObserverConfigBuilder {
             bitcoin_network: None,
             stacks_network: None,
             prometheus_monitoring_port: None,
+            descriptor_default_range: None,
+            pending_deliveries_path: None,
+            dead_letters_path: None,
         }
     }
 
@@ -186,6 +249,24 @@ impl EventObserverConfigBuilder {
         self
     }
 
+    /// Sets the derivation range used for descriptor predicates that do not define their own `range`.
+    pub fn descriptor_default_range(&mut self, range: [u32; 2]) -> &mut Self {
+        self.descriptor_default_range = Some(range);
+        self
+    }
+
+    /// Enables at-least-once delivery of `http_post` occurrences, persisting unacknowledged occurrences in `path`.
+    pub fn pending_deliveries_path(&mut self, path: &str) -> &mut Self {
+        self.pending_deliveries_path = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Writes the `http_post` occurrences that could not be delivered to `path`, instead of dropping them.
+    pub fn dead_letters_path(&mut self, path: &str) -> &mut Self {
+        self.dead_letters_path = Some(PathBuf::from(path));
+        self
+    }
+
     /// Attempts to convert a [EventObserverConfigBuilder] instance into an [EventObserverConfig], filling in
     /// defaults as necessary according to [EventObserverConfig::default].
     ///
@@ -217,6 +298,9 @@ pub struct BitcoinEventObserverConfigBuilder {
     pub bitcoin_network: Option<String>,
     pub bitcoind_zmq_url: Option<String>,
     pub prometheus_monitoring_port: Option<u16>,
+    pub descriptor_default_range: Option<[u32; 2]>,
+    pub pending_deliveries_path: Option<PathBuf>,
+    pub dead_letters_path: Option<PathBuf>,
 }
 impl BitcoinEventObserverConfigBuilder {
     pub fn new() -> Self {
@@ -227,6 +311,9 @@ impl BitcoinEventObserverConfigBuilder {
             bitcoin_network: None,
             bitcoind_zmq_url: None,
             prometheus_monitoring_port: None,
+            descriptor_default_range: None,
+            pending_deliveries_path: None,
+            dead_letters_path: None,
         }
     }
 
@@ -266,6 +353,24 @@ impl BitcoinEventObserverConfigBuilder {
         self
     }
 
+    /// Sets the derivation range used for descriptor predicates that do not define their own `range`.
+    pub fn descriptor_default_range(&mut self, range: [u32; 2]) -> &mut Self {
+        self.descriptor_default_range = Some(range);
+        self
+    }
+
+    /// Enables at-least-once delivery of `http_post` occurrences, persisting unacknowledged occurrences in `path`.
+    pub fn pending_deliveries_path(&mut self, path: &str) -> &mut Self {
+        self.pending_deliveries_path = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Writes the `http_post` occurrences that could not be delivered to `path`, instead of dropping them.
+    pub fn dead_letters_path(&mut self, path: &str) -> &mut Self {
+        self.dead_letters_path = Some(PathBuf::from(path));
+        self
+    }
+
     /// Attempts to convert a [BitcoinEventObserverConfigBuilder] instance into an [EventObserverConfig], filling in
     /// defaults as necessary according to [EventObserverConfig::default].
     ///
@@ -300,6 +405,16 @@ impl BitcoinEventObserverConfigBuilder {
             bitcoin_network: bitcoin_network,
             stacks_network: StacksNetwork::Devnet,
             prometheus_monitoring_port: self.prometheus_monitoring_port,
+            descriptor_default_range: self.descriptor_default_range,
+            pending_deliveries_path: self.pending_deliveries_path.clone(),
+            dead_letters_path: self.dead_letters_path.clone(),
+            http_client: None,
+            http_retry_policy: HttpRetryPolicy::default(),
+            signing_secrets: SigningSecrets::default(),
+            deduplication_hash: ContentHashAlgorithm::default(),
+            stacks_operations_parsing: StacksOperationsParsing::default(),
+            max_buffered_occurrences_size: None,
+            deduplicate_reapplied_blocks: false,
         })
     }
 }
@@ -320,9 +435,25 @@ impl EventObserverConfig {
             bitcoin_network: BitcoinNetwork::Regtest,
             stacks_network: StacksNetwork::Devnet,
             prometheus_monitoring_port: None,
+            descriptor_default_range: None,
+            pending_deliveries_path: None,
+            dead_letters_path: None,
+            http_client: None,
+            http_retry_policy: HttpRetryPolicy::default(),
+            signing_secrets: SigningSecrets::default(),
+            deduplication_hash: ContentHashAlgorithm::default(),
+            stacks_operations_parsing: StacksOperationsParsing::default(),
+            max_buffered_occurrences_size: None,
+            deduplicate_reapplied_blocks: false,
         }
     }
 
+    /// Sets the client used to deliver `http_post` occurrences, returning the updated config.
+    pub fn http_client(&mut self, http_client: HttpClient) -> &mut Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
     /// Adds a [ChainhookInstance] to config's the registered chainhook store, returning the updated config.
     pub fn register_chainhook_instance(
         &mut self,
@@ -419,6 +550,16 @@ impl EventObserverConfig {
             bitcoin_network,
             stacks_network,
             prometheus_monitoring_port: overrides.and_then(|c| c.prometheus_monitoring_port),
+            descriptor_default_range: overrides.and_then(|c| c.descriptor_default_range),
+            pending_deliveries_path: overrides.and_then(|c| c.pending_deliveries_path.clone()),
+            dead_letters_path: overrides.and_then(|c| c.dead_letters_path.clone()),
+            http_client: None,
+            http_retry_policy: HttpRetryPolicy::default(),
+            signing_secrets: SigningSecrets::default(),
+            deduplication_hash: ContentHashAlgorithm::default(),
+            stacks_operations_parsing: StacksOperationsParsing::default(),
+            max_buffered_occurrences_size: None,
+            deduplicate_reapplied_blocks: false,
         };
         Ok(config)
     }
@@ -443,6 +584,9 @@ pub enum ObserverCommand {
     DeregisterStacksPredicate(String),
     ExpireBitcoinPredicate(HookExpirationData),
     ExpireStacksPredicate(HookExpirationData),
+    UpdatePredicateAction(PredicateActionUpdateData),
+    /// Reconciles the registered predicates with the specifications read from the predicates db.
+    ReloadPredicates(Vec<ChainhookInstance>),
     NotifyBitcoinTransactionProxied,
     Terminate,
 }
@@ -453,6 +597,12 @@ pub struct HookExpirationData {
     pub block_height: u64,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct PredicateActionUpdateData {
+    pub predicate_uuid: String,
+    pub action: HookAction,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum StacksChainMempoolEvent {
     TransactionsAdmitted(Vec<MempoolAdmissionData>),
@@ -470,6 +620,8 @@ pub struct PredicateEvaluationReport {
     pub predicates_evaluated: BTreeMap<String, BTreeSet<BlockIdentifier>>,
     pub predicates_triggered: BTreeMap<String, BTreeSet<BlockIdentifier>>,
     pub predicates_expired: BTreeMap<String, BTreeSet<BlockIdentifier>>,
+    /// Blocks rolled back by a re-org on which the predicates had triggered.
+    pub predicates_rolled_back: BTreeMap<String, BTreeSet<BlockIdentifier>>,
 }
 
 impl PredicateEvaluationReport {
@@ -478,6 +630,7 @@ impl PredicateEvaluationReport {
             predicates_evaluated: BTreeMap::new(),
             predicates_triggered: BTreeMap::new(),
             predicates_expired: BTreeMap::new(),
+            predicates_rolled_back: BTreeMap::new(),
         }
     }
 
@@ -509,6 +662,21 @@ impl PredicateEvaluationReport {
         }
     }
 
+    pub fn track_rollback(&mut self, uuid: &str, blocks: &Vec<&BlockIdentifier>) {
+        for block_id in blocks.into_iter() {
+            self.predicates_rolled_back
+                .entry(uuid.to_string())
+                .and_modify(|e| {
+                    e.insert((*block_id).clone());
+                })
+                .or_insert_with(|| {
+                    let mut set = BTreeSet::new();
+                    set.insert((*block_id).clone());
+                    set
+                });
+        }
+    }
+
     pub fn track_expiration(&mut self, uuid: &str, block_identifier: &BlockIdentifier) {
         self.predicates_expired
             .entry(uuid.to_string())
@@ -540,6 +708,8 @@ pub enum ObserverEvent {
     PredicateRegistered(ChainhookInstance),
     PredicateDeregistered(PredicateDeregisteredEvent),
     PredicateEnabled(ChainhookInstance),
+    PredicateActionUpdated(ChainhookInstance),
+    PredicateReloaded(ChainhookInstance),
     BitcoinPredicateTriggered(BitcoinChainhookOccurrencePayload),
     StacksPredicateTriggered(StacksChainhookOccurrencePayload),
     PredicatesTriggered(usize),
@@ -1098,10 +1268,54 @@ pub async fn start_observer_commands_handler(
     let networks = (&config.bitcoin_network, &config.stacks_network);
     let mut bitcoin_block_store: HashMap<BlockIdentifier, BitcoinBlockDataCached> = HashMap::new();
     let http_client = build_http_client();
+    // built once for the lifetime of the observer when the config doesn't provide one
+    let delivery_client = match config.http_client {
+        Some(ref client) => client.clone(),
+        None => build_http_delivery_client(&HttpDeliveryConfig::default())?,
+    };
     let store_update_required = observer_sidecar
         .as_ref()
         .and_then(|s| s.bitcoin_blocks_mutator.as_ref())
         .is_some();
+    // index of the endpoint currently accepting the occurrences of each predicate with failover urls
+    let mut endpoints_health: HashMap<String, usize> = HashMap::new();
+    let mut pending_deliveries = match config.pending_deliveries_path {
+        Some(ref path) => {
+            let store = PendingDeliveryStore::open(path)?;
+            let registered_hooks: HashMap<String, HttpHook> = chainhook_store
+                .bitcoin_chainhooks
+                .iter()
+                .filter_map(|chainhook| Some((chainhook.key(), chainhook.action.http_hook()?)))
+                .chain(
+                    chainhook_store
+                        .stacks_chainhooks
+                        .iter()
+                        .filter_map(|chainhook| {
+                            Some((chainhook.key(), chainhook.action.http_hook()?))
+                        }),
+                )
+                .map(|(key, hook)| (key, hook.clone()))
+                .collect();
+            redeliver_pending_deliveries(
+                &store,
+                &registered_hooks,
+                &config.signing_secrets,
+                &mut endpoints_health,
+                &delivery_client,
+                &config.http_retry_policy,
+                &ctx,
+            )
+            .await?;
+            Some(store)
+        }
+        None => None,
+    };
+    let mut dead_letters = match config.dead_letters_path {
+        Some(ref path) => Some(DeadLetterStore::open(path)?),
+        None => None,
+    };
+    let mut applied_blocks = AppliedBlocksCache::new(DEFAULT_APPLIED_BLOCKS_CACHE_CAPACITY);
+    let mut digests = BitcoinDigestAccumulator::new();
 
     loop {
         let command = match observer_commands_rx.recv() {
@@ -1125,6 +1339,7 @@ pub async fn start_observer_commands_handler(
                     match standardize_bitcoin_block(
                         block_data.clone(),
                         &config.bitcoin_network,
+                        &config.stacks_operations_parsing,
                         &ctx,
                     ) {
                         Ok(block) => break Some(block),
@@ -1193,6 +1408,13 @@ pub async fn start_observer_commands_handler(
                 ctx.try_log(|logger| {
                     slog::info!(logger, "Handling PropagateBitcoinChainEvent command")
                 });
+                // files are rotated on each block, even when no row is appended to them
+                #[cfg(feature = "parquet")]
+                if let Err(e) = close_expired_parquet_files() {
+                    ctx.try_log(|logger| {
+                        slog::warn!(logger, "unable to close parquet files: {}", e)
+                    });
+                }
                 let mut confirmed_blocks = vec![];
 
                 // Update Chain event before propagation
@@ -1373,7 +1595,9 @@ pub async fn start_observer_commands_handler(
                 }
                 // process hooks
                 let mut hooks_ids_to_deregister = vec![];
-                let mut requests = vec![];
+                let mut hooks_ids_to_interrupt = vec![];
+                let mut occurrences =
+                    BitcoinOccurrencesBuffer::new(config.max_buffered_occurrences_size);
                 let mut report = PredicateEvaluationReport::new();
 
                 let bitcoin_chainhooks = chainhook_store
@@ -1394,14 +1618,25 @@ pub async fn start_observer_commands_handler(
                     evaluate_bitcoin_chainhooks_on_chain_event(
                         &chain_event,
                         &bitcoin_chainhooks,
+                        config.descriptor_default_range,
                         &ctx,
                     );
+                let predicates_triggered = if config.deduplicate_reapplied_blocks {
+                    predicates_triggered
+                        .into_iter()
+                        .filter_map(|trigger| applied_blocks.deduplicate(trigger))
+                        .collect::<Vec<_>>()
+                } else {
+                    predicates_triggered
+                };
 
                 for (uuid, block_identifier) in predicates_evaluated.into_iter() {
                     report.track_evaluation(uuid, block_identifier);
                 }
+                let mut hooks_ids_expired = vec![];
                 for (uuid, block_identifier) in predicates_expired.into_iter() {
                     report.track_expiration(uuid, block_identifier);
+                    hooks_ids_expired.push(uuid);
                 }
                 for entry in predicates_triggered.iter() {
                     let blocks_ids = entry
@@ -1410,6 +1645,12 @@ pub async fn start_observer_commands_handler(
                         .map(|e| &e.1.block_identifier)
                         .collect::<Vec<&BlockIdentifier>>();
                     report.track_trigger(&entry.chainhook.uuid, &blocks_ids);
+                    let rolled_back_blocks_ids = entry
+                        .rollback
+                        .iter()
+                        .map(|e| &e.1.block_identifier)
+                        .collect::<Vec<&BlockIdentifier>>();
+                    report.track_rollback(&entry.chainhook.uuid, &rolled_back_blocks_ids);
                 }
 
                 ctx.try_log(|logger| {
@@ -1443,6 +1684,39 @@ pub async fn start_observer_commands_handler(
                     }
                 }
 
+                // the occurrences of predicates with a digest are only delivered once their window closes
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                let mut closed_digests = vec![];
+                let (digested_triggers, chainhooks_to_trigger): (Vec<_>, Vec<_>) =
+                    chainhooks_to_trigger
+                        .into_iter()
+                        .partition(|trigger| trigger.chainhook.digest.is_some());
+                for trigger in digested_triggers.iter() {
+                    if let Some(digest) = digests.accumulate(trigger, now) {
+                        closed_digests.push((trigger.chainhook, digest));
+                    }
+                }
+                closed_digests.append(&mut digests.close_windows(
+                    &bitcoin_chainhooks,
+                    new_tip,
+                    now,
+                ));
+                // the windows of predicates reaching their `end_block` or `expire_after_occurrence`
+                // are delivered right away, since they won't be evaluated anymore
+                for chainhook in bitcoin_chainhooks.iter() {
+                    let is_expiring = hooks_ids_expired.contains(&chainhook.uuid.as_str())
+                        || hooks_ids_to_deregister.contains(&chainhook.uuid);
+                    if !is_expiring {
+                        continue;
+                    }
+                    if let Some(digest) = digests.close_window(&chainhook.uuid) {
+                        closed_digests.push((*chainhook, digest));
+                    }
+                }
+
                 let mut proofs = HashMap::new();
                 for trigger in chainhooks_to_trigger.iter() {
                     if trigger.chainhook.include_proof {
@@ -1453,19 +1727,50 @@ pub async fn start_observer_commands_handler(
                 ctx.try_log(|logger| {
                     slog::info!(
                         logger,
-                        "{} bitcoin chainhooks will be triggered",
-                        chainhooks_to_trigger.len()
+                        "{} bitcoin chainhooks will be triggered, {} digests delivered",
+                        chainhooks_to_trigger.len(),
+                        closed_digests.len()
                     )
                 });
 
                 if let Some(ref tx) = observer_events_tx {
                     let _ = tx.send(ObserverEvent::PredicatesTriggered(
-                        chainhooks_to_trigger.len(),
+                        chainhooks_to_trigger.len() + closed_digests.len(),
                     ));
                 }
-                for chainhook_to_trigger in chainhooks_to_trigger.into_iter() {
-                    let predicate_uuid = &chainhook_to_trigger.chainhook.uuid;
-                    match handle_bitcoin_hook_action(chainhook_to_trigger, &proofs) {
+                let occurrences_to_deliver = chainhooks_to_trigger
+                    .into_iter()
+                    .map(|trigger| {
+                        (
+                            trigger.chainhook.uuid.clone(),
+                            &trigger.chainhook.action,
+                            handle_bitcoin_hook_action(
+                                trigger,
+                                &proofs,
+                                config.descriptor_default_range,
+                                Some(&delivery_client),
+                                &config.signing_secrets,
+                            ),
+                        )
+                    })
+                    .chain(closed_digests.into_iter().map(|(chainhook, digest)| {
+                        (
+                            chainhook.uuid.clone(),
+                            &chainhook.action,
+                            handle_bitcoin_digest_action(
+                                chainhook,
+                                digest,
+                                Some(&delivery_client),
+                                &config.signing_secrets,
+                            ),
+                        )
+                    }));
+                // sent in batches once every occurrence of the chain event is built
+                #[cfg(feature = "sqs")]
+                let mut sqs_occurrences = vec![];
+                for (predicate_uuid, action, occurrence) in occurrences_to_deliver {
+                    let failover_urls = action.failover_urls().to_vec();
+                    match occurrence {
                         Err(e) => {
                             // todo: we may want to set predicates that reach this branch as interrupted,
                             // but for now we will error to see if this problem occurs.
@@ -1479,19 +1784,154 @@ pub async fn start_observer_commands_handler(
                             });
                         }
                         Ok(BitcoinChainhookOccurrence::Http(request, data)) => {
-                            requests.push((request, data));
+                            let verification = action.build_verification_request(
+                                &delivery_client,
+                                &config.signing_secrets,
+                                &data.intent(),
+                            );
+                            occurrences.push_request(request, failover_urls, verification, data);
                         }
                         Ok(BitcoinChainhookOccurrence::File(_path, _bytes)) => {
                             ctx.try_log(|logger| {
                                 slog::warn!(logger, "Writing to disk not supported in server mode")
                             })
                         }
+                        Ok(BitcoinChainhookOccurrence::UnixSocket(path, bytes)) => {
+                            occurrences.push_socket_write(predicate_uuid.clone(), path, bytes);
+                        }
+                        #[cfg(feature = "websocket")]
+                        Ok(BitcoinChainhookOccurrence::WebSocket(url, bytes)) => {
+                            if let Err(e) = send_websocket_message(&url, bytes, &ctx) {
+                                if let Some(ref tx) = observer_events_tx {
+                                    let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                                        PredicateInterruptedData {
+                                            predicate_key: ChainhookInstance::bitcoin_key(
+                                                &predicate_uuid,
+                                            ),
+                                            error: format!(
+                                                "Unable to push websocket message: {}",
+                                                e
+                                            ),
+                                        },
+                                    ));
+                                }
+                                hooks_ids_to_interrupt.push(predicate_uuid.clone());
+                            }
+                        }
+                        #[cfg(feature = "parquet")]
+                        Ok(BitcoinChainhookOccurrence::Parquet(hook, rows)) => {
+                            if let Err(e) = append_parquet_rows(&hook, &rows) {
+                                if let Some(ref tx) = observer_events_tx {
+                                    let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                                        PredicateInterruptedData {
+                                            predicate_key: ChainhookInstance::bitcoin_key(
+                                                &predicate_uuid,
+                                            ),
+                                            error: format!("Unable to write parquet rows: {}", e),
+                                        },
+                                    ));
+                                }
+                                hooks_ids_to_interrupt.push(predicate_uuid.clone());
+                            }
+                        }
+                        #[cfg(feature = "kafka")]
+                        Ok(BitcoinChainhookOccurrence::Kafka(hook, key, bytes)) => {
+                            if let Err(e) = produce_kafka_message(&hook, &key, &bytes).await {
+                                if let Some(ref tx) = observer_events_tx {
+                                    let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                                        PredicateInterruptedData {
+                                            predicate_key: ChainhookInstance::bitcoin_key(
+                                                &predicate_uuid,
+                                            ),
+                                            error: format!(
+                                                "Unable to produce kafka message: {}",
+                                                e
+                                            ),
+                                        },
+                                    ));
+                                }
+                                hooks_ids_to_interrupt.push(predicate_uuid.clone());
+                            }
+                        }
+                        #[cfg(feature = "grpc")]
+                        Ok(BitcoinChainhookOccurrence::Grpc(hook, _, bytes)) => {
+                            let res =
+                                send_grpc_occurrence(&hook, &predicate_uuid, bytes, &ctx).await;
+                            if let Err(e) = res {
+                                if let Some(ref tx) = observer_events_tx {
+                                    let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                                        PredicateInterruptedData {
+                                            predicate_key: ChainhookInstance::bitcoin_key(
+                                                &predicate_uuid,
+                                            ),
+                                            error: format!("Unable to stream occurrence: {}", e),
+                                        },
+                                    ));
+                                }
+                                hooks_ids_to_interrupt.push(predicate_uuid.clone());
+                            }
+                        }
+                        #[cfg(feature = "sqs")]
+                        Ok(BitcoinChainhookOccurrence::Sqs(hook, _, bytes)) => {
+                            sqs_occurrences.push((hook, predicate_uuid.clone(), bytes));
+                        }
+                        #[cfg(feature = "s3")]
+                        Ok(BitcoinChainhookOccurrence::S3Append(hook, key, bytes)) => {
+                            if let Err(e) = append_s3_object(&hook, &key, bytes).await {
+                                if let Some(ref tx) = observer_events_tx {
+                                    let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                                        PredicateInterruptedData {
+                                            predicate_key: ChainhookInstance::bitcoin_key(
+                                                &predicate_uuid,
+                                            ),
+                                            error: format!("Unable to write s3 object: {}", e),
+                                        },
+                                    ));
+                                }
+                                hooks_ids_to_interrupt.push(predicate_uuid.clone());
+                            }
+                        }
                         Ok(BitcoinChainhookOccurrence::Data(payload)) => {
-                            if let Some(ref tx) = observer_events_tx {
-                                let _ = tx.send(ObserverEvent::BitcoinPredicateTriggered(payload));
+                            if observer_events_tx.is_some() {
+                                occurrences.push_payload(payload);
                             }
                         }
                     }
+                    if occurrences.is_full() {
+                        ctx.try_log(|logger| {
+                            slog::warn!(
+                                logger,
+                                "{} bytes of bitcoin occurrences buffered, delivering them before building the next ones",
+                                occurrences.size()
+                            )
+                        });
+                        hooks_ids_to_interrupt.append(
+                            &mut deliver_bitcoin_occurrences(
+                                &mut occurrences,
+                                &mut pending_deliveries,
+                                &mut dead_letters,
+                                &mut endpoints_health,
+                                &config.http_retry_policy,
+                                &observer_events_tx,
+                                &ctx,
+                            )
+                            .await,
+                        );
+                    }
+                }
+                #[cfg(feature = "sqs")]
+                for (predicate_uuid, e) in
+                    send_sqs_occurrences(sqs_occurrences, config.deduplication_hash).await
+                {
+                    if let Some(ref tx) = observer_events_tx {
+                        let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                            PredicateInterruptedData {
+                                predicate_key: ChainhookInstance::bitcoin_key(&predicate_uuid),
+                                error: format!("Unable to send sqs message: {}", e),
+                            },
+                        ));
+                    }
+                    hooks_ids_to_interrupt.push(predicate_uuid);
                 }
                 ctx.try_log(|logger| {
                     slog::info!(
@@ -1518,24 +1958,23 @@ pub async fn start_observer_commands_handler(
                     }
                 }
 
-                for (request, data) in requests.into_iter() {
-                    match send_request(request, 3, 1, &ctx).await {
-                        Ok(_) => {
-                            if let Some(ref tx) = observer_events_tx {
-                                let _ = tx.send(ObserverEvent::BitcoinPredicateTriggered(data));
-                            }
-                        }
-                        Err(e) => {
-                            chainhook_store.deregister_bitcoin_hook(data.chainhook.uuid.clone());
-                            if let Some(ref tx) = observer_events_tx {
-                                let _ = tx.send(ObserverEvent::PredicateInterrupted(PredicateInterruptedData {
-                                    predicate_key: ChainhookInstance::bitcoin_key(&data.chainhook.uuid),
-                                    error: format!("Unable to evaluate predicate on Bitcoin chainstate: {}", e)
-                                }));
-                            }
-                        }
-                    }
+                hooks_ids_to_interrupt.append(
+                    &mut deliver_bitcoin_occurrences(
+                        &mut occurrences,
+                        &mut pending_deliveries,
+                        &mut dead_letters,
+                        &mut endpoints_health,
+                        &config.http_retry_policy,
+                        &observer_events_tx,
+                        &ctx,
+                    )
+                    .await,
+                );
+                for hook_uuid in hooks_ids_to_interrupt.into_iter() {
+                    chainhook_store.deregister_bitcoin_hook(hook_uuid);
                 }
+                #[cfg(feature = "websocket")]
+                close_unused_websocket_feeds(&chainhook_store);
 
                 prometheus_monitoring.btc_metrics_block_evaluated(new_tip);
 
@@ -1549,6 +1988,7 @@ pub async fn start_observer_commands_handler(
                 });
                 let mut hooks_ids_to_deregister = vec![];
                 let mut requests = vec![];
+                let mut socket_writes = vec![];
                 let mut report = PredicateEvaluationReport::new();
 
                 let stacks_chainhooks = chainhook_store
@@ -1619,6 +2059,12 @@ pub async fn start_observer_commands_handler(
                         .map(|e| e.1.get_identifier())
                         .collect::<Vec<&BlockIdentifier>>();
                     report.track_trigger(&entry.chainhook.uuid, &blocks_ids);
+                    let rolled_back_blocks_ids = entry
+                        .rollback
+                        .iter()
+                        .map(|e| e.1.get_identifier())
+                        .collect::<Vec<&BlockIdentifier>>();
+                    report.track_rollback(&entry.chainhook.uuid, &rolled_back_blocks_ids);
                 }
                 ctx.try_log(|logger| {
                     slog::info!(
@@ -1654,7 +2100,15 @@ pub async fn start_observer_commands_handler(
                 let proofs = HashMap::new();
                 for chainhook_to_trigger in chainhooks_to_trigger.into_iter() {
                     let predicate_uuid = &chainhook_to_trigger.chainhook.uuid;
-                    match handle_stacks_hook_action(chainhook_to_trigger, &proofs, &ctx) {
+                    let action = &chainhook_to_trigger.chainhook.action;
+                    let failover_urls = action.failover_urls().to_vec();
+                    match handle_stacks_hook_action(
+                        chainhook_to_trigger,
+                        &proofs,
+                        Some(&delivery_client),
+                        &config.signing_secrets,
+                        &ctx,
+                    ) {
                         Err(e) => {
                             ctx.try_log(|logger| {
                                 // todo: we may want to set predicates that reach this branch as interrupted,
@@ -1668,13 +2122,21 @@ pub async fn start_observer_commands_handler(
                             });
                         }
                         Ok(StacksChainhookOccurrence::Http(request, data)) => {
-                            requests.push((request, data));
+                            let verification = action.build_verification_request(
+                                &delivery_client,
+                                &config.signing_secrets,
+                                &data.intent(),
+                            );
+                            requests.push((request, failover_urls, verification, data));
                         }
                         Ok(StacksChainhookOccurrence::File(_path, _bytes)) => {
                             ctx.try_log(|logger| {
                                 slog::warn!(logger, "Writing to disk not supported in server mode")
                             })
                         }
+                        Ok(StacksChainhookOccurrence::UnixSocket(path, bytes)) => {
+                            socket_writes.push((predicate_uuid.clone(), path, bytes));
+                        }
                         Ok(StacksChainhookOccurrence::Data(payload)) => {
                             if let Some(ref tx) = observer_events_tx {
                                 let _ = tx.send(ObserverEvent::StacksPredicateTriggered(payload));
@@ -1700,7 +2162,7 @@ pub async fn start_observer_commands_handler(
                     }
                 }
 
-                for (request, data) in requests.into_iter() {
+                for (request, failover_urls, verification, data) in requests.into_iter() {
                     // todo(lgalabru): collect responses for reporting
                     ctx.try_log(|logger| {
                         slog::debug!(
@@ -1709,13 +2171,48 @@ pub async fn start_observer_commands_handler(
                             request
                         )
                     });
-                    match send_request(request, 3, 1, &ctx).await {
+                    let pending_delivery_id = pending_deliveries.as_mut().and_then(|store| {
+                        store.try_record(
+                            &ChainhookInstance::stacks_key(&data.chainhook.uuid),
+                            &request,
+                            &failover_urls,
+                            &ctx,
+                        )
+                    });
+                    let failed_request = dead_letters.as_ref().and_then(|_| request.try_clone());
+                    let healthy_endpoint = endpoints_health
+                        .entry(ChainhookInstance::stacks_key(&data.chainhook.uuid))
+                        .or_insert(0);
+                    match send_request_with_verification(
+                        verification,
+                        request,
+                        &failover_urls,
+                        healthy_endpoint,
+                        &config.http_retry_policy,
+                        &ctx,
+                    )
+                    .await
+                    {
                         Ok(_) => {
+                            if let (Some(store), Some(id)) =
+                                (pending_deliveries.as_ref(), pending_delivery_id)
+                            {
+                                store.try_acknowledge(id, &ctx);
+                            }
                             if let Some(ref tx) = observer_events_tx {
                                 let _ = tx.send(ObserverEvent::StacksPredicateTriggered(data));
                             }
                         }
                         Err(e) => {
+                            dead_letter_failed_delivery(
+                                &mut dead_letters,
+                                &pending_deliveries,
+                                pending_delivery_id,
+                                &ChainhookInstance::stacks_key(&data.chainhook.uuid),
+                                failed_request,
+                                &e,
+                                &ctx,
+                            );
                             chainhook_store.deregister_stacks_hook(data.chainhook.uuid.clone());
                             if let Some(ref tx) = observer_events_tx {
                                 let _ = tx.send(ObserverEvent::PredicateInterrupted(PredicateInterruptedData {
@@ -1727,6 +2224,23 @@ pub async fn start_observer_commands_handler(
                     };
                 }
 
+                for (predicate_uuid, path, bytes) in socket_writes.into_iter() {
+                    if let Err(e) = unix_socket_send(path, bytes, 3, 100, &ctx).await {
+                        chainhook_store.deregister_stacks_hook(predicate_uuid.clone());
+                        if let Some(ref tx) = observer_events_tx {
+                            let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                                PredicateInterruptedData {
+                                    predicate_key: ChainhookInstance::stacks_key(&predicate_uuid),
+                                    error: format!(
+                                        "Unable to evaluate predicate on Stacks chainstate: {}",
+                                        e
+                                    ),
+                                },
+                            ));
+                        }
+                    }
+                }
+
                 prometheus_monitoring.stx_metrics_block_evaluated(new_tip);
 
                 if let Some(ref tx) = observer_events_tx {
@@ -1800,6 +2314,13 @@ pub async fn start_observer_commands_handler(
                     slog::info!(logger, "Handling DeregisterStacksPredicate command")
                 });
                 let hook = chainhook_store.deregister_stacks_hook(hook_uuid.clone());
+                endpoints_health.remove(&ChainhookInstance::stacks_key(&hook_uuid));
+                if let Some(ref store) = pending_deliveries {
+                    store.try_drop_predicate_deliveries(
+                        &ChainhookInstance::stacks_key(&hook_uuid),
+                        &ctx,
+                    );
+                }
 
                 if hook.is_some() {
                     // on startup, only the predicates in the `chainhook_store` are added to the monitoring count,
@@ -1821,6 +2342,16 @@ pub async fn start_observer_commands_handler(
                     slog::info!(logger, "Handling DeregisterBitcoinPredicate command")
                 });
                 let hook = chainhook_store.deregister_bitcoin_hook(hook_uuid.clone());
+                endpoints_health.remove(&ChainhookInstance::bitcoin_key(&hook_uuid));
+                digests.close_window(&hook_uuid);
+                #[cfg(feature = "websocket")]
+                close_unused_websocket_feeds(&chainhook_store);
+                if let Some(ref store) = pending_deliveries {
+                    store.try_drop_predicate_deliveries(
+                        &ChainhookInstance::bitcoin_key(&hook_uuid),
+                        &ctx,
+                    );
+                }
 
                 if hook.is_some() {
                     // on startup, only the predicates in the `chainhook_store` are added to the monitoring count,
@@ -1853,18 +2384,233 @@ pub async fn start_observer_commands_handler(
                 });
                 chainhook_store.expire_bitcoin_hook(hook_uuid, block_height);
             }
+            ObserverCommand::UpdatePredicateAction(PredicateActionUpdateData {
+                predicate_uuid,
+                action,
+            }) => {
+                ctx.try_log(|logger| slog::info!(logger, "Handling UpdatePredicateAction command"));
+                match chainhook_store.update_instance_action(&predicate_uuid, action) {
+                    Ok(spec) => {
+                        if let Some(ref tx) = observer_events_tx {
+                            let _ = tx.send(ObserverEvent::PredicateActionUpdated(spec));
+                        }
+                    }
+                    Err(e) => ctx.try_log(|logger| {
+                        slog::warn!(
+                            logger,
+                            "Unable to update action of predicate {}: {}",
+                            predicate_uuid,
+                            e
+                        )
+                    }),
+                }
+            }
+            ObserverCommand::ReloadPredicates(instances) => {
+                ctx.try_log(|logger| slog::info!(logger, "Handling ReloadPredicates command"));
+                let (updated, added, removed) = chainhook_store.reload_instances(instances);
+                ctx.try_log(|logger| {
+                    slog::info!(
+                        logger,
+                        "{} predicates reloaded, {} predicates registered, {} predicates deregistered",
+                        updated.len(),
+                        added.len(),
+                        removed.len()
+                    )
+                });
+                for spec in updated.into_iter() {
+                    if let Some(ref tx) = observer_events_tx {
+                        let _ = tx.send(ObserverEvent::PredicateReloaded(spec));
+                    }
+                }
+                for mut spec in added.into_iter() {
+                    match spec {
+                        ChainhookInstance::Bitcoin(_) => {
+                            prometheus_monitoring.btc_metrics_register_predicate()
+                        }
+                        ChainhookInstance::Stacks(_) => {
+                            prometheus_monitoring.stx_metrics_register_predicate()
+                        }
+                    };
+                    // like registered predicates, they're only enabled once scanned
+                    if let Some(ref tx) = observer_events_tx {
+                        let _ = tx.send(ObserverEvent::PredicateRegistered(spec));
+                    } else {
+                        chainhook_store.enable_instance(&mut spec);
+                    }
+                }
+                for spec in removed.into_iter() {
+                    let chain = match spec {
+                        ChainhookInstance::Bitcoin(_) => {
+                            prometheus_monitoring.btc_metrics_deregister_predicate();
+                            digests.close_window(spec.uuid());
+                            Chain::Bitcoin
+                        }
+                        ChainhookInstance::Stacks(_) => {
+                            prometheus_monitoring.stx_metrics_deregister_predicate();
+                            Chain::Stacks
+                        }
+                    };
+                    endpoints_health.remove(&spec.key());
+                    if let Some(ref store) = pending_deliveries {
+                        store.try_drop_predicate_deliveries(&spec.key(), &ctx);
+                    }
+                    if let Some(ref tx) = observer_events_tx {
+                        let _ = tx.send(ObserverEvent::PredicateDeregistered(
+                            PredicateDeregisteredEvent {
+                                predicate_uuid: spec.uuid().to_string(),
+                                chain,
+                            },
+                        ));
+                    }
+                }
+                #[cfg(feature = "websocket")]
+                close_unused_websocket_feeds(&chainhook_store);
+            }
         }
     }
     terminate(ingestion_shutdown, observer_events_tx, &ctx);
     Ok(())
 }
 
+/// Closes the websocket feeds that no registered predicate pushes to anymore.
+#[cfg(feature = "websocket")]
+fn close_unused_websocket_feeds(chainhook_store: &ChainhookStore) {
+    let urls = chainhook_store
+        .bitcoin_chainhooks
+        .iter()
+        .filter_map(|chainhook| match &chainhook.action {
+            HookAction::WebSocket(websocket) => Some(websocket.url.as_str()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    retain_websocket_feeds(&urls);
+}
+
+/// Writes `failed_request`, whose delivery ended with `reason`, to the dead letters when enabled.
+/// Once dead lettered, the occurrence is no longer redelivered on restart.
+fn dead_letter_failed_delivery(
+    dead_letters: &mut Option<DeadLetterStore>,
+    pending_deliveries: &Option<PendingDeliveryStore>,
+    pending_delivery_id: Option<u64>,
+    predicate_key: &str,
+    failed_request: Option<RequestBuilder>,
+    reason: &str,
+    ctx: &Context,
+) {
+    let (Some(store), Some(failed_request)) = (dead_letters.as_mut(), failed_request) else {
+        return;
+    };
+    if store.try_record(predicate_key, &failed_request, reason, ctx) {
+        if let (Some(store), Some(id)) = (pending_deliveries.as_ref(), pending_delivery_id) {
+            store.try_acknowledge(id, ctx);
+        }
+    }
+}
+
+/// Delivers the occurrences buffered in `occurrences`, returning the uuids of the predicates whose
+/// occurrences could not be delivered. These predicates are reported as interrupted.
+async fn deliver_bitcoin_occurrences(
+    occurrences: &mut BitcoinOccurrencesBuffer,
+    pending_deliveries: &mut Option<PendingDeliveryStore>,
+    dead_letters: &mut Option<DeadLetterStore>,
+    endpoints_health: &mut HashMap<String, usize>,
+    retry_policy: &HttpRetryPolicy,
+    observer_events_tx: &Option<crossbeam_channel::Sender<ObserverEvent>>,
+    ctx: &Context,
+) -> Vec<String> {
+    let mut hooks_ids_to_interrupt = vec![];
+    let (requests, socket_writes, payloads) = occurrences.drain();
+    for (request, failover_urls, verification, data) in requests.into_iter() {
+        let pending_delivery_id = pending_deliveries.as_mut().and_then(|store| {
+            store.try_record(
+                &ChainhookInstance::bitcoin_key(&data.chainhook.uuid),
+                &request,
+                &failover_urls,
+                ctx,
+            )
+        });
+        let failed_request = dead_letters.as_ref().and_then(|_| request.try_clone());
+        let healthy_endpoint = endpoints_health
+            .entry(ChainhookInstance::bitcoin_key(&data.chainhook.uuid))
+            .or_insert(0);
+        match send_request_with_verification(
+            verification,
+            request,
+            &failover_urls,
+            healthy_endpoint,
+            retry_policy,
+            ctx,
+        )
+        .await
+        {
+            Ok(_) => {
+                if let (Some(store), Some(id)) = (pending_deliveries.as_ref(), pending_delivery_id)
+                {
+                    store.try_acknowledge(id, ctx);
+                }
+                if let Some(ref tx) = observer_events_tx {
+                    let _ = tx.send(ObserverEvent::BitcoinPredicateTriggered(data));
+                }
+            }
+            Err(e) => {
+                dead_letter_failed_delivery(
+                    dead_letters,
+                    pending_deliveries,
+                    pending_delivery_id,
+                    &ChainhookInstance::bitcoin_key(&data.chainhook.uuid),
+                    failed_request,
+                    &e,
+                    ctx,
+                );
+                if let Some(ref tx) = observer_events_tx {
+                    let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                        PredicateInterruptedData {
+                            predicate_key: ChainhookInstance::bitcoin_key(&data.chainhook.uuid),
+                            error: format!(
+                                "Unable to evaluate predicate on Bitcoin chainstate: {}",
+                                e
+                            ),
+                        },
+                    ));
+                }
+                hooks_ids_to_interrupt.push(data.chainhook.uuid);
+            }
+        }
+    }
+
+    for (predicate_uuid, path, bytes) in socket_writes.into_iter() {
+        if let Err(e) = unix_socket_send(path, bytes, 3, 100, ctx).await {
+            if let Some(ref tx) = observer_events_tx {
+                let _ = tx.send(ObserverEvent::PredicateInterrupted(
+                    PredicateInterruptedData {
+                        predicate_key: ChainhookInstance::bitcoin_key(&predicate_uuid),
+                        error: format!("Unable to evaluate predicate on Bitcoin chainstate: {}", e),
+                    },
+                ));
+            }
+            hooks_ids_to_interrupt.push(predicate_uuid);
+        }
+    }
+
+    if let Some(ref tx) = observer_events_tx {
+        for payload in payloads.into_iter() {
+            let _ = tx.send(ObserverEvent::BitcoinPredicateTriggered(payload));
+        }
+    }
+    hooks_ids_to_interrupt
+}
+
 fn terminate(
     ingestion_shutdown: Option<Shutdown>,
     observer_events_tx: Option<crossbeam_channel::Sender<ObserverEvent>>,
     ctx: &Context,
 ) {
     ctx.try_log(|logger| slog::info!(logger, "Handling Termination command"));
+    // the parquet writers are never dropped: their last row groups and footers are written here
+    #[cfg(feature = "parquet")]
+    if let Err(e) = close_parquet_files() {
+        ctx.try_log(|logger| slog::warn!(logger, "unable to close parquet files: {}", e));
+    }
     if let Some(ingestion_shutdown) = ingestion_shutdown {
         ingestion_shutdown.notify();
     }
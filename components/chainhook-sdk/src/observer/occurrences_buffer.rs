@@ -0,0 +1,94 @@
+use reqwest::RequestBuilder;
+
+use crate::chainhooks::bitcoin::BitcoinChainhookOccurrencePayload;
+
+/// A request delivering an occurrence, along with the failover urls of its predicate and the
+/// request verifying the delivery, if any.
+pub type BufferedRequest = (
+    RequestBuilder,
+    Vec<String>,
+    Option<RequestBuilder>,
+    BitcoinChainhookOccurrencePayload,
+);
+
+/// The Bitcoin occurrences built for the predicates triggered by a chain event, waiting to be
+/// delivered.
+///
+/// The memory held by the buffer is approximated by the size of the serialized payloads, whether
+/// they are delivered over http, written to unix sockets, or handed to the observer events channel.
+/// Once it exceeds `max_size`, the buffer is full: the observer delivers the buffered occurrences
+/// before building the ones of the remaining triggered predicates.
+pub struct BitcoinOccurrencesBuffer {
+    max_size: Option<usize>,
+    size: usize,
+    requests: Vec<BufferedRequest>,
+    socket_writes: Vec<(String, String, Vec<u8>)>,
+    payloads: Vec<BitcoinChainhookOccurrencePayload>,
+}
+
+impl BitcoinOccurrencesBuffer {
+    pub fn new(max_size: Option<usize>) -> Self {
+        BitcoinOccurrencesBuffer {
+            max_size,
+            size: 0,
+            requests: vec![],
+            socket_writes: vec![],
+            payloads: vec![],
+        }
+    }
+
+    pub fn push_request(
+        &mut self,
+        request: RequestBuilder,
+        failover_urls: Vec<String>,
+        verification: Option<RequestBuilder>,
+        data: BitcoinChainhookOccurrencePayload,
+    ) {
+        self.size += get_payload_size(&data);
+        self.requests
+            .push((request, failover_urls, verification, data));
+    }
+
+    pub fn push_socket_write(&mut self, predicate_uuid: String, path: String, bytes: Vec<u8>) {
+        self.size += bytes.len();
+        self.socket_writes.push((predicate_uuid, path, bytes));
+    }
+
+    /// Buffers a payload sent on the observer events channel once the buffer is drained.
+    pub fn push_payload(&mut self, payload: BitcoinChainhookOccurrencePayload) {
+        self.size += get_payload_size(&payload);
+        self.payloads.push(payload);
+    }
+
+    /// The approximate size of the buffered occurrences, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_full(&self) -> bool {
+        match self.max_size {
+            Some(max_size) => self.size > max_size,
+            None => false,
+        }
+    }
+
+    /// Empties the buffer, returning the occurrences to deliver.
+    pub fn drain(
+        &mut self,
+    ) -> (
+        Vec<BufferedRequest>,
+        Vec<(String, String, Vec<u8>)>,
+        Vec<BitcoinChainhookOccurrencePayload>,
+    ) {
+        self.size = 0;
+        (
+            std::mem::take(&mut self.requests),
+            std::mem::take(&mut self.socket_writes),
+            std::mem::take(&mut self.payloads),
+        )
+    }
+}
+
+fn get_payload_size(payload: &BitcoinChainhookOccurrencePayload) -> usize {
+    serde_json::to_vec(payload).map_or(0, |bytes| bytes.len())
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use hiro_system_kit::slog;
+use reqwest::{Client as HttpClient, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::chainhooks::types::{HttpHook, SigningSecrets};
+use crate::utils::{send_request_with_failover, Context, HttpRetryPolicy};
+
+/// An occurrence dispatched to an `http_post` action that has not yet been acknowledged
+/// by the consumer with a 2xx response.
+///
+/// The headers of the request are not kept, so that credentials and signatures are never written
+/// to disk: they're built again from the `http_post` action of the predicate when redelivered.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingDelivery {
+    pub id: u64,
+    pub predicate_key: String,
+    pub url: String,
+    pub body: String,
+    /// The failover urls of the predicate, tried in order when `url` can't be delivered.
+    #[serde(default)]
+    pub failover_urls: Vec<String>,
+}
+
+impl PendingDelivery {
+    pub fn from_request(
+        id: u64,
+        predicate_key: &str,
+        request_builder: &RequestBuilder,
+        failover_urls: &[String],
+    ) -> Result<PendingDelivery, String> {
+        let request = request_builder
+            .try_clone()
+            .ok_or(format!("unable to clone request builder"))?
+            .build()
+            .map_err(|e| format!("unable to build request: {}", e.to_string()))?;
+        let body = match request.body().and_then(|b| b.as_bytes()) {
+            Some(bytes) => String::from_utf8(bytes.to_vec())
+                .map_err(|e| format!("unable to decode request body: {}", e.to_string()))?,
+            None => String::new(),
+        };
+        Ok(PendingDelivery {
+            id,
+            predicate_key: predicate_key.to_string(),
+            url: request.url().to_string(),
+            body,
+            failover_urls: failover_urls.to_vec(),
+        })
+    }
+
+    /// The request posting the body again to its url, authorized and signed as configured by
+    /// `hook`.
+    pub fn to_request(
+        &self,
+        http_client: &HttpClient,
+        hook: &HttpHook,
+        signing_secrets: &SigningSecrets,
+    ) -> Result<RequestBuilder, String> {
+        hook.build_request_to(
+            http_client,
+            signing_secrets,
+            &self.url,
+            self.body.clone().into_bytes(),
+        )
+    }
+}
+
+/// Persists each [PendingDelivery] as its own file, so that occurrences that were not acknowledged
+/// before a crash can be redelivered on restart.
+///
+/// Deliveries are only retried when the observer restarts, and only cover the occurrences of
+/// streaming predicates: the occurrences delivered by scans bypass the store.
+pub struct PendingDeliveryStore {
+    dir: PathBuf,
+    next_id: u64,
+}
+
+impl PendingDeliveryStore {
+    pub fn open(dir: &PathBuf) -> Result<PendingDeliveryStore, String> {
+        fs::create_dir_all(dir).map_err(|e| {
+            format!(
+                "unable to create pending deliveries directory {}: {}",
+                dir.display(),
+                e.to_string()
+            )
+        })?;
+        let mut store = PendingDeliveryStore {
+            dir: dir.clone(),
+            next_id: 0,
+        };
+        // ids are read from the file names, so that a corrupt entry can't reset them
+        store.next_id = store
+            .get_delivery_paths()?
+            .iter()
+            .filter_map(|path| path.file_stem()?.to_str()?.parse::<u64>().ok())
+            .max()
+            .map(|id| id + 1)
+            .unwrap_or(0);
+        Ok(store)
+    }
+
+    fn get_delivery_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.json", id))
+    }
+
+    /// Durably records the delivery of `request_builder` before it is sent.
+    pub fn record(
+        &mut self,
+        predicate_key: &str,
+        request_builder: &RequestBuilder,
+        failover_urls: &[String],
+    ) -> Result<PendingDelivery, String> {
+        let delivery = PendingDelivery::from_request(
+            self.next_id,
+            predicate_key,
+            request_builder,
+            failover_urls,
+        )?;
+        let bytes = serde_json::to_vec(&delivery)
+            .map_err(|e| format!("unable to serialize pending delivery: {}", e.to_string()))?;
+        // Write to a temporary file first so that a crash never leaves a truncated entry behind.
+        let tmp_path = self.dir.join(format!("{:020}.json.tmp", delivery.id));
+        let mut file = fs::File::create(&tmp_path).map_err(|e| {
+            format!(
+                "unable to create file {}: {}",
+                tmp_path.display(),
+                e.to_string()
+            )
+        })?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("unable to write file {}: {}", tmp_path.display(), e))?;
+        let path = self.get_delivery_path(delivery.id);
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("unable to write file {}: {}", path.display(), e))?;
+        self.next_id += 1;
+        Ok(delivery)
+    }
+
+    /// Drops a delivery once the consumer returned a 2xx response.
+    pub fn acknowledge(&self, id: u64) -> Result<(), String> {
+        let path = self.get_delivery_path(id);
+        fs::remove_file(&path)
+            .map_err(|e| format!("unable to remove file {}: {}", path.display(), e))
+    }
+
+    fn get_delivery_paths(&self) -> Result<Vec<PathBuf>, String> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| {
+            format!(
+                "unable to read directory {}: {}",
+                self.dir.display(),
+                e.to_string()
+            )
+        })?;
+        let mut paths = vec![];
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("unable to read directory entry: {}", e.to_string()))?
+                .path();
+            if path.extension().and_then(|e| e.to_str()) == Some("json") {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Returns the deliveries still awaiting an acknowledgment, oldest first. Entries that can't
+    /// be read are logged and skipped, so that a corrupt file doesn't hold back the other ones.
+    pub fn get_pending_deliveries(&self, ctx: &Context) -> Result<Vec<PendingDelivery>, String> {
+        let mut deliveries = vec![];
+        for path in self.get_delivery_paths()?.into_iter() {
+            let delivery = fs::read(&path)
+                .map_err(|e| format!("unable to read file {}: {}", path.display(), e))
+                .and_then(|bytes| {
+                    serde_json::from_slice::<PendingDelivery>(&bytes).map_err(|e| {
+                        format!(
+                            "unable to parse pending delivery {}: {}",
+                            path.display(),
+                            e.to_string()
+                        )
+                    })
+                });
+            match delivery {
+                Ok(delivery) => deliveries.push(delivery),
+                Err(e) => {
+                    ctx.try_log(|logger| slog::warn!(logger, "Skipping pending delivery: {}", e))
+                }
+            }
+        }
+        deliveries.sort_by_key(|delivery| delivery.id);
+        Ok(deliveries)
+    }
+
+    /// Drops the deliveries of the predicate `predicate_key`, e.g. once it is deregistered.
+    /// Returns the number of deliveries dropped.
+    pub fn drop_predicate_deliveries(
+        &self,
+        predicate_key: &str,
+        ctx: &Context,
+    ) -> Result<usize, String> {
+        let mut dropped = 0;
+        for delivery in self.get_pending_deliveries(ctx)?.iter() {
+            if delivery.predicate_key == predicate_key {
+                self.acknowledge(delivery.id)?;
+                dropped += 1;
+            }
+        }
+        Ok(dropped)
+    }
+
+    pub fn try_record(
+        &mut self,
+        predicate_key: &str,
+        request_builder: &RequestBuilder,
+        failover_urls: &[String],
+        ctx: &Context,
+    ) -> Option<u64> {
+        match self.record(predicate_key, request_builder, failover_urls) {
+            Ok(delivery) => Some(delivery.id),
+            Err(e) => {
+                ctx.try_log(|logger| {
+                    slog::warn!(logger, "Unable to record pending delivery: {}", e)
+                });
+                None
+            }
+        }
+    }
+
+    pub fn try_acknowledge(&self, id: u64, ctx: &Context) {
+        if let Err(e) = self.acknowledge(id) {
+            ctx.try_log(|logger| {
+                slog::warn!(logger, "Unable to acknowledge pending delivery: {}", e)
+            });
+        }
+    }
+
+    pub fn try_drop_predicate_deliveries(&self, predicate_key: &str, ctx: &Context) {
+        if let Err(e) = self.drop_predicate_deliveries(predicate_key, ctx) {
+            ctx.try_log(|logger| {
+                slog::warn!(
+                    logger,
+                    "Unable to drop pending deliveries of predicate {}: {}",
+                    predicate_key,
+                    e
+                )
+            });
+        }
+    }
+}
+
+/// Sends every delivery left unacknowledged by a previous run, dropping the ones the consumer acknowledges.
+/// Deliveries that still fail are kept and retried on the next restart. `registered_hooks` holds the
+/// `http_post` actions of the registered predicates, by predicate key: deliveries are authorized and
+/// signed as they configure, and the ones of the predicates missing from it are dropped without
+/// being sent.
+///
+/// Deliveries fail over like the original ones, and `endpoints_health` is updated with the endpoint
+/// accepting the occurrences of each predicate, see [send_request_with_failover].
+///
+/// Returns the number of acknowledged deliveries.
+pub async fn redeliver_pending_deliveries(
+    store: &PendingDeliveryStore,
+    registered_hooks: &HashMap<String, HttpHook>,
+    signing_secrets: &SigningSecrets,
+    endpoints_health: &mut HashMap<String, usize>,
+    http_client: &HttpClient,
+    retry_policy: &HttpRetryPolicy,
+    ctx: &Context,
+) -> Result<usize, String> {
+    let mut deliveries = vec![];
+    for delivery in store.get_pending_deliveries(ctx)?.into_iter() {
+        if registered_hooks.contains_key(&delivery.predicate_key) {
+            deliveries.push(delivery);
+            continue;
+        }
+        ctx.try_log(|logger| {
+            slog::info!(
+                logger,
+                "Dropping unacknowledged occurrence of deregistered predicate {}",
+                delivery.predicate_key
+            )
+        });
+        store.acknowledge(delivery.id)?;
+    }
+    if !deliveries.is_empty() {
+        ctx.try_log(|logger| {
+            slog::info!(
+                logger,
+                "Redelivering {} unacknowledged occurrences",
+                deliveries.len()
+            )
+        });
+    }
+    let mut acknowledged = 0;
+    for delivery in deliveries.iter() {
+        let hook = &registered_hooks[&delivery.predicate_key];
+        let healthy_endpoint = endpoints_health
+            .entry(delivery.predicate_key.clone())
+            .or_insert(0);
+        let result = match delivery.to_request(http_client, hook, signing_secrets) {
+            Ok(request) => {
+                send_request_with_failover(
+                    request,
+                    &delivery.failover_urls,
+                    healthy_endpoint,
+                    retry_policy,
+                    ctx,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => {
+                store.acknowledge(delivery.id)?;
+                acknowledged += 1;
+            }
+            Err(e) => ctx.try_log(|logger| {
+                slog::warn!(
+                    logger,
+                    "Unable to redeliver occurrence for predicate {}: {}",
+                    delivery.predicate_key,
+                    e
+                )
+            }),
+        }
+    }
+    Ok(acknowledged)
+}
@@ -1,7 +1,13 @@
+use crate::chainhooks::bitcoin::evaluate_bitcoin_chainhooks_on_chain_event;
+use crate::chainhooks::bitcoin::handle_bitcoin_digest_action;
+use crate::chainhooks::bitcoin::handle_bitcoin_hook_action;
 use crate::chainhooks::bitcoin::BitcoinChainhookInstance;
+use crate::chainhooks::bitcoin::BitcoinChainhookOccurrence;
 use crate::chainhooks::bitcoin::BitcoinChainhookSpecification;
 use crate::chainhooks::bitcoin::BitcoinChainhookSpecificationNetworkMap;
 use crate::chainhooks::bitcoin::BitcoinPredicateType;
+use crate::chainhooks::bitcoin::BitcoinTriggerChainhook;
+use crate::chainhooks::bitcoin::DigestConfig;
 use crate::chainhooks::bitcoin::InscriptionFeedData;
 use crate::chainhooks::bitcoin::OrdinalOperations;
 use crate::chainhooks::bitcoin::OutputPredicate;
@@ -11,28 +17,38 @@ use crate::chainhooks::stacks::StacksChainhookSpecificationNetworkMap;
 use crate::chainhooks::stacks::StacksContractCallBasedPredicate;
 use crate::chainhooks::stacks::StacksPredicate;
 use crate::chainhooks::types::{
-    ChainhookInstance, ChainhookSpecificationNetworkMap, ChainhookStore, ExactMatchingRule,
-    HookAction,
+    ChainhookInstance, ChainhookSpecificationNetworkMap, ChainhookStore, ContentHashAlgorithm,
+    ExactMatchingRule, FileHook, HookAction, HttpHook, SigningSecrets,
 };
+use crate::indexer::bitcoin::{build_http_client, StacksOperationsParsing};
 use crate::indexer::fork_scratch_pad::ForkScratchPad;
 use crate::indexer::tests::helpers::transactions::generate_test_tx_bitcoin_p2pkh_transfer;
 use crate::indexer::tests::helpers::{
     accounts, bitcoin_blocks, stacks_blocks, transactions::generate_test_tx_stacks_contract_call,
 };
 use crate::monitoring::PrometheusMonitoring;
+use crate::observer::applied_blocks_cache::AppliedBlocksCache;
+use crate::observer::dead_letters::{redeliver_dead_letters, DeadLetterStore};
+use crate::observer::digests::BitcoinDigestAccumulator;
+use crate::observer::occurrences_buffer::BitcoinOccurrencesBuffer;
+use crate::observer::pending_deliveries::{redeliver_pending_deliveries, PendingDeliveryStore};
 use crate::observer::PredicateDeregisteredEvent;
 use crate::observer::{
     start_observer_commands_handler, EventObserverConfig, ObserverCommand, ObserverSidecar,
+    PredicateActionUpdateData,
 };
-use crate::utils::{AbstractBlock, Context};
+use crate::utils::{AbstractBlock, Context, HttpRetryPolicy};
 use chainhook_types::{
-    BitcoinBlockSignaling, BitcoinChainEvent, BitcoinNetwork, BlockchainEvent,
+    BitcoinBlockSignaling, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData,
+    BitcoinChainUpdatedWithReorgData, BitcoinNetwork, BlockchainEvent,
     BlockchainUpdatedWithHeaders, OrdinalInscriptionNumber, OrdinalInscriptionRevealData,
     OrdinalOperation, StacksBlockUpdate, StacksChainEvent, StacksChainUpdatedWithBlocksData,
     StacksNetwork, StacksNodeConfig,
 };
 use hiro_system_kit;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::net::TcpListener;
 use std::sync::mpsc::{channel, Sender};
 
 use super::{ObserverEvent, DEFAULT_INGESTION_PORT};
@@ -51,6 +67,16 @@ fn generate_test_config() -> (EventObserverConfig, ChainhookStore) {
         bitcoin_network: BitcoinNetwork::Regtest,
         stacks_network: StacksNetwork::Devnet,
         prometheus_monitoring_port: None,
+        descriptor_default_range: None,
+        pending_deliveries_path: None,
+        dead_letters_path: None,
+        http_client: None,
+        http_retry_policy: HttpRetryPolicy::default(),
+        signing_secrets: SigningSecrets::default(),
+        deduplication_hash: ContentHashAlgorithm::default(),
+        stacks_operations_parsing: StacksOperationsParsing::default(),
+        max_buffered_occurrences_size: None,
+        deduplicate_reapplied_blocks: false,
     };
     (config, ChainhookStore::new())
 }
@@ -71,6 +97,7 @@ fn stacks_chainhook_contract_call(
             expire_after_occurrence,
             capture_all_events: None,
             decode_clarity_values: Some(true),
+            skip_rollback_only_occurrences: None,
             include_contract_abi: None,
             predicate: StacksPredicate::ContractCall(StacksContractCallBasedPredicate {
                 contract_identifier: contract_identifier.to_string(),
@@ -84,6 +111,7 @@ fn stacks_chainhook_contract_call(
         uuid: format!("{}", id),
         name: format!("Chainhook {}", id),
         owner_uuid: None,
+        on_expire_url: None,
         networks,
         version: 1,
     };
@@ -101,6 +129,7 @@ fn bitcoin_chainhook_p2pkh(
         BitcoinChainhookSpecification {
             start_block: None,
             end_block: None,
+            end_block_grace_period: None,
             blocks: None,
             expire_after_occurrence,
             predicate: BitcoinPredicateType::Outputs(OutputPredicate::P2pkh(
@@ -111,6 +140,19 @@ fn bitcoin_chainhook_p2pkh(
             include_inputs: None,
             include_outputs: None,
             include_witness: None,
+            proof_format: None,
+            amount_format: None,
+            include_brc20_balance_deltas: None,
+            include_brc20_operation: None,
+            max_transactions_per_block: None,
+            skip_rollback_only_occurrences: None,
+            digest: None,
+            omit_empty_arrays: None,
+            payload_shape: None,
+            include_empty_blocks: None,
+            include_dropped: None,
+            include_version: None,
+            include_size: None,
         },
     );
 
@@ -118,6 +160,7 @@ fn bitcoin_chainhook_p2pkh(
         uuid: format!("{}", id),
         name: format!("Chainhook {}", id),
         owner_uuid: None,
+        on_expire_url: None,
         version: 1,
         networks,
     };
@@ -131,6 +174,7 @@ fn bitcoin_chainhook_ordinals(id: u8) -> BitcoinChainhookSpecificationNetworkMap
         BitcoinChainhookSpecification {
             start_block: None,
             end_block: None,
+            end_block_grace_period: None,
             blocks: None,
             expire_after_occurrence: None,
             predicate: BitcoinPredicateType::OrdinalsProtocol(OrdinalOperations::InscriptionFeed(
@@ -143,6 +187,19 @@ fn bitcoin_chainhook_ordinals(id: u8) -> BitcoinChainhookSpecificationNetworkMap
             include_inputs: None,
             include_outputs: None,
             include_witness: None,
+            proof_format: None,
+            amount_format: None,
+            include_brc20_balance_deltas: None,
+            include_brc20_operation: None,
+            max_transactions_per_block: None,
+            skip_rollback_only_occurrences: None,
+            digest: None,
+            omit_empty_arrays: None,
+            payload_shape: None,
+            include_empty_blocks: None,
+            include_dropped: None,
+            include_version: None,
+            include_size: None,
         },
     );
 
@@ -150,6 +207,7 @@ fn bitcoin_chainhook_ordinals(id: u8) -> BitcoinChainhookSpecificationNetworkMap
         uuid: format!("{}", id),
         name: format!("Chainhook {}", id),
         owner_uuid: None,
+        on_expire_url: None,
         version: 1,
         networks,
     };
@@ -1103,6 +1161,104 @@ fn test_bitcoin_chainhook_auto_deregister() {
     handle.join().expect("unable to terminate thread");
 }
 
+#[test]
+fn test_bitcoin_chainhook_action_update() {
+    let (observer_commands_tx, observer_commands_rx) = channel();
+    let (observer_events_tx, observer_events_rx) = crossbeam_channel::unbounded();
+    let prometheus_monitoring = PrometheusMonitoring::new();
+
+    let handle = std::thread::spawn(move || {
+        let (config, chainhook_store) = generate_test_config();
+        let _ = hiro_system_kit::nestable_block_on(start_observer_commands_handler(
+            config,
+            chainhook_store,
+            observer_commands_rx,
+            Some(observer_events_tx),
+            None,
+            prometheus_monitoring,
+            None,
+            Context::empty(),
+        ));
+    });
+
+    // Create and register a new chainhook (wallet_2 received some sats)
+    let chainhook = generate_and_register_new_bitcoin_chainhook(
+        &observer_commands_tx,
+        &observer_events_rx,
+        1,
+        &accounts::wallet_2_btc_address(),
+        None,
+    );
+
+    let trigger_block = |height: u64| {
+        let transactions = vec![generate_test_tx_bitcoin_p2pkh_transfer(
+            height,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_2_btc_address(),
+            3,
+        )];
+        let block = bitcoin_blocks::generate_test_bitcoin_block(0, height, transactions, None);
+        let _ = observer_commands_tx.send(ObserverCommand::CacheBitcoinBlock(block.clone()));
+        let chain_event =
+            BlockchainEvent::BlockchainUpdatedWithHeaders(BlockchainUpdatedWithHeaders {
+                new_headers: vec![block.get_header()],
+                confirmed_headers: vec![],
+            });
+        let _ = observer_commands_tx.send(ObserverCommand::PropagateBitcoinChainEvent(chain_event));
+    };
+
+    // With the Noop action, the occurrence is propagated as an event
+    trigger_block(1);
+    assert_predicates_triggered_event(&observer_events_rx, 1);
+    assert!(match observer_events_rx.recv() {
+        Ok(ObserverEvent::BitcoinPredicateTriggered(_)) => true,
+        _ => false,
+    });
+    assert!(match observer_events_rx.recv() {
+        Ok(ObserverEvent::BitcoinChainEvent(_)) => true,
+        _ => false,
+    });
+
+    // Updates of unknown predicates are ignored, and emit no event
+    let new_action = HookAction::FileAppend(FileHook {
+        path: "predicate-action-update.json".into(),
+    });
+    let _ = observer_commands_tx.send(ObserverCommand::UpdatePredicateAction(
+        PredicateActionUpdateData {
+            predicate_uuid: "unknown".into(),
+            action: new_action.clone(),
+        },
+    ));
+
+    // Update the action
+    let _ = observer_commands_tx.send(ObserverCommand::UpdatePredicateAction(
+        PredicateActionUpdateData {
+            predicate_uuid: chainhook.uuid.clone(),
+            action: new_action.clone(),
+        },
+    ));
+    assert!(match observer_events_rx.recv() {
+        Ok(ObserverEvent::PredicateActionUpdated(ChainhookInstance::Bitcoin(updated))) => {
+            assert_eq!(updated.uuid, chainhook.uuid);
+            assert_eq!(updated.action, new_action);
+            assert!(updated.enabled);
+            true
+        }
+        _ => false,
+    });
+
+    // Subsequent occurrences are delivered through the new action, and not propagated as events anymore
+    trigger_block(2);
+    assert_predicates_triggered_event(&observer_events_rx, 1);
+    assert!(match observer_events_rx.recv() {
+        Ok(ObserverEvent::BitcoinChainEvent(_)) => true,
+        _ => false,
+    });
+
+    let _ = observer_commands_tx.send(ObserverCommand::Terminate);
+    handle.join().expect("unable to terminate thread");
+}
+
 #[test]
 fn test_bitcoin_chainhook_through_reorg() {
     let (observer_commands_tx, observer_commands_rx) = channel();
@@ -1331,3 +1487,540 @@ fn test_bitcoin_chainhook_through_reorg() {
         .join()
         .expect("unable to terminate thread");
 }
+
+#[test]
+fn test_unacknowledged_deliveries_are_redelivered_on_restart() {
+    let pending_deliveries_path =
+        std::env::temp_dir().join(format!("pending-deliveries-{}", rand::random::<u64>()));
+    let listener = TcpListener::bind("127.0.0.1:0").expect("unable to bind listener");
+    let failover_url = format!("http://{}/", listener.local_addr().unwrap());
+    // the primary endpoint went down while the observer was stopped
+    let url = {
+        let unreachable = TcpListener::bind("127.0.0.1:0").expect("unable to bind listener");
+        format!("http://{}/", unreachable.local_addr().unwrap())
+    };
+    let body = r#"{"apply":[],"rollback":[]}"#;
+    let ctx = Context::empty();
+
+    // The occurrence is recorded before being dispatched, and the process crashes before the consumer acks it.
+    {
+        let mut store = PendingDeliveryStore::open(&pending_deliveries_path).unwrap();
+        let request = reqwest::Client::new()
+            .post(&url)
+            .header("Authorization", "Bearer secret")
+            .body(body);
+        store
+            .record(
+                &ChainhookInstance::bitcoin_key("1"),
+                &request,
+                &[failover_url.clone()],
+            )
+            .unwrap();
+    }
+
+    let consumer_handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("unable to accept connection");
+        let mut received = vec![];
+        let mut buffer = [0u8; 1024];
+        while !String::from_utf8_lossy(&received).ends_with(body) {
+            let read = stream.read(&mut buffer).expect("unable to read request");
+            if read == 0 {
+                break;
+            }
+            received.extend_from_slice(&buffer[..read]);
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .expect("unable to write response");
+        String::from_utf8_lossy(&received).to_string()
+    });
+
+    // The credentials of the request are never written to disk.
+    for entry in std::fs::read_dir(&pending_deliveries_path).unwrap() {
+        let content = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        assert!(!content.contains("Bearer secret"));
+    }
+
+    // On restart, the pending occurrence is redelivered to the failover url and dropped once
+    // acknowledged, authorized and signed as configured by the predicate.
+    let store = PendingDeliveryStore::open(&pending_deliveries_path).unwrap();
+    assert_eq!(store.get_pending_deliveries(&ctx).unwrap().len(), 1);
+    let hook = HttpHook {
+        url: url.clone(),
+        authorization_header: "Bearer secret".into(),
+        failover_urls: vec![failover_url.clone()],
+        signing_secret_id: Some("hook-secret".into()),
+        verification_url: None,
+    };
+    let signing_secrets =
+        SigningSecrets::new(HashMap::from([("hook-secret".into(), "s3cr3t".into())]));
+    let mut endpoints_health = HashMap::new();
+    let acknowledged = hiro_system_kit::nestable_block_on(redeliver_pending_deliveries(
+        &store,
+        &HashMap::from([(ChainhookInstance::bitcoin_key("1"), hook)]),
+        &signing_secrets,
+        &mut endpoints_health,
+        &build_http_client(),
+        &HttpRetryPolicy {
+            attempts_max: 1,
+            ..HttpRetryPolicy::default()
+        },
+        &ctx,
+    ))
+    .unwrap();
+    assert_eq!(acknowledged, 1);
+    assert_eq!(
+        endpoints_health.get(&ChainhookInstance::bitcoin_key("1")),
+        Some(&1)
+    );
+
+    let received = consumer_handle.join().expect("unable to terminate thread");
+    assert!(received.contains("Bearer secret"));
+    let signature = signing_secrets
+        .sign("hook-secret", body.as_bytes())
+        .unwrap();
+    assert!(received.contains(&signature));
+    assert!(received.ends_with(body));
+    assert_eq!(store.get_pending_deliveries(&ctx).unwrap(), vec![]);
+    std::fs::remove_dir_all(&pending_deliveries_path).unwrap();
+}
+
+#[test]
+fn test_pending_deliveries_skip_corrupt_and_deregistered_entries() {
+    let pending_deliveries_path =
+        std::env::temp_dir().join(format!("pending-deliveries-{}", rand::random::<u64>()));
+    let ctx = Context::empty();
+    let mut store = PendingDeliveryStore::open(&pending_deliveries_path).unwrap();
+    let request = reqwest::Client::new()
+        .post("http://localhost:20456/")
+        .body("{}");
+    store
+        .record(&ChainhookInstance::bitcoin_key("1"), &request, &[])
+        .unwrap();
+    store
+        .record(&ChainhookInstance::bitcoin_key("2"), &request, &[])
+        .unwrap();
+    std::fs::write(pending_deliveries_path.join(format!("{:020}.json", 2)), "{").unwrap();
+
+    // the corrupt entry is skipped, and doesn't reset the ids of the next deliveries
+    let mut store = PendingDeliveryStore::open(&pending_deliveries_path).unwrap();
+    assert_eq!(store.get_pending_deliveries(&ctx).unwrap().len(), 2);
+    let delivery = store
+        .record(&ChainhookInstance::bitcoin_key("2"), &request, &[])
+        .unwrap();
+    assert_eq!(delivery.id, 3);
+
+    // the deliveries of a deregistered predicate are dropped
+    store.try_drop_predicate_deliveries(&ChainhookInstance::bitcoin_key("2"), &ctx);
+    let deliveries = store.get_pending_deliveries(&ctx).unwrap();
+    assert_eq!(
+        deliveries
+            .iter()
+            .map(|delivery| delivery.predicate_key.clone())
+            .collect::<Vec<_>>(),
+        vec![ChainhookInstance::bitcoin_key("1")]
+    );
+
+    // and so are the ones of predicates missing on restart, without being sent
+    let acknowledged = hiro_system_kit::nestable_block_on(redeliver_pending_deliveries(
+        &store,
+        &HashMap::new(),
+        &SigningSecrets::default(),
+        &mut HashMap::new(),
+        &build_http_client(),
+        &HttpRetryPolicy::default(),
+        &ctx,
+    ))
+    .unwrap();
+    assert_eq!(acknowledged, 0);
+    assert_eq!(store.get_pending_deliveries(&ctx).unwrap(), vec![]);
+    std::fs::remove_dir_all(&pending_deliveries_path).unwrap();
+}
+
+#[test]
+fn test_dead_letters_recorded_by_concurrent_stores_are_kept() {
+    let dead_letters_path =
+        std::env::temp_dir().join(format!("dead-letters-{}", rand::random::<u64>()));
+    let ctx = Context::empty();
+
+    // e.g. the observer and a scan, both opened before either recorded anything
+    let mut stores = (0..2)
+        .map(|_| DeadLetterStore::open(&dead_letters_path).unwrap())
+        .collect::<Vec<_>>();
+    for (i, store) in stores.iter_mut().enumerate() {
+        for _ in 0..10 {
+            let request = reqwest::Client::new()
+                .post("http://127.0.0.1:1/")
+                .body(r#"{"apply":[],"rollback":[]}"#);
+            assert!(store.try_record(
+                &ChainhookInstance::bitcoin_key(&i.to_string()),
+                &request,
+                "rejected without retry",
+                &ctx
+            ));
+        }
+    }
+    let dead_letters = stores[0].get_dead_letters(&ctx).unwrap();
+    assert_eq!(dead_letters.len(), 20);
+    std::fs::remove_dir_all(&dead_letters_path).unwrap();
+}
+
+#[test]
+fn test_dead_letters_are_recorded_and_redelivered() {
+    let dead_letters_path =
+        std::env::temp_dir().join(format!("dead-letters-{}", rand::random::<u64>()));
+    let listener = TcpListener::bind("127.0.0.1:0").expect("unable to bind listener");
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let body = r#"{"apply":[],"rollback":[]}"#;
+    let ctx = Context::empty();
+
+    // The delivery failed for good, the occurrence is kept along with its url and the failure.
+    {
+        let mut store = DeadLetterStore::open(&dead_letters_path).unwrap();
+        let request = reqwest::Client::new()
+            .post(&url)
+            .header("Authorization", "Bearer secret")
+            .body(body);
+        assert!(store.try_record(
+            &ChainhookInstance::bitcoin_key("1"),
+            &request,
+            "rejected without retry",
+            &ctx
+        ));
+    }
+    // A corrupt entry is skipped without holding back the other ones.
+    std::fs::write(dead_letters_path.join("corrupt.json"), b"{").unwrap();
+    let store = DeadLetterStore::open(&dead_letters_path).unwrap();
+    let dead_letters = store.get_dead_letters(&ctx).unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].url, url);
+    assert_eq!(dead_letters[0].reason, "rejected without retry");
+    assert_eq!(
+        dead_letters[0].payload,
+        json!({"apply": [], "rollback": []})
+    );
+    // but not with its credentials
+    for entry in std::fs::read_dir(&dead_letters_path).unwrap() {
+        let content = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        assert!(!content.contains("Bearer secret"));
+    }
+
+    // The dead letters of predicates that are no longer registered are kept.
+    let (delivered, remaining) = hiro_system_kit::nestable_block_on(redeliver_dead_letters(
+        &store,
+        &HashMap::new(),
+        &SigningSecrets::default(),
+        &build_http_client(),
+        &HttpRetryPolicy::default(),
+        &ctx,
+    ))
+    .unwrap();
+    assert_eq!((delivered, remaining), (0, 1));
+
+    let consumer_handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("unable to accept connection");
+        let mut received = vec![];
+        let mut buffer = [0u8; 1024];
+        while !String::from_utf8_lossy(&received).ends_with(body) {
+            let read = stream.read(&mut buffer).expect("unable to read request");
+            if read == 0 {
+                break;
+            }
+            received.extend_from_slice(&buffer[..read]);
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .expect("unable to write response");
+        String::from_utf8_lossy(&received).to_string()
+    });
+
+    // Once redelivered, authorized as configured by the predicate, the dead letter is dropped.
+    let hook = HttpHook {
+        url: url.clone(),
+        authorization_header: "Bearer secret".into(),
+        failover_urls: vec![],
+        signing_secret_id: None,
+        verification_url: None,
+    };
+    let (delivered, remaining) = hiro_system_kit::nestable_block_on(redeliver_dead_letters(
+        &store,
+        &HashMap::from([(ChainhookInstance::bitcoin_key("1"), hook)]),
+        &SigningSecrets::default(),
+        &build_http_client(),
+        &HttpRetryPolicy::default(),
+        &ctx,
+    ))
+    .unwrap();
+    assert_eq!((delivered, remaining), (1, 0));
+
+    let received = consumer_handle.join().expect("unable to terminate thread");
+    assert!(received.contains("Bearer secret"));
+    assert!(received.ends_with(body));
+    assert_eq!(store.get_dead_letters(&ctx).unwrap(), vec![]);
+    std::fs::remove_dir_all(&dead_letters_path).unwrap();
+}
+
+#[test]
+fn test_bitcoin_occurrences_buffer_applies_backpressure() {
+    let p2pkh_address = accounts::wallet_2_btc_address();
+    let mut chainhook = bitcoin_chainhook_p2pkh(1, &p2pkh_address, None)
+        .into_specification_for_network(&BitcoinNetwork::Regtest)
+        .unwrap();
+    chainhook.action = HookAction::HttpPost(HttpHook {
+        url: "http://localhost:20455".into(),
+        authorization_header: "Bearer secret".into(),
+        failover_urls: vec![],
+        signing_secret_id: None,
+        verification_url: None,
+    });
+    let transactions = (0..1000)
+        .map(|i| {
+            generate_test_tx_bitcoin_p2pkh_transfer(
+                i,
+                &accounts::wallet_1_btc_address(),
+                &p2pkh_address,
+                1,
+            )
+        })
+        .collect::<Vec<_>>();
+    let block = bitcoin_blocks::generate_test_bitcoin_block(0, 1, transactions, None);
+    let build_occurrence = || {
+        let trigger = BitcoinTriggerChainhook {
+            chainhook: &chainhook,
+            apply: vec![(block.transactions.iter().collect(), &block)],
+            rollback: vec![],
+            dropped: vec![],
+        };
+        match handle_bitcoin_hook_action(
+            trigger,
+            &HashMap::new(),
+            None,
+            None,
+            &SigningSecrets::default(),
+        ) {
+            Ok(BitcoinChainhookOccurrence::Http(request, data)) => (request, data),
+            _ => panic!("expected an http occurrence"),
+        }
+    };
+
+    // without any limit, the occurrences of every triggered predicate are buffered
+    let mut occurrences = BitcoinOccurrencesBuffer::new(None);
+    for _ in 0..3 {
+        let (request, data) = build_occurrence();
+        occurrences.push_request(request, vec![], None, data);
+    }
+    assert!(occurrences.size() > 0);
+    assert!(!occurrences.is_full());
+
+    // with a limit lower than the size of a single occurrence, every occurrence is delivered right away
+    let mut occurrences = BitcoinOccurrencesBuffer::new(Some(16 * 1024));
+    let (request, data) = build_occurrence();
+    occurrences.push_request(request, vec![], None, data);
+    assert!(occurrences.size() > 16 * 1024);
+    assert!(occurrences.is_full());
+
+    let (requests, socket_writes, payloads) = occurrences.drain();
+    assert_eq!(requests.len(), 1);
+    assert!(socket_writes.is_empty());
+    assert!(payloads.is_empty());
+    assert_eq!(occurrences.size(), 0);
+    assert!(!occurrences.is_full());
+
+    // payloads handed to the observer events channel are accounted for as well
+    let (_, data) = build_occurrence();
+    occurrences.push_payload(data);
+    assert!(occurrences.size() > 16 * 1024);
+    assert!(occurrences.is_full());
+    let (_, _, payloads) = occurrences.drain();
+    assert_eq!(payloads.len(), 1);
+}
+
+#[test]
+fn test_applied_blocks_cache_suppresses_reapplied_blocks() {
+    let mut chainhook = bitcoin_chainhook_p2pkh(1, &accounts::wallet_2_btc_address(), None)
+        .into_specification_for_network(&BitcoinNetwork::Regtest)
+        .unwrap();
+    chainhook.predicate = BitcoinPredicateType::Block;
+    let ctx = Context::empty();
+    let mut applied_blocks = AppliedBlocksCache::new(16);
+    let mut evaluate = |chain_event: &BitcoinChainEvent| {
+        let (triggered, _, _) =
+            evaluate_bitcoin_chainhooks_on_chain_event(chain_event, &vec![&chainhook], None, &ctx);
+        triggered
+            .into_iter()
+            .filter_map(|trigger| applied_blocks.deduplicate(trigger))
+            .map(|trigger| {
+                let applied = trigger
+                    .apply
+                    .iter()
+                    .map(|(_, block)| block.block_identifier.clone())
+                    .collect::<Vec<_>>();
+                let rolled_back = trigger
+                    .rollback
+                    .iter()
+                    .map(|(_, block)| block.block_identifier.clone())
+                    .collect::<Vec<_>>();
+                (applied, rolled_back)
+            })
+            .collect::<Vec<_>>()
+    };
+    let block = |fork_id, height| {
+        let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            height,
+            &accounts::wallet_1_btc_address(),
+            &accounts::wallet_2_btc_address(),
+            1,
+        );
+        bitcoin_blocks::generate_test_bitcoin_block(fork_id, height, vec![transaction], None)
+    };
+
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![block(0, 1), block(0, 2)],
+            confirmed_blocks: vec![],
+        });
+    let triggered = evaluate(&chain_event);
+    assert_eq!(triggered.len(), 1);
+    assert_eq!(triggered[0].0.len(), 2);
+
+    // fork 1 takes over block 2
+    let chain_event = BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+        blocks_to_rollback: vec![block(0, 2)],
+        blocks_to_apply: vec![block(1, 2)],
+        confirmed_blocks: vec![],
+    });
+    let triggered = evaluate(&chain_event);
+    assert_eq!(triggered.len(), 1);
+    assert_eq!(triggered[0].0, vec![block(1, 2).block_identifier]);
+
+    // fork 0 takes over again: its block 2 was rolled back, and is delivered again
+    let chain_event = BitcoinChainEvent::ChainUpdatedWithReorg(BitcoinChainUpdatedWithReorgData {
+        blocks_to_rollback: vec![block(1, 2)],
+        blocks_to_apply: vec![block(0, 2), block(0, 3)],
+        confirmed_blocks: vec![],
+    });
+    let triggered = evaluate(&chain_event);
+    assert_eq!(triggered.len(), 1);
+    assert_eq!(
+        triggered[0].0,
+        vec![block(0, 2).block_identifier, block(0, 3).block_identifier]
+    );
+    assert_eq!(triggered[0].1, vec![block(1, 2).block_identifier]);
+
+    // blocks applied again without being rolled back first are not delivered twice
+    let chain_event =
+        BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+            new_blocks: vec![block(0, 3)],
+            confirmed_blocks: vec![],
+        });
+    assert!(evaluate(&chain_event).is_empty());
+
+    // the cache is bounded: the oldest blocks are eventually forgotten
+    let mut applied_blocks = AppliedBlocksCache::new(2);
+    assert!(applied_blocks.insert("uuid", "0x01"));
+    assert!(applied_blocks.insert("uuid", "0x02"));
+    assert!(!applied_blocks.insert("uuid", "0x02"));
+    assert!(applied_blocks.insert("uuid", "0x03"));
+    assert!(applied_blocks.insert("uuid", "0x01"));
+}
+
+#[test]
+fn test_bitcoin_digest_aggregates_occurrences_over_window() {
+    let p2pkh_address = accounts::wallet_2_btc_address();
+    let mut chainhook = bitcoin_chainhook_p2pkh(1, &p2pkh_address, None)
+        .into_specification_for_network(&BitcoinNetwork::Regtest)
+        .unwrap();
+    chainhook.action = HookAction::HttpPost(HttpHook {
+        url: "http://localhost:20455".into(),
+        authorization_header: "Bearer secret".into(),
+        failover_urls: vec![],
+        signing_secret_id: None,
+        verification_url: None,
+    });
+    chainhook.digest = Some(DigestConfig {
+        blocks: Some(3),
+        seconds: None,
+        max_sample_txids: Some(2),
+    });
+    let ctx = Context::empty();
+    let mut digests = BitcoinDigestAccumulator::new();
+    let mut deliveries = vec![];
+    for height in 1..=3 {
+        let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+            height,
+            &accounts::wallet_1_btc_address(),
+            &p2pkh_address,
+            1,
+        );
+        let block = bitcoin_blocks::generate_test_bitcoin_block(0, height, vec![transaction], None);
+        let chain_event =
+            BitcoinChainEvent::ChainUpdatedWithBlocks(BitcoinChainUpdatedWithBlocksData {
+                new_blocks: vec![block],
+                confirmed_blocks: vec![],
+            });
+        let (triggered, _, _) =
+            evaluate_bitcoin_chainhooks_on_chain_event(&chain_event, &vec![&chainhook], None, &ctx);
+        assert_eq!(triggered.len(), 1);
+        for trigger in triggered.iter() {
+            if let Some(digest) = digests.accumulate(trigger, 0) {
+                deliveries.push(digest);
+            }
+        }
+        deliveries.append(
+            &mut digests
+                .close_windows(&[&chainhook], height, 0)
+                .into_iter()
+                .map(|(_, digest)| digest)
+                .collect(),
+        );
+    }
+
+    // the three matches are delivered as a single digest once the window covers 3 blocks
+    assert_eq!(deliveries.len(), 1);
+    assert!(digests.is_empty());
+    let digest = deliveries.remove(0);
+    assert_eq!(digest.occurrences, 3);
+    assert_eq!(digest.applied_blocks, 3);
+    assert_eq!(digest.transactions, 3);
+    assert_eq!(digest.start_block.index, 1);
+    assert_eq!(digest.end_block.index, 3);
+    assert_eq!(digest.sample_txids.len(), 2);
+    match handle_bitcoin_digest_action(&chainhook, digest, None, &SigningSecrets::default()) {
+        Ok(BitcoinChainhookOccurrence::Http(_, data)) => {
+            assert!(data.apply.is_empty());
+            assert_eq!(data.digest.unwrap().occurrences, 3);
+        }
+        _ => panic!("expected an http occurrence"),
+    }
+
+    // time windows are closed when the next chain event is processed
+    chainhook.digest = Some(DigestConfig {
+        blocks: None,
+        seconds: Some(60),
+        max_sample_txids: None,
+    });
+    let transaction = generate_test_tx_bitcoin_p2pkh_transfer(
+        4,
+        &accounts::wallet_1_btc_address(),
+        &p2pkh_address,
+        1,
+    );
+    let block = bitcoin_blocks::generate_test_bitcoin_block(0, 4, vec![transaction], None);
+    let trigger = BitcoinTriggerChainhook {
+        chainhook: &chainhook,
+        apply: vec![(block.transactions.iter().collect(), &block)],
+        rollback: vec![],
+        dropped: vec![],
+    };
+    assert!(digests.accumulate(&trigger, 100).is_none());
+    assert!(digests.close_windows(&[&chainhook], 10, 159).is_empty());
+    assert_eq!(digests.close_windows(&[&chainhook], 10, 160).len(), 1);
+    assert!(digests.is_empty());
+
+    // the window of an expiring predicate is closed right away
+    assert!(digests.accumulate(&trigger, 200).is_none());
+    let digest = digests.close_window(&chainhook.uuid).unwrap();
+    assert_eq!(digest.occurrences, 1);
+    assert!(digests.is_empty());
+    assert!(digests.close_window(&chainhook.uuid).is_none());
+}
@@ -10,7 +10,8 @@ use chainhook_types::{
     StacksTransactionData,
 };
 use hiro_system_kit::slog::{self, Logger};
-use reqwest::RequestBuilder;
+use rand::Rng;
+use reqwest::{RequestBuilder, StatusCode};
 use serde_json::Value as JsonValue;
 
 #[derive(Clone)]
@@ -144,13 +145,81 @@ impl AbstractBlock for BitcoinBlockData {
     }
 }
 
+/// Retry policy of the `http_post` deliveries.
+///
+/// Failed deliveries are retried with an exponential backoff, waiting `base_delay_ms` after the
+/// first failure and doubling the delay after each subsequent one, up to `max_delay_ms`. Responses
+/// with a 4xx status are not retried, except for 408 (Request Timeout) and 429 (Too Many Requests).
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpRetryPolicy {
+    /// Number of attempts, including the first one.
+    pub attempts_max: u16,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// When enabled, each delay is drawn between half and all of its value, so that deliveries
+    /// failing together don't retry in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for HttpRetryPolicy {
+    fn default() -> Self {
+        HttpRetryPolicy {
+            attempts_max: 5,
+            base_delay_ms: 1_000,
+            max_delay_ms: 30_000,
+            jitter: true,
+        }
+    }
+}
+
+impl HttpRetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.attempts_max == 0 {
+            return Err("'attempts_max' must be greater than 0".into());
+        }
+        if self.base_delay_ms > self.max_delay_ms {
+            return Err("'base_delay_ms' must be lower than or equal to 'max_delay_ms'".into());
+        }
+        Ok(())
+    }
+
+    /// Returns the delay, before jitter, following the `failures`-th failed attempt.
+    pub fn get_backoff_delay_ms(&self, failures: u16) -> u64 {
+        let factor = 1u64
+            .checked_shl(failures.saturating_sub(1).into())
+            .unwrap_or(u64::MAX);
+        self.base_delay_ms
+            .saturating_mul(factor)
+            .min(self.max_delay_ms)
+    }
+
+    fn get_delay(&self, failures: u16) -> std::time::Duration {
+        let delay_ms = self.get_backoff_delay_ms(failures);
+        let delay_ms = if self.jitter && delay_ms > 0 {
+            rand::thread_rng().gen_range(delay_ms / 2..=delay_ms)
+        } else {
+            delay_ms
+        };
+        std::time::Duration::from_millis(delay_ms)
+    }
+}
+
+/// Whether a delivery answered with `status` is worth retrying: server errors, timeouts and rate
+/// limiting are transient, while the other client errors would be answered the same way again.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Sends `request_builder`, retrying according to `retry_policy`. The error describes the final
+/// outcome of the delivery, so that it can be recorded in the status of the predicate.
 pub async fn send_request(
     request_builder: RequestBuilder,
-    attempts_max: u16,
-    attempts_interval_sec: u16,
+    retry_policy: &HttpRetryPolicy,
     ctx: &Context,
 ) -> Result<(), String> {
-    let mut retry = 0;
+    let mut failures = 0;
     loop {
         let request_builder = match request_builder.try_clone() {
             Some(rb) => rb,
@@ -167,32 +236,135 @@ pub async fn send_request(
                     ctx.try_log(|logger| slog::debug!(logger, "Trigger {} successful", res.url()));
                     return Ok(());
                 } else {
-                    retry += 1;
+                    failures += 1;
                     let err_msg =
                         format!("Trigger {} failed with status {}", res.url(), res.status());
                     ctx.try_log(|logger| slog::warn!(logger, "{}", err_msg));
+                    if !is_retryable_status(res.status()) {
+                        return Err(format!(
+                            "unable to send request, rejected without retry: {}",
+                            err_msg
+                        ));
+                    }
                     err_msg
                 }
             }
             Err(e) => {
-                retry += 1;
+                failures += 1;
                 let err_msg = format!("unable to send request {}", e.to_string());
                 ctx.try_log(|logger| slog::warn!(logger, "{}", err_msg));
                 err_msg
             }
         };
-        if retry >= attempts_max {
+        if failures >= retry_policy.attempts_max {
             let msg: String = format!(
-                "unable to send request after several retries. most recent error: {}",
-                err_msg
+                "unable to send request after {} attempts. most recent error: {}",
+                failures, err_msg
             );
             ctx.try_log(|logger| slog::warn!(logger, "{}", msg));
             return Err(msg);
         }
-        std::thread::sleep(std::time::Duration::from_secs(attempts_interval_sec.into()));
+        tokio::time::sleep(retry_policy.get_delay(failures)).await;
     }
 }
 
+/// Sends `request_builder` to its url or, when it can't be delivered there with `retry_policy`, to
+/// each of the `failover_urls` in order.
+///
+/// `healthy_endpoint` is the index of the endpoint the delivery starts with: `0` for the request
+/// url, `n` for `failover_urls[n - 1]`. It is updated with the endpoint that accepted the request,
+/// so that subsequent deliveries skip the endpoints that are known to be failing.
+pub async fn send_request_with_failover(
+    request_builder: RequestBuilder,
+    failover_urls: &[String],
+    healthy_endpoint: &mut usize,
+    retry_policy: &HttpRetryPolicy,
+    ctx: &Context,
+) -> Result<(), String> {
+    let endpoints_count = failover_urls.len() + 1;
+    let mut err_msg = String::new();
+    for i in 0..endpoints_count {
+        let endpoint = (*healthy_endpoint + i) % endpoints_count;
+        let request_builder = if endpoint == 0 {
+            request_builder.try_clone()
+        } else {
+            with_url(&request_builder, &failover_urls[endpoint - 1])
+        };
+        let request_builder = match request_builder {
+            Some(rb) => rb,
+            None => {
+                ctx.try_log(|logger| slog::warn!(logger, "unable to clone request builder"));
+                return Err(format!(
+                    "internal server error: unable to clone request builder"
+                ));
+            }
+        };
+        match send_request(request_builder, retry_policy, ctx).await {
+            Ok(_) => {
+                if endpoint != *healthy_endpoint {
+                    ctx.try_log(|logger| {
+                        slog::info!(logger, "Failing over to endpoint #{}", endpoint)
+                    });
+                }
+                *healthy_endpoint = endpoint;
+                return Ok(());
+            }
+            Err(e) => err_msg = e,
+        }
+    }
+    Err(err_msg)
+}
+
+/// Delivers `request_builder` with [send_request_with_failover], once the `verification` request,
+/// if any, is answered with a 2xx status. Deliveries rejected by the verification are skipped
+/// without error; a verification request that can't be sent fails the delivery.
+pub async fn send_request_with_verification(
+    verification: Option<RequestBuilder>,
+    request_builder: RequestBuilder,
+    failover_urls: &[String],
+    healthy_endpoint: &mut usize,
+    retry_policy: &HttpRetryPolicy,
+    ctx: &Context,
+) -> Result<(), String> {
+    if let Some(verification) = verification {
+        match verification.send().await {
+            Ok(res) if res.status().is_success() => {}
+            Ok(res) => {
+                ctx.try_log(|logger| {
+                    slog::info!(
+                        logger,
+                        "Delivery rejected by verification {} with status {}",
+                        res.url(),
+                        res.status()
+                    )
+                });
+                return Ok(());
+            }
+            Err(e) => {
+                let msg = format!("unable to send verification request: {}", e);
+                ctx.try_log(|logger| slog::warn!(logger, "{}", msg));
+                return Err(msg);
+            }
+        }
+    }
+    send_request_with_failover(
+        request_builder,
+        failover_urls,
+        healthy_endpoint,
+        retry_policy,
+        ctx,
+    )
+    .await
+}
+
+fn with_url(request_builder: &RequestBuilder, url: &str) -> Option<RequestBuilder> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let (client, request) = request_builder.try_clone()?.build_split();
+    let mut request = request.ok()?;
+    *request.url_mut() = url;
+    Some(RequestBuilder::from_parts(client, request))
+}
+
 pub fn file_append(path: String, bytes: Vec<u8>, ctx: &Context) -> Result<(), String> {
     let mut file_path = match std::env::current_dir() {
         Err(e) => {
@@ -253,6 +425,63 @@ pub fn file_append(path: String, bytes: Vec<u8>, ctx: &Context) -> Result<(), St
     Ok(())
 }
 
+/// Writes `bytes` as a single newline-delimited JSON entry to the Unix domain socket at `path`,
+/// retrying with an exponential backoff while the socket is not ready.
+#[cfg(unix)]
+pub async fn unix_socket_send(
+    path: String,
+    bytes: Vec<u8>,
+    attempts_max: u16,
+    attempts_interval_ms: u64,
+    ctx: &Context,
+) -> Result<(), String> {
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::UnixStream;
+
+    let mut entry = bytes;
+    entry.push(b'\n');
+    let mut retry = 0;
+    let mut backoff_ms = attempts_interval_ms;
+    loop {
+        let err_msg = match UnixStream::connect(&path).await {
+            Ok(mut stream) => match stream.write_all(&entry).await {
+                Ok(_) => {
+                    ctx.try_log(|logger| slog::debug!(logger, "Trigger {} successful", path));
+                    return Ok(());
+                }
+                Err(e) => format!("unable to write to socket {}: {}", path, e.to_string()),
+            },
+            Err(e) => format!("unable to connect to socket {}: {}", path, e.to_string()),
+        };
+        retry += 1;
+        ctx.try_log(|logger| slog::warn!(logger, "{}", err_msg));
+        if retry >= attempts_max {
+            let msg: String = format!(
+                "unable to write to socket after several retries. most recent error: {}",
+                err_msg
+            );
+            ctx.try_log(|logger| slog::warn!(logger, "{}", msg));
+            return Err(msg);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(backoff_ms)).await;
+        backoff_ms = backoff_ms.saturating_mul(2);
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn unix_socket_send(
+    path: String,
+    _bytes: Vec<u8>,
+    _attempts_max: u16,
+    _attempts_interval_ms: u64,
+    _ctx: &Context,
+) -> Result<(), String> {
+    Err(format!(
+        "unable to write to socket {}: unix sockets are not supported on this platform",
+        path
+    ))
+}
+
 #[derive(Debug)]
 pub enum BlockHeightsError {
     ExceedsMaxEntries(u64, u64),
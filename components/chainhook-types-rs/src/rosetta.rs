@@ -316,6 +316,14 @@ pub struct BitcoinTransactionMetadata {
     pub proof: Option<String>,
     pub fee: u64,
     pub index: u32,
+    /// Left out of the payloads of predicates not including sizes.
+    #[serde(default)]
+    pub weight: u64,
+    #[serde(default)]
+    pub vsize: u64,
+    /// `nVersion` of the transaction, a signed integer.
+    #[serde(default)]
+    pub version: i32,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -334,11 +342,23 @@ pub struct StacksBlockCommitmentData {
     pub pox_cycle_index: u64,
     pub pox_cycle_length: u64,
     pub pox_cycle_position: u64,
+    #[serde(default)]
+    pub pox_cycle_in_prepare_phase: bool,
     pub pox_sats_burnt: u64,
     pub pox_sats_transferred: Vec<PoxReward>,
     // pub mining_address_pre_commit: Option<String>,
     pub mining_address_post_commit: Option<String>,
     pub mining_sats_left: u64,
+    /// Bitcoin block height of the commit of the parent Stacks block.
+    #[serde(default)]
+    pub parent_block_ptr: Option<u32>,
+    /// Position, within its Bitcoin block, of the commit of the parent Stacks block.
+    #[serde(default)]
+    pub parent_vtxindex: Option<u16>,
+    /// Whether the commit was sent by a Nakamoto miner, starting a new tenure if it wins the
+    /// sortition.
+    #[serde(default)]
+    pub anchors_tenure_change: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -361,6 +381,10 @@ pub struct PobBlockCommitmentData {
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct BlockCommitmentData {
     pub stacks_block_hash: String,
+    pub parent_block_ptr: Option<u32>,
+    pub parent_vtxindex: Option<u16>,
+    /// Stacks epoch marker of the memo, which is unknown for commits missing their memo.
+    pub epoch_marker: Option<u8>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]